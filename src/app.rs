@@ -1,27 +1,91 @@
-use crate::db::{AnnotationRecord, BookRecord, Db, VocabRecord};
-use crate::parser::{BookParser, EpubParser, PageContent, PdfParser};
+use crate::config::{self, AnnotationKindConfig, ImportRules, LayoutProfile, TopBarConfig};
+use crate::clippings;
+use crate::db::{
+    AnnotationRecord, BookPaceRecord, BookRecord, BookSessionRecord, Db, LastStopRecord,
+    NewAnnotation, PendingClippingRecord, SavedSearchRecord, SearchHit, VocabRecord,
+    VocabSource,
+};
+use crate::dictionary::LookupSettings;
+use crate::export::{self, ExportFormat, ExportMode, MarkdownMarkers};
+use crate::group::GroupPosition;
+use crate::instance::InstanceLock;
+use crate::koreader;
+use crate::kosync::{self, KosyncSettings};
+use crate::library::{self, AnnotationKind, ReadingStatus, Relink};
+use crate::llm::LlmSettings;
+use crate::locator::Locator;
+use crate::omnibox::{self, OmniboxHit};
+use crate::parser::filter::{self, TextFilter};
+use crate::parser::{
+    BookParser, DjvuParser, EpubParser, Figure, FigureKind, PageContent, PdfParser, html,
+};
+use crate::review::Grade;
+use crate::search::SearchPattern;
 use anyhow::Result;
+use chrono::{Datelike, Timelike};
+use crossterm::event::KeyCode;
 use image::imageops::FilterType;
 use ratatui_image::{picker::Picker, protocol::StatefulProtocol};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
-use walkdir::WalkDir;
 
 #[derive(Clone)]
 pub enum RenderLine {
     Text(String),
     Image { protocol_idx: usize, row_idx: usize },
+    /// Caption under an image. Like image rows, it has no words to select.
+    Caption(String),
 }
 
-#[derive(PartialEq, Clone, Copy)]
+/// A table of contents line: a chapter, or a captioned figure in one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TocEntry {
+    pub label: String,
+    pub chapter: usize,
+    pub figure: bool,
+}
+
+impl TocEntry {
+    /// Chapter titles with each chapter's figures listed after it. Figures of chapters
+    /// past the end of the titles come last; tables are left to the list of figures.
+    pub fn list(titles: Vec<String>, figures: Vec<Figure>) -> Vec<TocEntry> {
+        let mut entries = Vec::new();
+        let mut figures = figures
+            .into_iter()
+            .filter(|f| f.kind == FigureKind::Image)
+            .peekable();
+        for (chapter, label) in titles.into_iter().enumerate() {
+            entries.push(TocEntry {
+                label,
+                chapter,
+                figure: false,
+            });
+            while let Some(figure) = figures.next_if(|f| f.chapter <= chapter) {
+                entries.push(TocEntry {
+                    label: figure.label,
+                    chapter: figure.chapter,
+                    figure: true,
+                });
+            }
+        }
+        entries.extend(figures.map(|f| TocEntry {
+            label: f.label,
+            chapter: f.chapter,
+            figure: true,
+        }));
+        entries
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub enum AppView {
     Library,
     Reader,
     Search,
     Toc,
-    #[allow(dead_code)]
     Rsvp,
     Annotation,
     AnnotationList,
@@ -29,11 +93,22 @@ pub enum AppView {
     Visual,
     Select,
     Vocabulary,
+    VocabReview,
     GlobalSearch,
+    SavedSearches,
+    ConceptIndex,
     PathInput,
+    UrlInput,
+    LookupSettings,
     FileExplorer,
     Help,
     Stats,
+    NoteBrowser,
+    ClippingReview,
+    Figures,
+    LastStops,
+    BookSearch,
+    Omnibox,
 }
 
 #[derive(Clone, Copy)]
@@ -166,1166 +241,6429 @@ impl Theme {
             _ => Theme::Default,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Gruvbox => "gruvbox",
+            Theme::Nord => "nord",
+            Theme::Sepia => "sepia",
+        }
+    }
+
+    /// Line focus strength in percent when the config doesn't set one. Light
+    /// backgrounds need less to stand out.
+    fn default_line_focus_intensity(&self) -> u8 {
+        match self {
+            Theme::Default => 15,
+            Theme::Gruvbox | Theme::Nord => 12,
+            Theme::Sepia => 8,
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum AnnotationKind {
-    Highlight,
-    Question,
-    Summary,
+/// Which line of the reader gets a soft background as an anchor for the eyes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineFocus {
+    Off,
+    Cursor,
+    Middle,
 }
 
-impl AnnotationKind {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            AnnotationKind::Highlight => "highlight",
-            AnnotationKind::Question => "question",
-            AnnotationKind::Summary => "summary",
+impl LineFocus {
+    pub fn from_str(value: &str) -> LineFocus {
+        match value.to_lowercase().as_str() {
+            "cursor" => LineFocus::Cursor,
+            "middle" => LineFocus::Middle,
+            _ => LineFocus::Off,
         }
     }
 
-    pub fn label(&self) -> &'static str {
+    pub fn next(&self) -> LineFocus {
         match self {
-            AnnotationKind::Highlight => "H",
-            AnnotationKind::Question => "Q",
-            AnnotationKind::Summary => "S",
+            LineFocus::Off => LineFocus::Cursor,
+            LineFocus::Cursor => LineFocus::Middle,
+            LineFocus::Middle => LineFocus::Off,
         }
     }
 
-    pub fn from_str(value: &str) -> AnnotationKind {
-        match value {
-            "question" => AnnotationKind::Question,
-            "summary" => AnnotationKind::Summary,
-            _ => AnnotationKind::Highlight,
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineFocus::Off => "Off",
+            LineFocus::Cursor => "Current line",
+            LineFocus::Middle => "Middle line",
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum AnnotationFilter {
-    All,
-    Highlight,
-    Question,
-    Summary,
+/// How much of the text around the middle of the screen focus mode leaves undimmed, as a
+/// ruler laid on the page would.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FocusRuler {
+    Off,
+    Line,
+    Band,
 }
 
-impl AnnotationFilter {
+impl FocusRuler {
+    pub fn from_str(value: &str) -> FocusRuler {
+        match value.to_lowercase().as_str() {
+            "line" => FocusRuler::Line,
+            "band" => FocusRuler::Band,
+            _ => FocusRuler::Off,
+        }
+    }
+
+    pub fn next(&self) -> FocusRuler {
+        match self {
+            FocusRuler::Off => FocusRuler::Line,
+            FocusRuler::Line => FocusRuler::Band,
+            FocusRuler::Band => FocusRuler::Off,
+        }
+    }
+
     pub fn label(&self) -> &'static str {
         match self {
-            AnnotationFilter::All => "All",
-            AnnotationFilter::Highlight => "Highlights",
-            AnnotationFilter::Question => "Questions",
-            AnnotationFilter::Summary => "Summaries",
+            FocusRuler::Off => "Off",
+            FocusRuler::Line => "Middle line",
+            FocusRuler::Band => "Three-line band",
         }
     }
-}
 
-pub struct App {
-    pub view: AppView,
-    pub previous_view: Option<AppView>,
-    pub db: Db,
-    pub books: Vec<BookRecord>,
-    pub selected_book_index: usize,
-    pub current_book: Option<LoadedBook>,
-    pub should_quit: bool,
-    pub search_query: String,
-    pub toc_items: Vec<String>,
-    pub selected_toc_index: usize,
-    pub theme: Theme,
-    // RSVP State
-    pub rsvp_active: bool,
-    pub rsvp_index: usize,
-    pub rsvp_wpm: u64,
-    pub rsvp_words: Vec<String>,
-    // Annotation State
-    pub annotation_note: String,
-    pub all_annotations: Vec<AnnotationRecord>,
-    pub current_annotations: Vec<AnnotationRecord>,
-    pub selected_annotation_index: usize,
-    pub annotation_filter: AnnotationFilter,
-    // Dictionary State
-    pub dictionary_query: String,
-    pub dictionary_result: String,
-    // Vocabulary State
-    pub vocabulary: Vec<VocabRecord>,
-    pub selected_vocab_index: usize,
-    // Layout State
-    pub margin: u16,
-    pub line_spacing: u16,
-    pub daily_goal_words: usize,
-    pub focus_mode: bool,
-    pub pomodoro: PomodoroState,
-    // Global Search State
-    pub global_search_query: String,
-    pub global_search_results: Vec<(i32, String, usize, String)>,
-    pub selected_search_index: usize,
-    // Explorer State
-    pub explorer_path: String,
-    pub explorer_results: Vec<std::path::PathBuf>,
-    pub explorer_selected: HashSet<PathBuf>,
-    pub selected_explorer_index: usize,
-    pub is_scanning: bool,
-    pub image_picker: Picker,
-    pub current_library_cover: Option<StatefulProtocol>,
-    pub cover_cache: HashMap<i32, Arc<image::DynamicImage>>,
-    pub cover_missing: HashSet<i32>,
-    pub pending_cover_requests: HashSet<i32>,
-    pub last_library_selection: Option<i32>,
-    // Auto-scroll State
-    pub auto_scroll_active: bool,
-    pub auto_scroll_interval_ms: u64,
-    pub auto_scroll_last_tick: Instant,
+    /// Whether the screen row `y` is dimmed when `middle` is the middle row.
+    pub fn dims(&self, y: u16, middle: u16) -> bool {
+        match self {
+            FocusRuler::Off => false,
+            FocusRuler::Line => y != middle,
+            FocusRuler::Band => y + 1 < middle || y > middle + 1,
+        }
+    }
 }
 
-pub struct LoadedBook {
-    pub id: i32,
-    pub parser: BookParser,
-    pub path: String,
-    pub current_chapter: usize,
-    pub current_line: usize,              // Cursor line
-    pub viewport_top: usize,              // Viewport top line
-    pub chapter_content: Vec<RenderLine>, // Lines of current chapter
-    pub image_protocols: Vec<StatefulProtocol>,
-    pub word_index: usize,                        // Cursor word index
-    pub selection_anchor: Option<(usize, usize)>, // (line, word)
-    pub chapter_annotations: Vec<AnnotationRecord>,
-    pub start_time: Instant,
-    pub words_read: usize,
-    pub session_words_logged: usize,
+/// How many colors the terminal can show. Below true color, every color on screen is
+/// swapped for the nearest one it has; `Mono` drops color altogether and shows what
+/// stood out by its background in reverse video instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Mono,
 }
 
-#[derive(Clone)]
-pub struct CoverRequest {
-    pub book_id: i32,
-    pub path: String,
-}
+impl ColorDepth {
+    /// `None` for "auto" or anything unknown, leaving it to [`ColorDepth::detect`].
+    pub fn from_str(value: &str) -> Option<ColorDepth> {
+        match value.trim().to_lowercase().as_str() {
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Ansi256),
+            "16" | "8" => Some(ColorDepth::Ansi16),
+            "mono" | "none" => Some(ColorDepth::Mono),
+            _ => None,
+        }
+    }
 
-pub struct CoverResponse {
-    pub book_id: i32,
-    pub image: Option<image::DynamicImage>,
+    /// Goes by `COLORTERM`, which terminals with true color set, then by the terminfo
+    /// name in `TERM`. `NO_COLOR` and the `dumb` terminal get no color at all.
+    pub fn detect(colorterm: &str, term: &str, no_color: bool) -> ColorDepth {
+        let colorterm = colorterm.to_lowercase();
+        let term = term.to_lowercase();
+        const TRUE_COLOR_TERMS: [&str; 6] =
+            ["direct", "kitty", "ghostty", "alacritty", "wezterm", "foot"];
+        if no_color || term == "dumb" {
+            ColorDepth::Mono
+        } else if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || TRUE_COLOR_TERMS.iter().any(|t| term.contains(t))
+        {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else if term.is_empty() {
+            // Windows consoles leave TERM unset and have had true color for years.
+            ColorDepth::TrueColor
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
 }
 
-impl App {
-    pub fn new(db_path: &str) -> Result<Self> {
-        let db = Db::new(db_path)?;
-        let books = db.get_books()?;
-        let app = Self {
-            view: AppView::Library,
-            previous_view: None,
-            db,
-            books,
-            selected_book_index: 0,
-            current_book: None,
-            should_quit: false,
-            search_query: String::new(),
-            toc_items: Vec::new(),
-            selected_toc_index: 0,
-            theme: Theme::Default,
-            rsvp_active: false,
-            rsvp_index: 0,
-            rsvp_wpm: 300,
-            rsvp_words: Vec::new(),
-            annotation_note: String::new(),
-            all_annotations: Vec::new(),
-            current_annotations: Vec::new(),
-            selected_annotation_index: 0,
-            annotation_filter: AnnotationFilter::All,
-            dictionary_query: String::new(),
-            dictionary_result: String::new(),
-            vocabulary: Vec::new(),
-            selected_vocab_index: 0,
-            margin: 2,
-            line_spacing: 0,
-            daily_goal_words: 1500,
-            focus_mode: false,
-            pomodoro: PomodoroState::new(Duration::from_secs(1500), Duration::from_secs(300)),
-            global_search_query: String::new(),
-            global_search_results: Vec::new(),
-            selected_search_index: 0,
-            explorer_path: String::new(),
-            explorer_results: Vec::new(),
-            explorer_selected: HashSet::new(),
-            selected_explorer_index: 0,
-            is_scanning: false,
-            // Initialized to a reasonable default; in TUI mode this should be replaced with
-            // Picker::from_query_stdio() after entering alternate screen.
-            image_picker: Picker::halfblocks(),
-            current_library_cover: None,
-            cover_cache: HashMap::new(),
-            cover_missing: HashSet::new(),
-            pending_cover_requests: HashSet::new(),
-            last_library_selection: None,
-            auto_scroll_active: false,
-            auto_scroll_interval_ms: 2000, // Default scroll every 2 seconds
-            auto_scroll_last_tick: Instant::now(),
-        };
+/// What the gutter beside the reader text shows. Numbers count the chapter's lines the
+/// way notes record their position, so they stay the same however the text wraps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+}
 
-        Ok(app)
+impl LineNumbers {
+    pub fn from_str(value: &str) -> LineNumbers {
+        match value.to_lowercase().as_str() {
+            "absolute" | "on" => LineNumbers::Absolute,
+            "relative" => LineNumbers::Relative,
+            _ => LineNumbers::Off,
+        }
     }
 
-    pub fn apply_config(&mut self, config: &crate::config::AppConfig) {
-        self.margin = config.margin;
-        self.line_spacing = config.line_spacing;
-        self.daily_goal_words = config.daily_goal_words;
-        self.theme = Theme::from_str(&config.theme);
-        if self.explorer_path.is_empty() {
-            self.explorer_path = config.library_path.clone();
+    pub fn next(&self) -> LineNumbers {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
         }
-
-        let work = Duration::from_secs(config.pomodoro_work_minutes.saturating_mul(60));
-        let rest = Duration::from_secs(config.pomodoro_break_minutes.saturating_mul(60));
-        self.pomodoro
-            .set_durations(work.max(Duration::from_secs(60)), rest.max(Duration::from_secs(60)));
     }
 
-    pub fn toggle_focus_mode(&mut self) {
-        self.focus_mode = !self.focus_mode;
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineNumbers::Off => "Off",
+            LineNumbers::Absolute => "Chapter lines",
+            LineNumbers::Relative => "Relative to the current line",
+        }
     }
 
-    pub fn pomodoro_toggle(&mut self) {
-        self.pomodoro.toggle();
+    /// The number shown beside chapter line `line` with the cursor on `current`. The
+    /// current line keeps its own number in relative mode, as in vim.
+    pub fn number(&self, line: usize, current: usize) -> Option<usize> {
+        match self {
+            LineNumbers::Off => None,
+            LineNumbers::Relative if line != current => Some(line.abs_diff(current)),
+            _ => Some(line + 1),
+        }
     }
+}
 
-    pub fn pomodoro_reset(&mut self) {
-        self.pomodoro.reset();
-    }
+/// Where the reader shows its gauges of the way through the chapter and the book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressGauge {
+    Off,
+    /// Above the status bar, hidden with it in focus mode.
+    On,
+    /// Kept on screen in focus mode too.
+    Always,
+}
 
-    pub fn pomodoro_skip_break(&mut self) {
-        self.pomodoro.skip_break();
+impl ProgressGauge {
+    pub fn from_str(value: &str) -> ProgressGauge {
+        match value.to_lowercase().as_str() {
+            "on" | "true" => ProgressGauge::On,
+            "always" => ProgressGauge::Always,
+            _ => ProgressGauge::Off,
+        }
     }
 
-    pub fn tick_timers(&mut self) {
-        self.pomodoro.tick();
+    pub fn next(&self) -> ProgressGauge {
+        match self {
+            ProgressGauge::Off => ProgressGauge::On,
+            ProgressGauge::On => ProgressGauge::Always,
+            ProgressGauge::Always => ProgressGauge::Off,
+        }
     }
 
-    pub fn pomodoro_label(&self) -> Option<String> {
-        if !self.pomodoro.running && !self.focus_mode {
-            return None;
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProgressGauge::Off => "Off",
+            ProgressGauge::On => "On",
+            ProgressGauge::Always => "On, in focus mode too",
         }
-        let remaining = self.pomodoro.remaining_time();
-        let mins = remaining.as_secs() / 60;
-        let secs = remaining.as_secs() % 60;
-        let phase = if self.pomodoro.is_break { "Break" } else { "Focus" };
-        let status = if self.pomodoro.running { "" } else { " (paused)" };
-        Some(format!("{} {:02}:{:02}{}", phase, mins, secs, status))
     }
 
-    pub fn refresh_library(&mut self) -> Result<()> {
-        self.books = self.db.get_books()?;
-        if self.books.is_empty() {
-            self.selected_book_index = 0;
-            self.current_library_cover = None;
-            self.last_library_selection = None;
-        } else if self.selected_book_index >= self.books.len() {
-            self.selected_book_index = 0;
-        }
-        if !self.books.is_empty() {
-            let valid_ids: HashSet<i32> = self.books.iter().map(|b| b.id).collect();
-            self.cover_cache.retain(|id, _| valid_ids.contains(id));
-            self.cover_missing.retain(|id| valid_ids.contains(id));
-            self.pending_cover_requests.retain(|id| valid_ids.contains(id));
+    pub fn shown(&self, focus_mode: bool) -> bool {
+        match self {
+            ProgressGauge::Off => false,
+            ProgressGauge::On => !focus_mode,
+            ProgressGauge::Always => true,
         }
-        Ok(())
     }
+}
 
-    pub fn toggle_explorer_selection(&mut self) {
-        if let Some(path) = self.explorer_results.get(self.selected_explorer_index) {
-            if !self.explorer_selected.insert(path.clone()) {
-                self.explorer_selected.remove(path);
-            }
+/// Count prefixes stop growing here, so a held digit can't queue an endless repeat.
+pub const MAX_KEY_COUNT: usize = 9999;
+
+/// Where the reader's go-to prompt asked to go: `40%` of the book, or chapter `12`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GoTo {
+    Percent(f64),
+    Chapter(usize),
+}
+
+impl GoTo {
+    pub fn parse(input: &str) -> Option<GoTo> {
+        let input = input.trim();
+        match input.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(GoTo::Percent),
+            None => input.parse().ok().filter(|&n| n > 0).map(GoTo::Chapter),
         }
     }
+}
 
-    pub fn select_all_explorer_results(&mut self) {
-        self.explorer_selected = self.explorer_results.iter().cloned().collect();
+/// Reader settings bundled for a kind of book, picked per book from the reader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadingPreset {
+    /// Narrow centered text, turned a page at a time, with indented paragraphs.
+    Novel,
+    /// A wider scrolling column with space between paragraphs.
+    Textbook,
+    /// Two columns with PDF reflow on, for academic papers; marks default to questions.
+    Paper,
+}
+
+impl ReadingPreset {
+    pub fn from_str(value: &str) -> Option<ReadingPreset> {
+        match value.to_lowercase().as_str() {
+            "novel" => Some(ReadingPreset::Novel),
+            "textbook" => Some(ReadingPreset::Textbook),
+            "paper" => Some(ReadingPreset::Paper),
+            _ => None,
+        }
     }
 
-    pub fn clear_explorer_selection(&mut self) {
-        self.explorer_selected.clear();
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadingPreset::Novel => "novel",
+            ReadingPreset::Textbook => "textbook",
+            ReadingPreset::Paper => "paper",
+        }
     }
 
-    pub fn import_explorer_selection(&mut self) -> Result<usize> {
-        if self.explorer_results.is_empty() {
-            return Ok(0);
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReadingPreset::Novel => "Novel",
+            ReadingPreset::Textbook => "Textbook",
+            ReadingPreset::Paper => "Paper",
         }
+    }
 
-        let paths: Vec<PathBuf> = if self.explorer_selected.is_empty() {
-            self.explorer_results
-                .get(self.selected_explorer_index)
-                .map(|p| vec![p.clone()])
-                .unwrap_or_default()
-        } else {
-            self.explorer_selected.iter().cloned().collect()
+    /// The app-wide layout the preset reads with.
+    pub fn layout(&self) -> ReaderLayout {
+        let (margin, max_text_width, text_columns, pdf_reflow) = match self {
+            ReadingPreset::Novel => (4, Some(66), 1, false),
+            ReadingPreset::Textbook => (2, Some(90), 1, false),
+            ReadingPreset::Paper => (1, None, 2, true),
         };
+        ReaderLayout {
+            margin,
+            max_text_width,
+            text_columns,
+            pdf_reflow,
+        }
+    }
 
-        let imported = self.import_paths(&paths)?;
-        self.clear_explorer_selection();
-        Ok(imported)
+    /// Paging, paragraph spacing, and first-line indent, saved with the book.
+    pub fn typography(&self) -> (bool, u16, u16) {
+        match self {
+            ReadingPreset::Novel => (true, 0, 4),
+            ReadingPreset::Textbook => (false, 1, 0),
+            ReadingPreset::Paper => (false, 1, 0),
+        }
     }
 
-    fn import_paths(&mut self, paths: &[PathBuf]) -> Result<usize> {
-        let mut imported = 0;
-        for path in paths {
-            let path_str = path.to_string_lossy().to_string();
-            let lower = path_str.to_lowercase();
-            let parser = if lower.ends_with(".pdf") {
-                PdfParser::new(&path_str).ok().map(BookParser::Pdf)
-            } else if lower.ends_with(".epub") {
-                EpubParser::new(&path_str).ok().map(BookParser::Epub)
-            } else {
-                None
-            };
+    /// What `Enter` marks in select mode.
+    pub fn annotation_kind(&self) -> AnnotationKind {
+        match self {
+            ReadingPreset::Novel => AnnotationKind::Highlight,
+            ReadingPreset::Textbook => AnnotationKind::Summary,
+            ReadingPreset::Paper => AnnotationKind::Question,
+        }
+    }
+}
 
-            let Some(parser) = parser else {
-                continue;
-            };
+/// Reader settings shared by every book, which a preset overrides while its book is open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReaderLayout {
+    pub margin: u16,
+    pub max_text_width: Option<u16>,
+    pub text_columns: u16,
+    pub pdf_reflow: bool,
+}
 
-            let (title, author) = parser.get_metadata();
-            let total_chapters = parser.get_chapter_count();
-            let total_lines = 0;
-            self.db
-                .add_book(&title, &author, &path_str, total_chapters, total_lines)?;
-            imported += 1;
+/// Rows of wrapped text one notch of the mouse wheel scrolls.
+pub const WHEEL_ROWS: usize = 3;
+
+/// Where the reader last drew a word, so a click can be traced back to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WordArea {
+    pub x: u16,
+    pub y: u16,
+    /// Columns of the word and the space after it.
+    pub width: u16,
+    pub line: usize,
+    pub word: usize,
+}
+
+/// What a click on part of the screen does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClickTarget {
+    /// Presses the key, for footer hints such as `[Enter] Open` and the top bar buttons.
+    Key(KeyCode),
+    /// Selects a row of the view's list, or opens it when it is already selected.
+    ListRow(usize),
+}
+
+/// A one-row stretch of the screen the last frame made clickable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClickArea {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub target: ClickTarget,
+}
+
+/// Warm tint over colors and images late in the day, like a blue-light filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NightTint {
+    pub start_hour: u32,
+    pub end_hour: u32,
+    /// Percent, from 0 (no change) to 100.
+    pub strength: u8,
+}
+
+impl NightTint {
+    /// Whether the tint is on during `hour`. The span may run past midnight.
+    pub fn is_active(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
         }
-        Ok(imported)
     }
+}
 
-    pub fn refresh_current_book_render_cache(&mut self) -> Result<()> {
-        let Some(ref mut book) = self.current_book else {
-            return Ok(());
-        };
+/// `rgb` with blue and some green taken out, `percent` of the full strength.
+pub fn warm_tint([r, g, b]: [u8; 3], percent: u8) -> [u8; 3] {
+    let t = percent.min(100) as f32 / 100.0;
+    let scale = |c: u8, amount: f32| (c as f32 * (1.0 - t * amount)).round() as u8;
+    [r, scale(g, 0.25), scale(b, 0.6)]
+}
 
-        let chapter_idx = book.current_chapter;
-        let content = book.parser.get_chapter_content(chapter_idx)?;
-        let (chapter_content, image_protocols) =
-            Self::flatten_content(&mut self.image_picker, content);
+/// The image with the night tint applied, if it is on.
+fn tinted(image: image::DynamicImage, tint: Option<u8>) -> image::DynamicImage {
+    let Some(percent) = tint else {
+        return image;
+    };
+    let mut rgba = image.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b] = warm_tint([pixel[0], pixel[1], pixel[2]], percent);
+        (pixel[0], pixel[1], pixel[2]) = (r, g, b);
+    }
+    image::DynamicImage::ImageRgba8(rgba)
+}
 
-        book.chapter_content = chapter_content;
-        book.image_protocols = image_protocols;
-        Ok(())
+/// `line` cut into pieces of at most [`MAX_LINE_CHARS`] characters, at spaces where
+/// there are any.
+pub fn split_long_line(line: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while let Some((cut, _)) = rest.char_indices().nth(MAX_LINE_CHARS) {
+        let (piece, tail) = match rest[..cut].rfind(char::is_whitespace) {
+            Some(space) if space > 0 => (&rest[..space], rest[space..].trim_start()),
+            _ => rest.split_at(cut),
+        };
+        pieces.push(piece);
+        rest = tail;
     }
+    pieces.push(rest);
+    pieces
+}
 
-    pub fn cover_request_for_selected(&mut self) -> Option<CoverRequest> {
-        if self.books.is_empty() {
-            self.current_library_cover = None;
-            return None;
+/// An occurrence of the in-book search query: its line and the words it covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub line: usize,
+    pub start_word: usize,
+    pub end_word: usize,
+}
+
+/// Every match of `pattern` in the text lines of a chapter.
+pub fn find_matches(lines: &[RenderLine], pattern: &SearchPattern) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    for (line, content) in lines.iter().enumerate() {
+        let RenderLine::Text(text) = content else {
+            continue;
+        };
+        if !pattern.is_match(text) {
+            continue;
+        }
+        // Byte offset at which each word starts, to turn a match into word indices.
+        let mut starts = Vec::new();
+        let mut after_space = true;
+        for (i, c) in text.char_indices() {
+            if after_space && !c.is_whitespace() {
+                starts.push(i);
+            }
+            after_space = c.is_whitespace();
         }
+        let word_at = |offset: usize| starts.partition_point(|&s| s <= offset).saturating_sub(1);
+        for (start, end) in pattern.find_iter(text) {
+            matches.push(SearchMatch {
+                line,
+                start_word: word_at(start),
+                end_word: word_at(end - 1),
+            });
+        }
+    }
+    matches
+}
 
-        let book_record = &self.books[self.selected_book_index];
-        let book_id = book_record.id;
+/// Words of context either side of a match in the whole-book search list.
+const BOOK_SEARCH_CONTEXT_WORDS: usize = 8;
+/// Matches the whole-book search lists before it stops looking.
+pub const BOOK_SEARCH_LIMIT: usize = 500;
+
+/// A match of the search across every chapter of the open book.
+#[derive(Clone, Debug)]
+pub struct BookSearchHit {
+    pub chapter: usize,
+    /// The chapter's first line, usually its heading.
+    pub chapter_title: String,
+    pub locator: Locator,
+    /// The words around the match, with the matched words in brackets.
+    pub snippet: String,
+}
 
-        if self.last_library_selection != Some(book_id) {
-            self.last_library_selection = Some(book_id);
-            self.current_library_cover = None;
+/// The match's words in brackets, with a few words of its line either side.
+fn match_context(text: &str, m: &SearchMatch) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let from = m.start_word.saturating_sub(BOOK_SEARCH_CONTEXT_WORDS);
+    let to = (m.end_word + 1 + BOOK_SEARCH_CONTEXT_WORDS).min(words.len());
+    let mut parts = Vec::new();
+    if from > 0 {
+        parts.push("...".to_string());
+    }
+    parts.extend(words[from..m.start_word].iter().map(|w| w.to_string()));
+    parts.push(format!("[{}]", words[m.start_word..=m.end_word].join(" ")));
+    parts.extend(words[m.end_word + 1..to].iter().map(|w| w.to_string()));
+    if to < words.len() {
+        parts.push("...".to_string());
+    }
+    parts.join(" ")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationFilter {
+    All,
+    Highlight,
+    Question,
+    Summary,
+    /// One of the kinds defined in `tbook.toml`, by position.
+    Custom(usize),
+}
+
+impl AnnotationFilter {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnnotationFilter::All => "All",
+            AnnotationFilter::Highlight => "Highlights",
+            AnnotationFilter::Question => "Questions",
+            AnnotationFilter::Summary => "Summaries",
+            AnnotationFilter::Custom(_) => "Custom",
         }
+    }
 
-        if let Some(image) = self.cover_cache.get(&book_id) {
-            self.current_library_cover =
-                Some(self.image_picker.new_resize_protocol(image.as_ref().clone()));
-            return None;
+    /// Whether a note of `kind` passes, given the user-defined kinds.
+    pub fn matches(&self, kind: &str, custom: &[AnnotationKindConfig]) -> bool {
+        let is_custom = custom.iter().any(|k| k.name == kind);
+        match self {
+            AnnotationFilter::All => true,
+            // Unknown kinds render as highlights, so they are listed with them.
+            AnnotationFilter::Highlight => {
+                AnnotationKind::from_str(kind) == AnnotationKind::Highlight && !is_custom
+            }
+            AnnotationFilter::Question => {
+                AnnotationKind::from_str(kind) == AnnotationKind::Question
+            }
+            AnnotationFilter::Summary => AnnotationKind::from_str(kind) == AnnotationKind::Summary,
+            AnnotationFilter::Custom(i) => custom.get(*i).is_some_and(|k| k.name == kind),
         }
+    }
+}
 
-        if self.cover_missing.contains(&book_id) {
-            return None;
+/// How far back the note browser looks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotePeriod {
+    All,
+    Week,
+    Month,
+    Year,
+}
+
+impl NotePeriod {
+    pub fn next(self) -> NotePeriod {
+        match self {
+            NotePeriod::All => NotePeriod::Week,
+            NotePeriod::Week => NotePeriod::Month,
+            NotePeriod::Month => NotePeriod::Year,
+            NotePeriod::Year => NotePeriod::All,
         }
+    }
 
-        if self.pending_cover_requests.contains(&book_id) {
-            return None;
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotePeriod::All => "Any time",
+            NotePeriod::Week => "Last 7 days",
+            NotePeriod::Month => "Last 30 days",
+            NotePeriod::Year => "Last year",
         }
+    }
 
-        Some(CoverRequest {
-            book_id,
-            path: book_record.path.clone(),
-        })
+    fn days(&self) -> Option<i64> {
+        match self {
+            NotePeriod::All => None,
+            NotePeriod::Week => Some(7),
+            NotePeriod::Month => Some(30),
+            NotePeriod::Year => Some(365),
+        }
     }
+}
 
-    pub fn mark_cover_request_in_flight(&mut self, book_id: i32) {
-        self.pending_cover_requests.insert(book_id);
+/// Notes from every book, narrowed by book, kind, tag, and age.
+pub struct NoteBrowser {
+    pub notes: Vec<AnnotationRecord>,
+    /// Every tag in use, for cycling the tag filter.
+    pub tags: Vec<String>,
+    pub book: Option<i32>,
+    pub kind: AnnotationFilter,
+    pub tag: Option<String>,
+    pub period: NotePeriod,
+    pub selected: usize,
+}
+
+impl NoteBrowser {
+    fn new() -> Self {
+        Self {
+            notes: Vec::new(),
+            tags: Vec::new(),
+            book: None,
+            kind: AnnotationFilter::All,
+            tag: None,
+            period: NotePeriod::All,
+            selected: 0,
+        }
     }
 
-    pub fn apply_cover_response(&mut self, response: CoverResponse) {
-        self.pending_cover_requests.remove(&response.book_id);
+    /// Book ids that have notes, in list order.
+    pub fn books(&self) -> Vec<i32> {
+        let mut books: Vec<i32> = self.notes.iter().map(|n| n.book_id).collect();
+        books.dedup();
+        books
+    }
+}
 
-        let Some(image) = response.image else {
-            self.cover_missing.insert(response.book_id);
-            return;
-        };
+/// Kindle clippings no library book matched, waiting to be assigned to one by hand.
+#[derive(Default)]
+pub struct ClippingReview {
+    pub clippings: Vec<PendingClippingRecord>,
+    /// Every library book, whatever the status filter.
+    pub books: Vec<BookRecord>,
+    pub selected: usize,
+    /// Index into `books` of the book the selected clipping's title goes to.
+    pub target: usize,
+}
 
-        let image = Arc::new(image);
-        self.cover_cache.insert(response.book_id, Arc::clone(&image));
-        self.cover_missing.remove(&response.book_id);
+impl ClippingReview {
+    /// The selected clipping and the others from the same Kindle title.
+    pub fn selected_group(&self) -> Vec<&PendingClippingRecord> {
+        let Some(selected) = self.clippings.get(self.selected) else {
+            return Vec::new();
+        };
+        self.clippings
+            .iter()
+            .filter(|c| c.title == selected.title && c.author == selected.author)
+            .collect()
+    }
 
-        if self.last_library_selection == Some(response.book_id) {
-            self.current_library_cover =
-                Some(self.image_picker.new_resize_protocol(image.as_ref().clone()));
+    /// Points the target at the closest book to the selected clipping's title.
+    fn guess_target(&mut self) {
+        if let Some(clipping) = self.clippings.get(self.selected) {
+            self.target =
+                clippings::best_guess(&clipping.title, &clipping.author, &self.books).unwrap_or(0);
         }
     }
+}
 
-    pub fn load_cover_image(path: &str) -> Option<image::DynamicImage> {
-        let lower = path.to_lowercase();
-        if lower.ends_with(".epub") {
-            let mut epub = EpubParser::new(path).ok()?;
-            let cover = epub.get_cover_best_effort()?;
-            return Some(Self::downscale_cover(cover));
-        }
+/// Where the open book stands with the progress sync server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncState {
+    /// Asking the server for its position.
+    Checking,
+    /// A conflict was put off, so this book isn't synced until it is reopened.
+    Paused,
+    Sending,
+    Synced,
+    Failed,
+}
 
-        if lower.ends_with(".pdf") {
-            let pdf = PdfParser::new(path).ok()?;
-            let cover = pdf.get_cover_image_preview().ok()?;
-            return Some(Self::downscale_cover(cover));
+impl SyncState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SyncState::Checking => "sync …",
+            SyncState::Paused => "sync paused",
+            SyncState::Sending => "sync ↑",
+            SyncState::Synced => "synced",
+            SyncState::Failed => "sync failed",
         }
-
-        None
     }
+}
 
-    fn downscale_cover(image: image::DynamicImage) -> image::DynamicImage {
-        const MAX_COVER_DIM: u32 = 640;
-        let (w, h) = (image.width(), image.height());
-        let max_dim = w.max(h);
-        if max_dim <= MAX_COVER_DIM {
-            return image;
-        }
+/// Another device's position for the open book, when it differs from ours.
+pub struct SyncConflict {
+    pub remote: kosync::Position,
+    /// Where the remote position is in this book, if it could be found.
+    pub locator: Option<Locator>,
+}
 
-        let scale = MAX_COVER_DIM as f32 / max_dim as f32;
-        let new_w = (w as f32 * scale).round().max(1.0) as u32;
-        let new_h = (h as f32 * scale).round().max(1.0) as u32;
-        image.resize(new_w, new_h, FilterType::Triangle)
+/// Vocabulary words being reviewed as flashcards, one at a time.
+#[derive(Default)]
+pub struct VocabReview {
+    /// Words left in this session, the one showing first.
+    pub cards: VecDeque<VocabRecord>,
+    /// Question highlights asked after the words, in a review started by a pomodoro break.
+    pub questions: VecDeque<AnnotationRecord>,
+    /// Whether the showing word's definition has been revealed.
+    pub revealed: bool,
+    pub reviewed: usize,
+    /// Whether a pomodoro break opened the review, so its end closes it again.
+    pub on_break: bool,
+}
+
+/// Which period each bar of the stats chart covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsTab {
+    Days,
+    Months,
+}
+
+impl StatsTab {
+    pub const ALL: [StatsTab; 2] = [StatsTab::Days, StatsTab::Months];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatsTab::Days => "By Day",
+            StatsTab::Months => "By Month",
+        }
     }
 
-    pub fn open_selected_book(&mut self) -> Result<()> {
-        if self.books.is_empty() {
-            return Ok(());
+    pub fn unit(&self) -> &'static str {
+        match self {
+            StatsTab::Days => "Day",
+            StatsTab::Months => "Month",
         }
-        let book_record = self.books[self.selected_book_index].clone();
-        self.load_book(book_record)
     }
+}
 
-    pub fn load_book(&mut self, book_record: BookRecord) -> Result<()> {
-        let mut parser = if book_record.path.to_lowercase().ends_with(".pdf") {
-            BookParser::Pdf(PdfParser::new(&book_record.path)?)
-        } else {
-            BookParser::Epub(EpubParser::new(&book_record.path)?)
-        };
+/// What the stats chart measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsMetric {
+    Words,
+    Minutes,
+}
 
-        let content = parser.get_chapter_content(book_record.current_chapter)?;
-        let (chapter_content, image_protocols) =
-            Self::flatten_content(&mut self.image_picker, content);
+/// What the reading of the selected day or month is broken down by, beside the chart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsBreakdown {
+    /// The tags of the books read, such as fiction against technical.
+    Tag,
+    Author,
+}
 
-        let chapter_annotations = self
-            .db
-            .get_annotations(book_record.id)?
-            .into_iter()
-            .filter(|a| a.chapter == book_record.current_chapter)
-            .collect();
+impl StatsBreakdown {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatsBreakdown::Tag => "Tag",
+            StatsBreakdown::Author => "Author",
+        }
+    }
+}
 
-        self.current_book = Some(LoadedBook {
-            id: book_record.id,
-            parser,
-            path: book_record.path.clone(),
-            current_chapter: book_record.current_chapter,
-            current_line: book_record.current_line,
-            viewport_top: book_record.current_line,
-            chapter_content,
-            image_protocols,
-            word_index: 0,
-            selection_anchor: None,
-            chapter_annotations,
-            start_time: Instant::now(),
-            words_read: 0,
-            session_words_logged: 0,
-        });
-        self.db
-            .update_progress(
-                &book_record.path,
-                book_record.current_chapter,
-                book_record.current_line,
-                0,
-            )
-            .ok();
-        self.view = AppView::Reader;
-        Ok(())
+/// One day or month of the stats chart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsBar {
+    pub start: chrono::NaiveDate,
+    pub words: usize,
+    pub secs: u64,
+}
+
+impl StatsBar {
+    pub fn value(&self, metric: StatsMetric) -> u64 {
+        match metric {
+            StatsMetric::Words => self.words as u64,
+            StatsMetric::Minutes => self.secs / 60,
+        }
     }
 
-    pub fn flatten_content(
-        picker: &mut Picker,
-        content: Vec<PageContent>,
-    ) -> (Vec<RenderLine>, Vec<StatefulProtocol>) {
-        let mut lines = Vec::new();
-        let mut protocols = Vec::new();
-        for item in content {
-            match item {
-                PageContent::Text(s) => {
-                    for line in s.lines() {
-                        lines.push(RenderLine::Text(line.to_string()));
-                    }
-                }
-                PageContent::Image(img) => {
-                    let (w, h) = (img.width(), img.height());
-
-                    // Aspect-ratio aware height calculation.
-                    // Terminal cells are roughly 1:2 height:width ratio.
-                    // We want to fit the image reasonably.
-                    // Let's assume a default width of 80 characters for the reader.
-                    let target_width_chars = 80;
-                    let aspect_ratio = h as f32 / w as f32;
-                    // height_chars = (target_width_chars * aspect_ratio) * cell_width_to_height_ratio
-                    // typically cell_width_to_height_ratio is 0.5
-                    let mut height_lines =
-                        ((target_width_chars as f32 * aspect_ratio) * 0.5) as usize;
-
-                    // Cap the height so it doesn't take over too many screens
-                    height_lines = height_lines.clamp(5, 30);
-
-                    let dynamic_image = (*img).clone();
-                    let protocol = picker.new_resize_protocol(dynamic_image);
-                    let protocol_idx = protocols.len();
-                    protocols.push(protocol);
-                    for i in 0..height_lines {
-                        lines.push(RenderLine::Image {
-                            protocol_idx,
-                            row_idx: i,
-                        });
-                    }
-                }
-            }
+    /// Short enough to sit under a bar: `4 Mar` or `Mar 25`.
+    pub fn label(&self, tab: StatsTab) -> String {
+        match tab {
+            StatsTab::Days => self.start.format("%-d %b").to_string(),
+            StatsTab::Months => self.start.format("%b %y").to_string(),
         }
-        if lines.is_empty() {
-            lines.push(RenderLine::Text(" [ Empty ] ".to_string()));
+    }
+
+    /// `Tue 4 Mar 2025` or `March 2025`.
+    pub fn describe(&self, tab: StatsTab) -> String {
+        match tab {
+            StatsTab::Days => self.start.format("%a %-d %b %Y").to_string(),
+            StatsTab::Months => self.start.format("%B %Y").to_string(),
         }
-        (lines, protocols)
     }
+}
 
-    pub fn next_chapter(&mut self) -> Result<()> {
+/// Bars for every day (or month) from the first one with reading up to `today`, so quiet
+/// stretches show as empty bars instead of being skipped.
+pub fn stats_bars(
+    daily: &[(chrono::NaiveDate, usize, u64)],
+    tab: StatsTab,
+    today: chrono::NaiveDate,
+) -> Vec<StatsBar> {
+    let period = |day: chrono::NaiveDate| match tab {
+        StatsTab::Days => day,
+        StatsTab::Months => day.with_day(1).unwrap_or(day),
+    };
+    let mut totals: BTreeMap<chrono::NaiveDate, (usize, u64)> = BTreeMap::new();
+    for &(day, words, secs) in daily {
+        let total = totals.entry(period(day)).or_default();
+        total.0 += words;
+        total.1 += secs;
+    }
+    let (Some(&first), Some(&last)) = (totals.keys().next(), totals.keys().next_back()) else {
+        return Vec::new();
+    };
+    let end = last.max(period(today));
+    let mut bars = Vec::new();
+    let mut start = first;
+    while start <= end {
+        let (words, secs) = totals.get(&start).copied().unwrap_or_default();
+        bars.push(StatsBar { start, words, secs });
+        let next = match tab {
+            StatsTab::Days => start.succ_opt(),
+            StatsTab::Months => start.checked_add_months(chrono::Months::new(1)),
+        };
+        let Some(next) = next else {
+            break;
+        };
+        start = next;
+    }
+    bars
+}
+
+/// Days in a row with reading, counting back from `today`, or from yesterday when today
+/// has none yet, since the streak isn't broken until the day is over.
+pub fn reading_streak(days: &[chrono::NaiveDate], today: chrono::NaiveDate) -> usize {
+    let read: HashSet<chrono::NaiveDate> = days.iter().copied().collect();
+    let mut day = if read.contains(&today) {
+        today
+    } else {
+        match today.pred_opt() {
+            Some(yesterday) => yesterday,
+            None => return 0,
+        }
+    };
+    let mut streak = 0;
+    while read.contains(&day) {
+        streak += 1;
+        let Some(previous) = day.pred_opt() else {
+            break;
+        };
+        day = previous;
+    }
+    streak
+}
+
+/// A generated summary being reviewed in the annotation editor before it is saved.
+pub struct LlmDraft {
+    pub scope: String,
+    pub pending: bool,
+    pub return_view: AppView,
+}
+
+/// A line of the explorer list: a directory header or one of its books.
+pub enum ExplorerRow {
+    Group {
+        dir: PathBuf,
+        files: Vec<PathBuf>,
+        collapsed: bool,
+    },
+    File(PathBuf),
+}
+
+pub struct App {
+    pub view: AppView,
+    pub previous_view: Option<AppView>,
+    pub db: Db,
+    pub db_path: String,
+    /// Nothing is saved, and actions that would change the library are refused: another
+    /// session has it open.
+    pub read_only: bool,
+    /// This session's hold on its library, looked at now and then in case another
+    /// session takes over.
+    pub instance_lock: Option<InstanceLock>,
+    /// Disk work waiting to be handed to the background writer by the main loop.
+    writes: Vec<BackgroundWrite>,
+    /// The last reading position saved, which the library shows until it is on disk.
+    last_progress: Option<ProgressSave>,
+    /// Where exported notes are written; the working directory by default.
+    pub export_dir: PathBuf,
+    /// Whether the reader is asking which format to export notes in.
+    pub export_picker: bool,
+    /// Whether the reader is asking which reading preset the book should use.
+    pub preset_picker: bool,
+    /// Vault folder kept in step with each book's notes, from `export_vault_path`.
+    pub export_vault: Option<PathBuf>,
+    pub export_mode: ExportMode,
+    /// How each kind of note is marked in Markdown exports and copies.
+    pub markdown_markers: MarkdownMarkers,
+    pub books: Vec<BookRecord>,
+    /// Words per minute used to estimate time left, from past sittings when there are any.
+    pub reading_wpm: f64,
+    /// How each book that has been read has been read lately, by book id.
+    pub book_paces: HashMap<i32, BookPace>,
+    pub selected_book_index: usize,
+    pub pending_book_removal: Option<i32>,
+    /// Moved book files found at startup, waiting for the user to accept the new paths.
+    pub pending_relinks: Vec<Relink>,
+    pub library_status_filter: Option<ReadingStatus>,
+    pub current_book: Option<LoadedBook>,
+    pub should_quit: bool,
+    pub search_query: String,
+    /// Whether in-book and global search tell upper and lower case apart.
+    pub search_case_sensitive: bool,
+    pub toc_items: Vec<TocEntry>,
+    pub selected_toc_index: usize,
+    /// The open book's captioned images and tables, for the list of figures.
+    pub figure_items: Vec<Figure>,
+    pub selected_figure_index: usize,
+    /// Where the open book was left mid-chapter before, newest first.
+    pub last_stops: Vec<LastStopRecord>,
+    /// Shown over the reader after `q` until a key goes on to the library.
+    pub session_summary: Option<SessionSummary>,
+    pub selected_last_stop_index: usize,
+    pub theme: Theme,
+    // RSVP State
+    pub rsvp_active: bool,
+    pub rsvp_index: usize,
+    pub rsvp_wpm: u64,
+    pub rsvp_words: Vec<String>,
+    /// Where each RSVP word is in the chapter, as (line, word).
+    pub rsvp_positions: Vec<(usize, usize)>,
+    /// Words of the chapter before the first RSVP word, for showing how far through it is.
+    pub rsvp_skipped: usize,
+    pub rsvp_last_tick: Instant,
+    // Annotation State
+    pub annotation_note: String,
+    pub all_annotations: Vec<AnnotationRecord>,
+    pub current_annotations: Vec<AnnotationRecord>,
+    pub selected_annotation_index: usize,
+    /// Notes the selected note links to, and notes linking to it, loaded when the
+    /// selection changes.
+    pub annotation_links: Vec<AnnotationRecord>,
+    pub annotation_backlinks: Vec<AnnotationRecord>,
+    /// The note `annotation_links` were loaded for.
+    links_loaded_for: Option<i32>,
+    pub annotation_filter: AnnotationFilter,
+    /// User-defined annotation kinds, with lowercase names unique among all kinds.
+    pub annotation_kinds: Vec<AnnotationKindConfig>,
+    /// Tags being typed for the selected note, or the selected book in the library, while
+    /// the tag prompt is open.
+    pub tag_input: Option<String>,
+    /// Path being typed for a file to attach to the selected note, while the attach
+    /// prompt is open.
+    pub attach_input: Option<String>,
+    pub note_browser: NoteBrowser,
+    pub clipping_review: ClippingReview,
+    // Summarization State
+    pub llm: LlmSettings,
+    pub llm_draft: Option<LlmDraft>,
+    pub status_message: Option<String>,
+    // Progress Sync State
+    pub kosync: KosyncSettings,
+    /// The open book's id on the sync server, when sync is on.
+    pub sync_document: Option<String>,
+    /// Document whose remote position should be fetched, taken by the main loop.
+    pub sync_pull: Option<String>,
+    /// How the open book stands with the server; None when sync is off.
+    pub sync_state: Option<SyncState>,
+    /// The position moved since it was last pushed.
+    sync_dirty: bool,
+    /// Push without waiting for the next interval, e.g. after the user kept our position.
+    pub sync_push_now: bool,
+    /// A different position from another device, waiting for the user to pick one.
+    pub sync_conflict: Option<SyncConflict>,
+    // Reading Group State
+    /// Name and secret for reading-group positions, from `tbook.toml`.
+    pub group_name: String,
+    pub group_secret: String,
+    /// A group member's position being pasted, while the reader asks for one.
+    pub group_input: Option<String>,
+    // Dictionary State
+    pub dictionary_query: String,
+    /// Where the word being looked up was met, saved with it in the vocabulary.
+    pub dictionary_source: Option<VocabSource>,
+    pub dictionary_result: String,
+    pub dictionary_settings: Option<LookupSettings>,
+    pub default_dictionary_language: Option<String>,
+    pub default_dictionary: Option<String>,
+    pub lookup_language_input: String,
+    pub lookup_dictionary_input: String,
+    pub lookup_editing_dictionary: bool,
+    // Vocabulary State
+    pub vocabulary: Vec<VocabRecord>,
+    pub selected_vocab_index: usize,
+    pub vocab_review: VocabReview,
+    pub vocab_review_return_view: AppView,
+    /// Words due for review today, shown in the library title.
+    pub vocab_due: usize,
+    // Stats State
+    pub stats_tab: StatsTab,
+    pub stats_metric: StatsMetric,
+    pub stats_breakdown: StatsBreakdown,
+    /// Bar the stats chart has selected; `None` follows the latest one.
+    pub stats_cursor: Option<usize>,
+    /// First bar shown, kept by the chart so it only scrolls when the cursor leaves it.
+    pub stats_scroll: usize,
+    pub stats_return_view: AppView,
+    // Layout State
+    pub margin: u16,
+    pub line_spacing: u16,
+    /// Paragraph layout used by books without their own setting.
+    pub paragraph_spacing: u16,
+    pub first_line_indent: u16,
+    pub line_focus: LineFocus,
+    pub line_numbers: LineNumbers,
+    pub progress_gauge: ProgressGauge,
+    /// Whether paragraphs are justified, every row but the last ending flush right.
+    pub justify: bool,
+    /// Whether focus mode keeps the line being read in the middle of the screen.
+    pub typewriter_scrolling: bool,
+    pub focus_ruler: FocusRuler,
+    /// Digits typed before a reader key, as in vim's `10j`.
+    pub key_count: Option<usize>,
+    /// Set after a first `g`, waiting for the second of `gg`.
+    pub pending_g: bool,
+    /// The reader's go-to prompt, opened with `:`.
+    pub goto_input: Option<String>,
+    /// Words of the chapter as the last frame drew them.
+    pub word_areas: Vec<WordArea>,
+    /// Buttons, footer hints, and list rows as the last frame drew them.
+    pub click_areas: Vec<ClickArea>,
+    /// Configured line focus strengths in percent, keyed by lowercase theme name.
+    pub line_focus_intensity: HashMap<String, u8>,
+    /// Layout profile picked for this terminal, if any.
+    pub layout_profile: Option<String>,
+    /// Widest a column of reader text may get; the rest of the width is left as margin.
+    pub max_text_width: Option<u16>,
+    pub text_columns: u16,
+    /// The layout in place before a book's preset replaced it, restored when it is dropped.
+    layout_before_preset: Option<ReaderLayout>,
+    /// Width of one column of reader text, which images are sized to fit.
+    pub reader_text_width: u16,
+    /// Image height cap in pixels, converted to rows with the terminal's font size.
+    pub image_max_height_px: Option<u32>,
+    /// Images, cover previews, and animations are off, and the loop redraws less often.
+    pub light_mode: bool,
+    /// Colors the terminal can show; the screen is brought down to them after drawing.
+    pub color_depth: ColorDepth,
+    /// Whether the mouse is captured; without it the terminal keeps its own selection.
+    pub mouse_capture: bool,
+    pub top_bar: TopBarConfig,
+    pub night_tint: Option<NightTint>,
+    /// Strength of the warm tint right now, while the night tint is on.
+    pub night_tint_percent: Option<u8>,
+    pub daily_goal_words: usize,
+    pub focus_mode: bool,
+    pub pdf_reflow: bool,
+    pub pomodoro: PomodoroState,
+    /// Whether pomodoro breaks open a short flashcard review.
+    pub break_review: bool,
+    /// Break reviews opened so far, to ask different questions each time.
+    break_reviews: usize,
+    // Global Search State
+    pub global_search_query: String,
+    pub global_search_results: Vec<SearchHit>,
+    pub selected_search_index: usize,
+    pub search_history: Vec<String>,
+    pub search_history_index: Option<usize>,
+    pub saved_searches: Vec<SavedSearchRecord>,
+    pub selected_saved_search_index: usize,
+    pub saved_search_name: Option<String>,
+    /// Set while a global search is running on the worker.
+    pub global_search_progress: Option<GlobalSearchProgress>,
+    global_search_generation: u64,
+    pending_global_search: Option<GlobalSearchRequest>,
+    // Whole-book search state
+    pub book_search_query: String,
+    pub book_search_results: Vec<BookSearchHit>,
+    pub selected_book_search_index: usize,
+    pub omnibox_query: String,
+    pub omnibox_results: Vec<OmniboxHit>,
+    pub selected_omnibox_index: usize,
+    /// The view the omnibox was opened from, which Esc goes back to.
+    pub omnibox_return_view: AppView,
+    pub indexing_books: HashSet<i32>,
+    /// Books queued since the indexer was last idle, for "indexing 3/40" progress.
+    pub indexing_total: usize,
+    // Concept Index State
+    pub concept_index: Vec<(String, Vec<AnnotationRecord>)>,
+    pub selected_concept_index: usize,
+    pub selected_concept_note_index: usize,
+    pub concept_focus_notes: bool,
+    // Explorer State
+    pub explorer_path: String,
+    pub library_path: String,
+    pub recent_scan_paths: Vec<String>,
+    pub selected_recent_path: Option<usize>,
+    pub path_completions: Vec<String>,
+    pub path_completion_index: Option<usize>,
+    pub explorer_results: Vec<std::path::PathBuf>,
+    pub explorer_selected: HashSet<PathBuf>,
+    pub explorer_collapsed: HashSet<PathBuf>,
+    pub explorer_sizes: HashMap<PathBuf, u64>,
+    pub explorer_skipped: usize,
+    pub pending_large_import: Option<Vec<PathBuf>>,
+    pub import_rules: ImportRules,
+    /// Cleanup rules for book text from the config, picked per book as it opens.
+    pub text_filters: Vec<TextFilter>,
+    pub selected_explorer_index: usize,
+    pub is_scanning: bool,
+    // Web Article State
+    pub url_input: String,
+    pub url_fetch_status: Option<String>,
+    pub image_picker: Picker,
+    pub current_library_cover: Option<StatefulProtocol>,
+    pub cover_cache: HashMap<i32, Arc<image::DynamicImage>>,
+    pub cover_missing: HashSet<i32>,
+    pub pending_cover_requests: HashSet<i32>,
+    /// Wait after the selection moves before loading its cover, so scrolling past a book
+    /// doesn't decode it.
+    pub cover_debounce: Duration,
+    /// Time without input after which reading pauses (see [`App::pause_for_idle`]).
+    pub idle_timeout: Duration,
+    pub last_input_at: Instant,
+    /// What was paused for being idle, until the next input resumes it.
+    pub idle_pause: Option<IdlePause>,
+    pub cover_max_dimension: u32,
+    /// Neighbors on each side of the selection whose covers load ahead of time.
+    pub cover_prefetch: usize,
+    pub last_library_selection: Option<i32>,
+    // Auto-scroll State
+    pub auto_scroll_active: bool,
+    pub auto_scroll_interval_ms: u64,
+    pub auto_scroll_last_tick: Instant,
+    // Last list selection of auxiliary views, keyed by book for per-book views
+    pub view_selections: HashMap<(Option<i32>, AppView), usize>,
+}
+
+pub struct LoadedBook {
+    pub id: i32,
+    pub parser: BookParser,
+    pub path: String,
+    pub current_chapter: usize,
+    pub current_line: usize,              // Cursor line
+    pub viewport_top: usize,              // Viewport top line
+    pub chapter_content: Vec<RenderLine>, // Lines of current chapter
+    /// Lines of a giant chapter past the loaded ones, held back until they are reached.
+    pub pending_lines: Vec<RenderLine>,
+    pub image_protocols: Vec<StatefulProtocol>,
+    pub word_index: usize,                        // Cursor word index
+    pub selection_anchor: Option<(usize, usize)>, // (line, word)
+    pub chapter_annotations: Vec<AnnotationRecord>,
+    pub start_time: Instant,
+    pub words_read: usize,
+    pub session_words_logged: usize,
+    /// Chapter and reading position (see `reading_position`) up to which reading has been
+    /// counted in `words_read`, and the cursor it was taken at.
+    pub read_mark: Option<ReadMark>,
+    pub sitting: Option<Sitting>,
+    pub session: BookSession,
+    /// Words per chapter; empty until the background indexer has counted them.
+    pub chapter_words: Vec<usize>,
+    /// This book's paragraph layout, overriding the app-wide one when set.
+    pub paragraph_spacing: Option<u16>,
+    pub first_line_indent: Option<u16>,
+    pub status: ReadingStatus,
+    /// Turns a screenful at a time instead of scrolling line by line.
+    pub paged: bool,
+    pub preset: Option<ReadingPreset>,
+    /// Where the open chapter's pages start, for the layout they were worked out for.
+    page_cache: Option<PageCache>,
+    /// Matches of the in-book search in the open chapter.
+    search_cache: Option<SearchCache>,
+    /// Places jumped away from, newest last, for Ctrl-o as in vim's jump list.
+    pub jumps_back: Vec<Locator>,
+    /// Places Ctrl-o came back from, for Ctrl-i.
+    pub jumps_forward: Vec<Locator>,
+    /// Cleanup rules from the config that are for this book.
+    pub text_filters: Vec<TextFilter>,
+}
+
+impl LoadedBook {
+    /// A chapter as the parser reads it, with the book's text filters run over it.
+    fn chapter(&mut self, index: usize) -> Result<Vec<PageContent>> {
+        let content = self.parser.get_chapter_content(index)?;
+        Ok(filter::apply(&self.text_filters, content))
+    }
+}
+
+/// What decides where the reader's pages break and where on its row each word goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageLayout {
+    /// Text rows on screen, across every column.
+    pub rows: usize,
+    pub width: u16,
+    pub line_spacing: u16,
+    pub paragraph_spacing: u16,
+    pub first_line_indent: u16,
+    /// Whether the spaces of wrapped rows are widened to end them flush right.
+    pub justify: bool,
+}
+
+/// How far the reading position is from the next place to stop: the next heading in
+/// the chapter, and the chapter's end, where the next table of contents entry starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinishLine {
+    /// Words to the next heading, when one comes before the chapter ends.
+    pub section_words: Option<usize>,
+    pub chapter_words: usize,
+    /// Pages after this one, when reading a page at a time.
+    pub chapter_pages: Option<usize>,
+    /// Words to the end of the book, once the background indexer has counted them.
+    pub book_words: Option<usize>,
+}
+
+struct SearchCache {
+    query: String,
+    case_sensitive: bool,
+    chapter: usize,
+    lines: usize,
+    matches: Vec<SearchMatch>,
+}
+
+struct PageCache {
+    layout: PageLayout,
+    chapter: usize,
+    lines: usize,
+    starts: Vec<usize>,
+}
+
+pub const MAX_PARAGRAPH_SPACING: u16 = 3;
+pub const MAX_FIRST_LINE_INDENT: u16 = 8;
+pub const MAX_MARGIN: u16 = 20;
+pub const MAX_TEXT_COLUMNS: u16 = 4;
+/// Places each book's jump list remembers, as in vim.
+pub const MAX_JUMPS: usize = 100;
+
+/// Rows an image may take at most: at least this many under a layout profile's pixel cap,
+/// and the default when there is none.
+const MIN_IMAGE_ROWS: usize = 5;
+const DEFAULT_MAX_IMAGE_ROWS: usize = 30;
+/// Column width assumed until the terminal has been measured.
+const DEFAULT_READER_TEXT_WIDTH: u16 = 80;
+/// Stands in for an image in light mode.
+const IMAGE_PLACEHOLDER: &str = "[image]";
+/// A `[[id]]` reference from a note to another annotation.
+static ANNOTATION_REF: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\[\[(\d+)\]\]").unwrap());
+/// Longest text line kept whole. Malformed books can hold a single line of hundreds of
+/// thousands of characters, which would stall wrapping on every frame.
+pub const MAX_LINE_CHARS: usize = 2_000;
+/// Lines of a chapter loaded at first; the rest follow as the cursor nears the end.
+pub const CHAPTER_LINE_CAP: usize = 20_000;
+
+/// Reading speed assumed for time-left estimates until a sitting has been timed.
+pub const DEFAULT_READING_WPM: f64 = 250.0;
+/// Words read in a sitting after which time-left estimates go by its pace alone; before
+/// that it is blended with the long-run average.
+pub const ROLLING_WPM_WORDS: usize = 1_000;
+
+/// Days of reading a book's pace is taken over, for projecting when it will be finished.
+pub const PACE_DAYS: u64 = 14;
+/// A book being read that hasn't been read for this many days is stalled.
+pub const STALLED_DAYS: i64 = 30;
+
+/// How a book has been read lately.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookPace {
+    pub last_read: chrono::NaiveDate,
+    /// Words a day over the last [`PACE_DAYS`], from the first of them it was read on.
+    pub words_per_day: f64,
+}
+
+impl BookPace {
+    pub fn from_record(record: &BookPaceRecord, today: chrono::NaiveDate) -> Option<Self> {
+        let date = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok();
+        let days = record
+            .first_recent
+            .as_deref()
+            .and_then(date)
+            .map_or(0, |first| (today - first).num_days() + 1);
+        Some(Self {
+            last_read: date(&record.last_read)?,
+            words_per_day: if days > 0 {
+                record.recent_words as f64 / days as f64
+            } else {
+                0.0
+            },
+        })
+    }
+
+    /// The day the rest of the book would be read by at this pace, unless it hasn't
+    /// been read lately.
+    pub fn finish_date(
+        &self,
+        words_left: usize,
+        today: chrono::NaiveDate,
+    ) -> Option<chrono::NaiveDate> {
+        if words_left == 0 || self.words_per_day <= 0.0 {
+            return None;
+        }
+        let days = (words_left as f64 / self.words_per_day).ceil() as u64;
+        today.checked_add_days(chrono::Days::new(days))
+    }
+
+    /// Days since the book was last read, once that is [`STALLED_DAYS`] or more.
+    pub fn stalled_days(&self, today: chrono::NaiveDate) -> Option<i64> {
+        Some((today - self.last_read).num_days()).filter(|d| *d >= STALLED_DAYS)
+    }
+}
+
+/// Moves further than this many words at once, either way, are jumps rather than reading.
+pub const MAX_READ_STEP_WORDS: usize = 2_000;
+
+/// How far reading has been counted in the open book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadMark {
+    pub chapter: usize,
+    pub position: usize,
+    /// Line and word of the cursor when last counted, to skip recounting when it is still.
+    pub cursor: (usize, usize),
+}
+
+/// A sitting ends once the reader has been idle this long; it is recorded as ending at
+/// the last input, so the idle stretch is not counted.
+pub const DEFAULT_IDLE_MINUTES: u64 = 5;
+
+/// Most flashcards a pomodoro break review asks.
+pub const BREAK_REVIEW_CARDS: usize = 5;
+
+/// Shown when a read-only session is asked to change the library.
+pub const READ_ONLY_MESSAGE: &str = "Read-only: another tbook session has this library open";
+
+/// How far apart two devices' positions must be before asking which to keep, so
+/// rounding in the synced percentage doesn't prompt on every open.
+const SYNC_CONFLICT_MARGIN: f64 = 0.005;
+
+/// One uninterrupted stretch of reading, from the first input to the latest.
+pub struct Sitting {
+    pub started_at: chrono::DateTime<chrono::Local>,
+    pub last_active_at: chrono::DateTime<chrono::Local>,
+    pub words_at_start: usize,
+}
+
+/// What going idle stopped, to start again on the next input.
+#[derive(Clone, Copy, Debug)]
+pub struct IdlePause {
+    /// The input before going idle; the time since doesn't count as reading.
+    pub last_input_at: Instant,
+    pub pomodoro: bool,
+    pub auto_scroll: bool,
+    pub rsvp: bool,
+}
+
+/// Reading since the book was opened, or since the summary of the last session.
+pub struct BookSession {
+    pub opened_at: chrono::DateTime<chrono::Local>,
+    /// Length of the sittings closed since.
+    pub secs: u64,
+    pub words_at_start: usize,
+    pub annotations_at_start: usize,
+}
+
+/// What the popup on closing a book shows.
+pub struct SessionSummary {
+    pub session: BookSessionRecord,
+    /// Days in a row with reading, today included.
+    pub streak: usize,
+}
+
+/// A reading position on its way to the database.
+#[derive(Clone, Debug)]
+pub struct ProgressSave {
+    pub book_id: i32,
+    pub path: String,
+    pub chapter: usize,
+    pub line: usize,
+    pub words_read: usize,
+    pub locator: Locator,
+    pub position_words: Option<usize>,
+    /// Words read since the last save, added to today's reading.
+    pub session_words: usize,
+}
+
+impl ProgressSave {
+    fn write(&self, db: &Db) -> Result<()> {
+        db.update_progress(
+            &self.path,
+            self.chapter,
+            self.line,
+            self.words_read,
+            Some(&self.locator.to_string()),
+        )?;
+        if let Some(words) = self.position_words {
+            db.set_position_words(self.book_id, words)?;
+        }
+        if self.session_words > 0 {
+            db.log_reading_session(self.book_id, self.session_words)?;
+        }
+        Ok(())
+    }
+
+    fn apply_to(&self, record: &mut BookRecord) {
+        if record.id != self.book_id {
+            return;
+        }
+        record.current_chapter = self.chapter;
+        record.current_line = self.line;
+        record.lines_read = self.words_read;
+        record.locator = Some(self.locator.clone());
+        if let Some(words) = self.position_words {
+            record.position_words = words;
+        }
+    }
+}
+
+/// Disk work done off the render loop, so a slow disk or network share never holds up
+/// a key press. Writes run one at a time, in the order they were made.
+#[derive(Debug)]
+pub enum BackgroundWrite {
+    Progress(ProgressSave),
+    /// A file `name` in the export folder `dir`, which is made if it is missing.
+    Export {
+        dir: PathBuf,
+        name: String,
+        contents: String,
+        mode: ExportMode,
+    },
+    /// A book's note, `name`, in the vault folder `dir`.
+    VaultNote {
+        dir: PathBuf,
+        name: String,
+        contents: String,
+    },
+}
+
+impl BackgroundWrite {
+    /// Does the write, returning what to tell the user about it, if anything.
+    pub fn run(self, db: &Db) -> Option<String> {
+        match self {
+            Self::Progress(save) => save
+                .write(db)
+                .err()
+                .map(|e| format!("Could not save progress: {}", e)),
+            Self::Export {
+                dir,
+                name,
+                contents,
+                mode,
+            } => {
+                let path = dir.join(name);
+                let written = std::fs::create_dir_all(&dir)
+                    .and_then(|()| export::write_export(&path, &contents, mode));
+                Some(match written {
+                    Ok(()) if mode == ExportMode::Append => {
+                        format!("Added notes to {}", path.display())
+                    }
+                    Ok(()) => format!("Exported notes to {}", path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                })
+            }
+            Self::VaultNote {
+                dir,
+                name,
+                contents,
+            } => std::fs::create_dir_all(&dir)
+                .and_then(|()| {
+                    export::write_export(&dir.join(name), &contents, ExportMode::Overwrite)
+                })
+                .err()
+                .map(|e| format!("Vault export failed: {}", e)),
+        }
+    }
+}
+
+/// Hits a global search collects before it stops.
+pub const GLOBAL_SEARCH_LIMIT: usize = 50;
+
+/// A library-wide search for a worker to run, book by book.
+#[derive(Clone, Debug)]
+pub struct GlobalSearchRequest {
+    /// Tells the results of this search from those of one it replaced.
+    pub generation: u64,
+    pub query: String,
+    pub case_sensitive: bool,
+    /// Set to stop the search before the next book.
+    pub cancel: Arc<AtomicBool>,
+}
+
+/// What a global search found in one more book.
+#[derive(Debug)]
+pub struct GlobalSearchUpdate {
+    pub generation: u64,
+    pub scanned: usize,
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+    pub error: Option<String>,
+}
+
+/// How far the running global search has got.
+#[derive(Clone, Debug)]
+pub struct GlobalSearchProgress {
+    pub scanned: usize,
+    pub total: usize,
+    cancel: Arc<AtomicBool>,
+}
+
+impl GlobalSearchRequest {
+    /// Searches one book at a time, sending what each one held. Plain words go through
+    /// the full-text index; patterns and case-sensitive searches read the indexed text
+    /// of every chapter.
+    pub fn run(&self, db: &Db, mut send: impl FnMut(GlobalSearchUpdate)) {
+        let update = |scanned, total, hits, error| GlobalSearchUpdate {
+            generation: self.generation,
+            scanned,
+            total,
+            hits,
+            error,
+        };
+        let books = match db.get_books() {
+            Ok(books) => books,
+            Err(e) => return send(update(0, 0, Vec::new(), Some(e.to_string()))),
+        };
+        let pattern = SearchPattern::new(&self.query, self.case_sensitive);
+        let indexed = !self.case_sensitive && SearchPattern::is_plain(&self.query);
+        let mut found = 0;
+        for (i, book) in books.iter().enumerate() {
+            if self.cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let limit = GLOBAL_SEARCH_LIMIT - found;
+            let hits = match pattern {
+                Some(_) if indexed => db.search_book_chapters(book.id, &self.query, limit),
+                Some(ref pattern) => db.search_chapters_matching(pattern, Some(book.id), limit),
+                None => Ok(Vec::new()),
+            };
+            let (hits, error) = match hits {
+                Ok(hits) => (hits, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            };
+            found += hits.len();
+            let last = found >= GLOBAL_SEARCH_LIMIT || error.is_some();
+            // A full list ends the search, so report it as having looked everywhere.
+            let scanned = if last { books.len() } else { i + 1 };
+            send(update(scanned, books.len(), hits, error));
+            if last {
+                return;
+            }
+        }
+        if books.is_empty() {
+            send(update(0, 0, Vec::new(), None));
+        }
+    }
+}
+
+/// Cover loading when `tbook.toml` doesn't say otherwise.
+pub const COVER_DEBOUNCE_MS: u64 = 150;
+pub const COVER_MAX_DIMENSION: u32 = 640;
+
+#[derive(Clone)]
+pub struct CoverRequest {
+    pub book_id: i32,
+    pub path: String,
+    /// Longest side, in pixels, the decoded cover is shrunk to.
+    pub max_dimension: u32,
+}
+
+pub struct CoverResponse {
+    pub book_id: i32,
+    pub image: Option<image::DynamicImage>,
+}
+
+impl App {
+    pub fn new(db_path: &str) -> Result<Self> {
+        let db = Db::new(db_path)?;
+        let books = db.get_books()?;
+        let reading_wpm = db
+            .get_average_wpm()?
+            .unwrap_or(DEFAULT_READING_WPM);
+        let book_paces = Self::book_paces(&db)?;
+        let app = Self {
+            view: AppView::Library,
+            previous_view: None,
+            db,
+            db_path: db_path.to_string(),
+            read_only: false,
+            instance_lock: None,
+            writes: Vec::new(),
+            last_progress: None,
+            export_dir: PathBuf::from("."),
+            export_mode: ExportMode::default(),
+            export_vault: None,
+            markdown_markers: MarkdownMarkers::default(),
+            export_picker: false,
+            preset_picker: false,
+            books,
+            reading_wpm,
+            book_paces,
+            selected_book_index: 0,
+            pending_book_removal: None,
+            pending_relinks: Vec::new(),
+            library_status_filter: None,
+            current_book: None,
+            should_quit: false,
+            search_query: String::new(),
+            search_case_sensitive: false,
+            toc_items: Vec::new(),
+            selected_toc_index: 0,
+            figure_items: Vec::new(),
+            selected_figure_index: 0,
+            last_stops: Vec::new(),
+            session_summary: None,
+            selected_last_stop_index: 0,
+            theme: Theme::Default,
+            rsvp_active: false,
+            rsvp_index: 0,
+            rsvp_wpm: 300,
+            rsvp_words: Vec::new(),
+            rsvp_positions: Vec::new(),
+            rsvp_skipped: 0,
+            rsvp_last_tick: Instant::now(),
+            annotation_note: String::new(),
+            all_annotations: Vec::new(),
+            current_annotations: Vec::new(),
+            selected_annotation_index: 0,
+            annotation_links: Vec::new(),
+            annotation_backlinks: Vec::new(),
+            links_loaded_for: None,
+            annotation_filter: AnnotationFilter::All,
+            annotation_kinds: Vec::new(),
+            tag_input: None,
+            attach_input: None,
+            note_browser: NoteBrowser::new(),
+            clipping_review: ClippingReview::default(),
+            llm: LlmSettings::default(),
+            llm_draft: None,
+            status_message: None,
+            kosync: KosyncSettings::default(),
+            sync_document: None,
+            sync_pull: None,
+            sync_state: None,
+            sync_dirty: false,
+            sync_push_now: false,
+            sync_conflict: None,
+            group_name: default_group_name(),
+            group_secret: String::new(),
+            group_input: None,
+            dictionary_query: String::new(),
+            dictionary_source: None,
+            dictionary_result: String::new(),
+            dictionary_settings: None,
+            default_dictionary_language: None,
+            default_dictionary: None,
+            lookup_language_input: String::new(),
+            lookup_dictionary_input: String::new(),
+            lookup_editing_dictionary: false,
+            vocabulary: Vec::new(),
+            selected_vocab_index: 0,
+            vocab_review: VocabReview::default(),
+            vocab_review_return_view: AppView::Library,
+            vocab_due: 0,
+            stats_tab: StatsTab::Days,
+            stats_metric: StatsMetric::Words,
+            stats_breakdown: StatsBreakdown::Tag,
+            stats_cursor: None,
+            stats_scroll: usize::MAX,
+            stats_return_view: AppView::Library,
+            margin: 2,
+            line_spacing: 0,
+            paragraph_spacing: 0,
+            first_line_indent: 0,
+            line_focus: LineFocus::Off,
+            line_numbers: LineNumbers::Off,
+            progress_gauge: ProgressGauge::Off,
+            justify: false,
+            typewriter_scrolling: true,
+            focus_ruler: FocusRuler::Off,
+            key_count: None,
+            pending_g: false,
+            goto_input: None,
+            word_areas: Vec::new(),
+            click_areas: Vec::new(),
+            line_focus_intensity: HashMap::new(),
+            layout_profile: None,
+            max_text_width: None,
+            text_columns: 1,
+            layout_before_preset: None,
+            reader_text_width: DEFAULT_READER_TEXT_WIDTH,
+            image_max_height_px: None,
+            light_mode: false,
+            color_depth: ColorDepth::TrueColor,
+            mouse_capture: true,
+            top_bar: TopBarConfig::default(),
+            night_tint: None,
+            night_tint_percent: None,
+            daily_goal_words: 1500,
+            focus_mode: false,
+            pdf_reflow: false,
+            pomodoro: PomodoroState::new(Duration::from_secs(1500), Duration::from_secs(300)),
+            break_review: false,
+            break_reviews: 0,
+            global_search_query: String::new(),
+            global_search_results: Vec::new(),
+            selected_search_index: 0,
+            global_search_progress: None,
+            global_search_generation: 0,
+            pending_global_search: None,
+            book_search_query: String::new(),
+            book_search_results: Vec::new(),
+            selected_book_search_index: 0,
+            omnibox_query: String::new(),
+            omnibox_results: Vec::new(),
+            selected_omnibox_index: 0,
+            omnibox_return_view: AppView::Library,
+            search_history: Vec::new(),
+            search_history_index: None,
+            saved_searches: Vec::new(),
+            selected_saved_search_index: 0,
+            saved_search_name: None,
+            indexing_books: HashSet::new(),
+            indexing_total: 0,
+            concept_index: Vec::new(),
+            selected_concept_index: 0,
+            selected_concept_note_index: 0,
+            concept_focus_notes: false,
+            explorer_path: String::new(),
+            library_path: String::new(),
+            recent_scan_paths: Vec::new(),
+            selected_recent_path: None,
+            path_completions: Vec::new(),
+            path_completion_index: None,
+            explorer_results: Vec::new(),
+            explorer_selected: HashSet::new(),
+            explorer_collapsed: HashSet::new(),
+            explorer_sizes: HashMap::new(),
+            explorer_skipped: 0,
+            pending_large_import: None,
+            import_rules: ImportRules::default(),
+            text_filters: Vec::new(),
+            selected_explorer_index: 0,
+            is_scanning: false,
+            url_input: String::new(),
+            url_fetch_status: None,
+            // Initialized to a reasonable default; in TUI mode this should be replaced with
+            // Picker::from_query_stdio() after entering alternate screen.
+            image_picker: Picker::halfblocks(),
+            current_library_cover: None,
+            cover_cache: HashMap::new(),
+            cover_missing: HashSet::new(),
+            pending_cover_requests: HashSet::new(),
+            cover_debounce: Duration::from_millis(COVER_DEBOUNCE_MS),
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_MINUTES * 60),
+            last_input_at: Instant::now(),
+            idle_pause: None,
+            cover_max_dimension: COVER_MAX_DIMENSION,
+            cover_prefetch: 0,
+            last_library_selection: None,
+            auto_scroll_active: false,
+            auto_scroll_interval_ms: 2000, // Default scroll every 2 seconds
+            auto_scroll_last_tick: Instant::now(),
+            view_selections: HashMap::new(),
+        };
+
+        Ok(app)
+    }
+
+    pub fn apply_config(&mut self, config: &crate::config::AppConfig) {
+        self.margin = config.margin;
+        self.line_spacing = config.line_spacing;
+        self.paragraph_spacing = config.paragraph_spacing.unwrap_or(0).min(MAX_PARAGRAPH_SPACING);
+        self.first_line_indent = config.first_line_indent.unwrap_or(0).min(MAX_FIRST_LINE_INDENT);
+        self.line_focus = LineFocus::from_str(config.line_focus.as_deref().unwrap_or("off"));
+        self.line_numbers = LineNumbers::from_str(config.line_numbers.as_deref().unwrap_or("off"));
+        self.progress_gauge =
+            ProgressGauge::from_str(config.progress_gauge.as_deref().unwrap_or("off"));
+        self.justify = config.justify.unwrap_or(false);
+        self.text_columns = config.columns.unwrap_or(1).clamp(1, MAX_TEXT_COLUMNS);
+        self.typewriter_scrolling = config.typewriter_scrolling.unwrap_or(true);
+        self.focus_ruler = FocusRuler::from_str(config.focus_ruler.as_deref().unwrap_or("off"));
+        self.cover_debounce =
+            Duration::from_millis(config.cover_debounce_ms.unwrap_or(COVER_DEBOUNCE_MS));
+        self.cover_max_dimension = config.cover_max_dimension.unwrap_or(COVER_MAX_DIMENSION);
+        self.cover_prefetch = config.cover_prefetch.unwrap_or(0);
+        self.line_focus_intensity = config
+            .line_focus_intensity
+            .iter()
+            .flatten()
+            .map(|(theme, percent)| (theme.to_lowercase(), (*percent).min(100)))
+            .collect();
+        self.annotation_kinds.clear();
+        for kind in config.annotation_kinds.iter().flatten() {
+            let name = kind.name.trim().to_lowercase();
+            if name.is_empty()
+                || AnnotationKind::parse(&name).is_some()
+                || self.annotation_kinds.iter().any(|k| k.name == name)
+            {
+                continue;
+            }
+            self.annotation_kinds.push(AnnotationKindConfig {
+                name,
+                color: kind.color,
+            });
+        }
+        self.light_mode = config.light_mode.unwrap_or(false);
+        self.mouse_capture = config.mouse.unwrap_or(true);
+        self.top_bar = config.top_bar.clone().unwrap_or_default();
+        self.night_tint = config.night_tint_start.map(|start| NightTint {
+            start_hour: start % 24,
+            end_hour: config.night_tint_end.unwrap_or(6) % 24,
+            strength: config.night_tint_strength.unwrap_or(40).min(100),
+        });
+        self.update_night_tint(chrono::Local::now().hour());
+        if let Some(dir) = config.export_dir.as_deref().map(str::trim) {
+            if !dir.is_empty() {
+                self.export_dir = PathBuf::from(expand_path(dir));
+            }
+        }
+        self.export_mode = config
+            .export_mode
+            .as_deref()
+            .and_then(ExportMode::parse)
+            .unwrap_or_default();
+        self.export_vault = config
+            .export_vault_path
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| PathBuf::from(expand_path(p)));
+        self.markdown_markers =
+            MarkdownMarkers(config.markdown_markers.clone().unwrap_or_default());
+        self.daily_goal_words = config.daily_goal_words;
+        self.theme = Theme::from_str(&config.theme);
+        self.llm = LlmSettings {
+            command: config.llm_command.clone(),
+            endpoint: config.llm_endpoint.clone(),
+            model: config.llm_model.clone(),
+        };
+        self.kosync = KosyncSettings::new(
+            config.kosync_server.as_deref(),
+            config.kosync_username.as_deref().unwrap_or(""),
+            config.kosync_password.as_deref().unwrap_or(""),
+            config.kosync_device.as_deref().unwrap_or("tbook"),
+        );
+        self.group_name = config.group_name.clone().unwrap_or_else(default_group_name);
+        self.group_secret = config.group_secret.clone().unwrap_or_default();
+        self.default_dictionary_language = config.dictionary_language.clone();
+        self.default_dictionary = config.dictionary.clone();
+        self.library_path = config.library_path.clone();
+        self.import_rules = ImportRules::from_config(config);
+        self.text_filters = config
+            .text_filters
+            .iter()
+            .flatten()
+            .filter_map(TextFilter::new)
+            .collect();
+        if self.explorer_path.is_empty() {
+            self.explorer_path = config.library_path.clone();
+        }
+
+        let work = Duration::from_secs(config.pomodoro_work_minutes.saturating_mul(60));
+        let rest = Duration::from_secs(config.pomodoro_break_minutes.saturating_mul(60));
+        self.pomodoro
+            .set_durations(work.max(Duration::from_secs(60)), rest.max(Duration::from_secs(60)));
+        self.break_review = config.pomodoro_break_review.unwrap_or(false);
+        let idle_minutes = config.idle_minutes.unwrap_or(DEFAULT_IDLE_MINUTES).max(1);
+        self.idle_timeout = Duration::from_secs(idle_minutes * 60);
+    }
+
+    /// Applies the first profile that matches this terminal on top of the config, and
+    /// returns its name.
+    pub fn apply_layout_profile(
+        &mut self,
+        profiles: &[LayoutProfile],
+        width: u16,
+        height: u16,
+        hostname: &str,
+    ) -> Option<&str> {
+        let profile = config::pick_layout_profile(profiles, width, height, hostname)?;
+        if let Some(margin) = profile.margin {
+            self.margin = margin.min(MAX_MARGIN);
+        }
+        self.max_text_width = profile.max_width.filter(|w| *w > 0);
+        if let Some(columns) = profile.columns {
+            self.text_columns = columns.clamp(1, MAX_TEXT_COLUMNS);
+        }
+        self.image_max_height_px = profile.image_max_height_px;
+        self.layout_profile = Some(profile.name.clone());
+        self.layout_profile.as_deref()
+    }
+
+    pub fn cycle_line_focus(&mut self) {
+        self.line_focus = self.line_focus.next();
+        self.status_message = Some(format!("Line focus: {}", self.line_focus.label()));
+    }
+
+    pub fn cycle_focus_ruler(&mut self) {
+        self.focus_ruler = self.focus_ruler.next();
+        self.status_message = Some(format!("Focus ruler: {}", self.focus_ruler.label()));
+    }
+
+    /// Whether the reader draws the line being read in the middle of the screen. Paged
+    /// books and side-by-side columns keep their usual layout.
+    pub fn typewriter_active(&self) -> bool {
+        self.focus_mode
+            && self.typewriter_scrolling
+            && self.view == AppView::Reader
+            && self.text_columns <= 1
+            && !self.current_book.as_ref().is_some_and(|b| b.paged)
+    }
+
+    /// Flows the text into two side-by-side columns, or back into one. Terminals too
+    /// narrow for two keep showing one.
+    pub fn toggle_columns(&mut self) {
+        self.text_columns = if self.text_columns > 1 { 1 } else { 2 };
+        self.status_message = Some(
+            if self.text_columns > 1 {
+                "Two columns"
+            } else {
+                "One column"
+            }
+            .to_string(),
+        );
+    }
+
+    pub fn toggle_justify(&mut self) {
+        self.justify = !self.justify;
+        self.status_message = Some(
+            if self.justify {
+                "Justified text"
+            } else {
+                "Ragged-right text"
+            }
+            .to_string(),
+        );
+    }
+
+    pub fn cycle_line_numbers(&mut self) {
+        self.line_numbers = self.line_numbers.next();
+        self.status_message = Some(format!("Line numbers: {}", self.line_numbers.label()));
+    }
+
+    pub fn cycle_progress_gauge(&mut self) {
+        self.progress_gauge = self.progress_gauge.next();
+        self.status_message = Some(format!("Progress gauge: {}", self.progress_gauge.label()));
+    }
+
+    /// Adds a digit to the count for the next reader key.
+    pub fn push_count_digit(&mut self, digit: u32) {
+        let count = self.key_count.unwrap_or(0) * 10 + digit as usize;
+        self.key_count = Some(count.min(MAX_KEY_COUNT));
+    }
+
+    /// Moves to the top of the chapter, like vim's `gg`.
+    pub fn go_to_chapter_start(&mut self) -> Result<()> {
+        self.record_jump();
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        book.current_line = 0;
+        book.viewport_top = 0;
+        book.word_index = 0;
+        book.selection_anchor = None;
+        self.save_progress()
+    }
+
+    /// Moves to the last line of the chapter, like vim's `G`, with the screen (or in a
+    /// paged book, the page) ending there.
+    pub fn go_to_chapter_end(&mut self, rows: usize) -> Result<()> {
+        self.record_jump();
+        let layout = self.page_layout(rows);
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        book.chapter_content.append(&mut book.pending_lines);
+        let last = book.chapter_content.len().saturating_sub(1);
+        book.viewport_top = if book.paged {
+            Self::page_starts(book, layout).last().copied().unwrap_or(0)
+        } else {
+            // The cursor keeps two rows clear of the bottom, as when moving down.
+            (last + 3).saturating_sub(rows)
+        };
+        book.current_line = last;
+        book.word_index = 0;
+        book.selection_anchor = None;
+        self.mark_finished_if_at_end();
+        self.save_progress()
+    }
+
+    /// Jumps `percent` of the way through the book. Once the indexer has counted each
+    /// chapter's words the jump lands on the word that far in; until then, and in books
+    /// whose chapters are pages, it lands on the chapter that far in.
+    pub fn go_to_percent(&mut self, percent: f64) -> Result<()> {
+        self.record_jump();
+        let Some(ref book) = self.current_book else {
+            return Ok(());
+        };
+        let fraction = percent.clamp(0.0, 100.0) / 100.0;
+        let chapters = book.parser.get_chapter_count();
+        let total: usize = book.chapter_words.iter().sum();
+        if chapters == 0 {
+            return Ok(());
+        }
+        if total == 0 || book.parser.is_paged() {
+            let chapter = ((fraction * chapters as f64) as usize).min(chapters - 1);
+            return self.go_to_locator(&Locator::new(chapter, 0));
+        }
+        let mut target = ((fraction * total as f64) as usize).min(total - 1);
+        let mut chapter = 0;
+        for words in &book.chapter_words {
+            if target < *words {
+                break;
+            }
+            target -= words;
+            chapter += 1;
+        }
+        self.go_to_locator(&Locator::new(chapter.min(chapters - 1), 0))?;
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        let mut texts = Self::line_texts(&book.chapter_content);
+        texts.extend(Self::line_texts(&book.pending_lines));
+        let (mut line, mut word) = (0, target);
+        while line < texts.len() {
+            let count = texts[line].split_whitespace().count();
+            if word < count {
+                break;
+            }
+            word -= count;
+            line += 1;
+        }
+        if line == texts.len() {
+            (line, word) = (line.saturating_sub(1), 0);
+        }
+        let locator = Locator::from_position(book.current_chapter, &texts, line, word);
+        self.go_to_locator(&locator)
+    }
+
+    pub fn open_goto_prompt(&mut self) {
+        if self.current_book.is_some() {
+            self.goto_input = Some(String::new());
+        }
+    }
+
+    /// Goes where the go-to prompt says: `40%` of the way through, or chapter `12`.
+    pub fn confirm_goto(&mut self) -> Result<()> {
+        let Some(input) = self.goto_input.take() else {
+            return Ok(());
+        };
+        let chapters = self
+            .current_book
+            .as_ref()
+            .map_or(0, |b| b.parser.get_chapter_count());
+        match GoTo::parse(&input) {
+            Some(GoTo::Percent(percent)) => self.go_to_percent(percent),
+            Some(GoTo::Chapter(n)) if n <= chapters => {
+                self.record_jump();
+                self.go_to_locator(&Locator::new(n - 1, 0))
+            }
+            Some(GoTo::Chapter(_)) => {
+                self.status_message = Some(format!("This book has {} chapters", chapters));
+                Ok(())
+            }
+            None => {
+                self.status_message =
+                    Some("Go to a chapter number or a percentage, like 12 or 40%".to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Columns the line-number gutter takes from the reader text: room for the open
+    /// chapter's highest line number and a space, or nothing while numbers are off.
+    pub fn line_number_width(&self) -> u16 {
+        if self.line_numbers == LineNumbers::Off {
+            return 0;
+        }
+        let lines = self
+            .current_book
+            .as_ref()
+            .map_or(0, |b| b.chapter_content.len());
+        (lines.max(1).ilog10() as u16 + 1).max(3) + 1
+    }
+
+    /// Line focus strength in percent for the current theme.
+    pub fn line_focus_strength(&self) -> u8 {
+        self.line_focus_intensity
+            .get(self.theme.name())
+            .copied()
+            .unwrap_or_else(|| self.theme.default_line_focus_intensity())
+    }
+
+    pub fn toggle_focus_mode(&mut self) {
+        self.focus_mode = !self.focus_mode;
+    }
+
+    pub fn toggle_pdf_reflow(&mut self) -> Result<()> {
+        if !self.current_book.as_ref().is_some_and(|b| b.parser.is_pdf()) {
+            return Ok(());
+        }
+        self.pdf_reflow = !self.pdf_reflow;
+        if let Some(ref mut book) = self.current_book {
+            book.parser.set_reflow(self.pdf_reflow);
+        }
+        self.refresh_current_book_render_cache()
+    }
+
+    /// Flashes the rest of the chapter one word at a time, from the cursor.
+    pub fn start_rsvp(&mut self) {
+        if self.current_book.is_none() {
+            return;
+        }
+        self.load_rsvp_words();
+        self.rsvp_active = true;
+        self.rsvp_last_tick = Instant::now();
+        self.view = AppView::Rsvp;
+    }
+
+    /// The words of the chapter from the cursor on, with where each one is.
+    fn load_rsvp_words(&mut self) {
+        self.rsvp_words.clear();
+        self.rsvp_positions.clear();
+        self.rsvp_index = 0;
+        self.rsvp_skipped = 0;
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        book.chapter_content.append(&mut book.pending_lines);
+        let cursor = (book.current_line, book.word_index);
+        let texts = Self::line_texts(&book.chapter_content);
+        for (line, text) in texts.iter().enumerate().skip(cursor.0) {
+            for (word, w) in text.split_whitespace().enumerate() {
+                if (line, word) >= cursor {
+                    self.rsvp_words.push(w.to_string());
+                    self.rsvp_positions.push((line, word));
+                }
+            }
+        }
+        self.rsvp_skipped = texts
+            .iter()
+            .take(cursor.0)
+            .map(|t| t.split_whitespace().count())
+            .sum::<usize>()
+            + cursor.1;
+    }
+
+    pub fn toggle_rsvp(&mut self) {
+        self.rsvp_active = !self.rsvp_active;
+        self.rsvp_last_tick = Instant::now();
+    }
+
+    /// How long the RSVP word showing stays up: one beat at `rsvp_wpm`, held longer at
+    /// the end of a clause, longer again at the end of a sentence, and longest at the end
+    /// of a paragraph, so the pauses fall where they would reading aloud.
+    pub fn rsvp_word_time(&self) -> Duration {
+        let beat = Duration::from_millis(60_000 / self.rsvp_wpm.max(1));
+        let Some(word) = self.rsvp_words.get(self.rsvp_index) else {
+            return beat;
+        };
+        let line = self.rsvp_positions.get(self.rsvp_index).map(|p| p.0);
+        let next_line = self.rsvp_positions.get(self.rsvp_index + 1).map(|p| p.0);
+        // Paragraphs are separated by blank lines, which hold no words.
+        let paragraph_end = match (line, next_line) {
+            (Some(line), Some(next)) => next > line + 1,
+            _ => true,
+        };
+        // Closing quotes and brackets come after the punctuation that counts.
+        let end = word
+            .trim_end_matches(['"', '\'', ')', ']', '\u{201d}', '\u{2019}'])
+            .chars()
+            .last();
+        if paragraph_end {
+            beat * 3
+        } else if matches!(end, Some('.' | '!' | '?' | '\u{2026}')) {
+            beat * 2
+        } else if matches!(end, Some(',' | ';' | ':' | '\u{2013}' | '\u{2014}')) {
+            beat * 3 / 2
+        } else {
+            beat
+        }
+    }
+
+    /// Shows the next RSVP word once the one showing has had its time (see
+    /// [`App::rsvp_word_time`]), going on into the next chapter at the end of this one.
+    /// The reader's cursor follows, so the words count as read.
+    pub fn tick_rsvp(&mut self) {
+        if self.view != AppView::Rsvp || !self.rsvp_active {
+            return;
+        }
+        if self.rsvp_last_tick.elapsed() < self.rsvp_word_time() {
+            return;
+        }
+        self.rsvp_last_tick = Instant::now();
+        if self.rsvp_index + 1 < self.rsvp_words.len() {
+            self.rsvp_index += 1;
+        } else {
+            let last_chapter = self
+                .current_book
+                .as_ref()
+                .is_none_or(|b| b.current_chapter + 1 >= b.parser.get_chapter_count());
+            if last_chapter {
+                self.rsvp_active = false;
+                self.status_message = Some("End of the book".to_string());
+                return;
+            }
+            if self.next_chapter().is_err() {
+                self.rsvp_active = false;
+                return;
+            }
+            self.load_rsvp_words();
+        }
+        self.follow_rsvp();
+        self.count_words_read();
+        self.note_reading_activity();
+    }
+
+    /// Puts the reader's cursor on the RSVP word showing.
+    fn follow_rsvp(&mut self) {
+        let Some(&(line, word)) = self.rsvp_positions.get(self.rsvp_index) else {
+            return;
+        };
+        if let Some(ref mut book) = self.current_book {
+            book.current_line = line;
+            book.word_index = word;
+            book.selection_anchor = None;
+        }
+    }
+
+    /// Leaves RSVP for the reader, with the cursor on the word it stopped at.
+    pub fn stop_rsvp(&mut self) {
+        self.rsvp_active = false;
+        self.follow_rsvp();
+        if let Some(ref mut book) = self.current_book {
+            book.viewport_top = book.current_line;
+        }
+        self.view = AppView::Reader;
+        self.count_words_read();
+        self.save_progress().ok();
+    }
+
+    pub fn pomodoro_toggle(&mut self) {
+        self.pomodoro.toggle();
+    }
+
+    pub fn pomodoro_reset(&mut self) {
+        self.pomodoro.reset();
+    }
+
+    pub fn pomodoro_skip_break(&mut self) {
+        self.pomodoro.skip_break();
+    }
+
+    /// Opens a break review when a break starts while reading, and closes it when the
+    /// break is over.
+    fn pomodoro_phase_changed(&mut self) {
+        if self.pomodoro.is_break {
+            if self.break_review && self.view == AppView::Reader {
+                self.open_break_review().ok();
+            }
+        } else if self.view == AppView::VocabReview && self.vocab_review.on_break {
+            self.view = self.vocab_review_return_view;
+            self.status_message = Some("Break over".to_string());
+        }
+    }
+
+    pub fn tick_timers(&mut self) {
+        let was_break = self.pomodoro.is_break;
+        self.pomodoro.tick();
+        if self.pomodoro.is_break != was_break {
+            self.pomodoro_phase_changed();
+        }
+        self.update_night_tint(chrono::Local::now().hour());
+        if self.idle_pause.is_none() && self.last_input_at.elapsed() >= self.idle_timeout {
+            self.pause_for_idle();
+        }
+        let idle = self
+            .current_book
+            .as_ref()
+            .and_then(|b| b.sitting.as_ref())
+            .and_then(|s| (chrono::Local::now() - s.last_active_at).to_std().ok())
+            .is_some_and(|idle| idle >= self.idle_timeout);
+        if idle {
+            self.end_sitting();
+        }
+    }
+
+    /// Stops counting reading once nothing has been pressed for `idle_timeout`: the
+    /// sitting ends, and auto-scroll, RSVP and a pomodoro focus phase wait for the next
+    /// input.
+    /// A break goes on, being time away anyway.
+    pub fn pause_for_idle(&mut self) {
+        self.end_sitting();
+        let pause = IdlePause {
+            last_input_at: self.last_input_at,
+            pomodoro: self.pomodoro.running && !self.pomodoro.is_break,
+            auto_scroll: self.auto_scroll_active,
+            rsvp: self.rsvp_active,
+        };
+        if pause.pomodoro {
+            self.pomodoro.pause();
+        }
+        self.auto_scroll_active = false;
+        self.rsvp_active = false;
+        if self.current_book.is_some() {
+            self.status_message = Some("Idle: reading paused until the next key".to_string());
+        }
+        self.idle_pause = Some(pause);
+    }
+
+    /// Marks a key press or click, resuming whatever going idle paused. The time away
+    /// is taken off this sitting's speed.
+    pub fn note_input(&mut self) {
+        let now = Instant::now();
+        if let Some(pause) = self.idle_pause.take() {
+            let away = now.saturating_duration_since(pause.last_input_at);
+            if let Some(book) = self.current_book.as_mut() {
+                book.start_time += away;
+            }
+            if pause.pomodoro {
+                self.pomodoro.start();
+            }
+            if pause.auto_scroll {
+                self.auto_scroll_active = true;
+                self.auto_scroll_last_tick = now;
+            }
+            if pause.rsvp {
+                self.rsvp_active = true;
+                self.rsvp_last_tick = now;
+            }
+            if self.current_book.is_some() {
+                self.status_message =
+                    Some(format!("Resumed after {} min away", away.as_secs() / 60));
+            }
+        }
+        self.last_input_at = now;
+    }
+
+    /// Turns the warm tint on or off for the hour of the day. Images already shown keep
+    /// their colors until they are next loaded.
+    pub fn update_night_tint(&mut self, hour: u32) {
+        self.night_tint_percent = self
+            .night_tint
+            .filter(|tint| tint.is_active(hour))
+            .map(|tint| tint.strength);
+    }
+
+    /// Marks input in one of the reading views, starting a new sitting if none is open.
+    pub fn note_reading_activity(&mut self) {
+        let reading = matches!(
+            self.view,
+            AppView::Reader | AppView::Search | AppView::Rsvp | AppView::Select | AppView::Visual
+        );
+        let Some(book) = self.current_book.as_mut().filter(|_| reading) else {
+            return;
+        };
+        let now = chrono::Local::now();
+        match book.sitting.as_mut() {
+            Some(sitting) => sitting.last_active_at = now,
+            None => {
+                book.sitting = Some(Sitting {
+                    started_at: now,
+                    last_active_at: now,
+                    words_at_start: book.words_read,
+                })
+            }
+        }
+    }
+
+    /// Closes the open sitting, if any, and records it.
+    pub fn end_sitting(&mut self) {
+        let Some(book) = self.current_book.as_mut() else {
+            return;
+        };
+        let Some(sitting) = book.sitting.take() else {
+            return;
+        };
+        let words = book.words_read.saturating_sub(sitting.words_at_start);
+        book.session.secs += (sitting.last_active_at - sitting.started_at)
+            .num_seconds()
+            .max(0) as u64;
+        // A lone key press that read nothing is not a sitting.
+        if !self.read_only && (sitting.last_active_at > sitting.started_at || words > 0) {
+            self.db
+                .log_sitting(book.id, sitting.started_at, sitting.last_active_at, words)
+                .ok();
+        }
+    }
+
+    pub fn pomodoro_label(&self) -> Option<String> {
+        if !self.pomodoro.running && !self.focus_mode {
+            return None;
+        }
+        let remaining = self.pomodoro.remaining_time();
+        let mins = remaining.as_secs() / 60;
+        let secs = remaining.as_secs() % 60;
+        let phase = if self.pomodoro.is_break { "Break" } else { "Focus" };
+        let status = if self.pomodoro.running { "" } else { " (paused)" };
+        Some(format!("{} {:02}:{:02}{}", phase, mins, secs, status))
+    }
+
+    /// Stores the list selection of the current view so reopening it restores the position.
+    pub fn remember_selection(&mut self) {
+        let index = match self.view {
+            AppView::Toc => self.selected_toc_index,
+            AppView::Figures => self.selected_figure_index,
+            AppView::LastStops => self.selected_last_stop_index,
+            AppView::AnnotationList => self.selected_annotation_index,
+            AppView::Vocabulary => self.selected_vocab_index,
+            AppView::GlobalSearch => self.selected_search_index,
+            _ => return,
+        };
+        let key = self.selection_key(self.view);
+        self.view_selections.insert(key, index);
+    }
+
+    fn recall_selection(&self, view: AppView, len: usize) -> Option<usize> {
+        self.view_selections
+            .get(&self.selection_key(view))
+            .copied()
+            .filter(|_| len > 0)
+            .map(|i| i.min(len - 1))
+    }
+
+    fn selection_key(&self, view: AppView) -> (Option<i32>, AppView) {
+        // Vocabulary and global search span the whole library, so they aren't per-book.
+        let book_id = match view {
+            AppView::Toc | AppView::Figures | AppView::LastStops | AppView::AnnotationList => {
+                self.current_book.as_ref().map(|b| b.id)
+            }
+            _ => None,
+        };
+        (book_id, view)
+    }
+
+    pub fn open_global_search(&mut self) {
+        self.selected_search_index = self
+            .recall_selection(AppView::GlobalSearch, self.global_search_results.len())
+            .unwrap_or(0);
+        self.search_history = self.db.get_search_history().unwrap_or_default();
+        self.search_history_index = None;
+        self.saved_search_name = None;
+        self.view = AppView::GlobalSearch;
+    }
+
+    /// Opens the search across the open book, starting from the in-book query.
+    pub fn open_book_search(&mut self) {
+        if self.current_book.is_none() {
+            return;
+        }
+        if self.book_search_query.is_empty() {
+            self.book_search_query = self.search_query.trim().to_string();
+            self.book_search_results.clear();
+        }
+        self.view = AppView::BookSearch;
+    }
+
+    /// Finds the query in every chapter of the open book, in reading order.
+    pub fn run_book_search(&mut self) -> Result<()> {
+        self.book_search_results.clear();
+        self.selected_book_search_index = 0;
+        let Some(pattern) =
+            SearchPattern::new(&self.book_search_query, self.search_case_sensitive)
+        else {
+            return Ok(());
+        };
+        let light_mode = self.light_mode;
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        for chapter in 0..book.parser.get_chapter_count() {
+            // The same text lines the reader makes of the chapter, less the image rows,
+            // which hold no words and so don't move a locator.
+            let mut lines = Vec::new();
+            for item in book.chapter(chapter)? {
+                match item {
+                    PageContent::Text(text) => {
+                        for line in text.lines() {
+                            for piece in split_long_line(line) {
+                                lines.push(RenderLine::Text(piece.to_string()));
+                            }
+                        }
+                    }
+                    PageContent::Caption(caption) => lines.push(RenderLine::Caption(caption)),
+                    PageContent::Image(_) if light_mode => {
+                        lines.push(RenderLine::Text(IMAGE_PLACEHOLDER.to_string()));
+                    }
+                    PageContent::Image(_) => {}
+                }
+            }
+            let texts = Self::line_texts(&lines);
+            let chapter_title = texts
+                .iter()
+                .map(|t| t.trim().trim_start_matches('#').trim())
+                .find(|t| !t.is_empty())
+                .unwrap_or_default()
+                .to_string();
+            for m in find_matches(&lines, &pattern) {
+                if self.book_search_results.len() == BOOK_SEARCH_LIMIT {
+                    return Ok(());
+                }
+                self.book_search_results.push(BookSearchHit {
+                    chapter,
+                    chapter_title: chapter_title.clone(),
+                    locator: Locator::from_position(chapter, &texts, m.line, m.start_word),
+                    snippet: match_context(texts[m.line], &m),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Jumps to the selected whole-book match, which then stays highlighted in the reader
+    /// so n / N carry on from it.
+    pub fn open_book_search_hit(&mut self) -> Result<()> {
+        let Some(hit) = self
+            .book_search_results
+            .get(self.selected_book_search_index)
+            .cloned()
+        else {
+            return Ok(());
+        };
+        self.search_query = self.book_search_query.trim().to_string();
+        self.view = AppView::Reader;
+        self.record_jump();
+        self.go_to_locator(&hit.locator)
+    }
+
+    /// Opens the search across books, notes, words, and text, keeping the last query and
+    /// its results.
+    pub fn open_omnibox(&mut self) {
+        if self.view != AppView::Omnibox {
+            self.omnibox_return_view = self.view;
+        }
+        self.view = AppView::Omnibox;
+    }
+
+    pub fn run_omnibox_search(&mut self) -> Result<()> {
+        self.omnibox_results = omnibox::search(&self.db, &self.omnibox_query)?;
+        self.selected_omnibox_index = 0;
+        Ok(())
+    }
+
+    /// Goes to the selected omnibox result: a book opens where it was left, a note or a
+    /// passage opens its book there, and a word opens the book it was looked up in at
+    /// the sentence around it.
+    pub fn open_omnibox_hit(&mut self) -> Result<()> {
+        let Some(hit) = self
+            .omnibox_results
+            .get(self.selected_omnibox_index)
+            .cloned()
+        else {
+            return Ok(());
+        };
+        match hit {
+            OmniboxHit::Book(book) => {
+                if self.current_book.as_ref().map(|b| b.id) == Some(book.id) {
+                    self.view = AppView::Reader;
+                } else if self.select_book_by_id(book.id)? {
+                    self.save_progress().ok();
+                    self.open_selected_book()?;
+                }
+                Ok(())
+            }
+            OmniboxHit::Note(_, note) => self.open_note(note),
+            OmniboxHit::Word(word) => match word.source {
+                Some(source) => {
+                    let locator = omnibox::word_locator(&self.db, &source)?;
+                    self.open_book_at(source.book_id, &locator)
+                }
+                None => {
+                    self.status_message =
+                        Some(format!("\"{}\" wasn't looked up in a book", word.word));
+                    Ok(())
+                }
+            },
+            OmniboxHit::Text(hit) => self.open_book_at(hit.book_id, &hit.locator),
+        }
+    }
+
+    /// Opens a book at `locator`, as a jump when the book is already open.
+    fn open_book_at(&mut self, book_id: i32, locator: &Locator) -> Result<()> {
+        if self.current_book.as_ref().map(|b| b.id) == Some(book_id) {
+            self.record_jump();
+        } else {
+            if !self.select_book_by_id(book_id)? {
+                return Ok(());
+            }
+            self.save_progress().ok();
+            self.open_selected_book()?;
+        }
+        self.view = AppView::Reader;
+        self.go_to_locator(locator)
+    }
+
+    /// Starts searching the library for the query, replacing any search still running.
+    /// Hits arrive through [`App::apply_global_search_update`].
+    pub fn run_global_search(&mut self) -> Result<()> {
+        let query = self.global_search_query.trim().to_string();
+        if query.is_empty() {
+            return Ok(());
+        }
+        if !self.read_only {
+            self.db.add_search_history(&query)?;
+        }
+        self.search_history = self.db.get_search_history()?;
+        self.search_history_index = None;
+        self.stop_global_search();
+        self.global_search_results.clear();
+        self.selected_search_index = 0;
+        self.global_search_generation += 1;
+        let request = GlobalSearchRequest {
+            generation: self.global_search_generation,
+            query,
+            case_sensitive: self.search_case_sensitive,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        self.global_search_progress = Some(GlobalSearchProgress {
+            scanned: 0,
+            total: self.books.len(),
+            cancel: request.cancel.clone(),
+        });
+        self.pending_global_search = Some(request);
+        Ok(())
+    }
+
+    /// Hands over the global search waiting to be started, if any.
+    pub fn take_global_search(&mut self) -> Option<GlobalSearchRequest> {
+        self.pending_global_search.take()
+    }
+
+    /// Adds the hits from one more searched book, unless a newer search replaced this one.
+    pub fn apply_global_search_update(&mut self, update: GlobalSearchUpdate) {
+        if update.generation != self.global_search_generation {
+            return;
+        }
+        let Some(ref mut progress) = self.global_search_progress else {
+            return;
+        };
+        progress.scanned = update.scanned;
+        progress.total = update.total;
+        let room = GLOBAL_SEARCH_LIMIT.saturating_sub(self.global_search_results.len());
+        self.global_search_results.extend(update.hits.into_iter().take(room));
+        if let Some(error) = update.error {
+            self.status_message = Some(format!("Search failed: {}", error));
+            self.global_search_progress = None;
+        } else if update.scanned >= update.total {
+            self.global_search_progress = None;
+        }
+    }
+
+    /// Stops the running global search, keeping what it found so far.
+    pub fn cancel_global_search(&mut self) {
+        if let Some(progress) = self.stop_global_search() {
+            self.status_message = Some(format!(
+                "Search stopped after {} of {} books",
+                progress.scanned, progress.total
+            ));
+        }
+    }
+
+    pub(crate) fn stop_global_search(&mut self) -> Option<GlobalSearchProgress> {
+        let progress = self.global_search_progress.take()?;
+        progress.cancel.store(true, Ordering::Relaxed);
+        self.pending_global_search = None;
+        Some(progress)
+    }
+
+    /// Steps back through previous queries (Up in the search input).
+    pub fn search_history_prev(&mut self) {
+        if self.search_history.is_empty() {
+            return;
+        }
+        let next = match self.search_history_index {
+            Some(i) => (i + 1).min(self.search_history.len() - 1),
+            None => 0,
+        };
+        self.search_history_index = Some(next);
+        self.global_search_query = self.search_history[next].clone();
+    }
+
+    /// Steps forward towards the newest query, ending on an empty input.
+    pub fn search_history_next(&mut self) {
+        match self.search_history_index {
+            Some(0) | None => {
+                self.search_history_index = None;
+                self.global_search_query.clear();
+            }
+            Some(i) => {
+                self.search_history_index = Some(i - 1);
+                self.global_search_query = self.search_history[i - 1].clone();
+            }
+        }
+    }
+
+    pub fn save_current_search(&mut self, name: &str) -> Result<()> {
+        let query = self.global_search_query.trim();
+        if name.trim().is_empty() || query.is_empty() {
+            return Ok(());
+        }
+        self.db.save_search(name.trim(), query)?;
+        self.saved_searches = self.db.get_saved_searches()?;
+        Ok(())
+    }
+
+    pub fn open_saved_searches(&mut self) -> Result<()> {
+        self.saved_searches = self.db.get_saved_searches()?;
+        if self.selected_saved_search_index >= self.saved_searches.len() {
+            self.selected_saved_search_index = 0;
+        }
+        self.view = AppView::SavedSearches;
+        Ok(())
+    }
+
+    pub fn run_selected_saved_search(&mut self) -> Result<()> {
+        let Some(saved) = self.saved_searches.get(self.selected_saved_search_index) else {
+            return Ok(());
+        };
+        self.global_search_query = saved.query.clone();
+        self.view = AppView::GlobalSearch;
+        self.run_global_search()
+    }
+
+    pub fn delete_selected_saved_search(&mut self) -> Result<()> {
+        if let Some(saved) = self.saved_searches.get(self.selected_saved_search_index) {
+            self.db.delete_saved_search(saved.id)?;
+            self.saved_searches = self.db.get_saved_searches()?;
+            if self.selected_saved_search_index >= self.saved_searches.len() {
+                self.selected_saved_search_index = self.saved_searches.len().saturating_sub(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn book_paces(db: &Db) -> Result<HashMap<i32, BookPace>> {
+        let today = chrono::Local::now().date_naive();
+        let since = today - chrono::Days::new(PACE_DAYS - 1);
+        Ok(db
+            .get_book_paces(since)?
+            .iter()
+            .filter_map(|r| Some((r.book_id, BookPace::from_record(r, today)?)))
+            .collect())
+    }
+
+    /// Days since a book being read was last read, once it has stalled.
+    pub fn stalled_days(&self, book: &BookRecord) -> Option<i64> {
+        if ReadingStatus::from_str(&book.status) != ReadingStatus::Reading {
+            return None;
+        }
+        let today = chrono::Local::now().date_naive();
+        self.book_paces.get(&book.id)?.stalled_days(today)
+    }
+
+    pub fn refresh_library(&mut self) -> Result<()> {
+        self.books = self.db.get_books()?;
+        if let Some(save) = self.last_progress.take() {
+            for record in &mut self.books {
+                save.apply_to(record);
+            }
+            self.last_progress = Some(save);
+        }
+        self.reading_wpm = self
+            .db
+            .get_average_wpm()?
+            .unwrap_or(DEFAULT_READING_WPM);
+        self.book_paces = Self::book_paces(&self.db)?;
+        if let Some(filter) = self.library_status_filter {
+            self.books.retain(|b| ReadingStatus::from_str(&b.status) == filter);
+        }
+        if self.books.is_empty() {
+            self.selected_book_index = 0;
+            self.current_library_cover = None;
+            self.last_library_selection = None;
+        } else if self.selected_book_index >= self.books.len() {
+            self.selected_book_index = 0;
+        }
+        if !self.books.is_empty() {
+            let valid_ids: HashSet<i32> = self.books.iter().map(|b| b.id).collect();
+            self.cover_cache.retain(|id, _| valid_ids.contains(id));
+            self.cover_missing.retain(|id| valid_ids.contains(id));
+            self.pending_cover_requests.retain(|id| valid_ids.contains(id));
+        }
+        self.refresh_vocab_due();
+        Ok(())
+    }
+
+    /// Scan results grouped by parent directory, skipping the files of collapsed groups.
+    pub fn explorer_rows(&self) -> Vec<ExplorerRow> {
+        let mut groups: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        for path in &self.explorer_results {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            groups.entry(dir).or_default().push(path.clone());
+        }
+
+        let mut rows = Vec::new();
+        for (dir, mut files) in groups {
+            files.sort();
+            let collapsed = self.explorer_collapsed.contains(&dir);
+            let visible = if collapsed { Vec::new() } else { files.clone() };
+            rows.push(ExplorerRow::Group {
+                dir,
+                files,
+                collapsed,
+            });
+            rows.extend(visible.into_iter().map(ExplorerRow::File));
+        }
+        rows
+    }
+
+    /// Directory header text relative to the scanned path.
+    pub fn explorer_group_label(&self, dir: &Path) -> String {
+        let root = Path::new(&self.explorer_path);
+        match dir.strip_prefix(root) {
+            Ok(rel) if rel.as_os_str().is_empty() => "./".to_string(),
+            Ok(rel) => format!("{}/", rel.to_string_lossy()),
+            Err(_) => format!("{}/", dir.to_string_lossy()),
+        }
+    }
+
+    pub fn move_explorer_cursor(&mut self, down: bool) {
+        let len = self.explorer_rows().len();
+        if len == 0 {
+            return;
+        }
+        self.selected_explorer_index = if down {
+            (self.selected_explorer_index + 1) % len
+        } else if self.selected_explorer_index > 0 {
+            self.selected_explorer_index - 1
+        } else {
+            len - 1
+        };
+    }
+
+    /// Collapses or expands the group under the cursor, leaving the cursor on its header.
+    pub fn set_explorer_group_collapsed(&mut self, collapsed: bool) {
+        let rows = self.explorer_rows();
+        let Some(row) = rows.get(self.selected_explorer_index) else {
+            return;
+        };
+        let dir = match row {
+            ExplorerRow::Group { dir, .. } => dir.clone(),
+            ExplorerRow::File(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        if collapsed {
+            self.explorer_collapsed.insert(dir.clone());
+        } else {
+            self.explorer_collapsed.remove(&dir);
+        }
+        if let Some(idx) = self
+            .explorer_rows()
+            .iter()
+            .position(|r| matches!(r, ExplorerRow::Group { dir: d, .. } if *d == dir))
+        {
+            self.selected_explorer_index = idx;
+        }
+    }
+
+    pub fn toggle_explorer_group(&mut self) {
+        let collapsed = matches!(
+            self.explorer_rows().get(self.selected_explorer_index),
+            Some(ExplorerRow::Group { collapsed: true, .. })
+        );
+        self.set_explorer_group_collapsed(!collapsed);
+    }
+
+    /// Toggles the file under the cursor, or every file of a group when on its header.
+    pub fn toggle_explorer_selection(&mut self) {
+        match self.explorer_rows().into_iter().nth(self.selected_explorer_index) {
+            Some(ExplorerRow::File(path)) => {
+                if !self.explorer_selected.insert(path.clone()) {
+                    self.explorer_selected.remove(&path);
+                }
+            }
+            Some(ExplorerRow::Group { files, .. }) => {
+                if files.iter().all(|f| self.explorer_selected.contains(f)) {
+                    for f in &files {
+                        self.explorer_selected.remove(f);
+                    }
+                } else {
+                    self.explorer_selected.extend(files);
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn select_all_explorer_results(&mut self) {
+        self.explorer_selected = self.explorer_results.iter().cloned().collect();
+    }
+
+    pub fn clear_explorer_selection(&mut self) {
+        self.explorer_selected.clear();
+    }
+
+    /// Imports the selection (or the row under the cursor). Selections containing very large
+    /// files are held back for confirmation instead; returns false in that case.
+    pub fn import_explorer_selection(&mut self) -> Result<bool> {
+        let paths: Vec<PathBuf> = if self.explorer_selected.is_empty() {
+            match self.explorer_rows().into_iter().nth(self.selected_explorer_index) {
+                Some(ExplorerRow::File(path)) => vec![path],
+                Some(ExplorerRow::Group { files, .. }) => files,
+                None => Vec::new(),
+            }
+        } else {
+            self.explorer_selected.iter().cloned().collect()
+        };
+
+        if paths
+            .iter()
+            .any(|p| self.import_rules.is_large(self.explorer_size(p)))
+        {
+            self.pending_large_import = Some(paths);
+            return Ok(false);
+        }
+
+        self.import_paths(&paths)?;
+        self.clear_explorer_selection();
+        Ok(true)
+    }
+
+    pub fn confirm_large_import(&mut self) -> Result<()> {
+        if let Some(paths) = self.pending_large_import.take() {
+            self.import_paths(&paths)?;
+            self.clear_explorer_selection();
+        }
+        Ok(())
+    }
+
+    /// Shows the books a scan found in the explorer. Scanning a single file selects it.
+    pub fn apply_scan_result(&mut self, results: library::ScanResult) {
+        self.explorer_results = results.paths;
+        self.explorer_sizes = results.sizes;
+        self.explorer_skipped = results.skipped;
+        self.pending_large_import = None;
+        self.is_scanning = false;
+        self.selected_explorer_index = 0;
+        self.explorer_selected.clear();
+        self.explorer_collapsed.clear();
+        if Path::new(&self.explorer_path).is_file() && self.explorer_results.len() == 1 {
+            self.explorer_selected.insert(self.explorer_results[0].clone());
+        }
+    }
+
+    pub fn explorer_size(&self, path: &Path) -> u64 {
+        self.explorer_sizes.get(path).copied().unwrap_or(0)
+    }
+
+    pub fn import_paths(&mut self, paths: &[PathBuf]) -> Result<usize> {
+        let mut imported = 0;
+        for path in paths {
+            if !library::is_importable(path) {
+                continue;
+            }
+            let path_str = path.to_string_lossy().to_string();
+            let Ok(parser) = BookParser::open(&path_str) else {
+                continue;
+            };
+            library::register_book(&self.db, &path_str, &parser)?;
+            imported += 1;
+        }
+        Ok(imported)
+    }
+
+    pub fn refresh_current_book_render_cache(&mut self) -> Result<()> {
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+
+        // Line numbers can change with the new layout; the locator finds the same words.
+        let locator = Self::locator_at(book, book.current_line, book.word_index);
+        let chapter_idx = book.current_chapter;
+        let content = book.chapter(chapter_idx)?;
+        let (chapter_content, image_protocols) = Self::flatten_content(
+            &mut self.image_picker,
+            content,
+            self.reader_text_width,
+            self.image_max_height_px,
+            !self.light_mode,
+            self.night_tint_percent,
+        );
+
+        book.chapter_annotations =
+            Self::load_chapter_annotations(&self.db, book.id, chapter_idx, &chapter_content)?;
+        let mut chapter_content = chapter_content;
+        book.pending_lines = Self::cap_chapter(&mut chapter_content);
+        book.chapter_content = chapter_content;
+        book.image_protocols = image_protocols;
+        Self::place_cursor(book, &locator);
+        Ok(())
+    }
+
+    /// The book the preview panel describes: the selected one in the library, or the
+    /// one holding the selected hit in global search, even if the library filter hides
+    /// it.
+    pub fn previewed_book(&self) -> Option<BookRecord> {
+        match self.view {
+            AppView::GlobalSearch => {
+                let book_id = self
+                    .global_search_results
+                    .get(self.selected_search_index)?
+                    .book_id;
+                match self.books.iter().find(|b| b.id == book_id) {
+                    Some(book) => Some(book.clone()),
+                    None => self.db.get_book(book_id).ok().flatten(),
+                }
+            }
+            _ => self.books.get(self.selected_book_index).cloned(),
+        }
+    }
+
+    pub fn cover_request_for_selected(&mut self) -> Option<CoverRequest> {
+        let book_record = self.previewed_book().filter(|_| !self.light_mode);
+        let Some(book_record) = book_record else {
+            self.current_library_cover = None;
+            return None;
+        };
+        let book_id = book_record.id;
+
+        if self.last_library_selection != Some(book_id) {
+            self.last_library_selection = Some(book_id);
+            self.current_library_cover = None;
+        }
+
+        if let Some(image) = self.cover_cache.get(&book_id) {
+            let image = tinted(image.as_ref().clone(), self.night_tint_percent);
+            self.current_library_cover = Some(self.image_picker.new_resize_protocol(image));
+            return None;
+        }
+
+        if self.cover_missing.contains(&book_id) {
+            return None;
+        }
+
+        if self.pending_cover_requests.contains(&book_id) {
+            return None;
+        }
+
+        Some(CoverRequest {
+            book_id,
+            path: book_record.path,
+            max_dimension: self.cover_max_dimension,
+        })
+    }
+
+    /// Covers to load ahead for the books within `cover_prefetch` places of the
+    /// selection, nearest first, so they show at once when the selection gets there.
+    /// Each is marked as on its way.
+    pub fn cover_prefetch_requests(&mut self) -> Vec<CoverRequest> {
+        if self.light_mode || self.cover_prefetch == 0 {
+            return Vec::new();
+        }
+        // Neighbors in the list on screen: books in the library, hits in global search.
+        let (selected, ids): (usize, Vec<i32>) = match self.view {
+            AppView::Library => (
+                self.selected_book_index,
+                self.books.iter().map(|b| b.id).collect(),
+            ),
+            AppView::GlobalSearch => (
+                self.selected_search_index,
+                self.global_search_results.iter().map(|h| h.book_id).collect(),
+            ),
+            _ => return Vec::new(),
+        };
+        let mut requests = Vec::new();
+        for distance in 1..=self.cover_prefetch {
+            let neighbors = [selected.checked_add(distance), selected.checked_sub(distance)];
+            for &book_id in neighbors.iter().flatten().filter_map(|&i| ids.get(i)) {
+                let known = self.cover_cache.contains_key(&book_id)
+                    || self.cover_missing.contains(&book_id)
+                    || self.pending_cover_requests.contains(&book_id);
+                if known {
+                    continue;
+                }
+                let Some(book) = self.books.iter().find(|b| b.id == book_id) else {
+                    continue;
+                };
+                requests.push(CoverRequest {
+                    book_id,
+                    path: book.path.clone(),
+                    max_dimension: self.cover_max_dimension,
+                });
+                self.pending_cover_requests.insert(book_id);
+            }
+        }
+        requests
+    }
+
+    pub fn mark_cover_request_in_flight(&mut self, book_id: i32) {
+        self.pending_cover_requests.insert(book_id);
+    }
+
+    pub fn apply_cover_response(&mut self, response: CoverResponse) {
+        self.pending_cover_requests.remove(&response.book_id);
+
+        let Some(image) = response.image else {
+            self.cover_missing.insert(response.book_id);
+            return;
+        };
+
+        let image = Arc::new(image);
+        self.cover_cache.insert(response.book_id, Arc::clone(&image));
+        self.cover_missing.remove(&response.book_id);
+
+        if self.last_library_selection == Some(response.book_id) {
+            let image = tinted(image.as_ref().clone(), self.night_tint_percent);
+            self.current_library_cover = Some(self.image_picker.new_resize_protocol(image));
+        }
+    }
+
+    /// The book's cover, shrunk so its longer side is at most `max_dimension` pixels.
+    pub fn load_cover_image(path: &str, max_dimension: u32) -> Option<image::DynamicImage> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".epub") {
+            let mut epub = EpubParser::new(path).ok()?;
+            let cover = epub.get_cover_best_effort()?;
+            return Some(Self::downscale_cover(cover, max_dimension));
+        }
+
+        if lower.ends_with(".pdf") {
+            let pdf = PdfParser::new(path).ok()?;
+            let cover = pdf.get_cover_image_preview().ok()?;
+            return Some(Self::downscale_cover(cover, max_dimension));
+        }
+
+        if lower.ends_with(".djvu") || lower.ends_with(".djv") {
+            let djvu = DjvuParser::new(path).ok()?;
+            let cover = djvu.get_cover_image_preview().ok()?;
+            return Some(Self::downscale_cover(cover, max_dimension));
+        }
+
+        None
+    }
+
+    fn downscale_cover(image: image::DynamicImage, max_dimension: u32) -> image::DynamicImage {
+        let max_cover_dim = max_dimension.max(1);
+        let (w, h) = (image.width(), image.height());
+        let max_dim = w.max(h);
+        if max_dim <= max_cover_dim {
+            return image;
+        }
+
+        let scale = max_cover_dim as f32 / max_dim as f32;
+        let new_w = (w as f32 * scale).round().max(1.0) as u32;
+        let new_h = (h as f32 * scale).round().max(1.0) as u32;
+        image.resize(new_w, new_h, FilterType::Triangle)
+    }
+
+    /// Lookup language and dictionary for the open book: its override, then the language
+    /// the book declares, then the config.
+    pub fn lookup_settings(&self) -> LookupSettings {
+        let record = self
+            .current_book
+            .as_ref()
+            .and_then(|book| self.books.iter().find(|b| b.id == book.id));
+        let language = record.and_then(|b| b.language.clone()).or_else(|| {
+            self.current_book
+                .as_ref()
+                .and_then(|book| book.parser.get_language())
+        });
+        LookupSettings::resolve(
+            language.as_deref(),
+            record.and_then(|b| b.dictionary.as_deref()),
+            self.default_dictionary_language.as_deref(),
+            self.default_dictionary.as_deref(),
+        )
+    }
+
+    pub fn open_lookup_settings(&mut self) {
+        let Some(book) = self.books.get(self.selected_book_index) else {
+            return;
+        };
+        self.lookup_language_input = book.language.clone().unwrap_or_default();
+        self.lookup_dictionary_input = book.dictionary.clone().unwrap_or_default();
+        self.lookup_editing_dictionary = false;
+        self.view = AppView::LookupSettings;
+    }
+
+    /// Stores the per-book overrides; empty fields clear them.
+    pub fn save_lookup_settings(&mut self) -> Result<()> {
+        let Some(book) = self.books.get(self.selected_book_index) else {
+            return Ok(());
+        };
+        let language = self.lookup_language_input.trim();
+        let dictionary = self.lookup_dictionary_input.trim();
+        self.db.set_book_lookup(
+            book.id,
+            (!language.is_empty()).then_some(language),
+            (!dictionary.is_empty()).then_some(dictionary),
+        )?;
+        self.refresh_library()
+    }
+
+    /// Cycles the selected book through to-read, reading, finished, and abandoned.
+    pub fn cycle_selected_book_status(&mut self) -> Result<()> {
+        let Some(book) = self.books.get(self.selected_book_index) else {
+            return Ok(());
+        };
+        let (id, status) = (book.id, ReadingStatus::from_str(&book.status).next());
+        self.set_book_status(id, status)
+    }
+
+    pub fn set_book_status(&mut self, book_id: i32, status: ReadingStatus) -> Result<()> {
+        self.db.set_book_status(book_id, status.as_str())?;
+        if let Some(ref mut book) = self.current_book {
+            if book.id == book_id {
+                book.status = status;
+            }
+        }
+        let selected_id = self.books.get(self.selected_book_index).map(|b| b.id);
+        self.refresh_library()?;
+        // Keep the cursor on the same book; it may have been filtered out.
+        if let Some(idx) = selected_id.and_then(|id| self.books.iter().position(|b| b.id == id)) {
+            self.selected_book_index = idx;
+        }
+        Ok(())
+    }
+
+    /// Steps the library filter through every status and back to showing all books.
+    pub fn cycle_library_status_filter(&mut self) -> Result<()> {
+        self.library_status_filter = match self.library_status_filter {
+            None => Some(ReadingStatus::ToRead),
+            Some(ReadingStatus::Abandoned) => None,
+            Some(status) => Some(status.next()),
+        };
+        self.selected_book_index = 0;
+        self.current_library_cover = None;
+        self.last_library_selection = None;
+        self.refresh_library()
+    }
+
+    /// Selects a book in the library list, clearing the status filter if it hides the book.
+    pub fn select_book_by_id(&mut self, book_id: i32) -> Result<bool> {
+        if !self.books.iter().any(|b| b.id == book_id) && self.library_status_filter.is_some() {
+            self.library_status_filter = None;
+            self.refresh_library()?;
+        }
+        match self.books.iter().position(|b| b.id == book_id) {
+            Some(idx) => {
+                self.selected_book_index = idx;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Marks the open book finished once the cursor reaches the end of its last chapter.
+    fn mark_finished_if_at_end(&mut self) {
+        let Some(ref book) = self.current_book else {
+            return;
+        };
+        let last_chapter = book.current_chapter + 1 >= book.parser.get_chapter_count();
+        let last_line =
+            book.current_line + 1 >= book.chapter_content.len() && book.pending_lines.is_empty();
+        if !last_chapter || !last_line || book.status == ReadingStatus::Finished {
+            return;
+        }
+        let book_id = book.id;
+        if self.set_book_status(book_id, ReadingStatus::Finished).is_ok() {
+            self.status_message = Some("Marked as finished".to_string());
+        }
+    }
+
+    /// Shows the stats dashboard; leaving it returns to the current view.
+    pub fn open_stats(&mut self) {
+        if self.view != AppView::Stats {
+            self.stats_return_view = self.view;
+        }
+        self.view = AppView::Stats;
+        self.set_stats_tab(self.stats_tab);
+    }
+
+    /// Switches the chart between days and months, selecting the latest bar.
+    pub fn set_stats_tab(&mut self, tab: StatsTab) {
+        self.stats_tab = tab;
+        self.stats_cursor = None;
+        self.stats_scroll = usize::MAX;
+    }
+
+    /// The chart's bars for the current tab, up to today.
+    pub fn stats_series(&self) -> Vec<StatsBar> {
+        let daily: Vec<_> = self
+            .db
+            .get_daily_reading()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(date, words, secs)| {
+                let day = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()?;
+                Some((day, words, secs))
+            })
+            .collect();
+        stats_bars(&daily, self.stats_tab, chrono::Local::now().date_naive())
+    }
+
+    /// Days with any reading, oldest first.
+    pub fn reading_days(&self) -> Vec<chrono::NaiveDate> {
+        self.db
+            .get_daily_reading()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, words, secs)| *words > 0 || *secs > 0)
+            .filter_map(|(date, _, _)| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok())
+            .collect()
+    }
+
+    /// Moves the chart's selected bar by `delta`, stopping at either end.
+    pub fn move_stats_cursor(&mut self, delta: isize) {
+        let count = self.stats_series().len();
+        if count == 0 {
+            return;
+        }
+        let current = self.stats_cursor.unwrap_or(count - 1).min(count - 1);
+        self.stats_cursor = Some(current.saturating_add_signed(delta).min(count - 1));
+    }
+
+    pub fn toggle_stats_metric(&mut self) {
+        self.stats_metric = match self.stats_metric {
+            StatsMetric::Words => StatsMetric::Minutes,
+            StatsMetric::Minutes => StatsMetric::Words,
+        };
+    }
+
+    pub fn toggle_stats_breakdown(&mut self) {
+        self.stats_breakdown = match self.stats_breakdown {
+            StatsBreakdown::Tag => StatsBreakdown::Author,
+            StatsBreakdown::Author => StatsBreakdown::Tag,
+        };
+    }
+
+    /// Words and seconds read in the chart's selected day or month by each tag or author,
+    /// most words first. Untagged books are grouped as `untagged`.
+    pub fn stats_breakdown_rows(&self) -> Vec<(String, usize, u64)> {
+        let bars = self.stats_series();
+        let Some(bar) = self.stats_cursor.and_then(|i| bars.get(i)).or(bars.last()) else {
+            return Vec::new();
+        };
+        let to = match self.stats_tab {
+            StatsTab::Days => bar.start,
+            StatsTab::Months => bar
+                .start
+                .checked_add_months(chrono::Months::new(1))
+                .and_then(|next| next.pred_opt())
+                .unwrap_or(bar.start),
+        };
+        let rows = match self.stats_breakdown {
+            StatsBreakdown::Tag => self.db.get_reading_by_tag(bar.start, to),
+            StatsBreakdown::Author => self.db.get_reading_by_author(bar.start, to),
+        };
+        rows.unwrap_or_default()
+            .into_iter()
+            .map(|(name, words, secs)| {
+                let name = match (self.stats_breakdown, name.trim()) {
+                    (StatsBreakdown::Tag, "") => "untagged".to_string(),
+                    (StatsBreakdown::Tag, tag) => format!("#{}", tag),
+                    (StatsBreakdown::Author, "") => "Unknown author".to_string(),
+                    (StatsBreakdown::Author, author) => author.to_string(),
+                };
+                (name, words, secs)
+            })
+            .collect()
+    }
+
+    /// Opens the view named by `--view` at startup, on top of the library.
+    pub fn open_start_view(&mut self, name: &str) -> Result<()> {
+        match name {
+            "library" => {}
+            "stats" => self.open_stats(),
+            "vocab" => self.open_vocab_review()?,
+            _ => anyhow::bail!("Unknown view: {} (use library, stats, or vocab)", name),
+        }
+        Ok(())
+    }
+
+    /// Asks for confirmation before the selected book is removed.
+    pub fn request_book_removal(&mut self) {
+        self.pending_book_removal = self.books.get(self.selected_book_index).map(|b| b.id);
+    }
+
+    pub fn confirm_book_removal(&mut self) -> Result<()> {
+        let Some(book_id) = self.pending_book_removal.take() else {
+            return Ok(());
+        };
+        self.db.remove_book(book_id)?;
+        if self.current_book.as_ref().is_some_and(|b| b.id == book_id) {
+            self.current_book = None;
+        }
+        self.cover_cache.remove(&book_id);
+        self.cover_missing.remove(&book_id);
+        self.current_library_cover = None;
+        self.last_library_selection = None;
+        self.refresh_library()
+    }
+
+    /// Points every book in `pending_relinks` at its new file.
+    pub fn confirm_relinks(&mut self) -> Result<()> {
+        let relinks = std::mem::take(&mut self.pending_relinks);
+        let mut relinked = 0;
+        for relink in &relinks {
+            if library::relink_book(&self.db, relink.book_id, &relink.new_path).is_ok() {
+                self.cover_missing.remove(&relink.book_id);
+                relinked += 1;
+            }
+        }
+        self.status_message = Some(format!("Relinked {} of {} books", relinked, relinks.len()));
+        self.last_library_selection = None;
+        self.refresh_library()
+    }
+
+    pub fn open_selected_book(&mut self) -> Result<()> {
+        if self.books.is_empty() {
+            return Ok(());
+        }
+        let book_record = self.books[self.selected_book_index].clone();
+        self.load_book(book_record)
+    }
+
+    pub fn load_book(&mut self, mut book_record: BookRecord) -> Result<()> {
+        self.leave_book();
+        self.search_query.clear();
+        self.book_search_query.clear();
+        self.book_search_results.clear();
+        // Positions were recorded against the old file, so find the notes again first.
+        // That is saved work, left to a session that can save.
+        let mut changed = None;
+        if !self.read_only && library::has_changed(&book_record) {
+            // Reanchoring rewrites the saved position, so anything queued goes first.
+            self.flush_writes();
+            if self.last_progress.as_ref().is_some_and(|p| p.book_id == book_record.id) {
+                self.last_progress = None;
+            }
+            let report = library::reanchor_book(&self.db, &book_record)?;
+            changed = Some(Self::describe_reanchor(&book_record.title, &report));
+            if let Some(fresh) = self.db.get_book(book_record.id)? {
+                book_record = fresh;
+            }
+            self.refresh_book(book_record.id);
+        }
+        let mut status = ReadingStatus::from_str(&book_record.status);
+        if status == ReadingStatus::ToRead && !self.read_only {
+            status = ReadingStatus::Reading;
+            self.db.set_book_status(book_record.id, status.as_str())?;
+        }
+        let preset = book_record
+            .preset
+            .as_deref()
+            .and_then(ReadingPreset::from_str);
+        self.apply_preset_layout(preset);
+        let mut parser = BookParser::open(&book_record.path)?;
+        parser.set_reflow(self.pdf_reflow);
+        let text_filters: Vec<TextFilter> = self
+            .text_filters
+            .iter()
+            .filter(|f| f.applies_to(&book_record.title, &book_record.path))
+            .cloned()
+            .collect();
+
+        let content = filter::apply(
+            &text_filters,
+            parser.get_chapter_content(book_record.current_chapter)?,
+        );
+        let (mut chapter_content, image_protocols) = Self::flatten_content(
+            &mut self.image_picker,
+            content,
+            self.reader_text_width,
+            self.image_max_height_px,
+            !self.light_mode,
+            self.night_tint_percent,
+        );
+
+        let chapter_annotations = Self::load_chapter_annotations(
+            &self.db,
+            book_record.id,
+            book_record.current_chapter,
+            &chapter_content,
+        )?;
+        let pending_lines = Self::cap_chapter(&mut chapter_content);
+
+        let mut book = LoadedBook {
+            id: book_record.id,
+            parser,
+            path: book_record.path.clone(),
+            current_chapter: book_record.current_chapter,
+            current_line: book_record.current_line,
+            viewport_top: book_record.current_line,
+            chapter_content,
+            pending_lines,
+            image_protocols,
+            word_index: 0,
+            selection_anchor: None,
+            chapter_annotations,
+            start_time: Instant::now(),
+            words_read: 0,
+            session_words_logged: 0,
+            read_mark: None,
+            sitting: None,
+            session: BookSession {
+                opened_at: chrono::Local::now(),
+                secs: 0,
+                words_at_start: 0,
+                annotations_at_start: self
+                    .db
+                    .get_annotations(book_record.id)
+                    .map_or(0, |a| a.len()),
+            },
+            chapter_words: self.db.get_chapter_words(book_record.id).unwrap_or_default(),
+            paragraph_spacing: book_record.paragraph_spacing,
+            first_line_indent: book_record.first_line_indent,
+            status,
+            paged: book_record.paged,
+            preset,
+            page_cache: None,
+            search_cache: None,
+            jumps_back: Vec::new(),
+            jumps_forward: Vec::new(),
+            text_filters,
+        };
+        // The locator survives changes to wrapping or rendering; the stored line may not.
+        if let Some(ref locator) = book_record.locator {
+            if locator.spine == book.current_chapter {
+                Self::place_cursor(&mut book, locator);
+            }
+        }
+        self.current_book = Some(book);
+        let locator = book_record.locator.as_ref().map(|l| l.to_string());
+        self.db
+            .update_progress(
+                &book_record.path,
+                book_record.current_chapter,
+                book_record.current_line,
+                0,
+                locator.as_deref(),
+            )
+            .ok();
+        self.view = AppView::Reader;
+        if changed.is_some() {
+            self.status_message = changed;
+        }
+        self.sync_document = None;
+        self.sync_conflict = None;
+        self.sync_dirty = false;
+        self.sync_state = None;
+        if self.kosync.is_enabled() {
+            if let Ok(document) = koreader::partial_md5(Path::new(&book_record.path)) {
+                self.sync_pull = Some(document.clone());
+                self.sync_document = Some(document);
+                self.sync_state = Some(SyncState::Checking);
+            }
+        }
+        Ok(())
+    }
+
+    /// The open book's position for the sync server, if it moved since the last push.
+    /// Nothing is sent while the server's position is unknown or the user hasn't picked
+    /// between it and ours, so neither side is overwritten unasked.
+    pub fn take_sync_push(&mut self) -> Option<kosync::Position> {
+        let held = matches!(
+            self.sync_state,
+            Some(SyncState::Checking) | Some(SyncState::Paused)
+        );
+        if !self.sync_dirty || held || self.sync_conflict.is_some() {
+            return None;
+        }
+        self.sync_dirty = false;
+        self.sync_push_now = false;
+        let document = self.sync_document.clone()?;
+        let book = self.current_book.as_mut()?;
+        let (line, word) = (book.current_line, book.word_index);
+        let locator = Self::locator_at(book, line, word);
+        let progress = koreader::progress_pointer(&mut book.parser, &locator);
+        let percentage = self
+            .books
+            .iter()
+            .find(|b| b.id == book.id)
+            .map_or(0.0, |b| b.progress());
+        self.sync_state = Some(SyncState::Sending);
+        Some(kosync::Position {
+            document,
+            progress,
+            percentage,
+            device: self.kosync.device.clone(),
+            device_id: self.kosync.device_id.clone(),
+        })
+    }
+
+    pub fn apply_sync_push(&mut self, result: Result<(), String>) {
+        if self.sync_state != Some(SyncState::Sending) {
+            return;
+        }
+        self.sync_state = Some(match result {
+            Ok(()) => SyncState::Synced,
+            Err(e) => {
+                self.status_message = Some(format!("Progress sync failed: {}", e));
+                SyncState::Failed
+            }
+        });
+    }
+
+    /// Compares the server's position for a book with ours. When another device left
+    /// it somewhere else, the user is asked which one to keep.
+    pub fn apply_remote_position(
+        &mut self,
+        document: &str,
+        remote: Result<Option<kosync::Position>, String>,
+    ) {
+        // The user may have opened another book while the request was out.
+        if self.sync_document.as_deref() != Some(document) {
+            return;
+        }
+        self.sync_state = Some(SyncState::Synced);
+        let remote = match remote {
+            Ok(Some(remote)) => remote,
+            Ok(None) => return,
+            Err(e) => {
+                self.status_message = Some(format!("Progress sync failed: {}", e));
+                self.sync_state = Some(SyncState::Failed);
+                return;
+            }
+        };
+        let local = self.open_book_progress();
+        if remote.device_id == self.kosync.device_id
+            || (remote.percentage - local).abs() <= SYNC_CONFLICT_MARGIN
+        {
+            return;
+        }
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        let locator = koreader::locator_for_progress(
+            &mut book.parser,
+            &remote.progress,
+            Some(remote.percentage),
+        );
+        self.sync_conflict = Some(SyncConflict { remote, locator });
+    }
+
+    fn open_book_progress(&self) -> f64 {
+        self.current_book
+            .as_ref()
+            .and_then(|book| self.books.iter().find(|b| b.id == book.id))
+            .map_or(0.0, |b| b.progress())
+    }
+
+    /// The chooser shown while this device and another disagree on the position.
+    pub fn sync_conflict_prompt(&self) -> Option<String> {
+        let conflict = self.sync_conflict.as_ref()?;
+        let book = self.current_book.as_ref()?;
+        let unit = if book.parser.is_paged() { "p." } else { "ch." };
+        let remote = &conflict.remote;
+        let device = if remote.device.is_empty() {
+            "other device"
+        } else {
+            &remote.device
+        };
+        let remote_place = match conflict.locator {
+            Some(ref locator) => format!("{} {} ", unit, locator.spine + 1),
+            None => String::new(),
+        };
+        Some(format!(
+            "Local: {} {} ({:.0}%) / {}: {}({:.0}%) — keep [l]ocal or [r]emote? (Esc: later)",
+            unit,
+            book.current_chapter + 1,
+            self.open_book_progress() * 100.0,
+            device,
+            remote_place,
+            remote.percentage * 100.0
+        ))
+    }
+
+    /// Resolves a sync conflict in favor of this device, sending our position now.
+    pub fn keep_local_position(&mut self) {
+        if self.sync_conflict.take().is_some() {
+            self.sync_dirty = true;
+            self.sync_push_now = true;
+        }
+    }
+
+    /// Resolves a sync conflict in favor of the other device, moving there.
+    pub fn keep_remote_position(&mut self) -> Result<()> {
+        let Some(conflict) = self.sync_conflict.take() else {
+            return Ok(());
+        };
+        match conflict.locator {
+            Some(locator) => self.go_to_locator(&locator)?,
+            None => {
+                self.status_message =
+                    Some("Could not find the synced position in this book".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts off a sync conflict; the book isn't synced again until it is reopened.
+    pub fn postpone_sync_conflict(&mut self) {
+        if self.sync_conflict.take().is_some() {
+            self.sync_state = Some(SyncState::Paused);
+        }
+    }
+
+    /// Warning shown when a book opens after its file changed on disk.
+    fn describe_reanchor(title: &str, report: &library::Reanchor) -> String {
+        let mut message = format!("{} changed on disk", title);
+        if report.moved + report.lost == 0 {
+            message.push_str("; notes are where they were");
+        } else {
+            message.push_str(&format!(": {} notes moved with their text", report.moved));
+            if report.lost > 0 {
+                message.push_str(&format!(
+                    ", {} not found (tagged #{})",
+                    report.lost,
+                    library::UNANCHORED_TAG
+                ));
+            }
+        }
+        message
+    }
+
+    pub fn flatten_content(
+        picker: &mut Picker,
+        content: Vec<PageContent>,
+        text_width: u16,
+        image_max_height_px: Option<u32>,
+        show_images: bool,
+        tint: Option<u8>,
+    ) -> (Vec<RenderLine>, Vec<StatefulProtocol>) {
+        let max_rows = match image_max_height_px {
+            Some(px) => {
+                let cell_height = picker.font_size().1.max(1) as u32;
+                ((px / cell_height) as usize).max(MIN_IMAGE_ROWS)
+            }
+            None => DEFAULT_MAX_IMAGE_ROWS,
+        };
+        let mut lines = Vec::new();
+        let mut protocols = Vec::new();
+        for item in content {
+            match item {
+                PageContent::Text(s) => {
+                    for line in s.lines() {
+                        for piece in split_long_line(line) {
+                            lines.push(RenderLine::Text(piece.to_string()));
+                        }
+                    }
+                }
+                PageContent::Caption(caption) => lines.push(RenderLine::Caption(caption)),
+                // Light mode keeps a marker where the image was.
+                PageContent::Image(_) if !show_images => {
+                    lines.push(RenderLine::Text(IMAGE_PLACEHOLDER.to_string()));
+                }
+                PageContent::Image(img) => {
+                    let height_lines = Self::image_rows(
+                        (img.width(), img.height()),
+                        picker.font_size(),
+                        text_width,
+                        max_rows,
+                    );
+
+                    let dynamic_image = tinted((*img).clone(), tint);
+                    let protocol = picker.new_resize_protocol(dynamic_image);
+                    let protocol_idx = protocols.len();
+                    protocols.push(protocol);
+                    for i in 0..height_lines {
+                        lines.push(RenderLine::Image {
+                            protocol_idx,
+                            row_idx: i,
+                        });
+                    }
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(RenderLine::Text(" [ Empty ] ".to_string()));
+        }
+        (lines, protocols)
+    }
+
+    /// Rows an image takes in a column `text_width` cells wide. Images are shrunk to fit
+    /// the column but never enlarged, as the image widget draws them.
+    fn image_rows(
+        (width, height): (u32, u32),
+        (font_width, font_height): (u16, u16),
+        text_width: u16,
+        max_rows: usize,
+    ) -> usize {
+        let column_px = text_width.max(1) as u64 * font_width.max(1) as u64;
+        let shown_width = (width as u64).min(column_px);
+        let shown_height = height as u64 * shown_width / (width as u64).max(1);
+        (shown_height.div_ceil(font_height.max(1) as u64) as usize).clamp(1, max_rows)
+    }
+
+    /// Text of each rendered line, with image rows as empty lines, for locator math.
+    fn line_texts(lines: &[RenderLine]) -> Vec<&str> {
+        lines
+            .iter()
+            .map(|line| match line {
+                RenderLine::Text(text) => text.as_str(),
+                RenderLine::Image { .. } | RenderLine::Caption(_) => "",
+            })
+            .collect()
+    }
+
+    /// The sentence around the cursor in the open book, with the book and chapter.
+    pub fn vocab_source_at_cursor(&self) -> Option<VocabSource> {
+        let book = self.current_book.as_ref()?;
+        let texts = Self::line_texts(&book.chapter_content);
+        // Paragraphs are runs of text lines; a sentence may be wrapped across them.
+        let filled = |i: usize| texts.get(i).is_some_and(|t| !t.trim().is_empty());
+        let mut first = book.current_line;
+        while first > 0 && filled(first - 1) {
+            first -= 1;
+        }
+        let last = (book.current_line..texts.len())
+            .take_while(|&i| filled(i))
+            .last()?;
+        let words: Vec<&str> = texts[first..=last]
+            .iter()
+            .flat_map(|text| text.split_whitespace())
+            .collect();
+        let before: usize = texts[first..book.current_line]
+            .iter()
+            .map(|text| text.split_whitespace().count())
+            .sum();
+        let sentence = library::sentence_around(&words, before + book.word_index);
+        (!sentence.is_empty()).then_some(VocabSource {
+            book_id: book.id,
+            chapter: book.current_chapter,
+            sentence,
+        })
+    }
+
+    /// Up to `count` words starting at a (line, word) position, across line breaks.
+    fn words_from(texts: &[&str], line: usize, word: usize, count: usize) -> String {
+        texts
+            .iter()
+            .enumerate()
+            .skip(line)
+            .flat_map(|(i, text)| text.split_whitespace().skip(if i == line { word } else { 0 }))
+            .take(count)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Locator for a (line, word) position in the open chapter. The element path is
+    /// found by matching the next few words against the chapter's markup.
+    fn locator_at(book: &mut LoadedBook, line: usize, word: usize) -> Locator {
+        let texts = Self::line_texts(&book.chapter_content);
+        let mut locator = Locator::from_position(book.current_chapter, &texts, line, word);
+        let snippet = Self::words_from(&texts, line, word, 6);
+        if let Some(path) = book.parser.element_path(book.current_chapter, &snippet) {
+            locator.path = path;
+        }
+        locator
+    }
+
+    /// Holds back the lines of a giant chapter past the first [`CHAPTER_LINE_CAP`].
+    fn cap_chapter(lines: &mut Vec<RenderLine>) -> Vec<RenderLine> {
+        if lines.len() > CHAPTER_LINE_CAP {
+            lines.split_off(CHAPTER_LINE_CAP)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Loads held-back lines until `line` is well clear of the end of the loaded ones.
+    fn reveal_line(book: &mut LoadedBook, line: usize) {
+        while !book.pending_lines.is_empty()
+            && line + CHAPTER_LINE_CAP / 2 >= book.chapter_content.len()
+        {
+            let batch = book.pending_lines.len().min(CHAPTER_LINE_CAP);
+            book.chapter_content.extend(book.pending_lines.drain(..batch));
+        }
+    }
+
+    /// Moves the cursor and viewport to a locator in the currently loaded chapter.
+    fn place_cursor(book: &mut LoadedBook, locator: &Locator) {
+        let mut texts = Self::line_texts(&book.chapter_content);
+        texts.extend(Self::line_texts(&book.pending_lines));
+        let (line, word) = locator.resolve(&texts);
+        Self::reveal_line(book, line);
+        book.current_line = line;
+        book.viewport_top = line;
+        book.word_index = word;
+        book.selection_anchor = None;
+    }
+
+    /// Annotations in one chapter, with positions re-resolved from their locators so
+    /// highlights stay on the same words if the chapter renders differently.
+    fn load_chapter_annotations(
+        db: &Db,
+        book_id: i32,
+        chapter: usize,
+        lines: &[RenderLine],
+    ) -> Result<Vec<AnnotationRecord>> {
+        let texts = Self::line_texts(lines);
+        Ok(db
+            .get_annotations(book_id)?
+            .into_iter()
+            // Notes lost when the book changed would highlight the wrong words.
+            .filter(|a| {
+                a.chapter == chapter && !a.tags.iter().any(|t| t == library::UNANCHORED_TAG)
+            })
+            .map(|mut a| {
+                if let Some(ref locator) = a.start_locator {
+                    (a.start_line, a.start_word) = locator.resolve(&texts);
+                }
+                if let Some(ref locator) = a.end_locator {
+                    (a.end_line, a.end_word) = locator.resolve(&texts);
+                }
+                a
+            })
+            .collect())
+    }
+
+    /// Opens the locator's chapter (if it isn't already showing) and moves the cursor there.
+    pub fn go_to_locator(&mut self, locator: &Locator) -> Result<()> {
+        let needs_load = match self.current_book {
+            Some(ref book) => book.current_chapter != locator.spine,
+            None => return Ok(()),
+        };
+        if needs_load {
+            let content = if let Some(ref mut book) = self.current_book {
+                if locator.spine >= book.parser.get_chapter_count() {
+                    return Ok(());
+                }
+                book.chapter(locator.spine)?
+            } else {
+                return Ok(());
+            };
+            let (mut flattened, protocols) = Self::flatten_content(
+                &mut self.image_picker,
+                content,
+                self.reader_text_width,
+                self.image_max_height_px,
+                !self.light_mode,
+                self.night_tint_percent,
+            );
+            let book_id = self.current_book.as_ref().unwrap().id;
+            let chapter_annotations =
+                Self::load_chapter_annotations(&self.db, book_id, locator.spine, &flattened)?;
+            if let Some(ref mut book) = self.current_book {
+                book.current_chapter = locator.spine;
+                book.pending_lines = Self::cap_chapter(&mut flattened);
+                book.chapter_content = flattened;
+                book.image_protocols = protocols;
+                book.chapter_annotations = chapter_annotations;
+            }
+        }
+        if let Some(ref mut book) = self.current_book {
+            Self::place_cursor(book, locator);
+        }
+        self.save_progress()
+    }
+
+    /// Remembers where the reader is before a jump, so Ctrl-o can come back to it. A new
+    /// jump forgets the places Ctrl-o went back from, as in vim.
+    fn record_jump(&mut self) {
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        let here = Self::jump_position(book);
+        book.jumps_forward.clear();
+        if book.jumps_back.last() != Some(&here) {
+            book.jumps_back.push(here);
+        }
+        if book.jumps_back.len() > MAX_JUMPS {
+            book.jumps_back.remove(0);
+        }
+    }
+
+    fn jump_position(book: &LoadedBook) -> Locator {
+        let texts = Self::line_texts(&book.chapter_content);
+        Locator::from_position(
+            book.current_chapter,
+            &texts,
+            book.current_line,
+            book.word_index,
+        )
+    }
+
+    /// Goes back to where the last jump left from (Ctrl-o), or forward again (Ctrl-i).
+    /// Places the reader is already at are skipped.
+    pub fn follow_jump(&mut self, back: bool) -> Result<()> {
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        let here = Self::jump_position(book);
+        let (from, to) = if back {
+            (&mut book.jumps_back, &mut book.jumps_forward)
+        } else {
+            (&mut book.jumps_forward, &mut book.jumps_back)
+        };
+        let Some(target) = std::iter::from_fn(|| from.pop()).find(|l| *l != here) else {
+            self.status_message = Some(
+                if back {
+                    "No earlier jumps"
+                } else {
+                    "No later jumps"
+                }
+                .to_string(),
+            );
+            return Ok(());
+        };
+        to.push(here);
+        self.go_to_locator(&target)
+    }
+
+    pub fn next_chapter(&mut self) -> Result<()> {
         let (should_update, new_chapter_idx) = if let Some(ref book) = self.current_book {
             if book.current_chapter + 1 < book.parser.get_chapter_count() {
                 (true, book.current_chapter + 1)
             } else {
-                (false, 0)
+                (false, 0)
+            }
+        } else {
+            (false, 0)
+        };
+
+        if should_update {
+            if let Some(ref mut book) = self.current_book {
+                book.current_chapter = new_chapter_idx;
+                book.current_line = 0;
+                book.viewport_top = 0;
+                book.word_index = 0;
+                book.selection_anchor = None;
+            }
+
+            let content = if let Some(ref mut book) = self.current_book {
+                book.chapter(new_chapter_idx)?
+            } else {
+                return Ok(());
+            };
+
+            let (mut flattened, protocols) = Self::flatten_content(
+                &mut self.image_picker,
+                content,
+                self.reader_text_width,
+                self.image_max_height_px,
+                !self.light_mode,
+                self.night_tint_percent,
+            );
+
+            let book_id = self.current_book.as_ref().unwrap().id;
+            let chapter_annotations =
+                Self::load_chapter_annotations(&self.db, book_id, new_chapter_idx, &flattened)?;
+
+            if let Some(ref mut book) = self.current_book {
+                book.pending_lines = Self::cap_chapter(&mut flattened);
+                book.chapter_content = flattened;
+                book.image_protocols = protocols;
+                book.chapter_annotations = chapter_annotations;
+            }
+            self.save_progress()?;
+        }
+        Ok(())
+    }
+
+    pub fn prev_chapter(&mut self) -> Result<()> {
+        let (should_update, new_chapter_idx) = if let Some(ref book) = self.current_book {
+            if book.current_chapter > 0 {
+                (true, book.current_chapter - 1)
+            } else {
+                (false, 0)
+            }
+        } else {
+            (false, 0)
+        };
+
+        if should_update {
+            if let Some(ref mut book) = self.current_book {
+                book.current_chapter = new_chapter_idx;
+                book.current_line = 0;
+                book.viewport_top = 0;
+                book.word_index = 0;
+                book.selection_anchor = None;
+            }
+
+            let content = if let Some(ref mut book) = self.current_book {
+                book.chapter(new_chapter_idx)?
+            } else {
+                return Ok(());
+            };
+
+            let (mut flattened, protocols) = Self::flatten_content(
+                &mut self.image_picker,
+                content,
+                self.reader_text_width,
+                self.image_max_height_px,
+                !self.light_mode,
+                self.night_tint_percent,
+            );
+
+            let book_id = self.current_book.as_ref().unwrap().id;
+            let chapter_annotations =
+                Self::load_chapter_annotations(&self.db, book_id, new_chapter_idx, &flattened)?;
+
+            if let Some(ref mut book) = self.current_book {
+                book.pending_lines = Self::cap_chapter(&mut flattened);
+                book.chapter_content = flattened;
+                book.image_protocols = protocols;
+                book.chapter_annotations = chapter_annotations;
+            }
+            self.save_progress()?;
+        }
+        Ok(())
+    }
+
+    pub fn save_progress(&mut self) -> Result<()> {
+        self.count_words_read();
+        self.mark_finished_if_at_end();
+        if let Some(ref mut book) = self.current_book {
+            let (line, word) = (book.current_line, book.word_index);
+            let save = ProgressSave {
+                book_id: book.id,
+                path: book.path.clone(),
+                chapter: book.current_chapter,
+                line: book.current_line,
+                words_read: book.words_read,
+                locator: Self::locator_at(book, line, word),
+                position_words: Self::position_words(book),
+                session_words: book.words_read.saturating_sub(book.session_words_logged),
+            };
+            book.session_words_logged = book.words_read;
+            // The library shows the new position now; the write itself happens in the
+            // background.
+            for record in &mut self.books {
+                save.apply_to(record);
+            }
+            self.last_progress = Some(save.clone());
+            if !self.read_only {
+                self.writes.push(BackgroundWrite::Progress(save));
+                self.sync_dirty = self.sync_document.is_some();
+            }
+        }
+        Ok(())
+    }
+
+    /// Leaves a last stop where the open book is being closed, unless the reader is at
+    /// the very start or end of the chapter, where there is nothing to find again.
+    pub fn drop_last_stop(&mut self) {
+        if self.read_only {
+            return;
+        }
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        let (line, word) = (book.current_line, book.word_index);
+        let at_start = line == 0 && word == 0;
+        let at_end = line + 1 >= book.chapter_content.len() && book.pending_lines.is_empty();
+        if at_start || at_end {
+            return;
+        }
+        let locator = Self::locator_at(book, line, word);
+        let snippet = Self::words_from(&Self::line_texts(&book.chapter_content), line, word, 10);
+        self.db.add_last_stop(book.id, &locator, &snippet).ok();
+    }
+
+    /// Stops reading the open book for now: leaves a last stop, closes the sitting and
+    /// records the session since the book was opened. Returns its summary, unless
+    /// nothing was read.
+    pub fn leave_book(&mut self) -> Option<SessionSummary> {
+        self.drop_last_stop();
+        self.end_sitting();
+        let book = self.current_book.as_mut()?;
+        let annotations = self.db.get_annotations(book.id).map_or(0, |a| a.len());
+        let now = chrono::Local::now();
+        let stamp =
+            |time: chrono::DateTime<chrono::Local>| time.format("%Y-%m-%d %H:%M:%S").to_string();
+        let session = BookSessionRecord {
+            book_id: book.id,
+            title: self
+                .books
+                .iter()
+                .find(|b| b.id == book.id)
+                .map(|b| b.title.clone()),
+            opened_at: stamp(book.session.opened_at),
+            closed_at: stamp(now),
+            secs: book.session.secs,
+            words: book.words_read.saturating_sub(book.session.words_at_start),
+            annotations: annotations.saturating_sub(book.session.annotations_at_start),
+        };
+        // The book may stay open behind the library; reading it again is a new session.
+        book.session = BookSession {
+            opened_at: now,
+            secs: 0,
+            words_at_start: book.words_read,
+            annotations_at_start: annotations,
+        };
+        if session.secs == 0 && session.words == 0 && session.annotations == 0 {
+            return None;
+        }
+        if !self.read_only {
+            self.db.log_book_session(&session).ok();
+        }
+        let today = now.date_naive();
+        let mut days = self.reading_days();
+        // Today's words may still be on their way to the database.
+        days.push(today);
+        Some(SessionSummary {
+            session,
+            streak: reading_streak(&days, today),
+        })
+    }
+
+    /// Stops saving for good this session. The database is reopened read-only, so
+    /// nothing missed here can change it either.
+    pub fn set_read_only(&mut self) -> Result<()> {
+        self.db = Db::open_read_only(&self.db_path)?;
+        self.read_only = true;
+        self.writes
+            .retain(|write| !matches!(write, BackgroundWrite::Progress(_)));
+        self.sync_dirty = false;
+        Ok(())
+    }
+
+    /// Goes read-only once another session has taken the library over.
+    pub fn check_instance_lock(&mut self) {
+        if self
+            .instance_lock
+            .as_ref()
+            .is_none_or(InstanceLock::is_held)
+        {
+            return;
+        }
+        self.instance_lock = None;
+        self.set_read_only().ok();
+        self.status_message =
+            Some("Another tbook session took over this library; now read-only".to_string());
+    }
+
+    /// Hands over the disk work queued since the last call.
+    pub fn take_writes(&mut self) -> Vec<BackgroundWrite> {
+        std::mem::take(&mut self.writes)
+    }
+
+    /// Shows how a background write went, if there is anything to say.
+    pub fn apply_write_result(&mut self, message: Option<String>) {
+        if message.is_some() {
+            self.status_message = message;
+        }
+    }
+
+    /// Does the queued disk work right away, for callers without a background writer.
+    pub fn flush_writes(&mut self) {
+        for write in self.take_writes() {
+            let message = write.run(&self.db);
+            self.apply_write_result(message);
+        }
+    }
+
+    /// Puts the last saved position over a record read back from the database, which
+    /// may not have it yet.
+    fn overlay_progress(&self, record: &mut BookRecord) {
+        if let Some(ref save) = self.last_progress {
+            save.apply_to(record);
+        }
+    }
+
+    /// Words before the cursor across the whole book, once chapter word counts are known.
+    fn position_words(book: &LoadedBook) -> Option<usize> {
+        if book.chapter_words.is_empty() {
+            return None;
+        }
+        Some(Self::reading_position(book))
+    }
+
+    /// Words before the cursor: across the book when chapter word counts are known, and
+    /// within the chapter until then.
+    fn reading_position(book: &LoadedBook) -> usize {
+        let before: usize = book.chapter_words.iter().take(book.current_chapter).sum();
+        let within: usize = Self::line_texts(&book.chapter_content)
+            .iter()
+            .take(book.current_line)
+            .map(|text| text.split_whitespace().count())
+            .sum();
+        before + within + book.word_index
+    }
+
+    /// How far the cursor is through the open chapter and through the whole book, each
+    /// from 0 to 1. The book goes by words once its chapters are counted, and by chapters
+    /// until then.
+    pub fn reading_progress(book: &LoadedBook) -> (f64, f64) {
+        let before: usize = book.chapter_words.iter().take(book.current_chapter).sum();
+        let within = Self::reading_position(book) - before;
+        let chapter_words: usize = Self::line_texts(&book.chapter_content)
+            .into_iter()
+            .chain(Self::line_texts(&book.pending_lines))
+            .map(|text| text.split_whitespace().count())
+            .sum();
+        let chapter = if chapter_words == 0 {
+            0.0
+        } else {
+            (within as f64 / chapter_words as f64).min(1.0)
+        };
+        let book_words: usize = book.chapter_words.iter().sum();
+        let whole = if book_words > 0 {
+            (before + within) as f64 / book_words as f64
+        } else {
+            let chapters = book.parser.get_chapter_count().max(1);
+            (book.current_chapter as f64 + chapter) / chapters as f64
+        };
+        (chapter, whole.min(1.0))
+    }
+
+    /// Adds the words passed since reading was last counted to `words_read`, however the
+    /// cursor moved: scrolling, paging, turning chapters or following a link. Stepping
+    /// back a little keeps the mark, so going over a passage again isn't counted twice;
+    /// a jump further than [`MAX_READ_STEP_WORDS`] either way moves it without counting.
+    pub fn count_words_read(&mut self) {
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        let cursor = (book.current_line, book.word_index);
+        let chapter = book.current_chapter;
+        if book
+            .read_mark
+            .is_some_and(|mark| mark.chapter == chapter && mark.cursor == cursor)
+        {
+            return;
+        }
+        let position = Self::reading_position(book);
+        let mut mark = ReadMark {
+            chapter,
+            position,
+            cursor,
+        };
+        if let Some(last) = book.read_mark {
+            // Without word counts, positions in different chapters can't be compared.
+            let comparable = last.chapter == chapter || !book.chapter_words.is_empty();
+            let ahead = position.saturating_sub(last.position);
+            let behind = last.position.saturating_sub(position);
+            if comparable && ahead <= MAX_READ_STEP_WORDS && behind <= MAX_READ_STEP_WORDS {
+                book.words_read += ahead;
+                if behind > 0 {
+                    mark.position = last.position;
+                }
+            }
+        }
+        book.read_mark = Some(mark);
+    }
+
+    /// Marks a book as handed to the background indexer. False if it is already queued.
+    pub fn queue_indexing(&mut self, book_id: i32) -> bool {
+        let queued = self.indexing_books.insert(book_id);
+        if queued {
+            self.indexing_total += 1;
+        }
+        queued
+    }
+
+    /// Books indexed so far and books queued, while the background indexer is busy.
+    pub fn indexing_progress(&self) -> Option<(usize, usize)> {
+        if self.indexing_books.is_empty() {
+            return None;
+        }
+        let total = self.indexing_total.max(self.indexing_books.len());
+        Some((total - self.indexing_books.len(), total))
+    }
+
+    /// Called when the background indexer is done with a book, so its word counts show
+    /// up in the library and count towards the open book's progress.
+    pub fn finish_indexing(&mut self, book_id: i32) {
+        self.indexing_books.remove(&book_id);
+        if self.indexing_books.is_empty() {
+            self.indexing_total = 0;
+        }
+        if let Some(book) = self.current_book.as_mut().filter(|b| b.id == book_id) {
+            book.chapter_words = self.db.get_chapter_words(book_id).unwrap_or_default();
+        }
+        self.refresh_book(book_id);
+    }
+
+    /// Reloads one library entry so its progress is current without rebuilding the list.
+    fn refresh_book(&mut self, book_id: i32) {
+        if let Ok(Some(mut fresh)) = self.db.get_book(book_id) {
+            self.overlay_progress(&mut fresh);
+            if let Some(record) = self.books.iter_mut().find(|b| b.id == book_id) {
+                *record = fresh;
+            }
+        }
+    }
+
+    pub fn scroll_viewport_down(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            Self::reveal_line(book, book.viewport_top + 1);
+            if book.viewport_top + 1 < book.chapter_content.len() {
+                book.viewport_top += 1;
+                if book.current_line < book.viewport_top {
+                    book.current_line = book.viewport_top;
+                }
+            }
+        }
+        self.mark_finished_if_at_end();
+    }
+
+    pub fn scroll_viewport_up(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            if book.viewport_top > 0 {
+                book.viewport_top -= 1;
+            }
+        }
+    }
+
+    /// Scrolls by up to `rows` rows of wrapped text, as vim's Ctrl-d and Ctrl-f do: as many
+    /// whole lines as fit in that many rows, and always at least one, so no text is skipped
+    /// unseen. The cursor moves as far as the text, or to the first or last line once the
+    /// chapter can't scroll further.
+    pub fn scroll_rows(&mut self, forward: bool, rows: usize, screen_rows: usize) {
+        let layout = self.page_layout(screen_rows);
+        let rows_of = |line: &RenderLine| crate::ui::reader::line_rows(line, &layout).0;
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        let top = book.viewport_top;
+        let (mut lines, mut moved) = (0, 0);
+        if forward {
+            loop {
+                Self::reveal_line(book, top + lines + 1);
+                if top + lines + 1 >= book.chapter_content.len() {
+                    break;
+                }
+                moved += rows_of(&book.chapter_content[top + lines]);
+                if moved > rows && lines > 0 {
+                    break;
+                }
+                lines += 1;
+            }
+            book.viewport_top += lines;
+            book.current_line = if lines == 0 {
+                book.chapter_content.len().saturating_sub(1)
+            } else {
+                (book.current_line + lines).max(book.viewport_top)
+            };
+        } else {
+            while lines < top {
+                moved += rows_of(&book.chapter_content[top - lines - 1]);
+                if moved > rows && lines > 0 {
+                    break;
+                }
+                lines += 1;
+            }
+            book.viewport_top -= lines;
+            book.current_line = if lines == 0 {
+                0
+            } else {
+                book.current_line.saturating_sub(lines)
+            };
+        }
+        book.current_line = book
+            .current_line
+            .min(book.chapter_content.len().saturating_sub(1));
+        Self::sync_word_index(book);
+        self.mark_finished_if_at_end();
+    }
+
+    /// Moves the select cursor down a row of wrapped text, to the word nearest the
+    /// column it was in, as vim's `gj` does.
+    pub fn move_cursor_down(&mut self, height: usize) {
+        let layout = self.page_layout(height);
+        if let Some(ref mut book) = self.current_book {
+            Self::reveal_line(book, book.current_line + 1);
+            let (row, column) = Self::cursor_row(book, &layout);
+            let rows = Self::cursor_line_rows(book, &layout);
+            if let Some(next) = rows.get(row + 1) {
+                book.word_index = Self::word_near(next, column);
+            } else if book.current_line + 1 < book.chapter_content.len() {
+                book.current_line += 1;
+                let rows = Self::cursor_line_rows(book, &layout);
+                book.word_index = Self::word_near(&rows[0], column);
+            }
+            Self::keep_cursor_in_view(book, &layout);
+        }
+        self.mark_finished_if_at_end();
+    }
+
+    /// Moves the select cursor up a row of wrapped text, as vim's `gk` does.
+    pub fn move_cursor_up(&mut self, height: usize) {
+        let layout = self.page_layout(height);
+        if let Some(ref mut book) = self.current_book {
+            let (row, column) = Self::cursor_row(book, &layout);
+            if row > 0 {
+                let rows = Self::cursor_line_rows(book, &layout);
+                book.word_index = Self::word_near(&rows[row - 1], column);
+            } else if book.current_line > 0 {
+                book.current_line -= 1;
+                let rows = Self::cursor_line_rows(book, &layout);
+                book.word_index = Self::word_near(rows.last().unwrap_or(&Vec::new()), column);
+            }
+            Self::keep_cursor_in_view(book, &layout);
+        }
+    }
+
+    /// The words on each wrapped row of the cursor's line.
+    fn cursor_line_rows(book: &LoadedBook, layout: &PageLayout) -> Vec<Vec<(usize, u16)>> {
+        match book.chapter_content.get(book.current_line) {
+            Some(line) => crate::ui::reader::word_rows(line, layout),
+            None => vec![Vec::new()],
+        }
+    }
+
+    /// The wrapped row of its line the cursor's word is on, and the column it starts at.
+    fn cursor_row(book: &LoadedBook, layout: &PageLayout) -> (usize, u16) {
+        Self::cursor_line_rows(book, layout)
+            .iter()
+            .enumerate()
+            .find_map(|(row, words)| {
+                let &(_, x) = words.iter().find(|(i, _)| *i == book.word_index)?;
+                Some((row, x))
+            })
+            .unwrap_or((0, 0))
+    }
+
+    /// The last word of a row starting at or before `column`, else the row's first.
+    fn word_near(row: &[(usize, u16)], column: u16) -> usize {
+        row.iter()
+            .rev()
+            .find(|(_, x)| *x <= column)
+            .or(row.first())
+            .map_or(0, |(i, _)| *i)
+    }
+
+    /// Scrolls just far enough that the cursor's row is on screen, clear of the bottom
+    /// two rows, counting the rows wrapped lines take.
+    fn keep_cursor_in_view(book: &mut LoadedBook, layout: &PageLayout) {
+        if book.current_line < book.viewport_top {
+            book.viewport_top = book.current_line;
+            return;
+        }
+        let limit = layout.rows.saturating_sub(2).max(1);
+        let (row, _) = Self::cursor_row(book, layout);
+        let rows_of = |line: &RenderLine| crate::ui::reader::line_rows(line, layout).0;
+        let mut above: usize = book.chapter_content[book.viewport_top..book.current_line]
+            .iter()
+            .map(rows_of)
+            .sum();
+        let cursor_rows = row * (1 + layout.line_spacing as usize) + 1;
+        while above + cursor_rows > limit && book.viewport_top < book.current_line {
+            above -= rows_of(&book.chapter_content[book.viewport_top]);
+            book.viewport_top += 1;
+        }
+    }
+
+    fn sync_word_index(book: &mut LoadedBook) {
+        match book.chapter_content.get(book.current_line) {
+            Some(RenderLine::Text(_line)) => {
+                let words = _line.split_whitespace().count();
+                if book.word_index >= words && words > 0 {
+                    book.word_index = words.saturating_sub(1);
+                } else if words == 0 {
+                    book.word_index = 0;
+                }
+            }
+            Some(RenderLine::Image { .. } | RenderLine::Caption(_)) => {
+                book.word_index = 0;
+            }
+            None => {}
+        }
+    }
+
+    pub fn cursor_right(&mut self, height: usize) {
+        let layout = self.page_layout(height);
+        if let Some(ref mut book) = self.current_book {
+            Self::reveal_line(book, book.current_line + 1);
+            let words = match book.chapter_content.get(book.current_line) {
+                Some(RenderLine::Text(line)) => line.split_whitespace().count(),
+                Some(RenderLine::Image { .. } | RenderLine::Caption(_)) => 0,
+                None => return,
+            };
+            if book.word_index + 1 < words {
+                book.word_index += 1;
+            } else if book.current_line + 1 < book.chapter_content.len() {
+                book.current_line += 1;
+                book.word_index = 0;
+            }
+            Self::keep_cursor_in_view(book, &layout);
+        }
+    }
+
+    /// The line and word drawn at a screen cell, if any.
+    pub fn word_at(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        self.word_areas
+            .iter()
+            .find(|a| a.y == row && (a.x..a.x + a.width).contains(&column))
+            .map(|a| (a.line, a.word))
+    }
+
+    /// What a click at a screen cell does. Later areas were drawn on top, so they win.
+    pub fn click_target_at(&self, column: u16, row: u16) -> Option<ClickTarget> {
+        self.click_areas
+            .iter()
+            .rev()
+            .find(|a| a.y == row && (a.x..a.x + a.width).contains(&column))
+            .map(|a| a.target)
+    }
+
+    /// The selected row of the view's clickable list. `None` while a prompt waits for an
+    /// answer, so clicks can't move the selection from under it.
+    pub fn selected_row(&self) -> Option<usize> {
+        let prompting = self.tag_input.is_some()
+            || self.attach_input.is_some()
+            || self.pending_book_removal.is_some()
+            || !self.pending_relinks.is_empty();
+        if prompting {
+            return None;
+        }
+        match self.view {
+            AppView::Library => Some(self.selected_book_index),
+            AppView::Toc => Some(self.selected_toc_index),
+            AppView::AnnotationList => Some(self.selected_annotation_index),
+            _ => None,
+        }
+    }
+
+    /// Puts the select cursor on a word, as a click on it does.
+    pub fn move_cursor_to(&mut self, line: usize, word: usize) {
+        if let Some(ref mut book) = self.current_book {
+            if line >= book.chapter_content.len() {
+                return;
+            }
+            book.current_line = line;
+            book.word_index = word;
+            Self::sync_word_index(book);
+        }
+    }
+
+    pub fn cursor_left(&mut self, height: usize) {
+        let layout = self.page_layout(height);
+        if let Some(ref mut book) = self.current_book {
+            let on_text = matches!(
+                book.chapter_content.get(book.current_line),
+                Some(RenderLine::Text(_))
+            );
+            if on_text && book.word_index > 0 {
+                book.word_index -= 1;
+            } else if book.current_line > 0 {
+                book.current_line -= 1;
+                // Onto the last word of the line before.
+                book.word_index = usize::MAX;
+                Self::sync_word_index(book);
+            }
+            Self::keep_cursor_in_view(book, &layout);
+        }
+    }
+
+    pub fn enter_visual_mode(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            if let Some(RenderLine::Text(_)) = book.chapter_content.get(book.current_line) {
+                book.selection_anchor = Some((book.current_line, book.word_index));
+                self.view = AppView::Visual;
+            }
+        }
+    }
+
+    pub fn exit_visual_mode(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            book.selection_anchor = None;
+            if self.view == AppView::Visual {
+                self.view = AppView::Select;
+            }
+        }
+    }
+
+    pub fn get_selection_range(&self) -> Option<(usize, usize, usize, usize)> {
+        if let Some(ref book) = self.current_book {
+            if let Some((anchor_line, anchor_word)) = book.selection_anchor {
+                if anchor_line < book.current_line
+                    || (anchor_line == book.current_line && anchor_word <= book.word_index)
+                {
+                    return Some((anchor_line, anchor_word, book.current_line, book.word_index));
+                } else {
+                    return Some((book.current_line, book.word_index, anchor_line, anchor_word));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_selected_text(&self) -> String {
+        if let Some((sl, sw, el, ew)) = self.get_selection_range() {
+            if let Some(ref book) = self.current_book {
+                let mut selected_words = Vec::new();
+                for li in sl..=el {
+                    if let Some(RenderLine::Text(line)) = book.chapter_content.get(li) {
+                        let words: Vec<&str> = line.split_whitespace().collect();
+                        let w_start = if li == sl { sw } else { 0 };
+                        let w_end = if li == el {
+                            std::cmp::min(ew, words.len().saturating_sub(1))
+                        } else {
+                            words.len().saturating_sub(1)
+                        };
+
+                        for wi in w_start..=w_end {
+                            if let Some(w) = words.get(wi) {
+                                selected_words.push(*w);
+                            }
+                        }
+                    }
+                }
+                return selected_words.join(" ");
+            }
+        }
+        String::new()
+    }
+
+    pub fn open_toc(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            self.toc_items = TocEntry::list(book.parser.get_toc(), book.parser.figures());
+            let current = book.current_chapter;
+            let chapter_entry = self
+                .toc_items
+                .iter()
+                .position(|e| e.chapter == current && !e.figure)
+                .unwrap_or(0);
+            self.selected_toc_index = self
+                .recall_selection(AppView::Toc, self.toc_items.len())
+                .unwrap_or(chapter_entry);
+            self.view = AppView::Toc;
+        }
+    }
+
+    /// Opens the chapter of the selected table of contents entry, at its figure if it is one.
+    pub fn jump_to_toc(&mut self) -> Result<()> {
+        self.remember_selection();
+        let Some(entry) = self.toc_items.get(self.selected_toc_index).cloned() else {
+            return Ok(());
+        };
+        let caption = entry.figure.then_some((entry.label.as_str(), FigureKind::Image));
+        self.record_jump();
+        self.open_chapter_at(entry.chapter, caption)
+    }
+
+    /// Lists the open book's captioned images and tables, starting at the first one in
+    /// the current chapter or later.
+    pub fn open_figures(&mut self) {
+        if let Some(ref mut book) = self.current_book {
+            self.figure_items = book.parser.figures();
+            let current = book.current_chapter;
+            let here = self
+                .figure_items
+                .iter()
+                .position(|f| f.chapter >= current)
+                .unwrap_or(0);
+            self.selected_figure_index = self
+                .recall_selection(AppView::Figures, self.figure_items.len())
+                .unwrap_or(here);
+            self.view = AppView::Figures;
+        }
+    }
+
+    /// Opens the chapter of the selected figure or table, scrolled to it.
+    pub fn jump_to_figure(&mut self) -> Result<()> {
+        self.remember_selection();
+        let Some(figure) = self.figure_items.get(self.selected_figure_index).cloned() else {
+            return Ok(());
+        };
+        self.record_jump();
+        self.open_chapter_at(figure.chapter, Some((&figure.label, figure.kind)))
+    }
+
+    /// Lists the places the open book was left mid-chapter, newest first.
+    pub fn open_last_stops(&mut self) {
+        let Some(ref book) = self.current_book else {
+            return;
+        };
+        self.last_stops = self.db.get_last_stops(book.id).unwrap_or_default();
+        self.selected_last_stop_index = self
+            .recall_selection(AppView::LastStops, self.last_stops.len())
+            .unwrap_or(0);
+        self.view = AppView::LastStops;
+    }
+
+    /// Goes back to the selected last stop. Ctrl-o returns to where the reader was.
+    pub fn jump_to_last_stop(&mut self) -> Result<()> {
+        self.remember_selection();
+        let Some(stop) = self.last_stops.get(self.selected_last_stop_index).cloned() else {
+            return Ok(());
+        };
+        self.record_jump();
+        self.view = AppView::Reader;
+        self.go_to_locator(&stop.locator)
+    }
+
+    /// Loads `chapter` into the reader at its top, or at the given caption line. An image
+    /// caption sits under its image, so the view starts at the top of the image instead.
+    fn open_chapter_at(
+        &mut self,
+        chapter_idx: usize,
+        caption: Option<(&str, FigureKind)>,
+    ) -> Result<()> {
+        if let Some(ref mut book) = self.current_book {
+            book.current_chapter = chapter_idx;
+            book.current_line = 0;
+            book.viewport_top = 0;
+            book.word_index = 0;
+            book.selection_anchor = None;
+        }
+
+        let content = if let Some(ref mut book) = self.current_book {
+            book.chapter(chapter_idx)?
+        } else {
+            return Ok(());
+        };
+
+        let (mut flattened, protocols) = Self::flatten_content(
+            &mut self.image_picker,
+            content,
+            self.reader_text_width,
+            self.image_max_height_px,
+            !self.light_mode,
+            self.night_tint_percent,
+        );
+
+        let book_id = self.current_book.as_ref().unwrap().id;
+        let chapter_annotations =
+            Self::load_chapter_annotations(&self.db, book_id, chapter_idx, &flattened)?;
+
+        if let Some(ref mut book) = self.current_book {
+            if let Some((label, kind)) = caption {
+                let caption = flattened
+                    .iter()
+                    .position(|l| matches!(l, RenderLine::Caption(c) if c == label));
+                if let Some(mut top) = caption {
+                    while kind == FigureKind::Image
+                        && top > 0
+                        && matches!(flattened[top - 1], RenderLine::Image { .. })
+                    {
+                        top -= 1;
+                    }
+                    book.current_line = top;
+                    book.viewport_top = top;
+                }
+            }
+            book.pending_lines = Self::cap_chapter(&mut flattened);
+            book.chapter_content = flattened;
+            Self::reveal_line(book, book.current_line);
+            book.image_protocols = protocols;
+            book.chapter_annotations = chapter_annotations;
+        }
+
+        self.save_progress()?;
+        self.view = AppView::Reader;
+        Ok(())
+    }
+
+    pub fn toggle_theme(&mut self) {
+        self.theme = match self.theme {
+            Theme::Default => Theme::Gruvbox,
+            Theme::Gruvbox => Theme::Nord,
+            Theme::Nord => Theme::Sepia,
+            Theme::Sepia => Theme::Default,
+        };
+    }
+
+    /// Starts a generated summary of the current chapter. Returns the text to summarize.
+    pub fn begin_chapter_summary(&mut self) -> Option<String> {
+        let book = self.current_book.as_ref()?;
+        let text = book
+            .chapter_content
+            .iter()
+            .chain(&book.pending_lines)
+            .filter_map(|l| match l {
+                RenderLine::Text(t) => Some(t.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let scope = format!("Chapter {} summary", book.current_chapter + 1);
+        self.begin_llm_draft(scope, text)
+    }
+
+    /// Starts a generated summary of the annotations currently listed.
+    pub fn begin_highlights_summary(&mut self) -> Option<String> {
+        let text = self
+            .current_annotations
+            .iter()
+            .map(|a| match a.note {
+                Some(ref note) => format!("- {} (note: {})", a.content, note),
+                None => format!("- {}", a.content),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let scope = format!("{} summary", self.annotation_filter_label());
+        self.begin_llm_draft(scope, text)
+    }
+
+    fn begin_llm_draft(&mut self, scope: String, text: String) -> Option<String> {
+        if !self.llm.is_enabled() {
+            self.status_message = Some(
+                "Summaries are off. Set llm_command or llm_endpoint in tbook.toml.".to_string(),
+            );
+            return None;
+        }
+        if text.trim().is_empty() {
+            return None;
+        }
+        self.llm_draft = Some(LlmDraft {
+            scope,
+            pending: true,
+            return_view: self.view,
+        });
+        self.annotation_note.clear();
+        self.view = AppView::Annotation;
+        Some(text)
+    }
+
+    /// Shows a dictionary answer and adds the word to the vocabulary, with the sentence
+    /// it was met in.
+    pub fn apply_lookup_result(&mut self, result: String) {
+        let encyclopedia = self
+            .dictionary_settings
+            .as_ref()
+            .is_some_and(|s| s.source.is_encyclopedia());
+        // Articles about names and places don't belong in the vocabulary.
+        if encyclopedia {
+            self.dictionary_result = result;
+            return;
+        }
+        self.db
+            .add_to_vocabulary(
+                &self.dictionary_query,
+                &result,
+                self.dictionary_source.as_ref(),
+            )
+            .ok();
+        self.dictionary_result = result;
+    }
+
+    pub fn apply_llm_summary(&mut self, result: std::result::Result<String, String>) {
+        let Some(ref mut draft) = self.llm_draft else {
+            return;
+        };
+        if !draft.pending {
+            return;
+        }
+        match result {
+            Ok(summary) => {
+                draft.pending = false;
+                self.annotation_note = summary;
+            }
+            Err(e) => {
+                let return_view = draft.return_view;
+                self.llm_draft = None;
+                self.status_message = Some(format!("Summary failed: {}", e));
+                if self.view == AppView::Annotation {
+                    self.view = return_view;
+                }
+            }
+        }
+    }
+
+    pub fn cancel_llm_draft(&mut self) {
+        if let Some(draft) = self.llm_draft.take() {
+            self.annotation_note.clear();
+            self.view = draft.return_view;
+        }
+    }
+
+    fn save_llm_draft(&mut self, draft: LlmDraft) -> Result<()> {
+        if let Some(ref mut book) = self.current_book {
+            if !self.annotation_note.trim().is_empty() {
+                // Generated summaries are anchored at the cursor and labelled as such.
+                let line = book.current_line;
+                let locator = Self::locator_at(book, line, 0).to_string();
+                self.db.add_annotation(&NewAnnotation {
+                    book_id: book.id,
+                    chapter: book.current_chapter,
+                    start_line: book.current_line,
+                    end_line: book.current_line,
+                    content: &format!("[generated] {}", draft.scope),
+                    note: Some(self.annotation_note.trim()),
+                    kind: AnnotationKind::Summary.as_str(),
+                    start_locator: Some(&locator),
+                    end_locator: Some(&locator),
+                    ..Default::default()
+                })?;
+                book.chapter_annotations = Self::load_chapter_annotations(
+                    &self.db,
+                    book.id,
+                    book.current_chapter,
+                    &book.chapter_content,
+                )?;
+            }
+        }
+        self.sync_vault_note();
+        self.annotation_note.clear();
+        self.view = draft.return_view;
+        if self.view == AppView::AnnotationList {
+            self.load_annotations()?;
+        }
+        Ok(())
+    }
+
+    pub fn add_annotation_with_note(&mut self) -> Result<()> {
+        if let Some(draft) = self.llm_draft.take() {
+            if draft.pending {
+                self.llm_draft = Some(draft);
+                return Ok(());
+            }
+            return self.save_llm_draft(draft);
+        }
+        let range = self.get_selection_range();
+        let content = if range.is_some() {
+            self.get_selected_text()
+        } else if let Some(ref book) = self.current_book {
+            if let Some(RenderLine::Text(line)) = book.chapter_content.get(book.current_line) {
+                line.clone()
+            } else {
+                String::new()
             }
         } else {
-            (false, 0)
+            String::new()
         };
 
-        if should_update {
+        if let Some(ref mut book) = self.current_book {
+            if let Some(RenderLine::Text(line)) = book.chapter_content.get(book.current_line) {
+                let (sl, sw, el, ew) = range.unwrap_or((
+                    book.current_line,
+                    0,
+                    book.current_line,
+                    line.split_whitespace().count().saturating_sub(1),
+                ));
+
+                if !content.is_empty() {
+                    let start = Self::locator_at(book, sl, sw).to_string();
+                    let end = Self::locator_at(book, el, ew).to_string();
+                    let note = if self.annotation_note.trim().is_empty() {
+                        None
+                    } else {
+                        Some(self.annotation_note.as_str())
+                    };
+                    let id = self.db.add_annotation(&NewAnnotation {
+                        book_id: book.id,
+                        chapter: book.current_chapter,
+                        start_line: sl,
+                        start_word: sw,
+                        end_line: el,
+                        end_word: ew,
+                        content: &content,
+                        note,
+                        kind: AnnotationKind::Summary.as_str(),
+                        start_locator: Some(&start),
+                        end_locator: Some(&end),
+                    })?;
+                    if let Some(note) = note {
+                        self.db
+                            .add_annotation_links(id, &Self::parse_annotation_refs(note))?;
+                    }
+                    book.chapter_annotations = Self::load_chapter_annotations(
+                        &self.db,
+                        book.id,
+                        book.current_chapter,
+                        &book.chapter_content,
+                    )?;
+                }
+            }
+        }
+        self.sync_vault_note();
+        self.annotation_note.clear();
+        self.exit_visual_mode();
+        self.view = AppView::Reader;
+        Ok(())
+    }
+
+    /// Extracts `[[id]]` references to other annotations from a note.
+    pub fn parse_annotation_refs(note: &str) -> Vec<i32> {
+        ANNOTATION_REF
+            .captures_iter(note)
+            .filter_map(|cap| cap[1].parse().ok())
+            .collect()
+    }
+
+    /// Configured color of a user-defined annotation kind.
+    pub fn annotation_kind_color(&self, kind: &str) -> Option<[u8; 3]> {
+        self.annotation_kinds
+            .iter()
+            .find(|k| k.name == kind)
+            .map(|k| k.color)
+    }
+
+    pub fn annotation_filter_label(&self) -> String {
+        match self.annotation_filter {
+            AnnotationFilter::Custom(i) => self
+                .annotation_kinds
+                .get(i)
+                .map(|k| k.name.clone())
+                .unwrap_or_else(|| self.annotation_filter.label().to_string()),
+            filter => filter.label().to_string(),
+        }
+    }
+
+    /// Highlights the selection or the word under the cursor as the `index`-th custom kind.
+    pub fn add_custom_highlight(&mut self, index: usize) -> Result<()> {
+        match self.annotation_kinds.get(index) {
+            Some(kind) => {
+                let kind = kind.name.clone();
+                self.add_quick_highlight(&kind)
+            }
+            None => {
+                self.status_message = Some(format!(
+                    "No annotation kind {} in tbook.toml",
+                    index + 1
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    /// Saves the visual selection, or the word under the cursor, as an annotation of `kind`.
+    pub fn add_quick_highlight(&mut self, kind: &str) -> Result<()> {
+        let range = self.get_selection_range();
+        let selected_text = if range.is_some() {
+            self.get_selected_text()
+        } else {
+            String::new()
+        };
+
+        if let Some(ref mut book) = self.current_book {
+            // If we have a selection range (Visual mode), highlight it.
+            if let Some((sl, sw, el, ew)) = range {
+                if !selected_text.is_empty() {
+                    let start = Self::locator_at(book, sl, sw).to_string();
+                    let end = Self::locator_at(book, el, ew).to_string();
+                    self.db.add_annotation(&NewAnnotation {
+                        book_id: book.id,
+                        chapter: book.current_chapter,
+                        start_line: sl,
+                        start_word: sw,
+                        end_line: el,
+                        end_word: ew,
+                        content: &selected_text,
+                        kind,
+                        start_locator: Some(&start),
+                        end_locator: Some(&end),
+                        ..Default::default()
+                    })?;
+                }
+            } else {
+                // Otherwise, highlight the current word (useful in Select mode).
+                if let Some(crate::app::RenderLine::Text(line)) =
+                    book.chapter_content.get(book.current_line)
+                {
+                    if let Some(word) = line.split_whitespace().nth(book.word_index) {
+                        if !word.is_empty() {
+                            let word = word.to_string();
+                            let (line, index) = (book.current_line, book.word_index);
+                            let locator = Self::locator_at(book, line, index).to_string();
+                            self.db.add_annotation(&NewAnnotation {
+                                book_id: book.id,
+                                chapter: book.current_chapter,
+                                start_line: line,
+                                start_word: index,
+                                end_line: line,
+                                end_word: index,
+                                content: &word,
+                                kind,
+                                start_locator: Some(&locator),
+                                end_locator: Some(&locator),
+                                ..Default::default()
+                            })?;
+                        }
+                    }
+                }
+            }
+
+            book.chapter_annotations = Self::load_chapter_annotations(
+                &self.db,
+                book.id,
+                book.current_chapter,
+                &book.chapter_content,
+            )?;
+        }
+
+        self.sync_vault_note();
+        self.exit_visual_mode();
+        Ok(())
+    }
+
+    pub fn load_annotations(&mut self) -> Result<()> {
+        if let Some(ref book) = self.current_book {
+            self.all_annotations = self.db.get_annotations(book.id)?;
+            self.apply_annotation_filter();
+            if let Some(index) =
+                self.recall_selection(AppView::AnnotationList, self.current_annotations.len())
+            {
+                self.selected_annotation_index = index;
+            }
+            self.view = AppView::AnnotationList;
+            self.refresh_annotation_links();
+        }
+        Ok(())
+    }
+
+    pub fn set_annotation_filter(&mut self, filter: AnnotationFilter) {
+        if let AnnotationFilter::Custom(i) = filter {
+            if i >= self.annotation_kinds.len() {
+                return;
+            }
+        }
+        self.annotation_filter = filter;
+        self.apply_annotation_filter();
+    }
+
+    fn apply_annotation_filter(&mut self) {
+        self.current_annotations = self
+            .all_annotations
+            .iter()
+            .filter(|a| self.annotation_filter.matches(&a.kind, &self.annotation_kinds))
+            .cloned()
+            .collect();
+        self.selected_annotation_index = 0;
+        // Notes may have been edited, so links are loaded again even for the same note.
+        self.links_loaded_for = None;
+    }
+
+    /// Loads the links of the selected note in the notes list when the selection has
+    /// moved to another note.
+    pub fn refresh_annotation_links(&mut self) {
+        if self.view != AppView::AnnotationList {
+            return;
+        }
+        let id = self
+            .current_annotations
+            .get(self.selected_annotation_index)
+            .map(|a| a.id);
+        if id == self.links_loaded_for {
+            return;
+        }
+        self.links_loaded_for = id;
+        let Some(id) = id else {
+            self.annotation_links.clear();
+            self.annotation_backlinks.clear();
+            return;
+        };
+        let links = self.db.get_linked_annotations(id, true);
+        let backlinks = self.db.get_linked_annotations(id, false);
+        match (links, backlinks) {
+            (Ok(links), Ok(backlinks)) => {
+                self.annotation_links = links;
+                self.annotation_backlinks = backlinks;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                self.annotation_links.clear();
+                self.annotation_backlinks.clear();
+                self.status_message = Some(format!("Loading links failed: {}", e));
+            }
+        }
+    }
+
+    pub fn jump_to_annotation(&mut self) -> Result<()> {
+        self.remember_selection();
+        if self.current_annotations.len() > self.selected_annotation_index {
+            self.record_jump();
+        }
+        let (should_jump, chapter_idx, start_line, start_word) =
             if let Some(ref mut book) = self.current_book {
-                book.current_chapter = new_chapter_idx;
-                book.current_line = 0;
-                book.viewport_top = 0;
-                book.word_index = 0;
+                if let Some(anno) = self.current_annotations.get(self.selected_annotation_index) {
+                    if book.current_chapter != anno.chapter {
+                        (true, anno.chapter, anno.start_line, anno.start_word)
+                    } else {
+                        // Same chapter, just move cursor
+                        book.current_line = anno.start_line;
+                        book.viewport_top = anno.start_line;
+                        book.word_index = anno.start_word;
+                        book.selection_anchor = None;
+                        (false, 0, 0, 0)
+                    }
+                } else {
+                    (false, 0, 0, 0)
+                }
+            } else {
+                (false, 0, 0, 0)
+            };
+
+        if should_jump {
+            if let Some(ref mut book) = self.current_book {
+                book.current_chapter = chapter_idx;
+                book.current_line = start_line;
+                book.viewport_top = start_line;
+                book.word_index = start_word;
                 book.selection_anchor = None;
             }
 
             let content = if let Some(ref mut book) = self.current_book {
-                book.parser.get_chapter_content(new_chapter_idx)?
+                book.chapter(chapter_idx)?
             } else {
                 return Ok(());
             };
 
-            let (flattened, protocols) = Self::flatten_content(&mut self.image_picker, content);
-
+            let (mut flattened, protocols) = Self::flatten_content(
+                &mut self.image_picker,
+                content,
+                self.reader_text_width,
+                self.image_max_height_px,
+                !self.light_mode,
+                self.night_tint_percent,
+            );
             let book_id = self.current_book.as_ref().unwrap().id;
-            let chapter_annotations = self
-                .db
-                .get_annotations(book_id)?
-                .into_iter()
-                .filter(|a| a.chapter == new_chapter_idx)
-                .collect();
+            let chapter_annotations =
+                Self::load_chapter_annotations(&self.db, book_id, chapter_idx, &flattened)?;
 
             if let Some(ref mut book) = self.current_book {
+                book.pending_lines = Self::cap_chapter(&mut flattened);
                 book.chapter_content = flattened;
                 book.image_protocols = protocols;
                 book.chapter_annotations = chapter_annotations;
+                Self::reveal_line(book, book.current_line);
+            }
+        }
+        let locator = self
+            .current_annotations
+            .get(self.selected_annotation_index)
+            .and_then(|a| a.start_locator.clone());
+        if let Some(ref locator) = locator {
+            if let Some(ref mut book) = self.current_book {
+                if book.current_chapter == locator.spine {
+                    Self::place_cursor(book, locator);
+                }
             }
+        }
+        if should_jump {
             self.save_progress()?;
         }
+        if self.current_book.is_some() && !self.current_annotations.is_empty() {
+            self.view = AppView::Reader;
+        }
         Ok(())
     }
 
-    pub fn prev_chapter(&mut self) -> Result<()> {
-        let (should_update, new_chapter_idx) = if let Some(ref book) = self.current_book {
-            if book.current_chapter > 0 {
-                (true, book.current_chapter - 1)
-            } else {
-                (false, 0)
+    /// Groups question and summary annotations from every book by shared keyword.
+    pub fn open_concept_index(&mut self) -> Result<()> {
+        let annos = self.db.get_annotations_by_kinds(&[
+            AnnotationKind::Question.as_str(),
+            AnnotationKind::Summary.as_str(),
+        ])?;
+        self.concept_index = Self::build_concept_index(annos);
+        if self.selected_concept_index >= self.concept_index.len() {
+            self.selected_concept_index = 0;
+        }
+        self.selected_concept_note_index = 0;
+        self.concept_focus_notes = false;
+        self.view = AppView::ConceptIndex;
+        Ok(())
+    }
+
+    fn build_concept_index(annos: Vec<AnnotationRecord>) -> Vec<(String, Vec<AnnotationRecord>)> {
+        const STOPWORDS: &[&str] = &[
+            "about", "after", "again", "also", "because", "been", "before", "being", "between",
+            "both", "could", "does", "doing", "down", "during", "each", "from", "further", "have",
+            "having", "here", "into", "itself", "just", "more", "most", "much", "only", "other",
+            "over", "same", "should", "some", "such", "than", "that", "their", "them", "then",
+            "there", "these", "they", "this", "those", "through", "under", "until", "very", "were",
+            "what", "when", "where", "which", "while", "whom", "will", "with", "would", "your",
+        ];
+
+        let mut index: HashMap<String, Vec<AnnotationRecord>> = HashMap::new();
+        for anno in annos {
+            let text = format!("{} {}", anno.content, anno.note.as_deref().unwrap_or(""));
+            let mut seen = HashSet::new();
+            for word in text.split(|c: char| !c.is_alphanumeric()) {
+                let word = word.to_lowercase();
+                if word.chars().count() < 4
+                    || word.chars().all(|c| c.is_numeric())
+                    || STOPWORDS.contains(&word.as_str())
+                {
+                    continue;
+                }
+                if seen.insert(word.clone()) {
+                    index.entry(word).or_default().push(anno.clone());
+                }
             }
-        } else {
-            (false, 0)
+        }
+
+        // A concept only links notes when it shows up in more than one of them.
+        let mut concepts: Vec<(String, Vec<AnnotationRecord>)> =
+            index.into_iter().filter(|(_, notes)| notes.len() > 1).collect();
+        concepts.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        concepts
+    }
+
+    pub fn selected_concept_notes(&self) -> &[AnnotationRecord] {
+        self.concept_index
+            .get(self.selected_concept_index)
+            .map(|(_, notes)| notes.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn open_selected_concept_note(&mut self) -> Result<()> {
+        let Some(anno) = self
+            .selected_concept_notes()
+            .get(self.selected_concept_note_index)
+            .cloned()
+        else {
+            return Ok(());
         };
+        self.open_note(anno)
+    }
 
-        if should_update {
-            if let Some(ref mut book) = self.current_book {
-                book.current_chapter = new_chapter_idx;
-                book.current_line = 0;
-                book.viewport_top = 0;
-                book.word_index = 0;
-                book.selection_anchor = None;
+    /// Opens the note's book, if it isn't already, and jumps to the note.
+    fn open_note(&mut self, anno: AnnotationRecord) -> Result<()> {
+        let Some(mut book_record) = self
+            .db
+            .get_books()?
+            .into_iter()
+            .find(|b| b.id == anno.book_id)
+        else {
+            return Ok(());
+        };
+        self.overlay_progress(&mut book_record);
+
+        if self.current_book.as_ref().map(|b| b.id) != Some(book_record.id) {
+            self.save_progress().ok();
+            self.load_book(book_record)?;
+        }
+        self.current_annotations = vec![anno];
+        self.selected_annotation_index = 0;
+        self.jump_to_annotation()
+    }
+
+    pub fn open_note_browser(&mut self) -> Result<()> {
+        self.reload_note_browser()?;
+        self.view = AppView::NoteBrowser;
+        Ok(())
+    }
+
+    /// Rereads every note and tag, dropping filters on books or tags that are gone.
+    fn reload_note_browser(&mut self) -> Result<()> {
+        let browser = &mut self.note_browser;
+        browser.notes = self.db.get_all_annotations()?;
+        browser.tags = self.db.get_tags()?;
+        if browser.book.is_some_and(|id| !browser.books().contains(&id)) {
+            browser.book = None;
+        }
+        if browser.tag.as_ref().is_some_and(|t| !browser.tags.contains(t)) {
+            browser.tag = None;
+        }
+        let len = self.browsed_notes().len();
+        self.note_browser.selected = self.note_browser.selected.min(len.saturating_sub(1));
+        Ok(())
+    }
+
+    /// The notes that pass the browser's filters.
+    pub fn browsed_notes(&self) -> Vec<&AnnotationRecord> {
+        let browser = &self.note_browser;
+        let since = browser
+            .period
+            .days()
+            .map(|days| (chrono::Utc::now() - chrono::Duration::days(days)).naive_utc());
+        browser
+            .notes
+            .iter()
+            .filter(|n| browser.book.is_none_or(|id| n.book_id == id))
+            .filter(|n| browser.kind.matches(&n.kind, &self.annotation_kinds))
+            .filter(|n| browser.tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+            .filter(|n| {
+                since.is_none_or(|since| {
+                    n.created_at
+                        .as_deref()
+                        .and_then(|c| {
+                            chrono::NaiveDateTime::parse_from_str(c, "%Y-%m-%d %H:%M:%S").ok()
+                        })
+                        .is_some_and(|created| created >= since)
+                })
+            })
+            .collect()
+    }
+
+    pub fn note_browser_book_title(&self) -> &str {
+        match self.note_browser.book {
+            Some(id) => self
+                .books
+                .iter()
+                .find(|b| b.id == id)
+                .map(|b| b.title.as_str())
+                .unwrap_or("Unknown Book"),
+            None => "All books",
+        }
+    }
+
+    pub fn note_browser_kind_label(&self) -> String {
+        match self.note_browser.kind {
+            AnnotationFilter::Custom(i) => self
+                .annotation_kinds
+                .get(i)
+                .map(|k| k.name.clone())
+                .unwrap_or_default(),
+            kind => kind.label().to_string(),
+        }
+    }
+
+    /// Steps the book filter through every book with notes, then back to all books.
+    pub fn cycle_note_browser_book(&mut self) {
+        let books = self.note_browser.books();
+        let next = match self.note_browser.book {
+            None => books.first().copied(),
+            Some(id) => books
+                .iter()
+                .position(|b| *b == id)
+                .and_then(|i| books.get(i + 1))
+                .copied(),
+        };
+        self.note_browser.book = next;
+        self.note_browser.selected = 0;
+    }
+
+    /// Steps the tag filter through every tag in use, then back to any tag.
+    pub fn cycle_note_browser_tag(&mut self) {
+        let tags = &self.note_browser.tags;
+        let next = match self.note_browser.tag {
+            None => tags.first().cloned(),
+            Some(ref tag) => tags
+                .iter()
+                .position(|t| t == tag)
+                .and_then(|i| tags.get(i + 1))
+                .cloned(),
+        };
+        self.note_browser.tag = next;
+        self.note_browser.selected = 0;
+    }
+
+    pub fn cycle_note_browser_period(&mut self) {
+        self.note_browser.period = self.note_browser.period.next();
+        self.note_browser.selected = 0;
+    }
+
+    pub fn set_note_browser_kind(&mut self, filter: AnnotationFilter) {
+        if let AnnotationFilter::Custom(i) = filter {
+            if i >= self.annotation_kinds.len() {
+                return;
             }
+        }
+        self.note_browser.kind = filter;
+        self.note_browser.selected = 0;
+    }
 
-            let content = if let Some(ref mut book) = self.current_book {
-                book.parser.get_chapter_content(new_chapter_idx)?
-            } else {
-                return Ok(());
-            };
+    pub fn open_browsed_note(&mut self) -> Result<()> {
+        let Some(anno) = self
+            .browsed_notes()
+            .get(self.note_browser.selected)
+            .map(|n| (*n).clone())
+        else {
+            return Ok(());
+        };
+        self.open_note(anno)
+    }
+
+    /// Opens the list of Kindle clippings waiting for a book, if there are any.
+    pub fn open_clipping_review(&mut self) -> Result<()> {
+        let review = &mut self.clipping_review;
+        review.clippings = self.db.get_pending_clippings()?;
+        if review.clippings.is_empty() {
+            self.status_message = Some("No Kindle clippings are waiting for a book".to_string());
+            return Ok(());
+        }
+        review.books = self.db.get_books()?;
+        review.selected = review.selected.min(review.clippings.len() - 1);
+        review.guess_target();
+        self.view = AppView::ClippingReview;
+        Ok(())
+    }
 
-            let (flattened, protocols) = Self::flatten_content(&mut self.image_picker, content);
+    pub fn move_clipping_selection(&mut self, forward: bool) {
+        let review = &mut self.clipping_review;
+        let len = review.clippings.len();
+        if len == 0 {
+            return;
+        }
+        let title = |r: &ClippingReview| r.clippings.get(r.selected).map(|c| c.title.clone());
+        let before = title(review);
+        review.selected = if forward {
+            (review.selected + 1) % len
+        } else {
+            (review.selected + len - 1) % len
+        };
+        if title(review) != before {
+            review.guess_target();
+        }
+    }
 
-            let book_id = self.current_book.as_ref().unwrap().id;
-            let chapter_annotations = self
-                .db
-                .get_annotations(book_id)?
-                .into_iter()
-                .filter(|a| a.chapter == new_chapter_idx)
-                .collect();
+    pub fn cycle_clipping_book(&mut self, forward: bool) {
+        let review = &mut self.clipping_review;
+        let len = review.books.len();
+        if len > 0 {
+            review.target = if forward {
+                (review.target + 1) % len
+            } else {
+                (review.target + len - 1) % len
+            };
+        }
+    }
 
-            if let Some(ref mut book) = self.current_book {
-                book.chapter_content = flattened;
-                book.image_protocols = protocols;
-                book.chapter_annotations = chapter_annotations;
-            }
-            self.save_progress()?;
+    /// Adds every clipping of the selected Kindle title to the chosen book.
+    pub fn assign_clippings(&mut self) -> Result<()> {
+        let review = &self.clipping_review;
+        let Some(book) = review.books.get(review.target).cloned() else {
+            return Ok(());
+        };
+        let group: Vec<PendingClippingRecord> =
+            review.selected_group().into_iter().cloned().collect();
+        if group.is_empty() {
+            return Ok(());
         }
-        Ok(())
+        let report = clippings::assign_pending(&self.db, &group, &book)?;
+        self.status_message = Some(format!(
+            "Added {} notes to {}",
+            report.imported, book.title
+        ));
+        self.reload_clipping_review()
     }
 
-    pub fn save_progress(&mut self) -> Result<()> {
-        if let Some(ref mut book) = self.current_book {
-            self.db.update_progress(
-                &book.path,
-                book.current_chapter,
-                book.current_line,
-                book.words_read,
-            )?;
+    pub fn discard_clipping(&mut self) -> Result<()> {
+        let review = &self.clipping_review;
+        let Some(clipping) = review.clippings.get(review.selected) else {
+            return Ok(());
+        };
+        self.db.remove_pending_clippings(&[clipping.id])?;
+        self.reload_clipping_review()
+    }
 
-            // Log session words
-            let delta = book.words_read.saturating_sub(book.session_words_logged);
-            if delta > 0 {
-                self.db.log_reading_session(book.id, delta).ok();
-                book.session_words_logged = book.words_read;
-            }
+    /// Rereads the waiting clippings, returning to the library once none are left.
+    fn reload_clipping_review(&mut self) -> Result<()> {
+        let review = &mut self.clipping_review;
+        review.clippings = self.db.get_pending_clippings()?;
+        if review.clippings.is_empty() {
+            review.selected = 0;
+            self.view = AppView::Library;
+            return Ok(());
         }
+        review.selected = review.selected.min(review.clippings.len() - 1);
+        review.guess_target();
         Ok(())
     }
 
-    pub fn scroll_viewport_down(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            if book.viewport_top + 1 < book.chapter_content.len() {
-                book.viewport_top += 1;
-                if let Some(RenderLine::Text(line)) =
-                    book.chapter_content.get(book.viewport_top - 1)
-                {
-                    book.words_read += line.split_whitespace().count();
-                }
-                if book.current_line < book.viewport_top {
-                    book.current_line = book.viewport_top;
-                }
-            }
+    /// The note selected in the current notes list.
+    fn selected_note(&self) -> Option<&AnnotationRecord> {
+        match self.view {
+            AppView::AnnotationList => self.current_annotations.get(self.selected_annotation_index),
+            AppView::NoteBrowser => self
+                .browsed_notes()
+                .get(self.note_browser.selected)
+                .copied(),
+            _ => None,
         }
     }
 
-    pub fn scroll_viewport_up(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            if book.viewport_top > 0 {
-                book.viewport_top -= 1;
-            }
-        }
+    /// The selected note as Markdown, marked by its kind, for the clipboard.
+    pub fn selected_note_markdown(&self) -> Option<String> {
+        self.selected_note()
+            .map(|note| export::annotation_markdown(note, &self.markdown_markers))
     }
 
-    pub fn move_cursor_down(&mut self, height: usize) {
-        if let Some(ref mut book) = self.current_book {
-            if book.current_line + 1 < book.chapter_content.len() {
-                book.current_line += 1;
-                if book.current_line >= book.viewport_top + height.saturating_sub(2) {
-                    book.viewport_top += 1;
-                }
-                Self::sync_word_index(book);
-            }
+    /// Opens the tag prompt for the selected note, or the selected book in the library,
+    /// filled with its current tags.
+    pub fn begin_tag_edit(&mut self) {
+        let tags = match self.view {
+            AppView::Library => self.books.get(self.selected_book_index).map(|b| &b.tags),
+            _ => self.selected_note().map(|n| &n.tags),
+        };
+        if let Some(tags) = tags {
+            self.tag_input = Some(tags.join(" "));
         }
     }
 
-    pub fn move_cursor_up(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            if book.current_line > 0 {
-                book.current_line -= 1;
-                if book.current_line < book.viewport_top {
-                    book.viewport_top = book.current_line;
-                }
-                Self::sync_word_index(book);
-            }
+    pub fn save_tag_edit(&mut self) -> Result<()> {
+        let Some(input) = self.tag_input.take() else {
+            return Ok(());
+        };
+        let tags = library::parse_tags(&input);
+        if self.view == AppView::Library {
+            let Some(id) = self.books.get(self.selected_book_index).map(|b| b.id) else {
+                return Ok(());
+            };
+            self.db.set_book_tags(id, &tags)?;
+            self.refresh_book(id);
+        } else {
+            let Some(id) = self.selected_note().map(|n| n.id) else {
+                return Ok(());
+            };
+            self.db.set_annotation_tags(id, &tags)?;
         }
+        self.status_message = Some(if tags.is_empty() {
+            "Tags cleared".to_string()
+        } else {
+            let tags: Vec<String> = tags.iter().map(|t| format!("#{}", t)).collect();
+            format!("Tagged {}", tags.join(" "))
+        });
+        self.reload_notes()
     }
 
-    fn sync_word_index(book: &mut LoadedBook) {
-        match book.chapter_content.get(book.current_line) {
-            Some(RenderLine::Text(_line)) => {
-                let words = _line.split_whitespace().count();
-                if book.word_index >= words && words > 0 {
-                    book.word_index = words.saturating_sub(1);
-                } else if words == 0 {
-                    book.word_index = 0;
-                }
-            }
-            Some(RenderLine::Image { .. }) => {
-                book.word_index = 0;
-            }
-            None => {}
+    pub fn begin_attach(&mut self) {
+        if self.selected_note().is_some() {
+            self.attach_input = Some(String::new());
         }
     }
 
-    pub fn cursor_right(&mut self, height: usize) {
-        if let Some(ref mut book) = self.current_book {
-            match book.chapter_content.get(book.current_line) {
-                Some(RenderLine::Text(_line)) => {
-                    let words: Vec<&str> = _line.split_whitespace().collect();
-                    if book.word_index + 1 < words.len() {
-                        book.word_index += 1;
-                    } else if book.current_line + 1 < book.chapter_content.len() {
-                        book.current_line += 1;
-                        if book.current_line >= book.viewport_top + height.saturating_sub(2) {
-                            book.viewport_top += 1;
-                        }
-                        book.word_index = 0;
-                    }
-                }
-                Some(RenderLine::Image { .. }) => {
-                    // Move to next line
-                    if book.current_line + 1 < book.chapter_content.len() {
-                        book.current_line += 1;
-                        if book.current_line >= book.viewport_top + height.saturating_sub(2) {
-                            book.viewport_top += 1;
-                        }
-                        book.word_index = 0;
-                    }
-                }
-                None => {}
-            }
+    /// Attaches the file typed into the attach prompt to the selected note, or detaches
+    /// it when the note already has it. Existing files are stored by their full path;
+    /// anything else is kept as typed, for files that live elsewhere.
+    pub fn confirm_attach(&mut self) -> Result<()> {
+        let Some(input) = self.attach_input.take() else {
+            return Ok(());
+        };
+        let input = expand_path(input.trim());
+        if input.is_empty() {
+            return Ok(());
         }
+        let Some(id) = self.selected_note().map(|n| n.id) else {
+            return Ok(());
+        };
+        let path = std::fs::canonicalize(&input)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or(input);
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+        self.status_message = Some(if self.db.toggle_annotation_attachment(id, &path)? {
+            if Path::new(&path).exists() {
+                format!("Attached {}", name)
+            } else {
+                format!("Attached {} (file not found)", name)
+            }
+        } else {
+            format!("Detached {}", name)
+        });
+        self.reload_notes()
     }
 
-    pub fn cursor_left(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            match book.chapter_content.get(book.current_line) {
-                Some(RenderLine::Text(_line)) => {
-                    if book.word_index > 0 {
-                        book.word_index -= 1;
-                    } else if book.current_line > 0 {
-                        book.current_line -= 1;
-                        if book.current_line < book.viewport_top {
-                            book.viewport_top = book.current_line;
-                        }
-                        Self::sync_word_index(book);
-                    }
-                }
-                Some(RenderLine::Image { .. }) => {
-                    if book.current_line > 0 {
-                        book.current_line -= 1;
-                        if book.current_line < book.viewport_top {
-                            book.viewport_top = book.current_line;
-                        }
-                        Self::sync_word_index(book);
-                    }
-                }
-                None => {}
+    /// Reloads the notes list on screen after one of its notes changed, keeping the
+    /// selection.
+    fn reload_notes(&mut self) -> Result<()> {
+        match self.view {
+            AppView::AnnotationList => {
+                let Some(book_id) = self.current_book.as_ref().map(|b| b.id) else {
+                    return Ok(());
+                };
+                let selected = self.selected_annotation_index;
+                self.all_annotations = self.db.get_annotations(book_id)?;
+                self.apply_annotation_filter();
+                self.selected_annotation_index =
+                    selected.min(self.current_annotations.len().saturating_sub(1));
+                self.refresh_annotation_links();
             }
+            AppView::NoteBrowser => self.reload_note_browser()?,
+            _ => {}
         }
+        Ok(())
     }
 
-    pub fn enter_visual_mode(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            if let Some(RenderLine::Text(_)) = book.chapter_content.get(book.current_line) {
-                book.selection_anchor = Some((book.current_line, book.word_index));
-                self.view = AppView::Visual;
-            }
-        }
+    pub fn load_vocabulary(&mut self) -> Result<()> {
+        self.vocabulary = self.db.get_vocabulary()?;
+        self.selected_vocab_index = self
+            .recall_selection(AppView::Vocabulary, self.vocabulary.len())
+            .unwrap_or(0);
+        self.view = AppView::Vocabulary;
+        Ok(())
     }
 
-    pub fn exit_visual_mode(&mut self) {
-        if let Some(ref mut book) = self.current_book {
-            book.selection_anchor = None;
-            if self.view == AppView::Visual {
-                self.view = AppView::Select;
-            }
+    pub fn refresh_vocab_due(&mut self) {
+        let today = chrono::Local::now().date_naive();
+        self.vocab_due = self.db.count_due_vocabulary(today).unwrap_or(0);
+    }
+
+    /// Starts a flashcard session over the words due today.
+    pub fn open_vocab_review(&mut self) -> Result<()> {
+        let today = chrono::Local::now().date_naive();
+        self.vocab_review = VocabReview {
+            cards: self.db.get_due_vocabulary(today)?.into(),
+            ..VocabReview::default()
+        };
+        if self.view != AppView::VocabReview {
+            self.vocab_review_return_view = self.view;
         }
+        self.view = AppView::VocabReview;
+        Ok(())
     }
 
-    pub fn get_selection_range(&self) -> Option<(usize, usize, usize, usize)> {
-        if let Some(ref book) = self.current_book {
-            if let Some((anchor_line, anchor_word)) = book.selection_anchor {
-                if anchor_line < book.current_line
-                    || (anchor_line == book.current_line && anchor_word <= book.word_index)
-                {
-                    return Some((anchor_line, anchor_word, book.current_line, book.word_index));
-                } else {
-                    return Some((book.current_line, book.word_index, anchor_line, anchor_word));
-                }
-            }
+    /// A few cards to fill a pomodoro break: words due today, topped up with the open
+    /// book's question highlights. Nothing opens when there is nothing to ask.
+    pub fn open_break_review(&mut self) -> Result<()> {
+        let today = chrono::Local::now().date_naive();
+        let mut cards = self.db.get_due_vocabulary(today)?;
+        cards.truncate(BREAK_REVIEW_CARDS);
+        let mut questions = match self.current_book {
+            Some(ref book) => self.db.get_annotations(book.id)?,
+            None => Vec::new(),
+        };
+        questions.retain(|a| AnnotationKind::from_str(&a.kind) == AnnotationKind::Question);
+        // A different few each break, going round the book's questions.
+        let wanted = BREAK_REVIEW_CARDS - cards.len();
+        if questions.len() > wanted {
+            let start = self.break_reviews * wanted % questions.len();
+            questions.rotate_left(start);
         }
-        None
+        questions.truncate(wanted);
+        if cards.is_empty() && questions.is_empty() {
+            return Ok(());
+        }
+        self.break_reviews += 1;
+        self.vocab_review = VocabReview {
+            cards: cards.into(),
+            questions: questions.into(),
+            on_break: true,
+            ..VocabReview::default()
+        };
+        self.vocab_review_return_view = self.view;
+        self.view = AppView::VocabReview;
+        Ok(())
     }
 
-    pub fn get_selected_text(&self) -> String {
-        if let Some((sl, sw, el, ew)) = self.get_selection_range() {
-            if let Some(ref book) = self.current_book {
-                let mut selected_words = Vec::new();
-                for li in sl..=el {
-                    if let Some(RenderLine::Text(line)) = book.chapter_content.get(li) {
-                        let words: Vec<&str> = line.split_whitespace().collect();
-                        let w_start = if li == sl { sw } else { 0 };
-                        let w_end = if li == el {
-                            std::cmp::min(ew, words.len().saturating_sub(1))
-                        } else {
-                            words.len().saturating_sub(1)
-                        };
+    pub fn reveal_vocab_card(&mut self) {
+        let review = &mut self.vocab_review;
+        review.revealed = !review.cards.is_empty() || !review.questions.is_empty();
+    }
 
-                        for wi in w_start..=w_end {
-                            if let Some(w) = words.get(wi) {
-                                selected_words.push(*w);
-                            }
-                        }
-                    }
+    /// Schedules the showing word by how well it was recalled. Forgotten words go to the
+    /// back of the session to be tried again.
+    pub fn grade_vocab_card(&mut self, grade: Grade) -> Result<()> {
+        let review = &mut self.vocab_review;
+        if !review.revealed {
+            return Ok(());
+        }
+        let Some(mut card) = review.cards.pop_front() else {
+            // Questions aren't scheduled; forgotten ones are just asked again.
+            if let Some(question) = review.questions.pop_front() {
+                review.revealed = false;
+                review.reviewed += 1;
+                if grade == Grade::Again {
+                    review.questions.push_back(question);
                 }
-                return selected_words.join(" ");
             }
+            return Ok(());
+        };
+        let today = chrono::Local::now().date_naive();
+        card.schedule = card.schedule.next(grade);
+        self.db.set_vocab_schedule(&card.word, &card.schedule, today)?;
+        review.revealed = false;
+        review.reviewed += 1;
+        if grade == Grade::Again {
+            review.cards.push_back(card);
         }
-        String::new()
+        self.refresh_vocab_due();
+        Ok(())
     }
 
-    pub fn open_toc(&mut self) {
+    /// Queues the open book's notes to be written to the export folder, returning where
+    /// they will go. The status bar says when the file is written.
+    pub fn export_annotations(&mut self, format: ExportFormat) -> Result<String> {
         if let Some(ref book) = self.current_book {
-            self.toc_items = book.parser.get_toc();
-            self.selected_toc_index = book.current_chapter;
-            self.view = AppView::Toc;
+            let annos = self.db.get_annotations(book.id)?;
+            let (title, author) = book.parser.get_metadata();
+            let output = export::render_annotations(
+                format,
+                &title,
+                &author,
+                &book.path,
+                &annos,
+                &self.markdown_markers,
+            );
+            let path = self.export_dir.join(export::notes_filename(&title, format));
+            let path = self.export_mode.path(&path, chrono::Local::now().naive_local());
+            let shown = path.to_string_lossy().to_string();
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            self.writes.push(BackgroundWrite::Export {
+                dir: self.export_dir.clone(),
+                name,
+                contents: output,
+                mode: self.export_mode,
+            });
+            Ok(shown)
+        } else {
+            Err(anyhow::anyhow!("No book open"))
         }
     }
 
-    pub fn jump_to_toc(&mut self) -> Result<()> {
-        let (should_jump, chapter_idx) = if let Some(ref _book) = self.current_book {
-            (true, self.selected_toc_index)
-        } else {
-            (false, 0)
+    /// Rewrites the open book's note in the vault, if one is configured. A failure only
+    /// shows in the status bar; the annotation itself is already saved.
+    fn sync_vault_note(&mut self) {
+        let (Some(vault), Some(book)) = (&self.export_vault, &self.current_book) else {
+            return;
         };
-
-        if should_jump {
-            if let Some(ref mut book) = self.current_book {
-                book.current_chapter = chapter_idx;
-                book.current_line = 0;
-                book.viewport_top = 0;
-                book.word_index = 0;
-                book.selection_anchor = None;
+        let annos = match self.db.get_annotations(book.id) {
+            Ok(annos) => annos,
+            Err(e) => {
+                self.status_message = Some(format!("Vault export failed: {}", e));
+                return;
             }
+        };
+        let (title, author) = book.parser.get_metadata();
+        let note = export::annotations_vault_note(
+            &title,
+            &author,
+            &book.path,
+            &annos,
+            &self.markdown_markers,
+        );
+        self.writes.push(BackgroundWrite::VaultNote {
+            dir: vault.clone(),
+            name: export::vault_filename(&title),
+            contents: note,
+        });
+    }
 
-            let content = if let Some(ref mut book) = self.current_book {
-                book.parser.get_chapter_content(chapter_idx)?
-            } else {
-                return Ok(());
-            };
-
-            let (flattened, protocols) = Self::flatten_content(&mut self.image_picker, content);
+    /// "Where I stopped" in the open book, quoting the text at the cursor. Saves first so
+    /// the percentage is current.
+    pub fn handoff_snippet(&mut self) -> Result<String> {
+        self.save_progress()?;
+        let Some(ref book) = self.current_book else {
+            return Err(anyhow::anyhow!("No book open"));
+        };
+        let mut record = self
+            .db
+            .get_book(book.id)?
+            .ok_or_else(|| anyhow::anyhow!("Book is no longer in the library"))?;
+        self.overlay_progress(&mut record);
+        let quote = Self::line_texts(&book.chapter_content)
+            .iter()
+            .enumerate()
+            .skip(book.current_line)
+            .flat_map(|(i, text)| {
+                text.split_whitespace()
+                    .skip(if i == book.current_line { book.word_index } else { 0 })
+            })
+            .take(export::HANDOFF_QUOTE_WORDS)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(export::handoff_snippet(&record, &quote))
+    }
 
-            let book_id = self.current_book.as_ref().unwrap().id;
-            let chapter_annotations = self
-                .db
-                .get_annotations(book_id)?
-                .into_iter()
-                .filter(|a| a.chapter == chapter_idx)
-                .collect();
+    /// A token with the open book and where the reader is in it, for a reading group.
+    /// Saves first so the position is current.
+    pub fn group_position_token(&mut self) -> Result<String> {
+        self.save_progress()?;
+        let Some(ref book) = self.current_book else {
+            return Err(anyhow::anyhow!("No book open"));
+        };
+        let mut record = self
+            .db
+            .get_book(book.id)?
+            .ok_or_else(|| anyhow::anyhow!("Book is no longer in the library"))?;
+        self.overlay_progress(&mut record);
+        let position = GroupPosition::of_book(&record, &self.group_name)
+            .ok_or_else(|| anyhow::anyhow!("The book's file has not been fingerprinted yet"))?;
+        position.to_token(&self.group_secret)
+    }
 
-            if let Some(ref mut book) = self.current_book {
-                book.chapter_content = flattened;
-                book.image_protocols = protocols;
-                book.chapter_annotations = chapter_annotations;
+    /// Goes to a reading-group member's position, opening their book if it is another
+    /// one, and says how far ahead or behind they were.
+    pub fn join_group_position(&mut self, token: &str) -> Result<()> {
+        let theirs = match GroupPosition::parse(token, &self.group_secret) {
+            Ok(position) => position,
+            Err(e) => {
+                self.status_message = Some(e.to_string());
+                return Ok(());
             }
-
-            self.save_progress()?;
-            self.view = AppView::Reader;
+        };
+        self.save_progress().ok();
+        let books = self.db.get_books()?;
+        let Some(mut record) = theirs.find_book(&books).cloned() else {
+            self.status_message =
+                Some("That position is in a book that isn't in the library".to_string());
+            return Ok(());
+        };
+        self.overlay_progress(&mut record);
+        let gap = GroupPosition::of_book(&record, &self.group_name)
+            .map(|mine| theirs.gap(&mine, record.total_words));
+        if self.current_book.as_ref().map(|b| b.id) != Some(record.id) {
+            self.load_book(record)?;
         }
+        self.go_to_locator(&theirs.locator)?;
+        self.status_message = gap;
         Ok(())
     }
 
-    pub fn toggle_theme(&mut self) {
-        self.theme = match self.theme {
-            Theme::Default => Theme::Gruvbox,
-            Theme::Gruvbox => Theme::Nord,
-            Theme::Nord => Theme::Sepia,
-            Theme::Sepia => Theme::Default,
-        };
+    /// Records the width of a reader text column, and lays out the open chapter's images
+    /// again when it changes.
+    pub fn set_reader_text_width(&mut self, width: u16) {
+        if width == self.reader_text_width {
+            return;
+        }
+        self.reader_text_width = width;
+        if self.current_book.as_ref().is_some_and(|b| !b.image_protocols.is_empty()) {
+            self.refresh_current_book_render_cache().ok();
+        }
     }
 
-    pub fn add_annotation_with_note(&mut self) -> Result<()> {
-        let range = self.get_selection_range();
-        let content = if range.is_some() {
-            self.get_selected_text()
-        } else if let Some(ref book) = self.current_book {
-            if let Some(RenderLine::Text(line)) = book.chapter_content.get(book.current_line) {
-                line.clone()
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
-        };
+    pub fn adjust_margin(&mut self, delta: i16) {
+        let new_margin = (self.margin as i16) + delta;
+        self.margin = new_margin.clamp(0, MAX_MARGIN as i16) as u16;
+    }
 
-        if let Some(ref mut book) = self.current_book {
-            if let Some(RenderLine::Text(line)) = book.chapter_content.get(book.current_line) {
-                let (sl, sw, el, ew) = range.unwrap_or((
-                    book.current_line,
-                    0,
-                    book.current_line,
-                    line.split_whitespace().count().saturating_sub(1),
-                ));
+    pub fn adjust_spacing(&mut self, delta: i16) {
+        let new_spacing = (self.line_spacing as i16) + delta;
+        self.line_spacing = new_spacing.clamp(0, 5) as u16;
+    }
+
+    /// Paragraph spacing and first-line indent for the open book, falling back to the
+    /// app-wide settings.
+    pub fn paragraph_layout(&self) -> (u16, u16) {
+        let book = self.current_book.as_ref();
+        (
+            book.and_then(|b| b.paragraph_spacing)
+                .unwrap_or(self.paragraph_spacing),
+            book.and_then(|b| b.first_line_indent)
+                .unwrap_or(self.first_line_indent),
+        )
+    }
+
+    /// How the reader lays out `rows` rows of text, for working out its pages.
+    pub fn page_layout(&self, rows: usize) -> PageLayout {
+        let (paragraph_spacing, first_line_indent) = self.paragraph_layout();
+        PageLayout {
+            rows,
+            width: self.reader_text_width,
+            line_spacing: self.line_spacing,
+            paragraph_spacing,
+            first_line_indent,
+            justify: self.justify,
+        }
+    }
 
-                if !content.is_empty() {
-                    let note = if self.annotation_note.trim().is_empty() {
-                        None
-                    } else {
-                        Some(self.annotation_note.as_str())
-                    };
-                    self.db.add_annotation(
-                        book.id,
-                        book.current_chapter,
-                        sl,
-                        sw,
-                        el,
-                        ew,
-                        &content,
-                        note,
-                        AnnotationKind::Summary.as_str(),
-                    )?;
-                    book.chapter_annotations = self
-                        .db
-                        .get_annotations(book.id)?
-                        .into_iter()
-                        .filter(|a| a.chapter == book.current_chapter)
-                        .collect();
+    /// First line of each page of the open chapter. A page ends before the first
+    /// paragraph that would not fit whole, so none is split between two pages; one taller
+    /// than the screen gets a page to itself.
+    pub fn page_starts(book: &mut LoadedBook, layout: PageLayout) -> &[usize] {
+        let (chapter, lines) = (book.current_chapter, book.chapter_content.len());
+        let fresh = book
+            .page_cache
+            .as_ref()
+            .is_some_and(|c| c.layout == layout && c.chapter == chapter && c.lines == lines);
+        if !fresh {
+            let rows = layout.rows.max(1);
+            let mut starts = vec![0];
+            let mut used = 0;
+            for (i, line) in book.chapter_content.iter().enumerate() {
+                let (height, trailing) = crate::ui::reader::line_rows(line, &layout);
+                if used > 0 && used + height - trailing > rows {
+                    starts.push(i);
+                    used = 0;
                 }
+                used += height;
             }
+            book.page_cache = Some(PageCache {
+                layout,
+                chapter,
+                lines,
+                starts,
+            });
         }
-        self.annotation_note.clear();
-        self.exit_visual_mode();
-        self.view = AppView::Reader;
-        Ok(())
+        &book.page_cache.as_ref().unwrap().starts
     }
 
-    pub fn add_quick_highlight(&mut self) -> Result<()> {
-        self.add_quick_highlight_kind(AnnotationKind::Highlight)
+    /// The page at the top of the view and how many pages the chapter has, both from 1.
+    pub fn page_position(book: &mut LoadedBook, layout: PageLayout) -> (usize, usize) {
+        let top = book.viewport_top;
+        let starts = Self::page_starts(book, layout);
+        (starts.partition_point(|&s| s <= top).max(1), starts.len())
     }
 
-    pub fn add_question_highlight(&mut self) -> Result<()> {
-        self.add_quick_highlight_kind(AnnotationKind::Question)
+    /// Words left to the next heading and to the end of the open chapter, counted from the
+    /// cursor, and pages left when turning pages. The chapter's first heading is its
+    /// title, so only the ones after it start sections. Lines of a giant chapter still
+    /// held back count too.
+    pub fn finish_line(book: &mut LoadedBook, layout: PageLayout) -> FinishLine {
+        let texts: Vec<&str> = Self::line_texts(&book.chapter_content)
+            .into_iter()
+            .chain(Self::line_texts(&book.pending_lines))
+            .collect();
+        let is_heading = |text: &str| text.trim_start().starts_with('#');
+        let title = texts.iter().position(|t| is_heading(t));
+        let mut section_words = None;
+        let mut words = 0;
+        for (i, text) in texts.iter().enumerate().skip(book.current_line) {
+            let starts_section = i > book.current_line && title.is_some_and(|t| i > t);
+            if section_words.is_none() && starts_section && is_heading(text) {
+                section_words = Some(words);
+            }
+            words += text.split_whitespace().count();
+        }
+        let chapter_pages = book.paged.then(|| {
+            let (page, pages) = Self::page_position(book, layout);
+            pages - page
+        });
+        let book_words = (!book.chapter_words.is_empty()).then(|| {
+            let total: usize = book.chapter_words.iter().sum();
+            total.saturating_sub(Self::reading_position(book))
+        });
+        FinishLine {
+            section_words: section_words.map(|w: usize| w.saturating_sub(book.word_index)),
+            chapter_words: words.saturating_sub(book.word_index),
+            chapter_pages,
+            book_words,
+        }
     }
 
-    pub fn add_summary_highlight(&mut self) -> Result<()> {
-        self.add_quick_highlight_kind(AnnotationKind::Summary)
+    /// Turns to the next page, or the first page of the next chapter at the end of this one.
+    pub fn page_forward(&mut self, rows: usize) -> Result<()> {
+        let layout = self.page_layout(rows);
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        // A page never holds more lines than the screen has rows.
+        Self::reveal_line(book, book.viewport_top + rows + 1);
+        let top = book.viewport_top;
+        let next = Self::page_starts(book, layout)
+            .iter()
+            .copied()
+            .find(|&s| s > top);
+        match next {
+            Some(start) => {
+                book.viewport_top = start;
+                book.current_line = start;
+                book.word_index = 0;
+                self.mark_finished_if_at_end();
+                Ok(())
+            }
+            None => {
+                self.mark_finished_if_at_end();
+                self.next_chapter()
+            }
+        }
     }
 
-    fn add_quick_highlight_kind(&mut self, kind: AnnotationKind) -> Result<()> {
-        let range = self.get_selection_range();
-        let selected_text = if range.is_some() {
-            self.get_selected_text()
-        } else {
-            String::new()
+    /// Turns to the previous page, or the last page of the previous chapter at the top of
+    /// this one.
+    pub fn page_back(&mut self, rows: usize) -> Result<()> {
+        let layout = self.page_layout(rows);
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
         };
+        if book.viewport_top == 0 {
+            if book.current_chapter == 0 {
+                return Ok(());
+            }
+            self.prev_chapter()?;
+            let Some(ref mut book) = self.current_book else {
+                return Ok(());
+            };
+            let last = Self::page_starts(book, layout).last().copied().unwrap_or(0);
+            book.viewport_top = last;
+            book.current_line = last;
+            return Ok(());
+        }
+        let top = book.viewport_top;
+        let previous = Self::page_starts(book, layout)
+            .iter()
+            .copied()
+            .rfind(|&s| s < top)
+            .unwrap_or(0);
+        book.viewport_top = previous;
+        book.current_line = previous;
+        book.word_index = 0;
+        Ok(())
+    }
 
-        if let Some(ref mut book) = self.current_book {
-            // If we have a selection range (Visual mode), highlight it.
-            if let Some((sl, sw, el, ew)) = range {
-                if !selected_text.is_empty() {
-                    self.db.add_annotation(
-                        book.id,
-                        book.current_chapter,
-                        sl,
-                        sw,
-                        el,
-                        ew,
-                        &selected_text,
-                        None,
-                        kind.as_str(),
-                    )?;
-                }
+    /// Switches the open book between turning pages and scrolling, and remembers it.
+    pub fn toggle_paged(&mut self, rows: usize) {
+        let layout = self.page_layout(rows);
+        let Some(ref mut book) = self.current_book else {
+            return;
+        };
+        book.paged = !book.paged;
+        if book.paged {
+            // Start on the page the view is already on.
+            let (page, _) = Self::page_position(book, layout);
+            let start = Self::page_starts(book, layout)[page - 1];
+            book.viewport_top = start;
+            book.current_line = book.current_line.max(start);
+        }
+        let (book_id, paged) = (book.id, book.paged);
+        self.db.set_book_paged(book_id, paged).ok();
+        self.refresh_book(book_id);
+        self.status_message = Some(
+            if paged {
+                "Paged mode: Space turns the page"
             } else {
-                // Otherwise, highlight the current word (useful in Select mode).
-                if let Some(crate::app::RenderLine::Text(line)) =
-                    book.chapter_content.get(book.current_line)
-                {
-                    if let Some(word) = line.split_whitespace().nth(book.word_index) {
-                        if !word.is_empty() {
-                            self.db.add_annotation(
-                                book.id,
-                                book.current_chapter,
-                                book.current_line,
-                                book.word_index,
-                                book.current_line,
-                                book.word_index,
-                                word,
-                                None,
-                                kind.as_str(),
-                            )?;
-                        }
-                    }
-                }
+                "Scrolling mode"
             }
+            .to_string(),
+        );
+    }
 
-            book.chapter_annotations = self
-                .db
-                .get_annotations(book.id)?
-                .into_iter()
-                .filter(|a| a.chapter == book.current_chapter)
-                .collect();
+    /// Puts the open book on `preset`, or back on the shared settings with `None`, and
+    /// saves the choice with the book. A preset also sets the book's paging and
+    /// paragraph layout, which stay as they are when it is dropped.
+    pub fn set_reading_preset(&mut self, preset: Option<ReadingPreset>) -> Result<()> {
+        self.preset_picker = false;
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        book.preset = preset;
+        if let Some(preset) = preset {
+            let (paged, spacing, indent) = preset.typography();
+            book.paged = paged;
+            book.paragraph_spacing = Some(spacing);
+            book.first_line_indent = Some(indent);
+            self.db.set_book_paged(book.id, paged)?;
+            self.db
+                .set_book_typography(book.id, Some(spacing), Some(indent))?;
         }
+        let book_id = book.id;
+        self.db
+            .set_book_preset(book_id, preset.map(|p| p.as_str()))?;
+        self.refresh_book(book_id);
 
-        self.exit_visual_mode();
-        Ok(())
-    }
-
-    pub fn load_annotations(&mut self) -> Result<()> {
-        if let Some(ref book) = self.current_book {
-            self.all_annotations = self.db.get_annotations(book.id)?;
-            self.apply_annotation_filter();
-            self.view = AppView::AnnotationList;
+        let reflow = self.pdf_reflow;
+        self.apply_preset_layout(preset);
+        if let Some(ref mut book) = self.current_book {
+            if self.pdf_reflow != reflow && book.parser.is_pdf() {
+                book.parser.set_reflow(self.pdf_reflow);
+                self.refresh_current_book_render_cache()?;
+            }
         }
+        self.status_message = Some(match preset {
+            Some(preset) => format!("{} preset", preset.label()),
+            None => "No preset: shared layout".to_string(),
+        });
         Ok(())
     }
 
-    pub fn set_annotation_filter(&mut self, filter: AnnotationFilter) {
-        self.annotation_filter = filter;
-        self.apply_annotation_filter();
+    /// Switches the app-wide layout to the preset's, keeping the one it replaces to
+    /// go back to when a book without a preset is read.
+    fn apply_preset_layout(&mut self, preset: Option<ReadingPreset>) {
+        let current = ReaderLayout {
+            margin: self.margin,
+            max_text_width: self.max_text_width,
+            text_columns: self.text_columns,
+            pdf_reflow: self.pdf_reflow,
+        };
+        let layout = match preset {
+            Some(preset) => {
+                self.layout_before_preset.get_or_insert(current);
+                preset.layout()
+            }
+            None => match self.layout_before_preset.take() {
+                Some(layout) => layout,
+                None => return,
+            },
+        };
+        self.margin = layout.margin;
+        self.max_text_width = layout.max_text_width;
+        self.text_columns = layout.text_columns;
+        self.pdf_reflow = layout.pdf_reflow;
     }
 
-    fn apply_annotation_filter(&mut self) {
-        let filtered: Vec<AnnotationRecord> = match self.annotation_filter {
-            AnnotationFilter::All => self.all_annotations.clone(),
-            AnnotationFilter::Highlight => self
-                .all_annotations
-                .iter()
-                .filter(|a| AnnotationKind::from_str(&a.kind) == AnnotationKind::Highlight)
-                .cloned()
-                .collect(),
-            AnnotationFilter::Question => self
-                .all_annotations
-                .iter()
-                .filter(|a| AnnotationKind::from_str(&a.kind) == AnnotationKind::Question)
-                .cloned()
-                .collect(),
-            AnnotationFilter::Summary => self
-                .all_annotations
-                .iter()
-                .filter(|a| AnnotationKind::from_str(&a.kind) == AnnotationKind::Summary)
-                .cloned()
-                .collect(),
-        };
+    /// The kind select mode's `Enter` marks: the preset's, or a plain highlight.
+    pub fn default_annotation_kind(&self) -> AnnotationKind {
+        self.current_book
+            .as_ref()
+            .and_then(|b| b.preset)
+            .map_or(AnnotationKind::Highlight, |p| p.annotation_kind())
+    }
 
-        self.current_annotations = filtered;
-        self.selected_annotation_index = 0;
+    /// Matches of `query` in the open chapter, worked out again only when the query or
+    /// the chapter changes.
+    pub fn search_matches<'a>(
+        book: &'a mut LoadedBook,
+        query: &str,
+        case_sensitive: bool,
+    ) -> &'a [SearchMatch] {
+        let (chapter, lines) = (book.current_chapter, book.chapter_content.len());
+        let fresh = book.search_cache.as_ref().is_some_and(|c| {
+            c.query == query
+                && c.case_sensitive == case_sensitive
+                && c.chapter == chapter
+                && c.lines == lines
+        });
+        if !fresh {
+            let matches = SearchPattern::new(query, case_sensitive)
+                .map(|pattern| find_matches(&book.chapter_content, &pattern))
+                .unwrap_or_default();
+            book.search_cache = Some(SearchCache {
+                query: query.to_string(),
+                case_sensitive,
+                chapter,
+                lines,
+                matches,
+            });
+        }
+        &book.search_cache.as_ref().unwrap().matches
     }
 
-    pub fn jump_to_annotation(&mut self) -> Result<()> {
-        let (should_jump, chapter_idx, start_line, start_word) =
-            if let Some(ref mut book) = self.current_book {
-                if let Some(anno) = self.current_annotations.get(self.selected_annotation_index) {
-                    if book.current_chapter != anno.chapter {
-                        (true, anno.chapter, anno.start_line, anno.start_word)
-                    } else {
-                        // Same chapter, just move cursor
-                        book.current_line = anno.start_line;
-                        book.viewport_top = anno.start_line;
-                        book.word_index = anno.start_word;
-                        book.selection_anchor = None;
-                        (false, 0, 0, 0)
-                    }
-                } else {
-                    (false, 0, 0, 0)
-                }
-            } else {
-                (false, 0, 0, 0)
-            };
+    /// "match 3/17" when the cursor is on a match of the in-book search, or how many
+    /// matches the chapter has when it is not.
+    pub fn search_position(
+        book: &mut LoadedBook,
+        query: &str,
+        case_sensitive: bool,
+    ) -> Option<String> {
+        let cursor = (book.current_line, book.word_index);
+        let matches = Self::search_matches(book, query, case_sensitive);
+        if query.trim().is_empty() {
+            return None;
+        }
+        let total = matches.len();
+        Some(
+            match matches.iter().position(|m| (m.line, m.start_word) == cursor) {
+                Some(i) => format!("match {}/{}", i + 1, total),
+                None if total == 1 => "1 match".to_string(),
+                None => format!("{} matches", total),
+            },
+        )
+    }
 
-        if should_jump {
-            if let Some(ref mut book) = self.current_book {
-                book.current_chapter = chapter_idx;
-                book.current_line = start_line;
-                book.viewport_top = start_line;
-                book.word_index = start_word;
-                book.selection_anchor = None;
+    /// Moves the cursor to the next match of the in-book search, or the previous one,
+    /// going on into other chapters and around the book when this one has no more.
+    pub fn search_next(&mut self, forward: bool, height: usize) -> Result<()> {
+        let query = self.search_query.clone();
+        let case_sensitive = self.search_case_sensitive;
+        let Some(pattern) = SearchPattern::new(&query, case_sensitive) else {
+            self.status_message = Some("Nothing to find; press / to search".to_string());
+            return Ok(());
+        };
+        self.record_jump();
+        let layout = self.page_layout(height);
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        let has_match =
+            |line: &RenderLine| matches!(line, RenderLine::Text(t) if pattern.is_match(t));
+        let cursor = (book.current_line, book.word_index);
+        let ahead = |m: &&SearchMatch| (m.line, m.start_word) > cursor;
+        let matches = Self::search_matches(book, &query, case_sensitive);
+        if forward && !matches.iter().any(|m| ahead(&m)) {
+            // A giant chapter may hold the next match in lines not loaded yet.
+            if let Some(pos) = book.pending_lines.iter().position(has_match) {
+                Self::reveal_line(book, book.chapter_content.len() + pos);
             }
+        }
+        let matches = Self::search_matches(book, &query, case_sensitive);
+        let found = if forward {
+            matches.iter().find(ahead).copied()
+        } else {
+            matches.iter().rfind(|m| (m.line, m.start_word) < cursor).copied()
+        };
+        if let Some(found) = found {
+            Self::go_to_match(book, found, layout);
+            return Ok(());
+        }
 
-            let content = if let Some(ref mut book) = self.current_book {
-                book.parser.get_chapter_content(chapter_idx)?
+        // On through the other chapters, ending back at this one.
+        let (current, count) = (book.current_chapter, book.parser.get_chapter_count());
+        let mut chapter = None;
+        for step in 1..=count {
+            let candidate = if forward {
+                (current + step) % count
             } else {
-                return Ok(());
+                (current + count - step) % count
             };
-
-            let (flattened, protocols) = Self::flatten_content(&mut self.image_picker, content);
-            let book_id = self.current_book.as_ref().unwrap().id;
-            let chapter_annotations = self
-                .db
-                .get_annotations(book_id)?
-                .into_iter()
-                .filter(|a| a.chapter == chapter_idx)
-                .collect();
-
-            if let Some(ref mut book) = self.current_book {
-                book.chapter_content = flattened;
-                book.image_protocols = protocols;
-                book.chapter_annotations = chapter_annotations;
+            let content = book.chapter(candidate).unwrap_or_default();
+            let found = content
+                .iter()
+                .any(|block| matches!(block, PageContent::Text(t) if pattern.is_match(t)));
+            if found {
+                chapter = Some(candidate);
+                break;
             }
-            self.save_progress()?;
         }
-        if self.current_book.is_some() && !self.current_annotations.is_empty() {
-            self.view = AppView::Reader;
+        let Some(chapter) = chapter else {
+            self.status_message = Some(format!("No matches for \"{}\"", query.trim()));
+            return Ok(());
+        };
+        self.open_chapter_at(chapter, None)?;
+        let Some(ref mut book) = self.current_book else {
+            return Ok(());
+        };
+        if !forward {
+            Self::reveal_line(book, usize::MAX / 2);
+        }
+        let matches = Self::search_matches(book, &query, case_sensitive);
+        let found = if forward {
+            matches.first()
+        } else {
+            matches.last()
+        };
+        if let Some(&found) = found {
+            Self::go_to_match(book, found, layout);
+        }
+        let wrapped = if forward {
+            chapter <= current
+        } else {
+            chapter >= current
+        };
+        if wrapped {
+            self.status_message = Some(
+                if forward {
+                    "Search went past the end and started again at the beginning"
+                } else {
+                    "Search went past the beginning and started again at the end"
+                }
+                .to_string(),
+            );
         }
         Ok(())
     }
 
-    pub fn load_vocabulary(&mut self) -> Result<()> {
-        self.vocabulary = self.db.get_vocabulary()?;
-        self.selected_vocab_index = 0;
-        self.view = AppView::Vocabulary;
-        Ok(())
+    /// Puts the cursor on a search match and scrolls, or turns to the page, to show it.
+    fn go_to_match(book: &mut LoadedBook, found: SearchMatch, layout: PageLayout) {
+        book.current_line = found.line;
+        book.word_index = found.start_word;
+        book.selection_anchor = None;
+        let rows = layout.rows;
+        if book.paged {
+            let starts = Self::page_starts(book, layout);
+            let page = starts.partition_point(|&s| s <= found.line).max(1);
+            book.viewport_top = starts[page - 1];
+        } else if found.line < book.viewport_top
+            || book.chapter_content[book.viewport_top..found.line]
+                .iter()
+                .map(|l| crate::ui::reader::line_rows(l, &layout).0)
+                .sum::<usize>()
+                >= rows
+        {
+            // A few lines of context above the match.
+            book.viewport_top = found.line.saturating_sub(2);
+        }
     }
 
-    pub fn export_annotations(&self) -> Result<String> {
-        if let Some(ref book) = self.current_book {
-            let annos = self.db.get_annotations(book.id)?;
-            let (title, author) = book.parser.get_metadata();
-
-            let mut output = String::new();
-            // YAML Frontmatter for Obsidian/Logseq
-            output.push_str("---\n");
-            output.push_str(&format!("title: \"{}\"\n", title));
-            output.push_str(&format!("author: \"{}\"\n", author));
-            output.push_str(&format!("source: \"{}\"\n", book.path));
-            output.push_str(&format!(
-                "exported: {}\n",
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-            ));
-            output.push_str("tags: [tbook, reading-notes]\n");
-            output.push_str("---\n\n");
-
-            output.push_str(&format!("# Reading Notes: {}\n\n", title));
-
-            for a in annos {
-                output.push_str(&format!("### Chapter {}\n", a.chapter + 1));
-                output.push_str(&format!("> {}\n", a.content.replace("\n", "\n> ")));
-                if let Some(note) = a.note {
-                    output.push_str(&format!("\n**Note:** {}\n", note));
-                }
-                output.push_str("\n---\n\n");
-            }
-            let filename = format!("notes_{}.md", title.to_lowercase().replace(" ", "_"));
-            std::fs::write(&filename, output)?;
-            Ok(filename)
-        } else {
-            Err(anyhow::anyhow!("No book open"))
+    /// Changes the open book's paragraph spacing and saves it with the book.
+    pub fn adjust_paragraph_spacing(&mut self, delta: i16) {
+        let (spacing, _) = self.paragraph_layout();
+        let spacing = (spacing as i16 + delta).clamp(0, MAX_PARAGRAPH_SPACING as i16) as u16;
+        if let Some(ref mut book) = self.current_book {
+            book.paragraph_spacing = Some(spacing);
         }
+        self.save_book_typography();
+        self.status_message = Some(format!("Paragraph spacing: {}", spacing));
     }
 
-    pub fn adjust_margin(&mut self, delta: i16) {
-        let new_margin = (self.margin as i16) + delta;
-        self.margin = new_margin.clamp(0, 20) as u16;
+    /// Changes the open book's first-line indent and saves it with the book.
+    pub fn adjust_first_line_indent(&mut self, delta: i16) {
+        let (_, indent) = self.paragraph_layout();
+        let indent = (indent as i16 + delta).clamp(0, MAX_FIRST_LINE_INDENT as i16) as u16;
+        if let Some(ref mut book) = self.current_book {
+            book.first_line_indent = Some(indent);
+        }
+        self.save_book_typography();
+        self.status_message = Some(format!("First-line indent: {}", indent));
     }
 
-    pub fn adjust_spacing(&mut self, delta: i16) {
-        let new_spacing = (self.line_spacing as i16) + delta;
-        self.line_spacing = new_spacing.clamp(0, 5) as u16;
+    fn save_book_typography(&mut self) {
+        if let Some(ref book) = self.current_book {
+            self.db
+                .set_book_typography(book.id, book.paragraph_spacing, book.first_line_indent)
+                .ok();
+            let book_id = book.id;
+            self.refresh_book(book_id);
+        }
     }
 
     pub fn get_reading_stats(&self) -> (usize, f64) {
@@ -1342,131 +6680,242 @@ impl App {
         }
     }
 
-    pub fn scan_for_books_sync(path: String) -> Vec<std::path::PathBuf> {
-        let mut results = Vec::new();
-        let root = Path::new(&path);
+    /// Reading speed for time-left estimates: the long-run average, giving way to this
+    /// sitting's pace over its first [`ROLLING_WPM_WORDS`] words.
+    pub fn rolling_wpm(&self) -> f64 {
+        let (words, wpm) = self.get_reading_stats();
+        let weight = (words as f64 / ROLLING_WPM_WORDS as f64).min(1.0);
+        (self.reading_wpm * (1.0 - weight) + wpm * weight).max(1.0)
+    }
 
-        if root.is_file() {
-            let ext = root
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_lowercase();
-            if ext == "epub" || ext == "pdf" {
-                results.push(root.to_path_buf());
-            }
-            return results;
+    pub fn open_path_input(&mut self, initial: String) {
+        self.explorer_path = initial;
+        self.recent_scan_paths = self.db.get_scan_paths().unwrap_or_default();
+        self.selected_recent_path = None;
+        self.reset_path_completion();
+        self.view = AppView::PathInput;
+    }
+
+    pub fn reset_path_completion(&mut self) {
+        self.path_completions.clear();
+        self.path_completion_index = None;
+    }
+
+    /// Tab completion: extends to the longest shared prefix, then cycles the candidates.
+    pub fn complete_explorer_path(&mut self) {
+        if !self.path_completions.is_empty() {
+            let next = self
+                .path_completion_index
+                .map_or(0, |i| (i + 1) % self.path_completions.len());
+            self.path_completion_index = Some(next);
+            self.explorer_path = self.path_completions[next].clone();
+            return;
         }
 
-        for entry in WalkDir::new(path)
-            .follow_links(true)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let f_path = entry.path();
-            if f_path.is_file() {
-                let ext = f_path
-                    .extension()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-                if ext == "epub" || ext == "pdf" {
-                    results.push(f_path.to_path_buf());
-                }
+        let expanded = expand_path(&self.explorer_path);
+        let candidates = path_candidates(&expanded);
+        match candidates.len() {
+            0 => self.explorer_path = expanded,
+            1 => self.explorer_path = candidates[0].clone(),
+            _ => {
+                let prefix = common_prefix(&candidates);
+                self.explorer_path = if prefix.len() > expanded.len() {
+                    prefix
+                } else {
+                    expanded
+                };
+                self.path_completions = candidates;
             }
         }
-        results.sort();
-        results.dedup();
-        results
     }
 
-    pub fn global_search(&mut self, query: &str) -> Result<Vec<(i32, String, usize, String)>> {
-        let mut results = Vec::new();
-        let books = self.db.get_books()?;
+    /// Moves through the recent scan paths (Up/Down in the path prompt).
+    pub fn select_recent_path(&mut self, forward: bool) {
+        if self.recent_scan_paths.is_empty() {
+            return;
+        }
+        let last = self.recent_scan_paths.len() - 1;
+        let next = match (self.selected_recent_path, forward) {
+            (None, true) => 0,
+            (None, false) => last,
+            (Some(i), true) => (i + 1).min(last),
+            (Some(i), false) => i.saturating_sub(1),
+        };
+        self.selected_recent_path = Some(next);
+        self.explorer_path = self.recent_scan_paths[next].clone();
+        self.reset_path_completion();
+    }
+
+    /// Forgets the highlighted recent scan path.
+    pub fn forget_recent_path(&mut self) -> Result<()> {
+        let Some(idx) = self.selected_recent_path else {
+            return Ok(());
+        };
+        self.db.remove_scan_path(&self.recent_scan_paths[idx])?;
+        self.recent_scan_paths = self.db.get_scan_paths()?;
+        self.selected_recent_path = if self.recent_scan_paths.is_empty() {
+            None
+        } else {
+            Some(idx.min(self.recent_scan_paths.len() - 1))
+        };
+        if let Some(i) = self.selected_recent_path {
+            self.explorer_path = self.recent_scan_paths[i].clone();
+        }
+        Ok(())
+    }
+
+    /// Starts a scan of the configured `library_path` without going through the prompt.
+    pub fn rescan_library(&mut self) -> String {
+        self.explorer_path = self.library_path.clone();
+        self.begin_scan()
+    }
+
+    /// Expands the typed path, remembers it, and returns it for scanning.
+    pub fn begin_scan(&mut self) -> String {
+        self.explorer_path = expand_path(self.explorer_path.trim());
+        self.reset_path_completion();
+        if Path::new(&self.explorer_path).exists() {
+            self.db.add_scan_path(&self.explorer_path).ok();
+        }
+        self.view = AppView::FileExplorer;
+        self.is_scanning = true;
+        self.explorer_results.clear();
+        self.explorer_path.clone()
+    }
 
-        for book in books {
-            let mut parser = if book.path.to_lowercase().ends_with(".pdf") {
-                BookParser::Pdf(PdfParser::new(&book.path)?)
+    /// Switches both searches between ignoring and matching case.
+    pub fn toggle_search_case(&mut self) {
+        self.search_case_sensitive = !self.search_case_sensitive;
+        self.status_message = Some(
+            if self.search_case_sensitive {
+                "Search matches case"
             } else {
-                BookParser::Epub(EpubParser::new(&book.path)?)
-            };
-            let count = parser.get_chapter_count();
-            for i in 0..count {
-                if let Ok(content) = parser.get_chapter_content(i) {
-                    let mut dummy_picker = Picker::halfblocks();
-                    let (lines, _) = Self::flatten_content(&mut dummy_picker, content);
-                    for line_item in lines.iter() {
-                        if let RenderLine::Text(line) = line_item {
-                            if line.to_lowercase().contains(&query.to_lowercase()) {
-                                results.push((
-                                    book.id,
-                                    book.title.clone(),
-                                    i,
-                                    line.trim().to_string(),
-                                ));
-                                if results.len() > 50 {
-                                    return Ok(results);
-                                }
-                            }
-                        }
-                    }
-                }
+                "Search ignores case"
             }
+            .to_string(),
+        );
+    }
+
+    /// Fetches a web page, extracts the readable article, and stores it as an HTML book
+    /// under the data directory. Returns the saved path, ready for `import_paths`.
+    pub async fn fetch_article(url: String) -> Result<PathBuf> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("tbook/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        let body = client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let article = html::extract_article(&body);
+        let dir = config::data_dir().join("articles");
+        std::fs::create_dir_all(&dir)?;
+
+        let slug: String = article
+            .title
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>()
+            .split('_')
+            .filter(|part| !part.is_empty())
+            .take(8)
+            .collect::<Vec<_>>()
+            .join("_");
+        let filename = format!(
+            "{}_{}.html",
+            if slug.is_empty() { "article" } else { slug.as_str() },
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        );
+        let path = dir.join(filename);
+        std::fs::write(&path, html::render_article(&article, &url))?;
+        Ok(path)
+    }
+}
+
+/// The login name, which a reading group sees when `group_name` is unset.
+fn default_group_name() -> String {
+    ["USER", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "reader".to_string())
+}
+
+/// Expands a leading `~` and `$VAR` / `${VAR}` references. Unknown variables are kept as typed.
+pub fn expand_path(input: &str) -> String {
+    let mut path = input.to_string();
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            path = format!("{}{}", home.to_string_lossy(), &path[1..]);
         }
-        Ok(results)
-    }
-
-    pub async fn perform_lookup(word: String) -> String {
-        let url = format!("https://api.dictionaryapi.dev/api/v2/entries/en/{}", word);
-        let client = reqwest::Client::new();
-        match client.get(url).send().await {
-            Ok(resp) => {
-                if let Ok(json_str) = resp.text().await {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&json_str) {
-                        let mut result = String::new();
-                        if let Some(entries) = json.as_array() {
-                            for entry in entries {
-                                if let Some(w) = entry.get("word").and_then(|v| v.as_str()) {
-                                    result.push_str(&format!("# {}\n", w.to_uppercase()));
-                                }
-                                if let Some(meanings) =
-                                    entry.get("meanings").and_then(|v| v.as_array())
-                                {
-                                    for meaning in meanings {
-                                        if let Some(pos) =
-                                            meaning.get("partOfSpeech").and_then(|v| v.as_str())
-                                        {
-                                            result.push_str(&format!("\n[{}]\n", pos));
-                                        }
-                                        if let Some(definitions) =
-                                            meaning.get("definitions").and_then(|v| v.as_array())
-                                        {
-                                            for (i, def) in definitions.iter().enumerate() {
-                                                if let Some(d) =
-                                                    def.get("definition").and_then(|v| v.as_str())
-                                                {
-                                                    result.push_str(&format!("{}. {}\n", i + 1, d));
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                result.push_str("\n---\n");
-                            }
-                        }
-                        if result.is_empty() {
-                            "No definition found.".to_string()
-                        } else {
-                            result
-                        }
-                    } else {
-                        "Failed to parse.".to_string()
-                    }
-                } else {
-                    "Error reading response.".to_string()
-                }
+    }
+
+    let mut out = String::new();
+    let mut rest = path.as_str();
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(&rest[pos..pos + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Filesystem entries that complete the last component of `input`.
+fn path_candidates(input: &str) -> Vec<String> {
+    let (dir, partial) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let read_dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = std::fs::read_dir(read_dir) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden entries only show up once the user starts typing a dot.
+            if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+                return None;
             }
-            Err(e) => format!("Network Error: {}.", e),
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, suffix))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix = first.clone();
+    for candidate in &candidates[1..] {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
         }
     }
+    prefix
 }
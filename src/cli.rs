@@ -0,0 +1,505 @@
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tbook::config;
+use tbook::db::{BookRecord, Db, DbSummary};
+use tbook::export::{ExportFormat, ExportMode, MarkdownMarkers};
+use tbook::instance::LockOwner;
+use tbook::locator::Locator;
+use tbook::parser::BookParser;
+use tbook::digest::Digest;
+use tbook::{clippings, export, koreader, library};
+
+fn describe(summary: &DbSummary) -> String {
+    format!(
+        "{} books, {} annotations, {} vocabulary words",
+        summary.books, summary.annotations, summary.vocabulary
+    )
+}
+
+/// Timestamped file in the backups directory, e.g. `tbook-20250101-120000.db`.
+fn default_backup_path(label: &str) -> PathBuf {
+    config::data_dir().join("backups").join(format!(
+        "{}-{}.db",
+        label,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// What to do when another session already has the library open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockChoice {
+    ReadOnly,
+    TakeOver,
+    Quit,
+}
+
+/// Asks what to do about the session holding the library. Reading along read-only is
+/// the answer when nothing is typed.
+pub fn ask_about_lock(owner: &LockOwner) -> Result<LockChoice> {
+    let host = if owner.host.is_empty() || owner.host == config::hostname() {
+        String::new()
+    } else {
+        format!(" on {}", owner.host)
+    };
+    println!(
+        "tbook is already running with this library (process {}{}).",
+        owner.pid, host
+    );
+    print!("Open it [r]ead-only, [t]ake over, or [q]uit? [R/t/q] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim().to_lowercase().as_str() {
+        "t" | "take over" => LockChoice::TakeOver,
+        "q" | "quit" => LockChoice::Quit,
+        _ => LockChoice::ReadOnly,
+    })
+}
+
+fn write_backup(db: &Db, target: &Path) -> Result<()> {
+    if target.exists() {
+        bail!("{} already exists", target.display());
+    }
+    if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    db.backup_to(target)?;
+    Ok(())
+}
+
+/// `tbook backup [path]`: snapshots the library. A directory target gets a timestamped file.
+pub fn backup(db: &Db, target: Option<&str>) -> Result<()> {
+    let target = match target {
+        Some(path) if Path::new(path).is_dir() => {
+            Path::new(path).join(default_backup_path("tbook").file_name().unwrap())
+        }
+        Some(path) => PathBuf::from(path),
+        None => default_backup_path("tbook"),
+    };
+    write_backup(db, &target)?;
+    println!(
+        "Backed up {} to {}",
+        describe(&db.summary()?),
+        target.display()
+    );
+    Ok(())
+}
+
+/// `tbook restore <path>`: checks the snapshot, shows what changes, keeps a copy of the
+/// current library, and then replaces it.
+pub fn restore(db: &mut Db, source: &str, assume_yes: bool) -> Result<()> {
+    let source = Path::new(source);
+    if !source.is_file() {
+        bail!("No backup at {}", source.display());
+    }
+    let snapshot = Db::open_read_only(source)?;
+    let problems = snapshot
+        .integrity_check()
+        .with_context(|| format!("{} is not a tbook database", source.display()))?;
+    if !problems.is_empty() {
+        bail!(
+            "{} failed the integrity check:\n  {}",
+            source.display(),
+            problems.join("\n  ")
+        );
+    }
+    let incoming = snapshot
+        .summary()
+        .with_context(|| format!("{} is not a tbook database", source.display()))?;
+    drop(snapshot);
+    let current = db.summary()?;
+
+    println!("Current library: {}", describe(&current));
+    println!("Backup:          {}", describe(&incoming));
+    let lost_books = current.books.saturating_sub(incoming.books);
+    let lost_annotations = current.annotations.saturating_sub(incoming.annotations);
+    if lost_books > 0 || lost_annotations > 0 {
+        println!(
+            "Restoring will drop {} books and {} annotations added since the backup.",
+            lost_books, lost_annotations
+        );
+    }
+
+    if !assume_yes && !confirm("Replace the current library?")? {
+        println!("Restore cancelled.");
+        return Ok(());
+    }
+
+    let safety = default_backup_path("pre-restore");
+    write_backup(db, &safety)?;
+    db.restore_from(source)?;
+    println!(
+        "Restored {}. The previous library was saved to {}",
+        source.display(),
+        safety.display()
+    );
+    Ok(())
+}
+
+/// `tbook relink <id> <path>`: points one book at a file by hand.
+pub fn relink_one(db: &Db, book_id: &str, path: &str) -> Result<()> {
+    let book_id: i32 = book_id
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid book id: {}", book_id))?;
+    let Some(book) = db.get_books()?.into_iter().find(|b| b.id == book_id) else {
+        bail!("No book with id {}", book_id);
+    };
+    let path = std::fs::canonicalize(path).with_context(|| format!("No file at {}", path))?;
+    let path = path.to_string_lossy();
+    if let Some(expected) = &book.content_hash {
+        if library::fingerprint(Path::new(path.as_ref()))?.1 != *expected {
+            println!("Note: {} differs from the file originally imported.", path);
+        }
+    }
+    library::relink_book(db, book_id, &path)?;
+    println!("Relinked \"{}\" to {}", book.title, path);
+    Ok(())
+}
+
+/// `tbook handoff [id] [--out <file>]`: prints where reading stopped in a book (the last
+/// one read by default) as one line, or writes it to a file.
+pub fn handoff(db: &Db, book_id: Option<&str>, out: Option<&str>) -> Result<()> {
+    let book = find_book(db, book_id)?;
+    let locator = book
+        .locator
+        .clone()
+        .unwrap_or_else(|| Locator::new(book.current_chapter, 0));
+    // The position is still worth sharing when the file can't be opened.
+    let quote = BookParser::open(&book.path)
+        .ok()
+        .and_then(|mut parser| {
+            library::quote_at(&mut parser, &locator, export::HANDOFF_QUOTE_WORDS)
+        })
+        .unwrap_or_default();
+    let snippet = export::handoff_snippet(&book, &quote);
+    write_or_print(&format!("{}\n", snippet), out)
+}
+
+/// `tbook export [id] [--format md|json|csv|html|org] [--out <file>] [--append|--timestamped]`:
+/// prints the notes of a book (the last one read by default), or writes them to a file.
+/// With a `mode` and no `--out`, they go to the usual notes file in `dir`.
+pub fn export(
+    db: &Db,
+    book_id: Option<&str>,
+    format: Option<&str>,
+    out: Option<&str>,
+    mode: Option<ExportMode>,
+    dir: &Path,
+    markers: &MarkdownMarkers,
+) -> Result<()> {
+    let format = match format {
+        Some(name) => ExportFormat::parse(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown export format: {} (use md, json, csv, html, or org)",
+                name
+            )
+        })?,
+        None => ExportFormat::default(),
+    };
+    let book = find_book(db, book_id)?;
+    let annotations = db.get_annotations(book.id)?;
+    let output = export::render_annotations(
+        format,
+        &book.title,
+        &book.author,
+        &book.path,
+        &annotations,
+        markers,
+    );
+    let path = match (out, mode) {
+        (Some(out), _) => PathBuf::from(out),
+        (None, Some(_)) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Could not create {}", dir.display()))?;
+            dir.join(export::notes_filename(&book.title, format))
+        }
+        (None, None) => return write_or_print(&output, None),
+    };
+    let mode = mode.unwrap_or_default();
+    let path = mode.path(&path, chrono::Local::now().naive_local());
+    export::write_export(&path, &output, mode)
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// `tbook digest [--week] [--out <file>]`: prints a Markdown digest of the last seven
+/// days of reading, or writes it to a file.
+pub fn digest(db: &Db, out: Option<&str>) -> Result<()> {
+    let today = chrono::Local::now().date_naive();
+    write_or_print(&Digest::gather(db, today)?.to_markdown(), out)
+}
+
+/// `tbook import-clippings <path>`: adds Kindle highlights and notes to the books they
+/// match and reports the titles left for review.
+pub fn import_clippings(db: &Db, path: &str) -> Result<()> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?;
+    let report = clippings::import_clippings(db, &text)?;
+    println!(
+        "Imported {} notes ({} already there)",
+        report.imported, report.duplicates
+    );
+    if report.unplaced > 0 {
+        println!(
+            "{} notes were not found in their book's text and are filed at its start",
+            report.unplaced
+        );
+    }
+    if !report.pending.is_empty() {
+        println!("No library book matched these titles:");
+        for (title, count) in &report.pending {
+            println!("  {} ({} notes)", title, count);
+        }
+        println!("Press K in the library to assign them to a book.");
+    }
+    Ok(())
+}
+
+/// The sidecar `tbook koreader-*` works with: the given path, or KOReader's own next to
+/// the book.
+fn sidecar_path(book: &BookRecord, sidecar: Option<&str>) -> PathBuf {
+    sidecar.map_or_else(|| koreader::sidecar_path(&book.path), PathBuf::from)
+}
+
+/// `tbook koreader-import [id] [--sidecar <path>]`: adds the highlights KOReader keeps
+/// for a book (the last one read by default) and moves to where KOReader stopped.
+pub fn koreader_import(db: &Db, book_id: Option<&str>, sidecar: Option<&str>) -> Result<()> {
+    let book = find_book(db, book_id)?;
+    let path = sidecar_path(&book, sidecar);
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let report = koreader::import_sidecar(db, &book, &text)?;
+    println!(
+        "Imported {} highlights into {} ({} already there)",
+        report.imported, book.title, report.duplicates
+    );
+    if report.unplaced > 0 {
+        println!(
+            "{} highlights were not found in the book's text and are filed at its start",
+            report.unplaced
+        );
+    }
+    if let Some(chapter) = report.position {
+        println!("Reading position moved to chapter {}", chapter + 1);
+    }
+    Ok(())
+}
+
+/// `tbook koreader-export [id] [--sidecar <path>]`: merges the notes and reading position
+/// of a book (the last one read by default) into its KOReader sidecar.
+pub fn koreader_export(db: &Db, book_id: Option<&str>, sidecar: Option<&str>) -> Result<()> {
+    let book = find_book(db, book_id)?;
+    let path = sidecar_path(&book, sidecar);
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    let report = koreader::export_sidecar(db, &book, existing.as_deref())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // KOReader's own settings share the sidecar, so a failed write must not leave it cut short.
+    export::write_export(&path, &report.sidecar, ExportMode::Overwrite)
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+    println!(
+        "Exported {} notes to {} ({} already there)",
+        report.exported,
+        path.display(),
+        report.duplicates
+    );
+    if report.unplaced > 0 {
+        println!(
+            "{} notes were left out because their text was not found in the book",
+            report.unplaced
+        );
+    }
+    Ok(())
+}
+
+/// `tbook index`: fills in word counts and the search index for books imported before
+/// either existed, or for every book with `all`. Sleeps `pause` between books so a large
+/// library can be indexed without hogging the machine.
+pub fn index(db: &Db, all: bool, pause: std::time::Duration) -> Result<()> {
+    let books: Vec<BookRecord> = if all {
+        db.get_books()?
+    } else {
+        let pending: Vec<i32> = db
+            .get_unindexed_books()?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        db.get_books()?
+            .into_iter()
+            .filter(|b| pending.contains(&b.id))
+            .collect()
+    };
+    if books.is_empty() {
+        println!("Every book is already indexed");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for (i, book) in books.iter().enumerate() {
+        if i > 0 {
+            std::thread::sleep(pause);
+        }
+        print!("[{}/{}] {}: ", i + 1, books.len(), book.title);
+        std::io::stdout().flush()?;
+        match library::index_book(db, book.id, &book.path) {
+            Ok(()) => {
+                let words = db.get_book(book.id)?.and_then(|b| b.total_words);
+                println!("{} words", words.unwrap_or(0));
+            }
+            Err(e) => {
+                failed += 1;
+                println!("failed ({})", e);
+            }
+        }
+    }
+    println!("Indexed {} books", books.len() - failed);
+    if failed > 0 {
+        println!(
+            "{} books could not be read; `tbook doctor` lists missing files",
+            failed
+        );
+    }
+    Ok(())
+}
+
+/// The book with the given id, or the last one read.
+fn find_book(db: &Db, book_id: Option<&str>) -> Result<BookRecord> {
+    match book_id {
+        Some(id) => {
+            let id: i32 = id
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid book id: {}", id))?;
+            db.get_book(id)?
+                .ok_or_else(|| anyhow::anyhow!("No book with id {}", id))
+        }
+        None => db
+            .get_last_read_book()?
+            .ok_or_else(|| anyhow::anyhow!("The library is empty")),
+    }
+}
+
+/// Writes `text` to `out` when given, and to stdout otherwise so it can be piped.
+fn write_or_print(text: &str, out: Option<&str>) -> Result<()> {
+    match out {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("Could not write {}", path))?;
+            println!("Wrote {}", path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// `tbook relink [dir]`: searches `dir` for the files of books whose path no longer
+/// exists, by content hash, and offers to point the books at them.
+pub fn relink_moved(db: &Db, root: &Path, assume_yes: bool) -> Result<()> {
+    library::backfill_fingerprints(db)?;
+    let missing = db
+        .get_books()?
+        .into_iter()
+        .filter(|b| !Path::new(&b.path).exists())
+        .count();
+    if missing == 0 {
+        println!("All book files are where the library expects them");
+        return Ok(());
+    }
+
+    println!("Searching {} for {} missing books...", root.display(), missing);
+    let relinks = library::find_moved_books(db, root)?;
+    if relinks.is_empty() {
+        println!("No matching files found. Use `tbook relink <id> <path>` to relink by hand.");
+        return Ok(());
+    }
+    for relink in &relinks {
+        println!("  [{}] {}", relink.book_id, relink.title);
+        println!("      {}\n   -> {}", relink.old_path, relink.new_path);
+    }
+    if !assume_yes && !confirm(&format!("Relink {} books?", relinks.len()))? {
+        println!("Relink cancelled.");
+        return Ok(());
+    }
+    for relink in &relinks {
+        library::relink_book(db, relink.book_id, &relink.new_path)?;
+    }
+    println!("Relinked {} books", relinks.len());
+    if relinks.len() < missing {
+        println!("{} books are still missing", missing - relinks.len());
+    }
+    Ok(())
+}
+
+/// `tbook doctor`: reports database corruption, dangling rows, and books whose files are
+/// gone, along with the progress and notes tied to them. Returns whether all is well.
+pub fn doctor(db: &Db) -> Result<bool> {
+    let mut healthy = true;
+
+    let problems = db.integrity_check()?;
+    if problems.is_empty() {
+        println!("Integrity check: ok");
+    } else {
+        healthy = false;
+        println!("Integrity check: {} problems", problems.len());
+        for p in &problems {
+            println!("  {}", p);
+        }
+    }
+
+    let summary = db.summary()?;
+    println!("Library: {}", describe(&summary));
+
+    let orphaned = db.orphaned_annotation_count()?;
+    if orphaned > 0 {
+        healthy = false;
+        println!(
+            "{} annotations belong to books that are no longer in the library",
+            orphaned
+        );
+    }
+
+    let counts = db.annotation_counts()?;
+    let missing: Vec<_> = db
+        .get_books()?
+        .into_iter()
+        .filter(|b| !Path::new(&b.path).exists())
+        .collect();
+    if missing.is_empty() {
+        println!("All {} book files are present", summary.books);
+    } else {
+        healthy = false;
+        println!(
+            "{} book files are missing (`tbook relink` can find moved ones):",
+            missing.len()
+        );
+        for book in &missing {
+            println!("  [{}] {} — {}", book.id, book.title, book.path);
+            println!(
+                "      would lose {:.0}% progress and {} annotations if removed",
+                book.progress() * 100.0,
+                counts.get(&book.id).copied().unwrap_or(0)
+            );
+        }
+    }
+
+    let unindexed = db.get_unindexed_books()?.len();
+    if unindexed > 0 {
+        println!(
+            "{} books are not in the search index yet (indexed on next start)",
+            unindexed
+        );
+    }
+    Ok(healthy)
+}
@@ -0,0 +1,481 @@
+//! Import of Kindle's `My Clippings.txt`.
+//!
+//! Each entry is a title line, a line describing the clipping (kind, page, location,
+//! date), a blank line, and the clipped text, followed by a `==========` separator.
+//! Notes are typed at the end of a highlight and share its location, so they are merged
+//! back into the highlight they belong to. Titles are matched against the library
+//! loosely; clippings whose book isn't found are kept in the database for review.
+
+use crate::db::{BookRecord, Db, NewAnnotation, PendingClippingRecord};
+use crate::library::{self, AnnotationKind};
+use crate::locator::{self, Locator};
+use crate::parser::BookParser;
+use anyhow::Result;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Tag put on every note imported from a Kindle.
+pub const KINDLE_TAG: &str = "kindle";
+
+/// Title score at or above which a book matches on its title alone.
+const MATCH_THRESHOLD: f64 = 0.8;
+/// Lower score that is enough when the author matches too.
+const AUTHOR_MATCH_THRESHOLD: f64 = 0.5;
+
+static LOCATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bloc(?:ation|\.)?\s*(\d+)(?:\s*-\s*(\d+))?").unwrap());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClippingKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+/// One entry of `My Clippings.txt`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clipping {
+    pub title: String,
+    pub author: String,
+    pub kind: ClippingKind,
+    /// First and last Kindle location, when the entry gives one.
+    pub location: Option<(u32, u32)>,
+    pub text: String,
+}
+
+/// A clipping ready to become an annotation: a highlight with the note typed on it, or
+/// a note on its own (kept as a summary).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KindleNote {
+    pub title: String,
+    pub author: String,
+    pub kind: AnnotationKind,
+    pub content: String,
+    pub note: Option<String>,
+}
+
+impl From<&PendingClippingRecord> for KindleNote {
+    fn from(clipping: &PendingClippingRecord) -> Self {
+        Self {
+            title: clipping.title.clone(),
+            author: clipping.author.clone(),
+            kind: AnnotationKind::from_str(&clipping.kind),
+            content: clipping.content.clone(),
+            note: clipping.note.clone(),
+        }
+    }
+}
+
+/// What an import did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClippingImport {
+    /// Notes added to library books.
+    pub imported: usize,
+    /// Notes skipped because they were already imported or already waiting for review.
+    pub duplicates: usize,
+    /// Added notes whose text wasn't found in the book; they sit at its start.
+    pub unplaced: usize,
+    /// Notes kept for review because no book matched, per Kindle title.
+    pub pending: Vec<(String, usize)>,
+}
+
+/// Splits `Title (Author)` into its parts. The author is the last parenthesized group,
+/// so series names in the title survive, e.g. `Dune (Dune Chronicles) (Frank Herbert)`.
+fn split_title(line: &str) -> (String, String) {
+    let line = line.trim();
+    if let Some(inner) = line.strip_suffix(')') {
+        let mut depth = 0;
+        for (i, c) in inner.char_indices().rev() {
+            match c {
+                ')' => depth += 1,
+                '(' if depth == 0 => {
+                    return (
+                        inner[..i].trim().to_string(),
+                        inner[i + 1..].trim().to_string(),
+                    );
+                }
+                '(' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    (line.to_string(), String::new())
+}
+
+/// `Loc. 180-83` in older files abbreviates the end to the digits that changed.
+fn parse_location(meta: &str) -> Option<(u32, u32)> {
+    let caps = LOCATION.captures(meta)?;
+    let start_digits = &caps[1];
+    let start: u32 = start_digits.parse().ok()?;
+    let end = match caps.get(2) {
+        Some(end) if end.as_str().len() < start_digits.len() => {
+            let prefix = &start_digits[..start_digits.len() - end.as_str().len()];
+            format!("{}{}", prefix, end.as_str()).parse().ok()?
+        }
+        Some(end) => end.as_str().parse().ok()?,
+        None => start,
+    };
+    Some((start, end.max(start)))
+}
+
+/// Reads every highlight, note, and bookmark; entries in other languages or otherwise
+/// unrecognized are skipped.
+pub fn parse_clippings(text: &str) -> Vec<Clipping> {
+    let mut clippings = Vec::new();
+    let mut entry: Vec<&str> = Vec::new();
+    let lines = text
+        .lines()
+        .map(|l| l.trim_start_matches('\u{feff}').trim_end());
+    for line in lines.chain(std::iter::once("==========")) {
+        if line.trim() != "==========" {
+            entry.push(line);
+            continue;
+        }
+        let mut rest = entry.drain(..).skip_while(|l| l.trim().is_empty());
+        let (Some(header), Some(meta)) = (rest.next(), rest.next()) else {
+            continue;
+        };
+        let lower = meta.to_lowercase();
+        let kind = if lower.contains("highlight") {
+            ClippingKind::Highlight
+        } else if lower.contains("note") {
+            ClippingKind::Note
+        } else if lower.contains("bookmark") {
+            ClippingKind::Bookmark
+        } else {
+            continue;
+        };
+        let (title, author) = split_title(header);
+        clippings.push(Clipping {
+            title,
+            author,
+            kind,
+            location: parse_location(meta),
+            text: rest.collect::<Vec<_>>().join("\n").trim().to_string(),
+        });
+    }
+    clippings
+}
+
+/// Turns clippings into notes: each Kindle note is attached to the latest highlight of
+/// the same book that covers its location, highlights that were later extended are
+/// dropped in favor of the longer one, and bookmarks and empty entries are skipped.
+pub fn merge_notes(clippings: &[Clipping]) -> Vec<KindleNote> {
+    let mut notes: Vec<(KindleNote, Option<(u32, u32)>)> = Vec::new();
+    for clipping in clippings {
+        if clipping.text.is_empty() {
+            continue;
+        }
+        match clipping.kind {
+            ClippingKind::Bookmark => {}
+            ClippingKind::Highlight => {
+                let extended = notes.iter().position(|(n, loc)| {
+                    n.kind == AnnotationKind::Highlight
+                        && n.title == clipping.title
+                        && overlaps(*loc, clipping.location)
+                        && clipping.text.contains(&n.content)
+                });
+                let note = extended.and_then(|i| notes.remove(i).0.note);
+                notes.push((
+                    KindleNote {
+                        title: clipping.title.clone(),
+                        author: clipping.author.clone(),
+                        kind: AnnotationKind::Highlight,
+                        content: clipping.text.clone(),
+                        note,
+                    },
+                    clipping.location,
+                ));
+            }
+            ClippingKind::Note => {
+                let target = notes.iter_mut().rev().find(|(n, loc)| {
+                    n.kind == AnnotationKind::Highlight
+                        && n.note.is_none()
+                        && n.title == clipping.title
+                        && overlaps(*loc, clipping.location)
+                });
+                match target {
+                    Some((highlight, _)) => highlight.note = Some(clipping.text.clone()),
+                    None => notes.push((
+                        KindleNote {
+                            title: clipping.title.clone(),
+                            author: clipping.author.clone(),
+                            kind: AnnotationKind::Summary,
+                            content: clipping.text.clone(),
+                            note: None,
+                        },
+                        clipping.location,
+                    )),
+                }
+            }
+        }
+    }
+    notes.into_iter().map(|(note, _)| note).collect()
+}
+
+fn overlaps(a: Option<(u32, u32)>, b: Option<(u32, u32)>) -> bool {
+    match (a, b) {
+        (Some((a_start, a_end)), Some((b_start, b_end))) => a_start <= b_end && b_start <= a_end,
+        _ => false,
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !matches!(*w, "a" | "an" | "the"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The title without its subtitle or a trailing series or edition note.
+fn core_title(title: &str) -> &str {
+    let end = title.find([':', '(', '[']).unwrap_or(title.len());
+    match title[..end].trim() {
+        "" => title,
+        core => core,
+    }
+}
+
+/// Dice coefficient of the two word sets, from 0 to 1.
+fn word_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.iter().filter(|w| b.contains(w)).count();
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+/// How alike a Kindle title and a library title are, from 0 to 1, comparing them both
+/// whole and without subtitles.
+pub fn title_score(kindle_title: &str, book_title: &str) -> f64 {
+    word_similarity(kindle_title, book_title).max(word_similarity(
+        core_title(kindle_title),
+        core_title(book_title),
+    ))
+}
+
+/// Whether the two author fields share a name; Kindle often writes `Last, First`.
+fn same_author(kindle_author: &str, book_author: &str) -> bool {
+    let book = words(book_author);
+    words(kindle_author)
+        .iter()
+        .any(|w| w.chars().count() > 2 && book.contains(w))
+}
+
+/// The library book a Kindle title most likely belongs to, if any is close enough.
+pub fn match_book<'a>(
+    title: &str,
+    author: &str,
+    books: &'a [BookRecord],
+) -> Option<&'a BookRecord> {
+    books
+        .iter()
+        .filter_map(|book| {
+            let score = title_score(title, &book.title);
+            let author_matches = same_author(author, &book.author);
+            let good =
+                score >= MATCH_THRESHOLD || (author_matches && score >= AUTHOR_MATCH_THRESHOLD);
+            good.then_some((book, score + if author_matches { 0.1 } else { 0.0 }))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(book, _)| book)
+}
+
+/// Index of the book a pending clipping is most likely from, even when no book is close
+/// enough to match on its own; `None` for an empty library.
+pub fn best_guess(title: &str, author: &str, books: &[BookRecord]) -> Option<usize> {
+    let score = |book: &BookRecord| {
+        title_score(title, &book.title)
+            + if same_author(author, &book.author) {
+                0.5
+            } else {
+                0.0
+            }
+    };
+    (0..books.len()).max_by(|a, b| score(&books[*a]).total_cmp(&score(&books[*b])))
+}
+
+/// Start and end locators of `text` in the first chapter that contains it.
+fn place(chapters: &[(usize, String)], text: &str) -> Option<(Locator, Locator)> {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let last_word = collapsed.rsplit(' ').next()?.chars().count();
+    chapters.iter().find_map(|(chapter, chapter_text)| {
+        let start = locator::find_offset(chapter_text, &collapsed)?;
+        let end = start + collapsed.chars().count() - last_word;
+        Some((Locator::new(*chapter, start), Locator::new(*chapter, end)))
+    })
+}
+
+/// Adds notes to `book`, placed where their text appears in it and tagged `kindle`.
+fn add_to_book(
+    db: &Db,
+    book: &BookRecord,
+    notes: &[KindleNote],
+    report: &mut ClippingImport,
+) -> Result<()> {
+    // A missing file still takes the notes, just without positions.
+    let chapters = BookParser::open(&book.path)
+        .map(|mut parser| library::chapter_texts(&mut parser))
+        .unwrap_or_default();
+    let tags = [KINDLE_TAG.to_string()];
+    for note in notes {
+        if db.has_annotation(book.id, note.kind.as_str(), &note.content)? {
+            report.duplicates += 1;
+            continue;
+        }
+        let placed = place(&chapters, &note.content);
+        if placed.is_none() {
+            report.unplaced += 1;
+        }
+        let chapter = placed.as_ref().map_or(0, |(start, _)| start.spine);
+        let (start, end) = placed
+            .map(|(start, end)| (start.to_string(), end.to_string()))
+            .unzip();
+        let id = db.add_annotation(&NewAnnotation {
+            book_id: book.id,
+            chapter,
+            content: &note.content,
+            note: note.note.as_deref(),
+            kind: note.kind.as_str(),
+            start_locator: start.as_deref(),
+            end_locator: end.as_deref(),
+            ..Default::default()
+        })?;
+        db.set_annotation_tags(id, &tags)?;
+        report.imported += 1;
+    }
+    Ok(())
+}
+
+/// Imports the contents of a `My Clippings.txt`. Notes go to the library book their
+/// title matches; the rest wait in the database until assigned with [`assign_pending`].
+/// Importing the same file again skips what is already there.
+pub fn import_clippings(db: &Db, text: &str) -> Result<ClippingImport> {
+    let books = db.get_books()?;
+    let mut report = ClippingImport::default();
+    let notes = merge_notes(&parse_clippings(text));
+
+    let mut titles: Vec<(&str, &str)> = Vec::new();
+    for note in &notes {
+        if !titles.contains(&(note.title.as_str(), note.author.as_str())) {
+            titles.push((&note.title, &note.author));
+        }
+    }
+    for (title, author) in titles {
+        let group: Vec<KindleNote> = notes
+            .iter()
+            .filter(|n| n.title == title && n.author == author)
+            .cloned()
+            .collect();
+        match match_book(title, author, &books) {
+            Some(book) => add_to_book(db, book, &group, &mut report)?,
+            None => {
+                let mut waiting = 0;
+                for note in &group {
+                    let kind = note.kind.as_str();
+                    let note_text = note.note.as_deref();
+                    if db.add_pending_clipping(title, author, kind, &note.content, note_text)? {
+                        waiting += 1;
+                    } else {
+                        report.duplicates += 1;
+                    }
+                }
+                if waiting > 0 {
+                    report.pending.push((title.to_string(), waiting));
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Moves pending clippings into `book` and off the review list.
+pub fn assign_pending(
+    db: &Db,
+    clippings: &[PendingClippingRecord],
+    book: &BookRecord,
+) -> Result<ClippingImport> {
+    let mut report = ClippingImport::default();
+    let notes: Vec<KindleNote> = clippings.iter().map(KindleNote::from).collect();
+    add_to_book(db, book, &notes, &mut report)?;
+    let ids: Vec<i32> = clippings.iter().map(|c| c.id).collect();
+    db.remove_pending_clippings(&ids)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\u{feff}Dune (Dune Chronicles, Book 1) (Herbert, Frank)\r
+- Your Highlight on page 12 | Location 180-183 | Added on Monday, 1 January 2024 12:00:00\r
+\r
+I must not fear.\r
+==========\r
+\u{feff}Dune (Dune Chronicles, Book 1) (Herbert, Frank)\r
+- Your Highlight on page 12 | Location 180-185 | Added on Monday, 1 January 2024 12:01:00\r
+\r
+I must not fear. Fear is the mind-killer.\r
+==========\r
+Dune (Dune Chronicles, Book 1) (Herbert, Frank)\r
+- Your Note on page 12 | Location 185 | Added on Monday, 1 January 2024 12:02:00\r
+\r
+the litany\r
+==========\r
+Dune (Dune Chronicles, Book 1) (Herbert, Frank)\r
+- Your Bookmark on page 40 | Location 600 | Added on Monday, 1 January 2024 12:03:00\r
+\r
+\r
+==========\r
+Meditations (Marcus Aurelius)\r
+- Highlight Loc. 1021-25  | Added on Tuesday, 2 January 2024 08:00:00\r
+\r
+Waste no more time.\r
+==========\r
+Meditations (Marcus Aurelius)\r
+- Your Note Location 2000 | Added on Tuesday, 2 January 2024 08:05:00\r
+\r
+reread book 4\r
+==========\r
+";
+
+    #[test]
+    fn parses_every_entry_kind() {
+        let clippings = parse_clippings(SAMPLE);
+        assert_eq!(clippings.len(), 6);
+        assert_eq!(clippings[0].title, "Dune (Dune Chronicles, Book 1)");
+        assert_eq!(clippings[0].author, "Herbert, Frank");
+        assert_eq!(clippings[0].location, Some((180, 183)));
+        assert_eq!(clippings[2].kind, ClippingKind::Note);
+        assert_eq!(clippings[3].kind, ClippingKind::Bookmark);
+        assert_eq!(clippings[4].location, Some((1021, 1025)));
+        assert_eq!(clippings[4].text, "Waste no more time.");
+    }
+
+    #[test]
+    fn notes_join_their_highlight_and_extended_highlights_replace_the_old_one() {
+        let notes = merge_notes(&parse_clippings(SAMPLE));
+        assert_eq!(notes.len(), 3);
+        assert_eq!(
+            notes[0].content,
+            "I must not fear. Fear is the mind-killer."
+        );
+        assert_eq!(notes[0].note.as_deref(), Some("the litany"));
+        assert_eq!(notes[1].kind, AnnotationKind::Highlight);
+        // A note away from any highlight stands alone.
+        assert_eq!(notes[2].kind, AnnotationKind::Summary);
+        assert_eq!(notes[2].content, "reread book 4");
+    }
+
+    #[test]
+    fn titles_match_loosely() {
+        assert!(title_score("Dune (Dune Chronicles, Book 1)", "Dune") >= MATCH_THRESHOLD);
+        assert!(title_score("Meditations: A New Translation", "Meditations") >= MATCH_THRESHOLD);
+        assert!(title_score("The Odyssey", "Odyssey") >= MATCH_THRESHOLD);
+        assert!(title_score("Moby Dick", "Dune") < AUTHOR_MATCH_THRESHOLD);
+        assert!(same_author("Herbert, Frank", "Frank Herbert"));
+        assert!(!same_author("Marcus Aurelius", "Frank Herbert"));
+    }
+}
@@ -1,6 +1,10 @@
 use config::{Config, ConfigError, File};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Warn before importing files above this size unless the config says otherwise.
+const DEFAULT_WARN_SIZE_MB: u64 = 200;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AppConfig {
@@ -12,6 +16,134 @@ pub struct AppConfig {
     pub daily_goal_words: usize,
     pub pomodoro_work_minutes: u64,
     pub pomodoro_break_minutes: u64,
+    /// Flashcards of due words and the open book's questions when a pomodoro break starts.
+    pub pomodoro_break_review: Option<bool>,
+    /// Minutes without a key press or click before reading pauses: the sitting ends, and
+    /// auto-scroll and a pomodoro focus phase stop until the next input. Five when unset.
+    pub idle_minutes: Option<u64>,
+    /// Local command used for chapter/highlight summaries (prompt on stdin).
+    pub llm_command: Option<String>,
+    /// OpenAI-compatible chat completions endpoint, used when no command is set.
+    pub llm_endpoint: Option<String>,
+    pub llm_model: Option<String>,
+    /// Default lookup language (e.g. "en", "es"); books can override it.
+    pub dictionary_language: Option<String>,
+    /// Default dictionary: "free", "wiktionary", or a URL template with `{word}`/`{lang}`.
+    pub dictionary: Option<String>,
+    /// Folder scans skip files smaller than this many KiB.
+    pub import_min_size_kb: Option<u64>,
+    /// Folder scans skip files larger than this many MiB.
+    pub import_max_size_mb: Option<u64>,
+    /// Importing anything larger than this many MiB asks for confirmation first.
+    pub import_warn_size_mb: Option<u64>,
+    /// Filename patterns skipped by folder scans, e.g. "*sample*" (`*` and `?` wildcards).
+    pub import_skip_patterns: Option<Vec<String>>,
+    /// Blank rows after each paragraph; books can override it.
+    pub paragraph_spacing: Option<u16>,
+    /// Columns the first line of each paragraph is indented; books can override it.
+    pub first_line_indent: Option<u16>,
+    /// Widens the spaces of wrapped lines so paragraphs have an even right edge.
+    pub justify: Option<bool>,
+    /// Side-by-side text columns in the reader, where the terminal is wide enough; one
+    /// when unset. Layout profiles can set their own.
+    pub columns: Option<u16>,
+    /// Reader line focus: "off", "cursor" (the line being read), or "middle" of the screen.
+    pub line_focus: Option<String>,
+    /// Line focus strength in percent per theme name, e.g. `{ gruvbox = 20 }`.
+    pub line_focus_intensity: Option<HashMap<String, u8>>,
+    /// Reader line-number gutter: "off", "absolute", or "relative" to the current line.
+    pub line_numbers: Option<String>,
+    /// Gauges of the way through the chapter and the book above the reader status bar:
+    /// "off", "on", or "always" to keep them in focus mode.
+    pub progress_gauge: Option<String>,
+    /// Whether focus mode keeps the line being read in the middle of the screen; on when unset.
+    pub typewriter_scrolling: Option<bool>,
+    /// What focus mode dims: "off", "line" (all but the middle line), or "band" (all but
+    /// three lines around it).
+    pub focus_ruler: Option<String>,
+    /// Milliseconds the library waits after the selection moves before loading its cover;
+    /// 150 when unset.
+    pub cover_debounce_ms: Option<u64>,
+    /// Longest side, in pixels, covers are shrunk to after decoding; 640 when unset.
+    pub cover_max_dimension: Option<u32>,
+    /// Books on each side of the selection whose covers load ahead; none when unset.
+    pub cover_prefetch: Option<usize>,
+    /// Extra annotation kinds beyond highlight/question/summary, in select-mode key order.
+    pub annotation_kinds: Option<Vec<AnnotationKindConfig>>,
+    /// Cleanup rules for book text, such as stripping watermarks, in the order they run.
+    pub text_filters: Option<Vec<TextFilterConfig>>,
+    /// Layouts for particular terminals; the first one that matches at startup is used.
+    pub layout_profiles: Option<Vec<LayoutProfile>>,
+    /// No images or animations and fewer redraws, for slow SSH links or running on battery.
+    pub light_mode: Option<bool>,
+    /// Colors the terminal can show: "truecolor", "256", "16", "mono", or "auto" (the
+    /// default) to go by `COLORTERM` and `TERM`.
+    pub color_depth: Option<String>,
+    /// Captures the mouse for clicks and the wheel; off leaves the terminal's own text
+    /// selection working. On when unset.
+    pub mouse: Option<bool>,
+    /// Colors of the reader's top bar and its buttons.
+    pub top_bar: Option<TopBarConfig>,
+    /// Hour (0-23) from which colors and images get a warm tint for late reading.
+    pub night_tint_start: Option<u32>,
+    /// Hour the warm tint ends; 6 when unset.
+    pub night_tint_end: Option<u32>,
+    /// Strength of the warm tint in percent; 40 when unset.
+    pub night_tint_strength: Option<u8>,
+    /// Folder notes are exported to; the working directory when unset.
+    pub export_dir: Option<String>,
+    /// What an export does with a notes file already there: "overwrite" (the default),
+    /// "append", or "timestamped" to write a new file each time.
+    pub export_mode: Option<String>,
+    /// Obsidian vault folder where each book's note is rewritten whenever a note is added.
+    pub export_vault_path: Option<String>,
+    /// Markdown marker per annotation kind, e.g. `{ highlight = "=={text}==", question = "?> " }`.
+    pub markdown_markers: Option<HashMap<String, String>>,
+    /// KOReader progress sync server, e.g. "https://sync.koreader.rocks".
+    pub kosync_server: Option<String>,
+    pub kosync_username: Option<String>,
+    pub kosync_password: Option<String>,
+    /// Name KOReader shows for positions synced from tbook; "tbook" when unset.
+    pub kosync_device: Option<String>,
+    /// Name a reading group sees on positions shared from here; the login name when unset.
+    pub group_name: Option<String>,
+    /// Secret a reading group shares to sign positions. Without one, positions are neither
+    /// shared nor read.
+    pub group_secret: Option<String>,
+}
+
+/// A user-defined annotation kind, e.g. `{ name = "definition", color = [200, 120, 40] }`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct AnnotationKindConfig {
+    pub name: String,
+    pub color: [u8; 3],
+}
+
+/// The reader's top bar, e.g. `{ background = [40, 40, 40], buttons = false }`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TopBarConfig {
+    pub background: Option<[u8; 3]>,
+    pub foreground: Option<[u8; 3]>,
+    /// Color of the `[ - ]` button.
+    pub minus: Option<[u8; 3]>,
+    /// Color of the `[ + ]` button.
+    pub plus: Option<[u8; 3]>,
+    /// Whether the buttons show; when unset they do only while the mouse is captured.
+    pub buttons: Option<bool>,
+}
+
+/// A rule run over book text as chapters load, e.g.
+/// `{ pattern = "OceanofPDF\\.com", books = "*" }`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TextFilterConfig {
+    /// Regular expression searched for in each line of text.
+    pub pattern: String,
+    /// What matches become, with `$1` for groups; they are removed when unset.
+    pub replace: Option<String>,
+    /// Drops whole paragraphs that match, such as a promo blurb repeated every chapter.
+    pub drop: Option<bool>,
+    /// Books it is for, by title or file name with `*` and `?` wildcards; all when unset.
+    pub books: Option<String>,
 }
 
 impl Default for AppConfig {
@@ -28,6 +160,50 @@ impl Default for AppConfig {
             daily_goal_words: 1500,
             pomodoro_work_minutes: 25,
             pomodoro_break_minutes: 5,
+            pomodoro_break_review: None,
+            idle_minutes: None,
+            llm_command: None,
+            llm_endpoint: None,
+            llm_model: None,
+            dictionary_language: None,
+            dictionary: None,
+            import_min_size_kb: None,
+            import_max_size_mb: None,
+            import_warn_size_mb: None,
+            import_skip_patterns: None,
+            paragraph_spacing: None,
+            first_line_indent: None,
+            justify: None,
+            columns: None,
+            line_focus: None,
+            line_focus_intensity: None,
+            line_numbers: None,
+            progress_gauge: None,
+            typewriter_scrolling: None,
+            focus_ruler: None,
+            cover_debounce_ms: None,
+            cover_max_dimension: None,
+            cover_prefetch: None,
+            annotation_kinds: None,
+            text_filters: None,
+            layout_profiles: None,
+            light_mode: None,
+            color_depth: None,
+            mouse: None,
+            top_bar: None,
+            night_tint_start: None,
+            night_tint_end: None,
+            night_tint_strength: None,
+            export_dir: None,
+            export_mode: None,
+            export_vault_path: None,
+            markdown_markers: None,
+            kosync_server: None,
+            kosync_username: None,
+            kosync_password: None,
+            kosync_device: None,
+            group_name: None,
+            group_secret: None,
         }
     }
 }
@@ -35,7 +211,7 @@ impl Default for AppConfig {
 impl AppConfig {
     pub fn load() -> Result<Self, ConfigError> {
         let s = Config::builder()
-            .add_source(File::with_name("tbook").required(false))
+            .add_source(File::from(config_path()).required(false))
             .build()?;
         s.try_deserialize()
     }
@@ -43,7 +219,206 @@ impl AppConfig {
     #[allow(dead_code)]
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
         let toml = toml::to_string(self)?;
-        std::fs::write("tbook.toml", toml)?;
+        let path = config_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml)?;
         Ok(())
     }
 }
+
+/// A named reader layout, chosen by terminal size (in cells) or hostname.
+/// Unset conditions match anything; unset settings keep the global ones.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct LayoutProfile {
+    pub name: String,
+    pub hostname: Option<String>,
+    pub min_term_width: Option<u16>,
+    pub max_term_width: Option<u16>,
+    pub min_term_height: Option<u16>,
+    pub max_term_height: Option<u16>,
+    pub margin: Option<u16>,
+    /// Widest a column of text may get, in cells; narrower text is centered.
+    pub max_width: Option<u16>,
+    /// Text columns side by side in the reader.
+    pub columns: Option<u16>,
+    /// Tallest an image may get, in pixels, so the cap follows the font size.
+    pub image_max_height_px: Option<u32>,
+}
+
+impl LayoutProfile {
+    pub fn matches(&self, width: u16, height: u16, hostname: &str) -> bool {
+        self.hostname
+            .as_ref()
+            .is_none_or(|h| h.eq_ignore_ascii_case(hostname))
+            && self.min_term_width.is_none_or(|min| width >= min)
+            && self.max_term_width.is_none_or(|max| width <= max)
+            && self.min_term_height.is_none_or(|min| height >= min)
+            && self.max_term_height.is_none_or(|max| height <= max)
+    }
+}
+
+/// The first profile matching this terminal.
+pub fn pick_layout_profile<'a>(
+    profiles: &'a [LayoutProfile],
+    width: u16,
+    height: u16,
+    hostname: &str,
+) -> Option<&'a LayoutProfile> {
+    profiles.iter().find(|p| p.matches(width, height, hostname))
+}
+
+/// This machine's name, or an empty string when it can't be found.
+pub fn hostname() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// `$XDG_DATA_HOME/tbook` (or the platform equivalent), for the library database and articles.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tbook")
+}
+
+/// `$XDG_CONFIG_HOME/tbook` (or the platform equivalent).
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tbook")
+}
+
+pub fn default_db_path() -> PathBuf {
+    data_dir().join("tbook.db")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("tbook.toml")
+}
+
+/// Moves a file that older versions kept in the working directory to its new home,
+/// unless one already exists there. Returns whether anything was moved.
+pub fn migrate_legacy_file(legacy: &Path, target: &Path) -> std::io::Result<bool> {
+    if !legacy.is_file() || target.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(legacy, target).is_err() {
+        // Renaming fails across filesystems; copy and remove instead.
+        std::fs::copy(legacy, target)?;
+        std::fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+/// Size limits and filename patterns applied when scanning folders for books.
+#[derive(Clone, Debug)]
+pub struct ImportRules {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub warn_size: u64,
+    pub skip_patterns: Vec<String>,
+}
+
+impl Default for ImportRules {
+    fn default() -> Self {
+        Self {
+            min_size: None,
+            max_size: None,
+            warn_size: DEFAULT_WARN_SIZE_MB * 1024 * 1024,
+            skip_patterns: Vec::new(),
+        }
+    }
+}
+
+impl ImportRules {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            min_size: config.import_min_size_kb.map(|kb| kb * 1024),
+            max_size: config.import_max_size_mb.map(|mb| mb * 1024 * 1024),
+            warn_size: config.import_warn_size_mb.unwrap_or(DEFAULT_WARN_SIZE_MB) * 1024 * 1024,
+            skip_patterns: config.import_skip_patterns.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn skips(&self, path: &Path, size: u64) -> bool {
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return true;
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.skip_patterns
+            .iter()
+            .any(|p| wildcard_match(&p.to_lowercase(), &name))
+    }
+
+    pub fn is_large(&self, size: u64) -> bool {
+        size > self.warn_size
+    }
+}
+
+/// Matches `text` against a pattern where `*` is any run of characters and `?` is one character.
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the last `*` swallow one more character and retry.
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_matching_layout_profile_wins() {
+        let profile = |name: &str| LayoutProfile {
+            name: name.to_string(),
+            ..LayoutProfile::default()
+        };
+        let profiles = vec![
+            LayoutProfile {
+                min_term_width: Some(200),
+                ..profile("desktop")
+            },
+            LayoutProfile {
+                hostname: Some("Laptop".to_string()),
+                max_term_height: Some(40),
+                ..profile("laptop")
+            },
+            profile("fallback"),
+        ];
+        let pick = |w, h, host| pick_layout_profile(&profiles, w, h, host).map(|p| p.name.as_str());
+        assert_eq!(pick(240, 70, "tower"), Some("desktop"));
+        assert_eq!(pick(100, 30, "laptop"), Some("laptop"));
+        assert_eq!(pick(100, 50, "laptop"), Some("fallback"));
+        assert_eq!(pick_layout_profile(&profiles[..2], 100, 30, "tower"), None);
+    }
+}
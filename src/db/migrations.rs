@@ -0,0 +1,436 @@
+//! Versioned schema migrations.
+//!
+//! Each migration runs once, in order, inside a transaction that also bumps
+//! `schema_version`. Migrations are written to be idempotent so databases created
+//! before versioning existed (version 0) upgrade cleanly. Append new migrations to
+//! the end of `MIGRATIONS`; never edit or reorder ones that have shipped.
+
+use rusqlite::{Connection, Result};
+
+type Migration = fn(&Connection) -> Result<()>;
+
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("initial schema", initial_schema),
+    ("annotation kinds", annotation_kinds),
+    ("annotation links", annotation_links),
+    ("search history", search_history),
+    ("full-text index", full_text_index),
+    ("book lookup settings", book_lookup_settings),
+    ("scan paths", scan_paths),
+    ("reading status", reading_status),
+    ("page counts", page_counts),
+    ("locators", locators),
+    ("content hashes", content_hashes),
+    ("reading sittings", reading_sittings),
+    ("word counts", word_counts),
+    ("book typography", book_typography),
+    ("annotation tags", annotation_tags),
+    ("pending clippings", pending_clippings),
+    ("vocabulary review", vocabulary_review),
+    ("vocabulary sources", vocabulary_sources),
+    ("paged reading", paged_reading),
+    ("reading presets", reading_presets),
+    ("vocabulary added dates", vocabulary_added_dates),
+    ("annotation attachments", annotation_attachments),
+    ("book tags", book_tags),
+    ("last stops", last_stops),
+    ("book sessions", book_sessions),
+];
+
+pub fn run(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+    let current = current_version(conn)?;
+
+    for (idx, (_name, migrate)) in MIGRATIONS.iter().enumerate() {
+        let version = idx as i32 + 1;
+        if version <= current {
+            continue;
+        }
+        let tx = conn.transaction()?;
+        migrate(&tx)?;
+        tx.execute("DELETE FROM schema_version", [])?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            [version],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn current_version(conn: &Connection) -> Result<i32> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Adds `column` to `table` unless an older build already created it.
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for existing in columns {
+        if existing? == column {
+            return Ok(());
+        }
+    }
+    conn.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+        [],
+    )?;
+    Ok(())
+}
+
+fn initial_schema(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS books (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            author TEXT,
+            path TEXT NOT NULL UNIQUE,
+            current_chapter INTEGER DEFAULT 0,
+            current_line INTEGER DEFAULT 0,
+            total_chapters INTEGER DEFAULT 0,
+            total_lines INTEGER DEFAULT 0,
+            lines_read INTEGER DEFAULT 0,
+            last_read TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS annotations (
+            id INTEGER PRIMARY KEY,
+            book_id INTEGER NOT NULL,
+            chapter INTEGER NOT NULL,
+            start_line INTEGER NOT NULL,
+            start_word INTEGER NOT NULL,
+            end_line INTEGER NOT NULL,
+            end_word INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            note TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vocabulary (
+            id INTEGER PRIMARY KEY,
+            word TEXT NOT NULL UNIQUE,
+            definition TEXT,
+            lookup_count INTEGER DEFAULT 1,
+            last_lookup TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reading_sessions (
+            id INTEGER PRIMARY KEY,
+            book_id INTEGER NOT NULL,
+            date TEXT NOT NULL,
+            words_read INTEGER DEFAULT 0,
+            UNIQUE(book_id, date),
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn annotation_kinds(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "annotations", "kind", "TEXT DEFAULT 'highlight'")
+}
+
+fn annotation_links(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS annotation_links (
+            from_id INTEGER NOT NULL,
+            to_id INTEGER NOT NULL,
+            PRIMARY KEY(from_id, to_id),
+            FOREIGN KEY(from_id) REFERENCES annotations(id),
+            FOREIGN KEY(to_id) REFERENCES annotations(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn search_history(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS search_history (
+            id INTEGER PRIMARY KEY,
+            query TEXT NOT NULL UNIQUE,
+            searched_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved_searches (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            query TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn full_text_index(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS chapters_fts USING fts5(
+            content,
+            book_id UNINDEXED,
+            chapter UNINDEXED,
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS fts_indexed_books (
+            book_id INTEGER PRIMARY KEY,
+            indexed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn book_lookup_settings(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "language", "TEXT")?;
+    add_column_if_missing(conn, "books", "dictionary", "TEXT")
+}
+
+fn scan_paths(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scan_paths (
+            path TEXT PRIMARY KEY,
+            scanned_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn reading_status(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "status", "TEXT DEFAULT 'to-read'")?;
+    // Books that already have progress are being read.
+    conn.execute(
+        "UPDATE books SET status = 'reading'
+         WHERE status = 'to-read' AND (lines_read > 0 OR current_chapter > 0 OR current_line > 0)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn page_counts(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "total_pages", "INTEGER")?;
+    // PDF and DjVu books already store one chapter per page.
+    conn.execute(
+        "UPDATE books SET total_pages = total_chapters
+         WHERE total_pages IS NULL
+           AND (lower(path) LIKE '%.pdf' OR lower(path) LIKE '%.djvu' OR lower(path) LIKE '%.djv')",
+        [],
+    )?;
+    Ok(())
+}
+
+fn locators(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "locator", "TEXT")?;
+    add_column_if_missing(conn, "annotations", "start_locator", "TEXT")?;
+    add_column_if_missing(conn, "annotations", "end_locator", "TEXT")
+}
+
+fn content_hashes(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "file_size", "INTEGER")?;
+    add_column_if_missing(conn, "books", "content_hash", "TEXT")
+}
+
+fn reading_sittings(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reading_sittings (
+            id INTEGER PRIMARY KEY,
+            book_id INTEGER NOT NULL,
+            started_at TEXT NOT NULL,
+            ended_at TEXT NOT NULL,
+            duration_secs INTEGER NOT NULL,
+            words_read INTEGER DEFAULT 0,
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn word_counts(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "total_words", "INTEGER")?;
+    add_column_if_missing(conn, "books", "position_words", "INTEGER DEFAULT 0")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chapter_words (
+            book_id INTEGER NOT NULL,
+            chapter INTEGER NOT NULL,
+            words INTEGER NOT NULL,
+            PRIMARY KEY(book_id, chapter),
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn book_typography(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "paragraph_spacing", "INTEGER")?;
+    add_column_if_missing(conn, "books", "first_line_indent", "INTEGER")
+}
+
+fn annotation_tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS annotation_tags (
+            annotation_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY(annotation_id, tag),
+            FOREIGN KEY(annotation_id) REFERENCES annotations(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS annotation_tags_tag ON annotation_tags(tag)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn pending_clippings(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_clippings (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            author TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            content TEXT NOT NULL,
+            note TEXT,
+            UNIQUE(title, kind, content)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Spaced-repetition state per word; `due` is a `YYYY-MM-DD` day, NULL until the
+/// first review.
+fn vocabulary_review(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "vocabulary", "ease", "REAL DEFAULT 2.5")?;
+    add_column_if_missing(conn, "vocabulary", "interval_days", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "vocabulary", "repetitions", "INTEGER DEFAULT 0")?;
+    add_column_if_missing(conn, "vocabulary", "due", "TEXT")
+}
+
+/// The sentence, book, and chapter a word was last looked up in.
+fn vocabulary_sources(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "vocabulary", "context", "TEXT")?;
+    add_column_if_missing(conn, "vocabulary", "book_id", "INTEGER")?;
+    add_column_if_missing(conn, "vocabulary", "chapter", "INTEGER")
+}
+
+/// Whether a book turns pages instead of scrolling.
+fn paged_reading(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "paged", "INTEGER DEFAULT 0")
+}
+
+/// The reading preset (novel, textbook, paper) picked for a book.
+fn reading_presets(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "books", "preset", "TEXT")
+}
+
+/// When each word was first looked up, for the weekly digest. Words from before this
+/// column existed take their last lookup.
+fn vocabulary_added_dates(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "vocabulary", "added_at", "TIMESTAMP")?;
+    conn.execute(
+        "UPDATE vocabulary SET added_at = last_lookup WHERE added_at IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Files kept with a note, such as a screenshot or the output of an exercise.
+fn annotation_attachments(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS annotation_attachments (
+            annotation_id INTEGER NOT NULL,
+            path TEXT NOT NULL,
+            added_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY(annotation_id, path),
+            FOREIGN KEY(annotation_id) REFERENCES annotations(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Tags on whole books, such as `fiction` or `technical`, for reading statistics.
+fn book_tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS book_tags (
+            book_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY(book_id, tag),
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS book_tags_tag ON book_tags(tag)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Where the reader was left mid-chapter, newest kept per book, with the words there.
+fn last_stops(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS last_stops (
+            id INTEGER PRIMARY KEY,
+            book_id INTEGER NOT NULL,
+            locator TEXT NOT NULL,
+            snippet TEXT NOT NULL,
+            stopped_at TEXT NOT NULL,
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Each time a book was opened and closed again: time spent in sittings, words read and
+/// notes made, for the summary shown on closing and in the stats.
+fn book_sessions(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS book_sessions (
+            id INTEGER PRIMARY KEY,
+            book_id INTEGER NOT NULL,
+            opened_at TEXT NOT NULL,
+            closed_at TEXT NOT NULL,
+            duration_secs INTEGER NOT NULL,
+            words_read INTEGER NOT NULL,
+            annotations INTEGER NOT NULL,
+            FOREIGN KEY(book_id) REFERENCES books(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
@@ -1,5 +1,18 @@
-use rusqlite::{params, Connection, Result};
+mod migrations;
+
+use crate::locator::{self, Locator};
+use crate::review::Schedule;
+use crate::search::SearchPattern;
+use rusqlite::backup::{Backup, Progress};
+use rusqlite::{
+    params, params_from_iter, Connection, OpenFlags, OptionalExtension, Result, MAIN_DB,
+};
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
+
+/// Last stops kept per book; older ones are dropped as new ones come in.
+pub const LAST_STOPS_KEPT: usize = 5;
 
 pub struct Db {
     conn: Connection,
@@ -7,71 +20,89 @@ pub struct Db {
 
 impl Db {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        Self::init(&conn)?;
+        let mut conn = Connection::open(path)?;
+        // The background indexer writes through its own connection.
+        conn.busy_timeout(Duration::from_secs(5))?;
+        migrations::run(&mut conn)?;
         Ok(Self { conn })
     }
 
-    fn init(conn: &Connection) -> Result<()> {
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS books (
-                id INTEGER PRIMARY KEY,
-                title TEXT NOT NULL,
-                author TEXT,
-                path TEXT NOT NULL UNIQUE,
-                current_chapter INTEGER DEFAULT 0,
-                current_line INTEGER DEFAULT 0,
-                total_chapters INTEGER DEFAULT 0,
-                total_lines INTEGER DEFAULT 0,
-                lines_read INTEGER DEFAULT 0,
-                last_read TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+    /// Opens an existing database without creating or migrating anything, e.g. to
+    /// inspect a backup before restoring it.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Self { conn })
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS annotations (
-                id INTEGER PRIMARY KEY,
-                book_id INTEGER NOT NULL,
-                chapter INTEGER NOT NULL,
-                start_line INTEGER NOT NULL,
-                start_word INTEGER NOT NULL,
-                end_line INTEGER NOT NULL,
-                end_word INTEGER NOT NULL,
-                content TEXT NOT NULL,
-                note TEXT,
-                kind TEXT DEFAULT 'highlight',
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY(book_id) REFERENCES books(id)
-            )",
-            [],
-        )?;
+    /// Copies the database to `path` with SQLite's online backup API, so the snapshot
+    /// is consistent even while another connection (such as the indexer) is writing.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut dst = Connection::open(path)?;
+        let backup = Backup::new(&self.conn, &mut dst)?;
+        backup.run_to_completion(100, Duration::from_millis(10), None)
+    }
 
-        ensure_annotation_kind_column(conn)?;
+    /// Replaces the whole database with the snapshot at `path` and brings its schema
+    /// up to date.
+    pub fn restore_from<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.conn.restore(MAIN_DB, path, None::<fn(Progress)>)?;
+        migrations::run(&mut self.conn)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS vocabulary (
-                id INTEGER PRIMARY KEY,
-                word TEXT NOT NULL UNIQUE,
-                definition TEXT,
-                lookup_count INTEGER DEFAULT 1,
-                last_lookup TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+    /// Problems reported by `PRAGMA integrity_check`; empty when the file is sound.
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut problems = Vec::new();
+        for r in rows {
+            let line = r?;
+            if line != "ok" {
+                problems.push(line);
+            }
+        }
+        Ok(problems)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS reading_sessions (
-                id INTEGER PRIMARY KEY,
-                book_id INTEGER NOT NULL,
-                date TEXT NOT NULL,
-                words_read INTEGER DEFAULT 0,
-                UNIQUE(book_id, date),
-                FOREIGN KEY(book_id) REFERENCES books(id)
-            )",
-            [],
-        )?;
-        Ok(())
+    pub fn summary(&self) -> Result<DbSummary> {
+        let count = |table: &str| -> Result<usize> {
+            self.conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .map(|n| n as usize)
+        };
+        Ok(DbSummary {
+            books: count("books")?,
+            annotations: count("annotations")?,
+            vocabulary: count("vocabulary")?,
+        })
+    }
+
+    /// Number of annotations per book id.
+    pub fn annotation_counts(&self) -> Result<HashMap<i32, usize>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT book_id, COUNT(*) FROM annotations GROUP BY book_id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+        let mut counts = HashMap::new();
+        for r in rows {
+            let (book_id, count) = r?;
+            counts.insert(book_id, count);
+        }
+        Ok(counts)
+    }
+
+    /// Annotations whose book row no longer exists.
+    pub fn orphaned_annotation_count(&self) -> Result<usize> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM annotations WHERE book_id NOT IN (SELECT id FROM books)",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n as usize)
     }
 
     pub fn log_reading_session(&self, book_id: i32, words: usize) -> Result<()> {
@@ -98,6 +129,148 @@ impl Db {
         Ok(stats)
     }
 
+    /// Words read in each book between two days, inclusive, most first.
+    pub fn get_words_by_book(
+        &self,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<(i32, usize)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT book_id, SUM(words_read) AS words FROM reading_sessions
+             WHERE date BETWEEN ?1 AND ?2
+             GROUP BY book_id HAVING words > 0 ORDER BY words DESC, book_id",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                from.format("%Y-%m-%d").to_string(),
+                to.format("%Y-%m-%d").to_string()
+            ],
+            |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)),
+        )?;
+        rows.collect()
+    }
+
+    /// When each book was last read, and the words read in it from `since` on, with the
+    /// first day in that stretch it was read.
+    pub fn get_book_paces(&self, since: chrono::NaiveDate) -> Result<Vec<BookPaceRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT book_id, MAX(date), MIN(CASE WHEN date >= ?1 THEN date END),
+                    SUM(CASE WHEN date >= ?1 THEN words_read ELSE 0 END)
+             FROM reading_sessions WHERE words_read > 0
+             GROUP BY book_id ORDER BY book_id",
+        )?;
+        let rows = stmt.query_map(params![since.format("%Y-%m-%d").to_string()], |row| {
+            Ok(BookPaceRecord {
+                book_id: row.get(0)?,
+                last_read: row.get(1)?,
+                first_recent: row.get(2)?,
+                recent_words: row.get::<_, i64>(3)? as usize,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Words and seconds read by each author between two days, inclusive, most words first.
+    pub fn get_reading_by_author(
+        &self,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<(String, usize, u64)>> {
+        self.reading_grouped_by("b.author", "", from, to)
+    }
+
+    /// Words and seconds read in books with each tag between two days, inclusive, most
+    /// words first. A book with several tags counts towards each; untagged books come
+    /// under an empty tag.
+    pub fn get_reading_by_tag(
+        &self,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<(String, usize, u64)>> {
+        self.reading_grouped_by(
+            "COALESCE(t.tag, '')",
+            "LEFT JOIN book_tags t ON t.book_id = b.id",
+            from,
+            to,
+        )
+    }
+
+    /// Words from reading sessions and seconds from sittings between two days, summed per
+    /// `group` of the books (aliased `b`) they were read in.
+    fn reading_grouped_by(
+        &self,
+        group: &str,
+        join: &str,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<(String, usize, u64)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {group} AS grp, SUM(r.words) AS words, SUM(r.secs) AS secs FROM (
+                 SELECT book_id, words_read AS words, 0 AS secs FROM reading_sessions
+                 WHERE date BETWEEN ?1 AND ?2
+                 UNION ALL
+                 SELECT book_id, 0, duration_secs FROM reading_sittings
+                 WHERE substr(started_at, 1, 10) BETWEEN ?1 AND ?2
+             ) r
+             JOIN books b ON b.id = r.book_id {join}
+             GROUP BY grp HAVING words > 0 OR secs > 0
+             ORDER BY words DESC, secs DESC, grp",
+        ))?;
+        let rows = stmt.query_map(
+            params![
+                from.format("%Y-%m-%d").to_string(),
+                to.format("%Y-%m-%d").to_string()
+            ],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, i64>(1)? as usize,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            },
+        )?;
+        rows.collect()
+    }
+
+    /// Words read per month (`YYYY-MM`) for the last 12 months with any reading.
+    pub fn get_monthly_stats(&self) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT substr(date, 1, 7) AS month, SUM(words_read) FROM reading_sessions
+             GROUP BY month ORDER BY month DESC LIMIT 12",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i32>(1)? as usize)))?;
+        let mut stats = Vec::new();
+        for r in rows {
+            stats.push(r?);
+        }
+        stats.reverse();
+        Ok(stats)
+    }
+
+    /// Words and seconds read on each day with any reading, oldest first.
+    pub fn get_daily_reading(&self) -> Result<Vec<(String, usize, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date, SUM(words), SUM(secs) FROM (
+                 SELECT date, words_read AS words, 0 AS secs FROM reading_sessions
+                 UNION ALL
+                 SELECT substr(started_at, 1, 10), 0, duration_secs FROM reading_sittings
+             )
+             GROUP BY date ORDER BY date",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get::<_, i64>(1)? as usize,
+                row.get::<_, i64>(2)? as u64,
+            ))
+        })?;
+        let mut days = Vec::new();
+        for r in rows {
+            days.push(r?);
+        }
+        Ok(days)
+    }
+
     pub fn get_today_words(&self) -> Result<usize> {
         let date = chrono::Local::now().format("%Y-%m-%d").to_string();
         let mut stmt = self
@@ -107,6 +280,520 @@ impl Db {
         Ok(count as usize)
     }
 
+    /// Records one uninterrupted sitting with `book_id`, from its first to its last input.
+    pub fn log_sitting(
+        &self,
+        book_id: i32,
+        started_at: chrono::DateTime<chrono::Local>,
+        ended_at: chrono::DateTime<chrono::Local>,
+        words: usize,
+    ) -> Result<()> {
+        let duration = (ended_at - started_at).num_seconds().max(0);
+        self.conn.execute(
+            "INSERT INTO reading_sittings (book_id, started_at, ended_at, duration_secs, words_read)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                book_id,
+                started_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ended_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                duration,
+                words as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records a book having been opened and closed again.
+    pub fn log_book_session(&self, session: &BookSessionRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO book_sessions
+                 (book_id, opened_at, closed_at, duration_secs, words_read, annotations)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                session.book_id,
+                session.opened_at,
+                session.closed_at,
+                session.secs as i64,
+                session.words as i64,
+                session.annotations as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The session closed most recently, in any book.
+    pub fn get_last_book_session(&self) -> Result<Option<BookSessionRecord>> {
+        self.conn
+            .query_row(
+                "SELECT s.book_id, b.title, s.opened_at, s.closed_at, s.duration_secs,
+                        s.words_read, s.annotations
+                 FROM book_sessions s LEFT JOIN books b ON b.id = s.book_id
+                 ORDER BY s.id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(BookSessionRecord {
+                        book_id: row.get(0)?,
+                        title: row.get(1)?,
+                        opened_at: row.get(2)?,
+                        closed_at: row.get(3)?,
+                        secs: row.get::<_, i64>(4)? as u64,
+                        words: row.get::<_, i64>(5)? as usize,
+                        annotations: row.get::<_, i64>(6)? as usize,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Reading speed over all timed sittings, or `None` before the first one.
+    pub fn get_average_wpm(&self) -> Result<Option<f64>> {
+        let (words, secs): (i64, i64) = self.conn.query_row(
+            "SELECT COALESCE(SUM(words_read), 0), COALESCE(SUM(duration_secs), 0)
+             FROM reading_sittings WHERE duration_secs > 0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok((words > 0 && secs > 0).then(|| words as f64 * 60.0 / secs as f64))
+    }
+
+    /// Seconds spent reading in sittings that started today.
+    pub fn get_today_reading_secs(&self) -> Result<u64> {
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let secs: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(duration_secs), 0) FROM reading_sittings
+             WHERE substr(started_at, 1, 10) = ?1",
+            params![date],
+            |row| row.get(0),
+        )?;
+        Ok(secs as u64)
+    }
+
+    /// Mean sitting length in seconds, or `None` before the first sitting.
+    pub fn get_average_sitting_secs(&self) -> Result<Option<u64>> {
+        let avg: Option<f64> = self.conn.query_row(
+            "SELECT AVG(duration_secs) FROM reading_sittings",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(avg.map(|a| a.round() as u64))
+    }
+
+    /// Records a place the reader was left, replacing an earlier stop at the same spot.
+    /// Only the last few stops of each book are kept.
+    pub fn add_last_stop(&self, book_id: i32, locator: &Locator, snippet: &str) -> Result<()> {
+        let locator = locator.to_string();
+        self.conn.execute(
+            "DELETE FROM last_stops WHERE book_id = ?1 AND locator = ?2",
+            params![book_id, locator],
+        )?;
+        self.conn.execute(
+            "INSERT INTO last_stops (book_id, locator, snippet, stopped_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                book_id,
+                locator,
+                snippet,
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM last_stops WHERE book_id = ?1 AND id NOT IN
+             (SELECT id FROM last_stops WHERE book_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![book_id, LAST_STOPS_KEPT as i64],
+        )?;
+        Ok(())
+    }
+
+    /// A book's last stops, newest first.
+    pub fn get_last_stops(&self, book_id: i32) -> Result<Vec<LastStopRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT locator, snippet, stopped_at FROM last_stops
+             WHERE book_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![book_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut stops = Vec::new();
+        for r in rows {
+            let (locator, snippet, stopped_at) = r?;
+            // Unreadable locators have nowhere to go back to.
+            if let Some(locator) = Locator::parse(&locator) {
+                stops.push(LastStopRecord {
+                    locator,
+                    snippet,
+                    stopped_at,
+                });
+            }
+        }
+        Ok(stops)
+    }
+
+    pub fn add_search_history(&self, query: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO search_history (query) VALUES (?1)
+             ON CONFLICT(query) DO UPDATE SET searched_at = CURRENT_TIMESTAMP",
+            params![query],
+        )?;
+        // Keep only the most recent entries.
+        self.conn.execute(
+            "DELETE FROM search_history WHERE id NOT IN
+             (SELECT id FROM search_history ORDER BY searched_at DESC, id DESC LIMIT 50)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_search_history(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT query FROM search_history ORDER BY searched_at DESC, id DESC")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        let mut history = Vec::new();
+        for r in rows {
+            history.push(r?);
+        }
+        Ok(history)
+    }
+
+    pub fn add_scan_path(&self, path: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO scan_paths (path) VALUES (?1)
+             ON CONFLICT(path) DO UPDATE SET scanned_at = CURRENT_TIMESTAMP",
+            params![path],
+        )?;
+        self.conn.execute(
+            "DELETE FROM scan_paths WHERE path NOT IN
+             (SELECT path FROM scan_paths ORDER BY scanned_at DESC, rowid DESC LIMIT 20)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_scan_paths(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM scan_paths ORDER BY scanned_at DESC, rowid DESC")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        let mut paths = Vec::new();
+        for r in rows {
+            paths.push(r?);
+        }
+        Ok(paths)
+    }
+
+    pub fn remove_scan_path(&self, path: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM scan_paths WHERE path = ?1", params![path])?;
+        Ok(())
+    }
+
+    pub fn save_search(&self, name: &str, query: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO saved_searches (name, query) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET query = ?2",
+            params![name, query],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_saved_searches(&self) -> Result<Vec<SavedSearchRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, query FROM saved_searches ORDER BY name COLLATE NOCASE")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SavedSearchRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                query: row.get(2)?,
+            })
+        })?;
+        let mut searches = Vec::new();
+        for r in rows {
+            searches.push(r?);
+        }
+        Ok(searches)
+    }
+
+    pub fn delete_saved_search(&self, id: i32) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM saved_searches WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Replaces the full-text index of a book with the given chapter texts.
+    pub fn index_book_chapters(&self, book_id: i32, chapters: &[(usize, String)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        // The book may have been removed while the indexer was parsing it.
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1)",
+            params![book_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(());
+        }
+        tx.execute(
+            "DELETE FROM chapters_fts WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        for (chapter, content) in chapters {
+            tx.execute(
+                "INSERT INTO chapters_fts (content, book_id, chapter) VALUES (?1, ?2, ?3)",
+                params![content, book_id, *chapter as i32],
+            )?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO fts_indexed_books (book_id) VALUES (?1)",
+            params![book_id],
+        )?;
+        tx.commit()
+    }
+
+    /// Stores per-chapter word counts, as (chapter index, words), and the book's totals.
+    pub fn set_word_counts(
+        &self,
+        book_id: i32,
+        chapters: &[(usize, usize)],
+        total_lines: usize,
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM chapter_words WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        // Like the index, counts for a book removed in the meantime are dropped.
+        let updated = tx.execute(
+            "UPDATE books SET total_words = ?1, total_lines = ?2 WHERE id = ?3",
+            params![
+                chapters.iter().map(|(_, w)| w).sum::<usize>() as i64,
+                total_lines as i64,
+                book_id
+            ],
+        )?;
+        if updated == 0 {
+            return Ok(());
+        }
+        for (chapter, words) in chapters {
+            tx.execute(
+                "INSERT INTO chapter_words (book_id, chapter, words) VALUES (?1, ?2, ?3)",
+                params![book_id, *chapter as i32, *words as i64],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Words per chapter, indexed by chapter; chapters without text count as 0.
+    /// Empty until the book's words have been counted.
+    pub fn get_chapter_words(&self, book_id: i32) -> Result<Vec<usize>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT chapter, words FROM chapter_words WHERE book_id = ?1")?;
+        let rows = stmt.query_map(params![book_id], |row| {
+            Ok((row.get::<_, i32>(0)? as usize, row.get::<_, i64>(1)? as usize))
+        })?;
+        let mut words = Vec::new();
+        for r in rows {
+            let (chapter, count) = r?;
+            if words.len() <= chapter {
+                words.resize(chapter + 1, 0);
+            }
+            words[chapter] = count;
+        }
+        Ok(words)
+    }
+
+    /// Records how many words precede the reading position.
+    pub fn set_position_words(&self, book_id: i32, words: usize) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET position_words = ?1 WHERE id = ?2",
+            params![words as i64, book_id],
+        )?;
+        Ok(())
+    }
+
+    /// Books that have not been added to the full-text index or had their words counted yet.
+    pub fn get_unindexed_books(&self) -> Result<Vec<(i32, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path FROM books
+             WHERE id NOT IN (SELECT book_id FROM fts_indexed_books) OR total_words IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut books = Vec::new();
+        for r in rows {
+            books.push(r?);
+        }
+        Ok(books)
+    }
+
+    /// Ranked full-text search. Each hit points at the first occurrence of the first term.
+    pub fn search_chapters(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search_chapters_in(None, query, limit)
+    }
+
+    /// [`Db::search_chapters`] within one book.
+    pub fn search_book_chapters(
+        &self,
+        book_id: i32,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.search_chapters_in(Some(book_id), query, limit)
+    }
+
+    fn search_chapters_in(
+        &self,
+        book_id: Option<i32>,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        // Quote every term so user input can't be parsed as FTS5 query syntax.
+        let fts_query = query
+            .split_whitespace()
+            .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if fts_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let first_term = query.split_whitespace().next().unwrap_or("");
+
+        let mut stmt = self.conn.prepare(
+            "SELECT f.book_id, b.title, f.chapter,
+                    snippet(chapters_fts, 0, '[', ']', '...', 16), f.content
+             FROM chapters_fts f JOIN books b ON b.id = f.book_id
+             WHERE chapters_fts MATCH ?1 AND (?3 IS NULL OR f.book_id = ?3)
+             ORDER BY bm25(chapters_fts)
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![fts_query, limit as i64, book_id], |row| {
+            let snippet: String = row.get(3)?;
+            let content: String = row.get(4)?;
+            let chapter = row.get::<_, i32>(2)? as usize;
+            Ok(SearchHit {
+                book_id: row.get(0)?,
+                title: row.get(1)?,
+                locator: Locator::new(
+                    chapter,
+                    locator::find_offset(&content, first_term).unwrap_or(0),
+                ),
+                snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+            })
+        })?;
+        let mut results = Vec::new();
+        for r in rows {
+            results.push(r?);
+        }
+        Ok(results)
+    }
+
+    /// Chapters whose indexed text matches `pattern`, in library order, optionally within
+    /// one book. Unlike [`Db::search_chapters`] this reads every chapter, so regular
+    /// expressions and case-sensitive searches work.
+    pub fn search_chapters_matching(
+        &self,
+        pattern: &SearchPattern,
+        book_id: Option<i32>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.book_id, b.title, f.chapter, f.content
+             FROM chapters_fts f JOIN books b ON b.id = f.book_id
+             WHERE ?1 IS NULL OR f.book_id = ?1
+             ORDER BY b.title, f.book_id, f.chapter",
+        )?;
+        let mut rows = stmt.query(params![book_id])?;
+        let mut results = Vec::new();
+        while results.len() < limit {
+            let Some(row) = rows.next()? else {
+                break;
+            };
+            let content: String = row.get(3)?;
+            let Some(snippet) = pattern.snippet(&content) else {
+                continue;
+            };
+            results.push(SearchHit {
+                book_id: row.get(0)?,
+                title: row.get(1)?,
+                locator: Locator::new(
+                    row.get::<_, i32>(2)? as usize,
+                    pattern.find_offset(&content).unwrap_or(0),
+                ),
+                snippet,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Deletes a book together with its tags, its notes (and their links, tags, and
+    /// attachments), reading sessions, and search index.
+    /// Returns false when no book has that id.
+    pub fn remove_book(&self, book_id: i32) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM annotation_links
+             WHERE from_id IN (SELECT id FROM annotations WHERE book_id = ?1)
+                OR to_id IN (SELECT id FROM annotations WHERE book_id = ?1)",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM annotation_tags
+             WHERE annotation_id IN (SELECT id FROM annotations WHERE book_id = ?1)",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM annotation_attachments
+             WHERE annotation_id IN (SELECT id FROM annotations WHERE book_id = ?1)",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM annotations WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM book_tags WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM reading_sessions WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM reading_sittings WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM last_stops WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM book_sessions WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM chapter_words WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM chapters_fts WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "DELETE FROM fts_indexed_books WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        tx.execute(
+            "UPDATE vocabulary SET book_id = NULL, chapter = NULL WHERE book_id = ?1",
+            params![book_id],
+        )?;
+        let removed = tx.execute("DELETE FROM books WHERE id = ?1", params![book_id])?;
+        tx.commit()?;
+        Ok(removed > 0)
+    }
+
     pub fn add_book(
         &self,
         title: &str,
@@ -114,29 +801,123 @@ impl Db {
         path: &str,
         total_chapters: usize,
         total_lines: usize,
+        total_pages: Option<usize>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO books (title, author, path, total_chapters, total_lines, total_pages) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                title,
+                author,
+                path,
+                total_chapters as i32,
+                total_lines as i32,
+                total_pages.map(|p| p as i32)
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_book_lookup(
+        &self,
+        book_id: i32,
+        language: Option<&str>,
+        dictionary: Option<&str>,
     ) -> Result<()> {
         self.conn.execute(
-            "INSERT OR IGNORE INTO books (title, author, path, total_chapters, total_lines) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![title, author, path, total_chapters as i32, total_lines as i32],
+            "UPDATE books SET language = ?1, dictionary = ?2 WHERE id = ?3",
+            params![language, dictionary, book_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_book_typography(
+        &self,
+        book_id: i32,
+        paragraph_spacing: Option<u16>,
+        first_line_indent: Option<u16>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET paragraph_spacing = ?1, first_line_indent = ?2 WHERE id = ?3",
+            params![paragraph_spacing, first_line_indent, book_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_book_paged(&self, book_id: i32, paged: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET paged = ?1 WHERE id = ?2",
+            params![paged, book_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_book_preset(&self, book_id: i32, preset: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET preset = ?1 WHERE id = ?2",
+            params![preset, book_id],
+        )?;
+        Ok(())
+    }
+
+    /// Records the size and content hash of the file at `path`, used to find it again
+    /// after it moves.
+    pub fn set_book_fingerprint(&self, path: &str, size: u64, hash: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET file_size = ?1, content_hash = ?2 WHERE path = ?3",
+            params![size as i64, hash, path],
+        )?;
+        Ok(())
+    }
+
+    /// Updates the chapter (and page) count after a book's file changed.
+    pub fn set_chapter_count(
+        &self,
+        book_id: i32,
+        chapters: usize,
+        pages: Option<usize>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET total_chapters = ?1, total_pages = ?2 WHERE id = ?3",
+            params![chapters as i32, pages.map(|p| p as i32), book_id],
+        )?;
+        Ok(())
+    }
+
+    /// Books imported before content hashes were stored.
+    pub fn get_unfingerprinted_books(&self) -> Result<Vec<(i32, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, path FROM books WHERE content_hash IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut books = Vec::new();
+        for r in rows {
+            books.push(r?);
+        }
+        Ok(books)
+    }
+
+    /// Points a book at a new file, keeping its progress and notes.
+    pub fn relink_book(&self, book_id: i32, path: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET path = ?1 WHERE id = ?2",
+            params![path, book_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_book_status(&self, book_id: i32, status: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE books SET status = ?1 WHERE id = ?2",
+            params![status, book_id],
         )?;
         Ok(())
     }
 
     pub fn get_books(&self) -> Result<Vec<BookRecord>> {
-        let mut stmt = self.conn.prepare("SELECT id, title, author, path, current_chapter, current_line, total_chapters, total_lines, lines_read FROM books ORDER BY last_read DESC")?;
-        let book_iter = stmt.query_map([], |row| {
-            Ok(BookRecord {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                author: row.get(2)?,
-                path: row.get(3)?,
-                current_chapter: row.get::<_, i32>(4)? as usize,
-                current_line: row.get::<_, i32>(5)? as usize,
-                total_chapters: row.get::<_, i32>(6)? as usize,
-                total_lines: row.get::<_, i32>(7)? as usize,
-                lines_read: row.get::<_, i32>(8)? as usize,
-            })
-        })?;
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM books ORDER BY last_read DESC", BOOK_COLUMNS))?;
+        let book_iter = stmt.query_map([], book_from_row)?;
 
         let mut books = Vec::new();
         for book in book_iter {
@@ -145,6 +926,16 @@ impl Db {
         Ok(books)
     }
 
+    pub fn get_book(&self, book_id: i32) -> Result<Option<BookRecord>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM books WHERE id = ?1", BOOK_COLUMNS),
+                params![book_id],
+                book_from_row,
+            )
+            .optional()
+    }
+
     pub fn get_last_read_book(&self) -> Result<Option<BookRecord>> {
         let books = self.get_books()?;
         Ok(books.into_iter().next())
@@ -156,60 +947,135 @@ impl Db {
         chapter: usize,
         line: usize,
         lines_read: usize,
+        locator: Option<&str>,
     ) -> Result<()> {
         self.conn.execute(
-            "UPDATE books SET current_chapter = ?1, current_line = ?2, lines_read = ?3, last_read = CURRENT_TIMESTAMP WHERE path = ?4",
-            params![chapter as i32, line as i32, lines_read as i32, path],
+            "UPDATE books SET current_chapter = ?1, current_line = ?2, lines_read = ?3, locator = ?4, last_read = CURRENT_TIMESTAMP WHERE path = ?5",
+            params![chapter as i32, line as i32, lines_read as i32, locator, path],
         )?;
         Ok(())
     }
 
-    pub fn add_annotation(
+    pub fn add_annotation(&self, anno: &NewAnnotation) -> Result<i32> {
+        self.conn.execute(
+            "INSERT INTO annotations (book_id, chapter, start_line, start_word, end_line, end_word, content, note, kind, start_locator, end_locator) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                anno.book_id,
+                anno.chapter as i32,
+                anno.start_line as i32,
+                anno.start_word as i32,
+                anno.end_line as i32,
+                anno.end_word as i32,
+                anno.content,
+                anno.note,
+                anno.kind,
+                anno.start_locator,
+                anno.end_locator
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    /// Moves an annotation, e.g. after its book's text changed.
+    pub fn set_annotation_position(
         &self,
-        book_id: i32,
+        annotation_id: i32,
         chapter: usize,
-        start_line: usize,
-        start_word: usize,
-        end_line: usize,
-        end_word: usize,
-        content: &str,
-        note: Option<&str>,
-        kind: &str,
+        start_locator: &str,
+        end_locator: &str,
     ) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO annotations (book_id, chapter, start_line, start_word, end_line, end_word, content, note, kind) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
-                book_id,
-                chapter as i32,
-                start_line as i32,
-                start_word as i32,
-                end_line as i32,
-                end_word as i32,
-                content,
-                note,
-                kind
-            ],
+            "UPDATE annotations SET chapter = ?1, start_locator = ?2, end_locator = ?3 WHERE id = ?4",
+            params![chapter as i32, start_locator, end_locator, annotation_id],
         )?;
         Ok(())
     }
 
+    pub fn add_annotation_links(&self, from_id: i32, to_ids: &[i32]) -> Result<()> {
+        for to_id in to_ids {
+            if *to_id == from_id {
+                continue;
+            }
+            // Only link to annotations that actually exist.
+            self.conn.execute(
+                "INSERT OR IGNORE INTO annotation_links (from_id, to_id)
+                 SELECT ?1, id FROM annotations WHERE id = ?2",
+                params![from_id, to_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Annotations that `id` links to (`outgoing`) or that link to `id` (backlinks).
+    pub fn get_linked_annotations(&self, id: i32, outgoing: bool) -> Result<Vec<AnnotationRecord>> {
+        let join = if outgoing {
+            "a.id = l.to_id WHERE l.from_id = ?1"
+        } else {
+            "a.id = l.from_id WHERE l.to_id = ?1"
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM annotation_links l JOIN annotations a ON {join} ORDER BY a.id"
+        ))?;
+        let anno_iter = stmt.query_map(params![id], annotation_from_row)?;
+
+        let mut annos = Vec::new();
+        for anno in anno_iter {
+            annos.push(anno?);
+        }
+        Ok(annos)
+    }
+
     pub fn get_annotations(&self, book_id: i32) -> Result<Vec<AnnotationRecord>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, chapter, start_line, start_word, end_line, end_word, content, note, COALESCE(kind, 'highlight') FROM annotations WHERE book_id = ?1 ORDER BY chapter, start_line, start_word",
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM annotations a WHERE a.book_id = ?1 ORDER BY a.chapter, a.start_line, a.start_word"
+        ))?;
+        let anno_iter = stmt.query_map(params![book_id], annotation_from_row)?;
+
+        let mut annos = Vec::new();
+        for anno in anno_iter {
+            annos.push(anno?);
+        }
+        Ok(annos)
+    }
+
+    /// Annotations made between two local days, inclusive, book by book and oldest first.
+    pub fn get_annotations_created_between(
+        &self,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<AnnotationRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM annotations a
+             WHERE date(a.created_at, 'localtime') BETWEEN ?1 AND ?2
+             ORDER BY a.book_id, a.created_at, a.id"
+        ))?;
+        let rows = stmt.query_map(
+            params![
+                from.format("%Y-%m-%d").to_string(),
+                to.format("%Y-%m-%d").to_string()
+            ],
+            annotation_from_row,
         )?;
-        let anno_iter = stmt.query_map(params![book_id], |row| {
-            Ok(AnnotationRecord {
-                id: row.get(0)?,
-                chapter: row.get::<_, i32>(1)? as usize,
-                start_line: row.get::<_, i32>(2)? as usize,
-                start_word: row.get::<_, i32>(3)? as usize,
-                end_line: row.get::<_, i32>(4)? as usize,
-                end_word: row.get::<_, i32>(5)? as usize,
-                content: row.get(6)?,
-                note: row.get(7)?,
-                kind: row.get(8)?,
-            })
-        })?;
+        rows.collect()
+    }
+
+    /// Annotations of the given kinds across every book in the library.
+    pub fn get_annotations_by_kinds(&self, kinds: &[&str]) -> Result<Vec<AnnotationRecord>> {
+        let placeholders = vec!["?"; kinds.len()].join(", ");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM annotations a WHERE a.kind IN ({placeholders})
+             ORDER BY a.book_id, a.chapter, a.start_line, a.start_word"
+        ))?;
+        let rows = stmt.query_map(params_from_iter(kinds), annotation_from_row)?;
+        rows.collect()
+    }
+
+    /// Every annotation in the library, grouped by book in reading order.
+    pub fn get_all_annotations(&self) -> Result<Vec<AnnotationRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM annotations a ORDER BY a.book_id, a.chapter, a.start_line, a.start_word"
+        ))?;
+        let anno_iter = stmt.query_map([], annotation_from_row)?;
 
         let mut annos = Vec::new();
         for anno in anno_iter {
@@ -218,28 +1084,147 @@ impl Db {
         Ok(annos)
     }
 
-    pub fn add_to_vocabulary(&self, word: &str, definition: &str) -> Result<()> {
+    /// Attaches a file to an annotation, or detaches it when it is already attached.
+    /// Returns whether the file is attached afterwards.
+    pub fn toggle_annotation_attachment(&self, annotation_id: i32, path: &str) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM annotation_attachments WHERE annotation_id = ?1 AND path = ?2",
+            params![annotation_id, path],
+        )?;
+        if removed > 0 {
+            return Ok(false);
+        }
         self.conn.execute(
-            "INSERT INTO vocabulary (word, definition) VALUES (?1, ?2)
-             ON CONFLICT(word) DO UPDATE SET 
-                lookup_count = lookup_count + 1,
-                last_lookup = CURRENT_TIMESTAMP",
-            params![word, definition],
+            "INSERT INTO annotation_attachments (annotation_id, path) VALUES (?1, ?2)",
+            params![annotation_id, path],
         )?;
-        Ok(())
+        Ok(true)
     }
 
-    pub fn get_vocabulary(&self) -> Result<Vec<VocabRecord>> {
+    /// Replaces the tags of an annotation.
+    pub fn set_annotation_tags(&self, annotation_id: i32, tags: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM annotation_tags WHERE annotation_id = ?1",
+            params![annotation_id],
+        )?;
+        for tag in tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO annotation_tags (annotation_id, tag) VALUES (?1, ?2)",
+                params![annotation_id, tag],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Replaces the tags of a book.
+    pub fn set_book_tags(&self, book_id: i32, tags: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM book_tags WHERE book_id = ?1", params![book_id])?;
+        for tag in tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO book_tags (book_id, tag) VALUES (?1, ?2)",
+                params![book_id, tag],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Every tag in use, alphabetically.
+    pub fn get_tags(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT tag FROM annotation_tags ORDER BY tag")?;
+        let tags = stmt.query_map([], |row| row.get(0))?;
+        tags.collect()
+    }
+
+    /// Whether the book already has a note of `kind` with exactly this text.
+    pub fn has_annotation(&self, book_id: i32, kind: &str, content: &str) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM annotations WHERE book_id = ?1 AND kind = ?2 AND content = ?3)",
+            params![book_id, kind, content],
+            |row| row.get(0),
+        )
+    }
+
+    /// Keeps an imported clipping whose book wasn't found for review. Returns false when
+    /// the same clipping is already waiting.
+    pub fn add_pending_clipping(
+        &self,
+        title: &str,
+        author: &str,
+        kind: &str,
+        content: &str,
+        note: Option<&str>,
+    ) -> Result<bool> {
+        let added = self.conn.execute(
+            "INSERT OR IGNORE INTO pending_clippings (title, author, kind, content, note) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![title, author, kind, content, note],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Clippings waiting for a book, grouped by title in the order they were imported.
+    pub fn get_pending_clippings(&self) -> Result<Vec<PendingClippingRecord>> {
         let mut stmt = self.conn.prepare(
-            "SELECT word, definition, lookup_count FROM vocabulary ORDER BY last_lookup DESC",
+            "SELECT id, title, author, kind, content, note FROM pending_clippings
+             ORDER BY (SELECT MIN(p.id) FROM pending_clippings p WHERE p.title = pending_clippings.title), id",
         )?;
-        let vocab_iter = stmt.query_map([], |row| {
-            Ok(VocabRecord {
-                word: row.get(0)?,
-                definition: row.get(1)?,
-                lookup_count: row.get(2)?,
+        let clippings = stmt.query_map([], |row| {
+            Ok(PendingClippingRecord {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                author: row.get(2)?,
+                kind: row.get(3)?,
+                content: row.get(4)?,
+                note: row.get(5)?,
             })
         })?;
+        clippings.collect()
+    }
+
+    pub fn remove_pending_clippings(&self, ids: &[i32]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for id in ids {
+            tx.execute("DELETE FROM pending_clippings WHERE id = ?1", params![id])?;
+        }
+        tx.commit()
+    }
+
+    /// Records a lookup of `word`. The source, when given, replaces the one kept from
+    /// earlier lookups.
+    pub fn add_to_vocabulary(
+        &self,
+        word: &str,
+        definition: &str,
+        source: Option<&VocabSource>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO vocabulary (word, definition, context, book_id, chapter, added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)
+             ON CONFLICT(word) DO UPDATE SET 
+                lookup_count = lookup_count + 1,
+                last_lookup = CURRENT_TIMESTAMP,
+                context = COALESCE(excluded.context, context),
+                book_id = COALESCE(excluded.book_id, book_id),
+                chapter = COALESCE(excluded.chapter, chapter)",
+            params![
+                word,
+                definition,
+                source.map(|s| s.sentence.as_str()),
+                source.map(|s| s.book_id),
+                source.map(|s| s.chapter as i64)
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_vocabulary(&self) -> Result<Vec<VocabRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("{} ORDER BY v.last_lookup DESC", VOCAB_SELECT))?;
+        let vocab_iter = stmt.query_map([], vocab_from_row)?;
 
         let mut vocab = Vec::new();
         for v in vocab_iter {
@@ -247,6 +1232,69 @@ impl Db {
         }
         Ok(vocab)
     }
+
+    /// Words first looked up between two local days, inclusive, oldest first.
+    pub fn get_vocabulary_added_between(
+        &self,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> Result<Vec<VocabRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE date(v.added_at, 'localtime') BETWEEN ?1 AND ?2 ORDER BY v.added_at, v.id",
+            VOCAB_SELECT
+        ))?;
+        let rows = stmt.query_map(
+            params![
+                from.format("%Y-%m-%d").to_string(),
+                to.format("%Y-%m-%d").to_string()
+            ],
+            vocab_from_row,
+        )?;
+        rows.collect()
+    }
+
+    /// Words due for review on `today`: overdue ones first, then words never reviewed.
+    pub fn get_due_vocabulary(&self, today: chrono::NaiveDate) -> Result<Vec<VocabRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE v.due IS NULL OR v.due <= ?1
+             ORDER BY v.due IS NULL, v.due, v.last_lookup DESC",
+            VOCAB_SELECT
+        ))?;
+        let today = today.format("%Y-%m-%d").to_string();
+        let rows = stmt.query_map(params![today], vocab_from_row)?;
+        rows.collect()
+    }
+
+    pub fn count_due_vocabulary(&self, today: chrono::NaiveDate) -> Result<usize> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM vocabulary WHERE due IS NULL OR due <= ?1",
+                params![today.format("%Y-%m-%d").to_string()],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n as usize)
+    }
+
+    /// Records a review of `word` on `today`, with the schedule it led to.
+    pub fn set_vocab_schedule(
+        &self,
+        word: &str,
+        schedule: &Schedule,
+        today: chrono::NaiveDate,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE vocabulary SET ease = ?1, interval_days = ?2, repetitions = ?3, due = ?4
+             WHERE word = ?5",
+            params![
+                schedule.ease,
+                schedule.interval,
+                schedule.repetitions,
+                schedule.due(today).format("%Y-%m-%d").to_string(),
+                word
+            ],
+        )?;
+        Ok(())
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -260,12 +1308,65 @@ pub struct BookRecord {
     pub total_chapters: usize,
     pub total_lines: usize,
     pub lines_read: usize,
+    /// Lookup language override; `None` uses the configured default.
+    pub language: Option<String>,
+    /// Dictionary override; `None` uses the configured default.
+    pub dictionary: Option<String>,
+    pub status: String,
+    /// Set for page-based formats (PDF, DjVu), where each chapter is one page.
+    pub total_pages: Option<usize>,
+    /// Layout-independent reading position; preferred over `current_line` when present.
+    pub locator: Option<Locator>,
+    /// Size and SHA-256 of the file when it was imported, used to find it after a move.
+    pub file_size: Option<u64>,
+    pub content_hash: Option<String>,
+    /// Words in the whole book, counted in the background after import.
+    pub total_words: Option<usize>,
+    /// Words before the reading position.
+    pub position_words: usize,
+    /// Blank rows after each paragraph; `None` uses the configured default.
+    pub paragraph_spacing: Option<u16>,
+    /// Columns the first line of a paragraph is indented; `None` uses the configured default.
+    pub first_line_indent: Option<u16>,
+    /// Turns a screenful at a time instead of scrolling line by line.
+    pub paged: bool,
+    /// Reading preset picked for the book, by name.
+    pub preset: Option<String>,
+    /// Lowercase tags, alphabetically.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl BookRecord {
+    /// Reading progress between 0 and 1. Paged books count pages, others count words
+    /// once they have been counted and lines before that.
+    pub fn progress(&self) -> f64 {
+        match self.total_pages {
+            Some(pages) if pages > 0 => {
+                ((self.current_chapter + 1).min(pages)) as f64 / pages as f64
+            }
+            _ if self.total_words.is_some_and(|w| w > 0) => {
+                let total = self.total_words.unwrap_or(1);
+                (self.position_words as f64 / total as f64).min(1.0)
+            }
+            _ if self.total_lines > 0 => {
+                (self.lines_read as f64 / self.total_lines as f64).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Words after the reading position, once the book's words have been counted.
+    pub fn words_left(&self) -> Option<usize> {
+        self.total_words
+            .map(|total| total.saturating_sub(self.position_words))
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct AnnotationRecord {
-    #[allow(dead_code)]
     pub id: i32,
+    pub book_id: i32,
     pub chapter: usize,
     pub start_line: usize,
     pub start_word: usize,
@@ -274,31 +1375,238 @@ pub struct AnnotationRecord {
     pub content: String,
     pub note: Option<String>,
     pub kind: String,
+    pub start_locator: Option<Locator>,
+    pub end_locator: Option<Locator>,
+    /// When the note was made, as SQLite's UTC `YYYY-MM-DD HH:MM:SS`.
+    pub created_at: Option<String>,
+    /// Lowercase tags, alphabetically.
+    pub tags: Vec<String>,
+    /// Paths of files kept with the note, in the order they were attached.
+    pub attachments: Vec<String>,
 }
 
-fn ensure_annotation_kind_column(conn: &Connection) -> Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(annotations)")?;
-    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
-    let mut has_kind = false;
-    for column in columns {
-        if column? == "kind" {
-            has_kind = true;
-            break;
-        }
-    }
+/// Annotation columns read by `annotation_from_row`, for queries aliasing `annotations` as `a`.
+const ANNOTATION_COLUMNS: &str = "a.id, a.book_id, a.chapter, a.start_line, a.start_word, a.end_line, a.end_word, a.content, a.note, COALESCE(a.kind, 'highlight'), a.start_locator, a.end_locator, a.created_at, (SELECT GROUP_CONCAT(t.tag, ' ') FROM annotation_tags t WHERE t.annotation_id = a.id), (SELECT GROUP_CONCAT(f.path, char(10)) FROM (SELECT path FROM annotation_attachments WHERE annotation_id = a.id ORDER BY added_at, rowid) f)";
 
-    if !has_kind {
-        conn.execute(
-            "ALTER TABLE annotations ADD COLUMN kind TEXT DEFAULT 'highlight'",
-            [],
-        )?;
-    }
+const BOOK_COLUMNS: &str = "id, title, author, path, current_chapter, current_line, total_chapters, total_lines, lines_read, language, dictionary, status, total_pages, locator, file_size, content_hash, total_words, position_words, paragraph_spacing, first_line_indent, paged, preset, (SELECT GROUP_CONCAT(tag, ' ') FROM book_tags WHERE book_id = books.id)";
+
+fn book_from_row(row: &rusqlite::Row) -> Result<BookRecord> {
+    Ok(BookRecord {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        author: row.get(2)?,
+        path: row.get(3)?,
+        current_chapter: row.get::<_, i32>(4)? as usize,
+        current_line: row.get::<_, i32>(5)? as usize,
+        total_chapters: row.get::<_, i32>(6)? as usize,
+        total_lines: row.get::<_, i32>(7)? as usize,
+        lines_read: row.get::<_, i32>(8)? as usize,
+        language: row.get(9)?,
+        dictionary: row.get(10)?,
+        status: row
+            .get::<_, Option<String>>(11)?
+            .unwrap_or_else(|| "to-read".to_string()),
+        total_pages: row.get::<_, Option<i32>>(12)?.map(|p| p as usize),
+        locator: row
+            .get::<_, Option<String>>(13)?
+            .and_then(|l| Locator::parse(&l)),
+        file_size: row.get::<_, Option<i64>>(14)?.map(|s| s as u64),
+        content_hash: row.get(15)?,
+        total_words: row.get::<_, Option<i64>>(16)?.map(|w| w as usize),
+        position_words: row.get::<_, Option<i64>>(17)?.unwrap_or(0) as usize,
+        paragraph_spacing: row.get::<_, Option<i64>>(18)?.map(|s| s as u16),
+        first_line_indent: row.get::<_, Option<i64>>(19)?.map(|i| i as u16),
+        paged: row.get::<_, Option<bool>>(20)?.unwrap_or(false),
+        preset: row.get(21)?,
+        tags: {
+            let mut tags: Vec<String> = row
+                .get::<_, Option<String>>(22)?
+                .map(|t| t.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default();
+            tags.sort();
+            tags
+        },
+    })
+}
 
-    Ok(())
+fn annotation_from_row(row: &rusqlite::Row) -> Result<AnnotationRecord> {
+    Ok(AnnotationRecord {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        chapter: row.get::<_, i32>(2)? as usize,
+        start_line: row.get::<_, i32>(3)? as usize,
+        start_word: row.get::<_, i32>(4)? as usize,
+        end_line: row.get::<_, i32>(5)? as usize,
+        end_word: row.get::<_, i32>(6)? as usize,
+        content: row.get(7)?,
+        note: row.get(8)?,
+        kind: row.get(9)?,
+        start_locator: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|l| Locator::parse(&l)),
+        end_locator: row
+            .get::<_, Option<String>>(11)?
+            .and_then(|l| Locator::parse(&l)),
+        created_at: row.get(12)?,
+        tags: {
+            let tags: Option<String> = row.get(13)?;
+            let mut tags: Vec<String> = tags
+                .iter()
+                .flat_map(|t| t.split(' '))
+                .map(str::to_string)
+                .collect();
+            tags.sort();
+            tags
+        },
+        attachments: row
+            .get::<_, Option<String>>(14)?
+            .map(|paths| paths.lines().map(str::to_string).collect())
+            .unwrap_or_default(),
+    })
 }
 
+/// An annotation to be added with [`Db::add_annotation`]; locators are in their string
+/// form.
+#[derive(Clone, Debug, Default)]
+pub struct NewAnnotation<'a> {
+    pub book_id: i32,
+    pub chapter: usize,
+    pub start_line: usize,
+    pub start_word: usize,
+    pub end_line: usize,
+    pub end_word: usize,
+    pub content: &'a str,
+    pub note: Option<&'a str>,
+    pub kind: &'a str,
+    pub start_locator: Option<&'a str>,
+    pub end_locator: Option<&'a str>,
+}
+
+/// A Kindle clipping that didn't match a library book, kept until it is assigned by hand.
+#[derive(Clone, Debug)]
+pub struct PendingClippingRecord {
+    pub id: i32,
+    /// Book title and author as Kindle wrote them.
+    pub title: String,
+    pub author: String,
+    pub kind: String,
+    pub content: String,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Debug)]
 pub struct VocabRecord {
     pub word: String,
     pub definition: String,
     pub lookup_count: i32,
+    pub schedule: Schedule,
+    /// Day the word is next due for review, `YYYY-MM-DD`; None until first reviewed.
+    pub due: Option<String>,
+    pub source: Option<VocabSource>,
+    /// Title of the source book, unless it has been removed.
+    pub book_title: Option<String>,
+}
+
+/// Where a word was looked up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VocabSource {
+    pub book_id: i32,
+    pub chapter: usize,
+    /// The sentence around the word.
+    pub sentence: String,
+}
+
+const VOCAB_SELECT: &str = "SELECT v.word, v.definition, v.lookup_count, v.ease, v.interval_days,
+        v.repetitions, v.due, v.context, v.book_id, v.chapter, b.title
+     FROM vocabulary v LEFT JOIN books b ON b.id = v.book_id";
+
+fn vocab_from_row(row: &rusqlite::Row) -> Result<VocabRecord> {
+    let defaults = Schedule::default();
+    Ok(VocabRecord {
+        word: row.get(0)?,
+        definition: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+        lookup_count: row.get(2)?,
+        schedule: Schedule {
+            ease: row.get::<_, Option<f64>>(3)?.unwrap_or(defaults.ease),
+            interval: row.get::<_, Option<u32>>(4)?.unwrap_or(0),
+            repetitions: row.get::<_, Option<u32>>(5)?.unwrap_or(0),
+        },
+        due: row.get(6)?,
+        source: match (row.get(7)?, row.get(8)?) {
+            (Some(sentence), Some(book_id)) => Some(VocabSource {
+                book_id,
+                chapter: row.get::<_, Option<i64>>(9)?.unwrap_or(0) as usize,
+                sentence,
+            }),
+            _ => None,
+        },
+        book_title: row.get(10)?,
+    })
+}
+
+/// Row counts used to describe a database or backup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DbSummary {
+    pub books: usize,
+    pub annotations: usize,
+    pub vocabulary: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub book_id: i32,
+    pub title: String,
+    pub locator: Locator,
+    pub snippet: String,
+}
+
+/// When a book was last read, and how much of it lately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookPaceRecord {
+    pub book_id: i32,
+    /// Last day any of it was read, `YYYY-MM-DD`.
+    pub last_read: String,
+    /// First day it was read on in the recent stretch, if it was.
+    pub first_recent: Option<String>,
+    pub recent_words: usize,
+}
+
+/// One book opened and closed again.
+#[derive(Clone, Debug)]
+pub struct BookSessionRecord {
+    pub book_id: i32,
+    /// Title of the book, unless it has been removed; not stored with the session.
+    pub title: Option<String>,
+    /// Local times, `YYYY-MM-DD HH:MM:SS`.
+    pub opened_at: String,
+    pub closed_at: String,
+    /// Time spent in sittings, leaving out idle time.
+    pub secs: u64,
+    pub words: usize,
+    /// Notes and highlights made.
+    pub annotations: usize,
+}
+
+impl BookSessionRecord {
+    /// Words per minute over the session, or `None` when it was too short to tell.
+    pub fn wpm(&self) -> Option<f64> {
+        (self.words > 0 && self.secs > 0).then(|| self.words as f64 * 60.0 / self.secs as f64)
+    }
+}
+
+/// A place the reader was left mid-chapter.
+#[derive(Clone, Debug)]
+pub struct LastStopRecord {
+    pub locator: Locator,
+    /// The first words at the stop.
+    pub snippet: String,
+    /// Local time, `YYYY-MM-DD HH:MM:SS`.
+    pub stopped_at: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct SavedSearchRecord {
+    pub id: i32,
+    pub name: String,
+    pub query: String,
 }
@@ -6,6 +6,9 @@ fn env_override(name: &str) -> Option<PathBuf> {
     let var = match name {
         "pdftotext" => "TBOOK_PDFTOTEXT",
         "pdftoppm" => "TBOOK_PDFTOPPM",
+        "djvutxt" => "TBOOK_DJVUTXT",
+        "ddjvu" => "TBOOK_DDJVU",
+        "djvused" => "TBOOK_DJVUSED",
         _ => return None,
     };
 
@@ -46,24 +49,27 @@ fn path_binary(name: &str) -> Option<PathBuf> {
     None
 }
 
+fn resolve_binary(name: &str) -> Option<PathBuf> {
+    env_override(name)
+        .or_else(|| sibling_binary(name))
+        .or_else(|| data_dir_binary(name))
+        .or_else(|| path_binary(name))
+}
+
 pub fn resolve_poppler_binary(name: &str) -> Result<PathBuf> {
-    if let Some(path) = env_override(name) {
-        return Ok(path);
-    }
-    if let Some(path) = sibling_binary(name) {
-        return Ok(path);
-    }
-    if let Some(path) = data_dir_binary(name) {
-        return Ok(path);
-    }
-    if let Some(path) = path_binary(name) {
-        return Ok(path);
-    }
+    resolve_binary(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Missing {}. Install poppler-utils or use the bundled Linux release (includes poppler binaries).",
+            name
+        )
+    })
+}
 
-    Err(anyhow::anyhow!(
-        "Missing {}. Install poppler-utils or use the bundled Linux release (includes poppler binaries).",
-        name
-    ))
+pub fn resolve_djvu_command(name: &str) -> Result<std::process::Command> {
+    let path = resolve_binary(name).ok_or_else(|| {
+        anyhow::anyhow!("Missing {}. Install djvulibre (djvulibre-bin) for DjVu support.", name)
+    })?;
+    Ok(std::process::Command::new(path))
 }
 
 pub fn resolve_poppler_command(name: &str) -> Result<std::process::Command> {
@@ -0,0 +1,254 @@
+use serde_json::Value;
+
+/// Where word definitions are fetched from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DictionarySource {
+    /// dictionaryapi.dev, which only covers English well.
+    FreeDictionary,
+    /// Wiktionary's definition API, which covers most languages.
+    Wiktionary,
+    /// A URL template containing `{word}` and optionally `{lang}`.
+    Custom(String),
+    /// The summary of a Wikipedia article, for names, places, and ideas rather than words.
+    Wikipedia,
+}
+
+impl DictionarySource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if name.contains("{word}") {
+            return Some(Self::Custom(name.to_string()));
+        }
+        match name.to_lowercase().as_str() {
+            "free" | "freedictionary" | "dictionaryapi" => Some(Self::FreeDictionary),
+            "wiktionary" | "wiki" => Some(Self::Wiktionary),
+            _ => None,
+        }
+    }
+
+    /// The source used when neither the book nor the config picks one.
+    pub fn default_for(language: &str) -> Self {
+        if language == "en" {
+            Self::FreeDictionary
+        } else {
+            Self::Wiktionary
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Self::FreeDictionary => "free",
+            Self::Wiktionary => "wiktionary",
+            Self::Custom(_) => "custom",
+            Self::Wikipedia => "wikipedia",
+        }
+    }
+
+    /// Whether answers explain a topic instead of defining a word.
+    pub fn is_encyclopedia(&self) -> bool {
+        matches!(self, Self::Wikipedia)
+    }
+}
+
+/// Resolved language and dictionary for a lookup.
+#[derive(Clone, Debug)]
+pub struct LookupSettings {
+    pub language: String,
+    pub source: DictionarySource,
+}
+
+impl LookupSettings {
+    /// Per-book values win over the global ones; anything unset falls back to English.
+    pub fn resolve(
+        book_language: Option<&str>,
+        book_dictionary: Option<&str>,
+        default_language: Option<&str>,
+        default_dictionary: Option<&str>,
+    ) -> Self {
+        let language = book_language
+            .or(default_language)
+            .map(normalize_language)
+            .filter(|l| !l.is_empty())
+            .unwrap_or_else(|| "en".to_string());
+        let source = book_dictionary
+            .and_then(DictionarySource::from_name)
+            .or_else(|| default_dictionary.and_then(DictionarySource::from_name))
+            .unwrap_or_else(|| DictionarySource::default_for(&language));
+        Self { language, source }
+    }
+}
+
+/// Reduces tags like "es-MX" or "de_DE" to the primary language code.
+pub fn normalize_language(tag: &str) -> String {
+    tag.trim()
+        .split(['-', '_'])
+        .next()
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+pub async fn lookup(word: String, settings: LookupSettings) -> String {
+    let url = match &settings.source {
+        DictionarySource::FreeDictionary => format!(
+            "https://api.dictionaryapi.dev/api/v2/entries/{}/{}",
+            settings.language, word
+        ),
+        DictionarySource::Wiktionary => format!(
+            "https://en.wiktionary.org/api/rest_v1/page/definition/{}",
+            word
+        ),
+        DictionarySource::Custom(template) => template
+            .replace("{word}", &word)
+            .replace("{lang}", &settings.language),
+        DictionarySource::Wikipedia => match wikipedia_url(&settings.language, &word) {
+            Some(url) => url.to_string(),
+            None => return format!("No Wikipedia in language \"{}\".", settings.language),
+        },
+    };
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("tbook/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default();
+    let body = match client.get(url).send().await {
+        Ok(resp) => match resp.text().await {
+            Ok(body) => body,
+            Err(_) => return "Error reading response.".to_string(),
+        },
+        Err(e) => return format!("Network Error: {}.", e),
+    };
+
+    let result = match &settings.source {
+        DictionarySource::FreeDictionary => match serde_json::from_str::<Value>(&body) {
+            Ok(json) => format_free_dictionary(&json),
+            Err(_) => return "Failed to parse.".to_string(),
+        },
+        DictionarySource::Wiktionary => match serde_json::from_str::<Value>(&body) {
+            Ok(json) => format_wiktionary(&json, &settings.language),
+            Err(_) => return "Failed to parse.".to_string(),
+        },
+        DictionarySource::Custom(_) => match serde_json::from_str::<Value>(&body) {
+            Ok(json) => serde_json::to_string_pretty(&json).unwrap_or(body),
+            Err(_) => html2text::from_read(body.as_bytes(), 100).unwrap_or(body),
+        },
+        DictionarySource::Wikipedia => match serde_json::from_str::<Value>(&body) {
+            Ok(json) => format_wikipedia(&json),
+            Err(_) => return "Failed to parse.".to_string(),
+        },
+    };
+
+    if !result.trim().is_empty() {
+        result
+    } else if settings.source.is_encyclopedia() {
+        "No article found.".to_string()
+    } else {
+        "No definition found.".to_string()
+    }
+}
+
+fn format_free_dictionary(json: &Value) -> String {
+    let mut result = String::new();
+    let Some(entries) = json.as_array() else {
+        return result;
+    };
+    for entry in entries {
+        if let Some(w) = entry.get("word").and_then(|v| v.as_str()) {
+            result.push_str(&format!("# {}\n", w.to_uppercase()));
+        }
+        if let Some(meanings) = entry.get("meanings").and_then(|v| v.as_array()) {
+            for meaning in meanings {
+                if let Some(pos) = meaning.get("partOfSpeech").and_then(|v| v.as_str()) {
+                    result.push_str(&format!("\n[{}]\n", pos));
+                }
+                if let Some(definitions) = meaning.get("definitions").and_then(|v| v.as_array()) {
+                    for (i, def) in definitions.iter().enumerate() {
+                        if let Some(d) = def.get("definition").and_then(|v| v.as_str()) {
+                            result.push_str(&format!("{}. {}\n", i + 1, d));
+                        }
+                    }
+                }
+            }
+        }
+        result.push_str("\n---\n");
+    }
+    result
+}
+
+/// Wiktionary groups entries by language code and returns definitions as HTML.
+fn format_wiktionary(json: &Value, language: &str) -> String {
+    let mut result = String::new();
+    let Some(entries) = json.get(language).and_then(|v| v.as_array()) else {
+        return result;
+    };
+    for entry in entries {
+        if let Some(pos) = entry.get("partOfSpeech").and_then(|v| v.as_str()) {
+            result.push_str(&format!("\n[{}]\n", pos));
+        }
+        if let Some(definitions) = entry.get("definitions").and_then(|v| v.as_array()) {
+            for (i, def) in definitions.iter().enumerate() {
+                if let Some(d) = def.get("definition").and_then(|v| v.as_str()) {
+                    let text = html2text::from_read(d.as_bytes(), 200).unwrap_or_default();
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !text.is_empty() {
+                        result.push_str(&format!("{}. {}\n", i + 1, text));
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Title, short description, and opening paragraph of an article, with its link.
+/// Missing articles come back as an error object without an extract.
+fn format_wikipedia(json: &Value) -> String {
+    let field = |name: &str| json.get(name).and_then(|v| v.as_str()).unwrap_or("");
+    let extract = field("extract").trim();
+    if extract.is_empty() {
+        return String::new();
+    }
+    let mut result = format!("# {}\n", field("title"));
+    if !field("description").is_empty() {
+        result.push_str(&format!("{}\n", field("description")));
+    }
+    result.push_str(&format!("\n{}\n", extract));
+    if field("type") == "disambiguation" {
+        result.push_str("\nSeveral articles share this title; select more words to narrow it.\n");
+    }
+    if let Some(url) = json
+        .pointer("/content_urls/desktop/page")
+        .and_then(|v| v.as_str())
+    {
+        result.push_str(&format!("\n{}\n", url));
+    }
+    result
+}
+
+/// The summary API address of the article `title`. Titles use underscores for spaces, and
+/// the rest is percent-encoded as one path segment, so a `/`, `#`, `?` or `%` in it stays
+/// part of the title.
+fn wikipedia_url(language: &str, title: &str) -> Option<reqwest::Url> {
+    let mut url = reqwest::Url::parse(&format!(
+        "https://{}.wikipedia.org/api/rest_v1/page/summary",
+        language
+    ))
+    .ok()?;
+    url.path_segments_mut().ok()?.push(&title.replace(' ', "_"));
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikipedia_titles_are_one_encoded_path_segment() {
+        let url = |title| wikipedia_url("en", title).unwrap().to_string();
+        let base = "https://en.wikipedia.org/api/rest_v1/page/summary/";
+        assert_eq!(url("Moby Dick"), format!("{}Moby_Dick", base));
+        assert_eq!(url("C#"), format!("{}C%23", base));
+        assert_eq!(url("AC/DC"), format!("{}AC%2FDC", base));
+        assert_eq!(url("Who? 100%"), format!("{}Who%3F_100%25", base));
+        assert!(wikipedia_url("e n", "Moby").is_none());
+    }
+}
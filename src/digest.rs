@@ -0,0 +1,155 @@
+//! A Markdown digest of a week of reading: words and minutes per day, the books read,
+//! and the notes and words collected, for pasting into a weekly review.
+
+use crate::db::{AnnotationRecord, BookRecord, Db, VocabRecord};
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+
+/// Days a digest covers, ending with the day it is for.
+pub const DIGEST_DAYS: u64 = 7;
+
+/// One book read during the week.
+#[derive(Clone, Debug)]
+pub struct BookWeek {
+    pub book: BookRecord,
+    pub words: usize,
+}
+
+/// What was read and collected from `from` to `to`, both included.
+pub struct Digest {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Words and seconds read on each day of the week, quiet days included.
+    pub days: Vec<(NaiveDate, usize, u64)>,
+    /// Books read in, most words first.
+    pub books: Vec<BookWeek>,
+    /// Notes made, each with the title of its book.
+    pub notes: Vec<(String, AnnotationRecord)>,
+    pub vocabulary: Vec<VocabRecord>,
+}
+
+impl Digest {
+    /// The week ending with `to`.
+    pub fn gather(db: &Db, to: NaiveDate) -> Result<Self> {
+        let from = to - Days::new(DIGEST_DAYS - 1);
+        let daily = db.get_daily_reading()?;
+        let days = from
+            .iter_days()
+            .take_while(|day| *day <= to)
+            .map(|day| {
+                let key = day.format("%Y-%m-%d").to_string();
+                daily
+                    .iter()
+                    .find(|(date, _, _)| *date == key)
+                    .map_or((day, 0, 0), |(_, words, secs)| (day, *words, *secs))
+            })
+            .collect();
+        let library = db.get_books()?;
+        let title = |id: i32| {
+            library
+                .iter()
+                .find(|b| b.id == id)
+                .map(|b| b.title.clone())
+                .unwrap_or_else(|| "Removed book".to_string())
+        };
+        let books = db
+            .get_words_by_book(from, to)?
+            .into_iter()
+            .filter_map(|(id, words)| {
+                let book = library.iter().find(|b| b.id == id)?.clone();
+                Some(BookWeek { book, words })
+            })
+            .collect();
+        let notes = db
+            .get_annotations_created_between(from, to)?
+            .into_iter()
+            .map(|note| (title(note.book_id), note))
+            .collect();
+        Ok(Self {
+            from,
+            to,
+            days,
+            books,
+            notes,
+            vocabulary: db.get_vocabulary_added_between(from, to)?,
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut output = format!(
+            "# Reading digest: {} to {}\n\n",
+            self.from.format("%b %-d"),
+            self.to.format("%b %-d, %Y")
+        );
+
+        output.push_str("## Reading per day\n\n");
+        output.push_str("| Day | Words | Minutes |\n|---|---:|---:|\n");
+        for (day, words, secs) in &self.days {
+            output.push_str(&format!(
+                "| {} | {} | {} |\n",
+                day.format("%a %b %-d"),
+                words,
+                secs / 60
+            ));
+        }
+        let words: usize = self.days.iter().map(|(_, w, _)| w).sum();
+        let secs: u64 = self.days.iter().map(|(_, _, s)| s).sum();
+        output.push_str(&format!(
+            "| **Total** | **{}** | **{}** |\n\n",
+            words,
+            secs / 60
+        ));
+
+        output.push_str("## Books\n\n");
+        if self.books.is_empty() {
+            output.push_str("No reading this week.\n");
+        }
+        for week in &self.books {
+            output.push_str(&format!(
+                "- **{}** by {}: {} words, now {:.0}% through\n",
+                week.book.title,
+                week.book.author,
+                week.words,
+                week.book.progress() * 100.0
+            ));
+        }
+
+        output.push_str(&format!("\n## New notes ({})\n", self.notes.len()));
+        if self.notes.is_empty() {
+            output.push_str("\nNone this week.\n");
+        }
+        let mut book = None;
+        for (title, note) in &self.notes {
+            if book != Some(note.book_id) {
+                book = Some(note.book_id);
+                output.push_str(&format!("\n### {}\n\n", title));
+            }
+            output.push_str(&format!(
+                "- {} *({})*\n",
+                note.content.replace('\n', " "),
+                note.kind
+            ));
+            if let Some(ref text) = note.note {
+                output.push_str(&format!("  - {}\n", text.replace('\n', " ")));
+            }
+        }
+
+        output.push_str(&format!("\n## New words ({})\n\n", self.vocabulary.len()));
+        if self.vocabulary.is_empty() {
+            output.push_str("None this week.\n");
+        }
+        for word in &self.vocabulary {
+            // The first line of a definition past any heading is its gist.
+            let gist = word
+                .definition
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty() && !l.starts_with('#'));
+            match gist {
+                Some(gist) => output.push_str(&format!("- **{}**: {}\n", word.word, gist)),
+                None => output.push_str(&format!("- **{}**\n", word.word)),
+            }
+        }
+        output
+    }
+}
@@ -0,0 +1,781 @@
+use crate::db::{AnnotationRecord, BookRecord};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Words of text quoted in a handoff snippet.
+pub const HANDOFF_QUOTE_WORDS: usize = 6;
+
+/// File formats notes can be exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+    Csv,
+    Html,
+    Org,
+}
+
+impl ExportFormat {
+    /// Accepts the file extension or the full name, e.g. `md` or `markdown`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "html" | "htm" => Some(Self::Html),
+            "org" => Some(Self::Org),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Html => "html",
+            Self::Org => "org",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::Json => "JSON",
+            Self::Csv => "CSV",
+            Self::Html => "HTML",
+            Self::Org => "Org",
+        }
+    }
+}
+
+/// How the text of each annotation kind is written in Markdown, so other tools can tell
+/// highlights from questions. A marker is a template with `{text}`, e.g. `=={text}==`, or
+/// a prefix such as `?> `, and applies to every line. Kinds without one are quoted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarkdownMarkers(pub HashMap<String, String>);
+
+impl MarkdownMarkers {
+    pub fn apply(&self, kind: &str, text: &str) -> String {
+        let marker = self.0.get(kind).map(String::as_str).unwrap_or("> ");
+        text.lines()
+            .map(|line| {
+                if marker.contains("{text}") {
+                    marker.replace("{text}", line)
+                } else {
+                    format!("{}{}", marker, line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// One annotation as Markdown, for the clipboard: its marked text and its note.
+pub fn annotation_markdown(a: &AnnotationRecord, markers: &MarkdownMarkers) -> String {
+    let mut output = markers.apply(&a.kind, &a.content);
+    if let Some(ref note) = a.note {
+        output.push_str(&format!("\n\n**Note:** {}", note));
+    }
+    if !a.attachments.is_empty() {
+        output.push_str(&format!(
+            "\n\n**Files:** {}",
+            markdown_links(&a.attachments)
+        ));
+    }
+    output
+}
+
+/// Name a link to an attached file is shown with: its file name.
+fn attachment_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Markdown links to attached files, `[name](<path>)` so paths with spaces still work.
+fn markdown_links(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|p| format!("[{}](<{}>)", attachment_name(p), p))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Field names of the JSON and CSV exports. Scripts rely on them, so only add to them.
+pub const EXPORT_FIELDS: [&str; 12] = [
+    "id",
+    "book",
+    "author",
+    "chapter",
+    "kind",
+    "text",
+    "note",
+    "tags",
+    "created_at",
+    "start_locator",
+    "end_locator",
+    "attachments",
+];
+
+/// One annotation as it appears in the JSON and CSV exports. Chapters count from 1.
+#[derive(Serialize)]
+struct ExportedAnnotation<'a> {
+    id: i32,
+    book: &'a str,
+    author: &'a str,
+    chapter: usize,
+    kind: &'a str,
+    text: &'a str,
+    note: Option<&'a str>,
+    tags: &'a [String],
+    created_at: Option<&'a str>,
+    start_locator: Option<String>,
+    end_locator: Option<String>,
+    attachments: &'a [String],
+}
+
+impl<'a> ExportedAnnotation<'a> {
+    fn new(title: &'a str, author: &'a str, a: &'a AnnotationRecord) -> Self {
+        Self {
+            id: a.id,
+            book: title,
+            author,
+            chapter: a.chapter + 1,
+            kind: &a.kind,
+            text: &a.content,
+            note: a.note.as_deref(),
+            tags: &a.tags,
+            created_at: a.created_at.as_deref(),
+            start_locator: a.start_locator.as_ref().map(|l| l.to_string()),
+            end_locator: a.end_locator.as_ref().map(|l| l.to_string()),
+            attachments: &a.attachments,
+        }
+    }
+}
+
+/// Renders a book's annotations in the given format.
+pub fn render_annotations(
+    format: ExportFormat,
+    title: &str,
+    author: &str,
+    source: &str,
+    annotations: &[AnnotationRecord],
+    markers: &MarkdownMarkers,
+) -> String {
+    match format {
+        ExportFormat::Markdown => annotations_markdown(title, author, source, annotations, markers),
+        ExportFormat::Json => annotations_json(title, author, annotations),
+        ExportFormat::Csv => annotations_csv(title, author, annotations),
+        ExportFormat::Html => annotations_html(title, author, annotations),
+        ExportFormat::Org => annotations_org(title, author, source, annotations),
+    }
+}
+
+/// Renders a book's annotations as Markdown with YAML frontmatter for Obsidian/Logseq.
+pub fn annotations_markdown(
+    title: &str,
+    author: &str,
+    source: &str,
+    annotations: &[AnnotationRecord],
+    markers: &MarkdownMarkers,
+) -> String {
+    markdown_note(title, author, source, annotations, markers, false)
+}
+
+/// The per-book note kept in an Obsidian vault: the Markdown export with a stable
+/// `^tbook-<id>` block ID after each annotation, so links to a note survive rewrites.
+pub fn annotations_vault_note(
+    title: &str,
+    author: &str,
+    source: &str,
+    annotations: &[AnnotationRecord],
+    markers: &MarkdownMarkers,
+) -> String {
+    markdown_note(title, author, source, annotations, markers, true)
+}
+
+/// Block ID of an annotation in the vault note, without the leading `^`.
+pub fn vault_block_id(annotation_id: i32) -> String {
+    format!("tbook-{}", annotation_id)
+}
+
+fn markdown_note(
+    title: &str,
+    author: &str,
+    source: &str,
+    annotations: &[AnnotationRecord],
+    markers: &MarkdownMarkers,
+    block_ids: bool,
+) -> String {
+    let mut output = String::new();
+    output.push_str("---\n");
+    output.push_str(&format!("title: \"{}\"\n", title));
+    output.push_str(&format!("author: \"{}\"\n", author));
+    output.push_str(&format!("source: \"{}\"\n", source));
+    output.push_str(&format!(
+        "exported: {}\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    ));
+    output.push_str("tags: [tbook, reading-notes]\n");
+    output.push_str("---\n\n");
+
+    output.push_str(&format!("# Reading Notes: {}\n\n", title));
+
+    for a in annotations {
+        output.push_str(&format!("### Chapter {}\n", a.chapter + 1));
+        output.push_str(&format!("{}\n", markers.apply(&a.kind, &a.content)));
+        if let Some(ref note) = a.note {
+            output.push_str(&format!("\n**Note:** {}\n", note));
+        }
+        if !a.tags.is_empty() {
+            let tags: Vec<String> = a.tags.iter().map(|t| format!("#{}", t)).collect();
+            output.push_str(&format!("\n**Tags:** {}\n", tags.join(" ")));
+        }
+        if !a.attachments.is_empty() {
+            output.push_str(&format!(
+                "\n**Files:** {}\n",
+                markdown_links(&a.attachments)
+            ));
+        }
+        if block_ids {
+            // Obsidian wants the ID of a quote on its own line, set off by blank lines.
+            output.push_str(&format!("\n^{}\n", vault_block_id(a.id)));
+        }
+        output.push_str("\n---\n\n");
+    }
+    output
+}
+
+/// A JSON array with one object per annotation, keyed by [`EXPORT_FIELDS`].
+pub fn annotations_json(title: &str, author: &str, annotations: &[AnnotationRecord]) -> String {
+    let records: Vec<ExportedAnnotation> = annotations
+        .iter()
+        .map(|a| ExportedAnnotation::new(title, author, a))
+        .collect();
+    let mut output = serde_json::to_string_pretty(&records).unwrap_or_else(|_| "[]".to_string());
+    output.push('\n');
+    output
+}
+
+/// Quotes a CSV field when it holds a separator, quote, or line break (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A CSV table with a header row of [`EXPORT_FIELDS`]. Tags are separated by spaces and
+/// attached files by ` | `.
+pub fn annotations_csv(title: &str, author: &str, annotations: &[AnnotationRecord]) -> String {
+    let mut output = EXPORT_FIELDS.join(",");
+    output.push_str("\r\n");
+    for a in annotations {
+        let record = ExportedAnnotation::new(title, author, a);
+        let row = [
+            record.id.to_string(),
+            record.book.to_string(),
+            record.author.to_string(),
+            record.chapter.to_string(),
+            record.kind.to_string(),
+            record.text.to_string(),
+            record.note.unwrap_or_default().to_string(),
+            record.tags.join(" "),
+            record.created_at.unwrap_or_default().to_string(),
+            record.start_locator.unwrap_or_default(),
+            record.end_locator.unwrap_or_default(),
+            record.attachments.join(" | "),
+        ];
+        let row: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        output.push_str(&row.join(","));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A standalone HTML page, one `<section>` per annotation with its kind as a class.
+pub fn annotations_html(title: &str, author: &str, annotations: &[AnnotationRecord]) -> String {
+    let title = escape_html(title);
+    let mut output = String::new();
+    output.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    output.push_str(&format!("<title>Reading Notes: {}</title>\n", title));
+    output.push_str("</head>\n<body>\n");
+    output.push_str(&format!("<h1>Reading Notes: {}</h1>\n", title));
+    output.push_str(&format!(
+        "<p class=\"author\">{}</p>\n",
+        escape_html(author)
+    ));
+    for a in annotations {
+        output.push_str(&format!(
+            "<section class=\"{}\">\n<h3>Chapter {}</h3>\n",
+            escape_html(&a.kind),
+            a.chapter + 1
+        ));
+        output.push_str(&format!(
+            "<blockquote>{}</blockquote>\n",
+            escape_html(&a.content).replace('\n', "<br>\n")
+        ));
+        if let Some(ref note) = a.note {
+            output.push_str(&format!("<p class=\"note\">{}</p>\n", escape_html(note)));
+        }
+        if !a.tags.is_empty() {
+            let tags: Vec<String> = a
+                .tags
+                .iter()
+                .map(|t| format!("#{}", escape_html(t)))
+                .collect();
+            output.push_str(&format!("<p class=\"tags\">{}</p>\n", tags.join(" ")));
+        }
+        if !a.attachments.is_empty() {
+            let links: Vec<String> = a
+                .attachments
+                .iter()
+                .map(|p| {
+                    format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape_html(p),
+                        escape_html(&attachment_name(p))
+                    )
+                })
+                .collect();
+            output.push_str(&format!("<p class=\"files\">{}</p>\n", links.join(" ")));
+        }
+        output.push_str("</section>\n");
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// An Org document with a heading per annotation; tags become Org heading tags.
+pub fn annotations_org(
+    title: &str,
+    author: &str,
+    source: &str,
+    annotations: &[AnnotationRecord],
+) -> String {
+    let mut output = String::new();
+    output.push_str(&format!("#+TITLE: Reading Notes: {}\n", title));
+    output.push_str(&format!("#+AUTHOR: {}\n", author));
+    output.push_str(&format!("#+SOURCE: {}\n\n", source));
+    for a in annotations {
+        output.push_str(&format!("* Chapter {}", a.chapter + 1));
+        if !a.tags.is_empty() {
+            // Org tags only allow letters, digits, `_`, `@`, `#`, and `%`.
+            let tags: Vec<String> = a.tags.iter().map(|t| t.replace('-', "_")).collect();
+            output.push_str(&format!(" :{}:", tags.join(":")));
+        }
+        output.push('\n');
+        output.push_str(&format!(":PROPERTIES:\n:KIND: {}\n", a.kind));
+        if let Some(ref created) = a.created_at {
+            output.push_str(&format!(":CREATED: {}\n", created));
+        }
+        output.push_str(":END:\n");
+        output.push_str(&format!("#+BEGIN_QUOTE\n{}\n#+END_QUOTE\n", a.content));
+        if let Some(ref note) = a.note {
+            output.push_str(&format!("{}\n", note));
+        }
+        for path in &a.attachments {
+            output.push_str(&format!("- [[file:{}][{}]]\n", path, attachment_name(path)));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// File name an export is written to, e.g. `notes_moby_dick.md`.
+pub fn notes_filename(title: &str, format: ExportFormat) -> String {
+    let name = file_safe(title).to_lowercase().replace(' ', "_");
+    let name = if name.is_empty() { "untitled" } else { &name };
+    format!("notes_{}.{}", name, format.extension())
+}
+
+/// A title without the characters file systems reserve or leading dots, so that it can
+/// only name a file inside the folder it is joined to.
+fn file_safe(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect();
+    name.trim().trim_start_matches('.').trim().to_string()
+}
+
+/// What an export does with a notes file that is already there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportMode {
+    /// Replaces it.
+    #[default]
+    Overwrite,
+    /// Adds the new export after it, so notes edited by hand are kept.
+    Append,
+    /// Leaves it alone and writes a new file named with the time of the export.
+    Timestamped,
+}
+
+impl ExportMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "overwrite" => Some(Self::Overwrite),
+            "append" => Some(Self::Append),
+            "timestamped" => Some(Self::Timestamped),
+            _ => None,
+        }
+    }
+
+    /// Where an export to `path` goes: timestamped ones get the time before the extension,
+    /// e.g. `notes_dune-20240227-183005.md`.
+    pub fn path(self, path: &Path, now: NaiveDateTime) -> PathBuf {
+        if self != Self::Timestamped {
+            return path.to_path_buf();
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let stamp = now.format("%Y%m%d-%H%M%S");
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, stamp, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, stamp),
+        };
+        path.with_file_name(name)
+    }
+}
+
+/// Writes an export to `path` so that a crash or full disk never leaves it half written:
+/// the text goes to a temporary file next to it, which then replaces it. Appending keeps
+/// what the file held before, followed by a blank line. The folder must already exist.
+pub fn write_export(path: &Path, contents: &str, mode: ExportMode) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut text = String::new();
+    if mode == ExportMode::Append {
+        match std::fs::read_to_string(path) {
+            Ok(existing) if !existing.trim().is_empty() => {
+                text.push_str(existing.trim_end());
+                text.push_str("\n\n");
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    text.push_str(contents);
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+    let written = std::fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| std::fs::rename(&temp, path)) {
+        std::fs::remove_file(&temp).ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// File name of a book's note in the vault: the title with characters Obsidian does not
+/// allow in note names removed, e.g. `Moby Dick.md`.
+pub fn vault_filename(title: &str) -> String {
+    let name = file_safe(&title.replace(['#', '^', '[', ']'], ""));
+    if name.is_empty() {
+        "Untitled.md".to_string()
+    } else {
+        format!("{}.md", name)
+    }
+}
+
+/// One line saying where reading stopped, for pasting elsewhere, e.g.
+/// `Dune — ch. 12, 43%, "the spice must flow…"`. Paged books give the page instead.
+pub fn handoff_snippet(book: &BookRecord, quote: &str) -> String {
+    let place = match book.total_pages {
+        Some(_) => format!("p. {}", book.current_chapter + 1),
+        None => format!("ch. {}", book.current_chapter + 1),
+    };
+    let mut snippet = format!(
+        "{} — {}, {:.0}%",
+        book.title.trim(),
+        place,
+        book.progress() * 100.0
+    );
+    if !quote.trim().is_empty() {
+        snippet.push_str(&format!(", \"{}…\"", quote.trim()));
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(chapter: usize, content: &str, note: Option<&str>) -> AnnotationRecord {
+        AnnotationRecord {
+            id: 1,
+            book_id: 1,
+            chapter,
+            start_line: 0,
+            start_word: 0,
+            end_line: 0,
+            end_word: 0,
+            content: content.to_string(),
+            note: note.map(str::to_string),
+            kind: "highlight".to_string(),
+            start_locator: None,
+            end_locator: None,
+            created_at: None,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn markdown_quotes_every_line_and_includes_notes() {
+        let mut tagged = annotation(2, "Call me\nIshmael.", Some("opening line"));
+        tagged.tags = vec!["names".to_string(), "openings".to_string()];
+        let out = annotations_markdown(
+            "Moby Dick",
+            "Herman Melville",
+            "/books/moby.epub",
+            &[tagged],
+            &MarkdownMarkers::default(),
+        );
+        assert!(out.starts_with("---\ntitle: \"Moby Dick\"\n"));
+        assert!(out.contains("### Chapter 3\n> Call me\n> Ishmael.\n"));
+        assert!(out.contains("**Note:** opening line"));
+        assert!(out.contains("**Tags:** #names #openings"));
+    }
+
+    #[test]
+    fn markers_set_each_kind_apart() {
+        let markers = MarkdownMarkers(HashMap::from([
+            ("highlight".to_string(), "=={text}==".to_string()),
+            ("question".to_string(), "?> ".to_string()),
+        ]));
+        let mut question = annotation(0, "Who is\nAhab?", Some("find out"));
+        question.kind = "question".to_string();
+        let mut summary = annotation(1, "He goes to sea.", None);
+        summary.kind = "summary".to_string();
+        let highlight = annotation(2, "Call me Ishmael.", None);
+
+        let out = annotations_markdown(
+            "Moby Dick",
+            "Melville",
+            "/b.epub",
+            &[question.clone(), summary, highlight],
+            &markers,
+        );
+        assert!(out.contains("### Chapter 1\n?> Who is\n?> Ahab?\n"));
+        assert!(out.contains("### Chapter 2\n> He goes to sea.\n"));
+        assert!(out.contains("### Chapter 3\n==Call me Ishmael.==\n"));
+        assert_eq!(
+            annotation_markdown(&question, &markers),
+            "?> Who is\n?> Ahab?\n\n**Note:** find out"
+        );
+    }
+
+    #[test]
+    fn json_and_csv_use_the_stable_field_names() {
+        let mut tagged = annotation(2, "Call me, \"Ishmael\".", Some("opening line"));
+        tagged.tags = vec!["names".to_string(), "openings".to_string()];
+        let json = annotations_json(
+            "Moby Dick",
+            "Herman Melville",
+            std::slice::from_ref(&tagged),
+        );
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let record = records[0].as_object().unwrap();
+        let keys: Vec<&str> = record.keys().map(String::as_str).collect();
+        let mut expected = EXPORT_FIELDS.to_vec();
+        expected.sort();
+        assert_eq!(keys, expected);
+        assert_eq!(record["chapter"], 3);
+        assert_eq!(record["tags"], serde_json::json!(["names", "openings"]));
+
+        let csv = annotations_csv("Moby Dick", "Herman Melville", &[tagged]);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(EXPORT_FIELDS.join(",").as_str()));
+        assert_eq!(
+            lines.next(),
+            Some(
+                "1,Moby Dick,Herman Melville,3,highlight,\"Call me, \"\"Ishmael\"\".\",\
+                 opening line,names openings,,,,"
+            )
+        );
+    }
+
+    #[test]
+    fn html_escapes_and_org_tags_headings() {
+        let mut tagged = annotation(0, "<b>bold</b> & more", None);
+        tagged.tags = vec!["sea-life".to_string()];
+        let html = annotations_html("A & B", "", std::slice::from_ref(&tagged));
+        assert!(html.contains("<title>Reading Notes: A &amp; B</title>"));
+        assert!(html.contains("<blockquote>&lt;b&gt;bold&lt;/b&gt; &amp; more</blockquote>"));
+        assert!(html.contains("<p class=\"tags\">#sea-life</p>"));
+
+        let org = annotations_org("A & B", "", "/a.epub", &[tagged]);
+        assert!(org.starts_with("#+TITLE: Reading Notes: A & B\n"));
+        assert!(org.contains("* Chapter 1 :sea_life:\n:PROPERTIES:\n:KIND: highlight\n"));
+        assert!(org.contains("#+BEGIN_QUOTE\n<b>bold</b> & more\n#+END_QUOTE\n"));
+    }
+
+    #[test]
+    fn attached_files_become_links() {
+        let mut noted = annotation(0, "A diagram.", Some("see sketch"));
+        noted.attachments = vec![
+            "/notes/my sketch.png".to_string(),
+            "/notes/run.log".to_string(),
+        ];
+        let notes = std::slice::from_ref(&noted);
+        let md = annotations_markdown("B", "", "/b.epub", notes, &MarkdownMarkers::default());
+        assert!(md.contains(
+            "**Files:** [my sketch.png](</notes/my sketch.png>), [run.log](</notes/run.log>)"
+        ));
+        assert!(annotation_markdown(&noted, &MarkdownMarkers::default())
+            .ends_with("**Note:** see sketch\n\n**Files:** [my sketch.png](</notes/my sketch.png>), [run.log](</notes/run.log>)"));
+        assert!(annotations_html("B", "", notes).contains(
+            "<p class=\"files\"><a href=\"/notes/my sketch.png\">my sketch.png</a> <a href=\"/notes/run.log\">run.log</a></p>"
+        ));
+        assert!(annotations_org("B", "", "/b.epub", notes)
+            .contains("see sketch\n- [[file:/notes/my sketch.png][my sketch.png]]\n- [[file:/notes/run.log][run.log]]\n"));
+        let csv = annotations_csv("B", "", notes);
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .ends_with(",/notes/my sketch.png | /notes/run.log")
+        );
+    }
+
+    #[test]
+    fn handoff_names_the_place_and_quotes_the_text() {
+        let mut book = BookRecord {
+            id: 1,
+            title: "Dune".to_string(),
+            author: "Frank Herbert".to_string(),
+            path: "/books/dune.epub".to_string(),
+            current_chapter: 11,
+            current_line: 0,
+            total_chapters: 48,
+            total_lines: 0,
+            lines_read: 0,
+            language: None,
+            dictionary: None,
+            status: "reading".to_string(),
+            total_pages: None,
+            locator: None,
+            file_size: None,
+            content_hash: None,
+            total_words: Some(1000),
+            position_words: 430,
+            paragraph_spacing: None,
+            first_line_indent: None,
+            paged: false,
+            preset: None,
+            tags: Vec::new(),
+        };
+        assert_eq!(
+            handoff_snippet(&book, "the spice must flow"),
+            "Dune — ch. 12, 43%, \"the spice must flow…\""
+        );
+        book.total_pages = Some(100);
+        assert_eq!(handoff_snippet(&book, " "), "Dune — p. 12, 12%");
+    }
+
+    #[test]
+    fn filename_is_lowercase_snake_case() {
+        assert_eq!(
+            notes_filename("Moby Dick", ExportFormat::Markdown),
+            "notes_moby_dick.md"
+        );
+        assert_eq!(
+            notes_filename("Moby Dick", ExportFormat::Org),
+            "notes_moby_dick.org"
+        );
+        // A title can't reach outside the export folder.
+        assert_eq!(
+            notes_filename("AC/DC", ExportFormat::Markdown),
+            "notes_acdc.md"
+        );
+        assert_eq!(
+            notes_filename("x/../../evil", ExportFormat::Markdown),
+            "notes_x....evil.md"
+        );
+        assert_eq!(
+            notes_filename("..", ExportFormat::Markdown),
+            "notes_untitled.md"
+        );
+    }
+
+    #[test]
+    fn vault_note_gives_each_annotation_a_block_id() {
+        let mut first = annotation(0, "Call me Ishmael.", Some("opening"));
+        first.id = 7;
+        let mut second = annotation(2, "line one\nline two", None);
+        second.id = 12;
+        let markers = MarkdownMarkers::default();
+        let note = annotations_vault_note(
+            "Moby Dick",
+            "Melville",
+            "/b.epub",
+            &[first, second],
+            &markers,
+        );
+        assert!(note.contains("**Note:** opening\n\n^tbook-7\n"));
+        assert!(note.contains("> line one\n> line two\n\n^tbook-12\n"));
+        assert!(
+            !annotations_markdown("Moby Dick", "Melville", "/b.epub", &[], &markers).contains('^')
+        );
+    }
+
+    #[test]
+    fn vault_filename_keeps_the_title_readable() {
+        assert_eq!(vault_filename("Moby Dick"), "Moby Dick.md");
+        assert_eq!(vault_filename("Dune: Messiah?"), "Dune Messiah.md");
+        assert_eq!(vault_filename(" ../ "), "Untitled.md");
+    }
+
+    #[test]
+    fn exports_replace_append_or_take_a_new_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes").join("notes_dune.md");
+        // Only the caller makes the folder.
+        assert!(write_export(&path, "# First\n", ExportMode::Overwrite).is_err());
+        std::fs::create_dir(path.parent().unwrap()).unwrap();
+        write_export(&path, "# First\n", ExportMode::Overwrite).unwrap();
+        write_export(&path, "# Second\n", ExportMode::Append).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# First\n\n# Second\n"
+        );
+        write_export(&path, "# Third\n", ExportMode::Overwrite).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Third\n");
+        // Nothing is left behind from the temporary files.
+        let names: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["notes_dune.md"]);
+
+        let now =
+            NaiveDateTime::parse_from_str("2024-02-27 18:30:05", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            ExportMode::Timestamped.path(&path, now),
+            path.with_file_name("notes_dune-20240227-183005.md")
+        );
+        assert_eq!(ExportMode::Append.path(&path, now), path);
+        assert_eq!(ExportMode::parse(" Append "), Some(ExportMode::Append));
+        assert_eq!(ExportMode::parse("clobber"), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! Reading-group positions: a short token naming a book by its file, a place in it, and
+//! who is there, for passing around a group chat. Tokens carry an HMAC-SHA256 keyed with
+//! a secret the group shares, so a pasted one can be trusted to be a member's and to be
+//! whole. Without a secret there is nothing to trust, so none are made or read.
+
+use crate::db::BookRecord;
+use crate::locator::Locator;
+use anyhow::{Result, bail};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+const PREFIX: &str = "tbook1";
+/// Hex digits of the book's content hash a token keeps, plenty to tell books apart.
+const HASH_DIGITS: usize = 16;
+const SIGNATURE_DIGITS: usize = 16;
+const NO_SECRET: &str = "Set group_secret in tbook.toml to share positions with a group";
+
+/// Where one reader is in a book.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupPosition {
+    pub reader: String,
+    /// The start of the SHA-256 of the book's file, so everyone needs the same file.
+    pub book_hash: String,
+    pub locator: Locator,
+    /// Words before the position across the book, once the book's words are counted.
+    pub words: Option<usize>,
+}
+
+impl GroupPosition {
+    /// The position of `book` as it was last saved, or `None` for a book whose file has
+    /// not been fingerprinted.
+    pub fn of_book(book: &BookRecord, reader: &str) -> Option<Self> {
+        let hash = book.content_hash.as_deref()?;
+        Some(Self {
+            reader: reader.trim().to_string(),
+            book_hash: hash.chars().take(HASH_DIGITS).collect(),
+            locator: book
+                .locator
+                .clone()
+                .unwrap_or_else(|| Locator::new(book.current_chapter, 0)),
+            words: book.total_words.map(|_| book.position_words),
+        })
+    }
+
+    /// e.g. `tbook1.3f9a0c12d4e5b6a7.12-4031.88120.0c1d2e3f4a5b6c7d.Ana`. The name comes
+    /// last so it may hold dots. Fails without a secret to sign with.
+    pub fn to_token(&self, secret: &str) -> Result<String> {
+        if secret.is_empty() {
+            bail!(NO_SECRET);
+        }
+        let body = self.body();
+        let signature: String = signer(secret, &body, &self.reader)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .take(SIGNATURE_DIGITS / 2)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(format!("{}.{}.{}", body, signature, self.reader))
+    }
+
+    /// Reads a token, refusing one signed with another secret or changed since, and any
+    /// token at all without a secret to check it with.
+    pub fn parse(token: &str, secret: &str) -> Result<Self> {
+        if secret.is_empty() {
+            bail!(NO_SECRET);
+        }
+        let mut fields = token.trim().splitn(6, '.');
+        let mut next = || fields.next().unwrap_or_default();
+        let (prefix, hash, place, words, signed, reader) =
+            (next(), next(), next(), next(), next(), next());
+        if prefix != PREFIX {
+            bail!("Not a reading-group position");
+        }
+        let Some((spine, offset)) = place.split_once('-') else {
+            bail!("Not a reading-group position");
+        };
+        let (Ok(spine), Ok(offset)) = (spine.parse(), offset.parse()) else {
+            bail!("Not a reading-group position");
+        };
+        let words = match words {
+            "-" => None,
+            words => match words.parse() {
+                Ok(words) => Some(words),
+                Err(_) => bail!("Not a reading-group position"),
+            },
+        };
+        let position = Self {
+            reader: reader.to_string(),
+            book_hash: hash.to_string(),
+            locator: Locator::new(spine, offset),
+            words,
+        };
+        let signed = (signed.len() == SIGNATURE_DIGITS)
+            .then(|| {
+                (0..signed.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(signed.get(i..i + 2)?, 16).ok())
+                    .collect::<Option<Vec<u8>>>()
+            })
+            .flatten();
+        let genuine = signed.is_some_and(|signed| {
+            signer(secret, &position.body(), reader)
+                .verify_truncated_left(&signed)
+                .is_ok()
+        });
+        if !genuine {
+            bail!("The position was signed with another group secret, or has been changed");
+        }
+        Ok(position)
+    }
+
+    fn body(&self) -> String {
+        let words = self.words.map_or("-".to_string(), |w| w.to_string());
+        format!(
+            "{}.{}.{}-{}.{}",
+            PREFIX, self.book_hash, self.locator.spine, self.locator.offset, words
+        )
+    }
+
+    /// The book in `books` the position is in.
+    pub fn find_book<'a>(&self, books: &'a [BookRecord]) -> Option<&'a BookRecord> {
+        books.iter().find(|b| {
+            b.content_hash
+                .as_deref()
+                .is_some_and(|h| !self.book_hash.is_empty() && h.starts_with(&self.book_hash))
+        })
+    }
+
+    /// How far the position is from `mine`, in words and a share of the book's
+    /// `total_words` when both are counted, and in chapters otherwise.
+    pub fn gap(&self, mine: &GroupPosition, total_words: Option<usize>) -> String {
+        let reader = &self.reader;
+        if let (Some(theirs), Some(ours)) = (self.words, mine.words) {
+            let (words, direction) = match theirs.cmp(&ours) {
+                std::cmp::Ordering::Equal => return format!("{} is where you are", reader),
+                std::cmp::Ordering::Greater => (theirs - ours, "ahead of"),
+                std::cmp::Ordering::Less => (ours - theirs, "behind"),
+            };
+            return match total_words.filter(|t| *t > 0) {
+                Some(total) => format!(
+                    "{} is {} words ({:.0}%) {} you",
+                    reader,
+                    words,
+                    words as f64 * 100.0 / total as f64,
+                    direction
+                ),
+                None => format!("{} is {} words {} you", reader, words, direction),
+            };
+        }
+        let (theirs, ours) = (&self.locator, &mine.locator);
+        let direction = if (theirs.spine, theirs.offset) > (ours.spine, ours.offset) {
+            "ahead of"
+        } else {
+            "behind"
+        };
+        match theirs.spine.abs_diff(ours.spine) {
+            0 if theirs.offset == ours.offset => format!("{} is where you are", reader),
+            0 => format!("{} is {} you in this chapter", reader, direction),
+            1 => format!("{} is a chapter {} you", reader, direction),
+            n => format!("{} is {} chapters {} you", reader, n, direction),
+        }
+    }
+}
+
+/// An HMAC over the token's body and name; verifying with it compares in constant time.
+fn signer(secret: &str, body: &str, reader: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    mac.update(b"\n");
+    mac.update(reader.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(reader: &str, spine: usize, words: Option<usize>) -> GroupPosition {
+        GroupPosition {
+            reader: reader.to_string(),
+            book_hash: "3f9a0c12d4e5b6a7".to_string(),
+            locator: Locator::new(spine, 4031),
+            words,
+        }
+    }
+
+    #[test]
+    fn tokens_read_back_only_with_their_secret() {
+        let ana = position("Ana. B", 12, Some(88120));
+        let token = ana.to_token("moby").unwrap();
+        assert!(token.starts_with("tbook1.3f9a0c12d4e5b6a7.12-4031.88120."));
+        assert!(token.ends_with(".Ana. B"));
+        assert_eq!(GroupPosition::parse(&token, "moby").unwrap(), ana);
+
+        assert!(GroupPosition::parse(&token, "dick").is_err());
+        let moved = token.replace("12-4031", "13-4031");
+        assert!(GroupPosition::parse(&moved, "moby").is_err());
+        let renamed = token.replace("Ana", "Bob");
+        assert!(GroupPosition::parse(&renamed, "moby").is_err());
+        assert!(GroupPosition::parse("Dune, ch. 12", "moby").is_err());
+
+        let uncounted = position("Ana", 2, None);
+        let token = uncounted.to_token("moby").unwrap();
+        assert!(token.contains(".2-4031.-."));
+        assert_eq!(GroupPosition::parse(&token, "moby").unwrap(), uncounted);
+
+        // Without a secret anyone could make a token, so none are made or taken.
+        assert!(uncounted.to_token("").is_err());
+        assert!(GroupPosition::parse(&token, "").is_err());
+    }
+
+    #[test]
+    fn gaps_are_in_words_when_counted_and_chapters_otherwise() {
+        let me = position("me", 10, Some(80000));
+        assert_eq!(
+            position("Ana", 12, Some(88120)).gap(&me, Some(203000)),
+            "Ana is 8120 words (4%) ahead of you"
+        );
+        assert_eq!(
+            position("Bob", 9, Some(79000)).gap(&me, None),
+            "Bob is 1000 words behind you"
+        );
+        assert_eq!(
+            position("Cy", 10, Some(80000)).gap(&me, None),
+            "Cy is where you are"
+        );
+
+        let me = position("me", 10, None);
+        assert_eq!(
+            position("Ana", 13, None).gap(&me, None),
+            "Ana is 3 chapters ahead of you"
+        );
+        assert_eq!(
+            position("Bob", 9, Some(79000)).gap(&me, None),
+            "Bob is a chapter behind you"
+        );
+    }
+}
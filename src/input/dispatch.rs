@@ -0,0 +1,794 @@
+use super::{Action, Effect, Viewport};
+use crate::app::{App, AppView, READ_ONLY_MESSAGE, RenderLine, StatsTab, WHEEL_ROWS};
+use crate::dictionary::{DictionarySource, LookupSettings};
+
+fn wrap_next(index: &mut usize, len: usize) {
+    if len > 0 {
+        *index = (*index + 1) % len;
+    }
+}
+
+fn wrap_prev(index: &mut usize, len: usize) {
+    if len > 0 {
+        *index = if *index > 0 { *index - 1 } else { len - 1 };
+    }
+}
+
+impl App {
+    /// Carries out `action` in the current view and returns what the caller still has to do.
+    pub fn dispatch(&mut self, action: Action, viewport: Viewport) -> Vec<Effect> {
+        let effects = self.dispatch_in_view(action, viewport);
+        self.refresh_annotation_links();
+        effects
+    }
+
+    fn dispatch_in_view(&mut self, action: Action, viewport: Viewport) -> Vec<Effect> {
+        if self.read_only && action.changes_library() {
+            self.status_message = Some(READ_ONLY_MESSAGE.to_string());
+            return Vec::new();
+        }
+        let mut effects = Vec::new();
+        let cover = Effect::ShowCover { debounced: false };
+        match action {
+            Action::Quit => effects.push(Effect::Quit),
+            Action::ToggleHelp if self.view == AppView::Help => return self.back(),
+            Action::ToggleHelp => {
+                self.previous_view = Some(self.view);
+                self.view = AppView::Help;
+            }
+            Action::Back => return self.back(),
+            Action::ConfirmPrompt => return self.confirm_prompt(),
+            Action::CancelPrompt => self.cancel_prompt(),
+            Action::SelectNext => return self.move_selection(true),
+            Action::SelectPrev => return self.move_selection(false),
+            Action::SelectRow(index) => return self.select_row(index),
+            Action::OpenSelected => self.open_selected(),
+            Action::Input(c) => self.input_char(Some(c)),
+            Action::DeleteChar => self.input_char(None),
+
+            Action::RequestRemoval => self.request_book_removal(),
+            Action::Rescan => effects.push(Effect::Scan(self.rescan_library())),
+            Action::CycleImageProtocol => {
+                let next = self.image_picker.protocol_type().next();
+                self.image_picker.set_protocol_type(next);
+                self.refresh_current_book_render_cache().ok();
+                effects.push(cover);
+            }
+            Action::AddBooks => {
+                let home = dirs::home_dir()
+                    .unwrap_or_else(|| ".".into())
+                    .to_string_lossy()
+                    .to_string();
+                self.open_path_input(home);
+            }
+            Action::AddUrl => {
+                self.url_input.clear();
+                self.url_fetch_status = None;
+                self.view = AppView::UrlInput;
+            }
+            Action::OpenLookupSettings => self.open_lookup_settings(),
+            Action::CycleStatus => {
+                self.cycle_selected_book_status().ok();
+            }
+            Action::CycleStatusFilter => {
+                self.cycle_library_status_filter().ok();
+                effects.push(cover);
+            }
+            Action::OpenGlobalSearch => {
+                self.open_global_search();
+                effects.push(cover);
+            }
+            Action::OpenOmnibox => self.open_omnibox(),
+            Action::OpenConceptIndex => {
+                self.open_concept_index().ok();
+            }
+            Action::OpenNoteBrowser => {
+                self.open_note_browser().ok();
+            }
+            Action::OpenClippingReview => {
+                self.open_clipping_review().ok();
+            }
+            Action::OpenStats => self.open_stats(),
+            Action::OpenVocabReview => {
+                self.open_vocab_review().ok();
+            }
+
+            Action::StartScan => effects.push(Effect::Scan(self.begin_scan())),
+            Action::CompletePath => self.complete_explorer_path(),
+            Action::ForgetRecentPath => {
+                self.forget_recent_path().ok();
+            }
+            Action::FetchUrl => {
+                let url = self.url_input.trim().to_string();
+                if !url.is_empty() {
+                    self.url_fetch_status = Some("Fetching article...".into());
+                    effects.push(Effect::FetchArticle(url));
+                }
+            }
+            Action::ToggleLookupField => {
+                self.lookup_editing_dictionary = !self.lookup_editing_dictionary;
+            }
+            Action::SaveLookupSettings => {
+                self.save_lookup_settings().ok();
+                self.view = AppView::Library;
+                effects.push(cover);
+            }
+
+            Action::ToggleSelection if !self.is_scanning => self.toggle_explorer_selection(),
+            Action::SelectAll if !self.is_scanning => self.select_all_explorer_results(),
+            Action::ClearSelection if !self.is_scanning => self.clear_explorer_selection(),
+            Action::ImportAll if !self.is_scanning => {
+                self.select_all_explorer_results();
+                if self.import_explorer_selection().unwrap_or(true) {
+                    return self.finish_import();
+                }
+            }
+            Action::ImportSelection if !self.is_scanning && !self.explorer_results.is_empty() => {
+                if self.import_explorer_selection().unwrap_or(true) {
+                    return self.finish_import();
+                }
+            }
+            Action::ToggleSelection
+            | Action::SelectAll
+            | Action::ClearSelection
+            | Action::ImportAll
+            | Action::ImportSelection => {}
+            Action::SetGroupCollapsed(collapsed) => self.set_explorer_group_collapsed(collapsed),
+            Action::ToggleGroup => self.toggle_explorer_group(),
+
+            Action::RunSearch if self.view == AppView::BookSearch => {
+                self.run_book_search().ok();
+            }
+            Action::RunSearch if self.view == AppView::Omnibox => {
+                self.run_omnibox_search().ok();
+            }
+            Action::RunSearch => {
+                self.run_global_search().ok();
+            }
+            Action::CancelSearch => self.cancel_global_search(),
+            Action::ToggleSearchCase => {
+                self.toggle_search_case();
+                if self.view == AppView::BookSearch {
+                    if !self.book_search_results.is_empty() {
+                        self.run_book_search().ok();
+                    }
+                } else if !self.global_search_results.is_empty()
+                    || self.global_search_progress.is_some()
+                {
+                    self.run_global_search().ok();
+                }
+            }
+            Action::HistoryNext => self.search_history_next(),
+            Action::HistoryPrev => self.search_history_prev(),
+            Action::OpenSavedSearches => {
+                self.remember_selection();
+                self.open_saved_searches().ok();
+            }
+            Action::NameSearch => {
+                if !self.global_search_query.trim().is_empty() {
+                    self.saved_search_name = Some(String::new());
+                }
+            }
+            Action::DeleteSavedSearch => {
+                self.delete_selected_saved_search().ok();
+            }
+
+            Action::ToggleConceptFocus => self.concept_focus_notes = !self.concept_focus_notes,
+
+            Action::MoveStatsCursor(delta) => self.move_stats_cursor(delta),
+            Action::ToggleStatsMetric => self.toggle_stats_metric(),
+            Action::ToggleStatsBreakdown => self.toggle_stats_breakdown(),
+
+            Action::EditTags => self.begin_tag_edit(),
+            Action::AttachFile => self.begin_attach(),
+            Action::CycleNoteBook => self.cycle_note_browser_book(),
+            Action::CycleNoteTag => self.cycle_note_browser_tag(),
+            Action::CycleNotePeriod => self.cycle_note_browser_period(),
+
+            Action::CycleClippingBook(forward) => self.cycle_clipping_book(forward),
+            Action::AssignClippings => {
+                if let Err(e) = self.assign_clippings() {
+                    self.status_message = Some(format!("Could not add the clippings: {}", e));
+                }
+                if self.view == AppView::Library {
+                    self.refresh_library().ok();
+                    effects.push(cover);
+                }
+            }
+            Action::DiscardClipping => {
+                self.discard_clipping().ok();
+                if self.view == AppView::Library {
+                    effects.push(cover);
+                }
+            }
+
+            Action::RevealCard => self.reveal_vocab_card(),
+            Action::GradeCard(grade) => {
+                self.grade_vocab_card(grade).ok();
+            }
+
+            Action::CountDigit(digit) => self.push_count_digit(digit),
+            Action::PendingG => self.pending_g = true,
+            Action::GoToChapterStart => {
+                let _ = self.go_to_chapter_start();
+            }
+            Action::GoToChapterEnd => {
+                let _ = self.go_to_chapter_end(viewport.reader_height);
+            }
+            Action::GoToPercent(percent) => {
+                let _ = self.go_to_percent(percent as f64);
+            }
+            Action::OpenGoTo => self.open_goto_prompt(),
+            Action::ScrollDown => self.scroll_viewport_down(),
+            Action::ScrollHalfScreen(forward) => {
+                let rows = viewport.reader_height;
+                self.scroll_rows(forward, rows / 2, rows);
+            }
+            Action::ScrollScreen(forward) => {
+                let rows = viewport.reader_height;
+                // A couple of rows stay on screen for context, as in vim.
+                self.scroll_rows(forward, rows.saturating_sub(2), rows);
+            }
+            Action::FollowJump(back) => {
+                let _ = self.follow_jump(back);
+            }
+            Action::ScrollWheel(forward) => {
+                self.scroll_rows(forward, WHEEL_ROWS, viewport.reader_height)
+            }
+            Action::ScrollUp => self.scroll_viewport_up(),
+            Action::PageForward => {
+                let _ = self.page_forward(viewport.reader_height);
+            }
+            Action::PageBack => {
+                let _ = self.page_back(viewport.reader_height);
+            }
+            Action::TogglePaged => self.toggle_paged(viewport.reader_height),
+            Action::NextChapter => {
+                let _ = self.next_chapter();
+            }
+            Action::PrevChapter => {
+                let _ = self.prev_chapter();
+            }
+            Action::ToggleFocusMode => self.toggle_focus_mode(),
+            Action::CycleLineFocus => self.cycle_line_focus(),
+            Action::CycleFocusRuler => self.cycle_focus_ruler(),
+            Action::ToggleJustify => self.toggle_justify(),
+            Action::ToggleColumns => self.toggle_columns(),
+            Action::CycleLineNumbers => self.cycle_line_numbers(),
+            Action::CycleProgressGauge => self.cycle_progress_gauge(),
+            Action::TogglePdfReflow => {
+                self.toggle_pdf_reflow().ok();
+            }
+            Action::SummarizeChapter => {
+                if let Some(text) = self.begin_chapter_summary() {
+                    effects.push(Effect::Summarize(text));
+                }
+            }
+            Action::PomodoroToggle => self.pomodoro_toggle(),
+            Action::PomodoroReset => self.pomodoro_reset(),
+            Action::PomodoroSkipBreak => self.pomodoro_skip_break(),
+            Action::EnterSelect => self.view = AppView::Select,
+            Action::OpenAnnotations => {
+                let _ = self.load_annotations();
+            }
+            Action::OpenVocabulary => {
+                let _ = self.load_vocabulary();
+            }
+            Action::ExportNotes => self.export_picker = true,
+            Action::OpenPresets => self.preset_picker = true,
+            Action::SetPreset(preset) => {
+                if let Err(e) = self.set_reading_preset(preset) {
+                    self.status_message = Some(format!("Preset failed: {}", e));
+                }
+            }
+            Action::KeepLocalPosition => self.keep_local_position(),
+            Action::KeepRemotePosition => {
+                self.keep_remote_position().ok();
+            }
+            Action::ExportAs(format) => {
+                self.export_picker = false;
+                self.status_message = Some(match self.export_annotations(format) {
+                    Ok(path) => format!("Exporting notes to {}…", path),
+                    Err(e) => format!("Export failed: {}", e),
+                });
+            }
+            Action::CopyHandoff => match self.handoff_snippet() {
+                Ok(snippet) => {
+                    self.status_message = Some(format!("Copied: {}", snippet));
+                    effects.push(Effect::CopyToClipboard(snippet));
+                }
+                Err(e) => self.status_message = Some(format!("Copy failed: {}", e)),
+            },
+            Action::CopyGroupPosition => match self.group_position_token() {
+                Ok(token) => {
+                    self.status_message = Some(format!("Copied for your group: {}", token));
+                    effects.push(Effect::CopyToClipboard(token));
+                }
+                Err(e) => self.status_message = Some(format!("Copy failed: {}", e)),
+            },
+            Action::JoinGroup => self.group_input = Some(String::new()),
+            Action::CopyNote => {
+                if let Some(markdown) = self.selected_note_markdown() {
+                    self.status_message = Some("Copied the note as Markdown".to_string());
+                    effects.push(Effect::CopyToClipboard(markdown));
+                }
+            }
+            Action::OpenToc => self.open_toc(),
+            Action::OpenFigures => self.open_figures(),
+            Action::OpenLastStops => self.open_last_stops(),
+            Action::DismissSessionSummary => {
+                self.session_summary = None;
+                self.view = AppView::Library;
+                self.refresh_library().ok();
+                effects.push(cover);
+            }
+            Action::ToggleTheme => self.toggle_theme(),
+            Action::AdjustMargin(delta) => self.adjust_margin(delta),
+            Action::AdjustSpacing(delta) => self.adjust_spacing(delta),
+            Action::AdjustParagraphSpacing(delta) => self.adjust_paragraph_spacing(delta),
+            Action::AdjustFirstLineIndent(delta) => self.adjust_first_line_indent(delta),
+            Action::OpenBookSearch => self.open_book_search(),
+            Action::OpenFind => {
+                self.view = AppView::Search;
+                self.search_query.clear();
+            }
+            Action::ToggleAutoScroll => {
+                self.auto_scroll_active = !self.auto_scroll_active;
+                self.auto_scroll_last_tick = std::time::Instant::now();
+            }
+
+            Action::ToggleVisual => {
+                if self.view == AppView::Visual {
+                    self.exit_visual_mode();
+                } else {
+                    self.enter_visual_mode();
+                }
+            }
+            Action::WriteNote => {
+                self.annotation_note.clear();
+                self.view = AppView::Annotation;
+            }
+            Action::Highlight(kind) => {
+                let _ = self.add_quick_highlight(kind.as_str());
+            }
+            Action::HighlightCustom(index) => {
+                let _ = self.add_custom_highlight(index);
+            }
+            Action::HighlightDefault => {
+                let kind = self.default_annotation_kind();
+                let _ = self.add_quick_highlight(kind.as_str());
+            }
+            Action::LookUpWord => {
+                if let Some(word) = self.word_under_cursor() {
+                    self.dictionary_query = word.clone();
+                    self.dictionary_source = self.vocab_source_at_cursor();
+                    self.view = AppView::Dictionary;
+                    self.dictionary_result = "Loading...".into();
+                    let settings = self.lookup_settings();
+                    self.dictionary_settings = Some(settings.clone());
+                    effects.push(Effect::LookUp(word, settings));
+                }
+            }
+            Action::LookUpTopic => {
+                if let Some(topic) = self.topic_under_cursor() {
+                    self.dictionary_query = topic.clone();
+                    self.dictionary_source = None;
+                    self.view = AppView::Dictionary;
+                    self.dictionary_result = "Loading...".into();
+                    let settings = LookupSettings {
+                        source: DictionarySource::Wikipedia,
+                        ..self.lookup_settings()
+                    };
+                    self.dictionary_settings = Some(settings.clone());
+                    effects.push(Effect::LookUp(topic, settings));
+                }
+            }
+            Action::CursorDown => self.move_cursor_down(viewport.reader_height),
+            Action::CursorUp => self.move_cursor_up(viewport.reader_height),
+            Action::WordNext => self.cursor_right(viewport.reader_height),
+            Action::WordPrev => self.cursor_left(viewport.reader_height),
+            Action::ClickWord { line, word } => self.move_cursor_to(line, word),
+
+            Action::SaveNote => {
+                let _ = self.add_annotation_with_note();
+            }
+            Action::SummarizeHighlights => {
+                if let Some(text) = self.begin_highlights_summary() {
+                    effects.push(Effect::Summarize(text));
+                }
+            }
+            Action::FilterAnnotations(filter) if self.view == AppView::NoteBrowser => {
+                self.set_note_browser_kind(filter)
+            }
+            Action::FilterAnnotations(filter) => self.set_annotation_filter(filter),
+            Action::FindNext => {
+                self.view = AppView::Reader;
+                let _ = self.search_next(true, viewport.reader_height);
+            }
+            Action::SearchNext => {
+                let _ = self.search_next(true, viewport.reader_height);
+            }
+            Action::SearchPrev => {
+                let _ = self.search_next(false, viewport.reader_height);
+            }
+            Action::StartRsvp => self.start_rsvp(),
+            Action::ToggleRsvp => self.toggle_rsvp(),
+            Action::AdjustRsvpWpm(delta) => {
+                let wpm = self.rsvp_wpm as i64 + delta;
+                if wpm >= 50 {
+                    self.rsvp_wpm = wpm as u64;
+                }
+            }
+        }
+        effects
+    }
+
+    fn back(&mut self) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        match self.view {
+            AppView::Help => {
+                self.view = self.previous_view.take().unwrap_or(AppView::Library);
+                if self.view == AppView::Library {
+                    effects.push(Effect::ShowCover { debounced: false });
+                }
+            }
+            AppView::Library => {}
+            AppView::FileExplorer if self.is_scanning => {}
+            AppView::Stats => {
+                self.view = self.stats_return_view;
+                if self.view == AppView::Library {
+                    effects.push(Effect::ShowCover { debounced: false });
+                }
+            }
+            AppView::VocabReview => {
+                self.view = self.vocab_review_return_view;
+                if self.view == AppView::Vocabulary {
+                    self.load_vocabulary().ok();
+                } else if self.view == AppView::Library {
+                    effects.push(Effect::ShowCover { debounced: false });
+                }
+            }
+            AppView::PathInput
+            | AppView::UrlInput
+            | AppView::LookupSettings
+            | AppView::FileExplorer
+            | AppView::ConceptIndex
+            | AppView::NoteBrowser
+            | AppView::ClippingReview => {
+                self.view = AppView::Library;
+                effects.push(Effect::ShowCover { debounced: false });
+            }
+            AppView::GlobalSearch => {
+                self.remember_selection();
+                self.view = AppView::Library;
+                effects.push(Effect::ShowCover { debounced: false });
+            }
+            AppView::SavedSearches => self.open_global_search(),
+            AppView::Omnibox => {
+                self.view = self.omnibox_return_view;
+                if self.view == AppView::Library {
+                    effects.push(Effect::ShowCover { debounced: false });
+                }
+            }
+            AppView::Reader => {
+                self.save_progress().ok();
+                // The library comes once the summary of the session has been seen.
+                self.session_summary = self.leave_book();
+                if self.session_summary.is_none() {
+                    self.view = AppView::Library;
+                    self.refresh_library().ok();
+                    effects.push(Effect::ShowCover { debounced: false });
+                }
+            }
+            AppView::Visual => self.exit_visual_mode(),
+            AppView::Select | AppView::Search | AppView::BookSearch => self.view = AppView::Reader,
+            AppView::Toc
+            | AppView::Figures
+            | AppView::LastStops
+            | AppView::AnnotationList
+            | AppView::Vocabulary => {
+                self.remember_selection();
+                self.view = AppView::Reader;
+            }
+            AppView::Annotation => {
+                if self.llm_draft.is_some() {
+                    self.cancel_llm_draft();
+                } else {
+                    self.view = AppView::Select;
+                }
+            }
+            AppView::Dictionary => self.view = AppView::Select,
+            AppView::Rsvp => self.stop_rsvp(),
+        }
+        effects
+    }
+
+    /// Clears the count and `g` typed before this key, returning how many times to carry
+    /// out its action: the count for a motion, once for anything else. Typing a count or
+    /// the first `g` keeps them for the next key.
+    pub fn take_key_prefix(&mut self, action: Option<Action>) -> usize {
+        match action {
+            Some(Action::CountDigit(_)) => {
+                self.pending_g = false;
+                return 1;
+            }
+            Some(Action::PendingG) => return 1,
+            _ => {}
+        }
+        self.pending_g = false;
+        let count = self.key_count.take();
+        match action {
+            Some(action) if action.repeats() => count.unwrap_or(1),
+            _ => 1,
+        }
+    }
+
+    fn confirm_prompt(&mut self) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if self.pending_book_removal.is_some() {
+            self.confirm_book_removal().ok();
+            effects.push(Effect::ShowCover { debounced: false });
+        } else if !self.pending_relinks.is_empty() {
+            self.confirm_relinks().ok();
+            effects.push(Effect::ShowCover { debounced: false });
+        } else if self.pending_large_import.is_some() {
+            self.confirm_large_import().ok();
+            return self.finish_import();
+        } else if self.tag_input.is_some() {
+            self.save_tag_edit().ok();
+        } else if let Some(token) = self.group_input.take() {
+            self.join_group_position(&token).ok();
+        } else if self.attach_input.is_some() {
+            self.confirm_attach().ok();
+        } else if self.goto_input.is_some() {
+            self.confirm_goto().ok();
+        } else if let Some(name) = self.saved_search_name.take() {
+            self.save_current_search(&name).ok();
+        }
+        effects
+    }
+
+    fn cancel_prompt(&mut self) {
+        if self.pending_book_removal.is_some() {
+            self.pending_book_removal = None;
+        } else if !self.pending_relinks.is_empty() {
+            self.pending_relinks.clear();
+        } else if self.pending_large_import.is_some() {
+            self.pending_large_import = None;
+        } else if self.tag_input.is_some() {
+            self.tag_input = None;
+        } else if self.attach_input.is_some() {
+            self.attach_input = None;
+        } else if self.export_picker {
+            self.export_picker = false;
+        } else if self.preset_picker {
+            self.preset_picker = false;
+        } else if self.sync_conflict.is_some() {
+            self.postpone_sync_conflict();
+        } else if self.group_input.is_some() {
+            self.group_input = None;
+        } else if self.goto_input.is_some() {
+            self.goto_input = None;
+        } else {
+            self.saved_search_name = None;
+        }
+    }
+
+    /// Back to the library after an import, with the new books queued for indexing.
+    fn finish_import(&mut self) -> Vec<Effect> {
+        self.refresh_library().ok();
+        self.view = AppView::Library;
+        vec![
+            Effect::IndexNewBooks,
+            Effect::ShowCover { debounced: false },
+        ]
+    }
+
+    fn move_selection(&mut self, forward: bool) -> Vec<Effect> {
+        let step = if forward { wrap_next } else { wrap_prev };
+        match self.view {
+            AppView::Library if !self.books.is_empty() => {
+                step(&mut self.selected_book_index, self.books.len());
+                return vec![Effect::ShowCover { debounced: true }];
+            }
+            AppView::PathInput => self.select_recent_path(forward),
+            AppView::FileExplorer => self.move_explorer_cursor(forward),
+            AppView::BookSearch => step(
+                &mut self.selected_book_search_index,
+                self.book_search_results.len(),
+            ),
+            AppView::Omnibox => step(&mut self.selected_omnibox_index, self.omnibox_results.len()),
+            AppView::GlobalSearch if !self.global_search_results.is_empty() => {
+                step(
+                    &mut self.selected_search_index,
+                    self.global_search_results.len(),
+                );
+                return vec![Effect::ShowCover { debounced: true }];
+            }
+            AppView::SavedSearches => step(
+                &mut self.selected_saved_search_index,
+                self.saved_searches.len(),
+            ),
+            AppView::ConceptIndex if self.concept_focus_notes => {
+                let len = self.selected_concept_notes().len();
+                step(&mut self.selected_concept_note_index, len);
+            }
+            AppView::ConceptIndex if !self.concept_index.is_empty() => {
+                step(&mut self.selected_concept_index, self.concept_index.len());
+                self.selected_concept_note_index = 0;
+            }
+            AppView::Stats => {
+                let mut tab = StatsTab::ALL
+                    .iter()
+                    .position(|t| *t == self.stats_tab)
+                    .unwrap_or(0);
+                step(&mut tab, StatsTab::ALL.len());
+                self.set_stats_tab(StatsTab::ALL[tab]);
+            }
+            AppView::Toc => step(&mut self.selected_toc_index, self.toc_items.len()),
+            AppView::Figures => step(&mut self.selected_figure_index, self.figure_items.len()),
+            AppView::LastStops => step(&mut self.selected_last_stop_index, self.last_stops.len()),
+            AppView::AnnotationList => step(
+                &mut self.selected_annotation_index,
+                self.current_annotations.len(),
+            ),
+            AppView::Vocabulary => step(&mut self.selected_vocab_index, self.vocabulary.len()),
+            AppView::NoteBrowser => {
+                let len = self.browsed_notes().len();
+                step(&mut self.note_browser.selected, len);
+            }
+            AppView::ClippingReview => self.move_clipping_selection(forward),
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    fn select_row(&mut self, index: usize) -> Vec<Effect> {
+        if self.selected_row().is_none() {
+            return Vec::new();
+        }
+        match self.view {
+            AppView::Library if index < self.books.len() => {
+                self.selected_book_index = index;
+                return vec![Effect::ShowCover { debounced: true }];
+            }
+            AppView::Toc if index < self.toc_items.len() => self.selected_toc_index = index,
+            AppView::AnnotationList if index < self.current_annotations.len() => {
+                self.selected_annotation_index = index
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    fn open_selected(&mut self) {
+        match self.view {
+            AppView::Library => {
+                let _ = self.open_selected_book();
+            }
+            AppView::GlobalSearch => {
+                let Some(hit) = self
+                    .global_search_results
+                    .get(self.selected_search_index)
+                    .cloned()
+                else {
+                    return;
+                };
+                self.stop_global_search();
+                self.remember_selection();
+                if self.select_book_by_id(hit.book_id).unwrap_or(false) {
+                    let _ = self.open_selected_book();
+                    let _ = self.go_to_locator(&hit.locator);
+                }
+            }
+            AppView::SavedSearches => {
+                self.run_selected_saved_search().ok();
+            }
+            AppView::BookSearch => {
+                self.open_book_search_hit().ok();
+            }
+            AppView::Omnibox => {
+                self.open_omnibox_hit().ok();
+            }
+            AppView::ConceptIndex if self.concept_focus_notes => {
+                self.open_selected_concept_note().ok();
+            }
+            AppView::ConceptIndex => self.concept_focus_notes = true,
+            AppView::Toc => {
+                let _ = self.jump_to_toc();
+            }
+            AppView::Figures => {
+                let _ = self.jump_to_figure();
+            }
+            AppView::LastStops => {
+                let _ = self.jump_to_last_stop();
+            }
+            AppView::AnnotationList => {
+                let _ = self.jump_to_annotation();
+            }
+            AppView::NoteBrowser => {
+                self.open_browsed_note().ok();
+            }
+            _ => {}
+        }
+    }
+
+    /// Types `c` into the current view's text field, or deletes a character for `None`.
+    fn input_char(&mut self, c: Option<char>) {
+        let field = match self.view {
+            AppView::PathInput => {
+                self.selected_recent_path = None;
+                self.reset_path_completion();
+                &mut self.explorer_path
+            }
+            AppView::UrlInput => &mut self.url_input,
+            AppView::LookupSettings if self.lookup_editing_dictionary => {
+                &mut self.lookup_dictionary_input
+            }
+            AppView::LookupSettings => &mut self.lookup_language_input,
+            AppView::GlobalSearch => match self.saved_search_name {
+                Some(ref mut name) => name,
+                None => {
+                    // Editing the query invalidates the previous results.
+                    self.stop_global_search();
+                    self.global_search_results.clear();
+                    &mut self.global_search_query
+                }
+            },
+            AppView::Annotation => &mut self.annotation_note,
+            AppView::Library => match self.tag_input {
+                Some(ref mut tags) => tags,
+                None => return,
+            },
+            AppView::AnnotationList | AppView::NoteBrowser => {
+                match (&mut self.tag_input, &mut self.attach_input) {
+                    (Some(tags), _) => tags,
+                    (None, Some(path)) => path,
+                    (None, None) => return,
+                }
+            }
+            AppView::Search => &mut self.search_query,
+            AppView::Reader => match (self.goto_input.as_mut(), self.group_input.as_mut()) {
+                (Some(input), _) | (None, Some(input)) => input,
+                (None, None) => return,
+            },
+            AppView::BookSearch => {
+                self.book_search_results.clear();
+                &mut self.book_search_query
+            }
+            AppView::Omnibox => {
+                self.omnibox_results.clear();
+                &mut self.omnibox_query
+            }
+            _ => return,
+        };
+        match c {
+            Some(c) => field.push(c),
+            None => {
+                field.pop();
+            }
+        }
+    }
+
+    /// The word under the select-mode cursor, letters only.
+    fn word_under_cursor(&self) -> Option<String> {
+        let book = self.current_book.as_ref()?;
+        let Some(RenderLine::Text(line)) = book.chapter_content.get(book.current_line) else {
+            return None;
+        };
+        let word = line.split_whitespace().nth(book.word_index)?;
+        let clean: String = word.chars().filter(|c| c.is_alphabetic()).collect();
+        (!clean.is_empty()).then_some(clean)
+    }
+
+    /// The selected phrase without surrounding punctuation, or the word under the cursor
+    /// when nothing is selected.
+    fn topic_under_cursor(&self) -> Option<String> {
+        if self.get_selection_range().is_none() {
+            return self.word_under_cursor();
+        }
+        let text = self.get_selected_text();
+        let topic = text.trim_matches(|c: char| !c.is_alphanumeric());
+        (!topic.is_empty()).then(|| topic.to_string())
+    }
+}
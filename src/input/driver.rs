@@ -0,0 +1,136 @@
+//! Feeds synthetic key presses through `handle_key`, the same path `run_app` uses, and
+//! carries out the resulting effects synchronously so whole flows can be tested.
+
+use super::{Effect, Viewport, handle_key, handle_mouse};
+use crate::app::App;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use tbook::library;
+
+pub struct Driver {
+    pub app: App,
+    pub viewport: Viewport,
+    /// Network and model requests, which the driver records instead of running.
+    pub requests: Vec<Effect>,
+    /// The last text copied to the clipboard.
+    pub clipboard: Option<String>,
+    pub quit: bool,
+}
+
+impl Driver {
+    pub fn new(app: App) -> Self {
+        Self {
+            app,
+            viewport: Viewport { reader_height: 20 },
+            requests: Vec::new(),
+            clipboard: None,
+            quit: false,
+        }
+    }
+
+    pub fn press(&mut self, key: KeyEvent) {
+        let effects = handle_key(&mut self.app, key, self.viewport);
+        self.run(effects);
+    }
+
+    /// Sends a mouse event at a screen cell. Clicks on hints and rows need a frame drawn
+    /// first, as on screen.
+    pub fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) {
+        let event = MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let effects = handle_mouse(&mut self.app, event, self.viewport);
+        self.run(effects);
+    }
+
+    fn run(&mut self, effects: Vec<Effect>) {
+        for effect in effects {
+            match effect {
+                Effect::Quit => self.quit = true,
+                // Covers are decoded on a worker in the real loop; nothing to draw here.
+                Effect::ShowCover { .. } => {}
+                Effect::Scan(path) => {
+                    let result = library::scan_for_books(&path, &self.app.import_rules);
+                    self.app.apply_scan_result(result);
+                }
+                Effect::IndexNewBooks => {
+                    for (book_id, path) in self.app.db.get_unindexed_books().unwrap_or_default() {
+                        self.app.queue_indexing(book_id);
+                        library::index_book(&self.app.db, book_id, &path).ok();
+                        self.app.finish_indexing(book_id);
+                    }
+                }
+                Effect::CopyToClipboard(text) => self.clipboard = Some(text),
+                Effect::FetchArticle(_) | Effect::Summarize(_) | Effect::LookUp(..) => {
+                    self.requests.push(effect)
+                }
+            }
+        }
+        // The real loop hands these to background workers.
+        self.app.flush_writes();
+        if let Some(request) = self.app.take_global_search() {
+            let mut updates = Vec::new();
+            request.run(&self.app.db, |update| updates.push(update));
+            for update in updates {
+                self.app.apply_global_search_update(update);
+            }
+        }
+    }
+
+    /// Presses each key of a script; see `parse_keys`.
+    pub fn keys(&mut self, script: &str) {
+        for key in parse_keys(script) {
+            self.press(key);
+        }
+    }
+
+    /// Types text literally, spaces included.
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.press(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+}
+
+/// Parses a whitespace-separated key script. Plain tokens press each character in turn;
+/// `<Enter>`, `<Esc>`, `<Tab>`, `<BS>`, `<Space>`, `<Up>`, `<Down>`, `<Left>`, `<Right>`,
+/// `<PgUp>`, and `<PgDn>` name special keys, and `<C-x>` holds Control.
+pub fn parse_keys(script: &str) -> Vec<KeyEvent> {
+    let mut keys = Vec::new();
+    for token in script.split_whitespace() {
+        let Some(name) = token.strip_prefix('<').and_then(|t| t.strip_suffix('>')) else {
+            keys.extend(
+                token
+                    .chars()
+                    .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)),
+            );
+            continue;
+        };
+        let key = match name {
+            "Enter" => KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            "Esc" => KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            "Tab" => KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE),
+            "BS" => KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+            "Space" => KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            "Up" => KeyEvent::new(KeyCode::Up, KeyModifiers::NONE),
+            "Down" => KeyEvent::new(KeyCode::Down, KeyModifiers::NONE),
+            "Left" => KeyEvent::new(KeyCode::Left, KeyModifiers::NONE),
+            "Right" => KeyEvent::new(KeyCode::Right, KeyModifiers::NONE),
+            "PgUp" => KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE),
+            "PgDn" => KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE),
+            _ => match name
+                .strip_prefix("C-")
+                .map(|c| c.chars().collect::<Vec<_>>())
+            {
+                Some(c) if c.len() == 1 => {
+                    KeyEvent::new(KeyCode::Char(c[0]), KeyModifiers::CONTROL)
+                }
+                _ => panic!("unknown key <{}>", name),
+            },
+        };
+        keys.push(key);
+    }
+    keys
+}
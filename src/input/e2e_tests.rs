@@ -0,0 +1,2880 @@
+//! End-to-end flows driven by key scripts, from an empty library to exported notes.
+
+use super::Effect;
+use super::driver::{Driver, parse_keys};
+use crate::app::{
+    AnnotationFilter, App, AppView, BookPace, CHAPTER_LINE_CAP, ClickTarget, CoverResponse,
+    LineNumbers, MAX_LINE_CHARS, ProgressGauge, READ_ONLY_MESSAGE, ReadingPreset, RenderLine,
+    SearchMatch, Sitting, StatsBar, StatsBreakdown, StatsMetric, StatsTab, SyncState, find_matches,
+    reading_streak, split_long_line, stats_bars,
+};
+use crate::common::{Chapter, Workspace, epub, figures_epub, sample_epub};
+use crate::dictionary::DictionarySource;
+use crate::kosync;
+use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEventKind};
+use ratatui::{Terminal, backend::TestBackend, style::Color};
+use std::time::{Duration, Instant};
+use tbook::config::{
+    AnnotationKindConfig, AppConfig, LayoutProfile, TextFilterConfig, TopBarConfig,
+};
+use tbook::db::{BookPaceRecord, NewAnnotation, VocabSource};
+use tbook::export::ExportFormat;
+use tbook::instance::InstanceLock;
+use tbook::library::ReadingStatus;
+use tbook::search::SearchPattern;
+
+fn driver(ws: &Workspace) -> Driver {
+    let mut app = App::new(&ws.path().join("tbook.db").to_string_lossy()).unwrap();
+    app.export_dir = ws.path().to_path_buf();
+    Driver::new(app)
+}
+
+/// Opens the path prompt and scans `dir`, replacing the suggested home directory.
+fn scan(d: &mut Driver, dir: &str) {
+    d.keys("n");
+    assert!(d.app.view == AppView::PathInput);
+    for _ in 0..d.app.explorer_path.chars().count() {
+        d.keys("<BS>");
+    }
+    d.type_text(dir);
+    d.keys("<Enter>");
+}
+
+#[test]
+fn import_open_highlight_export() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    assert!(d.app.books.is_empty());
+
+    scan(&mut d, &ws.path().to_string_lossy());
+    assert!(d.app.view == AppView::FileExplorer);
+    assert_eq!(d.app.explorer_results.len(), 1);
+
+    d.keys("i");
+    assert!(d.app.view == AppView::Library);
+    assert_eq!(d.app.books.len(), 1);
+    assert!(d.app.db.get_unindexed_books().unwrap().is_empty());
+
+    // Open, move to the second chapter, and highlight the word under the cursor.
+    d.keys("<Enter> l s");
+    assert!(d.app.view == AppView::Select);
+    d.keys("jj h");
+    let book_id = d.app.books[0].id;
+    let annotations = d.app.db.get_annotations(book_id).unwrap();
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].chapter, 1);
+    assert_eq!(annotations[0].kind, "highlight");
+
+    d.keys("<Esc> E");
+    assert!(d.app.export_picker);
+    d.keys("m");
+    assert!(!d.app.export_picker);
+    let notes = std::fs::read_to_string(ws.path().join("notes_sample_book.md")).unwrap();
+    assert!(notes.contains("# Reading Notes: Sample Book"), "{notes}");
+    assert!(
+        notes.contains(&format!("> {}", annotations[0].content)),
+        "{notes}"
+    );
+
+    d.keys("E j");
+    let json = std::fs::read_to_string(ws.path().join("notes_sample_book.json")).unwrap();
+    let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(records[0]["text"], annotations[0].content.as_str());
+    assert_eq!(records[0]["chapter"], 2);
+    assert!(
+        d.app
+            .status_message
+            .as_deref()
+            .is_some_and(|m| m.ends_with("notes_sample_book.json")),
+        "{:?}",
+        d.app.status_message
+    );
+
+    // Esc only closes the picker.
+    d.keys("E <Esc>");
+    assert!(!d.app.export_picker);
+    assert!(d.app.view == AppView::Reader);
+
+    // Copies carry the kind's Markdown marker.
+    d.app
+        .markdown_markers
+        .0
+        .insert("highlight".to_string(), "=={text}==".to_string());
+    d.keys("A y");
+    assert!(d.app.view == AppView::AnnotationList);
+    assert_eq!(d.clipboard, Some(format!("=={}==", annotations[0].content)));
+    d.keys("q");
+
+    d.keys("q <Esc>");
+    assert!(d.app.view == AppView::Library);
+    assert!(!d.quit);
+    d.keys("q");
+    assert!(d.quit);
+}
+
+#[test]
+fn the_list_of_figures_jumps_to_images_and_tables() {
+    let ws = Workspace::new();
+    figures_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> F");
+    assert!(d.app.view == AppView::Figures);
+    let labels: Vec<&str> = d
+        .app
+        .figure_items
+        .iter()
+        .map(|f| f.label.as_str())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "Fig. 2.1: The head of a sperm whale",
+            "Fig. 2.2: Flukes raised before a dive",
+            "Table 2.1: Whale sizes",
+        ]
+    );
+
+    // A table's caption is above it, so the reader starts on the caption itself.
+    d.keys("jj <Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.current_chapter, 1);
+    assert!(matches!(
+        &book.chapter_content[book.viewport_top],
+        RenderLine::Caption(c) if c == "Table 2.1: Whale sizes"
+    ));
+
+    // Reopening remembers the selection; Esc goes back without moving.
+    d.keys("F");
+    assert_eq!(d.app.selected_figure_index, 2);
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Reader);
+}
+
+#[test]
+fn new_notes_rewrite_the_vault_note() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    let vault = ws.path().join("vault");
+    d.app.export_vault = Some(vault.clone());
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> s jj h");
+    let note = std::fs::read_to_string(vault.join("Sample Book.md")).unwrap();
+    let ids: Vec<i32> = d
+        .app
+        .db
+        .get_annotations(d.app.books[0].id)
+        .unwrap()
+        .iter()
+        .map(|a| a.id)
+        .collect();
+    assert_eq!(ids.len(), 1);
+    assert!(note.contains(&format!("^tbook-{}", ids[0])), "{note}");
+
+    // A second note rewrites the file and keeps the first block ID.
+    d.keys("w q");
+    let note = std::fs::read_to_string(vault.join("Sample Book.md")).unwrap();
+    let annotations = d.app.db.get_annotations(d.app.books[0].id).unwrap();
+    assert_eq!(annotations.len(), 2);
+    for a in &annotations {
+        assert!(note.contains(&format!("\n^tbook-{}\n", a.id)), "{note}");
+    }
+}
+
+#[test]
+fn unmatched_kindle_clippings_are_assigned_from_the_library() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    d.keys("K");
+    assert!(d.app.view == AppView::Library);
+    assert!(d.app.status_message.is_some());
+
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let clippings = "Loomings Notebook (Ishmael)\n- Your Highlight on Location 1-2 | Added on Monday\n\nCall me Ishmael.\n==========\nLoomings Notebook (Ishmael)\n- Your Highlight on Location 9 | Added on Monday\n\nnot in the text\n==========\n";
+    let report = tbook::clippings::import_clippings(&d.app.db, clippings).unwrap();
+    assert_eq!(report.pending.len(), 1);
+
+    d.keys("K");
+    assert!(d.app.view == AppView::ClippingReview);
+    assert_eq!(d.app.clipping_review.clippings.len(), 2);
+    assert_eq!(d.app.clipping_review.selected_group().len(), 2);
+
+    // Discarding one leaves the other; assigning it empties the list.
+    d.keys("j d");
+    assert_eq!(d.app.clipping_review.clippings.len(), 1);
+    d.keys("l <Enter>");
+    assert!(d.app.view == AppView::Library);
+    let annotations = d.app.db.get_annotations(d.app.books[0].id).unwrap();
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].content, "Call me Ishmael.");
+    assert_eq!(annotations[0].chapter, 0);
+}
+
+#[test]
+fn background_indexing_reports_progress_until_idle() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    assert!(d.app.books[0].total_words.is_some_and(|w| w > 0));
+    assert_eq!(d.app.indexing_progress(), None);
+
+    assert!(d.app.queue_indexing(1));
+    assert!(d.app.queue_indexing(2));
+    assert!(!d.app.queue_indexing(2));
+    assert_eq!(d.app.indexing_progress(), Some((0, 2)));
+    d.app.finish_indexing(1);
+    assert_eq!(d.app.indexing_progress(), Some((1, 2)));
+    d.app.finish_indexing(2);
+    assert_eq!(d.app.indexing_progress(), None);
+    assert!(d.app.queue_indexing(1));
+    assert_eq!(d.app.indexing_progress(), Some((0, 1)));
+}
+
+#[test]
+fn opening_a_changed_book_warns_and_moves_its_notes() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let book_id = d.app.books[0].id;
+    d.app
+        .db
+        .add_annotation(&NewAnnotation {
+            book_id,
+            content: "Call me Ishmael.",
+            kind: "highlight",
+            start_locator: Some("0!:10"),
+            end_locator: Some("0!:18"),
+            ..Default::default()
+        })
+        .unwrap();
+
+    epub(
+        ws.path(),
+        "sample.epub",
+        "Sample Book",
+        "Test Author",
+        &[Chapter {
+            title: "Loomings",
+            paragraphs: &["A foreword.", "Call me Ishmael."],
+        }],
+    );
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let status = d.app.status_message.clone().unwrap_or_default();
+    assert!(
+        status.contains("changed on disk: 1 notes moved"),
+        "{}",
+        status
+    );
+    let annotations = &d.app.current_book.as_ref().unwrap().chapter_annotations;
+    assert_ne!(
+        annotations[0].start_locator.as_ref().map(|l| l.offset),
+        Some(10)
+    );
+}
+
+#[test]
+fn differing_positions_from_another_device_ask_which_to_keep() {
+    let ws = Workspace::new();
+    epub(
+        ws.path(),
+        "three.epub",
+        "Three Chapters",
+        "Test Author",
+        &[
+            Chapter {
+                title: "One",
+                paragraphs: &["The first chapter begins here."],
+            },
+            Chapter {
+                title: "Two",
+                paragraphs: &["The second chapter goes on."],
+            },
+            Chapter {
+                title: "Three",
+                paragraphs: &["The third chapter ends it all."],
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    d.app.apply_config(&AppConfig {
+        kosync_server: Some("http://127.0.0.1:1".to_string()),
+        kosync_username: Some("reader".to_string()),
+        kosync_password: Some("secret".to_string()),
+        ..AppConfig::default()
+    });
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let document = d.app.sync_pull.take().expect("opening asks the server");
+    // Nothing is pushed until the server's position is known.
+    d.keys("j");
+    assert!(d.app.take_sync_push().is_none());
+
+    let remote = |device_id: &str, percentage| {
+        Ok(Some(kosync::Position {
+            document: document.clone(),
+            progress: "/body/DocFragment[3]/body/p[1]/text().0".to_string(),
+            percentage,
+            device: "Kobo".to_string(),
+            device_id: device_id.to_string(),
+        }))
+    };
+    // Our own last push is not news.
+    let own = d.app.kosync.device_id.clone();
+    d.app.apply_remote_position(&document, remote(&own, 0.9));
+    assert!(d.app.sync_conflict.is_none());
+    assert_eq!(d.app.sync_state, Some(SyncState::Synced));
+
+    // Another device further on: keep its position and move there.
+    let chapters = d
+        .app
+        .current_book
+        .as_mut()
+        .unwrap()
+        .parser
+        .get_chapter_count();
+    d.app.apply_remote_position(&document, remote("KOBO", 0.9));
+    let prompt = d.app.sync_conflict_prompt().unwrap_or_default();
+    assert!(prompt.starts_with("Local: ch. 1 "), "{}", prompt);
+    assert!(
+        prompt.contains(&format!("/ Kobo: ch. {} (90%)", chapters)),
+        "{}",
+        prompt
+    );
+    // Reading keys wait until a side is picked.
+    d.keys("j");
+    assert!(d.app.sync_conflict.is_some());
+    d.keys("r");
+    assert!(d.app.sync_conflict.is_none());
+    let chapter = d.app.current_book.as_ref().unwrap().current_chapter;
+    assert_eq!(chapter + 1, chapters);
+    let pushed = d.app.take_sync_push().expect("the jump is pushed");
+    assert_eq!(pushed.document, document);
+    assert_eq!(pushed.device, "tbook");
+    assert!(pushed.progress.starts_with("/body/DocFragment["));
+    assert!(d.app.take_sync_push().is_none());
+    d.app.apply_sync_push(Ok(()));
+    assert_eq!(d.app.sync_state, Some(SyncState::Synced));
+
+    // Another device behind: keep ours, which is sent straight away.
+    d.app.apply_remote_position(&document, remote("KOBO", 0.0));
+    assert!(d.app.sync_conflict.is_some());
+    assert!(d.app.take_sync_push().is_none());
+    d.keys("l");
+    assert!(d.app.sync_push_now);
+    let pushed = d.app.take_sync_push().expect("our position is sent");
+    assert!(pushed.percentage > 0.5);
+
+    // Putting the choice off stops syncing the book for now.
+    d.app.apply_remote_position(&document, remote("KOBO", 0.0));
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Reader);
+    assert_eq!(d.app.sync_state, Some(SyncState::Paused));
+    d.keys("k");
+    assert!(d.app.take_sync_push().is_none());
+}
+
+#[test]
+fn looked_up_words_keep_the_sentence_they_came_from() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> l s");
+    // "Quitting the good city of old Manhatto, I duly arrived in New Bedford."
+    let line = {
+        let book = d.app.current_book.as_ref().unwrap();
+        book.chapter_content
+            .iter()
+            .position(|l| matches!(l, RenderLine::Text(t) if t.starts_with("Quitting")))
+            .unwrap()
+    };
+    if let Some(ref mut book) = d.app.current_book {
+        book.current_line = line;
+        book.word_index = 6;
+    }
+    d.keys("d");
+    assert!(matches!(d.requests.as_slice(), [Effect::LookUp(word, _)] if word == "Manhatto"));
+    d.app
+        .apply_lookup_result("Old name of Manhattan.".to_string());
+
+    let vocab = d.app.db.get_vocabulary().unwrap();
+    let source = vocab[0].source.as_ref().unwrap();
+    assert_eq!(
+        source.sentence,
+        "Quitting the good city of old Manhatto, I duly arrived in New Bedford."
+    );
+    assert_eq!(source.chapter, 1);
+    assert_eq!(source.book_id, d.app.books[0].id);
+    assert_eq!(vocab[0].book_title.as_deref(), Some("Sample Book"));
+
+    // A lookup from outside a book keeps the sentence from before.
+    d.app.dictionary_source = None;
+    d.app
+        .apply_lookup_result("Old name of Manhattan.".to_string());
+    let vocab = d.app.db.get_vocabulary().unwrap();
+    assert_eq!(vocab[0].lookup_count, 2);
+    assert!(vocab[0].source.is_some());
+}
+
+#[test]
+fn lookups_use_the_book_language_unless_overridden() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    d.app.default_dictionary_language = Some("de".to_string());
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    // The book says it is in English, which beats the configured default.
+    assert_eq!(d.app.lookup_settings().language, "en");
+    assert_eq!(
+        d.app.lookup_settings().source,
+        DictionarySource::FreeDictionary
+    );
+
+    d.keys("q L");
+    assert!(d.app.view == AppView::LookupSettings);
+    d.type_text("fr");
+    d.keys("<Enter>");
+    d.keys("<Enter>");
+    let settings = d.app.lookup_settings();
+    assert_eq!(settings.language, "fr");
+    assert_eq!(settings.source, DictionarySource::Wiktionary);
+}
+
+#[test]
+fn selected_names_are_looked_up_in_the_encyclopedia() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> l s");
+    let line = {
+        let book = d.app.current_book.as_ref().unwrap();
+        book.chapter_content
+            .iter()
+            .position(|l| matches!(l, RenderLine::Text(t) if t.starts_with("Quitting")))
+            .unwrap()
+    };
+    if let Some(ref mut book) = d.app.current_book {
+        book.current_line = line;
+        book.word_index = 11;
+    }
+    d.keys("v w W");
+    assert!(d.app.view == AppView::Dictionary);
+    assert!(matches!(
+        d.requests.as_slice(),
+        [Effect::LookUp(topic, settings)]
+            if topic == "New Bedford" && settings.source == DictionarySource::Wikipedia
+    ));
+
+    // Articles are shown but not collected as vocabulary.
+    d.app
+        .apply_lookup_result("# New Bedford\nCity in Massachusetts".to_string());
+    assert!(d.app.dictionary_result.contains("Massachusetts"));
+    assert!(d.app.db.get_vocabulary().unwrap().is_empty());
+}
+
+#[test]
+fn due_words_are_reviewed_as_flashcards() {
+    let ws = Workspace::new();
+    let mut d = driver(&ws);
+    d.app
+        .db
+        .add_to_vocabulary("ephemeral", "Lasting a very short time.", None)
+        .unwrap();
+    d.app
+        .db
+        .add_to_vocabulary("laconic", "Using very few words.", None)
+        .unwrap();
+    d.app.refresh_library().unwrap();
+    assert_eq!(d.app.vocab_due, 2);
+
+    d.keys("R");
+    assert!(d.app.view == AppView::VocabReview);
+    let first = d.app.vocab_review.cards[0].word.clone();
+    // Grades only count once the definition is showing.
+    d.keys("3");
+    assert_eq!(d.app.vocab_review.reviewed, 0);
+    d.keys("<Space> 3");
+    assert_eq!(d.app.vocab_review.cards.len(), 1);
+
+    // A forgotten word comes back at the end of the session.
+    d.keys("<Space> 1");
+    assert_eq!(d.app.vocab_review.cards.len(), 1);
+    assert_eq!(d.app.vocab_due, 1);
+    d.keys("<Space> 4");
+    assert!(d.app.vocab_review.cards.is_empty());
+    assert_eq!(d.app.vocab_review.reviewed, 3);
+    assert_eq!(d.app.vocab_due, 0);
+
+    let tomorrow = chrono::Local::now().date_naive() + chrono::Days::new(1);
+    let due = d.app.db.get_due_vocabulary(tomorrow).unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].word, first);
+    assert_eq!(due[0].schedule.repetitions, 1);
+
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Library);
+}
+
+#[test]
+fn pomodoro_breaks_review_words_then_questions() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.app
+        .db
+        .add_to_vocabulary("ephemeral", "Lasting a very short time.", None)
+        .unwrap();
+    let book_id = d.app.books[0].id;
+    for i in 0..6 {
+        d.app
+            .db
+            .add_annotation(&NewAnnotation {
+                book_id,
+                start_line: i,
+                end_line: i,
+                end_word: 1,
+                content: &format!("Question {}?", i),
+                note: Some("An answer."),
+                kind: "question",
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    d.app.break_review = true;
+    let end_phase = |d: &mut Driver| {
+        d.app.pomodoro.end_time = Some(std::time::Instant::now());
+        d.app.tick_timers();
+    };
+
+    // A break starting outside the reader leaves the view alone.
+    d.app.pomodoro_toggle();
+    end_phase(&mut d);
+    assert!(d.app.pomodoro.is_break && d.app.view == AppView::Library);
+    end_phase(&mut d);
+
+    d.keys("<Enter>");
+    end_phase(&mut d);
+    assert!(d.app.view == AppView::VocabReview);
+    let review = &d.app.vocab_review;
+    assert_eq!((review.cards.len(), review.questions.len()), (1, 4));
+    let first_question = review.questions[0].content.clone();
+
+    // The word is graded as usual, then the questions, which a `1` asks again.
+    d.keys("<Space> 3");
+    assert!(d.app.vocab_review.cards.is_empty());
+    d.keys("<Space> 1");
+    assert_eq!(d.app.vocab_review.questions.len(), 4);
+    assert_eq!(d.app.vocab_review.questions[3].content, first_question);
+    d.keys("<Space> 3");
+    assert_eq!(d.app.vocab_review.questions.len(), 3);
+    assert_eq!(d.app.vocab_review.reviewed, 3);
+
+    // The end of the break goes back to the book.
+    end_phase(&mut d);
+    assert!(d.app.view == AppView::Reader);
+    assert_eq!(d.app.status_message.as_deref(), Some("Break over"));
+
+    // The next break asks other questions.
+    end_phase(&mut d);
+    let review = &d.app.vocab_review;
+    assert_eq!((review.cards.len(), review.questions.len()), (0, 5));
+    assert_ne!(review.questions[0].content, first_question);
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Reader);
+}
+
+#[test]
+fn giant_chapters_load_in_parts_and_long_lines_are_split() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..CHAPTER_LINE_CAP)
+        .map(|i| format!("Paragraph {}.", i))
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "giant.epub",
+        "Giant",
+        "Test Author",
+        &[Chapter {
+            title: "Everything",
+            paragraphs: &paragraphs,
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let loaded = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.chapter_content.len(), book.pending_lines.len())
+    };
+    let (first, held_back) = loaded(&d);
+    assert_eq!(first, CHAPTER_LINE_CAP);
+    assert!(held_back > 0);
+
+    // Held-back lines arrive before the cursor reaches the end, and count for search.
+    while loaded(&d).1 > 0 {
+        d.app.move_cursor_down(40);
+    }
+    assert_eq!(loaded(&d).0, first + held_back);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert!(book.current_line + 1 < book.chapter_content.len());
+    assert!(book.status != ReadingStatus::Finished);
+
+    let run = "x".repeat(MAX_LINE_CHARS * 2 + 10);
+    let line = format!("Start {} end", run);
+    let pieces = split_long_line(&line);
+    assert_eq!(pieces.len(), 4);
+    assert_eq!(pieces[0], "Start");
+    assert!(pieces.iter().all(|p| p.chars().count() <= MAX_LINE_CHARS));
+    assert_eq!(pieces[3], format!("{} end", "x".repeat(10)));
+}
+
+#[test]
+fn covers_near_the_selection_load_ahead_as_configured() {
+    let ws = Workspace::new();
+    for name in ["a", "b", "c", "d", "e"] {
+        epub(
+            ws.path(),
+            &format!("{}.epub", name),
+            name,
+            "Test Author",
+            &[Chapter {
+                title: "Only",
+                paragraphs: &["Some text."],
+            }],
+        );
+    }
+    let mut d = driver(&ws);
+    assert!(d.app.cover_prefetch_requests().is_empty());
+    d.app.apply_config(&AppConfig {
+        cover_debounce_ms: Some(40),
+        cover_max_dimension: Some(200),
+        cover_prefetch: Some(2),
+        ..AppConfig::default()
+    });
+    assert_eq!(d.app.cover_debounce.as_millis(), 40);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i j j");
+    assert_eq!(d.app.selected_book_index, 2);
+
+    let ids: Vec<i32> = d.app.books.iter().map(|b| b.id).collect();
+    let selected = d.app.cover_request_for_selected().unwrap();
+    assert_eq!((selected.book_id, selected.max_dimension), (ids[2], 200));
+    // Nearest first, and the selected cover is left to its own request.
+    let ahead: Vec<i32> = d
+        .app
+        .cover_prefetch_requests()
+        .iter()
+        .map(|r| r.book_id)
+        .collect();
+    assert_eq!(ahead, vec![ids[3], ids[1], ids[4], ids[0]]);
+    assert!(d.app.cover_prefetch_requests().is_empty());
+
+    d.app.apply_cover_response(CoverResponse {
+        book_id: ids[3],
+        image: None,
+    });
+    d.app.pending_cover_requests.clear();
+    let ahead: Vec<i32> = d
+        .app
+        .cover_prefetch_requests()
+        .iter()
+        .map(|r| r.book_id)
+        .collect();
+    assert_eq!(ahead, vec![ids[1], ids[4], ids[0]]);
+}
+
+#[test]
+fn start_views_open_over_the_library() {
+    let ws = Workspace::new();
+    let mut d = driver(&ws);
+    d.app.open_start_view("stats").unwrap();
+    assert!(d.app.view == AppView::Stats);
+    d.keys("q");
+    assert!(d.app.view == AppView::Library);
+
+    d.app.open_start_view("vocab").unwrap();
+    assert!(d.app.view == AppView::VocabReview);
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Library);
+
+    assert!(d.app.open_start_view("settings").is_err());
+    assert!(d.app.view == AppView::Library);
+}
+
+#[test]
+fn line_numbers_count_chapter_lines() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    assert_eq!(d.app.line_number_width(), 0);
+    d.keys("#");
+    assert!(d.app.line_numbers == LineNumbers::Absolute);
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Line numbers: Chapter lines")
+    );
+    // Three digits and a space, even for a short chapter.
+    assert_eq!(d.app.line_number_width(), 4);
+    assert_eq!(d.app.line_numbers.number(4, 2), Some(5));
+    d.keys("#");
+    assert_eq!(d.app.line_numbers.number(4, 2), Some(2));
+    assert_eq!(d.app.line_numbers.number(0, 2), Some(2));
+    assert_eq!(d.app.line_numbers.number(2, 2), Some(3));
+    d.keys("#");
+    assert!(d.app.line_numbers == LineNumbers::Off);
+    assert_eq!(d.app.line_numbers.number(4, 2), None);
+}
+
+#[test]
+fn go_to_keys_move_by_chapter_percent_and_count() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..10).map(|i| format!("Line {} of five.", i)).collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    let chapters: Vec<Chapter> = ["One", "Two", "Three", "Four"]
+        .into_iter()
+        .map(|title| Chapter {
+            title,
+            paragraphs: &paragraphs,
+        })
+        .collect();
+    epub(ws.path(), "even.epub", "Even", "Test Author", &chapters);
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let at = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.current_chapter, book.current_line, book.viewport_top)
+    };
+
+    d.keys("3 j");
+    assert_eq!(at(&d), (0, 3, 3));
+    d.keys("G");
+    let last = d.app.current_book.as_ref().unwrap().chapter_content.len() - 1;
+    assert_eq!(at(&d).1, last);
+    d.keys("g");
+    assert!(d.app.pending_g);
+    d.keys("g");
+    assert_eq!(at(&d), (0, 0, 0));
+
+    // A count waits for its motion and goes if something else comes first.
+    d.keys("1 2");
+    assert_eq!(d.app.key_count, Some(12));
+    d.keys("<Esc>");
+    assert_eq!(d.app.key_count, None);
+    d.keys("2 l");
+    assert_eq!(at(&d).0, 2);
+
+    // Chapters are the same length, so 60% is a little way into the third.
+    d.keys("6 0 %");
+    let (chapter, line, _) = at(&d);
+    assert_eq!(chapter, 2);
+    assert!((1..last).contains(&line), "line {}", line);
+
+    d.keys(":");
+    d.type_text("4");
+    d.keys("<Enter>");
+    assert_eq!(at(&d), (3, 0, 0));
+    assert!(d.app.goto_input.is_none());
+    d.keys(":");
+    d.type_text("9");
+    d.keys("<Enter>");
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("This book has 4 chapters")
+    );
+    d.keys(":");
+    d.type_text("10%");
+    d.keys("<Enter>");
+    assert_eq!(at(&d).0, 0);
+    d.keys(": x <Esc>");
+    assert!(d.app.goto_input.is_none());
+    assert_eq!(at(&d).0, 0);
+}
+
+#[test]
+fn control_keys_scroll_by_wrapped_rows() {
+    let ws = Workspace::new();
+    // Every other paragraph wraps onto several rows.
+    let paragraphs: Vec<String> = (0..40)
+        .map(|i| match i % 2 {
+            0 => format!("Short line {}.", i),
+            _ => format!("Long line {} {}", i, "that keeps on going ".repeat(8)),
+        })
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "long.epub",
+        "Long",
+        "Test Author",
+        &[Chapter {
+            title: "All",
+            paragraphs: &paragraphs,
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    d.app.reader_text_width = 40;
+    let layout = d.app.page_layout(20);
+    let rows = |d: &Driver, lines: std::ops::Range<usize>| -> usize {
+        let book = d.app.current_book.as_ref().unwrap();
+        book.chapter_content[lines]
+            .iter()
+            .map(|line| crate::ui::reader::line_rows(line, &layout).0)
+            .sum()
+    };
+    let top = |d: &Driver| d.app.current_book.as_ref().unwrap().viewport_top;
+
+    // The driver's screen has 20 rows, so half a screen is as many lines as fit in 10.
+    d.keys("<C-d>");
+    let half = top(&d);
+    assert!(rows(&d, 0..half) <= 10 && rows(&d, 0..half + 1) > 10);
+    assert!(half < 10, "wrapped lines count as several rows");
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_line, half);
+    d.keys("<C-u>");
+    assert_eq!(top(&d), 0);
+
+    d.keys("<C-f>");
+    let full = top(&d);
+    assert!(rows(&d, 0..full) <= 18 && rows(&d, 0..full + 1) > 18);
+    assert!(!d.app.focus_mode);
+    d.keys("<C-b>");
+    assert_eq!(top(&d), 0);
+    d.keys("2 <C-d>");
+    assert!(top(&d) > half);
+
+    // At the end of the chapter the cursor goes to the last line.
+    d.keys("G");
+    let last = d.app.current_book.as_ref().unwrap().chapter_content.len() - 1;
+    d.keys("k <C-d> <C-d> <C-d>");
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_line, last);
+}
+
+#[test]
+fn text_filters_clean_the_books_they_are_for() {
+    let ws = Workspace::new();
+    for (file, title) in [("pirated.epub", "Pirated"), ("clean.epub", "Clean")] {
+        epub(
+            ws.path(),
+            file,
+            title,
+            "Test Author",
+            &[Chapter {
+                title: "One",
+                paragraphs: &[
+                    "Call me OceanofPDF.com Ishmael.",
+                    "Visit our store for more great reads!",
+                    "Some years ago.",
+                ],
+            }],
+        );
+    }
+    let mut d = driver(&ws);
+    d.app.apply_config(&AppConfig {
+        text_filters: Some(vec![
+            TextFilterConfig {
+                pattern: r"\s*OceanofPDF\.com".to_string(),
+                books: Some("pirated*".to_string()),
+                ..TextFilterConfig::default()
+            },
+            TextFilterConfig {
+                pattern: "^Visit our store".to_string(),
+                drop: Some(true),
+                books: Some("pirated*".to_string()),
+                ..TextFilterConfig::default()
+            },
+        ]),
+        ..AppConfig::default()
+    });
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let text = |d: &mut Driver, title: &str| {
+        d.app.selected_book_index = d.app.books.iter().position(|b| b.title == title).unwrap();
+        d.keys("<Enter>");
+        let book = d.app.current_book.as_ref().unwrap();
+        let lines: Vec<String> = book
+            .chapter_content
+            .iter()
+            .filter_map(|line| match line {
+                RenderLine::Text(t) => Some(t.clone()),
+                _ => None,
+            })
+            .collect();
+        d.keys("q");
+        lines.join("\n")
+    };
+
+    let pirated = text(&mut d, "Pirated");
+    assert!(pirated.contains("Call me Ishmael."), "{pirated}");
+    assert!(!pirated.contains("Visit our store"));
+    assert!(!pirated.contains("\n\n\n"), "no gap where the promo was");
+    let clean = text(&mut d, "Clean");
+    assert!(clean.contains("OceanofPDF.com") && clean.contains("Visit our store"));
+}
+
+#[test]
+fn jumps_are_retraced_with_ctrl_o_and_ctrl_i() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..30)
+        .map(|i| format!("Line {} of the chapter.", i))
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "jumps.epub",
+        "Jumps",
+        "Test Author",
+        &[
+            Chapter {
+                title: "One",
+                paragraphs: &paragraphs,
+            },
+            Chapter {
+                title: "Two",
+                paragraphs: &paragraphs,
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let at = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.current_chapter, book.current_line)
+    };
+
+    // Places are kept as locators, which land on words, so start on a line of text.
+    d.keys("jjjj");
+    let start = at(&d);
+    d.keys("G");
+    let end = at(&d);
+    assert_ne!(start, end);
+    d.keys("<C-o>");
+    assert_eq!(at(&d), start);
+    d.keys("<Tab>");
+    assert_eq!(at(&d), end);
+
+    d.keys(": 2 <Enter>");
+    assert_eq!(at(&d), (1, 0));
+    d.keys("<C-o>");
+    assert_eq!(at(&d), end);
+    d.keys("<C-o>");
+    assert_eq!(at(&d), start);
+    d.keys("<C-o>");
+    assert_eq!(at(&d), start);
+    assert_eq!(d.app.status_message.as_deref(), Some("No earlier jumps"));
+    d.keys("<C-i> <C-i>");
+    assert_eq!(at(&d), (1, 0));
+
+    // A new jump drops the places Ctrl-o came back from.
+    d.keys("<C-o> gg <C-i>");
+    assert_eq!(at(&d), (0, 0));
+    assert_eq!(d.app.status_message.as_deref(), Some("No later jumps"));
+}
+
+#[test]
+fn wheel_scrolls_and_clicks_move_the_select_cursor() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..40)
+        .map(|i| format!("Line {} {}", i, "with several more words ".repeat(4)))
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "long.epub",
+        "Long",
+        "Test Author",
+        &[Chapter {
+            title: "All",
+            paragraphs: &paragraphs,
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let top = |d: &Driver| d.app.current_book.as_ref().unwrap().viewport_top;
+
+    d.mouse(MouseEventKind::ScrollDown, 10, 10);
+    assert!(top(&d) > 0);
+    d.mouse(MouseEventKind::ScrollUp, 10, 10);
+    assert_eq!(top(&d), 0);
+
+    // Clicks are traced back through the words the last frame drew.
+    d.keys("s");
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+    let target = *d
+        .app
+        .word_areas
+        .iter()
+        .find(|a| a.line > 0 && a.word > 2)
+        .unwrap();
+    d.mouse(
+        MouseEventKind::Down(MouseButton::Left),
+        target.x + 1,
+        target.y,
+    );
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(
+        (book.current_line, book.word_index),
+        (target.line, target.word)
+    );
+
+    // A click on a blank cell leaves the cursor where it is.
+    d.mouse(MouseEventKind::Down(MouseButton::Left), 0, 0);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(
+        (book.current_line, book.word_index),
+        (target.line, target.word)
+    );
+}
+
+#[test]
+fn select_cursor_moves_by_wrapped_rows() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..30)
+        .map(|i| format!("Line {} {}", i, "with several more words ".repeat(6)))
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "long.epub",
+        "Long",
+        "Test Author",
+        &[Chapter {
+            title: "All",
+            paragraphs: &paragraphs,
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    d.app.reader_text_width = 40;
+    let cursor = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.current_line, book.word_index)
+    };
+
+    // `j` and `k` step through the rows a long paragraph wraps to before leaving it.
+    d.keys("s j j j j");
+    let (line, word) = cursor(&d);
+    assert_eq!(word, 0);
+    d.keys("j");
+    assert_eq!(cursor(&d).0, line);
+    assert!(cursor(&d).1 > word);
+    d.keys("k");
+    assert_eq!(cursor(&d), (line, word));
+
+    d.keys("j j j j j j j j");
+    let (later, _) = cursor(&d);
+    assert!(later > line);
+    d.keys("k k k k k k k k");
+    assert_eq!(cursor(&d), (line, word));
+
+    // `b` at the start of a line goes back to the last word of the line before.
+    while cursor(&d).0 == line {
+        d.keys("j");
+    }
+    assert_eq!(cursor(&d).1, 0);
+    d.keys("b");
+    let words = match &d.app.current_book.as_ref().unwrap().chapter_content[line] {
+        crate::app::RenderLine::Text(text) => text.split_whitespace().count(),
+        _ => 0,
+    };
+    assert_eq!(cursor(&d), (line, words - 1));
+
+    // The page follows the cursor down.
+    for _ in 0..60 {
+        d.keys("j");
+    }
+    let book = d.app.current_book.as_ref().unwrap();
+    assert!(book.viewport_top > 0);
+    assert!(book.viewport_top <= book.current_line);
+}
+
+#[test]
+fn reading_presets_are_kept_per_book() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    epub(
+        ws.path(),
+        "plain.epub",
+        "Plain Book",
+        "Test Author",
+        &[Chapter {
+            title: "Only",
+            paragraphs: &["Nothing special here."],
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let open = |d: &mut Driver, title: &str| {
+        d.app.selected_book_index = d.app.books.iter().position(|b| b.title == title).unwrap();
+        d.keys("<Enter>");
+    };
+    let shared = (d.app.margin, d.app.text_columns, d.app.max_text_width);
+
+    open(&mut d, "Sample Book");
+    d.keys("e");
+    assert!(d.app.preset_picker);
+    d.keys("<Esc>");
+    assert!(!d.app.preset_picker);
+    d.keys("e p");
+    assert_eq!((d.app.margin, d.app.text_columns), (1, 2));
+    assert!(d.app.pdf_reflow);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.preset, Some(ReadingPreset::Paper));
+    assert_eq!(book.paragraph_spacing, Some(1));
+    let id = book.id;
+    assert_eq!(
+        d.app.db.get_book(id).unwrap().unwrap().preset.as_deref(),
+        Some("paper")
+    );
+
+    // Papers mark questions by default.
+    d.keys("s <Enter>");
+    let annotations = d.app.db.get_annotations(id).unwrap();
+    assert_eq!(annotations[0].kind, "question");
+
+    // Books without a preset read with the shared layout, and the preset comes back.
+    d.keys("<Esc> q <Esc>");
+    open(&mut d, "Plain Book");
+    assert_eq!(
+        (d.app.margin, d.app.text_columns, d.app.max_text_width),
+        shared
+    );
+    assert!(!d.app.pdf_reflow);
+    d.keys("q");
+    open(&mut d, "Sample Book");
+    assert_eq!(d.app.text_columns, 2);
+
+    d.keys("e n");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert!(book.paged);
+    assert_eq!((d.app.text_columns, d.app.max_text_width), (1, Some(66)));
+    d.keys("e o");
+    assert_eq!(
+        (d.app.margin, d.app.text_columns, d.app.max_text_width),
+        shared
+    );
+    assert_eq!(d.app.current_book.as_ref().unwrap().preset, None);
+    assert_eq!(d.app.db.get_book(id).unwrap().unwrap().preset, None);
+}
+
+#[test]
+fn books_are_tagged_in_the_library_and_stats_break_reading_down() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i T");
+    assert_eq!(d.app.tag_input.as_deref(), Some(""));
+    d.type_text("Fiction, sea");
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Library);
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Tagged #fiction #sea")
+    );
+    assert_eq!(d.app.books[0].tags, ["fiction", "sea"]);
+    d.app
+        .db
+        .log_reading_session(d.app.books[0].id, 900)
+        .unwrap();
+
+    d.keys("i");
+    assert!(d.app.stats_breakdown == StatsBreakdown::Tag);
+    let rows = d.app.stats_breakdown_rows();
+    assert_eq!(
+        rows,
+        [
+            ("#fiction".to_string(), 900, 0),
+            ("#sea".to_string(), 900, 0)
+        ]
+    );
+    d.keys("b");
+    assert_eq!(
+        d.app.stats_breakdown_rows(),
+        [("Test Author".to_string(), 900, 0)]
+    );
+    // The month's reading includes today's.
+    d.keys("j");
+    assert!(d.app.stats_tab == StatsTab::Months);
+    assert_eq!(d.app.stats_breakdown_rows()[0].1, 900);
+}
+
+#[test]
+fn stats_chart_selects_bars_and_fills_quiet_days() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let id = d.app.books[0].id;
+    d.app.db.log_reading_session(id, 1234).unwrap();
+    let now = chrono::Local::now();
+    d.app
+        .db
+        .log_sitting(id, now - chrono::Duration::minutes(25), now, 1234)
+        .unwrap();
+
+    d.keys("i");
+    assert!(d.app.view == AppView::Stats);
+    let bars = d.app.stats_series();
+    assert_eq!(bars.len(), 1);
+    assert_eq!((bars[0].words, bars[0].secs), (1234, 1500));
+    assert_eq!(bars[0].value(StatsMetric::Minutes), 25);
+    d.keys("h");
+    assert_eq!(d.app.stats_cursor, Some(0));
+    d.keys("m");
+    assert!(d.app.stats_metric == StatsMetric::Minutes);
+    d.keys("j");
+    assert_eq!(d.app.stats_cursor, None);
+
+    let day = |m, d| chrono::NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+    let daily = [
+        (day(2, 27), 100, 60),
+        (day(3, 2), 50, 0),
+        (day(3, 2), 25, 120),
+    ];
+    let bars = stats_bars(&daily, StatsTab::Days, day(3, 3));
+    let words: Vec<usize> = bars.iter().map(|b| b.words).collect();
+    assert_eq!(words, vec![100, 0, 0, 75, 0]);
+    assert_eq!(bars[1].label(StatsTab::Days), "28 Feb");
+    assert_eq!(bars[3].describe(StatsTab::Days), "Sun 2 Mar 2025");
+    assert_eq!(
+        stats_bars(&daily, StatsTab::Months, day(4, 1)),
+        vec![
+            StatsBar {
+                start: day(2, 1),
+                words: 100,
+                secs: 60
+            },
+            StatsBar {
+                start: day(3, 1),
+                words: 75,
+                secs: 120
+            },
+            StatsBar {
+                start: day(4, 1),
+                words: 0,
+                secs: 0
+            },
+        ]
+    );
+    assert!(stats_bars(&[], StatsTab::Days, day(3, 3)).is_empty());
+}
+
+#[test]
+fn stats_open_from_the_reader_and_return_to_it() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> i");
+    assert!(d.app.view == AppView::Stats);
+    assert!(d.app.stats_tab == StatsTab::Days);
+    d.keys("j");
+    assert!(d.app.stats_tab == StatsTab::Months);
+    d.keys("j");
+    assert!(d.app.stats_tab == StatsTab::Days);
+    d.keys("k q");
+    assert!(d.app.view == AppView::Reader);
+
+    d.keys("q i <Esc>");
+    assert!(d.app.view == AppView::Library);
+}
+
+fn sitting(d: &mut Driver) -> Option<&mut Sitting> {
+    d.app.current_book.as_mut().unwrap().sitting.as_mut()
+}
+
+#[test]
+fn idling_or_closing_the_book_ends_the_sitting() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> j");
+    let start = sitting(&mut d)
+        .expect("reading starts a sitting")
+        .started_at;
+
+    // Pretend the last key was pressed before the idle timeout.
+    let earlier = start - chrono::Duration::minutes(10);
+    let s = sitting(&mut d).unwrap();
+    s.started_at = earlier - chrono::Duration::minutes(3);
+    s.last_active_at = earlier;
+    d.app.tick_timers();
+    assert!(sitting(&mut d).is_none());
+    assert_eq!(d.app.db.get_average_sitting_secs().unwrap(), Some(180));
+
+    d.keys("j");
+    assert!(sitting(&mut d).is_some());
+    sitting(&mut d).unwrap().started_at -= chrono::Duration::minutes(1);
+    d.keys("q");
+    assert!(sitting(&mut d).is_none());
+    assert_eq!(d.app.db.get_today_reading_secs().unwrap() / 60, 4);
+}
+
+#[test]
+fn going_idle_pauses_auto_scroll_the_pomodoro_and_the_reading_clock() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.app.apply_config(&AppConfig {
+        idle_minutes: Some(2),
+        ..AppConfig::default()
+    });
+    d.keys("i <Enter> a p");
+    assert!(d.app.auto_scroll_active && d.app.pomodoro.running);
+
+    // Nothing pressed for a while yet: still reading.
+    let away = Duration::from_secs(3 * 60);
+    d.app.last_input_at = Instant::now() - Duration::from_secs(60);
+    d.app.tick_timers();
+    assert!(d.app.idle_pause.is_none());
+
+    d.app.last_input_at = Instant::now() - away;
+    d.app.tick_timers();
+    assert!(d.app.idle_pause.is_some() && sitting(&mut d).is_none());
+    assert!(!d.app.auto_scroll_active && !d.app.pomodoro.running);
+    let remaining = d.app.pomodoro.remaining;
+    let started = d.app.current_book.as_ref().unwrap().start_time;
+
+    // The next key picks up where reading left off, without the time away.
+    d.keys("j");
+    assert!(d.app.idle_pause.is_none());
+    assert!(d.app.auto_scroll_active && d.app.pomodoro.running);
+    assert!(d.app.pomodoro.remaining_time() <= remaining);
+    assert!(d.app.current_book.as_ref().unwrap().start_time >= started + away);
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Resumed after 3 min away")
+    );
+}
+
+#[test]
+fn reading_updates_library_progress() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    assert!(d.app.books[0].total_words.is_some());
+    assert_eq!(d.app.books[0].progress(), 0.0);
+
+    // The library entry follows along while the book is still open.
+    d.keys("<Enter> l");
+    let chapter_words = d.app.db.get_chapter_words(d.app.books[0].id).unwrap();
+    assert_eq!(d.app.books[0].position_words, chapter_words[0]);
+    assert!(d.app.books[0].progress() > 0.0);
+
+    d.keys("q <Esc>");
+    assert!(d.app.view == AppView::Library);
+    assert_eq!(d.app.books[0].position_words, chapter_words[0]);
+    let left = d.app.books[0].words_left().unwrap();
+    assert_eq!(left, chapter_words.iter().sum::<usize>() - chapter_words[0]);
+}
+
+#[test]
+fn words_read_follow_the_position_however_it_moves() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let chapter_words = d.app.db.get_chapter_words(d.app.books[0].id).unwrap();
+    let words_read = |d: &Driver| d.app.current_book.as_ref().unwrap().words_read;
+    assert_eq!(words_read(&d), 0);
+
+    // Turning the page counts the rest of the chapter, as scrolling through it would.
+    d.keys("j");
+    let first_line = words_read(&d);
+    assert!(first_line > 0);
+    d.keys("l");
+    assert_eq!(words_read(&d), chapter_words[0]);
+
+    // Going back and forth over the same pages doesn't count them again.
+    d.keys("h");
+    d.keys("l");
+    assert_eq!(words_read(&d), chapter_words[0]);
+    d.keys("l");
+    assert_eq!(words_read(&d), chapter_words[0] + chapter_words[1]);
+}
+
+#[test]
+fn handoff_copies_where_reading_stopped() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l Y");
+    let snippet = d.clipboard.clone().expect("snippet copied");
+    assert!(snippet.starts_with("Sample Book — ch. 2, "), "{snippet}");
+    assert!(snippet.ends_with("…\""), "{snippet}");
+    assert_eq!(d.app.status_message, Some(format!("Copied: {}", snippet)));
+}
+
+#[test]
+fn group_positions_copy_and_jump_with_how_far_ahead_they_are() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.app.group_name = "Ana".to_string();
+    d.app.group_secret = "moby".to_string();
+    let at = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.current_chapter, book.current_line, book.word_index)
+    };
+    d.keys("i <Enter> l j j j j j j y");
+    let token = d.clipboard.clone().expect("position copied");
+    assert!(
+        token.starts_with("tbook1.") && token.ends_with(".Ana"),
+        "{token}"
+    );
+    assert_eq!(
+        d.app.status_message,
+        Some(format!("Copied for your group: {}", token))
+    );
+
+    // Back in the first chapter, the group's position is ahead.
+    d.keys("h m");
+    d.type_text(&token);
+    d.keys("<Enter>");
+    assert_eq!(at(&d), (1, 6, 0));
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Ana is 28 words (48%) ahead of you")
+    );
+
+    // Another group's secret doesn't take, and neither does a changed position.
+    d.keys("h");
+    d.app.group_secret = "dick".to_string();
+    d.keys("m");
+    d.type_text(&token);
+    d.keys("<Enter>");
+    assert_eq!(at(&d).0, 0);
+    assert!(d.app.group_input.is_none());
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("The position was signed with another group secret, or has been changed")
+    );
+}
+
+#[test]
+fn re_rendering_keeps_the_cursor_on_its_word() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l s j ww <Esc>");
+    let position = |d: &Driver| {
+        let book = d.app.current_book.as_ref().unwrap();
+        (book.current_line, book.word_index)
+    };
+    let before = position(&d);
+    assert!(before.1 > 0);
+
+    d.app.refresh_current_book_render_cache().unwrap();
+    assert_eq!(position(&d), before);
+}
+
+#[test]
+fn paragraph_layout_is_kept_per_book() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    d.app.first_line_indent = 2;
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> >> )");
+    assert_eq!(d.app.paragraph_layout(), (1, 4));
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Paragraph spacing: 1")
+    );
+
+    // Stored with the book, so a fresh session picks it up.
+    d.keys("q");
+    assert_eq!(d.app.books[0].first_line_indent, Some(4));
+    let mut d = driver(&ws);
+    d.app.first_line_indent = 2;
+    d.keys("<Enter>");
+    assert_eq!(d.app.paragraph_layout(), (1, 4));
+}
+
+#[test]
+fn custom_annotation_kinds_come_from_the_config() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    let kind = |name: &str, color| AnnotationKindConfig {
+        name: name.to_string(),
+        color,
+    };
+    d.app.apply_config(&AppConfig {
+        annotation_kinds: Some(vec![
+            kind(" Definition ", [200, 120, 40]),
+            kind("question", [1, 2, 3]),
+            kind("definition", [4, 5, 6]),
+            kind("quote", [90, 90, 160]),
+        ]),
+        ..AppConfig::default()
+    });
+    // Built-in names and repeats are dropped; the rest keep their order.
+    let names: Vec<_> = d
+        .app
+        .annotation_kinds
+        .iter()
+        .map(|k| k.name.as_str())
+        .collect();
+    assert_eq!(names, ["definition", "quote"]);
+
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l s 2 jj h 9");
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("No annotation kind 9 in tbook.toml")
+    );
+    let book_id = d.app.books[0].id;
+    let kinds: Vec<_> = d.app.db.get_annotations(book_id).unwrap();
+    let kinds: Vec<_> = kinds.iter().map(|a| a.kind.as_str()).collect();
+    assert_eq!(kinds, ["quote", "highlight"]);
+    assert_eq!(d.app.annotation_kind_color("quote"), Some([90, 90, 160]));
+
+    d.keys("<Esc> A 5");
+    assert!(d.app.view == AppView::AnnotationList);
+    assert_eq!(d.app.annotation_filter_label(), "definition");
+    assert!(d.app.current_annotations.is_empty());
+    d.keys("6");
+    assert_eq!(d.app.current_annotations.len(), 1);
+    assert_eq!(d.app.current_annotations[0].kind, "quote");
+    d.keys("2");
+    assert_eq!(d.app.current_annotations.len(), 1);
+    assert_eq!(d.app.current_annotations[0].kind, "highlight");
+    // Filters past the configured kinds are ignored.
+    d.keys("9");
+    assert!(d.app.annotation_filter == AnnotationFilter::Highlight);
+}
+
+#[test]
+fn the_notes_list_shows_links_of_the_selected_note() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter>");
+    let id = d.app.books[0].id;
+    let first = d
+        .app
+        .db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            start_line: 2,
+            end_line: 2,
+            end_word: 2,
+            content: "Call me Ishmael.",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    let second = d
+        .app
+        .db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            start_line: 2,
+            end_line: 2,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            kind: "note",
+            ..Default::default()
+        })
+        .unwrap();
+    d.app.db.add_annotation_links(second, &[first]).unwrap();
+
+    d.keys("A");
+    assert!(d.app.view == AppView::AnnotationList);
+    let linked = |d: &Driver| {
+        let ids = |notes: &[tbook::db::AnnotationRecord]| -> Vec<i32> {
+            notes.iter().map(|a| a.id).collect()
+        };
+        (
+            ids(&d.app.annotation_links),
+            ids(&d.app.annotation_backlinks),
+        )
+    };
+    assert_eq!(d.app.current_annotations[0].id, first);
+    assert_eq!(linked(&d), (vec![], vec![second]));
+    d.keys("j");
+    assert_eq!(linked(&d), (vec![first], vec![]));
+
+    d.keys("k");
+    assert_eq!(linked(&d), (vec![], vec![second]));
+
+    // Reopening the list loads links made since.
+    d.app.db.add_annotation_links(first, &[second]).unwrap();
+    d.keys("<Esc> A");
+    assert_eq!(linked(&d), (vec![second], vec![second]));
+}
+
+#[test]
+fn tagged_notes_are_browsed_across_the_library() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l s jj h jj q <Esc> A");
+    assert!(d.app.view == AppView::AnnotationList);
+    assert_eq!(d.app.current_annotations.len(), 2);
+
+    // Tag the question; Esc abandons an edit, Enter saves it.
+    d.keys("j T");
+    assert_eq!(d.app.tag_input.as_deref(), Some(""));
+    d.type_text("whaling");
+    d.keys("<Esc>");
+    assert!(d.app.tag_input.is_none());
+    assert!(d.app.view == AppView::AnnotationList);
+    d.keys("T");
+    d.type_text("#Voyage, sea");
+    d.keys("<Enter>");
+    assert_eq!(d.app.status_message.as_deref(), Some("Tagged #sea #voyage"));
+    assert_eq!(d.app.current_annotations[1].tags, ["sea", "voyage"]);
+
+    d.keys("<Esc> q <Esc> A");
+    assert!(d.app.view == AppView::NoteBrowser);
+    assert_eq!(d.app.browsed_notes().len(), 2);
+    d.keys("t");
+    assert_eq!(d.app.note_browser.tag.as_deref(), Some("sea"));
+    assert_eq!(d.app.browsed_notes().len(), 1);
+    assert_eq!(d.app.browsed_notes()[0].kind, "question");
+    d.keys("t t");
+    assert!(d.app.note_browser.tag.is_none());
+
+    d.keys("3");
+    assert_eq!(d.app.browsed_notes().len(), 1);
+    d.keys("1 b");
+    assert_eq!(d.app.note_browser_book_title(), "Sample Book");
+    d.keys("d");
+    assert_eq!(d.app.browsed_notes().len(), 2, "made just now");
+    // Notes older than the period drop out.
+    for note in &mut d.app.note_browser.notes {
+        note.created_at = Some("2000-01-01 00:00:00".to_string());
+    }
+    assert!(d.app.browsed_notes().is_empty());
+    d.keys("ddd");
+    assert_eq!(d.app.browsed_notes().len(), 2);
+
+    d.keys("j <Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.current_chapter, 1);
+}
+
+#[test]
+fn files_are_attached_to_notes_and_detached_again() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let sketch = ws.path().join("sketch.png");
+    std::fs::write(&sketch, b"png").unwrap();
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l s jj h jj q <Esc> A");
+    assert!(d.app.view == AppView::AnnotationList);
+
+    d.keys("F");
+    assert_eq!(d.app.attach_input.as_deref(), Some(""));
+    d.type_text("nowhere.txt");
+    d.keys("<Esc>");
+    assert!(d.app.attach_input.is_none());
+    assert!(d.app.current_annotations[0].attachments.is_empty());
+
+    let sketch = sketch.canonicalize().unwrap().to_string_lossy().to_string();
+    d.keys("F");
+    d.type_text(&sketch);
+    d.keys("<Enter>");
+    assert_eq!(d.app.status_message.as_deref(), Some("Attached sketch.png"));
+    d.keys("F");
+    d.type_text("/gone/run.log");
+    d.keys("<Enter>");
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Attached run.log (file not found)")
+    );
+    assert_eq!(
+        d.app.current_annotations[0].attachments,
+        [sketch.as_str(), "/gone/run.log"]
+    );
+
+    // The files come along to the library-wide list; attaching one again detaches it.
+    let note = d.app.current_annotations[0].id;
+    d.keys("<Esc> q <Esc> A");
+    assert!(d.app.view == AppView::NoteBrowser);
+    while d.app.browsed_notes()[d.app.note_browser.selected].id != note {
+        d.keys("j");
+    }
+    d.keys("F");
+    d.type_text("/gone/run.log");
+    d.keys("<Enter>");
+    assert_eq!(d.app.status_message.as_deref(), Some("Detached run.log"));
+    let browsed = d.app.browsed_notes();
+    let noted = browsed.iter().find(|n| n.id == note).unwrap();
+    assert_eq!(noted.attachments, [sketch.as_str()]);
+}
+
+#[test]
+fn global_search_opens_the_hit() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i S");
+    assert!(d.app.view == AppView::GlobalSearch);
+
+    d.type_text("New Bedford");
+    d.keys("<Enter>");
+    assert_eq!(d.app.global_search_results.len(), 1);
+    // The hit's book is previewed even when the library filter hides it.
+    d.app.library_status_filter = Some(ReadingStatus::Finished);
+    d.app.refresh_library().unwrap();
+    assert!(d.app.books.is_empty());
+    let previewed = d.app.previewed_book().unwrap();
+    assert_eq!(previewed.id, d.app.global_search_results[0].book_id);
+    assert_eq!(previewed.title, "Sample Book");
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.current_chapter, 1);
+}
+
+#[test]
+fn global_search_reports_each_book_and_can_be_stopped() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    figures_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i S");
+    d.type_text("ishmael");
+
+    // Searches wait for the worker, which sends what each book held.
+    d.app.run_global_search().unwrap();
+    let progress = d.app.global_search_progress.clone().unwrap();
+    assert_eq!((progress.scanned, progress.total), (0, 2));
+    let request = d.app.take_global_search().unwrap();
+    assert!(d.app.take_global_search().is_none());
+    let mut updates = Vec::new();
+    request.run(&d.app.db, |update| updates.push(update));
+    assert_eq!(
+        updates.iter().map(|u| u.scanned).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(updates.iter().map(|u| u.hits.len()).sum::<usize>(), 1);
+
+    let mut updates = updates.into_iter();
+    d.app.apply_global_search_update(updates.next().unwrap());
+    assert_eq!(d.app.global_search_progress.as_ref().unwrap().scanned, 1);
+    d.app.apply_global_search_update(updates.next().unwrap());
+    assert!(d.app.global_search_progress.is_none());
+    assert_eq!(d.app.global_search_results.len(), 1);
+
+    // A new search drops whatever the one it replaced still sends.
+    d.app.run_global_search().unwrap();
+    let mut stale = Vec::new();
+    request.run(&d.app.db, |update| stale.push(update));
+    for update in stale {
+        d.app.apply_global_search_update(update);
+    }
+    assert!(d.app.global_search_results.is_empty());
+
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::GlobalSearch);
+    assert!(d.app.global_search_progress.is_none());
+    assert!(d.app.take_global_search().is_none());
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Search stopped after 0 of 2 books")
+    );
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Library);
+}
+
+#[test]
+fn figures_are_listed_in_the_toc_and_jumped_to() {
+    let ws = Workspace::new();
+    figures_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> t");
+    assert!(d.app.view == AppView::Toc);
+    let entries: Vec<(&str, bool)> = d
+        .app
+        .toc_items
+        .iter()
+        .map(|e| (e.label.as_str(), e.figure))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("Introduction", false),
+            ("Anatomy", false),
+            ("Fig. 2.1: The head of a sperm whale", true),
+            ("Fig. 2.2: Flukes raised before a dive", true),
+        ]
+    );
+
+    d.keys("jjj <Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.current_chapter, 1);
+    // The reader lands on the top row of the image, just above its caption.
+    assert!(matches!(
+        book.chapter_content[book.viewport_top],
+        RenderLine::Image { row_idx: 0, .. }
+    ));
+    let caption = book.chapter_content[book.viewport_top..]
+        .iter()
+        .find_map(|l| match l {
+            RenderLine::Caption(c) => Some(c.as_str()),
+            _ => None,
+        });
+    assert_eq!(caption, Some("Fig. 2.2: Flukes raised before a dive"));
+}
+
+#[test]
+fn paged_books_turn_whole_paragraphs_at_a_time() {
+    let ws = Workspace::new();
+    let paragraphs: Vec<String> = (0..40)
+        .map(|i| {
+            format!(
+                "Paragraph {} {}",
+                i,
+                "runs on for a while. ".repeat(i % 7 + 1)
+            )
+        })
+        .collect();
+    let paragraphs: Vec<&str> = paragraphs.iter().map(String::as_str).collect();
+    epub(
+        ws.path(),
+        "paged.epub",
+        "Paged",
+        "Test Author",
+        &[
+            Chapter {
+                title: "Long",
+                paragraphs: &paragraphs,
+            },
+            Chapter {
+                title: "Short",
+                paragraphs: &["The end."],
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> P");
+    let rows = d.viewport.reader_height;
+    let layout = d.app.page_layout(rows);
+    let book = d.app.current_book.as_mut().unwrap();
+    assert!(book.paged);
+    let starts = App::page_starts(book, layout).to_vec();
+    assert!(starts.len() > 2, "{starts:?}");
+
+    // Each page is as full as it can be without cutting the next paragraph.
+    let lines = book.chapter_content.clone();
+    for (start, end) in starts.iter().zip(starts.iter().skip(1)) {
+        let heights: Vec<_> = lines[*start..=*end]
+            .iter()
+            .map(|l| crate::ui::reader::line_rows(l, &layout))
+            .collect();
+        let (page, last) = heights.split_at(heights.len() - 1);
+        let used: usize = page.iter().map(|(h, _)| h).sum();
+        assert!(used - page.last().unwrap().1 <= rows);
+        assert!(used + last[0].0 - last[0].1 > rows);
+    }
+
+    d.keys("<Space>");
+    let book = d.app.current_book.as_mut().unwrap();
+    assert_eq!(book.viewport_top, starts[1]);
+    assert_eq!(App::page_position(book, layout), (2, starts.len()));
+    d.keys("j <PgDn>");
+    assert_eq!(d.app.current_book.as_ref().unwrap().viewport_top, starts[3]);
+    d.keys("k <PgUp>");
+    assert_eq!(d.app.current_book.as_ref().unwrap().viewport_top, starts[1]);
+
+    // Paging runs on into the next chapter and back to the last page of this one.
+    for _ in 1..starts.len() {
+        d.keys("<Space>");
+    }
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_chapter, 1);
+    d.keys("<PgUp>");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(book.current_chapter, 0);
+    assert_eq!(book.viewport_top, *starts.last().unwrap());
+
+    // The mode stays with the book.
+    d.keys("q <Esc>");
+    assert!(d.app.books[0].paged);
+    d.keys("<Enter>");
+    assert!(d.app.current_book.as_ref().unwrap().paged);
+    d.keys("P");
+    assert!(!d.app.current_book.as_ref().unwrap().paged);
+    assert!(!d.app.db.get_books().unwrap()[0].paged);
+}
+
+#[test]
+fn exports_go_to_the_configured_folder_and_keep_earlier_notes() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    let out = ws.path().join("exports");
+    d.app.apply_config(&AppConfig {
+        export_dir: Some(out.to_string_lossy().to_string()),
+        export_mode: Some("append".to_string()),
+        ..AppConfig::default()
+    });
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> E m E m");
+    let notes = std::fs::read_to_string(out.join("notes_sample_book.md")).unwrap();
+    assert_eq!(notes.matches("# Reading Notes: Sample Book").count(), 2);
+    assert!(
+        d.app
+            .status_message
+            .as_deref()
+            .is_some_and(|m| m.starts_with("Added notes to")),
+        "{:?}",
+        d.app.status_message
+    );
+}
+
+#[test]
+fn search_matches_are_found_across_chapters() {
+    let ws = Workspace::new();
+    epub(
+        ws.path(),
+        "whales.epub",
+        "Whales",
+        "Test Author",
+        &[
+            Chapter {
+                title: "One",
+                paragraphs: &["Call me Ishmael.", "The white Whale swims."],
+            },
+            Chapter {
+                title: "Two",
+                paragraphs: &["Nothing to see."],
+            },
+            Chapter {
+                title: "Three",
+                paragraphs: &["A whale and another whale."],
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> /");
+    d.type_text("whale");
+    d.keys("<Enter>");
+    let at = |d: &mut Driver| {
+        let query = d.app.search_query.clone();
+        let book = d.app.current_book.as_mut().unwrap();
+        let word = match &book.chapter_content[book.current_line] {
+            RenderLine::Text(text) => text.split_whitespace().nth(book.word_index).unwrap(),
+            _ => "",
+        }
+        .to_string();
+        let position = App::search_position(book, &query, false);
+        (book.current_chapter, word, position.unwrap())
+    };
+    assert_eq!(
+        at(&mut d),
+        (0, "Whale".to_string(), "match 1/1".to_string())
+    );
+
+    // The chapter without a match is passed over.
+    d.keys("n");
+    assert_eq!(
+        at(&mut d),
+        (2, "whale".to_string(), "match 1/2".to_string())
+    );
+    d.keys("n");
+    assert_eq!(
+        at(&mut d),
+        (2, "whale.".to_string(), "match 2/2".to_string())
+    );
+    d.keys("n");
+    assert_eq!(at(&mut d).0, 0);
+    assert!(
+        d.app
+            .status_message
+            .as_deref()
+            .is_some_and(|m| m.contains("beginning")),
+        "{:?}",
+        d.app.status_message
+    );
+    d.keys("N");
+    assert_eq!(
+        at(&mut d),
+        (2, "whale.".to_string(), "match 2/2".to_string())
+    );
+
+    let lines = [RenderLine::Text("The white Whale swims.".to_string())];
+    let pattern = SearchPattern::new("WHITE whale", false).unwrap();
+    assert_eq!(
+        find_matches(&lines, &pattern),
+        vec![SearchMatch {
+            line: 0,
+            start_word: 1,
+            end_word: 2
+        }]
+    );
+}
+
+#[test]
+fn book_search_lists_matches_by_chapter_and_jumps_to_one() {
+    let ws = Workspace::new();
+    epub(
+        ws.path(),
+        "whales.epub",
+        "Whales",
+        "Test Author",
+        &[
+            Chapter {
+                title: "One",
+                paragraphs: &["Call me Ishmael.", "The white Whale swims."],
+            },
+            Chapter {
+                title: "Two",
+                paragraphs: &["Nothing to see."],
+            },
+            Chapter {
+                title: "Three",
+                paragraphs: &["A whale and another whale went by."],
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> S");
+    assert!(d.app.view == AppView::BookSearch);
+    d.type_text("whale");
+    d.keys("<Enter>");
+    let hits: Vec<(usize, &str, &str)> = d
+        .app
+        .book_search_results
+        .iter()
+        .map(|h| (h.chapter, h.chapter_title.as_str(), h.snippet.as_str()))
+        .collect();
+    assert_eq!(
+        hits,
+        vec![
+            (0, "One", "The white [Whale] swims."),
+            (2, "Three", "A [whale] and another whale went by."),
+            (2, "Three", "A whale and another [whale] went by."),
+        ]
+    );
+
+    d.keys("<Down> <Down> <Enter>");
+    assert!(d.app.view == AppView::Reader);
+    assert_eq!(d.app.search_query, "whale");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_chapter, book.word_index), (2, 4));
+    assert!(matches!(
+        &book.chapter_content[book.current_line],
+        RenderLine::Text(t) if t.starts_with("A whale")
+    ));
+
+    // The list is kept for going back to, and editing the query starts over.
+    d.keys("S");
+    assert_eq!(d.app.book_search_results.len(), 3);
+    d.keys("<BS>");
+    assert!(d.app.book_search_results.is_empty());
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Reader);
+}
+
+#[test]
+fn searches_take_regular_expressions_and_can_match_case() {
+    let ws = Workspace::new();
+    epub(
+        ws.path(),
+        "whales.epub",
+        "Whales",
+        "Test Author",
+        &[
+            Chapter {
+                title: "One",
+                paragraphs: &["Call me Ishmael.", "The white Whale swims (slowly)."],
+            },
+            Chapter {
+                title: "Two",
+                paragraphs: &["A whale and another whale."],
+            },
+        ],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter>");
+    let find = |d: &mut Driver, query: &str| {
+        d.keys("/");
+        d.type_text(query);
+        d.keys("<Enter>");
+        let (query, case) = (d.app.search_query.clone(), d.app.search_case_sensitive);
+        let book = d.app.current_book.as_mut().unwrap();
+        let matches = App::search_matches(book, &query, case).len();
+        (book.current_chapter, matches)
+    };
+    assert_eq!(find(&mut d, "wh.le|ishmael"), (0, 2));
+
+    // With match case on, the lower-case pattern skips the first chapter.
+    d.keys("/ <C-t> <Esc>");
+    assert!(d.app.search_case_sensitive);
+    assert_eq!(find(&mut d, "wh.le|ishmael"), (1, 2));
+    assert_eq!(find(&mut d, "Wh.le|Ishmael"), (0, 2));
+    assert_eq!(
+        d.app.status_message.as_deref(),
+        Some("Search went past the end and started again at the beginning")
+    );
+
+    // A query that is not a valid pattern is searched for as it is.
+    assert_eq!(find(&mut d, "(slowly"), (0, 1));
+    d.keys("/ <C-t> <Esc>");
+
+    d.keys("q <Esc> S");
+    d.type_text("Wh.le");
+    d.keys("<Enter>");
+    assert_eq!(d.app.global_search_results.len(), 2);
+    d.keys("<C-t>");
+    assert_eq!(d.app.global_search_results.len(), 1);
+    let hit = &d.app.global_search_results[0];
+    assert!(
+        hit.snippet.ends_with("The white [Whale] swims (slowly)."),
+        "{}",
+        hit.snippet
+    );
+    assert_eq!(d.app.global_search_results[0].locator.spine, 0);
+}
+
+#[test]
+fn saves_and_exports_wait_for_the_background_writer() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    d.keys("<Enter> l");
+    let book_id = d.app.books[0].id;
+    assert_eq!(
+        d.app.db.get_book(book_id).unwrap().unwrap().current_chapter,
+        1
+    );
+
+    // Outside the driver nothing is written until the queue is run.
+    d.app.current_book.as_mut().unwrap().current_line = 1;
+    d.app.save_progress().unwrap();
+    let path = d.app.export_annotations(ExportFormat::Markdown).unwrap();
+    assert_eq!(d.app.db.get_book(book_id).unwrap().unwrap().current_line, 0);
+    assert!(!std::path::Path::new(&path).exists());
+    // The library already shows where reading stopped.
+    assert_eq!(d.app.books[0].current_line, 1);
+    d.app.refresh_library().unwrap();
+    assert_eq!(d.app.books[0].current_line, 1);
+
+    d.app.flush_writes();
+    assert_eq!(d.app.db.get_book(book_id).unwrap().unwrap().current_line, 1);
+    assert!(std::path::Path::new(&path).exists());
+    assert_eq!(
+        d.app.status_message,
+        Some(format!("Exported notes to {}", path))
+    );
+}
+
+#[test]
+fn network_requests_are_handed_back() {
+    let ws = Workspace::new();
+    let mut d = driver(&ws);
+    d.keys("u");
+    d.type_text("https://example.com/post");
+    d.keys("<Enter>");
+    assert!(matches!(
+        d.requests.as_slice(),
+        [Effect::FetchArticle(url)] if url == "https://example.com/post"
+    ));
+}
+
+#[test]
+fn key_scripts_parse() {
+    let keys = parse_keys("ab <Enter> <C-s> <Space>");
+    let codes: Vec<_> = keys.iter().map(|k| (k.code, k.modifiers)).collect();
+    assert_eq!(
+        codes,
+        vec![
+            (KeyCode::Char('a'), KeyModifiers::NONE),
+            (KeyCode::Char('b'), KeyModifiers::NONE),
+            (KeyCode::Enter, KeyModifiers::NONE),
+            (KeyCode::Char('s'), KeyModifiers::CONTROL),
+            (KeyCode::Char(' '), KeyModifiers::NONE),
+        ]
+    );
+}
+
+#[test]
+fn the_finish_line_counts_down_to_the_next_section_and_chapter_end() {
+    let ws = Workspace::new();
+    epub(
+        ws.path(),
+        "sections.epub",
+        "Sections",
+        "Author",
+        &[Chapter {
+            title: "Voyage",
+            paragraphs: &[
+                "One two three.",
+                "</p><h2>Landfall</h2><p>Four five.",
+                "Six.",
+            ],
+        }],
+    );
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter>");
+    let finish = |d: &mut Driver| {
+        let layout = d.app.page_layout(20);
+        App::finish_line(d.app.current_book.as_mut().unwrap(), layout)
+    };
+
+    // The chapter's own heading doesn't start a section; `## Landfall` does.
+    let start = finish(&mut d);
+    assert_eq!(start.section_words, Some(6));
+    assert_eq!(start.chapter_words, 11);
+    assert_eq!(start.chapter_pages, None);
+    assert_eq!(
+        crate::ui::reader::finish_line_label(&start),
+        "Left: § 6w, ch 11w"
+    );
+
+    // It counts down as the cursor moves, and past the last heading only the chapter is left.
+    d.app.current_book.as_mut().unwrap().current_line = 4;
+    let inside = finish(&mut d);
+    assert_eq!((inside.section_words, inside.chapter_words), (Some(3), 8));
+    d.keys("G");
+    let end = finish(&mut d);
+    assert_eq!(end.section_words, None);
+    assert!(end.chapter_words < 3, "{:?}", end);
+
+    // Turning pages, the chapter's end is counted in pages.
+    d.keys("gg P");
+    assert_eq!(finish(&mut d).chapter_pages, Some(0));
+    assert_eq!(
+        crate::ui::reader::finish_line_label(&finish(&mut d)),
+        "Left: § 6w, ch 0pg"
+    );
+}
+
+#[test]
+fn two_columns_are_toggled_and_can_be_the_default() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter>");
+    assert_eq!(d.app.text_columns, 1);
+    d.keys("|");
+    assert_eq!(d.app.text_columns, 2);
+    assert_eq!(d.app.status_message.as_deref(), Some("Two columns"));
+    d.keys("|");
+    assert_eq!(d.app.text_columns, 1);
+    assert_eq!(d.app.status_message.as_deref(), Some("One column"));
+
+    // The config's default stays unless a layout profile sets its own.
+    d.app.apply_config(&AppConfig {
+        columns: Some(2),
+        ..AppConfig::default()
+    });
+    assert_eq!(d.app.text_columns, 2);
+    let profile = |columns| LayoutProfile {
+        name: "wide".to_string(),
+        columns,
+        ..LayoutProfile::default()
+    };
+    d.app.apply_layout_profile(&[profile(None)], 200, 50, "");
+    assert_eq!(d.app.text_columns, 2);
+    d.app.apply_layout_profile(&[profile(Some(3))], 200, 50, "");
+    assert_eq!(d.app.text_columns, 3);
+    d.keys("|");
+    assert_eq!(d.app.text_columns, 1);
+}
+
+#[test]
+fn the_status_bar_names_the_chapter_and_estimates_time_left() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!(
+        book.parser.chapter_title(book.current_chapter).as_deref(),
+        Some("The Carpet-Bag")
+    );
+
+    // A slow reader with nothing read yet this sitting, so the sample book takes minutes.
+    d.app.reading_wpm = 10.0;
+    d.app.current_book.as_mut().unwrap().words_read = 0;
+    let wpm = d.app.rolling_wpm();
+    assert_eq!(wpm, 10.0);
+    let layout = d.app.page_layout(20);
+    let book = d.app.current_book.as_mut().unwrap();
+    let finish = App::finish_line(book, layout);
+    let chapter_words = book.chapter_words.clone();
+    assert_eq!(
+        finish.book_words,
+        Some(chapter_words[1..].iter().sum::<usize>())
+    );
+    let minutes = |words: usize| words / 10;
+    assert_eq!(
+        crate::ui::reader::time_left_label(&finish, wpm),
+        format!(
+            "~{} min ch, ~{} min book",
+            minutes(finish.chapter_words),
+            minutes(finish.book_words.unwrap())
+        )
+    );
+
+    // This sitting's pace takes over as it goes on.
+    let book = d.app.current_book.as_mut().unwrap();
+    book.start_time = std::time::Instant::now() - std::time::Duration::from_secs(60);
+    book.words_read = 500;
+    let blended = d.app.rolling_wpm();
+    assert!((250.0..256.0).contains(&blended), "{blended}");
+}
+
+#[test]
+fn rows_footer_hints_and_top_bar_buttons_are_clickable() {
+    let ws = Workspace::new();
+    for (file, title) in [("first.epub", "First"), ("second.epub", "Second")] {
+        epub(
+            ws.path(),
+            file,
+            title,
+            "Test Author",
+            &[Chapter {
+                title: "One",
+                paragraphs: &["Some words to read."],
+            }],
+        );
+    }
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    let mut draw = |d: &mut Driver| {
+        terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+    };
+    let find = |d: &Driver, target: ClickTarget| {
+        let area = d
+            .app
+            .click_areas
+            .iter()
+            .find(|a| a.target == target)
+            .unwrap();
+        (area.x + 1, area.y)
+    };
+    let click = MouseEventKind::Down(MouseButton::Left);
+
+    // The wheel moves through the list, and a click on a row selects it.
+    draw(&mut d);
+    d.mouse(MouseEventKind::ScrollDown, 10, 5);
+    assert_eq!(d.app.selected_book_index, 1);
+    let (x, y) = find(&d, ClickTarget::ListRow(0));
+    d.mouse(click, x, y);
+    assert_eq!(d.app.selected_book_index, 0);
+    assert!(d.app.view == AppView::Library);
+
+    // A footer hint presses its key.
+    draw(&mut d);
+    let (x, y) = find(&d, ClickTarget::Key(KeyCode::Char('n')));
+    d.mouse(click, x, y);
+    assert!(d.app.view == AppView::PathInput);
+    d.keys("<Esc>");
+
+    // A second click on the selected row opens it.
+    draw(&mut d);
+    let (x, y) = find(&d, ClickTarget::ListRow(1));
+    d.mouse(click, x, y);
+    assert_eq!(d.app.selected_book_index, 1);
+    d.mouse(click, x, y);
+    assert!(d.app.view == AppView::Reader);
+    let title = d.app.books[1].title.clone();
+    assert!(
+        d.app
+            .current_book
+            .as_ref()
+            .unwrap()
+            .path
+            .contains(&title.to_lowercase())
+    );
+
+    // The top bar buttons narrow and widen the text.
+    let margin = d.app.margin;
+    draw(&mut d);
+    let (x, y) = find(&d, ClickTarget::Key(KeyCode::Char('+')));
+    assert_eq!(y, 0);
+    d.mouse(click, x, y);
+    assert_eq!(d.app.margin, margin - 1);
+    let (x, y) = find(&d, ClickTarget::Key(KeyCode::Char('-')));
+    d.mouse(click, x, y);
+    assert_eq!(d.app.margin, margin);
+}
+
+#[test]
+fn the_progress_gauge_follows_the_cursor_and_can_stay_in_focus_mode() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l");
+    assert!(!d.app.progress_gauge.shown(false));
+    d.keys("M");
+    assert_eq!(d.app.status_message.as_deref(), Some("Progress gauge: On"));
+
+    let progress = |d: &Driver| App::reading_progress(d.app.current_book.as_ref().unwrap());
+    let (chapter, book) = progress(&d);
+    assert_eq!(chapter, 0.0);
+    let words = d.app.current_book.as_ref().unwrap().chapter_words.clone();
+    let total: usize = words.iter().sum();
+    assert_eq!(book, words[0] as f64 / total as f64);
+    d.keys("j j");
+    let (later, further) = progress(&d);
+    assert!(later > chapter && further > book);
+    d.keys("G");
+    assert!(progress(&d).0 > later);
+
+    // On hides with the status bar in focus mode; always keeps it.
+    d.keys("f");
+    assert!(!d.app.progress_gauge.shown(d.app.focus_mode));
+    d.keys("M");
+    assert!(d.app.progress_gauge == ProgressGauge::Always);
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+    let bottom: String = terminal.backend().buffer().content[80 * 23..]
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect();
+    assert!(bottom.starts_with(" Ch ━"), "{bottom}");
+    assert!(bottom.contains("Book"));
+}
+
+#[test]
+fn a_session_that_loses_its_lock_reads_on_without_saving() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let db_path = ws.path().join("tbook.db");
+    d.app.instance_lock = Some(InstanceLock::acquire(&db_path).unwrap().unwrap());
+    d.app.check_instance_lock();
+    assert!(!d.app.read_only);
+
+    // Another session takes over, and this one notices.
+    let _other = InstanceLock::take_over(&db_path).unwrap();
+    d.app.check_instance_lock();
+    assert!(d.app.read_only && d.app.instance_lock.is_none());
+    assert!(
+        d.app
+            .status_message
+            .as_deref()
+            .unwrap()
+            .contains("now read-only")
+    );
+
+    // Reading goes on, but neither the position nor a highlight is saved.
+    d.keys("<Enter> l j j");
+    let book = d.app.current_book.as_ref().unwrap();
+    let (book_id, chapter) = (book.id, book.current_chapter);
+    assert_eq!(chapter, 1);
+    let saved = d.app.db.get_book(book_id).unwrap().unwrap();
+    assert_eq!((saved.current_chapter, saved.current_line), (0, 0));
+    d.keys("s h");
+    assert_eq!(d.app.status_message.as_deref(), Some(READ_ONLY_MESSAGE));
+    assert!(d.app.db.get_annotations(book_id).unwrap().is_empty());
+}
+
+#[test]
+fn closing_a_book_mid_chapter_leaves_a_last_stop_to_go_back_to() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+
+    // At the top of a chapter there is nothing to find again.
+    d.keys("i <Enter> q <Enter> '");
+    assert!(d.app.view == AppView::LastStops);
+    assert!(d.app.last_stops.is_empty());
+
+    d.keys("q l 4 j q <Esc>");
+    assert!(d.app.view == AppView::Library);
+    d.keys("<Enter> h '");
+    assert_eq!(d.app.last_stops.len(), 1);
+    let stop = d.app.last_stops[0].clone();
+    assert_eq!(stop.locator.spine, 1);
+    assert!(stop.snippet.starts_with("I stuffed a shirt"));
+
+    // Going back is a jump, so Ctrl-o returns to where the reader was.
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_chapter, book.current_line), (1, 4));
+    d.keys("<C-o>");
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_chapter, 0);
+
+    // Leaving from the same place again doesn't add a second stop.
+    d.keys("<C-i> q <Enter> '");
+    assert_eq!(d.app.last_stops.len(), 1);
+}
+
+#[test]
+fn closing_a_book_sums_up_the_session_before_the_library() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+
+    // A book left without reading anything goes straight back.
+    d.keys("i <Enter> q");
+    assert!(d.app.view == AppView::Library);
+    assert!(d.app.session_summary.is_none());
+
+    d.keys("<Enter> l l s h <Esc> q");
+    assert!(d.app.view == AppView::Reader);
+    let summary = d.app.session_summary.as_ref().expect("session summary");
+    let words = summary.session.words;
+    assert!(words > 0);
+    assert_eq!((summary.session.annotations, summary.streak), (1, 1));
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    let frame = terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+    let text: String = frame.buffer.content.iter().map(|c| c.symbol()).collect();
+    assert!(text.contains("Notes made     1"));
+
+    // Any key goes on to the library, and the session is kept for the stats.
+    d.keys("x");
+    assert!(d.app.view == AppView::Library);
+    assert!(d.app.session_summary.is_none());
+    let last = d.app.db.get_last_book_session().unwrap().unwrap();
+    assert_eq!((last.words, last.annotations), (words, 1));
+    assert_eq!(last.title.as_deref(), Some("Sample Book"));
+}
+
+#[test]
+fn reading_streaks_run_to_today_or_yesterday() {
+    let day = |d| chrono::NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+    let days = [day(1), day(3), day(4), day(5)];
+    assert_eq!(reading_streak(&days, day(5)), 3);
+    // Today isn't over, so the streak holds until tomorrow.
+    assert_eq!(reading_streak(&days, day(6)), 3);
+    assert_eq!(reading_streak(&days, day(7)), 0);
+    assert_eq!(reading_streak(&[], day(7)), 0);
+}
+
+#[test]
+fn the_omnibox_finds_notes_words_and_text_and_jumps_to_them() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i");
+    let id = d.app.books[0].id;
+    d.app
+        .db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            end_word: 1,
+            content: "The Carpet-Bag",
+            note: Some("Where it starts"),
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    let source = VocabSource {
+        book_id: id,
+        chapter: 1,
+        sentence: "I stuffed a shirt or two into my old carpet-bag.".to_string(),
+    };
+    d.app
+        .db
+        .add_to_vocabulary("carpet-bag", "A travelling bag.", Some(&source))
+        .unwrap();
+
+    d.keys("<C-k>");
+    assert!(d.app.view == AppView::Omnibox);
+    d.type_text("carpet");
+    d.keys("<Enter>");
+    let groups: Vec<_> = d.app.omnibox_results.iter().map(|h| h.group()).collect();
+    assert_eq!(groups, ["Notes", "Words", "Text"]);
+
+    // A note opens its book on the highlighted line.
+    d.keys("<Enter>");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_chapter, book.current_line), (1, 0));
+
+    // The query and results are still there from the reader, and a word goes to the
+    // sentence it was looked up in.
+    d.keys("<C-k> <Down> <Enter>");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_chapter, book.current_line), (1, 4));
+    d.keys("<C-o>");
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_line, 0);
+
+    // Typing starts a new search; Esc goes back where the omnibox was opened.
+    d.keys("<C-k> <BS>");
+    assert!(d.app.omnibox_results.is_empty());
+    d.keys("<Esc>");
+    assert!(d.app.view == AppView::Reader);
+}
+
+#[test]
+fn books_project_a_finish_from_their_pace_or_show_as_stalled() {
+    let day = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+    let today = day("2024-05-20");
+    let record = BookPaceRecord {
+        book_id: 1,
+        last_read: "2024-05-19".to_string(),
+        first_recent: Some("2024-05-11".to_string()),
+        recent_words: 5000,
+    };
+    // The pace runs from the first day read in the fortnight, not the fortnight's start.
+    let pace = BookPace::from_record(&record, today).unwrap();
+    assert_eq!(pace.words_per_day, 500.0);
+    assert_eq!(pace.finish_date(1200, today), Some(day("2024-05-23")));
+    assert_eq!(pace.finish_date(0, today), None);
+    assert_eq!(pace.stalled_days(today), None);
+    let idle = BookPaceRecord {
+        last_read: "2024-04-10".to_string(),
+        first_recent: None,
+        recent_words: 0,
+        ..record
+    };
+    let pace = BookPace::from_record(&idle, today).unwrap();
+    assert_eq!(pace.finish_date(1200, today), None);
+    assert_eq!(pace.stalled_days(today), Some(40));
+
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l l q <Esc>");
+    assert!(d.app.view == AppView::Library);
+    let id = d.app.books[0].id;
+    let pace = d.app.book_paces[&id];
+    assert!(pace.words_per_day > 0.0);
+    let screen = |d: &mut Driver| {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let frame = terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+        let cells = frame.buffer.content.iter();
+        cells.map(|c| c.symbol()).collect::<String>()
+    };
+    assert!(screen(&mut d).contains("Progress · done by"));
+
+    // A book being read that hasn't been opened for a month is flagged.
+    let today = chrono::Local::now().date_naive();
+    d.app.book_paces.insert(
+        id,
+        BookPace {
+            last_read: today - chrono::Days::new(45),
+            words_per_day: 0.0,
+        },
+    );
+    assert_eq!(d.app.stalled_days(&d.app.books[0]), Some(45));
+    let text = screen(&mut d);
+    assert!(text.contains("Sample Book (stalled)"));
+    assert!(text.contains("Progress · stalled 45 days"));
+    d.keys("s");
+    assert_eq!(d.app.stalled_days(&d.app.books[0]), None);
+}
+
+#[test]
+fn the_top_bar_takes_its_colors_and_drops_its_buttons_without_the_mouse() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter>");
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    let mut top_bar = |d: &mut Driver| {
+        let frame = terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+        let row: String = frame.buffer.content[..80]
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        (row, frame.buffer[(0, 0)].bg, frame.buffer[(75, 0)].fg)
+    };
+    let button = ClickTarget::Key(KeyCode::Char('+'));
+
+    let (row, bg, plus) = top_bar(&mut d);
+    assert!(row.trim_end().ends_with("[ - ]  [ + ]"));
+    assert_eq!((bg, plus), (Color::Rgb(50, 50, 50), Color::Green));
+    assert!(d.app.click_areas.iter().any(|a| a.target == button));
+
+    let colors = TopBarConfig {
+        background: Some([40, 40, 40]),
+        plus: Some([152, 151, 26]),
+        ..TopBarConfig::default()
+    };
+    d.app.apply_config(&AppConfig {
+        mouse: Some(false),
+        top_bar: Some(colors.clone()),
+        ..AppConfig::default()
+    });
+    assert!(!d.app.mouse_capture);
+    let (row, bg, _) = top_bar(&mut d);
+    assert!(row.starts_with(" Reading: ") && !row.contains("[ + ]"));
+    assert_eq!(bg, Color::Rgb(40, 40, 40));
+    assert!(!d.app.click_areas.iter().any(|a| a.target == button));
+
+    // They can be kept without the mouse, or hidden with it.
+    d.app.apply_config(&AppConfig {
+        mouse: Some(false),
+        top_bar: Some(TopBarConfig {
+            buttons: Some(true),
+            ..colors
+        }),
+        ..AppConfig::default()
+    });
+    let (row, _, plus) = top_bar(&mut d);
+    assert!(row.trim_end().ends_with("[ - ]  [ + ]"));
+    assert_eq!(plus, Color::Rgb(152, 151, 26));
+    d.app.apply_config(&AppConfig {
+        top_bar: Some(TopBarConfig {
+            buttons: Some(false),
+            ..TopBarConfig::default()
+        }),
+        ..AppConfig::default()
+    });
+    assert!(!top_bar(&mut d).0.contains("[ - ]"));
+}
+
+#[test]
+fn rsvp_reads_on_from_the_cursor_and_leaves_it_where_it_stopped() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l 4 j r");
+    assert!(d.app.view == AppView::Rsvp);
+    assert_eq!(d.app.rsvp_words[..3], ["I", "stuffed", "a"]);
+    let words_before = d.app.current_book.as_ref().unwrap().words_read;
+    let tick = |d: &mut Driver| {
+        d.app.rsvp_last_tick = Instant::now() - Duration::from_secs(1);
+        d.app.tick_rsvp();
+    };
+
+    // A word shows for as long as the speed allows, and the cursor follows.
+    tick(&mut d);
+    // Too soon for the next word.
+    d.app.tick_rsvp();
+    assert_eq!(d.app.rsvp_words[d.app.rsvp_index], "stuffed");
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_line, book.word_index), (4, 1));
+
+    d.keys("<Space>");
+    tick(&mut d);
+    assert_eq!(d.app.rsvp_index, 1);
+    d.keys("<Space>");
+    for _ in 0..3 {
+        tick(&mut d);
+    }
+    d.keys("q");
+    assert!(d.app.view == AppView::Reader);
+    let book = d.app.current_book.as_ref().unwrap();
+    assert_eq!((book.current_line, book.word_index), (4, 4));
+    assert_eq!(book.viewport_top, 4);
+    assert!(book.words_read > words_before);
+
+    // At the end of a chapter it goes on into the next, and stops at the end of the book.
+    d.keys("r");
+    d.app.rsvp_index = d.app.rsvp_words.len() - 1;
+    tick(&mut d);
+    assert_eq!(d.app.current_book.as_ref().unwrap().current_chapter, 2);
+    assert_eq!(d.app.rsvp_words[d.app.rsvp_index], "The");
+    d.app.rsvp_index = d.app.rsvp_words.len() - 1;
+    tick(&mut d);
+    assert!(!d.app.rsvp_active);
+    assert_eq!(d.app.status_message.as_deref(), Some("End of the book"));
+}
+
+#[test]
+fn rsvp_holds_words_longer_at_punctuation_and_paragraph_ends() {
+    let ws = Workspace::new();
+    sample_epub(ws.path());
+    let mut d = driver(&ws);
+    scan(&mut d, &ws.path().to_string_lossy());
+    d.keys("i <Enter> l 4 j r");
+    let time_of = |d: &mut Driver, word: &str| {
+        d.app.rsvp_index = d.app.rsvp_words.iter().position(|w| w == word).unwrap();
+        d.app.rsvp_word_time().as_millis()
+    };
+
+    // A beat at 300 words a minute is 200ms.
+    assert_eq!(time_of(&mut d, "stuffed"), 200);
+    assert_eq!(time_of(&mut d, "Manhatto,"), 300);
+    assert_eq!(time_of(&mut d, "carpet-bag."), 600);
+    // The sample has no sentence ending inside a paragraph, so make one.
+    let shirt = d.app.rsvp_words.iter().position(|w| w == "shirt").unwrap();
+    d.app.rsvp_words[shirt] = "shirt.\u{201d}".to_string();
+    assert_eq!(time_of(&mut d, "shirt.\u{201d}"), 400);
+
+    // Progress counts the words of the chapter before the cursor.
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal.draw(|f| crate::ui::render(f, &mut d.app)).unwrap();
+    let screen: String = terminal
+        .backend()
+        .buffer()
+        .content
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect();
+    assert!(screen.contains(&format!(" Word {}/28 ", shirt + 6)));
+}
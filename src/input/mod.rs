@@ -0,0 +1,884 @@
+//! Key handling shared by the terminal loop and the test driver.
+//!
+//! Each view has a handler that maps a key press to an [`Action`] without touching the
+//! app. `App::dispatch` then carries the action out and returns the side effects the
+//! caller has to run, such as background scans and network requests, so the same
+//! bindings can be exercised without a terminal or a runtime.
+
+mod dispatch;
+
+use crate::app::{AnnotationFilter, App, AppView, ClickTarget, ReadingPreset};
+use crate::dictionary::LookupSettings;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use tbook::export::ExportFormat;
+use tbook::library::AnnotationKind;
+use tbook::review::Grade;
+
+#[cfg(test)]
+pub mod driver;
+#[cfg(test)]
+mod e2e_tests;
+
+/// Something the user asked for, independent of the key that asked for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleHelp,
+    /// Leaves the current view for the one it was opened from.
+    Back,
+    /// Answers yes or no to the open confirmation prompt.
+    ConfirmPrompt,
+    CancelPrompt,
+    /// Moves the selection in the current list, wrapping at the ends.
+    SelectNext,
+    SelectPrev,
+    /// Selects a clicked row of the current list.
+    SelectRow(usize),
+    /// Opens or jumps to the selected item of the current list.
+    OpenSelected,
+    /// Edits the text field of the current view.
+    Input(char),
+    DeleteChar,
+
+    // Library
+    RequestRemoval,
+    Rescan,
+    CycleImageProtocol,
+    AddBooks,
+    AddUrl,
+    OpenLookupSettings,
+    CycleStatus,
+    CycleStatusFilter,
+    OpenGlobalSearch,
+    OpenOmnibox,
+    OpenConceptIndex,
+    OpenNoteBrowser,
+    OpenClippingReview,
+    OpenStats,
+    OpenVocabReview,
+
+    // Path, URL, and lookup prompts
+    StartScan,
+    CompletePath,
+    ForgetRecentPath,
+    FetchUrl,
+    ToggleLookupField,
+    SaveLookupSettings,
+
+    // File explorer
+    ToggleSelection,
+    SelectAll,
+    ClearSelection,
+    ImportSelection,
+    ImportAll,
+    SetGroupCollapsed(bool),
+    ToggleGroup,
+
+    // Global search
+    RunSearch,
+    CancelSearch,
+    ToggleSearchCase,
+    HistoryNext,
+    HistoryPrev,
+    OpenSavedSearches,
+    NameSearch,
+    DeleteSavedSearch,
+
+    ToggleConceptFocus,
+
+    // Stats chart
+    MoveStatsCursor(isize),
+    ToggleStatsMetric,
+    ToggleStatsBreakdown,
+
+    // Notes lists
+    EditTags,
+    AttachFile,
+    CycleNoteBook,
+    CycleNoteTag,
+    CycleNotePeriod,
+
+    // Kindle clipping review
+    /// Picks the next or previous book for the selected clipping's title.
+    CycleClippingBook(bool),
+    AssignClippings,
+    DiscardClipping,
+
+    // Vocabulary review
+    RevealCard,
+    GradeCard(Grade),
+
+    // Reader
+    /// A digit of the count for the next key, as in `10j`.
+    CountDigit(u32),
+    /// The first `g` of `gg`.
+    PendingG,
+    GoToChapterStart,
+    GoToChapterEnd,
+    GoToPercent(usize),
+    OpenGoTo,
+    ScrollDown,
+    ScrollUp,
+    /// Scrolls half a screen of wrapped rows (`true` for down), as vim's Ctrl-d/Ctrl-u.
+    ScrollHalfScreen(bool),
+    /// Scrolls a whole screen of wrapped rows, as vim's Ctrl-f/Ctrl-b.
+    ScrollScreen(bool),
+    /// Back through the jump list with `true` (Ctrl-o), forward again with `false` (Ctrl-i).
+    FollowJump(bool),
+    /// Scrolls a few wrapped rows for a notch of the mouse wheel.
+    ScrollWheel(bool),
+    /// Turns a screenful, never splitting a paragraph between pages.
+    PageForward,
+    PageBack,
+    /// Switches the open book between turning pages and scrolling.
+    TogglePaged,
+    NextChapter,
+    PrevChapter,
+    ToggleFocusMode,
+    CycleLineFocus,
+    CycleFocusRuler,
+    ToggleJustify,
+    ToggleColumns,
+    CycleLineNumbers,
+    CycleProgressGauge,
+    TogglePdfReflow,
+    SummarizeChapter,
+    PomodoroToggle,
+    PomodoroReset,
+    PomodoroSkipBreak,
+    EnterSelect,
+    OpenAnnotations,
+    OpenVocabulary,
+    /// Asks which format to export notes in.
+    ExportNotes,
+    ExportAs(ExportFormat),
+    /// Asks which reading preset the book should use.
+    OpenPresets,
+    SetPreset(Option<ReadingPreset>),
+    KeepLocalPosition,
+    KeepRemotePosition,
+    CopyHandoff,
+    CopyGroupPosition,
+    /// Asks for a reading-group member's position to go to.
+    JoinGroup,
+    /// Copy the selected note as Markdown.
+    CopyNote,
+    OpenToc,
+    OpenFigures,
+    OpenLastStops,
+    DismissSessionSummary,
+    ToggleTheme,
+    AdjustMargin(i16),
+    AdjustSpacing(i16),
+    AdjustParagraphSpacing(i16),
+    AdjustFirstLineIndent(i16),
+    OpenFind,
+    OpenBookSearch,
+    /// Jumps to the next or previous match of the in-book search.
+    SearchNext,
+    SearchPrev,
+    ToggleAutoScroll,
+
+    // Select and visual modes
+    ToggleVisual,
+    WriteNote,
+    Highlight(AnnotationKind),
+    /// Highlight as the nth kind defined in `tbook.toml`.
+    HighlightCustom(usize),
+    /// Marks with the kind the book's preset defaults to.
+    HighlightDefault,
+    LookUpWord,
+    /// Look the selection, or the word under the cursor, up in the encyclopedia.
+    LookUpTopic,
+    CursorDown,
+    CursorUp,
+    WordNext,
+    WordPrev,
+    /// Moves the cursor to a clicked word.
+    ClickWord {
+        line: usize,
+        word: usize,
+    },
+
+    SaveNote,
+    SummarizeHighlights,
+    FilterAnnotations(AnnotationFilter),
+    FindNext,
+    StartRsvp,
+    ToggleRsvp,
+    AdjustRsvpWpm(i64),
+}
+
+impl Action {
+    /// Motions a count prefix repeats, as in `10j` or `3n`.
+    fn repeats(&self) -> bool {
+        matches!(
+            self,
+            Action::ScrollDown
+                | Action::ScrollUp
+                | Action::ScrollHalfScreen(_)
+                | Action::ScrollScreen(_)
+                | Action::PageForward
+                | Action::PageBack
+                | Action::NextChapter
+                | Action::PrevChapter
+                | Action::SearchNext
+                | Action::SearchPrev
+        )
+    }
+
+    /// Actions that add to or change the library, refused in a read-only session.
+    fn changes_library(&self) -> bool {
+        matches!(
+            self,
+            Action::RequestRemoval
+                | Action::Rescan
+                | Action::CycleStatus
+                | Action::StartScan
+                | Action::ForgetRecentPath
+                | Action::FetchUrl
+                | Action::SaveLookupSettings
+                | Action::ImportSelection
+                | Action::ImportAll
+                | Action::NameSearch
+                | Action::DeleteSavedSearch
+                | Action::EditTags
+                | Action::AttachFile
+                | Action::AssignClippings
+                | Action::DiscardClipping
+                | Action::GradeCard(_)
+                | Action::TogglePaged
+                | Action::WriteNote
+                | Action::Highlight(_)
+                | Action::HighlightCustom(_)
+                | Action::HighlightDefault
+                | Action::SaveNote
+                | Action::SummarizeHighlights
+        )
+    }
+}
+
+/// Work an action asks for that `App` can't do on its own.
+#[derive(Clone, Debug)]
+pub enum Effect {
+    Quit,
+    /// Load the cover of the selected book, after the debounce delay when set.
+    ShowCover {
+        debounced: bool,
+    },
+    /// Scan a path for books in the background.
+    Scan(String),
+    /// Queue newly imported books for the full-text index.
+    IndexNewBooks,
+    FetchArticle(String),
+    /// Ask the configured model to summarize this text.
+    Summarize(String),
+    LookUp(String, LookupSettings),
+    /// Put text on the system clipboard.
+    CopyToClipboard(String),
+}
+
+/// Rows available for text, which cursor movement needs to keep the cursor on screen.
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub reader_height: usize,
+}
+
+pub fn handle_key(app: &mut App, key: KeyEvent, viewport: Viewport) -> Vec<Effect> {
+    // Transient messages last until the next key press.
+    app.status_message = None;
+    app.note_input();
+    app.note_reading_activity();
+    let action = action_for(app, key);
+    let times = app.take_key_prefix(action);
+    let mut effects = Vec::new();
+    if let Some(action) = action {
+        for _ in 0..times {
+            effects.extend(app.dispatch(action, viewport));
+        }
+    }
+    app.count_words_read();
+    effects
+}
+
+/// Clicks on what the last frame made clickable (see [`App::click_target_at`]), wheel
+/// scrolling in the reader, and clicks that put the select cursor on a word. A button or
+/// footer hint presses its key, and a list row is selected by the first click and opened
+/// by the next, so clicks do whatever the keys would.
+pub fn handle_mouse(app: &mut App, mouse: MouseEvent, viewport: Viewport) -> Vec<Effect> {
+    let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    if matches!(mouse.kind, MouseEventKind::Moved) {
+        return Vec::new();
+    }
+    app.note_input();
+    // A click goes on from the summary of a closed book, as a key does.
+    if app.session_summary.is_some() {
+        return match mouse.kind {
+            MouseEventKind::Down(_) => app.dispatch(Action::DismissSessionSummary, viewport),
+            _ => Vec::new(),
+        };
+    }
+    let target = match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => app.click_target_at(mouse.column, mouse.row),
+        _ => None,
+    };
+    match target {
+        Some(ClickTarget::Key(code)) => return handle_key(app, press(code), viewport),
+        Some(ClickTarget::ListRow(index)) if app.selected_row() == Some(index) => {
+            return handle_key(app, press(KeyCode::Enter), viewport);
+        }
+        Some(ClickTarget::ListRow(index)) => {
+            app.status_message = None;
+            return app.dispatch(Action::SelectRow(index), viewport);
+        }
+        None => {}
+    }
+    let Some(action) = mouse_action(app, mouse) else {
+        return Vec::new();
+    };
+    app.note_reading_activity();
+    let effects = app.dispatch(action, viewport);
+    app.count_words_read();
+    effects
+}
+
+fn mouse_action(app: &App, mouse: MouseEvent) -> Option<Action> {
+    let paged = app.current_book.as_ref().is_some_and(|b| b.paged);
+    match (app.view, mouse.kind) {
+        (AppView::Reader, MouseEventKind::ScrollDown) if paged => Some(Action::PageForward),
+        (AppView::Reader, MouseEventKind::ScrollUp) if paged => Some(Action::PageBack),
+        (AppView::Reader | AppView::Select | AppView::Visual, MouseEventKind::ScrollDown) => {
+            Some(Action::ScrollWheel(true))
+        }
+        (AppView::Reader | AppView::Select | AppView::Visual, MouseEventKind::ScrollUp) => {
+            Some(Action::ScrollWheel(false))
+        }
+        // The wheel moves through the lists rows can be clicked in.
+        (_, MouseEventKind::ScrollDown) if app.selected_row().is_some() => Some(Action::SelectNext),
+        (_, MouseEventKind::ScrollUp) if app.selected_row().is_some() => Some(Action::SelectPrev),
+        (AppView::Select | AppView::Visual, MouseEventKind::Down(MouseButton::Left)) => {
+            let (line, word) = app.word_at(mouse.column, mouse.row)?;
+            Some(Action::ClickWord { line, word })
+        }
+        _ => None,
+    }
+}
+
+/// The action `key` stands for in the current view, if any.
+pub fn action_for(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.session_summary.is_some() {
+        return Some(Action::DismissSessionSummary);
+    }
+    if key.code == KeyCode::Char('?') {
+        return Some(Action::ToggleHelp);
+    }
+    match app.view {
+        AppView::Help => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => Some(Action::Back),
+            _ => None,
+        },
+        AppView::Library => library(app, key),
+        AppView::Stats => stats(key),
+        AppView::PathInput => path_input(key),
+        AppView::UrlInput => url_input(key),
+        AppView::LookupSettings => lookup_settings(key),
+        AppView::FileExplorer => file_explorer(app, key),
+        AppView::GlobalSearch => global_search(app, key),
+        AppView::BookSearch => book_search(app, key),
+        AppView::Omnibox => omnibox(app, key),
+        AppView::SavedSearches => saved_searches(key),
+        AppView::ConceptIndex => concept_index(key),
+        AppView::Reader => reader(app, key),
+        AppView::Select | AppView::Visual => select(key),
+        AppView::Toc | AppView::Figures | AppView::LastStops => list(key),
+        AppView::Vocabulary => vocabulary(key),
+        AppView::VocabReview => vocab_review(app, key),
+        AppView::Annotation => text_field(key, Action::SaveNote),
+        AppView::AnnotationList => annotation_list(app, key),
+        AppView::NoteBrowser => note_browser(app, key),
+        AppView::ClippingReview => clipping_review(key),
+        AppView::Dictionary => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Back),
+            _ => None,
+        },
+        AppView::Search => match key.code {
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::ToggleSearchCase)
+            }
+            _ => text_field(key, Action::FindNext),
+        },
+        AppView::Rsvp => rsvp(key),
+    }
+}
+
+fn prompt(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Enter => Some(Action::ConfirmPrompt),
+        KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('q') => Some(Action::CancelPrompt),
+        _ => None,
+    }
+}
+
+/// A single-line editor: Enter submits, Esc goes back, everything else is typed.
+fn text_field(key: KeyEvent, submit: Action) -> Option<Action> {
+    match key.code {
+        KeyCode::Enter => Some(submit),
+        KeyCode::Esc => Some(Action::Back),
+        KeyCode::Char(c) => Some(Action::Input(c)),
+        KeyCode::Backspace => Some(Action::DeleteChar),
+        _ => None,
+    }
+}
+
+/// A plain list: j/k to move, Enter to open, q or Esc to leave.
+fn list(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Back),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::SelectNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::SelectPrev),
+        KeyCode::Enter => Some(Action::OpenSelected),
+        _ => None,
+    }
+}
+
+fn library(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.pending_book_removal.is_some() || !app.pending_relinks.is_empty() {
+        return prompt(key);
+    }
+    if app.tag_input.is_some() {
+        return line_prompt(key);
+    }
+    match key.code {
+        KeyCode::Char('q') => Some(Action::Quit),
+        KeyCode::Char('d') | KeyCode::Char('x') => Some(Action::RequestRemoval),
+        KeyCode::Char('r') => Some(Action::Rescan),
+        // Cycle image protocols to debug cover rendering across terminals.
+        KeyCode::Char('p') => Some(Action::CycleImageProtocol),
+        KeyCode::Char('n') => Some(Action::AddBooks),
+        KeyCode::Char('u') => Some(Action::AddUrl),
+        KeyCode::Char('L') => Some(Action::OpenLookupSettings),
+        KeyCode::Char('s') => Some(Action::CycleStatus),
+        KeyCode::Char('T') => Some(Action::EditTags),
+        KeyCode::Char('f') => Some(Action::CycleStatusFilter),
+        KeyCode::Char('S') => Some(Action::OpenGlobalSearch),
+        KeyCode::Char('C') => Some(Action::OpenConceptIndex),
+        KeyCode::Char('A') => Some(Action::OpenNoteBrowser),
+        KeyCode::Char('K') => Some(Action::OpenClippingReview),
+        KeyCode::Char('i') => Some(Action::OpenStats),
+        KeyCode::Char('R') => Some(Action::OpenVocabReview),
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::OpenOmnibox)
+        }
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::SelectNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::SelectPrev),
+        KeyCode::Enter => Some(Action::OpenSelected),
+        _ => None,
+    }
+}
+
+fn path_input(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Tab => Some(Action::CompletePath),
+        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::ForgetRecentPath)
+        }
+        KeyCode::Down => Some(Action::SelectNext),
+        KeyCode::Up => Some(Action::SelectPrev),
+        _ => text_field(key, Action::StartScan),
+    }
+}
+
+fn url_input(key: KeyEvent) -> Option<Action> {
+    text_field(key, Action::FetchUrl)
+}
+
+fn lookup_settings(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Tab | KeyCode::Up | KeyCode::Down => Some(Action::ToggleLookupField),
+        _ => text_field(key, Action::SaveLookupSettings),
+    }
+}
+
+fn file_explorer(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.pending_large_import.is_some() {
+        return prompt(key);
+    }
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(Action::Back),
+        KeyCode::Char(' ') => Some(Action::ToggleSelection),
+        KeyCode::Char('a') => Some(Action::SelectAll),
+        KeyCode::Char('c') => Some(Action::ClearSelection),
+        KeyCode::Char('i') => Some(Action::ImportAll),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::SelectNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::SelectPrev),
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::SetGroupCollapsed(true)),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::SetGroupCollapsed(false)),
+        KeyCode::Tab => Some(Action::ToggleGroup),
+        KeyCode::Enter => Some(Action::ImportSelection),
+        _ => None,
+    }
+}
+
+fn global_search(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.saved_search_name.is_some() {
+        return match key.code {
+            KeyCode::Enter => Some(Action::ConfirmPrompt),
+            KeyCode::Esc => Some(Action::CancelPrompt),
+            KeyCode::Char(c) => Some(Action::Input(c)),
+            KeyCode::Backspace => Some(Action::DeleteChar),
+            _ => None,
+        };
+    }
+    let has_results = !app.global_search_results.is_empty();
+    match key.code {
+        KeyCode::Esc if app.global_search_progress.is_some() => Some(Action::CancelSearch),
+        KeyCode::Enter if has_results => Some(Action::OpenSelected),
+        KeyCode::Enter => Some(Action::RunSearch),
+        KeyCode::Down if has_results => Some(Action::SelectNext),
+        KeyCode::Down => Some(Action::HistoryNext),
+        KeyCode::Up if has_results => Some(Action::SelectPrev),
+        KeyCode::Up => Some(Action::HistoryPrev),
+        KeyCode::Tab => Some(Action::OpenSavedSearches),
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::NameSearch)
+        }
+        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::ToggleSearchCase)
+        }
+        _ => text_field(key, Action::RunSearch),
+    }
+}
+
+/// Enter searches, then goes to the selected result; typing starts a new search.
+fn omnibox(app: &App, key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Enter if !app.omnibox_results.is_empty() => Some(Action::OpenSelected),
+        KeyCode::Down => Some(Action::SelectNext),
+        KeyCode::Up => Some(Action::SelectPrev),
+        _ => text_field(key, Action::RunSearch),
+    }
+}
+
+/// Like global search, without history or saved searches.
+fn book_search(app: &App, key: KeyEvent) -> Option<Action> {
+    let has_results = !app.book_search_results.is_empty();
+    match key.code {
+        KeyCode::Enter if has_results => Some(Action::OpenSelected),
+        KeyCode::Down => Some(Action::SelectNext),
+        KeyCode::Up => Some(Action::SelectPrev),
+        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::ToggleSearchCase)
+        }
+        _ => text_field(key, Action::RunSearch),
+    }
+}
+
+fn saved_searches(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Tab => Some(Action::Back),
+        KeyCode::Char('d') => Some(Action::DeleteSavedSearch),
+        _ => list(key),
+    }
+}
+
+/// h/l pick a bar, j/k switch between days and months, m between words and minutes.
+fn stats(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::MoveStatsCursor(-1)),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::MoveStatsCursor(1)),
+        KeyCode::Char('m') => Some(Action::ToggleStatsMetric),
+        KeyCode::Char('b') => Some(Action::ToggleStatsBreakdown),
+        _ => list(key),
+    }
+}
+
+fn concept_index(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Tab | KeyCode::Char('h') | KeyCode::Char('l') => Some(Action::ToggleConceptFocus),
+        _ => list(key),
+    }
+}
+
+/// The export format picker: the first letter of a format exports, Esc or q cancels.
+fn export_picker(key: KeyEvent) -> Option<Action> {
+    let format = match key.code {
+        KeyCode::Char('m') => ExportFormat::Markdown,
+        KeyCode::Char('j') => ExportFormat::Json,
+        KeyCode::Char('c') => ExportFormat::Csv,
+        KeyCode::Char('h') => ExportFormat::Html,
+        KeyCode::Char('o') => ExportFormat::Org,
+        KeyCode::Esc | KeyCode::Char('q') => return Some(Action::CancelPrompt),
+        _ => return None,
+    };
+    Some(Action::ExportAs(format))
+}
+
+/// The preset picker: n/t/p pick novel, textbook, or paper, o drops the preset.
+fn preset_picker(key: KeyEvent) -> Option<Action> {
+    let preset = match key.code {
+        KeyCode::Char('n') => Some(ReadingPreset::Novel),
+        KeyCode::Char('t') => Some(ReadingPreset::Textbook),
+        KeyCode::Char('p') => Some(ReadingPreset::Paper),
+        KeyCode::Char('o') => None,
+        KeyCode::Esc | KeyCode::Char('q') => return Some(Action::CancelPrompt),
+        _ => return None,
+    };
+    Some(Action::SetPreset(preset))
+}
+
+/// Which reading position to keep when another device disagrees with this one.
+fn sync_chooser(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('l') => Some(Action::KeepLocalPosition),
+        KeyCode::Char('r') => Some(Action::KeepRemotePosition),
+        KeyCode::Esc | KeyCode::Char('q') => Some(Action::CancelPrompt),
+        _ => None,
+    }
+}
+
+fn reader(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.export_picker {
+        return export_picker(key);
+    }
+    if app.preset_picker {
+        return preset_picker(key);
+    }
+    if app.sync_conflict.is_some() {
+        return sync_chooser(key);
+    }
+    if app.goto_input.is_some() || app.group_input.is_some() {
+        return line_prompt(key);
+    }
+    let paged = app.current_book.as_ref().is_some_and(|b| b.paged);
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        // Paged books turn pages where others scroll.
+        KeyCode::Char('d') | KeyCode::Char('f') if ctrl && paged => Some(Action::PageForward),
+        KeyCode::Char('u') | KeyCode::Char('b') if ctrl && paged => Some(Action::PageBack),
+        KeyCode::Char('d') if ctrl => Some(Action::ScrollHalfScreen(true)),
+        KeyCode::Char('u') if ctrl => Some(Action::ScrollHalfScreen(false)),
+        KeyCode::Char('f') if ctrl => Some(Action::ScrollScreen(true)),
+        KeyCode::Char('b') if ctrl => Some(Action::ScrollScreen(false)),
+        KeyCode::Char('o') if ctrl => Some(Action::FollowJump(true)),
+        // Terminals send Ctrl-i as Tab.
+        KeyCode::Char('i') if ctrl => Some(Action::FollowJump(false)),
+        KeyCode::Tab => Some(Action::FollowJump(false)),
+        KeyCode::Char('k') if ctrl => Some(Action::OpenOmnibox),
+        KeyCode::Char(c @ '1'..='9') => Some(Action::CountDigit(c as u32 - '0' as u32)),
+        KeyCode::Char('0') if app.key_count.is_some() => Some(Action::CountDigit(0)),
+        KeyCode::Char('g') if app.pending_g => Some(Action::GoToChapterStart),
+        KeyCode::Char('g') => Some(Action::PendingG),
+        KeyCode::Char('G') => Some(Action::GoToChapterEnd),
+        // `50%`, as in vim: a count with `%` goes that far through the book.
+        KeyCode::Char('%') => app.key_count.map(Action::GoToPercent),
+        KeyCode::Char(':') => Some(Action::OpenGoTo),
+        KeyCode::Char('q') => Some(Action::Back),
+        KeyCode::Char('f') => Some(Action::ToggleFocusMode),
+        KeyCode::Char('r') => Some(Action::StartRsvp),
+        KeyCode::Char('W') => Some(Action::TogglePdfReflow),
+        KeyCode::Char('Z') => Some(Action::SummarizeChapter),
+        KeyCode::Char('p') => Some(Action::PomodoroToggle),
+        KeyCode::Char('R') => Some(Action::PomodoroReset),
+        KeyCode::Char('B') => Some(Action::PomodoroSkipBreak),
+        KeyCode::Char('s') => Some(Action::EnterSelect),
+        KeyCode::Char('A') => Some(Action::OpenAnnotations),
+        KeyCode::Char('V') => Some(Action::OpenVocabulary),
+        KeyCode::Char('E') => Some(Action::ExportNotes),
+        KeyCode::Char('e') => Some(Action::OpenPresets),
+        KeyCode::Char('Y') => Some(Action::CopyHandoff),
+        KeyCode::Char('y') => Some(Action::CopyGroupPosition),
+        KeyCode::Char('m') => Some(Action::JoinGroup),
+        KeyCode::Char('L') => Some(Action::CycleLineFocus),
+        KeyCode::Char('T') => Some(Action::CycleFocusRuler),
+        KeyCode::Char('J') => Some(Action::ToggleJustify),
+        KeyCode::Char('|') => Some(Action::ToggleColumns),
+        KeyCode::Char('#') => Some(Action::CycleLineNumbers),
+        KeyCode::Char('M') => Some(Action::CycleProgressGauge),
+        KeyCode::Char('t') => Some(Action::OpenToc),
+        KeyCode::Char('F') => Some(Action::OpenFigures),
+        KeyCode::Char('\'') => Some(Action::OpenLastStops),
+        KeyCode::Char('i') => Some(Action::OpenStats),
+        KeyCode::Char('P') => Some(Action::TogglePaged),
+        KeyCode::Char(' ') | KeyCode::PageDown => Some(Action::PageForward),
+        KeyCode::PageUp => Some(Action::PageBack),
+        // Paged books turn pages where others scroll.
+        KeyCode::Down | KeyCode::Char('j') if paged => Some(Action::PageForward),
+        KeyCode::Up | KeyCode::Char('k') if paged => Some(Action::PageBack),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::ScrollDown),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::ScrollUp),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::NextChapter),
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::PrevChapter),
+        KeyCode::Char('c') => Some(Action::ToggleTheme),
+        KeyCode::Char('[') | KeyCode::Char('-') => Some(Action::AdjustMargin(1)),
+        KeyCode::Char(']') | KeyCode::Char('+') | KeyCode::Char('=') => {
+            Some(Action::AdjustMargin(-1))
+        }
+        KeyCode::Char('{') => Some(Action::AdjustSpacing(1)),
+        KeyCode::Char('}') => Some(Action::AdjustSpacing(-1)),
+        KeyCode::Char(')') => Some(Action::AdjustParagraphSpacing(1)),
+        KeyCode::Char('(') => Some(Action::AdjustParagraphSpacing(-1)),
+        KeyCode::Char('>') => Some(Action::AdjustFirstLineIndent(1)),
+        KeyCode::Char('<') => Some(Action::AdjustFirstLineIndent(-1)),
+        KeyCode::Char('/') => Some(Action::OpenFind),
+        KeyCode::Char('S') => Some(Action::OpenBookSearch),
+        KeyCode::Char('n') => Some(Action::SearchNext),
+        KeyCode::Char('N') => Some(Action::SearchPrev),
+        KeyCode::Char('a') => Some(Action::ToggleAutoScroll),
+        _ => None,
+    }
+}
+
+fn select(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('v') => Some(Action::ToggleVisual),
+        KeyCode::Char('a') => Some(Action::WriteNote),
+        KeyCode::Char('h') => Some(Action::Highlight(AnnotationKind::Highlight)),
+        KeyCode::Char('q') => Some(Action::Highlight(AnnotationKind::Question)),
+        KeyCode::Char('m') => Some(Action::Highlight(AnnotationKind::Summary)),
+        KeyCode::Char(c @ '1'..='9') => Some(Action::HighlightCustom(c as usize - '1' as usize)),
+        KeyCode::Enter => Some(Action::HighlightDefault),
+        KeyCode::Char('d') => Some(Action::LookUpWord),
+        KeyCode::Char('W') => Some(Action::LookUpTopic),
+        KeyCode::Char('f') => Some(Action::ToggleFocusMode),
+        KeyCode::Char('p') => Some(Action::PomodoroToggle),
+        KeyCode::Char('R') => Some(Action::PomodoroReset),
+        KeyCode::Char('B') => Some(Action::PomodoroSkipBreak),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::CursorDown),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::CursorUp),
+        KeyCode::Char('w') => Some(Action::WordNext),
+        KeyCode::Char('b') => Some(Action::WordPrev),
+        KeyCode::Esc => Some(Action::Back),
+        _ => None,
+    }
+}
+
+/// A one-line prompt, such as tags in a notes list: Enter saves, Esc cancels.
+fn line_prompt(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Enter => Some(Action::ConfirmPrompt),
+        KeyCode::Esc => Some(Action::CancelPrompt),
+        KeyCode::Char(c) => Some(Action::Input(c)),
+        KeyCode::Backspace => Some(Action::DeleteChar),
+        _ => None,
+    }
+}
+
+/// Kind filters shared by the notes lists: 1-4 built in, 5-9 from `tbook.toml`.
+fn kind_filter(key: KeyEvent) -> Option<Action> {
+    let filter = match key.code {
+        KeyCode::Char('1') => AnnotationFilter::All,
+        KeyCode::Char('2') => AnnotationFilter::Highlight,
+        KeyCode::Char('3') => AnnotationFilter::Question,
+        KeyCode::Char('4') => AnnotationFilter::Summary,
+        KeyCode::Char(c @ '5'..='9') => AnnotationFilter::Custom(c as usize - '5' as usize),
+        _ => return None,
+    };
+    Some(Action::FilterAnnotations(filter))
+}
+
+fn annotation_list(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.tag_input.is_some() || app.attach_input.is_some() {
+        return line_prompt(key);
+    }
+    match key.code {
+        KeyCode::Char('G') => Some(Action::SummarizeHighlights),
+        KeyCode::Char('T') => Some(Action::EditTags),
+        KeyCode::Char('F') => Some(Action::AttachFile),
+        KeyCode::Char('y') => Some(Action::CopyNote),
+        _ => kind_filter(key).or_else(|| list(key)),
+    }
+}
+
+fn note_browser(app: &App, key: KeyEvent) -> Option<Action> {
+    if app.tag_input.is_some() || app.attach_input.is_some() {
+        return line_prompt(key);
+    }
+    match key.code {
+        KeyCode::Char('T') => Some(Action::EditTags),
+        KeyCode::Char('F') => Some(Action::AttachFile),
+        KeyCode::Char('y') => Some(Action::CopyNote),
+        KeyCode::Char('b') => Some(Action::CycleNoteBook),
+        KeyCode::Char('t') => Some(Action::CycleNoteTag),
+        KeyCode::Char('d') => Some(Action::CycleNotePeriod),
+        _ => kind_filter(key).or_else(|| list(key)),
+    }
+}
+
+/// Kindle clippings without a book: h/l pick the book, Enter assigns the whole title.
+fn clipping_review(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::CycleClippingBook(false)),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::CycleClippingBook(true)),
+        KeyCode::Enter => Some(Action::AssignClippings),
+        KeyCode::Char('d') => Some(Action::DiscardClipping),
+        _ => list(key),
+    }
+}
+
+fn vocabulary(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('r') => Some(Action::OpenVocabReview),
+        _ => list(key),
+    }
+}
+
+fn vocab_review(app: &App, key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Back),
+        KeyCode::Char(' ') | KeyCode::Enter => Some(Action::RevealCard),
+        KeyCode::Char(c) if app.vocab_review.revealed => Grade::from_key(c).map(Action::GradeCard),
+        _ => None,
+    }
+}
+
+fn rsvp(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Back),
+        KeyCode::Char(' ') => Some(Action::ToggleRsvp),
+        KeyCode::Char('+') | KeyCode::Char('=') => Some(Action::AdjustRsvpWpm(50)),
+        KeyCode::Char('-') => Some(Action::AdjustRsvpWpm(-50)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Workspace;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn the_same_key_means_different_things_per_view() {
+        let ws = Workspace::new();
+        let mut app = App::new(&ws.path().join("tbook.db").to_string_lossy()).unwrap();
+        assert_eq!(action_for(&app, key('q')), Some(Action::Quit));
+
+        app.view = AppView::Reader;
+        assert_eq!(action_for(&app, key('q')), Some(Action::Back));
+        app.view = AppView::Select;
+        assert_eq!(
+            action_for(&app, key('q')),
+            Some(Action::Highlight(AnnotationKind::Question))
+        );
+        app.view = AppView::Annotation;
+        assert_eq!(action_for(&app, key('q')), Some(Action::Input('q')));
+        assert_eq!(action_for(&app, key('?')), Some(Action::ToggleHelp));
+    }
+
+    #[test]
+    fn prompts_take_over_the_view_keys() {
+        let ws = Workspace::new();
+        let mut app = App::new(&ws.path().join("tbook.db").to_string_lossy()).unwrap();
+        app.pending_book_removal = Some(1);
+        assert_eq!(action_for(&app, key('q')), Some(Action::CancelPrompt));
+        assert_eq!(action_for(&app, key('j')), None);
+
+        let viewport = Viewport { reader_height: 8 };
+        app.dispatch(Action::CancelPrompt, viewport);
+        assert_eq!(app.pending_book_removal, None);
+        assert_eq!(action_for(&app, key('j')), Some(Action::SelectNext));
+    }
+}
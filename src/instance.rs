@@ -0,0 +1,123 @@
+//! Keeps two sessions from saving over each other's progress in one library. The session
+//! that opens a library leaves a lock file beside the database naming its process;
+//! another session that finds it can read without saving, or take over, after which the
+//! first one stops saving.
+
+use crate::config;
+use anyhow::Result;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The session holding a library's lock.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    /// When the lock was taken, in nanoseconds since the epoch, which tells apart two
+    /// locks taken by one process.
+    taken_at: u128,
+}
+
+impl LockOwner {
+    fn this_process() -> Self {
+        Self {
+            pid: std::process::id(),
+            host: config::hostname(),
+            taken_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        }
+    }
+
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines().map(str::trim);
+        let pid = lines.next()?.parse().ok()?;
+        let host = lines.next().unwrap_or_default().to_string();
+        let taken_at = lines
+            .next()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or_default();
+        Some(Self {
+            pid,
+            host,
+            taken_at,
+        })
+    }
+
+    fn contents(&self) -> String {
+        format!("{}\n{}\n{}\n", self.pid, self.host, self.taken_at)
+    }
+
+    /// Whether the session may still be running. Processes on other machines can't be
+    /// checked, so they count as running, as do all processes where `/proc` is missing.
+    pub fn is_running(&self) -> bool {
+        if self.host != config::hostname() || !Path::new("/proc/self").exists() {
+            return true;
+        }
+        Path::new("/proc").join(self.pid.to_string()).exists()
+    }
+}
+
+/// A held lock on a library, given up when dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    owner: LockOwner,
+}
+
+impl InstanceLock {
+    /// The lock file for the database at `db_path`, e.g. `tbook.db.lock`.
+    pub fn path_for(db_path: &Path) -> PathBuf {
+        let mut name = db_path.as_os_str().to_owned();
+        name.push(".lock");
+        PathBuf::from(name)
+    }
+
+    /// Takes the lock, or names the session holding it. A lock left behind by a session
+    /// that is no longer running is taken over.
+    pub fn acquire(db_path: &Path) -> Result<std::result::Result<Self, LockOwner>> {
+        let path = Self::path_for(db_path);
+        let owner = LockOwner::this_process();
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(owner.contents().as_bytes())?;
+                return Ok(Ok(Self { path, owner }));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        match Self::holder(&path) {
+            Some(holder) if holder.is_running() => Ok(Err(holder)),
+            _ => Self::take_over(db_path).map(Ok),
+        }
+    }
+
+    /// Takes the lock whoever holds it. The session that held it finds out through
+    /// [`InstanceLock::is_held`].
+    pub fn take_over(db_path: &Path) -> Result<Self> {
+        let path = Self::path_for(db_path);
+        let owner = LockOwner::this_process();
+        fs::write(&path, owner.contents())?;
+        Ok(Self { path, owner })
+    }
+
+    /// Whether this session still holds the lock, false once another has taken it over.
+    pub fn is_held(&self) -> bool {
+        Self::holder(&self.path).as_ref() == Some(&self.owner)
+    }
+
+    fn holder(path: &Path) -> Option<LockOwner> {
+        LockOwner::parse(&fs::read_to_string(path).ok()?)
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // A session that took over keeps its lock.
+        if self.is_held() {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
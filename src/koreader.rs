@@ -0,0 +1,904 @@
+//! Import and export of KOReader's per-book sidecar.
+//!
+//! KOReader keeps what it knows about a book in `<name>.sdr/metadata.<ext>.lua` next to
+//! the file: a Lua table holding, among its display settings, the highlights
+//! (`annotations`) and the reading position (`last_xpointer`, `percent_finished`). EPUB
+//! positions are crengine XPointers into the spine item (`DocFragment`) they belong to.
+//! Importing places highlights by their text and moves tbook's reading position there;
+//! exporting merges tbook's notes and position into the sidecar and leaves everything
+//! else in it as it was.
+
+use crate::db::{AnnotationRecord, BookRecord, Db, NewAnnotation};
+use crate::library::{self, AnnotationKind};
+use crate::locator::{self, Locator};
+use crate::parser::BookParser;
+use anyhow::{Result, bail};
+use md5::{Digest, Md5};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Tag put on every note imported from KOReader.
+pub const KOREADER_TAG: &str = "koreader";
+
+/// Words of text used to find a reading position again.
+const POSITION_WORDS: usize = 12;
+
+/// A Lua value as KOReader writes them. Numbers keep their source text so that settings
+/// tbook doesn't touch are written back exactly.
+#[derive(Clone, Debug, PartialEq)]
+enum Lua {
+    Nil,
+    Bool(bool),
+    Number(String),
+    Str(String),
+    Table(Vec<(LuaKey, Lua)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum LuaKey {
+    Index(usize),
+    Name(String),
+}
+
+impl Lua {
+    fn get(&self, name: &str) -> Option<&Lua> {
+        self.fields()
+            .iter()
+            .find(|(key, _)| matches!(key, LuaKey::Name(n) if n == name))
+            .map(|(_, value)| value)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Lua::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Lua::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Fields of a table, in source order; nothing for other values.
+    fn fields(&self) -> &[(LuaKey, Lua)] {
+        match self {
+            Lua::Table(fields) => fields,
+            _ => &[],
+        }
+    }
+
+    /// Replaces the field `name`, or adds it at the end.
+    fn set(&mut self, name: &str, value: Lua) {
+        let Lua::Table(fields) = self else {
+            return;
+        };
+        match fields
+            .iter_mut()
+            .find(|(key, _)| matches!(key, LuaKey::Name(n) if n == name))
+        {
+            Some((_, old)) => *old = value,
+            None => fields.push((LuaKey::Name(name.to_string()), value)),
+        }
+    }
+}
+
+/// Reads the `return { ... }` file KOReader writes. Only data is understood: tables,
+/// strings, numbers, booleans and `nil`, which is all a sidecar holds.
+fn parse_lua(text: &str) -> Result<Lua> {
+    let mut reader = LuaReader {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    reader.skip_space();
+    if reader.word() != "return" {
+        bail!("Not a KOReader sidecar: it should start with `return`");
+    }
+    let value = reader.value()?;
+    reader.skip_space();
+    if reader.pos < reader.chars.len() {
+        bail!("Unexpected text after the sidecar table");
+    }
+    Ok(value)
+}
+
+struct LuaReader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LuaReader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    /// Skips whitespace and `--` comments, including `--[[ ]]` blocks.
+    fn skip_space(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            if !self.starts_with("--") {
+                return;
+            }
+            self.pos += 2;
+            let end = if self.starts_with("[[") { "]]" } else { "\n" };
+            while self.pos < self.chars.len() && !self.starts_with(end) {
+                self.pos += 1;
+            }
+            // A comment left open by a truncated sidecar ends with the file.
+            self.pos = (self.pos + end.len()).min(self.chars.len());
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c) {
+            bail!("Expected `{}` at character {} of the sidecar", c, self.pos);
+        }
+        Ok(())
+    }
+
+    /// A name, keyword or number.
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '-' || c == '+')
+                && matches!(self.chars.get(self.pos.wrapping_sub(1)), Some('e' | 'E'))
+                && self.chars[start].is_ascii_digit();
+            if c.is_alphanumeric() || c == '_' || c == '.' || exponent_sign {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn value(&mut self) -> Result<Lua> {
+        self.skip_space();
+        match self.peek() {
+            Some('{') => self.table(),
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                Ok(Lua::Str(self.string(quote)?))
+            }
+            Some('-') => {
+                self.pos += 1;
+                Ok(Lua::Number(format!("-{}", self.word())))
+            }
+            _ => match self.word().as_str() {
+                "" => bail!("Unexpected character at {} of the sidecar", self.pos),
+                "nil" => Ok(Lua::Nil),
+                "true" => Ok(Lua::Bool(true)),
+                "false" => Ok(Lua::Bool(false)),
+                number if number.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+                    Ok(Lua::Number(number.to_string()))
+                }
+                other => bail!("Unexpected `{}` in the sidecar", other),
+            },
+        }
+    }
+
+    fn table(&mut self) -> Result<Lua> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        let mut next_index = 1;
+        loop {
+            if self.eat('}') {
+                return Ok(Lua::Table(fields));
+            }
+            let key = if self.eat('[') {
+                let key = match self.value()? {
+                    Lua::Str(name) => LuaKey::Name(name),
+                    Lua::Number(n) => LuaKey::Index(
+                        n.parse()
+                            .map_err(|_| anyhow::anyhow!("Unsupported table key {}", n))?,
+                    ),
+                    _ => bail!("Unsupported table key in the sidecar"),
+                };
+                self.expect(']')?;
+                self.expect('=')?;
+                key
+            } else {
+                let start = self.pos;
+                let name = self.word();
+                if !name.is_empty() && self.eat('=') && !self.eat('=') {
+                    LuaKey::Name(name)
+                } else {
+                    // A positional value, e.g. `{ "a", "b" }`.
+                    self.pos = start;
+                    next_index += 1;
+                    LuaKey::Index(next_index - 1)
+                }
+            };
+            fields.push((key, self.value()?));
+            if !self.eat(',') && !self.eat(';') {
+                self.expect('}')?;
+                return Ok(Lua::Table(fields));
+            }
+        }
+    }
+
+    /// The rest of a quoted string, with Lua's escapes. Lua strings are bytes, and
+    /// KOReader writes UTF-8 through `\ddd` escapes too.
+    fn string(&mut self, quote: char) -> Result<String> {
+        let mut bytes = Vec::new();
+        loop {
+            let Some(c) = self.peek() else {
+                bail!("Unterminated string in the sidecar");
+            };
+            self.pos += 1;
+            if c == quote {
+                return Ok(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            if c != '\\' {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            let Some(escape) = self.peek() else {
+                bail!("Unterminated string in the sidecar");
+            };
+            self.pos += 1;
+            match escape {
+                'n' | '\n' => bytes.push(b'\n'),
+                't' => bytes.push(b'\t'),
+                'r' => bytes.push(b'\r'),
+                'a' => bytes.push(0x07),
+                'b' => bytes.push(0x08),
+                'f' => bytes.push(0x0c),
+                'v' => bytes.push(0x0b),
+                'z' => {
+                    while self.peek().is_some_and(char::is_whitespace) {
+                        self.pos += 1;
+                    }
+                }
+                'x' => {
+                    let hex: String = self.chars[self.pos..].iter().take(2).collect();
+                    self.pos += hex.chars().count();
+                    bytes.push(u8::from_str_radix(&hex, 16)?);
+                }
+                d if d.is_ascii_digit() => {
+                    let mut value = d.to_digit(10).unwrap_or(0);
+                    for _ in 0..2 {
+                        match self.peek().and_then(|c| c.to_digit(10)) {
+                            Some(digit) => {
+                                value = value * 10 + digit;
+                                self.pos += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                    bytes.push(u8::try_from(value)?);
+                }
+                other => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Writes a value the way KOReader does, four spaces per level.
+fn write_lua(value: &Lua, depth: usize, out: &mut String) {
+    match value {
+        Lua::Nil => out.push_str("nil"),
+        Lua::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Lua::Number(n) => out.push_str(n),
+        Lua::Str(s) => out.push_str(&quote_lua(s)),
+        Lua::Table(fields) if fields.is_empty() => out.push_str("{}"),
+        Lua::Table(fields) => {
+            out.push_str("{\n");
+            let indent = "    ".repeat(depth + 1);
+            for (key, value) in fields {
+                out.push_str(&indent);
+                match key {
+                    LuaKey::Index(i) => out.push_str(&format!("[{}] = ", i)),
+                    LuaKey::Name(name) => out.push_str(&format!("[{}] = ", quote_lua(name))),
+                }
+                write_lua(value, depth + 1, out);
+                out.push_str(",\n");
+            }
+            out.push_str(&"    ".repeat(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn quote_lua(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() && (c as u32) < 256 => {
+                quoted.push_str(&format!("\\{:03}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Where KOReader keeps the sidecar of a book: `book.sdr/metadata.epub.lua` next to
+/// `book.epub`.
+pub fn sidecar_path(book_path: &str) -> PathBuf {
+    let path = Path::new(book_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.sdr", stem))
+        .join(format!("metadata.{}.lua", ext))
+}
+
+/// A highlight read from a sidecar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KoreaderNote {
+    pub text: String,
+    pub note: Option<String>,
+    /// Chapter the highlight's position points into, when it can be told.
+    pub chapter: Option<usize>,
+}
+
+/// The parts of a sidecar tbook uses.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sidecar {
+    pub notes: Vec<KoreaderNote>,
+    /// XPointer of the reading position, for EPUBs.
+    pub xpointer: Option<String>,
+    /// Page of the reading position, for page-based formats.
+    pub page: Option<usize>,
+    /// How far through the book, between 0 and 1.
+    pub percent: Option<f64>,
+}
+
+/// Spine index of an XPointer's `DocFragment`.
+fn xpointer_chapter(xpointer: &str) -> Option<usize> {
+    let rest = xpointer.strip_prefix("/body/DocFragment[")?;
+    rest[..rest.find(']')?]
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)
+}
+
+/// Where a KOReader position points: a spine index from an XPointer, or a page.
+fn position_chapter(position: &Lua) -> Option<usize> {
+    match position {
+        Lua::Str(xpointer) => xpointer_chapter(xpointer),
+        Lua::Number(_) => position
+            .as_number()
+            .map(|page| (page as usize).saturating_sub(1)),
+        table => position_chapter(table.get("page")?),
+    }
+}
+
+fn highlight(fields: &Lua) -> Option<KoreaderNote> {
+    let text = fields.get("text")?.as_str()?.trim();
+    // Page bookmarks have a position but no text.
+    if text.is_empty() || fields.get("pos0").is_none() {
+        return None;
+    }
+    Some(KoreaderNote {
+        text: text.to_string(),
+        note: fields
+            .get("note")
+            .and_then(Lua::as_str)
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(String::from),
+        chapter: fields.get("pos0").and_then(position_chapter),
+    })
+}
+
+/// Reads the highlights and reading position of a sidecar. Besides the `annotations`
+/// list of current KOReader versions, the per-page `highlight` table of older ones is
+/// read too, though not the notes older versions kept apart from it.
+pub fn parse_sidecar(text: &str) -> Result<Sidecar> {
+    let root = parse_lua(text)?;
+    let mut notes: Vec<KoreaderNote> = match root.get("annotations") {
+        Some(annotations) => annotations
+            .fields()
+            .iter()
+            .filter_map(|(_, a)| highlight(a))
+            .collect(),
+        None => root
+            .get("highlight")
+            .map(Lua::fields)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|(_, page)| page.fields())
+            .filter_map(|(_, h)| highlight(h))
+            .collect(),
+    };
+    notes.dedup_by(|a, b| a.text == b.text);
+    Ok(Sidecar {
+        notes,
+        xpointer: root
+            .get("last_xpointer")
+            .and_then(Lua::as_str)
+            .map(String::from),
+        page: root
+            .get("last_page")
+            .and_then(Lua::as_number)
+            .map(|page| page as usize),
+        percent: root.get("percent_finished").and_then(Lua::as_number),
+    })
+}
+
+/// What an import did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KoreaderImport {
+    /// Highlights added to the book.
+    pub imported: usize,
+    /// Highlights skipped because the book already has them.
+    pub duplicates: usize,
+    /// Added highlights whose text wasn't found in the book; they sit at its start.
+    pub unplaced: usize,
+    /// Chapter the reading position moved to, if the sidecar had one.
+    pub position: Option<usize>,
+}
+
+/// Words before `offset` (a [`Locator`] offset) in a chapter's text.
+fn words_before(text: &str, offset: usize) -> usize {
+    let mut chars = 0;
+    text.split_whitespace()
+        .take_while(|word| {
+            chars += word.chars().count() + 1;
+            chars <= offset
+        })
+        .count()
+}
+
+/// The word a fraction of the way through the book.
+fn locator_at_fraction(chapters: &[(usize, String)], fraction: f64) -> Option<Locator> {
+    let total: usize = chapters
+        .iter()
+        .map(|(_, text)| text.split_whitespace().count())
+        .sum();
+    let mut target = (fraction.clamp(0.0, 1.0) * total as f64) as usize;
+    for (chapter, text) in chapters {
+        let words = text.split_whitespace().count();
+        if target < words {
+            let offset = text
+                .split_whitespace()
+                .take(target)
+                .map(|w| w.chars().count() + 1)
+                .sum();
+            return Some(Locator::new(*chapter, offset));
+        }
+        target -= words;
+    }
+    let (chapter, _) = chapters.last()?;
+    Some(Locator::new(*chapter, 0))
+}
+
+/// KOReader's name for a reading position: an XPointer in EPUBs, the page number in
+/// page-based formats.
+pub fn progress_pointer(parser: &mut BookParser, locator: &Locator) -> String {
+    if parser.is_paged() {
+        return (locator.spine + 1).to_string();
+    }
+    library::quote_at(parser, locator, POSITION_WORDS)
+        .and_then(|quote| parser.xpointers(locator.spine, &quote))
+        .map(|(start, _)| start)
+        .unwrap_or_else(|| format!("/body/DocFragment[{}]/body", locator.spine + 1))
+}
+
+/// Where a KOReader position (see [`progress_pointer`]) is in the book, falling back
+/// to `percentage` of the way through when the position doesn't fit it.
+pub fn locator_for_progress(
+    parser: &mut BookParser,
+    progress: &str,
+    percentage: Option<f64>,
+) -> Option<Locator> {
+    let chapters = parser.get_chapter_count();
+    if parser.is_paged() {
+        if let Ok(page) = progress.trim().parse::<usize>() {
+            if (1..=chapters).contains(&page) {
+                return Some(Locator::new(page - 1, 0));
+            }
+        }
+    } else if let Some((chapter, text)) = parser.xpointer_text(progress, POSITION_WORDS) {
+        let offset = library::chapter_text(parser, chapter)
+            .and_then(|chapter_text| locator::find_span(&chapter_text, &text))
+            .map_or(0, |(start, _)| start);
+        return Some(Locator::new(chapter, offset));
+    }
+    locator_at_fraction(&library::chapter_texts(parser), percentage?)
+}
+
+/// KOReader's id for a document in progress sync: the MD5 of 1 KiB samples at offsets
+/// 0, 1 KiB, 4 KiB, 16 KiB and so on up to 1 GiB, stopping at the end of the file.
+pub fn partial_md5(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Md5::new();
+    let mut sample = Vec::with_capacity(1024);
+    for i in -1..=10 {
+        // KOReader computes `1024 << 2i` with LuaJIT, where a shift of -2 gives 0.
+        let offset = if i < 0 { 0 } else { 1024u64 << (2 * i) };
+        file.seek(SeekFrom::Start(offset))?;
+        sample.clear();
+        (&mut file).take(1024).read_to_end(&mut sample)?;
+        if sample.is_empty() {
+            break;
+        }
+        hasher.update(&sample);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Adds the highlights of a sidecar to `book`, tagged `koreader`, and moves the
+/// reading position to KOReader's. Highlights the book already has are skipped.
+pub fn import_sidecar(db: &Db, book: &BookRecord, text: &str) -> Result<KoreaderImport> {
+    let sidecar = parse_sidecar(text)?;
+    let mut report = KoreaderImport::default();
+    // A missing file still takes the notes, just without positions.
+    let mut parser = BookParser::open(&book.path).ok();
+    let chapters = parser
+        .as_mut()
+        .map(library::chapter_texts)
+        .unwrap_or_default();
+    let chapter_text = |chapter: usize| {
+        chapters
+            .iter()
+            .find(|(i, _)| *i == chapter)
+            .map(|(_, text)| text.as_str())
+    };
+
+    let tags = [KOREADER_TAG.to_string()];
+    let kind = AnnotationKind::Highlight.as_str();
+    for note in &sidecar.notes {
+        if db.has_annotation(book.id, kind, &note.text)? {
+            report.duplicates += 1;
+            continue;
+        }
+        // The chapter the highlight's position names first, then the whole book.
+        let hinted = note
+            .chapter
+            .and_then(|c| Some((c, chapter_text(c)?)))
+            .into_iter();
+        let placed = hinted
+            .chain(chapters.iter().map(|(i, text)| (*i, text.as_str())))
+            .find_map(|(chapter, text)| {
+                let (start, end) = locator::find_span(text, &note.text)?;
+                Some((Locator::new(chapter, start), Locator::new(chapter, end)))
+            });
+        if placed.is_none() {
+            report.unplaced += 1;
+        }
+        let chapter = placed.as_ref().map_or(0, |(start, _)| start.spine);
+        let (start, end) = placed
+            .map(|(start, end)| (start.to_string(), end.to_string()))
+            .unzip();
+        let id = db.add_annotation(&NewAnnotation {
+            book_id: book.id,
+            chapter,
+            content: &note.text,
+            note: note.note.as_deref(),
+            kind,
+            start_locator: start.as_deref(),
+            end_locator: end.as_deref(),
+            ..Default::default()
+        })?;
+        db.set_annotation_tags(id, &tags)?;
+        report.imported += 1;
+    }
+
+    let progress = sidecar
+        .xpointer
+        .clone()
+        .or_else(|| sidecar.page.map(|page| page.to_string()))
+        .unwrap_or_default();
+    let position = parser
+        .as_mut()
+        .and_then(|parser| locator_for_progress(parser, &progress, sidecar.percent));
+    if let Some(locator) = position {
+        db.update_progress(
+            &book.path,
+            locator.spine,
+            0,
+            book.lines_read,
+            Some(&locator.to_string()),
+        )?;
+        let words: usize = chapters
+            .iter()
+            .take_while(|(i, _)| *i < locator.spine)
+            .map(|(_, text)| text.split_whitespace().count())
+            .sum();
+        let within = chapter_text(locator.spine).map_or(0, |t| words_before(t, locator.offset));
+        db.set_position_words(book.id, words + within)?;
+        report.position = Some(locator.spine);
+    }
+    Ok(report)
+}
+
+/// Alphanumeric fingerprint of a highlight, so the same text matches across readers.
+fn fingerprint(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// KOReader's local `YYYY-MM-DD HH:MM:SS` for a database (UTC) timestamp.
+fn local_datetime(created_at: Option<&str>) -> String {
+    let utc = created_at
+        .and_then(|t| chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").ok())
+        .map(|t| t.and_utc())
+        .unwrap_or_else(chrono::Utc::now);
+    utc.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn annotation_table(a: &AnnotationRecord, pos0: String, pos1: String) -> Lua {
+    let mut fields = vec![
+        ("chapter", Lua::Str(format!("Chapter {}", a.chapter + 1))),
+        ("color", Lua::Str("yellow".to_string())),
+        (
+            "datetime",
+            Lua::Str(local_datetime(a.created_at.as_deref())),
+        ),
+        ("drawer", Lua::Str("lighten".to_string())),
+    ];
+    if let Some(note) = a.note.as_deref().filter(|n| !n.trim().is_empty()) {
+        fields.push(("note", Lua::Str(note.to_string())));
+    }
+    fields.extend([
+        ("page", Lua::Str(pos0.clone())),
+        ("pos0", Lua::Str(pos0)),
+        ("pos1", Lua::Str(pos1)),
+        ("text", Lua::Str(a.content.clone())),
+    ]);
+    Lua::Table(
+        fields
+            .into_iter()
+            .map(|(name, value)| (LuaKey::Name(name.to_string()), value))
+            .collect(),
+    )
+}
+
+/// What an export did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KoreaderExport {
+    /// The sidecar to write.
+    pub sidecar: String,
+    /// Notes added to the sidecar.
+    pub exported: usize,
+    /// Notes KOReader already had.
+    pub duplicates: usize,
+    /// Notes left out because their text wasn't found in the book.
+    pub unplaced: usize,
+}
+
+/// Merges the notes and reading position of `book` into its sidecar (`existing`, or a
+/// new one). Only EPUBs can be exported, since other formats need page coordinates
+/// tbook doesn't have.
+pub fn export_sidecar(
+    db: &Db,
+    book: &BookRecord,
+    existing: Option<&str>,
+) -> Result<KoreaderExport> {
+    let mut parser = BookParser::open(&book.path)?;
+    if !matches!(parser, BookParser::Epub(_)) {
+        bail!("Only EPUB books can be exported to KOReader");
+    }
+    let mut root = match existing {
+        Some(text) => parse_lua(text)?,
+        None => Lua::Table(Vec::new()),
+    };
+    let mut report = KoreaderExport::default();
+
+    let mut annotations: Vec<Lua> = root
+        .get("annotations")
+        .map(|a| a.fields().iter().map(|(_, value)| value.clone()).collect())
+        .unwrap_or_default();
+    let known: Vec<String> = annotations
+        .iter()
+        .filter_map(|a| a.get("text")?.as_str().map(fingerprint))
+        .collect();
+    for a in db.get_annotations(book.id)? {
+        if known.contains(&fingerprint(&a.content)) {
+            report.duplicates += 1;
+            continue;
+        }
+        let chapter = a.start_locator.as_ref().map_or(a.chapter, |l| l.spine);
+        match parser.xpointers(chapter, &a.content) {
+            Some((pos0, pos1)) => {
+                annotations.push(annotation_table(&a, pos0, pos1));
+                report.exported += 1;
+            }
+            None => report.unplaced += 1,
+        }
+    }
+
+    // KOReader expects its list in reading order.
+    let chapters = library::chapter_texts(&mut parser);
+    let mut keyed: Vec<((usize, usize), Lua)> = annotations
+        .into_iter()
+        .map(|a| {
+            let key = a
+                .get("pos0")
+                .and_then(Lua::as_str)
+                .and_then(|pos0| parser.xpointer_text(pos0, POSITION_WORDS))
+                .and_then(|(chapter, text)| {
+                    let (_, chapter_text) = chapters.iter().find(|(i, _)| *i == chapter)?;
+                    let (offset, _) = locator::find_span(chapter_text, &text)?;
+                    Some((chapter, offset))
+                })
+                .unwrap_or((usize::MAX, 0));
+            (key, a)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    root.set(
+        "annotations",
+        Lua::Table(
+            keyed
+                .into_iter()
+                .enumerate()
+                .map(|(i, (_, a))| (LuaKey::Index(i + 1), a))
+                .collect(),
+        ),
+    );
+
+    let locator = book
+        .locator
+        .clone()
+        .unwrap_or_else(|| Locator::new(book.current_chapter, 0));
+    root.set(
+        "last_xpointer",
+        Lua::Str(progress_pointer(&mut parser, &locator)),
+    );
+    root.set(
+        "percent_finished",
+        Lua::Number(format!("{}", book.progress())),
+    );
+
+    let mut sidecar = format!("-- {}\nreturn ", book.path);
+    write_lua(&root, 0, &mut sidecar);
+    sidecar.push('\n');
+    report.sidecar = sidecar;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIDECAR: &str = r#"-- we can read Lua syntax here!
+return {
+    ["annotations"] = {
+        [1] = {
+            ["chapter"] = "Loomings",
+            ["datetime"] = "2024-03-01 10:00:00",
+            ["drawer"] = "lighten",
+            ["note"] = "the famous one",
+            ["page"] = "/body/DocFragment[2]/body/p[1]/text().0",
+            ["pos0"] = "/body/DocFragment[2]/body/p[1]/text().0",
+            ["pos1"] = "/body/DocFragment[2]/body/p[1]/text().16",
+            ["text"] = "Call me \"Ishmael\".\
+Some years ago",
+        },
+        [2] = {
+            ["datetime"] = "2024-03-01 10:05:00",
+            ["page"] = "/body/DocFragment[3]/body/p[2]/text().0",
+        },
+    },
+    ["cre_dom_version"] = 20240114,
+    ["percent_finished"] = 0.25,
+    ["last_xpointer"] = "/body/DocFragment[2]/body/p[3]/text().4",
+    ["summary"] = {
+        ["status"] = "reading",
+        ["modified"] = "2024-03-01",
+    },
+    ["font_size"] = 22.5,
+    ["gamma"] = -1.5e-2,
+    ["empty"] = {},
+    ["list"] = { "a", 'b\65', false, nil },
+}
+"#;
+
+    #[test]
+    fn reads_highlights_and_position() {
+        let sidecar = parse_sidecar(SIDECAR).unwrap();
+        assert_eq!(
+            sidecar.notes,
+            vec![KoreaderNote {
+                text: "Call me \"Ishmael\".\nSome years ago".to_string(),
+                note: Some("the famous one".to_string()),
+                chapter: Some(1),
+            }]
+        );
+        assert_eq!(
+            sidecar.xpointer.as_deref(),
+            Some("/body/DocFragment[2]/body/p[3]/text().4")
+        );
+        assert_eq!(sidecar.percent, Some(0.25));
+        assert_eq!(sidecar.page, None);
+    }
+
+    #[test]
+    fn older_sidecars_keep_highlights_per_page() {
+        let old = r#"return {
+            ["highlight"] = {
+                [12] = {
+                    [1] = { ["text"] = "Fear is the mind-killer.", ["pos0"] = { ["page"] = 12, ["x"] = 1.5 } },
+                },
+            },
+            ["last_page"] = 14,
+        }"#;
+        let sidecar = parse_sidecar(old).unwrap();
+        assert_eq!(sidecar.notes[0].chapter, Some(11));
+        assert_eq!(sidecar.page, Some(14));
+    }
+
+    #[test]
+    fn settings_survive_a_rewrite() {
+        let root = parse_lua(SIDECAR).unwrap();
+        let mut written = String::from("return ");
+        write_lua(&root, 0, &mut written);
+        assert_eq!(parse_lua(&written).unwrap(), root);
+        assert!(written.contains("    [\"font_size\"] = 22.5,\n"));
+        assert!(written.contains("[\"gamma\"] = -1.5e-2,"));
+        assert!(written.contains("[2] = \"bA\","));
+        assert!(written.contains("\"Call me \\\"Ishmael\\\".\\nSome years ago\""));
+        assert!(parse_lua("return { [1] = }").is_err());
+        assert!(parse_lua("return --[[ truncated").is_err());
+        assert!(parse_lua("return -- x").is_err());
+        assert!(parse_lua("settings = {}").is_err());
+    }
+
+    #[test]
+    fn sidecar_sits_next_to_the_book() {
+        assert_eq!(
+            sidecar_path("/books/Moby Dick.epub"),
+            PathBuf::from("/books/Moby Dick.sdr/metadata.epub.lua")
+        );
+    }
+
+    #[test]
+    fn document_ids_sample_the_file_like_koreader() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small.epub");
+        std::fs::write(&small, b"hello").unwrap();
+        assert_eq!(
+            partial_md5(&small).unwrap(),
+            "5d41402abc4b2a76b9719d911017c592"
+        );
+
+        // 0..1024 and 1024..2048 are sampled; 4096 is past the end.
+        let bytes: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        let large = dir.path().join("large.epub");
+        std::fs::write(&large, &bytes).unwrap();
+        let expected: String = Md5::digest(&bytes[..2048])
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(partial_md5(&large).unwrap(), expected);
+    }
+}
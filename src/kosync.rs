@@ -0,0 +1,129 @@
+//! Client for KOReader's progress sync server (kosync), so the reading position follows
+//! a book between tbook and KOReader. Books are identified by KOReader's partial MD5 of
+//! the file, and positions use its XPointers, so both readers understand each other.
+
+use anyhow::{Context, Result, bail};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const ACCEPT: &str = "application/vnd.koreader.v1+json";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Server and account from `tbook.toml`. Sync is off until a server and user are set.
+#[derive(Clone, Default)]
+pub struct KosyncSettings {
+    pub server: Option<String>,
+    pub username: String,
+    /// MD5 of the password, which is what the server checks.
+    pub userkey: String,
+    /// Name KOReader shows for positions pushed from here.
+    pub device: String,
+    pub device_id: String,
+}
+
+impl KosyncSettings {
+    pub fn new(server: Option<&str>, username: &str, password: &str, device: &str) -> Self {
+        Self {
+            server: server
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+            username: username.to_string(),
+            userkey: md5_hex(password),
+            device: device.to_string(),
+            // KOReader only compares ids, so a stable one per device name will do.
+            device_id: md5_hex(&format!("tbook:{}", device)).to_uppercase(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.server.is_some() && !self.username.is_empty()
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> Result<reqwest::RequestBuilder> {
+        let Some(ref server) = self.server else {
+            bail!("Progress sync is not configured. Set kosync_server in tbook.toml.");
+        };
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("tbook/", env!("CARGO_PKG_VERSION")))
+            .timeout(TIMEOUT)
+            .build()?;
+        Ok(client
+            .request(method, format!("{}{}", server.trim_end_matches('/'), path))
+            .header("Accept", ACCEPT)
+            .header("x-auth-user", &self.username)
+            .header("x-auth-key", &self.userkey))
+    }
+}
+
+fn md5_hex(text: &str) -> String {
+    Md5::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A reading position as the server keeps it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub document: String,
+    /// XPointer in EPUBs, page number in page-based formats.
+    pub progress: String,
+    /// How far through the book, between 0 and 1.
+    pub percentage: f64,
+    pub device: String,
+    pub device_id: String,
+}
+
+/// The server answers `{}` for documents it has no position for.
+#[derive(Deserialize)]
+struct Stored {
+    progress: Option<String>,
+    percentage: Option<f64>,
+    #[serde(default)]
+    device: String,
+    #[serde(default)]
+    device_id: String,
+}
+
+fn check(status: reqwest::StatusCode) -> Result<()> {
+    match status.as_u16() {
+        200..=299 => Ok(()),
+        401 | 403 => bail!("the sync server did not accept kosync_username/kosync_password"),
+        code => bail!("the sync server answered {}", code),
+    }
+}
+
+pub async fn push(settings: &KosyncSettings, position: &Position) -> Result<()> {
+    let response = settings
+        .request(reqwest::Method::PUT, "/syncs/progress")?
+        .json(position)
+        .send()
+        .await
+        .context("Cannot reach the sync server")?;
+    check(response.status())
+}
+
+pub async fn pull(settings: &KosyncSettings, document: &str) -> Result<Option<Position>> {
+    let response = settings
+        .request(
+            reqwest::Method::GET,
+            &format!("/syncs/progress/{}", document),
+        )?
+        .send()
+        .await
+        .context("Cannot reach the sync server")?;
+    check(response.status())?;
+    let stored: Stored = response.json().await?;
+    Ok(match (stored.progress, stored.percentage) {
+        (Some(progress), Some(percentage)) => Some(Position {
+            document: document.to_string(),
+            progress,
+            percentage,
+            device: stored.device,
+            device_id: stored.device_id,
+        }),
+        _ => None,
+    })
+}
@@ -0,0 +1,37 @@
+//! Book-handling core of tbook: format parsers, the library database, position
+//! locators, library scanning/import, Kindle clipping and KOReader sidecar import,
+//! note export, reading-group position tokens, weekly digests, vocabulary review
+//! scheduling, one search across books, notes and words, and the lock that keeps two
+//! sessions from sharing a library. The `tbook` binary is a terminal UI built on top
+//! of this crate; other frontends and scripts can use it directly.
+//!
+//! ```no_run
+//! use tbook::{db::Db, library, parser::BookParser};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let db = Db::new("tbook.db")?;
+//! library::import_book(&db, "moby-dick.epub")?;
+//! for book in db.get_books()? {
+//!     let mut parser = BookParser::open(&book.path)?;
+//!     println!("{} ({} chapters)", book.title, parser.get_chapter_count());
+//!     let _first_chapter = parser.get_chapter_content(0)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod clippings;
+pub mod config;
+pub mod db;
+mod deps;
+pub mod digest;
+pub mod export;
+pub mod group;
+pub mod instance;
+pub mod koreader;
+pub mod library;
+pub mod locator;
+pub mod omnibox;
+pub mod parser;
+pub mod review;
+pub mod search;
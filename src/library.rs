@@ -0,0 +1,561 @@
+use crate::config::ImportRules;
+use crate::db::{BookRecord, Db};
+use crate::locator::{self, Locator};
+use crate::parser::{BookParser, PageContent};
+use anyhow::{Result, bail};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Extensions picked up by folder scans. HTML can be imported directly but isn't
+/// scanned for, since most HTML files on disk aren't books.
+const SCAN_EXTENSIONS: &[&str] = &["epub", "pdf", "djvu", "djv"];
+
+/// Extensions `import_book` accepts.
+const IMPORT_EXTENSIONS: &[&str] = &["epub", "pdf", "djvu", "djv", "html", "htm"];
+
+/// Where a book is in the to-read / reading / finished cycle, as stored in `books.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadingStatus {
+    ToRead,
+    Reading,
+    Finished,
+    Abandoned,
+}
+
+impl ReadingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadingStatus::ToRead => "to-read",
+            ReadingStatus::Reading => "reading",
+            ReadingStatus::Finished => "finished",
+            ReadingStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReadingStatus::ToRead => "To Read",
+            ReadingStatus::Reading => "Reading",
+            ReadingStatus::Finished => "Finished",
+            ReadingStatus::Abandoned => "Abandoned",
+        }
+    }
+
+    pub fn badge(&self) -> &'static str {
+        match self {
+            ReadingStatus::ToRead => "[ ]",
+            ReadingStatus::Reading => "[~]",
+            ReadingStatus::Finished => "[✓]",
+            ReadingStatus::Abandoned => "[x]",
+        }
+    }
+
+    // Infallible: unknown values fall back to the default, unlike `FromStr`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> ReadingStatus {
+        match value {
+            "reading" => ReadingStatus::Reading,
+            "finished" => ReadingStatus::Finished,
+            "abandoned" => ReadingStatus::Abandoned,
+            _ => ReadingStatus::ToRead,
+        }
+    }
+
+    pub fn next(&self) -> ReadingStatus {
+        match self {
+            ReadingStatus::ToRead => ReadingStatus::Reading,
+            ReadingStatus::Reading => ReadingStatus::Finished,
+            ReadingStatus::Finished => ReadingStatus::Abandoned,
+            ReadingStatus::Abandoned => ReadingStatus::ToRead,
+        }
+    }
+}
+
+/// What an annotation is for, as stored in `annotations.kind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationKind {
+    Highlight,
+    Question,
+    Summary,
+}
+
+impl AnnotationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnotationKind::Highlight => "highlight",
+            AnnotationKind::Question => "question",
+            AnnotationKind::Summary => "summary",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnnotationKind::Highlight => "H",
+            AnnotationKind::Question => "Q",
+            AnnotationKind::Summary => "S",
+        }
+    }
+
+    /// The built-in kind stored as `value`, if it is one.
+    pub fn parse(value: &str) -> Option<AnnotationKind> {
+        match value {
+            "highlight" => Some(AnnotationKind::Highlight),
+            "question" => Some(AnnotationKind::Question),
+            "summary" => Some(AnnotationKind::Summary),
+            _ => None,
+        }
+    }
+
+    // Infallible: unknown values fall back to the default, unlike `FromStr`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> AnnotationKind {
+        AnnotationKind::parse(value).unwrap_or(AnnotationKind::Highlight)
+    }
+}
+
+/// Short list label for a stored kind: H/Q/S, or the initial of a user-defined kind.
+pub fn kind_label(kind: &str) -> String {
+    match AnnotationKind::parse(kind) {
+        Some(kind) => kind.label().to_string(),
+        None => kind
+            .chars()
+            .next()
+            .map(|c| c.to_uppercase().collect())
+            .unwrap_or_else(|| AnnotationKind::Highlight.label().to_string()),
+    }
+}
+
+/// Splits user input like `#history, Rome war` into lowercase tags, sorted and deduplicated.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|t| t.trim_start_matches('#').to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Books found by a scan, with their sizes and how many files the skip rules dropped.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub paths: Vec<PathBuf>,
+    pub sizes: HashMap<PathBuf, u64>,
+    pub skipped: usize,
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    extensions.contains(&ext.as_str())
+}
+
+/// Whether `import_book` knows how to open this file.
+pub fn is_importable(path: &Path) -> bool {
+    has_extension(path, IMPORT_EXTENSIONS)
+}
+
+/// Walks `path` for books. Blocking; callers in async code should use `spawn_blocking`.
+pub fn scan_for_books(path: &str, rules: &ImportRules) -> ScanResult {
+    let mut result = ScanResult::default();
+    let root = Path::new(path);
+
+    // A single file was asked for explicitly, so the skip rules don't apply.
+    if root.is_file() {
+        if has_extension(root, SCAN_EXTENSIONS) {
+            let size = root.metadata().map(|m| m.len()).unwrap_or(0);
+            result.sizes.insert(root.to_path_buf(), size);
+            result.paths.push(root.to_path_buf());
+        }
+        return result;
+    }
+
+    for entry in WalkDir::new(path)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let f_path = entry.path();
+        if f_path.is_file() && has_extension(f_path, SCAN_EXTENSIONS) {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if rules.skips(f_path, size) {
+                result.skipped += 1;
+                continue;
+            }
+            result.sizes.insert(f_path.to_path_buf(), size);
+            result.paths.push(f_path.to_path_buf());
+        }
+    }
+    result.paths.sort();
+    result.paths.dedup();
+    result
+}
+
+/// Adds an already opened book to the library. Re-adding a known path only refreshes
+/// the stored fingerprint.
+pub fn register_book(db: &Db, path: &str, parser: &BookParser) -> Result<()> {
+    let (title, author) = parser.get_metadata();
+    db.add_book(
+        &title,
+        &author,
+        path,
+        parser.get_chapter_count(),
+        0,
+        parser.get_page_count(),
+    )?;
+    let (size, hash) = fingerprint(Path::new(path))?;
+    db.set_book_fingerprint(path, size, &hash)?;
+    Ok(())
+}
+
+/// Size and hex SHA-256 of a file's contents.
+pub fn fingerprint(path: &Path) -> std::io::Result<(u64, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((size, hash))
+}
+
+/// Stores fingerprints for books imported before they were recorded. Books whose file
+/// is already gone are left alone. Returns how many were filled in.
+pub fn backfill_fingerprints(db: &Db) -> Result<usize> {
+    let mut filled = 0;
+    for (_, path) in db.get_unfingerprinted_books()? {
+        if let Ok((size, hash)) = fingerprint(Path::new(&path)) {
+            db.set_book_fingerprint(&path, size, &hash)?;
+            filled += 1;
+        }
+    }
+    Ok(filled)
+}
+
+/// A book whose file is missing, and the file under the search root with the same contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relink {
+    pub book_id: i32,
+    pub title: String,
+    pub old_path: String,
+    pub new_path: String,
+}
+
+/// Looks under `root` for the files of books whose recorded path no longer exists.
+/// Only files with a matching size are hashed, so large libraries stay cheap to search.
+pub fn find_moved_books(db: &Db, root: &Path) -> Result<Vec<Relink>> {
+    let books = db.get_books()?;
+    let known: HashSet<&str> = books.iter().map(|b| b.path.as_str()).collect();
+    let mut missing: Vec<_> = books
+        .iter()
+        .filter(|b| b.content_hash.is_some() && !Path::new(&b.path).exists())
+        .collect();
+    if missing.is_empty() || !root.is_dir() {
+        return Ok(Vec::new());
+    }
+    let sizes: HashSet<u64> = missing.iter().filter_map(|b| b.file_size).collect();
+
+    let mut relinks = Vec::new();
+    for entry in WalkDir::new(root)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if !path.is_file() || !is_importable(path) {
+            continue;
+        }
+        let path_str = path.to_string_lossy();
+        if known.contains(path_str.as_ref()) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if !sizes.contains(&size) {
+            continue;
+        }
+        let Ok((_, hash)) = fingerprint(path) else {
+            continue;
+        };
+        if let Some(pos) = missing
+            .iter()
+            .position(|b| b.content_hash.as_deref() == Some(hash.as_str()))
+        {
+            let book = missing.remove(pos);
+            relinks.push(Relink {
+                book_id: book.id,
+                title: book.title.clone(),
+                old_path: book.path.clone(),
+                new_path: path_str.into_owned(),
+            });
+            if missing.is_empty() {
+                break;
+            }
+        }
+    }
+    Ok(relinks)
+}
+
+/// Points a book at its file's new location and refreshes the stored fingerprint.
+pub fn relink_book(db: &Db, book_id: i32, new_path: &str) -> Result<()> {
+    if !Path::new(new_path).is_file() {
+        bail!("No file at {}", new_path);
+    }
+    if let Some(other) = db.get_books()?.into_iter().find(|b| b.path == new_path) {
+        if other.id != book_id {
+            bail!("{} is already in the library as book {}", new_path, other.id);
+        }
+    }
+    db.relink_book(book_id, new_path)?;
+    let (size, hash) = fingerprint(Path::new(new_path))?;
+    db.set_book_fingerprint(new_path, size, &hash)?;
+    Ok(())
+}
+
+/// Opens the file at `path` and adds it to the library.
+pub fn import_book(db: &Db, path: &str) -> Result<()> {
+    if !is_importable(Path::new(path)) {
+        bail!("Unsupported file type: {}", path);
+    }
+    let parser = BookParser::open(path)?;
+    register_book(db, path, &parser)
+}
+
+/// Plain text of every non-empty chapter, as (chapter index, text).
+pub fn chapter_texts(parser: &mut BookParser) -> Vec<(usize, String)> {
+    (0..parser.get_chapter_count())
+        .filter_map(|i| Some((i, chapter_text(parser, i)?)))
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect()
+}
+
+/// Plain text of one chapter, without images and captions.
+pub fn chapter_text(parser: &mut BookParser, chapter: usize) -> Option<String> {
+    let text = parser
+        .get_chapter_content(chapter)
+        .ok()?
+        .into_iter()
+        .filter_map(|c| match c {
+            PageContent::Text(t) => Some(t),
+            PageContent::Image(_) | PageContent::Caption(_) => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(text)
+}
+
+/// Up to `count` words of chapter text starting at `locator`, joined by single spaces.
+pub fn quote_at(parser: &mut BookParser, locator: &Locator, count: usize) -> Option<String> {
+    let text = chapter_text(parser, locator.spine)?;
+    // Locator offsets count characters with whitespace collapsed to single spaces.
+    let mut offset = 0;
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .skip_while(|token| {
+            let next = offset + token.chars().count() + 1;
+            let before = next <= locator.offset;
+            offset = next;
+            before
+        })
+        .take(count)
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// The sentence containing `words[index]`, going by the punctuation that ends the
+/// words around it. Very long sentences are cut to a few dozen words either side.
+pub fn sentence_around(words: &[&str], index: usize) -> String {
+    const MAX_SIDE: usize = 30;
+    if index >= words.len() {
+        return String::new();
+    }
+    let ends_sentence = |word: &str| {
+        word.trim_end_matches(['"', '\'', '”', '’', ')', ']'])
+            .ends_with(['.', '!', '?', '…'])
+    };
+    let mut start = index;
+    while start > 0 && index - start < MAX_SIDE && !ends_sentence(words[start - 1]) {
+        start -= 1;
+    }
+    let mut end = index;
+    while end + 1 < words.len() && end - index < MAX_SIDE && !ends_sentence(words[end]) {
+        end += 1;
+    }
+    words[start..=end].join(" ")
+}
+
+/// Extracts the text of every chapter, stores it in the full-text index, and records
+/// the book's word and line counts.
+pub fn index_book(db: &Db, book_id: i32, path: &str) -> Result<()> {
+    let mut parser = BookParser::open(path)?;
+    store_index(db, book_id, &chapter_texts(&mut parser))
+}
+
+fn store_index(db: &Db, book_id: i32, chapters: &[(usize, String)]) -> Result<()> {
+    db.index_book_chapters(book_id, chapters)?;
+    let words: Vec<(usize, usize)> = chapters
+        .iter()
+        .map(|(i, text)| (*i, text.split_whitespace().count()))
+        .collect();
+    let lines = chapters
+        .iter()
+        .map(|(_, text)| text.lines().filter(|l| !l.trim().is_empty()).count())
+        .sum();
+    db.set_word_counts(book_id, &words, lines)?;
+    Ok(())
+}
+
+/// Tag put on notes whose text can't be found again after their book's file changed.
+pub const UNANCHORED_TAG: &str = "unanchored";
+
+/// What re-anchoring the notes of a changed book did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reanchor {
+    /// Notes whose text was found somewhere else.
+    pub moved: usize,
+    /// Notes whose text is still where it was.
+    pub kept: usize,
+    /// Notes whose text is gone; they are tagged `unanchored`.
+    pub lost: usize,
+}
+
+/// Whether the file of a book differs from the one its positions were recorded
+/// against. Books without a fingerprint, or whose file can't be read, count as
+/// unchanged.
+pub fn has_changed(book: &BookRecord) -> bool {
+    let Some(ref recorded) = book.content_hash else {
+        return false;
+    };
+    fingerprint(Path::new(&book.path))
+        .is_ok_and(|(size, hash)| Some(size) != book.file_size || &hash != recorded)
+}
+
+/// Brings a book whose file changed up to date: finds every note again by its text (the
+/// match nearest its old place), re-indexes the book, and records its new fingerprint.
+/// Notes whose text is gone keep their old position and are tagged `unanchored`.
+pub fn reanchor_book(db: &Db, book: &BookRecord) -> Result<Reanchor> {
+    let mut parser = BookParser::open(&book.path)?;
+    let chapters = chapter_texts(&mut parser);
+    let mut report = Reanchor::default();
+    for a in db.get_annotations(book.id)? {
+        let old = a
+            .start_locator
+            .clone()
+            .unwrap_or_else(|| Locator::new(a.chapter, 0));
+        // The note's own chapter first, then the rest of the book in order.
+        let found = chapters
+            .iter()
+            .filter(|(i, _)| *i == old.spine)
+            .chain(chapters.iter().filter(|(i, _)| *i != old.spine))
+            .find_map(|(chapter, text)| {
+                let spans = locator::find_spans(text, &a.content);
+                let nearest = spans
+                    .into_iter()
+                    .min_by_key(|(start, _)| start.abs_diff(old.offset))?;
+                Some((*chapter, nearest))
+            });
+        let mut tags = a.tags.clone();
+        match found {
+            Some((chapter, (start, end))) => {
+                let start = Locator::new(chapter, start);
+                if a.start_locator.as_ref().map(|l| (l.spine, l.offset))
+                    == Some((start.spine, start.offset))
+                {
+                    report.kept += 1;
+                } else {
+                    let end = Locator::new(chapter, end);
+                    db.set_annotation_position(
+                        a.id,
+                        chapter,
+                        &start.to_string(),
+                        &end.to_string(),
+                    )?;
+                    report.moved += 1;
+                }
+                tags.retain(|t| t != UNANCHORED_TAG);
+            }
+            None => {
+                report.lost += 1;
+                if !tags.iter().any(|t| t == UNANCHORED_TAG) {
+                    tags.push(UNANCHORED_TAG.to_string());
+                    tags.sort();
+                }
+            }
+        }
+        if tags != a.tags {
+            db.set_annotation_tags(a.id, &tags)?;
+        }
+    }
+
+    // Keep the reading position inside the book if chapters were removed.
+    let count = parser.get_chapter_count();
+    db.set_chapter_count(book.id, count, parser.get_page_count())?;
+    if count > 0 && book.current_chapter >= count {
+        db.update_progress(&book.path, count - 1, 0, book.lines_read, None)?;
+    }
+    store_index(db, book.id, &chapters)?;
+    let (size, hash) = fingerprint(Path::new(&book.path))?;
+    db.set_book_fingerprint(&book.path, size, &hash)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_status_round_trips_and_cycles() {
+        let mut status = ReadingStatus::ToRead;
+        for _ in 0..4 {
+            assert_eq!(ReadingStatus::from_str(status.as_str()), status);
+            status = status.next();
+        }
+        assert_eq!(status, ReadingStatus::ToRead);
+        assert_eq!(ReadingStatus::from_str("bogus"), ReadingStatus::ToRead);
+    }
+
+    #[test]
+    fn annotation_kind_defaults_to_highlight() {
+        assert_eq!(
+            AnnotationKind::from_str("question"),
+            AnnotationKind::Question
+        );
+        assert_eq!(AnnotationKind::from_str(""), AnnotationKind::Highlight);
+    }
+
+    #[test]
+    fn sentences_end_at_closing_punctuation() {
+        let words: Vec<&str> = "He left. \"Is it ephemeral?\" she asked, and smiled. Then"
+            .split_whitespace()
+            .collect();
+        assert_eq!(sentence_around(&words, 4), "\"Is it ephemeral?\"");
+        assert_eq!(sentence_around(&words, 6), "she asked, and smiled.");
+        assert_eq!(sentence_around(&words, 0), "He left.");
+        assert_eq!(sentence_around(&words, 9), "Then");
+        assert_eq!(sentence_around(&words, 10), "");
+    }
+
+    #[test]
+    fn import_rejects_unknown_extensions() {
+        let db = Db::new(":memory:").unwrap();
+        assert!(import_book(&db, "notes.txt").is_err());
+        assert!(db.get_books().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+/// Cap on the text sent to the summarizer so huge chapters don't blow up prompts.
+const MAX_INPUT_CHARS: usize = 24_000;
+
+const PROMPT: &str = "Summarize the following reading material in a few concise paragraphs. \
+Focus on the key ideas and arguments.";
+
+/// Opt-in summarization backend. A local command takes precedence over an HTTP endpoint.
+#[derive(Clone, Default)]
+pub struct LlmSettings {
+    /// Shell command that reads the prompt on stdin and prints the summary.
+    pub command: Option<String>,
+    /// OpenAI-compatible chat completions URL.
+    pub endpoint: Option<String>,
+    pub model: Option<String>,
+}
+
+impl LlmSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.command.as_deref().is_some_and(|c| !c.trim().is_empty())
+            || self.endpoint.as_deref().is_some_and(|e| !e.trim().is_empty())
+    }
+}
+
+pub async fn summarize(settings: LlmSettings, text: String) -> Result<String> {
+    let input: String = text.chars().take(MAX_INPUT_CHARS).collect();
+
+    if let Some(command) = settings.command.filter(|c| !c.trim().is_empty()) {
+        return summarize_with_command(&command, &input).await;
+    }
+    if let Some(endpoint) = settings.endpoint.filter(|e| !e.trim().is_empty()) {
+        let model = settings.model.unwrap_or_else(|| "default".to_string());
+        return summarize_with_endpoint(&endpoint, &model, &input).await;
+    }
+    Err(anyhow::anyhow!(
+        "Summarization is not configured. Set llm_command or llm_endpoint in tbook.toml."
+    ))
+}
+
+async fn summarize_with_command(command: &str, input: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start llm_command")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(format!("{}\n\n{}", PROMPT, input).as_bytes())
+            .await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("llm_command failed: {}", stderr.trim()));
+    }
+    let summary = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if summary.is_empty() {
+        return Err(anyhow::anyhow!("llm_command returned no output"));
+    }
+    Ok(summary)
+}
+
+async fn summarize_with_endpoint(endpoint: &str, model: &str, input: &str) -> Result<String> {
+    let body = serde_json::json!({
+        "model": model,
+        "messages": [
+            { "role": "system", "content": PROMPT },
+            { "role": "user", "content": input },
+        ],
+    });
+
+    let mut req = reqwest::Client::new().post(endpoint).json(&body);
+    if let Ok(key) = std::env::var("TBOOK_LLM_API_KEY") {
+        req = req.bearer_auth(key);
+    }
+    let json: serde_json::Value = req.send().await?.error_for_status()?.json().await?;
+
+    json.pointer("/choices/0/message/content")
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Unexpected response from llm_endpoint"))
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+/// A position in a book that survives re-rendering, loosely modelled on EPUB CFI:
+/// the spine item (chapter), an element path inside it, and a character offset.
+///
+/// The offset counts characters of the chapter text with whitespace collapsed to single
+/// spaces, so it does not depend on line wrapping or layout. The element path is a
+/// best-effort hint (CFI-style even steps from the document root) and may be empty for
+/// formats without a DOM.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Locator {
+    pub spine: usize,
+    pub path: Vec<usize>,
+    pub offset: usize,
+}
+
+impl Locator {
+    pub fn new(spine: usize, offset: usize) -> Self {
+        Self {
+            spine,
+            path: Vec::new(),
+            offset,
+        }
+    }
+
+    /// Builds a locator from a rendered (line, word) position.
+    pub fn from_position(spine: usize, lines: &[&str], line: usize, word: usize) -> Self {
+        let mut offset = 0;
+        for (i, text) in lines.iter().enumerate() {
+            for (w, token) in text.split_whitespace().enumerate() {
+                if i > line || (i == line && w >= word) {
+                    return Self::new(spine, offset);
+                }
+                offset += token.chars().count() + 1;
+            }
+        }
+        Self::new(spine, offset.saturating_sub(1))
+    }
+
+    /// Maps the offset back onto rendered lines, returning (line, word).
+    /// Offsets past the end land on the last word.
+    pub fn resolve(&self, lines: &[&str]) -> (usize, usize) {
+        let mut offset = 0;
+        let mut last = (0, 0);
+        for (i, text) in lines.iter().enumerate() {
+            for (w, token) in text.split_whitespace().enumerate() {
+                let next = offset + token.chars().count() + 1;
+                if self.offset < next {
+                    return (i, w);
+                }
+                offset = next;
+                last = (i, w);
+            }
+        }
+        last
+    }
+
+    /// Serialized form stored in the database, e.g. `3!/4/2/6:120`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (spine, rest) = value.split_once('!')?;
+        let (path, offset) = rest.rsplit_once(':')?;
+        let path = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().ok())
+            .collect::<Option<Vec<usize>>>()?;
+        Some(Self {
+            spine: spine.parse().ok()?,
+            path,
+            offset: offset.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Locator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}!", self.spine)?;
+        for step in &self.path {
+            write!(f, "/{}", step)?;
+        }
+        write!(f, ":{}", self.offset)
+    }
+}
+
+/// Offset (in the collapsed-whitespace sense used by [`Locator`]) of the first
+/// case-insensitive occurrence of `needle` in `text`.
+pub fn find_offset(text: &str, needle: &str) -> Option<usize> {
+    let haystack = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let needle = needle
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    if needle.is_empty() {
+        return None;
+    }
+    let byte_pos = haystack.find(&needle)?;
+    let char_pos = haystack[..byte_pos].chars().count();
+    // Snap to the start of the word containing the match.
+    let word_start = haystack[..byte_pos]
+        .rfind(' ')
+        .map_or(0, |p| haystack[..=p].chars().count());
+    Some(word_start.min(char_pos))
+}
+
+/// Like [`find_offset`], but matches on letters and digits only, so markup and
+/// punctuation in either text don't get in the way. Returns the offsets of the words
+/// holding the first and last matched characters.
+pub fn find_span(text: &str, needle: &str) -> Option<(usize, usize)> {
+    find_spans(text, needle).into_iter().next()
+}
+
+/// Every match of [`find_span`], in order.
+pub fn find_spans(text: &str, needle: &str) -> Vec<(usize, usize)> {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Each kept character with the offset of the word it belongs to.
+    let mut chars = Vec::new();
+    let mut word_start = 0;
+    for (i, c) in collapsed.chars().enumerate() {
+        if c == ' ' {
+            word_start = i + 1;
+        } else if c.is_alphanumeric() {
+            chars.extend(c.to_lowercase().map(|l| (l, word_start)));
+        }
+    }
+    let needle: Vec<char> = needle
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    chars
+        .windows(needle.len())
+        .filter(|w| w.iter().map(|(c, _)| *c).eq(needle.iter().copied()))
+        .map(|w| (w[0].1, w[w.len() - 1].1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_string_form() {
+        let locator = Locator {
+            spine: 3,
+            path: vec![4, 2, 6],
+            offset: 120,
+        };
+        assert_eq!(locator.to_string(), "3!/4/2/6:120");
+        assert_eq!(Locator::parse("3!/4/2/6:120"), Some(locator));
+        assert_eq!(Locator::parse("2!:7"), Some(Locator::new(2, 7)));
+        assert_eq!(Locator::parse("garbage"), None);
+    }
+
+    #[test]
+    fn survives_rewrapping() {
+        let narrow = ["The quick brown", "fox jumps", "", "over the dog"];
+        let wide = ["The quick brown fox jumps", "over the dog"];
+        let locator = Locator::from_position(0, &narrow, 3, 1);
+        assert_eq!(locator.resolve(&narrow), (3, 1));
+        assert_eq!(locator.resolve(&wide), (1, 1));
+    }
+
+    #[test]
+    fn find_offset_snaps_to_word_start() {
+        let text = "Call me\n  Ishmael. Some years ago";
+        let offset = find_offset(text, "MAEL").unwrap();
+        assert_eq!(
+            Locator::new(0, offset).resolve(&["Call me Ishmael. Some"]),
+            (0, 2)
+        );
+        assert_eq!(find_offset(text, "whale"), None);
+    }
+
+    #[test]
+    fn find_span_ignores_markup() {
+        let text = "Call me **Ishmael**. Some\nyears ago";
+        assert_eq!(find_span(text, "me, Ishmael! Some"), Some((5, 21)));
+        assert_eq!(find_span(text, "..."), None);
+        assert_eq!(find_span(text, "whale"), None);
+        assert_eq!(find_spans("a cat, a Cat", "cat"), vec![(2, 2), (9, 9)]);
+    }
+}
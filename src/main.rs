@@ -1,15 +1,19 @@
 mod app;
-mod config;
-mod db;
-mod deps;
-mod parser;
+mod cli;
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod common;
+mod dictionary;
+mod input;
+mod kosync;
+mod llm;
 mod ui;
 
 use anyhow::Result;
-use app::{App, AppView};
+use app::{App, AppView, ColorDepth};
 use config::AppConfig;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{
         EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode, window_size,
@@ -18,13 +22,60 @@ use crossterm::{
 use ratatui::{Terminal, backend::CrosstermBackend};
 use ratatui_image::picker::{Picker, ProtocolType};
 use std::{io, time::{Duration, Instant}};
+use tbook::{
+    clippings, config, db, export, group, instance, koreader, library, locator, omnibox, parser,
+    review, search,
+};
+
+/// How long the loop waits for input before redrawing.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Light mode redraws about once a second when idle, enough for the pomodoro clock.
+const LIGHT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Pause between books while the background indexer works through the library.
+const INDEX_PAUSE: Duration = Duration::from_millis(200);
+/// While reading, the position goes to the sync server at most this often; leaving the
+/// reader or quitting sends it straight away.
+const SYNC_PUSH_INTERVAL: Duration = Duration::from_secs(30);
+/// How long quitting waits for the last position to reach the sync server.
+const SYNC_QUIT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How often the loop looks at the library's lock to see whether another session took
+/// it over.
+const LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let db_path = match take_flag_value(&mut args, "--db")? {
+        Some(path) => std::path::PathBuf::from(app::expand_path(&path)),
+        None => {
+            let path = config::default_db_path();
+            migrate_legacy_file(std::path::Path::new("tbook.db"), &path);
+            path
+        }
+    };
+    migrate_legacy_file(std::path::Path::new("tbook.toml"), &config::config_path());
+    if let Some(parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let light = take_flag(&mut args, "--light");
+    let read_only = take_flag(&mut args, "--read-only");
+    let mut start_view = take_flag_value(&mut args, "--view")?;
+    if args.len() == 2 && args[1] == "vocab" {
+        start_view = Some(args.remove(1));
+    }
+
     let config = AppConfig::load().unwrap_or_default();
-    let mut app = App::new("tbook.db")?;
+    let mut app = App::new(&db_path.to_string_lossy())?;
     app.apply_config(&config);
+    if light {
+        app.light_mode = true;
+    }
+    app.color_depth = config
+        .color_depth
+        .as_deref()
+        .and_then(ColorDepth::from_str)
+        .unwrap_or_else(detect_color_depth);
 
     if args.len() > 2 && args[1] == "add" {
         let path = &args[2];
@@ -32,6 +83,124 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    if args.len() > 2 && args[1] == "add-url" {
+        let path = App::fetch_article(args[2].clone()).await?;
+        let path = path.to_string_lossy().to_string();
+        add_book_to_db(&mut app, &path)?;
+        println!("Saved article to {}", path);
+        return Ok(());
+    }
+
+    if args.len() > 2 && args[1] == "remove" {
+        let id: i32 = args[2]
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid book id: {}", args[2]))?;
+        if app.db.remove_book(id)? {
+            println!("Removed book {}", id);
+        } else {
+            println!("No book with id {}", id);
+        }
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "backup" {
+        cli::backup(&app.db, args.get(2).map(String::as_str))?;
+        return Ok(());
+    }
+
+    if args.len() > 2 && args[1] == "restore" {
+        let assume_yes = args[3..].iter().any(|a| a == "--yes" || a == "-y");
+        cli::restore(&mut app.db, &args[2], assume_yes)?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "relink" {
+        let assume_yes = args.iter().any(|a| a == "--yes" || a == "-y");
+        let rest: Vec<&String> = args[2..]
+            .iter()
+            .filter(|a| *a != "--yes" && *a != "-y")
+            .collect();
+        match rest.as_slice() {
+            [id, path] => cli::relink_one(&app.db, id, path)?,
+            _ => {
+                let root = rest.first().map(|d| d.as_str()).unwrap_or(&config.library_path);
+                let root = app::expand_path(root);
+                cli::relink_moved(&app.db, std::path::Path::new(&root), assume_yes)?;
+            }
+        }
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "handoff" {
+        let out = take_flag_value(&mut args, "--out")?;
+        cli::handoff(&app.db, args.get(2).map(String::as_str), out.as_deref())?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "export" {
+        let format = take_flag_value(&mut args, "--format")?;
+        let out = take_flag_value(&mut args, "--out")?;
+        let mode = match (take_flag(&mut args, "--append"), take_flag(&mut args, "--timestamped")) {
+            (true, true) => anyhow::bail!("Use only one of --append and --timestamped"),
+            (true, false) => Some(export::ExportMode::Append),
+            (false, true) => Some(export::ExportMode::Timestamped),
+            (false, false) => None,
+        };
+        cli::export(
+            &app.db,
+            args.get(2).map(String::as_str),
+            format.as_deref(),
+            out.as_deref(),
+            mode,
+            &app.export_dir,
+            &app.markdown_markers,
+        )?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "digest" {
+        let out = take_flag_value(&mut args, "--out")?;
+        // A week is the only span so far; the flag keeps scripts readable.
+        take_flag(&mut args, "--week");
+        cli::digest(&app.db, out.as_deref())?;
+        return Ok(());
+    }
+
+    if args.len() > 2 && args[1] == "import-clippings" {
+        cli::import_clippings(&app.db, &app::expand_path(&args[2]))?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && (args[1] == "koreader-import" || args[1] == "koreader-export") {
+        let sidecar = take_flag_value(&mut args, "--sidecar")?;
+        let book_id = args.get(2).map(String::as_str);
+        if args[1] == "koreader-import" {
+            cli::koreader_import(&app.db, book_id, sidecar.as_deref())?;
+        } else {
+            cli::koreader_export(&app.db, book_id, sidecar.as_deref())?;
+        }
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "index" {
+        let pause = match take_flag_value(&mut args, "--pause")? {
+            Some(ms) => ms
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid pause: {}", ms))?,
+            None => 0,
+        };
+        let all = args.iter().any(|a| a == "--all");
+        cli::index(&app.db, all, Duration::from_millis(pause))?;
+        return Ok(());
+    }
+
+    if args.len() > 1 && args[1] == "doctor" {
+        if !cli::doctor(&app.db)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     if args.len() > 1 && args[1] == "list" {
         for b in app.books {
             println!(
@@ -42,7 +211,35 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    if config.auto_resume && args.len() == 1 {
+    // Progress is saved from the moment a book opens, so settle who may save first.
+    let lock = if read_only {
+        None
+    } else {
+        match instance::InstanceLock::acquire(&db_path)? {
+            Ok(lock) => Some(lock),
+            Err(owner) => match cli::ask_about_lock(&owner)? {
+                cli::LockChoice::ReadOnly => None,
+                cli::LockChoice::TakeOver => Some(instance::InstanceLock::take_over(&db_path)?),
+                cli::LockChoice::Quit => return Ok(()),
+            },
+        }
+    };
+    match lock {
+        Some(lock) => app.instance_lock = Some(lock),
+        None => app.set_read_only()?,
+    }
+
+    // Chosen before any book is laid out, so the first chapter already uses it.
+    if let Ok((width, height)) = crossterm::terminal::size() {
+        let profiles = config.layout_profiles.as_deref().unwrap_or_default();
+        if let Some(name) = app.apply_layout_profile(profiles, width, height, &config::hostname()) {
+            app.status_message = Some(format!("Layout: {}", name));
+        }
+    }
+
+    if let Some(ref view) = start_view {
+        app.open_start_view(view)?;
+    } else if config.auto_resume && args.len() == 1 {
         if let Some(last_book) = app.db.get_last_read_book()? {
             app.load_book(last_book).ok();
         }
@@ -50,22 +247,30 @@ async fn main() -> Result<()> {
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
+    let mouse_capture = app.mouse_capture;
+    if mouse_capture {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Query terminal capabilities (protocol + pixel cell size) after entering alt screen.
-    // This improves Kitty/Ghostty image sharpness vs guessing.
-    app.image_picker = build_image_picker();
+    // This improves Kitty/Ghostty image sharpness vs guessing. Light mode shows no images,
+    // so it skips the query and its round trip over slow links.
+    if !app.light_mode {
+        app.image_picker = build_image_picker();
+        // A resumed book was laid out with the placeholder picker's font size.
+        app.refresh_current_book_render_cache().ok();
+    }
 
     let res = run_app(&mut terminal, app).await;
 
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if mouse_capture {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
@@ -85,6 +290,12 @@ fn prefers_kitty_protocol() -> bool {
         || std::env::var("KITTY_WINDOW_ID").is_ok()
 }
 
+fn detect_color_depth() -> ColorDepth {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    ColorDepth::detect(&var("COLORTERM"), &var("TERM"), no_color)
+}
+
 fn build_image_picker() -> Picker {
     let mut picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::halfblocks());
 
@@ -114,41 +325,104 @@ fn reader_content_height(
     view: AppView,
     focus_mode: bool,
     show_status: bool,
+    show_gauge: bool,
 ) -> usize {
     let top_bar = if focus_mode { 0u16 } else { 1u16 };
     let status_bar = if show_status { 1u16 } else { 0u16 };
+    let gauge = if show_gauge { 1u16 } else { 0u16 };
     let search_bar = if matches!(view, AppView::Search) { 3u16 } else { 0u16 };
-    let content = term_height.saturating_sub(top_bar + status_bar + search_bar);
+    let content = term_height.saturating_sub(top_bar + status_bar + gauge + search_bar);
     let content = content.saturating_sub(margin.saturating_mul(2));
     content as usize
 }
 
+fn spawn_scan(
+    path: String,
+    rules: config::ImportRules,
+    tx_scan: &tokio::sync::mpsc::Sender<library::ScanResult>,
+) {
+    let tx = tx_scan.clone();
+    tokio::spawn(async move {
+        let res = library::scan_for_books(&path, &rules);
+        let _ = tx.send(res).await;
+    });
+}
+
+/// Hands every book missing from the full-text index to the background indexer.
+fn queue_unindexed_books(
+    app: &mut App,
+    tx_index: &tokio::sync::mpsc::UnboundedSender<(i32, String)>,
+) {
+    // The indexer saves what it finds.
+    if app.read_only {
+        return;
+    }
+    let Ok(books) = app.db.get_unindexed_books() else {
+        return;
+    };
+    for (book_id, path) in books {
+        if app.queue_indexing(book_id) {
+            let _ = tx_index.send((book_id, path));
+        }
+    }
+}
+
+/// Loads the selected book's cover after `delay`, and the neighbors' covers with it. The
+/// deadline is set even when the selected cover is already loaded, so prefetching still
+/// follows the selection.
 fn schedule_cover_request(
     app: &mut App,
     pending_cover_request: &mut Option<app::CoverRequest>,
     pending_cover_deadline: &mut Option<Instant>,
     delay: Duration,
 ) {
-    *pending_cover_request = None;
-    *pending_cover_deadline = None;
-    if let Some(req) = app.cover_request_for_selected() {
-        *pending_cover_request = Some(req);
-        *pending_cover_deadline = Some(Instant::now() + delay);
+    *pending_cover_request = app.cover_request_for_selected();
+    *pending_cover_deadline = Some(Instant::now() + delay);
+}
+
+/// Removes `--flag` from the arguments and returns whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|a| a != flag);
+    args.len() != len
+}
+
+/// Removes `--flag value` (or `--flag=value`) from the arguments and returns the value.
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>> {
+    let prefix = format!("{}=", flag);
+    let Some(pos) = args
+        .iter()
+        .position(|a| a == flag || a.starts_with(&prefix))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(pos);
+    if let Some(value) = arg.strip_prefix(&prefix) {
+        return Ok(Some(value.to_string()));
+    }
+    if pos < args.len() {
+        Ok(Some(args.remove(pos)))
+    } else {
+        Err(anyhow::anyhow!("{} needs a value", flag))
+    }
+}
+
+/// Moves files that older versions created in the working directory.
+fn migrate_legacy_file(legacy: &std::path::Path, target: &std::path::Path) {
+    match config::migrate_legacy_file(legacy, target) {
+        Ok(true) => eprintln!("Moved {} to {}", legacy.display(), target.display()),
+        Ok(false) => {}
+        Err(e) => eprintln!(
+            "Could not move {} to {}: {}",
+            legacy.display(),
+            target.display(),
+            e
+        ),
     }
 }
 
 fn add_book_to_db(app: &mut App, path: &str) -> Result<()> {
-    let parser = if path.to_lowercase().ends_with(".pdf") {
-        parser::BookParser::Pdf(parser::PdfParser::new(path)?)
-    } else {
-        parser::BookParser::Epub(parser::EpubParser::new(path)?)
-    };
-    let (title, author) = parser.get_metadata();
-    let total_chapters = parser.get_chapter_count();
-    let total_lines = 0;
-    app.db
-        .add_book(&title, &author, path, total_chapters, total_lines)?;
-    Ok(())
+    library::import_book(&app.db, path)
 }
 
 async fn run_app<B: ratatui::backend::Backend>(
@@ -156,23 +430,48 @@ async fn run_app<B: ratatui::backend::Backend>(
     mut app: App,
 ) -> Result<()> {
     let (tx_dict, mut rx_dict) = tokio::sync::mpsc::channel::<String>(10);
-    let (tx_scan, mut rx_scan) = tokio::sync::mpsc::channel::<Vec<std::path::PathBuf>>(1);
+    let (tx_scan, mut rx_scan) = tokio::sync::mpsc::channel::<library::ScanResult>(1);
+    let (tx_article, mut rx_article) =
+        tokio::sync::mpsc::channel::<Result<std::path::PathBuf, String>>(1);
+    let (tx_llm, mut rx_llm) = tokio::sync::mpsc::channel::<Result<String, String>>(1);
+    let (tx_index, mut rx_index) = tokio::sync::mpsc::unbounded_channel::<(i32, String)>();
+    let (tx_index_done, mut rx_index_done) = tokio::sync::mpsc::unbounded_channel::<i32>();
+    let (tx_sync_pull, mut rx_sync_pull) = tokio::sync::mpsc::channel::<(
+        String,
+        Result<Option<kosync::Position>, String>,
+    )>(1);
+    let (tx_sync_push, mut rx_sync_push) = tokio::sync::mpsc::channel::<Result<(), String>>(1);
+    let mut last_sync_push = Instant::now();
+    let mut last_lock_check = Instant::now();
+    let (tx_relink, mut rx_relink) = tokio::sync::mpsc::channel::<Vec<library::Relink>>(1);
     let (tx_cover, mut rx_cover) = tokio::sync::mpsc::channel::<app::CoverResponse>(4);
     let (tx_cover_req, mut rx_cover_req) =
         tokio::sync::watch::channel::<Option<app::CoverRequest>>(None);
 
-    let cover_debounce = Duration::from_millis(150);
+    // Covers loaded ahead wait until the selected book's cover is done.
+    let (tx_cover_prefetch, mut rx_cover_prefetch) =
+        tokio::sync::mpsc::unbounded_channel::<app::CoverRequest>();
     let mut pending_cover_request: Option<app::CoverRequest> = None;
     let mut pending_cover_deadline: Option<Instant> = None;
 
     let tx_cover_worker = tx_cover.clone();
     tokio::spawn(async move {
-        while rx_cover_req.changed().await.is_ok() {
-            let req = rx_cover_req.borrow().clone();
-            let Some(req) = req else {
-                continue;
+        loop {
+            let req = tokio::select! {
+                biased;
+                changed = rx_cover_req.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let req = rx_cover_req.borrow().clone();
+                    let Some(req) = req else {
+                        continue;
+                    };
+                    req
+                }
+                Some(req) = rx_cover_prefetch.recv() => req,
             };
-            let image = App::load_cover_image(&req.path);
+            let image = App::load_cover_image(&req.path, req.max_dimension);
             let _ = tx_cover_worker
                 .send(app::CoverResponse {
                     book_id: req.book_id,
@@ -182,27 +481,99 @@ async fn run_app<B: ratatui::backend::Backend>(
         }
     });
 
+    // Books are indexed one at a time so the reader never competes with several parsers.
+    let db_path = app.db_path.clone();
+    tokio::spawn(async move {
+        while let Some((book_id, path)) = rx_index.recv().await {
+            let db_path = db_path.clone();
+            // The worker writes through its own connection.
+            let _ = tokio::task::spawn_blocking(move || -> Result<()> {
+                let db = db::Db::new(&db_path)?;
+                library::index_book(&db, book_id, &path)
+            })
+            .await;
+            let _ = tx_index_done.send(book_id);
+            // A short rest between books keeps backfilling a large library from
+            // making the reader stutter.
+            tokio::time::sleep(INDEX_PAUSE).await;
+        }
+    });
+
+    // Progress saves and exports go through one connection, in order, so a slow disk
+    // never holds up a key press.
+    let (tx_write, mut rx_write) = tokio::sync::mpsc::unbounded_channel::<app::BackgroundWrite>();
+    let (tx_write_done, mut rx_write_done) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let db_path = app.db_path.clone();
+    let writer = tokio::task::spawn_blocking(move || {
+        let db = db::Db::new(&db_path);
+        while let Some(write) = rx_write.blocking_recv() {
+            let message = match db {
+                Ok(ref db) => write.run(db),
+                Err(ref e) => Some(format!("Could not open the library: {}", e)),
+            };
+            if let Some(message) = message {
+                let _ = tx_write_done.send(message);
+            }
+        }
+    });
+
+    let (tx_search, mut rx_search) =
+        tokio::sync::mpsc::unbounded_channel::<app::GlobalSearchUpdate>();
+
+    // Fingerprint older books, then look for the files of any that went missing.
+    let db_path = app.db_path.clone();
+    let library_root = std::path::PathBuf::from(app::expand_path(&app.library_path));
+    tokio::task::spawn_blocking(move || {
+        let Ok(db) = db::Db::new(&db_path) else {
+            return;
+        };
+        library::backfill_fingerprints(&db).ok();
+        if let Ok(relinks) = library::find_moved_books(&db, &library_root) {
+            if !relinks.is_empty() {
+                let _ = tx_relink.blocking_send(relinks);
+            }
+        }
+    });
+
     schedule_cover_request(
         &mut app,
         &mut pending_cover_request,
         &mut pending_cover_deadline,
         Duration::from_millis(0),
     );
+    queue_unindexed_books(&mut app, &tx_index);
 
     loop {
         let term_size = terminal
             .size()
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-        let viewport_height = (term_size.height as usize).saturating_sub(1);
         let show_status = !app.focus_mode || app.pomodoro.running;
-        let reader_height = reader_content_height(
+        let reader_rows = reader_content_height(
             term_size.height,
             app.margin,
             app.view,
             app.focus_mode,
             show_status,
+            app.progress_gauge.shown(app.focus_mode),
         )
         .max(1);
+        // Text flows down each column in turn, so the cursor can travel through all of them.
+        let columns = ui::reader::column_count(
+            term_size.width,
+            app.margin,
+            app.text_columns,
+            app.max_text_width,
+            app.line_number_width(),
+        );
+        let reader_height = reader_rows * columns;
+        // Images are sized to the column, so a resize or margin change re-lays them out.
+        app.set_reader_text_width(ui::reader::column_width(
+            term_size.width,
+            app.margin,
+            app.text_columns,
+            app.max_text_width,
+            app.line_number_width(),
+        ));
 
         terminal
             .draw(|f| ui::render(f, &mut app))
@@ -213,22 +584,96 @@ async fn run_app<B: ratatui::backend::Backend>(
         }
 
         if let Ok(res) = rx_dict.try_recv() {
-            app.dictionary_result = res.clone();
-            app.db.add_to_vocabulary(&app.dictionary_query, &res).ok();
+            app.apply_lookup_result(res);
         }
 
         if let Ok(results) = rx_scan.try_recv() {
-            app.explorer_results = results;
-            app.is_scanning = false;
-            app.selected_explorer_index = 0;
-            app.explorer_selected.clear();
-            if std::path::Path::new(&app.explorer_path).is_file()
-                && app.explorer_results.len() == 1
-            {
-                app.explorer_selected
-                    .insert(app.explorer_results[0].clone());
+            app.apply_scan_result(results);
+        }
+
+        if let Ok(result) = rx_article.try_recv() {
+            match result {
+                Ok(path) => {
+                    app.import_paths(&[path]).ok();
+                    app.refresh_library().ok();
+                    queue_unindexed_books(&mut app, &tx_index);
+                    app.url_fetch_status = None;
+                    if app.view == AppView::UrlInput {
+                        app.view = AppView::Library;
+                        schedule_cover_request(
+                            &mut app,
+                            &mut pending_cover_request,
+                            &mut pending_cover_deadline,
+                            Duration::from_millis(0),
+                        );
+                    }
+                }
+                Err(e) => app.url_fetch_status = Some(format!("Failed: {}", e)),
+            }
+        }
+
+        if let Ok(relinks) = rx_relink.try_recv() {
+            app.pending_relinks = relinks;
+        }
+
+        while let Ok(message) = rx_write_done.try_recv() {
+            app.apply_write_result(Some(message));
+        }
+
+        while let Ok(book_id) = rx_index_done.try_recv() {
+            app.finish_indexing(book_id);
+        }
+
+        let had_hits = !app.global_search_results.is_empty();
+        while let Ok(update) = rx_search.try_recv() {
+            app.apply_global_search_update(update);
+        }
+        // The first hits of a search pick the book to preview.
+        if !had_hits && !app.global_search_results.is_empty() {
+            schedule_cover_request(
+                &mut app,
+                &mut pending_cover_request,
+                &mut pending_cover_deadline,
+                Duration::from_millis(0),
+            );
+        }
+
+        if let Ok(result) = rx_llm.try_recv() {
+            app.apply_llm_summary(result);
+        }
+
+        if let Some(document) = app.sync_pull.take() {
+            let settings = app.kosync.clone();
+            let tx = tx_sync_pull.clone();
+            tokio::spawn(async move {
+                let res = kosync::pull(&settings, &document)
+                    .await
+                    .map_err(|e| e.to_string());
+                let _ = tx.send((document, res)).await;
+            });
+        }
+        if let Ok((document, result)) = rx_sync_pull.try_recv() {
+            app.apply_remote_position(&document, result);
+        }
+        if app.view != AppView::Reader
+            || app.sync_push_now
+            || last_sync_push.elapsed() >= SYNC_PUSH_INTERVAL
+        {
+            if let Some(position) = app.take_sync_push() {
+                last_sync_push = Instant::now();
+                let settings = app.kosync.clone();
+                let tx = tx_sync_push.clone();
+                tokio::spawn(async move {
+                    let res = kosync::push(&settings, &position)
+                        .await
+                        .map_err(|e| e.to_string());
+                    let _ = tx.send(res).await;
+                });
             }
         }
+        if let Ok(result) = rx_sync_push.try_recv() {
+            app.apply_sync_push(result);
+        }
 
         app.tick_timers();
 
@@ -237,561 +682,164 @@ async fn run_app<B: ratatui::backend::Backend>(
             if app.auto_scroll_last_tick.elapsed().as_millis() as u64 >= app.auto_scroll_interval_ms
             {
                 app.scroll_viewport_down();
+                app.count_words_read();
+                app.note_reading_activity();
                 app.auto_scroll_last_tick = std::time::Instant::now();
             }
         }
 
-        if app.view == AppView::Library {
-            if let (Some(req), Some(deadline)) = (&pending_cover_request, pending_cover_deadline) {
-                if Instant::now() >= deadline {
+        app.tick_rsvp();
+
+        if matches!(app.view, AppView::Library | AppView::GlobalSearch) {
+            if pending_cover_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                if let Some(req) = pending_cover_request.take() {
                     app.mark_cover_request_in_flight(req.book_id);
-                    let _ = tx_cover_req.send(Some(req.clone()));
-                    pending_cover_request = None;
-                    pending_cover_deadline = None;
+                    let _ = tx_cover_req.send(Some(req));
                 }
+                for req in app.cover_prefetch_requests() {
+                    let _ = tx_cover_prefetch.send(req);
+                }
+                pending_cover_deadline = None;
             }
         } else {
             pending_cover_request = None;
             pending_cover_deadline = None;
         }
 
-        if event::poll(Duration::from_millis(10))? {
+        // RSVP shows words faster than light mode would redraw.
+        let rsvp_running = app.view == AppView::Rsvp && app.rsvp_active;
+        let poll_interval = if app.light_mode && !rsvp_running {
+            LIGHT_POLL_INTERVAL
+        } else {
+            POLL_INTERVAL
+        };
+        if event::poll(poll_interval)? {
             let ev = event::read()?;
-            if let Event::Mouse(mouse) = ev {
-                if mouse.kind == event::MouseEventKind::Down(event::MouseButton::Left) {
-                    if app.view == AppView::Reader {
-                        let total_width = term_size.width;
-                        if mouse.row == 0 {
-                            // [ - ] area: x in [total_width - 14, total_width - 10]
-                            if mouse.column >= total_width.saturating_sub(14)
-                                && mouse.column <= total_width.saturating_sub(10)
-                            {
-                                app.adjust_margin(1); // Increase margin = decrease text width
-                            }
-                            // [ + ] area: x in [total_width - 7, total_width - 3]
-                            if mouse.column >= total_width.saturating_sub(7)
-                                && mouse.column <= total_width.saturating_sub(3)
-                            {
-                                app.adjust_margin(-1); // Decrease margin = increase text width
-                            }
-                        }
+            let viewport = input::Viewport { reader_height };
+            // Clicks go through the same keys and effects as typing.
+            let effects = match ev {
+                Event::Key(key) => input::handle_key(&mut app, key, viewport),
+                Event::Mouse(mouse) => input::handle_mouse(&mut app, mouse, viewport),
+                _ => Vec::new(),
+            };
+            for effect in effects {
+                match effect {
+                    input::Effect::Quit => {
+                        app.leave_book();
+                        finish_writes(&mut app, tx_write, writer).await;
+                        flush_sync_push(&mut app).await;
+                        return Ok(());
+                    }
+                    input::Effect::ShowCover { debounced } => {
+                        let delay = if debounced {
+                            app.cover_debounce
+                        } else {
+                            Duration::from_millis(0)
+                        };
+                        schedule_cover_request(
+                            &mut app,
+                            &mut pending_cover_request,
+                            &mut pending_cover_deadline,
+                            delay,
+                        )
+                    }
+                    input::Effect::Scan(path) => {
+                        spawn_scan(path, app.import_rules.clone(), &tx_scan)
+                    }
+                    input::Effect::IndexNewBooks => queue_unindexed_books(&mut app, &tx_index),
+                    input::Effect::FetchArticle(url) => {
+                        let tx = tx_article.clone();
+                        tokio::spawn(async move {
+                            let res = App::fetch_article(url).await.map_err(|e| e.to_string());
+                            let _ = tx.send(res).await;
+                        });
+                    }
+                    input::Effect::Summarize(text) => {
+                        let settings = app.llm.clone();
+                        let tx = tx_llm.clone();
+                        tokio::spawn(async move {
+                            let res = llm::summarize(settings, text)
+                                .await
+                                .map_err(|e| e.to_string());
+                            let _ = tx.send(res).await;
+                        });
+                    }
+                    input::Effect::LookUp(word, settings) => {
+                        let tx = tx_dict.clone();
+                        tokio::spawn(async move {
+                            let result = dictionary::lookup(word, settings).await;
+                            let _ = tx.send(result).await;
+                        });
+                    }
+                    // OSC 52 reaches the local clipboard even over SSH, in terminals
+                    // that allow it.
+                    input::Effect::CopyToClipboard(text) => {
+                        let _ = execute!(
+                            io::stdout(),
+                            crossterm::clipboard::CopyToClipboard::to_clipboard_from(text)
+                        );
                     }
                 }
             }
+        }
 
-            if let Event::Key(key) = ev {
-                if key.code == KeyCode::Char('?') {
-                    if app.view == AppView::Help {
-                        let next_view = app.previous_view.take().unwrap_or(AppView::Library);
-                        app.view = next_view;
-                        if app.view == AppView::Library {
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                    } else {
-                        app.previous_view = Some(app.view);
-                        app.view = AppView::Help;
-                    }
-                    continue;
-                }
+        if last_lock_check.elapsed() >= LOCK_CHECK_INTERVAL {
+            last_lock_check = Instant::now();
+            app.check_instance_lock();
+        }
+        for write in app.take_writes() {
+            let _ = tx_write.send(write);
+        }
 
-                match app.view {
-                    AppView::Help => {
-                        if key.code == KeyCode::Esc || key.code == KeyCode::Char('q') {
-                            let next_view = app.previous_view.take().unwrap_or(AppView::Library);
-                            app.view = next_view;
-                            if app.view == AppView::Library {
-                                schedule_cover_request(
-                                    &mut app,
-                                    &mut pending_cover_request,
-                                    &mut pending_cover_deadline,
-                                    Duration::from_millis(0),
-                                );
-                            }
-                        }
+        // Each search reads through its own connection and reports book by book.
+        if let Some(request) = app.take_global_search() {
+            let db_path = app.db_path.clone();
+            let tx_search = tx_search.clone();
+            tokio::task::spawn_blocking(move || {
+                let db = match db::Db::new(&db_path) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        let _ = tx_search.send(app::GlobalSearchUpdate {
+                            generation: request.generation,
+                            scanned: 0,
+                            total: 0,
+                            hits: Vec::new(),
+                            error: Some(e.to_string()),
+                        });
+                        return;
                     }
-                    AppView::Library => match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char('p') => {
-                            // Cycle image protocols to debug cover rendering across terminals.
-                            let next = app.image_picker.protocol_type().next();
-                            app.image_picker.set_protocol_type(next);
-                            app.refresh_current_book_render_cache().ok();
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                        KeyCode::Char('n') => {
-                            app.explorer_path = dirs::home_dir()
-                                .unwrap_or_else(|| ".".into())
-                                .to_string_lossy()
-                                .to_string();
-                            app.view = AppView::PathInput;
-                        }
-                        KeyCode::Char('S') => {
-                            app.global_search_query.clear();
-                            app.global_search_results.clear();
-                            app.view = AppView::GlobalSearch;
-                        }
-                        KeyCode::Char('i') => {
-                            app.view = AppView::Stats;
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if !app.books.is_empty() {
-                                app.selected_book_index =
-                                    (app.selected_book_index + 1) % app.books.len();
-                                schedule_cover_request(
-                                    &mut app,
-                                    &mut pending_cover_request,
-                                    &mut pending_cover_deadline,
-                                    cover_debounce,
-                                );
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if !app.books.is_empty() {
-                                if app.selected_book_index > 0 {
-                                    app.selected_book_index -= 1;
-                                } else {
-                                    app.selected_book_index = app.books.len() - 1;
-                                }
-                                schedule_cover_request(
-                                    &mut app,
-                                    &mut pending_cover_request,
-                                    &mut pending_cover_deadline,
-                                    cover_debounce,
-                                );
-                            }
-                        }
-                        KeyCode::Enter => {
-                            let _ = app.open_selected_book();
-                        }
-                        _ => {}
-                    },
-                    AppView::Stats => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            app.view = AppView::Library;
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                        _ => {}
-                    },
-                    AppView::PathInput => match key.code {
-                        KeyCode::Esc => {
-                            app.view = AppView::Library;
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                        KeyCode::Enter => {
-                            app.view = AppView::FileExplorer;
-                            app.is_scanning = true;
-                            app.explorer_results.clear();
-                            let p = app.explorer_path.clone();
-                            let tx = tx_scan.clone();
-                            tokio::spawn(async move {
-                                let res = App::scan_for_books_sync(p);
-                                let _ = tx.send(res).await;
-                            });
-                        }
-                        KeyCode::Char(c) => app.explorer_path.push(c),
-                        KeyCode::Backspace => {
-                            app.explorer_path.pop();
-                        }
-                        _ => {}
-                    },
-                    AppView::FileExplorer => match key.code {
-                        KeyCode::Esc | KeyCode::Char('q') => {
-                            if !app.is_scanning {
-                                app.view = AppView::Library;
-                                schedule_cover_request(
-                                    &mut app,
-                                    &mut pending_cover_request,
-                                    &mut pending_cover_deadline,
-                                    Duration::from_millis(0),
-                                );
-                            }
-                        }
-                        KeyCode::Char(' ') => {
-                            if !app.is_scanning {
-                                app.toggle_explorer_selection();
-                            }
-                        }
-                        KeyCode::Char('a') => {
-                            if !app.is_scanning {
-                                app.select_all_explorer_results();
-                            }
-                        }
-                        KeyCode::Char('c') => {
-                            if !app.is_scanning {
-                                app.clear_explorer_selection();
-                            }
-                        }
-                        KeyCode::Char('i') => {
-                            if !app.is_scanning {
-                                app.select_all_explorer_results();
-                                let _ = app.import_explorer_selection();
-                                app.refresh_library().ok();
-                                app.view = AppView::Library;
-                                schedule_cover_request(
-                                    &mut app,
-                                    &mut pending_cover_request,
-                                    &mut pending_cover_deadline,
-                                    Duration::from_millis(0),
-                                );
-                            }
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if !app.explorer_results.is_empty() {
-                                app.selected_explorer_index =
-                                    (app.selected_explorer_index + 1) % app.explorer_results.len();
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if !app.explorer_results.is_empty() {
-                                if app.selected_explorer_index > 0 {
-                                    app.selected_explorer_index -= 1;
-                                } else {
-                                    app.selected_explorer_index = app.explorer_results.len() - 1;
-                                }
-                            }
-                        }
-                        KeyCode::Enter => {
-                            if !app.is_scanning {
-                                if !app.explorer_results.is_empty() {
-                                    let _ = app.import_explorer_selection();
-                                    app.refresh_library().ok();
-                                    app.view = AppView::Library;
-                                    schedule_cover_request(
-                                        &mut app,
-                                        &mut pending_cover_request,
-                                        &mut pending_cover_deadline,
-                                        Duration::from_millis(0),
-                                    );
-                                }
-                            }
-                        }
-                        _ => {}
-                    },
-                    AppView::GlobalSearch => match key.code {
-                        KeyCode::Esc => {
-                            app.view = AppView::Library;
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                        KeyCode::Enter => {
-                            if !app.global_search_results.is_empty() {
-                                let res = &app.global_search_results[app.selected_search_index];
-                                let book_id = res.0;
-                                let chapter = res.2;
-                                if let Some(idx) = app.books.iter().position(|b| b.id == book_id) {
-                                    app.selected_book_index = idx;
-                                    let _ = app.open_selected_book();
-
-                                    if let Some(ref mut book) = app.current_book {
-                                        book.current_chapter = chapter;
-                                        let content = book
-                                            .parser
-                                            .get_chapter_content(chapter)
-                                            .unwrap_or_default();
-
-                                        let (chapter_content, image_protocols) =
-                                            App::flatten_content(&mut app.image_picker, content);
-                                        book.chapter_content = chapter_content;
-                                        book.image_protocols = image_protocols;
-                                    }
-                                }
-                            } else {
-                                let q = app.global_search_query.clone();
-                                if let Ok(results) = app.global_search(&q) {
-                                    app.global_search_results = results;
-                                    app.selected_search_index = 0;
-                                }
-                            }
-                        }
-                        KeyCode::Char(c) => app.global_search_query.push(c),
-                        KeyCode::Backspace => {
-                            app.global_search_query.pop();
-                        }
-                        _ => {}
-                    },
-                    AppView::Reader => match key.code {
-                        KeyCode::Char('q') => {
-                            app.save_progress().ok();
-                            app.view = AppView::Library;
-                            app.refresh_library().ok();
-                            schedule_cover_request(
-                                &mut app,
-                                &mut pending_cover_request,
-                                &mut pending_cover_deadline,
-                                Duration::from_millis(0),
-                            );
-                        }
-                        KeyCode::Char('f') => app.toggle_focus_mode(),
-                        KeyCode::Char('p') => app.pomodoro_toggle(),
-                        KeyCode::Char('R') => app.pomodoro_reset(),
-                        KeyCode::Char('B') => app.pomodoro_skip_break(),
-                        KeyCode::Char('s') => app.view = AppView::Select,
-                        KeyCode::Char('A') => {
-                            let _ = app.load_annotations();
-                        }
-                        KeyCode::Char('V') => {
-                            let _ = app.load_vocabulary();
-                        }
-                        KeyCode::Char('E') => {
-                            let _ = app.export_annotations();
-                        }
-                        KeyCode::Char('t') => app.open_toc(),
-                        KeyCode::Down | KeyCode::Char('j') => app.scroll_viewport_down(),
-                        KeyCode::Up | KeyCode::Char('k') => app.scroll_viewport_up(),
-                        KeyCode::Right | KeyCode::Char('l') => {
-                            let _ = app.next_chapter();
-                        }
-                        KeyCode::Left | KeyCode::Char('h') => {
-                            let _ = app.prev_chapter();
-                        }
-                        KeyCode::Char('c') => app.toggle_theme(),
-                        KeyCode::Char('[') | KeyCode::Char('-') => app.adjust_margin(1),
-                        KeyCode::Char(']') | KeyCode::Char('+') | KeyCode::Char('=') => {
-                            app.adjust_margin(-1)
-                        }
-                        KeyCode::Char('{') => app.adjust_spacing(1),
-                        KeyCode::Char('}') => app.adjust_spacing(-1),
-                        KeyCode::Char('/') => {
-                            app.view = AppView::Search;
-                            app.search_query.clear();
-                        }
-                        KeyCode::Char('a') => {
-                            app.auto_scroll_active = !app.auto_scroll_active;
-                            app.auto_scroll_last_tick = std::time::Instant::now();
-                        }
-                        _ => {}
-                    },
-                    AppView::Select | AppView::Visual => match key.code {
-                        KeyCode::Char('v') => {
-                            if app.view == AppView::Visual {
-                                app.exit_visual_mode();
-                            } else {
-                                app.enter_visual_mode();
-                            }
-                        }
-                        KeyCode::Char('a') => {
-                            app.annotation_note.clear();
-                            app.view = AppView::Annotation;
-                        }
-                        KeyCode::Char('h') => {
-                            if app.view == AppView::Visual || app.view == AppView::Select {
-                                let _ = app.add_quick_highlight();
-                            }
-                        }
-                        KeyCode::Char('q') => {
-                            if app.view == AppView::Visual || app.view == AppView::Select {
-                                let _ = app.add_question_highlight();
-                            }
-                        }
-                        KeyCode::Char('m') => {
-                            if app.view == AppView::Visual || app.view == AppView::Select {
-                                let _ = app.add_summary_highlight();
-                            }
-                        }
-                        KeyCode::Char('d') => {
-                            if let Some(ref book) = app.current_book {
-                                if let Some(app::RenderLine::Text(line)) =
-                                    book.chapter_content.get(book.current_line)
-                                {
-                                    if let Some(word) = line.split_whitespace().nth(book.word_index)
-                                    {
-                                        let clean_word: String =
-                                            word.chars().filter(|c| c.is_alphabetic()).collect();
-                                        if !clean_word.is_empty() {
-                                            app.dictionary_query = clean_word.clone();
-                                            app.view = AppView::Dictionary;
-                                            app.dictionary_result = "Loading...".into();
-                                            let tx_clone = tx_dict.clone();
-                                            tokio::spawn(async move {
-                                                let result = App::perform_lookup(clean_word).await;
-                                                let _ = tx_clone.send(result).await;
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        KeyCode::Char('f') => app.toggle_focus_mode(),
-                        KeyCode::Char('p') => app.pomodoro_toggle(),
-                        KeyCode::Char('R') => app.pomodoro_reset(),
-                        KeyCode::Char('B') => app.pomodoro_skip_break(),
-                        KeyCode::Down | KeyCode::Char('j') => app.move_cursor_down(reader_height),
-                        KeyCode::Up | KeyCode::Char('k') => app.move_cursor_up(),
-                        KeyCode::Char('w') => app.cursor_right(reader_height),
-                        KeyCode::Char('b') => app.cursor_left(),
-                        KeyCode::Esc => {
-                            if app.view == AppView::Visual {
-                                app.exit_visual_mode();
-                            } else {
-                                app.view = AppView::Reader;
-                            }
-                        }
-                        _ => {}
-                    },
-                    AppView::Toc => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => app.view = AppView::Reader,
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if !app.toc_items.is_empty() {
-                                app.selected_toc_index =
-                                    (app.selected_toc_index + 1) % app.toc_items.len();
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if !app.toc_items.is_empty() {
-                                if app.selected_toc_index > 0 {
-                                    app.selected_toc_index -= 1;
-                                } else {
-                                    app.selected_toc_index = app.toc_items.len() - 1;
-                                }
-                            }
-                        }
-                        KeyCode::Enter => {
-                            let _ = app.jump_to_toc();
-                        }
-                        _ => {}
-                    },
-                    AppView::Annotation => match key.code {
-                        KeyCode::Enter => {
-                            let _ = app.add_annotation_with_note();
-                        }
-                        KeyCode::Esc => app.view = AppView::Select,
-                        KeyCode::Char(c) => app.annotation_note.push(c),
-                        KeyCode::Backspace => {
-                            app.annotation_note.pop();
-                        }
-                        _ => {}
-                    },
-                    AppView::AnnotationList => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => app.view = AppView::Reader,
-                        KeyCode::Char('1') => app.set_annotation_filter(app::AnnotationFilter::All),
-                        KeyCode::Char('2') => {
-                            app.set_annotation_filter(app::AnnotationFilter::Highlight)
-                        }
-                        KeyCode::Char('3') => {
-                            app.set_annotation_filter(app::AnnotationFilter::Question)
-                        }
-                        KeyCode::Char('4') => {
-                            app.set_annotation_filter(app::AnnotationFilter::Summary)
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if !app.current_annotations.is_empty() {
-                                app.selected_annotation_index = (app.selected_annotation_index + 1)
-                                    % app.current_annotations.len();
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if !app.current_annotations.is_empty() {
-                                if app.selected_annotation_index > 0 {
-                                    app.selected_annotation_index -= 1;
-                                } else {
-                                    app.selected_annotation_index =
-                                        app.current_annotations.len() - 1;
-                                }
-                            }
-                        }
-                        KeyCode::Enter => {
-                            let _ = app.jump_to_annotation();
-                        }
-                        _ => {}
-                    },
-                    AppView::Dictionary => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => app.view = AppView::Select,
-                        _ => {}
-                    },
-                    AppView::Vocabulary => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => app.view = AppView::Reader,
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if !app.vocabulary.is_empty() {
-                                app.selected_vocab_index =
-                                    (app.selected_vocab_index + 1) % app.vocabulary.len();
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if !app.vocabulary.is_empty() {
-                                if app.selected_vocab_index > 0 {
-                                    app.selected_vocab_index -= 1;
-                                } else {
-                                    app.selected_vocab_index = app.vocabulary.len() - 1;
-                                }
-                            }
-                        }
-                        _ => {}
-                    },
-                    AppView::Search => match key.code {
-                        KeyCode::Enter => {
-                            if let Some(ref book) = app.current_book {
-                                if let Some(pos) = book
-                                    .chapter_content
-                                    .iter()
-                                    .skip(book.current_line + 1)
-                                    .position(|l| {
-                                        if let app::RenderLine::Text(text) = l {
-                                            text.contains(&app.search_query)
-                                        } else {
-                                            false
-                                        }
-                                    })
-                                {
-                                    for _ in 0..(pos + 1) {
-                                        app.move_cursor_down(viewport_height);
-                                    }
-                                }
-                            }
-                            app.view = AppView::Reader;
-                        }
-                        KeyCode::Esc => app.view = AppView::Reader,
-                        KeyCode::Char(c) => app.search_query.push(c),
-                        KeyCode::Backspace => {
-                            app.search_query.pop();
-                        }
-                        _ => {}
-                    },
-                    AppView::Rsvp => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            app.rsvp_active = false;
-                            app.view = AppView::Reader;
-                        }
-                        KeyCode::Char(' ') => {
-                            app.rsvp_active = !app.rsvp_active;
-                        }
-                        KeyCode::Char('+') | KeyCode::Char('=') => app.rsvp_wpm += 50,
-                        KeyCode::Char('-') => {
-                            if app.rsvp_wpm > 50 {
-                                app.rsvp_wpm -= 50;
-                            }
-                        }
-                        _ => {}
-                    },
-                }
-            }
+                };
+                request.run(&db, |update| {
+                    let _ = tx_search.send(update);
+                });
+            });
         }
 
         if app.should_quit {
+            finish_writes(&mut app, tx_write, writer).await;
+            flush_sync_push(&mut app).await;
             return Ok(());
         }
     }
 }
+
+/// Hands over the last writes and waits for the writer to finish them before exiting.
+async fn finish_writes(
+    app: &mut App,
+    tx_write: tokio::sync::mpsc::UnboundedSender<app::BackgroundWrite>,
+    writer: tokio::task::JoinHandle<()>,
+) {
+    for write in app.take_writes() {
+        let _ = tx_write.send(write);
+    }
+    drop(tx_write);
+    let _ = writer.await;
+}
+
+/// Sends the last reading position before exiting, without holding up an offline quit
+/// for long.
+async fn flush_sync_push(app: &mut App) {
+    if let Some(position) = app.take_sync_push() {
+        let _ = tokio::time::timeout(SYNC_QUIT_TIMEOUT, kosync::push(&app.kosync, &position)).await;
+    }
+}
@@ -0,0 +1,88 @@
+//! One search across the whole library: the titles and authors of books, notes, looked-up
+//! words, and the text of every book, for finding something half remembered without
+//! knowing where it was read.
+
+use crate::db::{AnnotationRecord, BookRecord, Db, SearchHit, VocabRecord, VocabSource};
+use crate::locator::Locator;
+use crate::search::SearchPattern;
+use anyhow::Result;
+
+/// Results kept in each group.
+pub const OMNIBOX_GROUP_LIMIT: usize = 10;
+
+#[derive(Clone, Debug)]
+pub enum OmniboxHit {
+    Book(BookRecord),
+    /// A note, with the title of its book.
+    Note(String, AnnotationRecord),
+    Word(VocabRecord),
+    Text(SearchHit),
+}
+
+impl OmniboxHit {
+    /// The heading the hit is listed under.
+    pub fn group(&self) -> &'static str {
+        match self {
+            OmniboxHit::Book(_) => "Books",
+            OmniboxHit::Note(..) => "Notes",
+            OmniboxHit::Word(_) => "Words",
+            OmniboxHit::Text(_) => "Text",
+        }
+    }
+}
+
+/// Everything matching `query`, case ignored: books, then notes, then words, then
+/// passages of text. Like global search, plain words find text through the full-text
+/// index and a regular expression reads through the indexed text.
+pub fn search(db: &Db, query: &str) -> Result<Vec<OmniboxHit>> {
+    let Some(pattern) = SearchPattern::new(query, false) else {
+        return Ok(Vec::new());
+    };
+    let library = db.get_books()?;
+    let title = |id: i32| {
+        library
+            .iter()
+            .find(|b| b.id == id)
+            .map(|b| b.title.clone())
+            .unwrap_or_else(|| "Removed book".to_string())
+    };
+
+    let mut hits: Vec<OmniboxHit> = library
+        .iter()
+        .filter(|b| pattern.is_match(&b.title) || pattern.is_match(&b.author))
+        .take(OMNIBOX_GROUP_LIMIT)
+        .map(|b| OmniboxHit::Book(b.clone()))
+        .collect();
+    let notes = db.get_all_annotations()?.into_iter().filter(|n| {
+        pattern.is_match(&n.content) || n.note.as_deref().is_some_and(|t| pattern.is_match(t))
+    });
+    for note in notes.take(OMNIBOX_GROUP_LIMIT) {
+        hits.push(OmniboxHit::Note(title(note.book_id), note));
+    }
+    let words = db
+        .get_vocabulary()?
+        .into_iter()
+        .filter(|w| pattern.is_match(&w.word) || pattern.is_match(&w.definition));
+    hits.extend(words.take(OMNIBOX_GROUP_LIMIT).map(OmniboxHit::Word));
+    let text = if SearchPattern::is_plain(query) {
+        db.search_chapters(query.trim(), OMNIBOX_GROUP_LIMIT)?
+    } else {
+        db.search_chapters_matching(&pattern, None, OMNIBOX_GROUP_LIMIT)?
+    };
+    hits.extend(text.into_iter().map(OmniboxHit::Text));
+    Ok(hits)
+}
+
+/// Where the sentence a word was looked up in sits in its book, or the start of the
+/// chapter when the indexed text no longer holds it.
+pub fn word_locator(db: &Db, source: &VocabSource) -> Result<Locator> {
+    let start = Locator::new(source.chapter, 0);
+    let Some(sentence) = SearchPattern::new(&regex::escape(&source.sentence), false) else {
+        return Ok(start);
+    };
+    let hit = db
+        .search_chapters_matching(&sentence, Some(source.book_id), usize::MAX)?
+        .into_iter()
+        .find(|h| h.locator.spine == source.chapter);
+    Ok(hit.map_or(start, |h| h.locator))
+}
@@ -0,0 +1,133 @@
+use crate::deps;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+pub struct DjvuParser {
+    path: String,
+    page_count: usize,
+}
+
+impl DjvuParser {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path_str = path.as_ref().to_string_lossy().to_string();
+        if !path.as_ref().is_file() {
+            return Err(anyhow::anyhow!("Failed to open DjVu document"));
+        }
+
+        // `djvused -e n` prints the page count without decoding any pages.
+        let mut cmd = deps::resolve_djvu_command("djvused")?;
+        let output = cmd
+            .args(["-e", "n", &path_str])
+            .output()
+            .context("Failed to execute djvused. Ensure djvulibre is installed.")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("djvused failed: {}", stderr));
+        }
+        let page_count = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<usize>()
+            .unwrap_or(1)
+            .max(1);
+
+        Ok(Self {
+            path: path_str,
+            page_count,
+        })
+    }
+
+    pub fn get_metadata(&self) -> (String, String) {
+        let title = Path::new(&self.path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Unknown DjVu")
+            .to_string();
+        (title, "DjVu Document".to_string())
+    }
+
+    pub fn get_chapter_count(&self) -> usize {
+        self.page_count
+    }
+
+    pub fn get_cover_image_preview(&self) -> Result<image::DynamicImage> {
+        // Smaller scale for faster preview rendering in library.
+        self.render_page_image_with_scale(1, 50)
+    }
+
+    pub fn get_chapter_content(&mut self, index: usize) -> Result<Vec<crate::parser::PageContent>> {
+        // Pages are 1-based in djvulibre tools.
+        let page_num = index + 1;
+
+        let mut cmd = deps::resolve_djvu_command("djvutxt")?;
+        let output = cmd
+            .args([&format!("--page={}", page_num), &self.path])
+            .output()
+            .context("Failed to execute djvutxt. Ensure djvulibre is installed.")?;
+
+        let text = if output.status.success() {
+            String::from_utf8_lossy(&output.stdout).to_string()
+        } else {
+            String::new()
+        };
+
+        if text.trim().is_empty() {
+            // Most scanned DjVu files have no hidden text layer; show the page image instead.
+            match self.render_page_image_with_scale(page_num, 100) {
+                Ok(img) => Ok(vec![crate::parser::PageContent::Image(
+                    std::sync::Arc::new(img),
+                )]),
+                Err(_) => Ok(vec![crate::parser::PageContent::Text(
+                    " [ Blank Page or Text Not Extractable ] ".to_string(),
+                )]),
+            }
+        } else {
+            Ok(vec![crate::parser::PageContent::Text(text)])
+        }
+    }
+
+    fn render_page_image_with_scale(
+        &self,
+        page_num: usize,
+        scale: u32,
+    ) -> Result<image::DynamicImage> {
+        let out = std::env::temp_dir().join(format!(
+            "tbook_djvu_{}_{}_{}.ppm",
+            std::process::id(),
+            page_num,
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+        ));
+        let out_str = out
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("temp path not valid utf-8"))?;
+
+        let mut cmd = deps::resolve_djvu_command("ddjvu")?;
+        let output = cmd
+            .args([
+                "-format=ppm",
+                &format!("-page={}", page_num),
+                &format!("-scale={}", scale),
+                &self.path,
+                out_str,
+            ])
+            .output()
+            .context("Failed to execute ddjvu. Ensure djvulibre is installed.")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("ddjvu failed: {}", stderr));
+        }
+
+        let bytes = fs::read(&out)
+            .with_context(|| format!("Failed to read rendered page image: {:?}", out))?;
+        let _ = fs::remove_file(&out);
+        let img = image::load_from_memory(&bytes).context("Failed to decode rendered DjVu page")?;
+        Ok(img)
+    }
+
+    pub fn get_toc(&self) -> Vec<String> {
+        (0..self.page_count)
+            .map(|i| format!("Page {}", i + 1))
+            .collect()
+    }
+}
@@ -1,21 +1,216 @@
-use crate::parser::PageContent;
+use crate::parser::{Figure, FigureKind, PageContent, figure_label, table_label};
 use anyhow::{Context, Result};
-use epub::doc::EpubDoc;
+use epub::doc::{EpubDoc, NavPoint};
 use html2text::from_read;
 use regex::Regex;
+use scraper::{ElementRef, Html, Node};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
-use std::sync::Arc;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+// Covers common EPUB image patterns:
+// - <img src="...">
+// - <img srcset="...">
+// - <image href="..."> (SVG)
+// - <image xlink:href="..."> (SVG)
+// NOTE: This is still a best-effort regex approach and may miss CSS background images.
+static IMAGE_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)<(?:img[^>]+(?:src=["']([^"']+)["']|srcset=["']([^"']+)["'])|image[^>]+(?:href=["']([^"']+)["']|xlink:href=["']([^"']+)["']))[^>]*>"#,
+    )
+    .unwrap()
+});
+static FIGCAPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<figcaption[^>]*>(.*?)</figcaption\s*>").unwrap());
+static TABLE_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<table\b[^>]*>").unwrap());
+static TABLE_CAPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<caption[^>]*>(.*?)</caption\s*>").unwrap());
+static ALT_TEXT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\salt\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// A node of a parsed chapter, which `scraper` only names through `ElementRef`.
+type NodeRef<'a> = <ElementRef<'a> as std::ops::Deref>::Target;
+
+/// An image in chapter HTML, with its caption when it has one.
+struct ImageRef {
+    /// Byte range of the image tag.
+    tag: Range<usize>,
+    src: String,
+    /// Text of the `<figcaption>` of the surrounding `<figure>`, or else the alt text.
+    caption: Option<String>,
+    /// Byte range of the `<figcaption>` the caption came from.
+    caption_range: Option<Range<usize>>,
+}
+
+/// Inline images of a chapter, in document order. Only the first image of a figure takes
+/// its caption.
+fn find_images(html: &str) -> Vec<ImageRef> {
+    // ASCII lowercasing keeps byte offsets, so positions found here index `html` too.
+    let lower = html.to_ascii_lowercase();
+    let mut images: Vec<ImageRef> = Vec::new();
+    for cap in IMAGE_TAG.captures_iter(html) {
+        let tag = cap.get(0).unwrap().range();
+        let src = cap
+            .get(1)
+            .or(cap.get(2))
+            .or(cap.get(3))
+            .or(cap.get(4))
+            .map(|m| m.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        let figure = lower[..tag.start]
+            .rfind("<figure")
+            .filter(|&start| !lower[start..tag.start].contains("</figure"))
+            .and_then(|start| {
+                let end = lower[tag.end..].find("</figure")?;
+                Some(start..tag.end + end)
+            });
+        let mut caption_range = figure.and_then(|figure| {
+            let found = FIGCAPTION.captures(&html[figure.clone()])?;
+            let whole = found.get(0)?.range();
+            let text = collapse(&html_to_text(found.get(1)?.as_str()));
+            Some((figure.start + whole.start..figure.start + whole.end, text))
+        });
+        if caption_range.as_ref().is_some_and(|(range, _)| {
+            images
+                .iter()
+                .any(|i| i.caption_range.as_ref() == Some(range))
+        }) {
+            caption_range = None;
+        }
+        let (caption_range, caption) = match caption_range {
+            Some((range, text)) => (Some(range), Some(text).filter(|t| !t.is_empty())),
+            None => {
+                let alt = ALT_TEXT
+                    .captures(&html[tag.clone()])
+                    .and_then(|c| c.get(1).or(c.get(2)))
+                    .map(|m| collapse(&html_to_text(m.as_str())))
+                    .filter(|t| !t.is_empty());
+                (None, alt)
+            }
+        };
+        images.push(ImageRef {
+            tag,
+            src,
+            caption,
+            caption_range,
+        });
+    }
+    images
+}
+
+/// A table with a `<caption>`.
+struct TableRef {
+    /// Byte offset of the `<table>` tag.
+    start: usize,
+    caption: String,
+    /// Byte range of the `<caption>`, which is shown above the table instead.
+    caption_range: Range<usize>,
+}
+
+/// Captioned tables of a chapter, in document order. A caption counts only when it
+/// belongs to the table itself rather than to one nested in it.
+fn find_tables(html: &str) -> Vec<TableRef> {
+    let lower = html.to_ascii_lowercase();
+    let mut tables = Vec::new();
+    for tag in TABLE_TAG.find_iter(html) {
+        let body = tag.end()
+            ..lower[tag.end()..]
+                .find("<table")
+                .map_or(html.len(), |i| tag.end() + i);
+        let Some(found) = TABLE_CAPTION.captures(&html[body.clone()]) else {
+            continue;
+        };
+        let whole = found.get(0).unwrap().range();
+        let caption = collapse(&html_to_text(&found[1]));
+        if caption.is_empty() {
+            continue;
+        }
+        tables.push(TableRef {
+            start: tag.start(),
+            caption,
+            caption_range: body.start + whole.start..body.start + whole.end,
+        });
+    }
+    tables
+}
+
+/// Where chapter text is interrupted: by an image, or by a captioned table whose caption
+/// goes above it.
+enum Break<'a> {
+    Image(&'a ImageRef),
+    Table(&'a TableRef),
+}
+
+impl Break<'_> {
+    fn start(&self) -> usize {
+        match self {
+            Break::Image(image) => image.tag.start,
+            Break::Table(table) => table.start,
+        }
+    }
+
+    /// Where the text resumes: after an image tag, or at the table itself.
+    fn end(&self) -> usize {
+        match self {
+            Break::Image(image) => image.tag.end,
+            Break::Table(table) => table.start,
+        }
+    }
+}
+
+/// Images and captioned tables of a chapter, in document order.
+fn find_breaks<'a>(images: &'a [ImageRef], tables: &'a [TableRef]) -> Vec<Break<'a>> {
+    let mut breaks: Vec<Break> = images.iter().map(Break::Image).collect();
+    breaks.extend(tables.iter().map(Break::Table));
+    breaks.sort_by_key(Break::start);
+    breaks
+}
+
+/// Plain text of an HTML fragment, without the Markdown-style markup of chapter text.
+fn html_to_text(html: &str) -> String {
+    Html::parse_fragment(html).root_element().text().collect()
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Text of `range` in a chapter, leaving out the `skip` ranges. `None` when it is blank.
+fn html_text(html: &str, range: Range<usize>, skip: &[Range<usize>]) -> Option<String> {
+    let mut fragment = String::new();
+    let mut pos = range.start;
+    for s in skip
+        .iter()
+        .filter(|s| s.start >= range.start && s.end <= range.end)
+    {
+        fragment.push_str(&html[pos..s.start]);
+        pos = s.end;
+    }
+    fragment.push_str(&html[pos..range.end]);
+    // Wrap in div to ensure block context if it was a fragment
+    let wrapped_html = format!("<div>{}</div>", fragment);
+    from_read(wrapped_html.as_bytes(), 120)
+        .ok()
+        .filter(|text| !text.trim().is_empty())
+}
 
 pub struct EpubParser {
     doc: EpubDoc<BufReader<File>>,
+    /// Table of contents entries in order, nested ones included, with the spine item
+    /// each points into.
+    toc_chapters: Vec<(usize, String)>,
 }
 
 impl EpubParser {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let doc = EpubDoc::new(path).context("Failed to open EPUB document")?;
-        Ok(Self { doc })
+        let mut toc_chapters = Vec::new();
+        toc_spine_items(&doc, &doc.toc, &mut toc_chapters);
+        Ok(Self { doc, toc_chapters })
     }
 
     pub fn get_metadata(&self) -> (String, String) {
@@ -32,6 +227,13 @@ impl EpubParser {
         (title, author)
     }
 
+    pub fn get_language(&self) -> Option<String> {
+        self.doc
+            .mdata("language")
+            .map(|v| v.value.trim().to_string())
+            .filter(|l| !l.is_empty())
+    }
+
     pub fn get_chapter_count(&self) -> usize {
         self.doc.spine.len()
     }
@@ -56,46 +258,41 @@ impl EpubParser {
         let content_str = String::from_utf8_lossy(&content_bytes);
 
         let mut result_items = Vec::new();
-        // Regex to find inline images.
-        // Covers common EPUB patterns:
-        // - <img src="...">
-        // - <img srcset="...">
-        // - <image href="..."> (SVG)
-        // - <image xlink:href="..."> (SVG)
-        // NOTE: This is still a best-effort regex approach and may miss CSS background images.
-        let re = Regex::new(
-            r#"(?i)<(?:img[^>]+(?:src=["']([^"']+)["']|srcset=["']([^"']+)["'])|image[^>]+(?:href=["']([^"']+)["']|xlink:href=["']([^"']+)["']))[^>]*>"#,
-        )
-        .unwrap();
-
+        let images = find_images(&content_str);
+        let tables = find_tables(&content_str);
+        // Captions are shown on their own line by their image or table, so they are left
+        // out of the text.
+        let mut captions: Vec<Range<usize>> = images
+            .iter()
+            .filter_map(|i| i.caption_range.clone())
+            .chain(tables.iter().map(|t| t.caption_range.clone()))
+            .collect();
+        captions.sort_by_key(|r| r.start);
+        let mut figure = 0;
+        let mut table = 0;
         let mut last_pos = 0;
 
-        for cap in re.captures_iter(&content_str) {
-            let match_start = cap.get(0).unwrap().start();
-            let match_end = cap.get(0).unwrap().end();
-
-            // Extract text before image
-            if match_start > last_pos {
-                let text_html = &content_str[last_pos..match_start];
-                // Wrap in div to ensure block context if it was a fragment
-                let wrapped_html = format!("<div>{}</div>", text_html);
-                let plain_text_res = from_read(wrapped_html.as_bytes(), 120);
-                if let Ok(plain_text) = plain_text_res {
-                    if !plain_text.trim().is_empty() {
-                        result_items.push(PageContent::Text(plain_text));
-                    }
-                }
+        for item in find_breaks(&images, &tables) {
+            // Extract text before the image or table
+            if let Some(text) = html_text(&content_str, last_pos..item.start(), &captions) {
+                result_items.push(PageContent::Text(text));
             }
+            last_pos = item.end();
+            let image = match item {
+                Break::Image(image) => image,
+                Break::Table(t) => {
+                    table += 1;
+                    result_items.push(PageContent::Caption(table_label(
+                        chapter_index,
+                        table,
+                        &t.caption,
+                    )));
+                    continue;
+                }
+            };
 
             // Extract Image
-            let mut src = cap
-                .get(1)
-                .or(cap.get(2))
-                .or(cap.get(3))
-                .or(cap.get(4))
-                .map(|m| m.as_str())
-                .unwrap_or("")
-                .to_string();
+            let mut src = image.src.clone();
 
             // If this was a srcset, take the first URL.
             // Format is typically: "url1 1x, url2 2x" or "url1 300w, url2 600w".
@@ -104,7 +301,6 @@ impl EpubParser {
                     .split(',')
                     .next()
                     .unwrap_or("")
-                    .trim()
                     .split_whitespace()
                     .next()
                     .unwrap_or("")
@@ -176,20 +372,19 @@ impl EpubParser {
                     )));
                 }
             }
-
-            last_pos = match_end;
+            if let Some(ref caption) = image.caption {
+                figure += 1;
+                result_items.push(PageContent::Caption(figure_label(
+                    chapter_index,
+                    figure,
+                    caption,
+                )));
+            }
         }
 
         // Remaining text
-        if last_pos < content_str.len() {
-            let text_html = &content_str[last_pos..];
-            let wrapped_html = format!("<div>{}</div>", text_html);
-            let plain_text_res = from_read(wrapped_html.as_bytes(), 120);
-            if let Ok(plain_text) = plain_text_res {
-                if !plain_text.trim().is_empty() {
-                    result_items.push(PageContent::Text(plain_text));
-                }
-            }
+        if let Some(text) = html_text(&content_str, last_pos..content_str.len(), &captions) {
+            result_items.push(PageContent::Text(text));
         }
 
         if result_items.is_empty() {
@@ -201,6 +396,211 @@ impl EpubParser {
         Ok(result_items)
     }
 
+    /// CFI-style element path (even steps from the root element) of the first, deepest
+    /// element whose text contains `snippet`. `None` when the text can't be matched.
+    pub fn element_path(&mut self, chapter_index: usize, snippet: &str) -> Option<Vec<usize>> {
+        let needle = normalize_text(snippet);
+        if needle.is_empty() || chapter_index >= self.doc.spine.len() {
+            return None;
+        }
+        self.doc.set_current_chapter(chapter_index);
+        let (html, _) = self.doc.get_current_str()?;
+        let doc = Html::parse_document(&html);
+
+        let mut path = Vec::new();
+        let mut current = doc.root_element();
+        if !normalize_text(&current.text().collect::<String>()).contains(&needle) {
+            return None;
+        }
+        loop {
+            let next = current
+                .children()
+                .filter_map(ElementRef::wrap)
+                .enumerate()
+                .find(|(_, child)| {
+                    normalize_text(&child.text().collect::<String>()).contains(&needle)
+                });
+            match next {
+                Some((index, child)) => {
+                    path.push((index + 1) * 2);
+                    current = child;
+                }
+                None => break,
+            }
+        }
+        Some(path)
+    }
+
+    /// KOReader (crengine) XPointers to the start and end of `snippet` in a chapter, e.g.
+    /// `/body/DocFragment[3]/body/div/p[2]/text().14`. Like [`locator::find_span`], the
+    /// match ignores markup and punctuation. `None` when the text can't be found.
+    ///
+    /// [`locator::find_span`]: crate::locator::find_span
+    pub fn xpointers(&mut self, chapter_index: usize, snippet: &str) -> Option<(String, String)> {
+        let needle: Vec<char> = snippet
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        if needle.is_empty() || chapter_index >= self.doc.spine.len() {
+            return None;
+        }
+        self.doc.set_current_chapter(chapter_index);
+        let (html, _) = self.doc.get_current_str()?;
+        let doc = Html::parse_document(&html);
+        let body = doc
+            .root_element()
+            .child_elements()
+            .find(|e| e.value().name() == "body")?;
+
+        // Every letter and digit of the body, with its text node and offset in it.
+        let mut chars = Vec::new();
+        for node in body.descendants() {
+            if let Some(text) = node.value().as_text() {
+                for (offset, c) in text.chars().enumerate() {
+                    if c.is_alphanumeric() {
+                        chars.extend(c.to_lowercase().map(|l| (l, node, offset)));
+                    }
+                }
+            }
+        }
+        let start = chars
+            .windows(needle.len())
+            .position(|w| w.iter().map(|(c, _, _)| *c).eq(needle.iter().copied()))?;
+        let (_, first, first_offset) = chars[start];
+        let (_, last, last_offset) = chars[start + needle.len() - 1];
+        let pointer = |node: NodeRef, offset: usize| -> Option<String> {
+            let mut steps: Vec<String> = node
+                .ancestors()
+                .take_while(|a| a.id() != body.id())
+                .filter_map(xpointer_step)
+                .collect();
+            steps.reverse();
+            Some(format!(
+                "/body/DocFragment[{}]/body/{}{}.{}",
+                chapter_index + 1,
+                steps.iter().map(|s| format!("{}/", s)).collect::<String>(),
+                xpointer_step(node)?,
+                offset
+            ))
+        };
+        Some((
+            pointer(first, first_offset)?,
+            pointer(last, last_offset + 1)?,
+        ))
+    }
+
+    /// The chapter a KOReader XPointer points into, and about `words` words of text from
+    /// that point on. `None` when the pointer doesn't fit the book.
+    pub fn xpointer_text(&mut self, xpointer: &str, words: usize) -> Option<(usize, String)> {
+        let rest = xpointer.strip_prefix("/body/DocFragment[")?;
+        let (fragment, rest) = rest.split_once(']')?;
+        let chapter_index = fragment.parse::<usize>().ok()?.checked_sub(1)?;
+        if chapter_index >= self.doc.spine.len() {
+            return None;
+        }
+        let rest = rest.strip_prefix("/body").unwrap_or(rest);
+        self.doc.set_current_chapter(chapter_index);
+        let (html, _) = self.doc.get_current_str()?;
+        let doc = Html::parse_document(&html);
+        let body = doc
+            .root_element()
+            .child_elements()
+            .find(|e| e.value().name() == "body")?;
+
+        let mut current: NodeRef = *body;
+        let mut offset = 0;
+        for step in rest.split('/').filter(|s| !s.is_empty()) {
+            let step = match step.rsplit_once('.') {
+                Some((step, chars)) if step.starts_with("text()") => {
+                    offset = chars.parse().unwrap_or(0);
+                    step
+                }
+                _ => step,
+            };
+            let (name, index) = match step.split_once('[') {
+                Some((name, index)) => (name, index.trim_end_matches(']').parse().ok()?),
+                None => (step, 1),
+            };
+            current = current
+                .children()
+                .filter(|n| match n.value() {
+                    Node::Text(t) => name == "text()" && !t.trim().is_empty(),
+                    Node::Element(e) => e.name() == name,
+                    _ => false,
+                })
+                .nth(index - 1)?;
+        }
+
+        let first = if current.value().is_text() {
+            current
+        } else {
+            offset = 0;
+            current
+                .descendants()
+                .find(|n| n.value().as_text().is_some_and(|t| !t.trim().is_empty()))?
+        };
+        let mut text = String::new();
+        for node in body
+            .descendants()
+            .skip_while(|n| n.id() != first.id())
+            .filter_map(|n| n.value().as_text())
+        {
+            if text.is_empty() {
+                text.extend(node.chars().skip(offset));
+            } else {
+                text.push(' ');
+                text.push_str(node);
+            }
+            if text.split_whitespace().count() > words {
+                break;
+            }
+        }
+        let text = text
+            .split_whitespace()
+            .take(words)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some((chapter_index, text))
+    }
+
+    /// Captioned images and tables of every chapter, in reading order and numbered as
+    /// the reader numbers them.
+    pub fn figures(&mut self) -> Vec<Figure> {
+        let mut figures = Vec::new();
+        for chapter in 0..self.doc.spine.len() {
+            self.doc.set_current_chapter(chapter);
+            let Some((html, _)) = self.doc.get_current_str() else {
+                continue;
+            };
+            let images = find_images(&html);
+            let tables = find_tables(&html);
+            let (mut figure, mut table) = (0, 0);
+            for item in find_breaks(&images, &tables) {
+                let (kind, label) = match item {
+                    Break::Image(ImageRef {
+                        caption: Some(caption),
+                        ..
+                    }) => {
+                        figure += 1;
+                        (FigureKind::Image, figure_label(chapter, figure, caption))
+                    }
+                    Break::Image(_) => continue,
+                    Break::Table(t) => {
+                        table += 1;
+                        (FigureKind::Table, table_label(chapter, table, &t.caption))
+                    }
+                };
+                figures.push(Figure {
+                    chapter,
+                    kind,
+                    label,
+                });
+            }
+        }
+        figures
+    }
+
     pub fn get_toc(&self) -> Vec<String> {
         if self.doc.toc.is_empty() {
             // Fallback: list chapters by index
@@ -212,6 +612,20 @@ impl EpubParser {
         }
     }
 
+    /// The table of contents label of a spine item: the first entry pointing into it, or
+    /// the last one before it when it carries on a section split across files. Items
+    /// ahead of the first entry, such as a cover, have none.
+    pub fn chapter_title(&self, chapter: usize) -> Option<String> {
+        if let Some((_, label)) = self.toc_chapters.iter().find(|(c, _)| *c == chapter) {
+            return Some(label.clone());
+        }
+        self.toc_chapters
+            .iter()
+            .filter(|(c, _)| *c < chapter)
+            .max_by_key(|(c, _)| *c)
+            .map(|(_, label)| label.clone())
+    }
+
     pub fn get_cover(&mut self) -> Option<image::DynamicImage> {
         self.doc
             .get_cover()
@@ -259,3 +673,59 @@ impl EpubParser {
         best.map(|(_, img)| img)
     }
 }
+
+/// crengine's name for a node among its siblings: `p`, `p[2]`, `text()`, `text()[2]`.
+/// Blank text nodes don't count, as crengine drops them.
+fn xpointer_step(node: NodeRef) -> Option<String> {
+    let parent = node.parent()?;
+    let (name, siblings): (&str, Vec<_>) = match node.value() {
+        Node::Text(_) => (
+            "text()",
+            parent
+                .children()
+                .filter(|n| n.value().as_text().is_some_and(|t| !t.trim().is_empty()))
+                .collect(),
+        ),
+        Node::Element(e) => (
+            e.name(),
+            parent
+                .children()
+                .filter(|n| n.value().as_element().is_some_and(|s| s.name() == e.name()))
+                .collect(),
+        ),
+        _ => return None,
+    };
+    if siblings.len() > 1 {
+        let index = siblings.iter().position(|n| n.id() == node.id())? + 1;
+        Some(format!("{}[{}]", name, index))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Flattens `points` into `(spine item, label)` pairs, parents before their children.
+/// Entries pointing outside the spine are left out.
+fn toc_spine_items(
+    doc: &EpubDoc<BufReader<File>>,
+    points: &[NavPoint],
+    out: &mut Vec<(usize, String)>,
+) {
+    for point in points {
+        let content = point.content.to_string_lossy();
+        let path = PathBuf::from(content.split('#').next().unwrap_or_default());
+        let label = point.label.trim();
+        if let Some(chapter) = doc.resource_uri_to_chapter(&path) {
+            if !label.is_empty() {
+                out.push((chapter, label.to_string()));
+            }
+        }
+        toc_spine_items(doc, &point.children, out);
+    }
+}
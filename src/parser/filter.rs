@@ -0,0 +1,180 @@
+//! Cleanup rules for book text from `tbook.toml`: watermarks stripped, boilerplate
+//! rewritten, promo paragraphs dropped. They run over each chapter as the parser hands
+//! it over, before the reader splits it into lines, so search and notes see the same
+//! text the reader shows.
+
+use super::PageContent;
+use crate::config::{TextFilterConfig, wildcard_match};
+use regex::Regex;
+
+#[derive(Clone, Debug)]
+pub struct TextFilter {
+    /// Lowercase wildcard pattern for the books it is for; every book when `None`.
+    books: Option<String>,
+    regex: Regex,
+    replace: String,
+    drop: bool,
+}
+
+impl TextFilter {
+    /// `None` for an empty pattern. A pattern that doesn't compile as a regular
+    /// expression is matched as literal text, as in search.
+    pub fn new(config: &TextFilterConfig) -> Option<Self> {
+        let pattern = config.pattern.trim();
+        if pattern.is_empty() {
+            return None;
+        }
+        let regex = Regex::new(pattern)
+            .or_else(|_| Regex::new(&regex::escape(pattern)))
+            .ok()?;
+        Some(Self {
+            books: config
+                .books
+                .as_deref()
+                .map(|b| b.trim().to_lowercase())
+                .filter(|b| !b.is_empty()),
+            regex,
+            replace: config.replace.clone().unwrap_or_default(),
+            drop: config.drop.unwrap_or(false),
+        })
+    }
+
+    /// Whether the filter is for the book with this title and file, going by either the
+    /// title or the file name.
+    pub fn applies_to(&self, title: &str, path: &str) -> bool {
+        let Some(ref books) = self.books else {
+            return true;
+        };
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        wildcard_match(books, &title.to_lowercase()) || wildcard_match(books, &name)
+    }
+
+    /// The line with the filter applied, or `None` when it drops the line.
+    fn filter_line(&self, line: &str) -> Option<String> {
+        if !self.regex.is_match(line) {
+            return Some(line.to_string());
+        }
+        if self.drop {
+            return None;
+        }
+        Some(
+            self.regex
+                .replace_all(line, self.replace.as_str())
+                .into_owned(),
+        )
+    }
+}
+
+/// Runs `filters` over a chapter's text and captions. Lines a filter drops or leaves
+/// blank go, along with the blank line after them, so no gap is left behind.
+pub fn apply(filters: &[TextFilter], content: Vec<PageContent>) -> Vec<PageContent> {
+    if filters.is_empty() {
+        return content;
+    }
+    content
+        .into_iter()
+        .filter_map(|block| match block {
+            PageContent::Text(text) => {
+                let text = filter_text(filters, &text);
+                (!text.trim().is_empty()).then_some(PageContent::Text(text))
+            }
+            PageContent::Caption(text) => {
+                let text = filter_text(filters, &text);
+                (!text.trim().is_empty()).then_some(PageContent::Caption(text))
+            }
+            image => Some(image),
+        })
+        .collect()
+}
+
+fn filter_text(filters: &[TextFilter], text: &str) -> String {
+    let mut kept: Vec<String> = Vec::new();
+    let mut removed = false;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !removed {
+                kept.push(line.to_string());
+            }
+            removed = false;
+            continue;
+        }
+        let filtered = filters
+            .iter()
+            .try_fold(line.to_string(), |line, f| f.filter_line(&line))
+            .filter(|l| !l.trim().is_empty());
+        removed = filtered.is_none();
+        kept.extend(filtered);
+    }
+    kept.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(pattern: &str, replace: Option<&str>, drop: bool) -> TextFilter {
+        TextFilter::new(&TextFilterConfig {
+            pattern: pattern.to_string(),
+            replace: replace.map(str::to_string),
+            drop: Some(drop),
+            books: None,
+        })
+        .unwrap()
+    }
+
+    fn texts(content: &[PageContent]) -> Vec<&str> {
+        content
+            .iter()
+            .map(|block| match block {
+                PageContent::Text(t) | PageContent::Caption(t) => t.as_str(),
+                PageContent::Image(_) => "[image]",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn watermarks_go_and_promo_paragraphs_are_dropped() {
+        let filters = [
+            filter(r"\s*OceanofPDF\.com\s*", Some(" "), false),
+            filter("(?i)^get your free", None, true),
+            filter(r"Ch\. (\d+)", Some("Chapter $1"), false),
+        ];
+        let content = vec![
+            PageContent::Text(
+                "It was a dark OceanofPDF.com night.\n\nGet your free ebook now!\n\nCh. 2 begins."
+                    .to_string(),
+            ),
+            PageContent::Text("OceanofPDF.com".to_string()),
+            PageContent::Caption("Fig. 1 OceanofPDF.com".to_string()),
+        ];
+        assert_eq!(
+            texts(&apply(&filters, content)),
+            vec!["It was a dark night.\n\nChapter 2 begins.", "Fig. 1 ",]
+        );
+    }
+
+    #[test]
+    fn filters_are_picked_by_title_or_file_name() {
+        let for_books = |books: &str| {
+            TextFilter::new(&TextFilterConfig {
+                pattern: "(unbalanced".to_string(),
+                books: Some(books.to_string()),
+                ..TextFilterConfig::default()
+            })
+            .unwrap()
+        };
+        assert!(for_books("*dune*").applies_to("Dune Messiah", "/books/a.epub"));
+        assert!(for_books("*.pdf").applies_to("Paper", "/papers/attention.pdf"));
+        assert!(!for_books("*.pdf").applies_to("Dune", "/books/dune.epub"));
+        // A pattern that isn't a regular expression is matched as written.
+        let literal = for_books("*");
+        assert_eq!(
+            literal.filter_line("an (unbalanced aside"),
+            Some("an  aside".to_string())
+        );
+        assert!(TextFilter::new(&TextFilterConfig::default()).is_none());
+    }
+}
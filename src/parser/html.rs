@@ -0,0 +1,193 @@
+use crate::parser::PageContent;
+use anyhow::{Context, Result};
+use html2text::from_read;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Reads a standalone HTML document (e.g. a saved web article) as a single-chapter book.
+pub struct HtmlParser {
+    title: String,
+    author: String,
+    language: Option<String>,
+    body: String,
+}
+
+impl HtmlParser {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path).context("Failed to open HTML document")?;
+        let doc = Html::parse_document(&raw);
+
+        let title = select_text(&doc, "title")
+            .or_else(|| select_text(&doc, "h1"))
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("Unknown Title")
+                    .to_string()
+            });
+        let author = select_attr(&doc, r#"meta[name="author"]"#, "content")
+            .unwrap_or_else(|| "Unknown Author".to_string());
+        let language = select_attr(&doc, "html", "lang");
+        let body = Selector::parse("body")
+            .ok()
+            .and_then(|sel| doc.select(&sel).next().map(|b| b.inner_html()))
+            .unwrap_or(raw);
+
+        Ok(Self {
+            title,
+            author,
+            language,
+            body,
+        })
+    }
+
+    pub fn get_metadata(&self) -> (String, String) {
+        (self.title.clone(), self.author.clone())
+    }
+
+    pub fn get_language(&self) -> Option<String> {
+        self.language.clone()
+    }
+
+    pub fn get_chapter_count(&self) -> usize {
+        1
+    }
+
+    pub fn get_chapter_content(&mut self, chapter_index: usize) -> Result<Vec<PageContent>> {
+        if chapter_index > 0 {
+            return Err(anyhow::anyhow!("Chapter index out of bounds"));
+        }
+
+        let wrapped_html = format!("<div>{}</div>", self.body);
+        let text = from_read(wrapped_html.as_bytes(), 120).unwrap_or_default();
+        if text.trim().is_empty() {
+            return Ok(vec![PageContent::Text(
+                " [ Document contains no renderable text ] ".to_string(),
+            )]);
+        }
+        Ok(vec![PageContent::Text(text)])
+    }
+
+    pub fn get_toc(&self) -> Vec<String> {
+        vec![self.title.clone()]
+    }
+}
+
+/// The readable part of a fetched web page.
+pub struct Article {
+    pub title: String,
+    pub byline: Option<String>,
+    pub content_html: String,
+}
+
+/// Best-effort readability extraction: prefer `<article>`, otherwise the element whose
+/// direct `<p>` children carry the most text, otherwise the whole `<body>`.
+pub fn extract_article(raw: &str) -> Article {
+    let doc = Html::parse_document(raw);
+
+    let title = select_attr(&doc, r#"meta[property="og:title"]"#, "content")
+        .or_else(|| select_text(&doc, "title"))
+        .or_else(|| select_text(&doc, "h1"))
+        .unwrap_or_else(|| "Untitled Article".to_string());
+    let byline = select_attr(&doc, r#"meta[name="author"]"#, "content")
+        .or_else(|| select_attr(&doc, r#"meta[property="article:author"]"#, "content"));
+
+    let content = best_article_element(&doc)
+        .or_else(|| best_paragraph_container(&doc))
+        .map(|el| el.inner_html())
+        .or_else(|| {
+            Selector::parse("body")
+                .ok()
+                .and_then(|sel| doc.select(&sel).next().map(|b| b.inner_html()))
+        })
+        .unwrap_or_default();
+
+    Article {
+        title,
+        byline,
+        content_html: strip_boilerplate(&content),
+    }
+}
+
+/// Wraps an extracted article in a minimal HTML document that `HtmlParser` can read back.
+pub fn render_article(article: &Article, source_url: &str) -> String {
+    let author = article.byline.as_deref().unwrap_or("Web Article");
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<meta name=\"author\" content=\"{}\">\n<meta name=\"tbook:source\" content=\"{}\">\n</head>\n<body>\n<h1>{}</h1>\n<p><a href=\"{}\">{}</a></p>\n{}\n</body>\n</html>\n",
+        escape_html(&article.title),
+        escape_html(author),
+        escape_html(source_url),
+        escape_html(&article.title),
+        escape_html(source_url),
+        escape_html(source_url),
+        article.content_html
+    )
+}
+
+fn best_article_element(doc: &Html) -> Option<ElementRef<'_>> {
+    let sel = Selector::parse("article").ok()?;
+    doc.select(&sel)
+        .map(|el| (text_len(&el), el))
+        .filter(|(len, _)| *len >= 250)
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, el)| el)
+}
+
+fn best_paragraph_container(doc: &Html) -> Option<ElementRef<'_>> {
+    let sel = Selector::parse("p").ok()?;
+    let mut scores = HashMap::new();
+    for p in doc.select(&sel) {
+        let len = text_len(&p);
+        // Skip short paragraphs like captions, bylines, or cookie notices.
+        if len < 25 {
+            continue;
+        }
+        if let Some(parent) = p.parent() {
+            *scores.entry(parent.id()).or_insert(0usize) += len;
+        }
+    }
+
+    let (best_id, _) = scores.into_iter().max_by_key(|(_, score)| *score)?;
+    doc.tree.get(best_id).and_then(ElementRef::wrap)
+}
+
+fn strip_boilerplate(html: &str) -> String {
+    let mut cleaned = html.to_string();
+    for tag in [
+        "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "iframe",
+    ] {
+        if let Ok(re) = Regex::new(&format!(r"(?is)<{tag}\b.*?</{tag}>")) {
+            cleaned = re.replace_all(&cleaned, "").to_string();
+        }
+    }
+    cleaned
+}
+
+fn text_len(el: &ElementRef) -> usize {
+    el.text().map(|t| t.trim().len()).sum()
+}
+
+fn select_text(doc: &Html, selector: &str) -> Option<String> {
+    let sel = Selector::parse(selector).ok()?;
+    let text = doc.select(&sel).next()?.text().collect::<String>();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn select_attr(doc: &Html, selector: &str, attr: &str) -> Option<String> {
+    let sel = Selector::parse(selector).ok()?;
+    let value = doc.select(&sel).next()?.value().attr(attr)?.trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,7 +1,12 @@
+pub mod djvu;
 pub mod epub;
+pub mod filter;
+pub mod html;
 pub mod pdf;
 
+pub use self::djvu::DjvuParser;
 pub use self::epub::EpubParser;
+pub use self::html::HtmlParser;
 pub use self::pdf::PdfParser;
 
 use anyhow::Result;
@@ -12,18 +17,75 @@ use std::sync::Arc;
 pub enum PageContent {
     Text(String),
     Image(Arc<DynamicImage>),
+    /// Numbered caption of the image before it, e.g. `Fig. 3.1: The Pequod`.
+    Caption(String),
 }
 
+/// What a [`Figure`] captions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FigureKind {
+    Image,
+    Table,
+}
+
+/// A captioned image or table. Images are listed in the table of contents under their
+/// chapter; both are listed in the book's list of figures and tables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Figure {
+    pub chapter: usize,
+    pub kind: FigureKind,
+    /// The caption as the reader shows it.
+    pub label: String,
+}
+
+/// Caption text for the `number`th captioned image (from 1) of a chapter.
+pub fn figure_label(chapter: usize, number: usize, caption: &str) -> String {
+    format!("Fig. {}.{}: {}", chapter + 1, number, caption)
+}
+
+/// Caption text for the `number`th captioned table (from 1) of a chapter.
+pub fn table_label(chapter: usize, number: usize, caption: &str) -> String {
+    format!("Table {}.{}: {}", chapter + 1, number, caption)
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum BookParser {
     Epub(EpubParser),
     Pdf(PdfParser),
+    Html(HtmlParser),
+    Djvu(DjvuParser),
 }
 
 impl BookParser {
+    /// Picks a backend from the file extension, defaulting to EPUB.
+    pub fn open(path: &str) -> Result<Self> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".pdf") {
+            Ok(BookParser::Pdf(PdfParser::new(path)?))
+        } else if lower.ends_with(".html") || lower.ends_with(".htm") {
+            Ok(BookParser::Html(HtmlParser::new(path)?))
+        } else if lower.ends_with(".djvu") || lower.ends_with(".djv") {
+            Ok(BookParser::Djvu(DjvuParser::new(path)?))
+        } else {
+            Ok(BookParser::Epub(EpubParser::new(path)?))
+        }
+    }
+
     pub fn get_metadata(&self) -> (String, String) {
         match self {
             BookParser::Epub(p) => p.get_metadata(),
             BookParser::Pdf(p) => p.get_metadata(),
+            BookParser::Html(p) => p.get_metadata(),
+            BookParser::Djvu(p) => p.get_metadata(),
+        }
+    }
+
+    /// Language the book declares itself to be in, as a tag like "fr" or "pt-BR".
+    pub fn get_language(&self) -> Option<String> {
+        match self {
+            BookParser::Epub(p) => p.get_language(),
+            BookParser::Html(p) => p.get_language(),
+            BookParser::Pdf(_) | BookParser::Djvu(_) => None,
         }
     }
 
@@ -31,6 +93,8 @@ impl BookParser {
         match self {
             BookParser::Epub(p) => p.get_chapter_count(),
             BookParser::Pdf(p) => p.get_chapter_count(),
+            BookParser::Html(p) => p.get_chapter_count(),
+            BookParser::Djvu(p) => p.get_chapter_count(),
         }
     }
 
@@ -38,6 +102,62 @@ impl BookParser {
         match self {
             BookParser::Epub(p) => p.get_chapter_content(index),
             BookParser::Pdf(p) => p.get_chapter_content(index),
+            BookParser::Html(p) => p.get_chapter_content(index),
+            BookParser::Djvu(p) => p.get_chapter_content(index),
+        }
+    }
+
+    /// Toggles PDF reflow; other formats already flow naturally.
+    pub fn set_reflow(&mut self, reflow: bool) {
+        if let BookParser::Pdf(p) = self {
+            p.set_reflow(reflow);
+        }
+    }
+
+    pub fn is_pdf(&self) -> bool {
+        matches!(self, BookParser::Pdf(_))
+    }
+
+    /// Page-based formats map one chapter to one page.
+    pub fn is_paged(&self) -> bool {
+        matches!(self, BookParser::Pdf(_) | BookParser::Djvu(_))
+    }
+
+    /// Page count for page-based formats.
+    pub fn get_page_count(&self) -> Option<usize> {
+        self.is_paged().then(|| self.get_chapter_count())
+    }
+
+    /// Element path for a locator, where the format has a DOM to point into.
+    pub fn element_path(&mut self, index: usize, snippet: &str) -> Option<Vec<usize>> {
+        match self {
+            BookParser::Epub(p) => p.element_path(index, snippet),
+            _ => None,
+        }
+    }
+
+    /// KOReader XPointers to the start and end of `snippet` in a chapter. EPUB only.
+    pub fn xpointers(&mut self, index: usize, snippet: &str) -> Option<(String, String)> {
+        match self {
+            BookParser::Epub(p) => p.xpointers(index, snippet),
+            _ => None,
+        }
+    }
+
+    /// The chapter and text a KOReader XPointer points at. EPUB only.
+    pub fn xpointer_text(&mut self, xpointer: &str, words: usize) -> Option<(usize, String)> {
+        match self {
+            BookParser::Epub(p) => p.xpointer_text(xpointer, words),
+            _ => None,
+        }
+    }
+
+    /// The table of contents label of a chapter. Pages of PDFs and DjVu files have none.
+    pub fn chapter_title(&self, index: usize) -> Option<String> {
+        match self {
+            BookParser::Epub(p) => p.chapter_title(index),
+            BookParser::Html(p) => p.get_toc().into_iter().next(),
+            BookParser::Pdf(_) | BookParser::Djvu(_) => None,
         }
     }
 
@@ -45,6 +165,17 @@ impl BookParser {
         match self {
             BookParser::Epub(p) => p.get_toc(),
             BookParser::Pdf(p) => p.get_toc(),
+            BookParser::Html(p) => p.get_toc(),
+            BookParser::Djvu(p) => p.get_toc(),
+        }
+    }
+
+    /// Captioned images and tables of every chapter, in reading order; only EPUBs mark
+    /// them up.
+    pub fn figures(&mut self) -> Vec<Figure> {
+        match self {
+            BookParser::Epub(p) => p.figures(),
+            _ => Vec::new(),
         }
     }
 
@@ -7,6 +7,7 @@ use std::path::Path;
 pub struct PdfParser {
     path: String,
     page_count: usize,
+    reflow: bool,
 }
 
 impl PdfParser {
@@ -24,6 +25,7 @@ impl PdfParser {
         Ok(Self {
             path: path_str,
             page_count,
+            reflow: false,
         })
     }
 
@@ -40,6 +42,12 @@ impl PdfParser {
         self.page_count
     }
 
+    /// Reflow mode drops `-layout` and rebuilds flowing paragraphs, which reads far better
+    /// for two-column academic papers than the raw page layout.
+    pub fn set_reflow(&mut self, reflow: bool) {
+        self.reflow = reflow;
+    }
+
     pub fn get_cover_image_preview(&self) -> Result<image::DynamicImage> {
         // Lower DPI for faster preview rendering in library.
         self.render_page_image_with_dpi(1, 120)
@@ -52,16 +60,12 @@ impl PdfParser {
 
         let mut cmd = deps::resolve_poppler_command("pdftotext")
             .context("Failed to locate pdftotext. Bundle or install poppler-utils.")?;
+        cmd.args(["-f", &page_num.to_string(), "-l", &page_num.to_string()]);
+        if !self.reflow {
+            cmd.arg("-layout"); // Preserve layout
+        }
         let output = cmd
-            .args(&[
-                "-f",
-                &page_num.to_string(),
-                "-l",
-                &page_num.to_string(),
-                "-layout", // Preserve layout
-                &self.path,
-                "-", // Output to stdout
-            ])
+            .args([&self.path, "-"]) // Output to stdout
             .output()
             .context("Failed to execute pdftotext. Ensure poppler-utils is installed.")?;
 
@@ -70,7 +74,12 @@ impl PdfParser {
             return Err(anyhow::anyhow!("pdftotext failed: {}", stderr));
         }
 
-        let text = String::from_utf8_lossy(&output.stdout).to_string();
+        let raw = String::from_utf8_lossy(&output.stdout);
+        let text = if self.reflow {
+            reflow_text(&raw)
+        } else {
+            crop_margin(&raw)
+        };
 
         if text.trim().is_empty() {
             // For scanned/image-based PDFs, fall back to rendering the page as an image.
@@ -108,7 +117,7 @@ impl PdfParser {
         let mut cmd = deps::resolve_poppler_command("pdftoppm")
             .context("Failed to locate pdftoppm. Bundle or install poppler-utils.")?;
         let output = cmd
-            .args(&[
+            .args([
                 "-f",
                 &page_num.to_string(),
                 "-l",
@@ -142,3 +151,81 @@ impl PdfParser {
             .collect()
     }
 }
+
+/// Removes the indentation shared by every non-blank line (the page's left margin).
+fn crop_margin(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    text.lines()
+        .map(|l| l.get(indent..).unwrap_or(l.trim_start()).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Joins wrapped lines into paragraphs, repairs hyphenated breaks, and drops
+/// page numbers and running headers/footers at the edges of the page.
+fn reflow_text(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().map(str::trim).collect();
+
+    // Headers/footers: short lines at the very top or bottom that carry a page number.
+    for _ in 0..2 {
+        while lines.first().is_some_and(|l| l.is_empty()) {
+            lines.remove(0);
+        }
+        if lines.first().is_some_and(|l| is_running_header(l)) {
+            lines.remove(0);
+        }
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        if lines.last().is_some_and(|l| is_running_header(l)) {
+            lines.pop();
+        }
+    }
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if current.is_empty() {
+            current.push_str(line);
+        } else if current.ends_with('-')
+            && current
+                .chars()
+                .rev()
+                .nth(1)
+                .is_some_and(|c| c.is_alphabetic())
+            && line.chars().next().is_some_and(|c| c.is_lowercase())
+        {
+            current.pop();
+            current.push_str(line);
+        } else {
+            current.push(' ');
+            current.push_str(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs.join("\n\n")
+}
+
+fn is_running_header(line: &str) -> bool {
+    let words = line.split_whitespace().count();
+    if words == 0 || words > 8 {
+        return false;
+    }
+    let first = line.split_whitespace().next().unwrap_or("");
+    let last = line.split_whitespace().last().unwrap_or("");
+    let is_number = |w: &str| !w.is_empty() && w.chars().all(|c| c.is_ascii_digit());
+    is_number(first) || is_number(last) || line.to_lowercase().starts_with("page ")
+}
@@ -0,0 +1,158 @@
+//! Spaced-repetition scheduling for vocabulary review, after SuperMemo's SM-2: each
+//! word keeps an ease factor that recall grades nudge up or down, and the gap before
+//! it comes back grows by that factor every time it is remembered.
+
+use chrono::{Days, NaiveDate};
+
+/// Ease a word starts with, and the lowest it can drop to.
+pub const START_EASE: f64 = 2.5;
+pub const MIN_EASE: f64 = 1.3;
+
+/// How well a word was recalled, from the 1–4 keys in the review view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grade {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    pub fn from_key(key: char) -> Option<Grade> {
+        match key {
+            '1' => Some(Grade::Again),
+            '2' => Some(Grade::Hard),
+            '3' => Some(Grade::Good),
+            '4' => Some(Grade::Easy),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Grade::Again => "Again",
+            Grade::Hard => "Hard",
+            Grade::Good => "Good",
+            Grade::Easy => "Easy",
+        }
+    }
+}
+
+/// Where a word stands in its review cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Schedule {
+    pub ease: f64,
+    /// Days until the word is due again; 0 means later today.
+    pub interval: u32,
+    /// Reviews in a row the word was remembered.
+    pub repetitions: u32,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            ease: START_EASE,
+            interval: 0,
+            repetitions: 0,
+        }
+    }
+}
+
+impl Schedule {
+    /// The schedule after a review graded `grade`.
+    pub fn next(&self, grade: Grade) -> Schedule {
+        let grown = |factor: f64| ((self.interval.max(1) as f64) * factor).round() as u32;
+        let (ease, interval) = match grade {
+            // Forgotten words start over and come back in the same session.
+            Grade::Again => {
+                return Schedule {
+                    ease: (self.ease - 0.2).max(MIN_EASE),
+                    interval: 0,
+                    repetitions: 0,
+                };
+            }
+            Grade::Hard => {
+                let interval = if self.repetitions == 0 {
+                    1
+                } else {
+                    grown(1.2).max(self.interval + 1)
+                };
+                (self.ease - 0.15, interval)
+            }
+            Grade::Good => {
+                let interval = match self.repetitions {
+                    0 => 1,
+                    1 => 6,
+                    _ => grown(self.ease),
+                };
+                (self.ease, interval)
+            }
+            Grade::Easy => {
+                let interval = match self.repetitions {
+                    0 => 4,
+                    _ => grown(self.ease * 1.3),
+                };
+                (self.ease + 0.15, interval)
+            }
+        };
+        Schedule {
+            ease: ease.max(MIN_EASE),
+            interval,
+            repetitions: self.repetitions + 1,
+        }
+    }
+
+    /// The day a word reviewed on `today` is due again.
+    pub fn due(&self, today: NaiveDate) -> NaiveDate {
+        today
+            .checked_add_days(Days::new(self.interval as u64))
+            .unwrap_or(today)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembered_words_come_back_further_apart() {
+        let first = Schedule::default().next(Grade::Good);
+        assert_eq!((first.interval, first.repetitions), (1, 1));
+        let second = first.next(Grade::Good);
+        assert_eq!(second.interval, 6);
+        let third = second.next(Grade::Good);
+        assert_eq!(third.interval, 15);
+        assert_eq!(third.ease, START_EASE);
+
+        let easy = second.next(Grade::Easy);
+        assert!(easy.interval > third.interval);
+        assert!(easy.ease > START_EASE);
+        let hard = second.next(Grade::Hard);
+        assert_eq!(hard.interval, 7);
+        assert!(hard.ease < START_EASE);
+    }
+
+    #[test]
+    fn forgotten_words_start_over_with_less_ease() {
+        let mut schedule = Schedule::default();
+        for _ in 0..3 {
+            schedule = schedule.next(Grade::Good);
+        }
+        let again = schedule.next(Grade::Again);
+        assert_eq!((again.interval, again.repetitions), (0, 0));
+        assert!((again.ease - 2.3).abs() < 1e-9);
+
+        let mut floor = again;
+        for _ in 0..20 {
+            floor = floor.next(Grade::Again);
+        }
+        assert_eq!(floor.ease, MIN_EASE);
+
+        let today = NaiveDate::from_ymd_opt(2024, 2, 27).unwrap();
+        assert_eq!(again.due(today), today);
+        assert_eq!(
+            schedule.due(today),
+            NaiveDate::from_ymd_opt(2024, 3, 13).unwrap()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! The pattern behind both in-book and library-wide search. A query is a regular
+//! expression when it compiles as one and literal text when it doesn't, so `whale|shark`
+//! finds either word while a stray `(` still finds a parenthesis. Case is ignored unless
+//! asked otherwise.
+
+use regex::{Regex, RegexBuilder};
+
+/// Words of context on each side of a match in a search snippet.
+const SNIPPET_WORDS: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct SearchPattern {
+    regex: Regex,
+    /// True when the query could not be read as a regular expression.
+    pub literal: bool,
+}
+
+impl SearchPattern {
+    /// `None` for an empty query.
+    pub fn new(query: &str, case_sensitive: bool) -> Option<Self> {
+        let query = query.trim();
+        if query.is_empty() {
+            return None;
+        }
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+        };
+        match build(query) {
+            Ok(regex) => Some(Self {
+                regex,
+                literal: false,
+            }),
+            Err(_) => Some(Self {
+                regex: build(&regex::escape(query)).ok()?,
+                literal: true,
+            }),
+        }
+    }
+
+    /// Whether the query is plain words, with nothing a regular expression would read
+    /// differently.
+    pub fn is_plain(query: &str) -> bool {
+        let query = query.trim();
+        regex::escape(query) == query
+    }
+
+    /// Byte ranges of the matches in `text`. Patterns that can match nothing, like `a*`,
+    /// only count where they match something.
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.regex
+            .find_iter(text)
+            .filter(|m| m.start() < m.end())
+            .map(|m| (m.start(), m.end()))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_iter(text).next().is_some()
+    }
+
+    /// Offset of the first match in the collapsed-whitespace sense used by
+    /// [`crate::locator::Locator`], snapped to the start of its word.
+    pub fn find_offset(&self, text: &str) -> Option<usize> {
+        let collapsed = collapse(text);
+        let (start, _) = self.find_iter(&collapsed).next()?;
+        let word_start = collapsed[..start].rfind(' ').map_or(0, |p| p + 1);
+        Some(collapsed[..word_start].chars().count())
+    }
+
+    /// A few words either side of the first match, with the match in brackets, e.g.
+    /// `...the white [whale] swam...`.
+    pub fn snippet(&self, text: &str) -> Option<String> {
+        let collapsed = collapse(text);
+        let (start, end) = self.find_iter(&collapsed).next()?;
+        let before: Vec<&str> = collapsed[..start].split(' ').collect();
+        let after: Vec<&str> = collapsed[end..].split(' ').collect();
+        // The words the match starts and ends inside are kept whole.
+        let (lead, head) = before.split_at(before.len() - 1);
+        let (tail, trail) = after.split_at(1);
+        let skipped = lead.len().saturating_sub(SNIPPET_WORDS);
+        let mut snippet = String::new();
+        if skipped > 0 {
+            snippet.push_str("...");
+        }
+        for word in &lead[skipped..] {
+            snippet.push_str(word);
+            snippet.push(' ');
+        }
+        snippet.push_str(&format!(
+            "{}[{}]{}",
+            head[0],
+            &collapsed[start..end],
+            tail[0]
+        ));
+        for word in trail.iter().take(SNIPPET_WORDS) {
+            snippet.push(' ');
+            snippet.push_str(word);
+        }
+        if trail.len() > SNIPPET_WORDS {
+            snippet.push_str("...");
+        }
+        Some(snippet)
+    }
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_patterns_unless_they_do_not_compile() {
+        let either = SearchPattern::new("whale|shark", false).unwrap();
+        assert!(!either.literal);
+        let text = "A Shark, then a whale.";
+        assert_eq!(either.find_iter(text).count(), 2);
+
+        let strict = SearchPattern::new("whale|shark", true).unwrap();
+        assert_eq!(strict.find_iter(text).collect::<Vec<_>>(), vec![(16, 21)]);
+
+        let broken = SearchPattern::new("(aside", false).unwrap();
+        assert!(broken.literal);
+        assert!(broken.is_match("as said (aside) here"));
+        assert!(SearchPattern::new("  ", false).is_none());
+        assert!(!SearchPattern::new("a*", false).unwrap().is_match("xyz"));
+
+        assert!(SearchPattern::is_plain("white whale"));
+        assert!(!SearchPattern::is_plain("wh.le"));
+    }
+
+    #[test]
+    fn snippets_show_the_match_in_context() {
+        let text = "one two three four five six seven eight nine ten  the   white whale \
+                    swam on and on past eleven twelve thirteen fourteen fifteen sixteen";
+        let pattern = SearchPattern::new("wh.te wh", false).unwrap();
+        assert_eq!(
+            pattern.snippet(text).unwrap(),
+            "...four five six seven eight nine ten the [white wh]ale swam on and on past \
+             eleven twelve thirteen..."
+        );
+        assert_eq!(
+            pattern.find_offset(text),
+            Some(crate::locator::find_offset(text, "white").unwrap())
+        );
+        assert_eq!(
+            SearchPattern::new("one", false).unwrap().snippet("one two"),
+            Some("[one] two".to_string())
+        );
+    }
+}
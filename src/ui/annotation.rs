@@ -1,8 +1,10 @@
-use crate::app::{AnnotationKind, App, Theme};
+use crate::app::{App, Theme};
+use crate::library::{self, AnnotationKind};
+use crate::ui::ellipsize;
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
@@ -14,17 +16,28 @@ pub fn render_add(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Length(3), Constraint::Min(0)])
         .split(f.area());
 
-    let help = Paragraph::new(" Type your note and press Enter to save, Esc to cancel ")
-        .style(Style::default().fg(fg).bg(bg));
+    let (help_text, title) = match app.llm_draft {
+        Some(ref draft) if draft.pending => (
+            " Generating summary... (Esc to cancel) ".to_string(),
+            format!(" Draft: {} (generated) ", draft.scope),
+        ),
+        Some(ref draft) => (
+            " Generated draft: edit, then Enter to save as a summary note, Esc to discard "
+                .to_string(),
+            format!(" Draft: {} (generated) ", draft.scope),
+        ),
+        None => (
+            " Type your note and press Enter to save, Esc to cancel ".to_string(),
+            " Add Annotation/Note ".to_string(),
+        ),
+    };
+    let help = Paragraph::new(help_text).style(Style::default().fg(fg).bg(bg));
     f.render_widget(help, chunks[0]);
 
     let input = Paragraph::new(app.annotation_note.as_str())
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Add Annotation/Note "),
-        )
-        .style(Style::default().fg(fg).bg(bg));
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .style(Style::default().fg(fg).bg(bg))
+        .wrap(Wrap { trim: false });
     f.render_widget(input, chunks[1]);
 }
 
@@ -35,12 +48,10 @@ pub fn render_list(f: &mut Frame, app: &mut App) {
         .margin(2)
         .constraints([Constraint::Min(0), Constraint::Length(1)])
         .split(f.area());
-
-    let kind_color = |kind: &str| match AnnotationKind::from_str(kind) {
-        AnnotationKind::Highlight => Color::Rgb(200, 170, 80),
-        AnnotationKind::Question => Color::Rgb(120, 160, 220),
-        AnnotationKind::Summary => Color::Rgb(140, 200, 140),
-    };
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(chunks[0]);
 
     let items: Vec<ListItem> = app
         .current_annotations
@@ -52,16 +63,17 @@ pub fn render_list(f: &mut Frame, app: &mut App) {
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD)
             } else {
-                Style::default().fg(kind_color(&a.kind)).bg(bg)
+                Style::default().fg(kind_color(app, &a.kind)).bg(bg)
             };
             let note = a.note.as_deref().unwrap_or("No note");
-            let kind = AnnotationKind::from_str(&a.kind).label();
+            let kind = library::kind_label(&a.kind);
             ListItem::new(format!(
-                "{} Ch {}: {}... [{}]",
+                "#{} {} Ch {}: {} [{}]",
+                a.id,
                 kind,
                 a.chapter + 1,
-                &a.content[..std::cmp::min(20, a.content.len())],
-                note
+                ellipsize(&a.content, 24),
+                ellipsize(note, 30)
             ))
             .style(style)
         })
@@ -70,7 +82,7 @@ pub fn render_list(f: &mut Frame, app: &mut App) {
     let list = List::new(items)
         .block(
             Block::default()
-                .title(format!(" Annotations ({}) ", app.annotation_filter.label()))
+                .title(format!(" Annotations ({}) ", app.annotation_filter_label()))
                 .borders(Borders::ALL)
                 .style(Style::default().fg(fg).bg(bg)),
         )
@@ -80,16 +92,130 @@ pub fn render_list(f: &mut Frame, app: &mut App) {
     if !app.current_annotations.is_empty() {
         list_state.select(Some(app.selected_annotation_index));
     }
-    f.render_stateful_widget(list, chunks[0], &mut list_state);
+    f.render_stateful_widget(list, panes[0], &mut list_state);
+    let (offset, len) = (list_state.offset(), app.current_annotations.len());
+    super::register_rows(app, panes[0], offset, len);
+
+    // Detail pane: full text, note, outgoing [[id]] links and backlinks.
+    let detail = if let Some(a) = app.current_annotations.get(app.selected_annotation_index) {
+        let describe = |links: &[crate::db::AnnotationRecord]| {
+            if links.is_empty() {
+                "  (none)".to_string()
+            } else {
+                links
+                    .iter()
+                    .map(|l| {
+                        format!(
+                            "  #{} Ch {}: {}",
+                            l.id,
+                            l.chapter + 1,
+                            ellipsize(&l.content, 40)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+        format!(
+            "#{} ({})  Ch {}\n\n{}\n\nNote: {}\nTags: {}{}\n\nLinks to:\n{}\n\nBacklinks:\n{}",
+            a.id,
+            a.kind,
+            a.chapter + 1,
+            a.content,
+            a.note.as_deref().unwrap_or("-"),
+            format_tags(&a.tags),
+            format_attachments(&a.attachments),
+            describe(&app.annotation_links),
+            describe(&app.annotation_backlinks)
+        )
+    } else {
+        "No annotations yet.".to_string()
+    };
+    let detail_p = Paragraph::new(detail)
+        .block(
+            Block::default()
+                .title(" Detail (reference with [[id]] in notes) ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(detail_p, panes[1]);
+
+    let custom_filters: String = app
+        .annotation_kinds
+        .iter()
+        .take(5)
+        .enumerate()
+        .map(|(i, k)| format!(" [{}] {} |", i + 5, k.name))
+        .collect();
+    let footer = match (&app.tag_input, &app.attach_input) {
+        (Some(tags), _) => tag_prompt(tags),
+        (None, Some(path)) => attach_prompt(path),
+        (None, None) => format!(
+            " [1] All | [2] Highlights | [3] Questions | [4] Summaries |{} [T] Tags | [F] Attach File | [Enter] Jump | [Esc] Back ",
+            custom_filters
+        ),
+    };
+    super::render_footer(f, app, &footer, chunks[1], Style::default().fg(fg).bg(bg));
+}
+
+/// List color of a note kind: configured for user-defined kinds, fixed for the built-in ones.
+pub fn kind_color(app: &App, kind: &str) -> Color {
+    match app.annotation_kind_color(kind) {
+        Some([r, g, b]) => Color::Rgb(r, g, b),
+        None => match AnnotationKind::from_str(kind) {
+            AnnotationKind::Highlight => Color::Rgb(200, 170, 80),
+            AnnotationKind::Question => Color::Rgb(120, 160, 220),
+            AnnotationKind::Summary => Color::Rgb(140, 200, 140),
+        },
+    }
+}
+
+/// `#history #rome`, or `-` for an untagged note.
+pub fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        return "-".to_string();
+    }
+    let tags: Vec<String> = tags.iter().map(|t| format!("#{}", t)).collect();
+    tags.join(" ")
+}
+
+/// The files attached to a note, one per line under a `Files:` heading, with missing
+/// ones marked; nothing for a note without files.
+pub fn format_attachments(paths: &[String]) -> String {
+    if paths.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = paths
+        .iter()
+        .map(|p| {
+            if std::path::Path::new(p).exists() {
+                format!("  {}", p)
+            } else {
+                format!("  {} (missing)", p)
+            }
+        })
+        .collect();
+    format!("\nFiles:\n{}", lines.join("\n"))
+}
+
+/// Footer line while a file to attach to a note is being typed.
+pub fn attach_prompt(input: &str) -> String {
+    format!(
+        " Attach file: {}_  (Enter to attach, or detach if already attached, Esc to cancel)",
+        input
+    )
+}
 
-    let footer = Paragraph::new(
-        " [1] All | [2] Highlights | [3] Questions | [4] Summaries | [Enter] Jump | [Esc] Back ",
+/// Footer line while the tags of a note are being edited.
+pub fn tag_prompt(input: &str) -> String {
+    format!(
+        " Tags: {}_  (spaces or commas between tags, Enter to save, Esc to cancel)",
+        input
     )
-    .style(Style::default().fg(fg).bg(bg));
-    f.render_widget(footer, chunks[1]);
 }
 
-fn get_theme_colors(theme: Theme) -> (Color, Color) {
+pub fn get_theme_colors(theme: Theme) -> (Color, Color) {
     match theme {
         Theme::Default => (Color::Reset, Color::Reset),
         Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
@@ -0,0 +1,97 @@
+use crate::app::{App, Theme, BOOK_SEARCH_LIMIT};
+use crate::ui::reader::case_label;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = match app.theme {
+        Theme::Default => (Color::Reset, Color::Reset),
+        Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
+        Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
+        Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let input = Paragraph::new(app.book_search_query.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                " Search This Book ({}; Ctrl-t to change) ",
+                case_label(app.search_case_sensitive)
+            ))
+            .style(Style::default().fg(fg).bg(bg)),
+    );
+    f.render_widget(input, chunks[0]);
+
+    // Matches are grouped under a heading for each chapter they are in.
+    let mut items = Vec::new();
+    let mut selected = None;
+    let mut chapters = 0;
+    for (i, hit) in app.book_search_results.iter().enumerate() {
+        let new_chapter = i == 0 || app.book_search_results[i - 1].chapter != hit.chapter;
+        if new_chapter {
+            chapters += 1;
+            let heading = if hit.chapter_title.is_empty() {
+                format!("Chapter {}", hit.chapter + 1)
+            } else {
+                format!("Chapter {}: {}", hit.chapter + 1, hit.chapter_title)
+            };
+            items.push(
+                ListItem::new(heading).style(
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .bg(bg)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            );
+        }
+        let style = if i == app.selected_book_search_index {
+            selected = Some(items.len());
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(fg).bg(bg)
+        };
+        items.push(ListItem::new(format!("  {}", hit.snippet)).style(style));
+    }
+
+    let matches = app.book_search_results.len();
+    let results_title = match matches {
+        0 => " Matches ".to_string(),
+        BOOK_SEARCH_LIMIT => format!(" First {} matches ", BOOK_SEARCH_LIMIT),
+        1 => " 1 match ".to_string(),
+        n if chapters == 1 => format!(" {} matches in 1 chapter ", n),
+        n => format!(" {} matches in {} chapters ", n, chapters),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(results_title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    list_state.select(selected);
+    f.render_stateful_widget(list, chunks[1], &mut list_state);
+
+    let footer = " [Enter] Search/Go to Match | [Up/Down] Matches | [Esc] Back ";
+    super::render_footer(f, app, footer, chunks[2], Style::default().fg(fg).bg(bg));
+}
@@ -0,0 +1,111 @@
+use crate::app::App;
+use crate::library;
+use crate::ui::annotation::get_theme_colors;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+    let outer = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(outer[1]);
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let review = &app.clipping_review;
+    let group = review.selected_group();
+    let target = review
+        .books
+        .get(review.target)
+        .map_or("(library is empty)", |b| b.title.as_str());
+    let header = match group.first() {
+        Some(clipping) => format!(
+            " Add {} notes from \"{}\" to: [h] < {} > [l] ",
+            group.len(),
+            clipping.title,
+            target
+        ),
+        None => String::new(),
+    };
+    f.render_widget(
+        Paragraph::new(header).style(Style::default().fg(Color::Cyan).bg(bg)),
+        outer[0],
+    );
+
+    let items: Vec<ListItem> = review
+        .clippings
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let style = if i == review.selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(fg).bg(bg)
+            };
+            ListItem::new(format!(
+                "{} {}: {}",
+                library::kind_label(&c.kind),
+                c.title,
+                c.content.replace('\n', " ")
+            ))
+            .style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    " Kindle clippings without a book ({}) ",
+                    review.clippings.len()
+                ))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    if !review.clippings.is_empty() {
+        list_state.select(Some(review.selected));
+    }
+    f.render_stateful_widget(list, panes[0], &mut list_state);
+
+    let detail = match review.clippings.get(review.selected) {
+        Some(c) => format!(
+            "{}\n{}\n\n{}\n\nNote: {}",
+            c.title,
+            if c.author.is_empty() { "-" } else { &c.author },
+            c.content,
+            c.note.as_deref().unwrap_or("-")
+        ),
+        None => "No clippings are waiting for a book.".to_string(),
+    };
+    let detail = Paragraph::new(detail)
+        .block(
+            Block::default()
+                .title(" Detail ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(detail, panes[1]);
+
+    let footer = " [Enter] Add to Book | [d] Discard | [Esc] Back ";
+    super::render_footer(f, app, footer, outer[2], Style::default().fg(fg).bg(bg));
+}
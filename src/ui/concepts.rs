@@ -0,0 +1,134 @@
+use crate::app::{App, Theme};
+use crate::library::AnnotationKind;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = match app.theme {
+        Theme::Default => (Color::Reset, Color::Reset),
+        Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
+        Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
+        Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
+    };
+
+    let outer = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.area());
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(outer[0]);
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let focus_style = |focused: bool| {
+        if focused {
+            Style::default().fg(Color::Cyan).bg(bg)
+        } else {
+            Style::default().fg(fg).bg(bg)
+        }
+    };
+
+    if app.concept_index.is_empty() {
+        let empty = Paragraph::new(
+            "\n\n\nNo shared concepts yet.\nAdd question or summary highlights that mention the same words.",
+        )
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::default().title(" Concepts ").borders(Borders::ALL))
+        .style(Style::default().fg(fg).bg(bg));
+        f.render_widget(empty, outer[0]);
+    } else {
+        let items: Vec<ListItem> = app
+            .concept_index
+            .iter()
+            .enumerate()
+            .map(|(i, (keyword, notes))| {
+                let style = if i == app.selected_concept_index {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(fg).bg(bg)
+                };
+                ListItem::new(format!("{} ({})", keyword, notes.len())).style(style)
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(" Concepts ")
+                    .borders(Borders::ALL)
+                    .style(focus_style(!app.concept_focus_notes)),
+            )
+            .highlight_symbol(">> ");
+        let mut list_state = ListState::default();
+        list_state.select(Some(app.selected_concept_index));
+        f.render_stateful_widget(list, chunks[0], &mut list_state);
+
+        let notes = app.selected_concept_notes();
+        let items: Vec<ListItem> = notes
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let style = if app.concept_focus_notes && i == app.selected_concept_note_index {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(fg).bg(bg)
+                };
+                let title = app
+                    .books
+                    .iter()
+                    .find(|b| b.id == a.book_id)
+                    .map(|b| b.title.as_str())
+                    .unwrap_or("Unknown Book");
+                let kind = AnnotationKind::from_str(&a.kind).label();
+                let note = a
+                    .note
+                    .as_deref()
+                    .map(|n| format!(" — {}", n))
+                    .unwrap_or_default();
+                ListItem::new(format!(
+                    "{} {} [Ch {}]: {}{}",
+                    kind,
+                    title,
+                    a.chapter + 1,
+                    a.content.replace('\n', " "),
+                    note
+                ))
+                .style(style)
+            })
+            .collect();
+
+        let keyword = app
+            .concept_index
+            .get(app.selected_concept_index)
+            .map(|(k, _)| k.as_str())
+            .unwrap_or("");
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(" Notes about \"{}\" ", keyword))
+                    .borders(Borders::ALL)
+                    .style(focus_style(app.concept_focus_notes)),
+            )
+            .highlight_symbol(">> ");
+        let mut list_state = ListState::default();
+        if app.concept_focus_notes && !notes.is_empty() {
+            list_state.select(Some(app.selected_concept_note_index));
+        }
+        f.render_stateful_widget(list, chunks[1], &mut list_state);
+    }
+
+    let footer = " [j/k] Move | [Tab] Switch Pane | [Enter] Open Note | [Esc] Back ";
+    super::render_footer(f, app, footer, outer[1], Style::default().fg(fg).bg(bg));
+}
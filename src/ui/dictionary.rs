@@ -20,7 +20,16 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Length(3), Constraint::Min(0)])
         .split(f.area());
 
-    let title = Paragraph::new(format!(" Definition: {} ", app.dictionary_query)).block(
+    let source = app
+        .dictionary_settings
+        .as_ref()
+        .map(|s| format!(" ({} · {})", s.language, s.source.label()))
+        .unwrap_or_default();
+    let heading = match app.dictionary_settings {
+        Some(ref s) if s.source.is_encyclopedia() => "Encyclopedia",
+        _ => "Definition",
+    };
+    let title = Paragraph::new(format!(" {}: {}{} ", heading, app.dictionary_query, source)).block(
         Block::default()
             .borders(Borders::ALL)
             .style(Style::default().fg(fg).bg(bg)),
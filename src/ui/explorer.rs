@@ -1,8 +1,8 @@
-use crate::app::{App, Theme};
+use crate::app::{App, ExplorerRow, Theme};
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
@@ -57,11 +57,11 @@ pub fn render(f: &mut Frame, app: &mut App) {
                 .style(Style::default().fg(fg).bg(bg));
             f.render_widget(empty, chunks[1]);
         } else {
-            let items: Vec<ListItem> = app
-                .explorer_results
+            let rows = app.explorer_rows();
+            let items: Vec<ListItem> = rows
                 .iter()
                 .enumerate()
-                .map(|(i, path)| {
+                .map(|(i, row)| {
                     let style = if i == app.selected_explorer_index {
                         Style::default()
                             .fg(Color::Yellow)
@@ -69,15 +69,72 @@ pub fn render(f: &mut Frame, app: &mut App) {
                     } else {
                         Style::default().fg(fg).bg(bg)
                     };
-                    let is_selected = app.explorer_selected.contains(path);
-                    let checkbox = if is_selected { "[x]" } else { "[ ]" };
-                    ListItem::new(format!("{} {}", checkbox, path.to_string_lossy())).style(style)
+                    match row {
+                        ExplorerRow::Group {
+                            dir,
+                            files,
+                            collapsed,
+                        } => {
+                            let picked = files
+                                .iter()
+                                .filter(|f| app.explorer_selected.contains(*f))
+                                .count();
+                            let checkbox = if picked == 0 {
+                                "[ ]"
+                            } else if picked == files.len() {
+                                "[x]"
+                            } else {
+                                "[-]"
+                            };
+                            let arrow = if *collapsed { "▸" } else { "▾" };
+                            let total: u64 = files.iter().map(|f| app.explorer_size(f)).sum();
+                            ListItem::new(format!(
+                                "{} {} {} ({} books, {})",
+                                arrow,
+                                checkbox,
+                                app.explorer_group_label(dir),
+                                files.len(),
+                                format_size(total)
+                            ))
+                            .style(style.add_modifier(Modifier::BOLD))
+                        }
+                        ExplorerRow::File(path) => {
+                            let checkbox = if app.explorer_selected.contains(path) {
+                                "[x]"
+                            } else {
+                                "[ ]"
+                            };
+                            let name = path
+                                .file_name()
+                                .map(|n| n.to_string_lossy().to_string())
+                                .unwrap_or_else(|| path.to_string_lossy().to_string());
+                            let size = app.explorer_size(path);
+                            let warning = if app.import_rules.is_large(size) {
+                                "  (!) large"
+                            } else {
+                                ""
+                            };
+                            ListItem::new(format!(
+                                "    {} {:>9}  {}{}",
+                                checkbox,
+                                format_size(size),
+                                name,
+                                warning
+                            ))
+                            .style(style)
+                        }
+                    }
                 })
                 .collect();
 
+            let skipped = if app.explorer_skipped > 0 {
+                format!(", {} skipped by rules", app.explorer_skipped)
+            } else {
+                String::new()
+            };
             let list_title = format!(
-                " Files Found ({}/{} selected) ",
-                selected_count, total_count
+                " Files Found ({}/{} selected{}) ",
+                selected_count, total_count, skipped
             );
             let list = List::new(items)
                 .block(
@@ -88,16 +145,67 @@ pub fn render(f: &mut Frame, app: &mut App) {
                 )
                 .highlight_symbol(">> ");
             let mut list_state = ListState::default();
-            if !app.explorer_results.is_empty() {
+            if !rows.is_empty() {
                 list_state.select(Some(app.selected_explorer_index));
             }
             f.render_stateful_widget(list, chunks[1], &mut list_state);
         }
     }
 
-    let footer = Paragraph::new(
-        " [Space] Toggle | [a] All | [c] Clear | [Enter] Import | [i] Import All | [Esc] Back ",
-    )
-    .style(Style::default().fg(fg).bg(bg));
-    f.render_widget(footer, chunks[2]);
+    let footer = " [Space] Toggle | [h/l/Tab] Fold | [a] All | [c] Clear | [Enter] Import | [i] Import All | [Esc] Back ";
+    super::render_footer(f, app, footer, chunks[2], Style::default().fg(fg).bg(bg));
+
+    if let Some(ref paths) = app.pending_large_import {
+        let large: Vec<String> = paths
+            .iter()
+            .filter(|p| app.import_rules.is_large(app.explorer_size(p)))
+            .map(|p| {
+                format!(
+                    "{} ({})",
+                    p.file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    format_size(app.explorer_size(p))
+                )
+            })
+            .collect();
+        let area = super::help::centered_rect(60, 40, f.area());
+        f.render_widget(Clear, area);
+        let shown = large.iter().take(5).cloned().collect::<Vec<_>>().join("\n");
+        let more = if large.len() > 5 {
+            format!("\n...and {} more", large.len() - 5)
+        } else {
+            String::new()
+        };
+        let confirm = Paragraph::new(format!(
+            "\n{} of {} files are over {}:\n\n{}{}\n\nImport anyway?\n\n[y] Import  [n] Cancel",
+            large.len(),
+            paths.len(),
+            format_size(app.import_rules.warn_size),
+            shown,
+            more
+        ))
+        .block(
+            Block::default()
+                .title(" Large Import ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Red).bg(bg)),
+        )
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(fg).bg(bg))
+        .wrap(Wrap { trim: true });
+        f.render_widget(confirm, area);
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let b = bytes as f64;
+    if b >= KB * KB * KB {
+        format!("{:.1} GB", b / (KB * KB * KB))
+    } else if b >= KB * KB {
+        format!("{:.1} MB", b / (KB * KB))
+    } else {
+        format!("{:.0} KB", b / KB)
+    }
 }
@@ -0,0 +1,53 @@
+use crate::app::App;
+use crate::ui::annotation::get_theme_colors;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Min(0)])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let block = Block::default()
+        .title(" Figures and Tables (Enter to Jump, Esc to Back) ")
+        .borders(Borders::ALL)
+        .style(Style::default().fg(fg).bg(bg));
+
+    if app.figure_items.is_empty() {
+        let empty = Paragraph::new("This book has no captioned figures or tables.").block(block);
+        f.render_widget(empty, chunks[0]);
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .figure_items
+        .iter()
+        .enumerate()
+        .map(|(i, figure)| {
+            let style = if i == app.selected_figure_index {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(fg).bg(bg)
+            };
+            ListItem::new(figure.label.as_str()).style(style)
+        })
+        .collect();
+
+    let list = List::new(items).block(block).highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    list_state.select(Some(app.selected_figure_index));
+    f.render_stateful_widget(list, chunks[0], &mut list_state);
+}
@@ -1,4 +1,6 @@
 use crate::app::{App, Theme};
+use crate::ui::library::render_book_panel;
+use crate::ui::reader::case_label;
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
@@ -17,18 +19,37 @@ pub fn render(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
-        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
         .split(f.area());
 
     // Fill background
     f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
 
-    let input = Paragraph::new(app.global_search_query.as_str()).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(" Global Search (Type and press Enter) ")
-            .style(Style::default().fg(fg).bg(bg)),
-    );
+    let input = if let Some(ref name) = app.saved_search_name {
+        Paragraph::new(name.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    " Save \"{}\" as (Enter to save, Esc to cancel) ",
+                    app.global_search_query
+                ))
+                .style(Style::default().fg(Color::Cyan).bg(bg)),
+        )
+    } else {
+        Paragraph::new(app.global_search_query.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    " Global Search ({}; Ctrl-t to change) - Type and press Enter ",
+                    case_label(app.search_case_sensitive)
+                ))
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+    };
     f.render_widget(input, chunks[0]);
 
     let items: Vec<ListItem> = app
@@ -43,14 +64,32 @@ pub fn render(f: &mut Frame, app: &mut App) {
             } else {
                 Style::default().fg(fg).bg(bg)
             };
-            ListItem::new(format!("{} [Ch {}]: {}", res.1, res.2 + 1, res.3)).style(style)
+            ListItem::new(format!(
+                "{} [Ch {}]: {}",
+                res.title,
+                res.locator.spine + 1,
+                res.snippet
+            )).style(style)
         })
         .collect();
 
+    let results_title = if let Some(ref progress) = app.global_search_progress {
+        format!(
+            " Search Results (searching {}/{} books, Esc to stop) ",
+            progress.scanned, progress.total
+        )
+    } else if app.indexing_books.is_empty() {
+        " Search Results ".to_string()
+    } else {
+        format!(
+            " Search Results (indexing {} books...) ",
+            app.indexing_books.len()
+        )
+    };
     let list = List::new(items)
         .block(
             Block::default()
-                .title(" Search Results ")
+                .title(results_title)
                 .borders(Borders::ALL)
                 .style(Style::default().fg(fg).bg(bg)),
         )
@@ -59,5 +98,71 @@ pub fn render(f: &mut Frame, app: &mut App) {
     if !app.global_search_results.is_empty() {
         list_state.select(Some(app.selected_search_index));
     }
-    f.render_stateful_widget(list, chunks[1], &mut list_state);
+    // The book holding the selected hit shows beside the results, to check the edition
+    // before opening it.
+    match app.previewed_book() {
+        Some(book) => {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(chunks[1]);
+            f.render_stateful_widget(list, panes[0], &mut list_state);
+            render_book_panel(f, app, &book, panes[1], bg, fg);
+        }
+        None => f.render_stateful_widget(list, chunks[1], &mut list_state),
+    }
+
+    let footer = " [Enter] Search/Open | [Up/Down] History/Results | [Ctrl-s] Save Search | [Tab] Saved | [Esc] Back ";
+    super::render_footer(f, app, footer, chunks[2], Style::default().fg(fg).bg(bg));
+}
+
+pub fn render_saved(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = match app.theme {
+        Theme::Default => (Color::Reset, Color::Reset),
+        Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
+        Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
+        Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let items: Vec<ListItem> = app
+        .saved_searches
+        .iter()
+        .enumerate()
+        .map(|(i, saved)| {
+            let style = if i == app.selected_saved_search_index {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(fg).bg(bg)
+            };
+            ListItem::new(format!("{:<24} {}", saved.name, saved.query)).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(" Saved Searches ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    if !app.saved_searches.is_empty() {
+        list_state.select(Some(app.selected_saved_search_index));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut list_state);
+
+    let footer = " [Enter] Run | [d] Delete | [Esc/Tab] Back ";
+    super::render_footer(f, app, footer, chunks[1], Style::default().fg(fg).bg(bg));
 }
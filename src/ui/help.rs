@@ -14,39 +14,108 @@ pub fn render(f: &mut Frame, _app: &App) {
         "--- GLOBAL ---",
         "? : Toggle Help",
         "q : Back / Quit",
+        "Ctrl-k : Search Books, Notes, Words and Text at Once",
+        "Click : Press a [key] hint / Select, then Open a Row",
         "--- LIBRARY ---",
         "Enter : Open Book",
         "i : View Reading Statistics",
         "n : Scan Drive for Books",
+        "r : Rescan Library Path",
+        "u : Save Web Article",
+        "s : Cycle Reading Status",
+        "T : Tag the Selected Book",
+        "f : Filter by Status",
+        "d/x : Remove Book",
+        "L : Book Lookup Language",
         "S : Global Search",
+        "C : Concept Index",
+        "A : Notes Across All Books",
+        "K : Kindle Clippings Without a Book",
+        "R : Review Vocabulary Due Today",
+        "--- GLOBAL SEARCH ---",
+        "Up/Down : History / Results",
+        "Ctrl-s : Save Search",
+        "Tab : Saved Searches",
+        "Ctrl-t : Match Case On / Off (Here and in Find)",
         "--- READER ---",
-        "j/k : Scroll View",
+        "j/k : Scroll View (Turn Pages When Paged)",
+        "Space/PgDn, PgUp : Next / Previous Page",
+        "Ctrl-d/u, Ctrl-f/b : Half / Full Screen Down / Up",
+        "Wheel : Scroll | Click : Move Select Cursor",
+        "P : Toggle Paged Mode for This Book",
+        "gg / G : Start / End of Chapter",
+        "50% or : : Go to a Percentage or Chapter",
+        "Ctrl-o / Ctrl-i : Back / Forward Through Jumps",
+        "q : Session Summary, Then Back to Library",
+        "10j, 3l, 2n... : Repeat a Motion",
+        "/ : Find in Book | n/N : Next / Previous Match",
+        "S : List Every Match in the Book, by Chapter",
         "a : Toggle Auto-Scroll",
         "+/- : Adjust Text Size (Zoom)",
+        "( / ) : Paragraph Spacing | { / } : Line Spacing",
+        "J : Toggle Justified Text",
+        "| : Toggle Two Columns",
+        "< / > : First-Line Indent",
         "f : Toggle Focus Mode",
+        "L : Cycle Line Focus",
+        "T : Cycle Focus-Mode Ruler",
+        "# : Line Numbers (Off / Chapter / Relative)",
+        "M : Progress Gauge (Off / On / Also in Focus)",
+        "r : Speed-Read (RSVP) From the Cursor",
+        "W : Toggle PDF Reflow",
         "p : Pomodoro Start/Pause",
         "R : Pomodoro Reset",
         "B : Skip Break",
         "s : Enter Select Mode",
-        "t : Table of Contents",
+        "t : Table of Contents and Figures",
+        "F : List of Figures and Tables",
+        "' : Last Stops (Where the Book Was Closed Mid-Chapter)",
         "A : View All Notes",
         "V : View Vocabulary",
-        "E : Export to Markdown",
+        "E : Export notes (md/json/csv/html/org)",
+        "e : Reading Preset (Novel / Textbook / Paper)",
+        "Y : Copy where you stopped",
+        "y : Copy your position for a reading group",
+        "m : Go to a group member's position",
+        "Z : Generate Chapter Summary (opt-in)",
+        "i : Reading Statistics",
+        "--- VOCABULARY ---",
+        "r : Review Words Due Today",
+        "Space : Show Definition",
+        "1/2/3/4 : Again / Hard / Good / Easy",
+        "--- STATISTICS ---",
+        "j/k : Last 7 Days / Last 12 Months",
+        "b : Break Down by Tag / Author",
         "--- NOTES LIST ---",
-        "1/2/3/4 : Filter Notes",
+        "1/2/3/4 : Filter Notes (5-9: your own kinds)",
+        "T : Edit Tags of the Selected Note",
+        "F : Attach / Detach a File",
+        "y : Copy the Selected Note as Markdown",
+        "--- NOTES ACROSS ALL BOOKS ---",
+        "b / t / d : Filter by Book / Tag / Date",
+        "1-9 : Filter by Kind",
+        "T : Edit Tags | Enter : Open Note",
+        "F : Attach / Detach a File",
+        "y : Copy the Selected Note as Markdown",
+        "G : Summarize Listed Notes (opt-in)",
         "--- SELECT MODE ---",
-        "j/k : Move Cursor",
+        "j/k : Move Cursor by Screen Row",
         "w/b : Move by Word",
         "v : Start Visual Selection",
         "h : Highlight",
         "q : Question Highlight",
         "m : Summary Highlight",
+        "1-9 : Highlight as a Kind from tbook.toml",
+        "Enter : Highlight as the Book's Preset Prefers",
         "d : Dictionary Lookup",
+        "W : Wikipedia Summary of the Word",
         "--- VISUAL MODE ---",
         "h : Highlight",
         "q : Question Highlight",
         "m : Summary Highlight",
-        "a : Highlight + Note",
+        "1-9 : Highlight as a Kind from tbook.toml",
+        "a : Highlight + Note ([[id]] links another note)",
+        "W : Wikipedia Summary of the Selection",
     ];
 
     let p = Paragraph::new(help_text.join("\n"))
@@ -56,7 +125,7 @@ pub fn render(f: &mut Frame, _app: &App) {
     f.render_widget(p, area);
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
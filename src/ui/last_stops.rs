@@ -0,0 +1,62 @@
+use crate::app::App;
+use crate::ui::annotation::get_theme_colors;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Min(0)])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let block = Block::default()
+        .title(" Last Stops (Enter to Jump, Esc to Back) ")
+        .borders(Borders::ALL)
+        .style(Style::default().fg(fg).bg(bg));
+
+    if app.last_stops.is_empty() {
+        let empty = Paragraph::new("No last stops yet. Closing a book mid-chapter leaves one.")
+            .block(block);
+        f.render_widget(empty, chunks[0]);
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .last_stops
+        .iter()
+        .enumerate()
+        .map(|(i, stop)| {
+            let chapter = app
+                .current_book
+                .as_ref()
+                .and_then(|b| b.parser.chapter_title(stop.locator.spine))
+                .unwrap_or_else(|| format!("Chapter {}", stop.locator.spine + 1));
+            // Minutes are enough to tell stops apart.
+            let when = stop.stopped_at.get(..16).unwrap_or(&stop.stopped_at);
+            let label = format!("{}  {}: \"{}...\"", when, chapter, stop.snippet);
+            let style = if i == app.selected_last_stop_index {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(fg).bg(bg)
+            };
+            ListItem::new(label).style(style)
+        })
+        .collect();
+
+    let list = List::new(items).block(block).highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    list_state.select(Some(app.selected_last_stop_index));
+    f.render_stateful_widget(list, chunks[0], &mut list_state);
+}
@@ -1,13 +1,24 @@
+use super::annotation::{format_tags, tag_prompt};
+use super::{fit_width, format_days, format_minutes};
 use crate::app::{App, Theme};
+use crate::db::BookRecord;
+use crate::library::ReadingStatus;
+use chrono::Datelike;
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 use ratatui_image::{protocol::StatefulProtocol, FilterType, Resize, StatefulImage};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// A text bar such as `███░░░░░░░` for list rows, where a gauge widget cannot go.
+fn progress_bar(ratio: f64, width: usize) -> String {
+    let filled = ((ratio.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
 pub fn render(f: &mut Frame, app: &mut App) {
     let (bg, fg) = match app.theme {
         Theme::Default => (Color::Reset, Color::Reset),
@@ -48,6 +59,11 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
         .split(chunks[1]);
 
+    // Room inside the borders and the highlight symbol, split between title and bar.
+    let row_width = main_chunks[0].width.saturating_sub(5) as usize;
+    let bar_width = (row_width / 4).clamp(5, 20);
+    let title_width = row_width.saturating_sub(bar_width + 10).max(10);
+
     let items: Vec<ListItem> = app
         .books
         .iter()
@@ -61,20 +77,41 @@ pub fn render(f: &mut Frame, app: &mut App) {
                 Style::default().fg(fg).bg(bg)
             };
 
-            let progress = if b.total_lines > 0 {
-                (b.lines_read as f64 / b.total_lines as f64) * 100.0
-            } else {
-                0.0
-            };
+            let progress = b.progress();
 
-            ListItem::new(format!("{:<30} | {:>3.0}%", b.title, progress)).style(style)
+            let status = ReadingStatus::from_str(&b.status);
+            let title = match app.stalled_days(b) {
+                Some(_) => format!("{} (stalled)", b.title),
+                None => b.title.clone(),
+            };
+            ListItem::new(format!(
+                "{} {} {} {:>3.0}%",
+                status.badge(),
+                fit_width(&title, title_width),
+                progress_bar(progress, bar_width),
+                progress * 100.0
+            ))
+            .style(style)
         })
         .collect();
 
+    let mut library_title = match app.library_status_filter {
+        Some(status) => format!(" Library [{}] ", status.label()),
+        None => " Library ".to_string(),
+    };
+    if let Some((done, total)) = app.indexing_progress() {
+        library_title.push_str(&format!("(indexing {}/{}) ", done, total));
+    }
+    if app.vocab_due > 0 {
+        library_title.push_str(&format!("({} words to review, R) ", app.vocab_due));
+    }
+    if app.read_only {
+        library_title.push_str("(read-only) ");
+    }
     let list = List::new(items)
         .block(
             Block::default()
-                .title(" Library ")
+                .title(library_title)
                 .borders(Borders::ALL)
                 .style(Style::default().fg(fg).bg(bg)),
         )
@@ -85,96 +122,202 @@ pub fn render(f: &mut Frame, app: &mut App) {
         list_state.select(Some(app.selected_book_index));
     }
     f.render_stateful_widget(list, main_chunks[0], &mut list_state);
+    let (offset, len) = (list_state.offset(), app.books.len());
+    super::register_rows(app, main_chunks[0], offset, len);
 
     // Book Info & Cover Preview
-    if let Some(selected_book) = app.books.get(app.selected_book_index) {
-        let info_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Min(6),    // Cover area (keep visible on small terminals)
-                Constraint::Length(8), // Text info area
-                Constraint::Length(3), // Progress bar
-            ])
-            .split(main_chunks[1]);
-
-        // 1. Render Cover
-        let cover_block = Block::default()
-            .title(" Preview ")
-            .borders(Borders::ALL)
-            .style(Style::default().fg(fg).bg(bg));
-        let cover_inner = cover_block.inner(info_chunks[0]);
-        f.render_widget(cover_block, info_chunks[0]);
-
-        let selected_id = selected_book.id;
-        let is_cover_loading = app.current_library_cover.is_none()
-            && !app.cover_cache.contains_key(&selected_id)
-            && !app.cover_missing.contains(&selected_id);
-
-        if let Some(ref mut protocol) = app.current_library_cover {
-            // Use a higher quality resize filter so downscaled covers look less muddy.
-            let widget = StatefulImage::<StatefulProtocol>::default()
-                .resize(Resize::Fit(Some(FilterType::Lanczos3)));
-            f.render_stateful_widget(widget, cover_inner, protocol);
-        } else if is_cover_loading {
-            const SPINNER: [&str; 4] = ["-", "\\", "|", "/"];
-            let ticks = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_millis() as usize)
-                .unwrap_or(0);
-            let spinner = SPINNER[(ticks / 120) % SPINNER.len()];
-            let loading = Paragraph::new(format!("\n\n\nLoading cover {}", spinner))
-                .alignment(ratatui::layout::Alignment::Center)
-                .style(Style::default().fg(Color::DarkGray));
-            f.render_widget(loading, cover_inner);
-        } else {
-            let no_cover = Paragraph::new("\n\n\n[ No Cover Preview ]")
-                .alignment(ratatui::layout::Alignment::Center)
-                .style(Style::default().fg(Color::DarkGray));
-            f.render_widget(no_cover, cover_inner);
-        }
+    if let Some(book) = app.previewed_book() {
+        render_book_panel(f, app, &book, main_chunks[1], bg, fg);
+    }
 
-        // 2. Render Text Info
-        let info = format!(
-            "Title: {}\nAuthor: {}\nPath: {}\nChapters: {}\nTotal Lines: {}",
-            selected_book.title,
-            selected_book.author,
-            selected_book.path,
-            selected_book.total_chapters,
-            selected_book.total_lines
-        );
-        let info_p = Paragraph::new(info)
+    let proto = format!(
+        "proto={:?} font={:?}  ([p] cycle proto)",
+        app.image_picker.protocol_type(),
+        app.image_picker.font_size()
+    );
+    let help = match app.tag_input {
+        Some(ref tags) => tag_prompt(tags),
+        None => format!(
+            " [Enter] Open | [n] Add New | [r] Rescan | [u] Add URL | [s] Status | [T] Tags | [f] Filter | [d] Remove | [L] Lookup Lang | [S] Search | [?] Help | [p] Proto | [q] Quit  |  {} ",
+            proto
+        ),
+    };
+    super::render_footer(f, app, &help, chunks[2], Style::default().fg(fg).bg(bg));
+
+    if let Some(book_id) = app.pending_book_removal {
+        let title = app
+            .books
+            .iter()
+            .find(|b| b.id == book_id)
+            .map(|b| b.title.as_str())
+            .unwrap_or("this book");
+        let area = super::help::centered_rect(50, 25, f.area());
+        f.render_widget(Clear, area);
+        let confirm = Paragraph::new(format!(
+            "\nRemove \"{}\" from the library?\n\nIts notes, reading history, and search index are deleted too.\nThe file on disk is kept.\n\n[y] Remove  [n] Cancel",
+            title
+        ))
+        .block(
+            Block::default()
+                .title(" Remove Book ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Red).bg(bg)),
+        )
+        .alignment(ratatui::layout::Alignment::Center)
+        .style(Style::default().fg(fg).bg(bg))
+        .wrap(Wrap { trim: true });
+        f.render_widget(confirm, area);
+    } else if !app.pending_relinks.is_empty() {
+        let mut text = String::from("\nThese books were moved. Point them at the new files?\n\n");
+        for relink in &app.pending_relinks {
+            text.push_str(&format!(
+                "{}\n  {}\n  -> {}\n",
+                relink.title, relink.old_path, relink.new_path
+            ));
+        }
+        text.push_str("\n[y] Relink  [n] Not now");
+        let area = super::help::centered_rect(70, 50, f.area());
+        f.render_widget(Clear, area);
+        let prompt = Paragraph::new(text)
             .block(
                 Block::default()
-                    .title(" Book Info ")
+                    .title(" Relink Moved Books ")
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(fg).bg(bg)),
+                    .style(Style::default().fg(Color::Yellow).bg(bg)),
             )
             .style(Style::default().fg(fg).bg(bg))
-            .wrap(Wrap { trim: true });
-        f.render_widget(info_p, info_chunks[1]);
+            .wrap(Wrap { trim: false });
+        f.render_widget(prompt, area);
+    }
+}
 
-        // 3. Render Progress Gauge
-        let progress = if selected_book.total_lines > 0 {
-            selected_book.lines_read as f64 / selected_book.total_lines as f64
-        } else {
-            0.0
-        };
-        let gauge = Gauge::default()
-            .block(Block::default().title(" Progress ").borders(Borders::ALL))
-            .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
-            .ratio(progress);
-        f.render_widget(gauge, info_chunks[2]);
+/// The cover, details and progress of `book`, as the library shows for the selected one.
+/// Global search shows the same panel for the book a hit is in.
+pub fn render_book_panel(
+    f: &mut Frame,
+    app: &mut App,
+    book: &BookRecord,
+    area: Rect,
+    bg: Color,
+    fg: Color,
+) {
+    let info_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(6),    // Cover area (keep visible on small terminals)
+            Constraint::Length(9), // Text info area
+            Constraint::Length(3), // Progress bar
+        ])
+        .split(area);
+
+    // 1. Render Cover
+    let cover_block = Block::default()
+        .title(" Preview ")
+        .borders(Borders::ALL)
+        .style(Style::default().fg(fg).bg(bg));
+    let cover_inner = cover_block.inner(info_chunks[0]);
+    f.render_widget(cover_block, info_chunks[0]);
+
+    let selected_id = book.id;
+    let is_cover_loading = !app.light_mode
+        && app.current_library_cover.is_none()
+        && !app.cover_cache.contains_key(&selected_id)
+        && !app.cover_missing.contains(&selected_id);
+
+    if let Some(ref mut protocol) = app.current_library_cover {
+        // Use a higher quality resize filter so downscaled covers look less muddy.
+        let widget = StatefulImage::<StatefulProtocol>::default()
+            .resize(Resize::Fit(Some(FilterType::Lanczos3)));
+        f.render_stateful_widget(widget, cover_inner, protocol);
+    } else if is_cover_loading {
+        const SPINNER: [&str; 4] = ["-", "\\", "|", "/"];
+        let ticks = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as usize)
+            .unwrap_or(0);
+        let spinner = SPINNER[(ticks / 120) % SPINNER.len()];
+        let loading = Paragraph::new(format!("\n\n\nLoading cover {}", spinner))
+            .alignment(ratatui::layout::Alignment::Center)
+            .style(Style::default().fg(Color::DarkGray));
+        f.render_widget(loading, cover_inner);
+    } else {
+        let no_cover = Paragraph::new("\n\n\n[ No Cover Preview ]")
+            .alignment(ratatui::layout::Alignment::Center)
+            .style(Style::default().fg(Color::DarkGray));
+        f.render_widget(no_cover, cover_inner);
     }
 
-    let proto = format!(
-        "proto={:?} font={:?}  ([p] cycle proto)",
-        app.image_picker.protocol_type(),
-        app.image_picker.font_size()
+    // 2. Render Text Info
+    let extent = match book.total_pages {
+        Some(pages) => format!(
+            "Page: {} of {}",
+            (book.current_chapter + 1).min(pages),
+            pages
+        ),
+        None => format!(
+            "Chapters: {}\nLength: {}",
+            book.total_chapters,
+            match book.total_words {
+                Some(words) => format!("{} words, {} lines", words, book.total_lines),
+                None => "counting...".to_string(),
+            }
+        ),
+    };
+    let info = format!(
+        "Title: {}\nAuthor: {}\nStatus: {}\n{}Path: {}\n{}\nLookup: {} / {}",
+        book.title,
+        book.author,
+        ReadingStatus::from_str(&book.status).label(),
+        if book.tags.is_empty() {
+            String::new()
+        } else {
+            format!("Tags: {}\n", format_tags(&book.tags))
+        },
+        book.path,
+        extent,
+        book.language.as_deref().unwrap_or("default"),
+        book.dictionary.as_deref().unwrap_or("default")
     );
-    let help = Paragraph::new(format!(
-        " [Enter] Open | [n] Add New | [S] Search | [?] Help | [p] Proto | [q] Quit  |  {} ",
-        proto
-    ))
-    .style(Style::default().fg(fg).bg(bg));
-    f.render_widget(help, chunks[2]);
+    let info_p = Paragraph::new(info)
+        .block(
+            Block::default()
+                .title(" Book Info ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .style(Style::default().fg(fg).bg(bg))
+        .wrap(Wrap { trim: true });
+    f.render_widget(info_p, info_chunks[1]);
+
+    // 3. Render Progress Gauge
+    let progress = book.progress();
+    let mut label = format!("{:.0}%", progress * 100.0);
+    if let Some(words) = book
+        .words_left()
+        .filter(|_| book.total_pages.is_none())
+    {
+        let secs = (words as f64 / app.reading_wpm.max(1.0) * 60.0) as u64;
+        label.push_str(&format!(" · {} left", format_minutes(secs)));
+    }
+    // At the recent pace with the book, when it will be done, or how long it has sat.
+    let today = chrono::Local::now().date_naive();
+    let finish = app
+        .book_paces
+        .get(&book.id)
+        .zip(book.words_left())
+        .and_then(|(pace, words)| pace.finish_date(words, today));
+    let gauge_title = match (app.stalled_days(book), finish) {
+        (Some(days), _) => format!(" Progress · stalled {} ", format_days(days as usize)),
+        (None, Some(date)) if date.year() == today.year() => {
+            format!(" Progress · done by {} ", date.format("%b %-d"))
+        }
+        (None, Some(date)) => format!(" Progress · done by {} ", date.format("%b %-d, %Y")),
+        (None, None) => " Progress ".to_string(),
+    };
+    let gauge = Gauge::default()
+        .block(Block::default().title(gauge_title).borders(Borders::ALL))
+        .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
+        .label(label)
+        .ratio(progress);
+    f.render_widget(gauge, info_chunks[2]);
 }
@@ -0,0 +1,88 @@
+use crate::app::{App, Theme};
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = match app.theme {
+        Theme::Default => (Color::Reset, Color::Reset),
+        Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
+        Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
+        Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(5)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let book_title = app
+        .books
+        .get(app.selected_book_index)
+        .map(|b| b.title.as_str())
+        .unwrap_or("");
+    let title = Paragraph::new(format!(" Lookup Settings: {} ", book_title))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .alignment(Alignment::Center)
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(title, chunks[0]);
+
+    let field_style = |active: bool| {
+        if active {
+            Style::default().fg(Color::Yellow).bg(bg)
+        } else {
+            Style::default().fg(fg).bg(bg)
+        }
+    };
+
+    let language = Paragraph::new(app.lookup_language_input.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Language (e.g. en, es, de) ")
+            .style(field_style(!app.lookup_editing_dictionary)),
+    );
+    f.render_widget(language, chunks[1]);
+
+    let dictionary = Paragraph::new(app.lookup_dictionary_input.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Dictionary (free, wiktionary, or URL with {word}/{lang}) ")
+            .style(field_style(app.lookup_editing_dictionary)),
+    );
+    f.render_widget(dictionary, chunks[2]);
+
+    let note = Paragraph::new(
+        "\nLeave a field empty to use the defaults from tbook.toml \
+         (dictionary_language, dictionary).",
+    )
+    .alignment(Alignment::Center)
+    .style(Style::default().fg(Color::DarkGray).bg(bg))
+    .wrap(Wrap { trim: true });
+    f.render_widget(note, chunks[3]);
+
+    let help = Paragraph::new(" [Tab] Switch Field | [Enter] Save | [Esc] Cancel ")
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(fg).bg(bg));
+    f.render_widget(help, chunks[4]);
+}
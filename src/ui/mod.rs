@@ -1,33 +1,66 @@
 pub mod annotation;
+pub mod booksearch;
+pub mod clippings;
+pub mod concepts;
 pub mod dictionary;
 pub mod explorer;
+pub mod figures;
 pub mod globalsearch;
 pub mod help;
+pub mod last_stops;
 pub mod library;
+pub mod lookup;
+pub mod notes;
+pub mod omnibox;
 pub mod path_input;
 pub mod reader;
 pub mod rsvp;
+#[cfg(test)]
+mod snapshot_tests;
 pub mod stats;
 pub mod toc;
+pub mod url_input;
+pub mod vocab_review;
 pub mod vocabulary;
 
-use crate::app::{App, AppView};
-use ratatui::Frame;
+use crate::app::{self, App, AppView, ClickArea, ClickTarget, ColorDepth};
+use crossterm::event::KeyCode;
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Margin, Rect},
+    style::{Color, Modifier, Style},
+    widgets::Paragraph,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub fn render(f: &mut Frame, app: &mut App) {
+    app.click_areas.clear();
     match app.view {
         AppView::Library => library::render(f, app),
         AppView::Reader | AppView::Search | AppView::Visual | AppView::Select => {
             reader::render(f, app)
         }
         AppView::Toc => toc::render(f, app),
+        AppView::Figures => figures::render(f, app),
+        AppView::LastStops => last_stops::render(f, app),
         AppView::Rsvp => rsvp::render(f, app),
         AppView::Annotation => annotation::render_add(f, app),
         AppView::AnnotationList => annotation::render_list(f, app),
         AppView::Dictionary => dictionary::render(f, app),
         AppView::Vocabulary => vocabulary::render(f, app),
+        AppView::VocabReview => vocab_review::render(f, app),
         AppView::GlobalSearch => globalsearch::render(f, app),
+        AppView::SavedSearches => globalsearch::render_saved(f, app),
+        AppView::BookSearch => booksearch::render(f, app),
+        AppView::Omnibox => omnibox::render(f, app),
+        AppView::ConceptIndex => concepts::render(f, app),
+        AppView::NoteBrowser => notes::render(f, app),
+        AppView::ClippingReview => clippings::render(f, app),
         AppView::PathInput => path_input::render(f, app),
+        AppView::UrlInput => url_input::render(f, app),
+        AppView::LookupSettings => lookup::render(f, app),
         AppView::FileExplorer => explorer::render(f, app),
         AppView::Help => {
             help::render(f, app);
@@ -38,4 +71,423 @@ pub fn render(f: &mut Frame, app: &mut App) {
     if app.view == AppView::Help {
         help::render(f, app);
     }
+
+    if let Some(percent) = app.night_tint_percent {
+        tint_buffer(f.buffer_mut(), percent);
+    }
+    degrade_buffer(f.buffer_mut(), app.color_depth);
+}
+
+/// Draws a one-line footer of `[key] Label` hints, each of which a click presses.
+pub fn render_footer(f: &mut Frame, app: &mut App, text: &str, area: Rect, style: Style) {
+    f.render_widget(Paragraph::new(text).style(style), area);
+    register_hints(app, text, area);
+}
+
+/// Makes the `[key] Label` hints of a line drawn from the left of `area` clickable.
+pub fn register_hints(app: &mut App, text: &str, area: Rect) {
+    app.click_areas.extend(hint_areas(text, area));
+}
+
+/// Makes the rows of a bordered list drawn in `area` clickable, one item per row starting
+/// with item `offset`.
+pub fn register_rows(app: &mut App, area: Rect, offset: usize, len: usize) {
+    let inner = area.inner(Margin::new(1, 1));
+    let shown = len.saturating_sub(offset).min(inner.height as usize);
+    app.click_areas.extend((0..shown).map(|i| ClickArea {
+        x: inner.x,
+        y: inner.y + i as u16,
+        width: inner.width,
+        target: ClickTarget::ListRow(offset + i),
+    }));
+}
+
+/// Where each hint of a line is, from its bracket to the end of its label. Hints offering
+/// a choice of keys, such as `[j/k]`, are left out.
+fn hint_areas(text: &str, area: Rect) -> Vec<ClickArea> {
+    let right = (area.x + area.width) as usize;
+    let mut areas = Vec::new();
+    let mut column = area.x as usize;
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        column += rest[..open].width();
+        rest = &rest[open..];
+        let Some(close) = rest.find(']') else {
+            break;
+        };
+        // The label runs up to the next separator or hint.
+        let end = rest[close..]
+            .find(['|', '['])
+            .map_or(rest.len(), |i| close + i);
+        let width = rest[..end]
+            .trim_end()
+            .width()
+            .min(right.saturating_sub(column));
+        if let Some(key) = hint_key(rest[1..close].trim()).filter(|_| width > 0) {
+            areas.push(ClickArea {
+                x: column as u16,
+                y: area.y,
+                width: width as u16,
+                target: ClickTarget::Key(key),
+            });
+        }
+        column += rest[..end].width();
+        rest = &rest[end..];
+    }
+    areas
+}
+
+/// The key a hint names: `Enter`, `Esc`, `Tab`, `Space`, or a single character.
+fn hint_key(name: &str) -> Option<KeyCode> {
+    match name {
+        "Enter" => Some(KeyCode::Enter),
+        "Esc" => Some(KeyCode::Esc),
+        "Tab" => Some(KeyCode::Tab),
+        "Space" => Some(KeyCode::Char(' ')),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(KeyCode::Char(c)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Warms every color on screen for night reading. The terminal's default colors are
+/// left as they are, since there is no telling what they look like.
+fn tint_buffer(buffer: &mut Buffer, percent: u8) {
+    for cell in buffer.content.iter_mut() {
+        cell.fg = tint_color(cell.fg, percent);
+        cell.bg = tint_color(cell.bg, percent);
+    }
+}
+
+fn tint_color(color: Color, percent: u8) -> Color {
+    let Some(rgb) = named_rgb(color) else {
+        return color;
+    };
+    let [r, g, b] = app::warm_tint(rgb, percent);
+    Color::Rgb(r, g, b)
+}
+
+/// The sixteen named colors, in the order of their ANSI numbers.
+const ANSI_16: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// Levels of each channel in the 6x6x6 color cube of 256-color terminals.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Named and RGB colors as xterm shows them by default.
+fn named_rgb(color: Color) -> Option<[u8; 3]> {
+    Some(match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        Color::Black => [0, 0, 0],
+        Color::Red => [205, 0, 0],
+        Color::Green => [0, 205, 0],
+        Color::Yellow => [205, 205, 0],
+        Color::Blue => [0, 0, 238],
+        Color::Magenta => [205, 0, 205],
+        Color::Cyan => [0, 205, 205],
+        Color::Gray => [229, 229, 229],
+        Color::DarkGray => [127, 127, 127],
+        Color::LightRed => [255, 0, 0],
+        Color::LightGreen => [0, 255, 0],
+        Color::LightYellow => [255, 255, 0],
+        Color::LightBlue => [92, 92, 255],
+        Color::LightMagenta => [255, 0, 255],
+        Color::LightCyan => [0, 255, 255],
+        Color::White => [255, 255, 255],
+        Color::Reset | Color::Indexed(_) => return None,
+    })
+}
+
+/// Any color but the terminal's default as RGB, indexed ones from xterm's palette.
+fn color_rgb(color: Color) -> Option<[u8; 3]> {
+    match color {
+        Color::Indexed(i @ 0..16) => named_rgb(ANSI_16[i as usize]),
+        Color::Indexed(i @ 16..232) => {
+            let i = i - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize % 6];
+            Some([level(i / 36), level(i / 6), level(i)])
+        }
+        Color::Indexed(i) => {
+            let gray = 8 + (i - 232) * 10;
+            Some([gray, gray, gray])
+        }
+        color => named_rgb(color),
+    }
+}
+
+/// How far apart two colors look, weighting green, which the eye is most sensitive to.
+fn color_distance([r1, g1, b1]: [u8; 3], [r2, g2, b2]: [u8; 3]) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    2 * d(r1, r2) + 4 * d(g1, g2) + 3 * d(b1, b2)
+}
+
+/// The nearest color a terminal of `depth` can show. The default color and colors it
+/// already has are kept.
+fn degrade_color(color: Color, depth: ColorDepth) -> Color {
+    let Some(rgb) = color_rgb(color) else {
+        return color;
+    };
+    match depth {
+        ColorDepth::TrueColor => color,
+        ColorDepth::Mono => Color::Reset,
+        ColorDepth::Ansi256 if matches!(color, Color::Indexed(_)) => color,
+        ColorDepth::Ansi256 => {
+            let nearest = |c: u8| {
+                (0..6)
+                    .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+                    .unwrap_or(0) as u8
+            };
+            let [r, g, b] = rgb.map(nearest);
+            let cube = 16 + 36 * r + 6 * g + b;
+            let step = (rgb.iter().map(|&c| c as u32).sum::<u32>() / 3).clamp(8, 238);
+            let gray = 232 + ((step - 8 + 5) / 10).min(23) as u8;
+            [cube, gray]
+                .into_iter()
+                .min_by_key(|&i| color_distance(rgb, color_rgb(Color::Indexed(i)).unwrap()))
+                .map_or(color, Color::Indexed)
+        }
+        ColorDepth::Ansi16 if ANSI_16.contains(&color) => color,
+        ColorDepth::Ansi16 => ANSI_16
+            .into_iter()
+            .min_by_key(|&c| color_distance(rgb, named_rgb(c).unwrap()))
+            .unwrap_or(color),
+    }
+}
+
+/// Brings every color on screen down to what the terminal can show. Text whose colors
+/// end up the same as its background is made black or white, whichever shows. Without
+/// color, cells that stood out by their background from the rest of the screen, such
+/// as selections, highlights and the status bar, are shown in reverse video.
+fn degrade_buffer(buffer: &mut Buffer, depth: ColorDepth) {
+    if depth == ColorDepth::TrueColor {
+        return;
+    }
+    if depth == ColorDepth::Mono {
+        let page = page_background(buffer);
+        for cell in buffer.content.iter_mut() {
+            let stands_out = match (color_rgb(cell.bg), page.and_then(color_rgb)) {
+                (Some(bg), Some(page)) => color_distance(bg, page) > MONO_CONTRAST,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if stands_out {
+                cell.modifier.insert(Modifier::REVERSED);
+            }
+            cell.fg = Color::Reset;
+            cell.bg = Color::Reset;
+        }
+        return;
+    }
+    for cell in buffer.content.iter_mut() {
+        let (fg, bg) = (degrade_color(cell.fg, depth), degrade_color(cell.bg, depth));
+        let merged = fg == bg && fg != Color::Reset && cell.fg != cell.bg;
+        cell.fg = match color_rgb(bg) {
+            Some(bg) if merged => {
+                if bg.iter().map(|&c| c as u32).sum::<u32>() > 3 * 128 {
+                    Color::Black
+                } else {
+                    Color::White
+                }
+            }
+            _ => fg,
+        };
+        cell.bg = bg;
+    }
+}
+
+/// How far a background has to be from the screen's to be shown reversed without color;
+/// enough to leave the slightly lighter panels of a theme alone.
+const MONO_CONTRAST: u32 = 9 * 48 * 48;
+
+/// The background most cells on screen have.
+fn page_background(buffer: &Buffer) -> Option<Color> {
+    let mut counts: Vec<(Color, usize)> = Vec::new();
+    for cell in &buffer.content {
+        match counts.iter_mut().find(|(c, _)| *c == cell.bg) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((cell.bg, 1)),
+        }
+    }
+    counts.into_iter().max_by_key(|(_, n)| *n).map(|(c, _)| c)
+}
+
+/// Cuts `text` to at most `width` terminal columns, marking the cut with an ellipsis.
+/// Graphemes stay whole, so accents and emoji are never split, and wide characters
+/// such as CJK count as the two columns they take.
+pub fn ellipsize(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut cut = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        used += grapheme.width();
+        // One column stays free for the ellipsis.
+        if used >= width {
+            break;
+        }
+        cut.push_str(grapheme);
+    }
+    cut.push('…');
+    cut
+}
+
+/// [`ellipsize`], then padded with spaces to exactly `width` columns, for aligned rows.
+pub fn fit_width(text: &str, width: usize) -> String {
+    let mut fitted = ellipsize(text, width);
+    let padding = width.saturating_sub(fitted.width());
+    fitted.extend(std::iter::repeat_n(' ', padding));
+    fitted
+}
+
+/// Whole minutes, switching to hours past the first: `45 min`, `1h 05m`.
+pub fn format_minutes(secs: u64) -> String {
+    let mins = secs / 60;
+    if mins < 60 {
+        format!("{} min", mins)
+    } else {
+        format!("{}h {:02}m", mins / 60, mins % 60)
+    }
+}
+
+/// `1 day` or `4 days`.
+pub fn format_days(days: usize) -> String {
+    if days == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", days)
+    }
+}
+
+/// A count in three figures or fewer: `840`, `1.2k`, `15k`.
+pub fn format_count(count: usize) -> String {
+    if count < 1000 {
+        count.to_string()
+    } else if count < 10_000 {
+        format!("{:.1}k", count as f64 / 1000.0)
+    } else {
+        format!("{}k", (count + 500) / 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ellipsizing_counts_columns_and_keeps_graphemes_whole() {
+        assert_eq!(ellipsize("Moby-Dick", 20), "Moby-Dick");
+        assert_eq!(ellipsize("Moby-Dick", 5), "Moby…");
+        // Each of these takes two columns.
+        assert_eq!(ellipsize("吾輩は猫である", 7), "吾輩は…");
+        // The accent is a separate code point that stays with its letter.
+        assert_eq!(ellipsize("Que\u{301}bec libre", 5), "Que\u{301}b…");
+        assert_eq!(ellipsize("👩‍👩‍👧 family", 4), "👩‍👩‍👧 …");
+        assert_eq!(ellipsize("anything", 0), "");
+        assert_eq!(fit_width("吾輩は猫である", 8), "吾輩は… ");
+        assert_eq!(fit_width("Moby", 6), "Moby  ");
+    }
+
+    #[test]
+    fn counts_are_shortened_to_three_figures() {
+        assert_eq!(format_count(840), "840");
+        assert_eq!(format_count(1240), "1.2k");
+        assert_eq!(format_count(15_400), "15k");
+    }
+
+    #[test]
+    fn hints_are_clickable_from_bracket_to_label() {
+        let footer = " [Enter] Open | [j/k] Move | [Esc] Back ";
+        let areas = hint_areas(footer, Rect::new(2, 9, 30, 1));
+        let spans: Vec<_> = areas.iter().map(|a| (a.x, a.width, a.target)).collect();
+        // `[j/k]` offers a choice of keys, and `[Esc] Back` is cut off at the edge.
+        assert_eq!(
+            spans,
+            vec![
+                (3, 12, ClickTarget::Key(KeyCode::Enter)),
+                (31, 1, ClickTarget::Key(KeyCode::Esc)),
+            ]
+        );
+        assert!(areas.iter().all(|a| a.y == 9));
+    }
+
+    #[test]
+    fn colors_come_down_to_what_the_terminal_shows() {
+        let detect = |colorterm, term| ColorDepth::detect(colorterm, term, false);
+        assert_eq!(detect("truecolor", "xterm"), ColorDepth::TrueColor);
+        assert_eq!(detect("", "tmux-256color"), ColorDepth::Ansi256);
+        assert_eq!(detect("", "linux"), ColorDepth::Ansi16);
+        assert_eq!(detect("24bit", "dumb"), ColorDepth::Mono);
+        let no_color = ColorDepth::detect("truecolor", "xterm", true);
+        assert_eq!(no_color, ColorDepth::Mono);
+        assert_eq!(ColorDepth::from_str("auto"), None);
+
+        // Gruvbox's background and text.
+        let (bg, fg) = (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178));
+        assert_eq!(degrade_color(bg, ColorDepth::Ansi256), Color::Indexed(235));
+        assert_eq!(degrade_color(fg, ColorDepth::Ansi256), Color::Indexed(187));
+        assert_eq!(degrade_color(bg, ColorDepth::Ansi16), Color::Black);
+        assert_eq!(degrade_color(fg, ColorDepth::Ansi16), Color::Gray);
+        assert_eq!(degrade_color(Color::Blue, ColorDepth::Ansi16), Color::Blue);
+        let reset = degrade_color(Color::Reset, ColorDepth::Ansi16);
+        assert_eq!(reset, Color::Reset);
+
+        // Dark gray on near black both become black, so the text turns white.
+        let mut buffer = Buffer::empty(ratatui::layout::Rect::new(0, 0, 2, 1));
+        buffer.content[0].fg = Color::Rgb(50, 50, 50);
+        buffer.content[0].bg = Color::Rgb(10, 10, 10);
+        degrade_buffer(&mut buffer, ColorDepth::Ansi16);
+        assert_eq!(
+            (buffer.content[0].fg, buffer.content[0].bg),
+            (Color::White, Color::Black)
+        );
+    }
+
+    #[test]
+    fn without_color_what_stood_out_is_reversed() {
+        let mut buffer = Buffer::empty(ratatui::layout::Rect::new(0, 0, 4, 1));
+        for cell in buffer.content.iter_mut() {
+            cell.fg = Color::Rgb(235, 219, 178);
+            cell.bg = Color::Rgb(40, 40, 40);
+        }
+        // A slightly lighter panel, and a status bar.
+        buffer.content[1].bg = Color::Rgb(50, 48, 47);
+        buffer.content[3].bg = Color::Blue;
+        degrade_buffer(&mut buffer, ColorDepth::Mono);
+        let reversed: Vec<bool> = buffer
+            .content
+            .iter()
+            .map(|c| c.modifier.contains(Modifier::REVERSED))
+            .collect();
+        assert_eq!(reversed, [false, false, false, true]);
+        assert!(
+            buffer
+                .content
+                .iter()
+                .all(|c| c.fg == Color::Reset && c.bg == Color::Reset)
+        );
+    }
 }
@@ -0,0 +1,127 @@
+use crate::app::App;
+use crate::library;
+use crate::ui::annotation::{
+    attach_prompt, format_attachments, format_tags, get_theme_colors, kind_color, tag_prompt,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+    let outer = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(outer[1]);
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let browser = &app.note_browser;
+    let filters = format!(
+        " [b] Book: {} | [1-9] Kind: {} | [t] Tag: {} | [d] {} ",
+        app.note_browser_book_title(),
+        app.note_browser_kind_label(),
+        browser.tag.as_deref().map_or("any".to_string(), |t| format!("#{}", t)),
+        browser.period.label()
+    );
+    f.render_widget(
+        Paragraph::new(filters).style(Style::default().fg(Color::Cyan).bg(bg)),
+        outer[0],
+    );
+
+    let title_of = |book_id: i32| {
+        app.books
+            .iter()
+            .find(|b| b.id == book_id)
+            .map(|b| b.title.as_str())
+            .unwrap_or("Unknown Book")
+    };
+    let notes = app.browsed_notes();
+    let items: Vec<ListItem> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            let style = if i == browser.selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(kind_color(app, &a.kind)).bg(bg)
+            };
+            let tags: String = a.tags.iter().map(|t| format!(" #{}", t)).collect();
+            ListItem::new(format!(
+                "{} {} [Ch {}]: {}{}",
+                library::kind_label(&a.kind),
+                title_of(a.book_id),
+                a.chapter + 1,
+                a.content.replace('\n', " "),
+                tags
+            ))
+            .style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(" Notes across the library ({}) ", notes.len()))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    if !notes.is_empty() {
+        list_state.select(Some(browser.selected));
+    }
+    f.render_stateful_widget(list, panes[0], &mut list_state);
+
+    let detail = match notes.get(browser.selected) {
+        Some(a) => format!(
+            "{}  Ch {}\n{} ({})\n\n{}\n\nNote: {}\nTags: {}{}",
+            title_of(a.book_id),
+            a.chapter + 1,
+            a.created_at
+                .as_deref()
+                .and_then(|c| c.get(..10))
+                .unwrap_or("-"),
+            a.kind,
+            a.content,
+            a.note.as_deref().unwrap_or("-"),
+            format_tags(&a.tags),
+            format_attachments(&a.attachments)
+        ),
+        None if browser.notes.is_empty() => {
+            "No notes yet.\nHighlight text in select mode to add some.".to_string()
+        }
+        None => "No notes match these filters.".to_string(),
+    };
+    let detail = Paragraph::new(detail)
+        .block(
+            Block::default()
+                .title(" Detail ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(detail, panes[1]);
+
+    let footer = match (&app.tag_input, &app.attach_input) {
+        (Some(tags), _) => tag_prompt(tags),
+        (None, Some(path)) => attach_prompt(path),
+        (None, None) => " [T] Edit Tags | [F] Attach File | [Enter] Open | [Esc] Back ".to_string(),
+    };
+    super::render_footer(f, app, &footer, outer[2], Style::default().fg(fg).bg(bg));
+}
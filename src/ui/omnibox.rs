@@ -0,0 +1,103 @@
+use crate::app::App;
+use crate::omnibox::OmniboxHit;
+use crate::ui::annotation::get_theme_colors;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let input = Paragraph::new(app.omnibox_query.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Search Books, Notes, Words and Text - Type and press Enter ")
+            .style(Style::default().fg(fg).bg(bg)),
+    );
+    f.render_widget(input, chunks[0]);
+
+    // Results are grouped under a heading for each kind.
+    let mut items = Vec::new();
+    let mut selected = None;
+    for (i, hit) in app.omnibox_results.iter().enumerate() {
+        if i == 0 || app.omnibox_results[i - 1].group() != hit.group() {
+            items.push(
+                ListItem::new(hit.group()).style(
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .bg(bg)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            );
+        }
+        let label = match hit {
+            OmniboxHit::Book(book) => format!("{} by {}", book.title, book.author),
+            OmniboxHit::Note(title, note) => {
+                let mut label = format!("{}: \"{}\"", title, note.content.replace('\n', " "));
+                if let Some(ref text) = note.note {
+                    label.push_str(&format!(" - {}", text.replace('\n', " ")));
+                }
+                label
+            }
+            OmniboxHit::Word(word) => {
+                // The first line of a definition past any heading is its gist.
+                let gist = word
+                    .definition
+                    .lines()
+                    .map(str::trim)
+                    .find(|l| !l.is_empty() && !l.starts_with('#'))
+                    .unwrap_or_default();
+                match word.book_title {
+                    Some(ref title) => format!("{}: {} ({})", word.word, gist, title),
+                    None => format!("{}: {}", word.word, gist),
+                }
+            }
+            OmniboxHit::Text(hit) => format!("{}: {}", hit.title, hit.snippet),
+        };
+        let style = if i == app.selected_omnibox_index {
+            selected = Some(items.len());
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(fg).bg(bg)
+        };
+        items.push(ListItem::new(format!("  {}", label)).style(style));
+    }
+
+    let results_title = match app.omnibox_results.len() {
+        0 => " Results ".to_string(),
+        1 => " 1 result ".to_string(),
+        n => format!(" {} results ", n),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(results_title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    list_state.select(selected);
+    f.render_stateful_widget(list, chunks[1], &mut list_state);
+
+    let footer = " [Enter] Search/Go to Result | [Up/Down] Results | [Esc] Back ";
+    super::render_footer(f, app, footer, chunks[2], Style::default().fg(fg).bg(bg));
+}
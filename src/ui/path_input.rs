@@ -2,7 +2,7 @@ use crate::app::{App, Theme};
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Frame,
 };
 
@@ -17,7 +17,12 @@ pub fn render(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(5)
-        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
         .split(f.area());
 
     // Fill background
@@ -47,12 +52,49 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .alignment(Alignment::Left);
     f.render_widget(input, chunks[1]);
 
-    let help = Paragraph::new(" [Enter] Scan/Select | [Esc] Cancel ")
-        .alignment(Alignment::Center)
-        .style(Style::default().fg(fg).bg(bg));
-    let help_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(1)])
-        .split(chunks[1]);
-    f.render_widget(help, help_chunks[1]);
+    // Completion candidates take over the dropdown while Tab is cycling them.
+    let (list_title, entries, selected) = if !app.path_completions.is_empty() {
+        (
+            " Completions [Tab] ",
+            &app.path_completions,
+            app.path_completion_index,
+        )
+    } else {
+        (
+            " Recent Scans [Up/Down] Select | [Ctrl-d] Forget ",
+            &app.recent_scan_paths,
+            app.selected_recent_path,
+        )
+    };
+    let items: Vec<ListItem> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let style = if Some(i) == selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(fg).bg(bg)
+            };
+            ListItem::new(p.as_str()).style(style)
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(list_title)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .highlight_symbol(">> ");
+    let mut list_state = ListState::default();
+    list_state.select(selected);
+    f.render_stateful_widget(list, chunks[2], &mut list_state);
+
+    let help =
+        Paragraph::new(" [Enter] Scan/Select | [Tab] Complete | [Up/Down] Recent | [Esc] Cancel ")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(fg).bg(bg));
+    f.render_widget(help, chunks[3]);
 }
@@ -1,16 +1,27 @@
-use crate::app::{AnnotationKind, App, AppView, RenderLine, Theme};
+use crate::app::{
+    App, AppView, FinishLine, FocusRuler, LineFocus, LoadedBook, PageLayout, RenderLine,
+    SessionSummary, Theme, WordArea,
+};
+use crate::library::AnnotationKind;
+use crate::ui::annotation::get_theme_colors;
+use crate::ui::{ellipsize, format_count, format_days, format_minutes};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 use ratatui_image::{protocol::StatefulProtocol, StatefulImage};
 use std::collections::HashSet;
 use unicode_width::UnicodeWidthStr;
 
-fn wrap_words_to_lines<'a>(words: &'a [&'a str], max_width: u16) -> Vec<Vec<(usize, &'a str)>> {
+/// `first_indent` columns are taken from the first line, which starts indented.
+fn wrap_words_to_lines<'a>(
+    words: &'a [&'a str],
+    max_width: u16,
+    first_indent: u16,
+) -> Vec<Vec<(usize, &'a str)>> {
     let max_width = max_width as usize;
     if max_width == 0 {
         return vec![Vec::new()];
@@ -18,7 +29,7 @@ fn wrap_words_to_lines<'a>(words: &'a [&'a str], max_width: u16) -> Vec<Vec<(usi
 
     let mut out: Vec<Vec<(usize, &str)>> = Vec::new();
     let mut current: Vec<(usize, &str)> = Vec::new();
-    let mut current_w = 0usize;
+    let mut current_w = first_indent as usize;
 
     for (idx, w) in words.iter().enumerate() {
         let ww = UnicodeWidthStr::width(*w);
@@ -44,16 +55,325 @@ fn wrap_words_to_lines<'a>(words: &'a [&'a str], max_width: u16) -> Vec<Vec<(usi
     out
 }
 
+/// Spaces after each word of a wrapped row starting at column `start`: one each, or when
+/// `justify`ing, as many as make the row end flush with `width`, the first gaps taking
+/// any left over.
+fn row_gaps(words: &[(usize, &str)], start: u16, width: u16, justify: bool) -> Vec<u16> {
+    let mut gaps = vec![1; words.len()];
+    let between = words.len().saturating_sub(1);
+    if !justify || between == 0 {
+        return gaps;
+    }
+    let text: usize = words.iter().map(|(_, w)| UnicodeWidthStr::width(*w)).sum();
+    let slack = (width as usize).saturating_sub(start as usize + text + between);
+    for (i, gap) in gaps[..between].iter_mut().enumerate() {
+        *gap += (slack / between + usize::from(i < slack % between)) as u16;
+    }
+    gaps
+}
+
+/// Rows `line` takes in the wrapped reader, and how many of those are spacing after its
+/// last row of text.
+pub fn line_rows(line: &RenderLine, layout: &PageLayout) -> (usize, usize) {
+    let (spacing, paragraph) = (
+        layout.line_spacing as usize,
+        layout.paragraph_spacing as usize,
+    );
+    match line {
+        RenderLine::Text(text) => {
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let is_paragraph = !words.is_empty() && !text.trim_start().starts_with('#');
+            let indent = if is_paragraph { layout.first_line_indent } else { 0 };
+            let rows = wrap_words_to_lines(&words, layout.width, indent).len();
+            let after = if is_paragraph { paragraph } else { 0 };
+            (rows * (1 + spacing) + after, spacing + after)
+        }
+        RenderLine::Caption(caption) => {
+            let words: Vec<&str> = caption.split_whitespace().collect();
+            let rows = wrap_words_to_lines(&words, layout.width, 0).len();
+            (rows + paragraph, paragraph)
+        }
+        RenderLine::Image { .. } => (1, 0),
+    }
+}
+
+/// Where typewriter scrolling starts drawing a column `height` rows tall: the first line
+/// shown and the blank rows above it, so the line at the top of the viewport begins in
+/// the middle with the text before it above.
+fn typewriter_start(book: &LoadedBook, layout: &PageLayout, height: u16) -> (usize, u16) {
+    let half = (height / 2) as usize;
+    let mut first = book.viewport_top.min(book.chapter_content.len());
+    let mut above = 0;
+    while first > 0 {
+        let rows = line_rows(&book.chapter_content[first - 1], layout).0;
+        if above + rows > half {
+            break;
+        }
+        above += rows;
+        first -= 1;
+    }
+    (first, (half - above) as u16)
+}
+
+/// The words on each row `line` wraps to in the reader, as word indices with the column
+/// each starts at. Images and blank lines are a single row without words.
+pub fn word_rows(line: &RenderLine, layout: &PageLayout) -> Vec<Vec<(usize, u16)>> {
+    let RenderLine::Text(text) = line else {
+        return vec![Vec::new()];
+    };
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let is_paragraph = !words.is_empty() && !text.trim_start().starts_with('#');
+    let indent = if is_paragraph { layout.first_line_indent } else { 0 };
+    let wrapped = wrap_words_to_lines(&words, layout.width, indent);
+    let last_row = wrapped.len() - 1;
+    wrapped
+        .into_iter()
+        .enumerate()
+        .map(|(row, line_words)| {
+            let mut x = if row == 0 { indent } else { 0 };
+            let justify = layout.justify && is_paragraph && row < last_row;
+            let gaps = row_gaps(&line_words, x, layout.width, justify);
+            line_words
+                .into_iter()
+                .zip(gaps)
+                .map(|((i, w), gap)| {
+                    let at = x;
+                    x = x.saturating_add(UnicodeWidthStr::width(w) as u16 + gap);
+                    (i, at)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The theme background moved `percent` of the way towards the text color. Terminal
+/// default colors can't be mixed, so those get a gray of that strength instead.
+fn focus_color(bg: Color, fg: Color, percent: u8) -> Color {
+    let t = percent.min(100) as f32 / 100.0;
+    match (bg, fg) {
+        (Color::Rgb(br, bg_, bb), Color::Rgb(fr, fg_, fb)) => {
+            let mix = |b: u8, f: u8| (b as f32 + (f as f32 - b as f32) * t).round() as u8;
+            Color::Rgb(mix(br, fr), mix(bg_, fg_), mix(bb, fb))
+        }
+        _ => {
+            let gray = (255.0 * t).round() as u8;
+            Color::Rgb(gray, gray, gray)
+        }
+    }
+}
+
+/// Longest chapter title the status bar shows before cutting it short.
+const MAX_STATUS_TITLE: usize = 32;
+/// Space between side-by-side text columns.
+const COLUMN_GAP: u16 = 4;
+/// Columns narrower than this are merged, so small terminals fall back to fewer columns.
+const MIN_COLUMN_WIDTH: u16 = 20;
+/// Shown in the status bar while the export format picker is open.
+const EXPORT_PROMPT: &str =
+    "Export notes as: [m] Markdown [j] JSON [c] CSV [h] HTML [o] Org | [Esc] Cancel";
+const PRESET_PROMPT: &str =
+    "Reading preset: [n] Novel [t] Textbook [p] Paper [o] None | [Esc] Cancel";
+
+/// Splits the text area into up to `count` equal columns, each at most `max_width` wide,
+/// centered in the area. Each column keeps `gutter` cells on its left for line numbers,
+/// which the returned text areas leave out.
+fn column_areas(area: Rect, count: u16, max_width: Option<u16>, gutter: u16) -> Vec<Rect> {
+    let mut count = count.max(1);
+    while count > 1
+        && area.width.saturating_sub(COLUMN_GAP * (count - 1)) / count < MIN_COLUMN_WIDTH + gutter
+    {
+        count -= 1;
+    }
+    let gaps = COLUMN_GAP * (count - 1);
+    let mut width = area.width.saturating_sub(gaps) / count;
+    if let Some(max) = max_width {
+        width = width.min(max + gutter);
+    }
+    let left = area.x + area.width.saturating_sub(width * count + gaps) / 2;
+    let gutter = gutter.min(width);
+    (0..count)
+        .map(|i| Rect {
+            x: left + i * (width + COLUMN_GAP) + gutter,
+            y: area.y,
+            width: width - gutter,
+            height: area.height,
+        })
+        .collect()
+}
+
+/// Width of one reader text column in a terminal `width` cells wide.
+pub fn column_width(
+    width: u16,
+    margin: u16,
+    columns: u16,
+    max_width: Option<u16>,
+    gutter: u16,
+) -> u16 {
+    let area = Rect::new(0, 0, width.saturating_sub(margin.saturating_mul(2)), 1);
+    column_areas(area, columns, max_width, gutter)[0].width
+}
+
+/// Text columns that fit side by side in a terminal `width` cells wide.
+pub fn column_count(
+    width: u16,
+    margin: u16,
+    columns: u16,
+    max_width: Option<u16>,
+    gutter: u16,
+) -> usize {
+    let area = Rect::new(0, 0, width.saturating_sub(margin.saturating_mul(2)), 1);
+    column_areas(area, columns, max_width, gutter).len()
+}
+
+/// How the search bars describe the match-case setting.
+pub fn case_label(case_sensitive: bool) -> &'static str {
+    if case_sensitive {
+        "regex, matching case"
+    } else {
+        "regex, ignoring case"
+    }
+}
+
+/// Moves `y` to the top of the next column once it has run past the bottom of the
+/// current one. Returns false when every column is full.
+fn next_row(y: &mut u16, column: &mut usize, columns: &[Rect]) -> bool {
+    while let Some(area) = columns.get(*column) {
+        if *y < area.bottom() {
+            return true;
+        }
+        *column += 1;
+        if let Some(next) = columns.get(*column) {
+            *y = next.y;
+        }
+    }
+    false
+}
+
+/// The status bar's countdown to the next heading and the chapter's end, kept short:
+/// `Left: § 340w, ch 1.2kw`, or `Left: ch 3pg` when turning pages.
+pub fn finish_line_label(finish: &FinishLine) -> String {
+    let chapter = match finish.chapter_pages {
+        Some(pages) => format!("ch {}pg", pages),
+        None => format!("ch {}w", format_count(finish.chapter_words)),
+    };
+    match finish.section_words {
+        Some(words) => format!("Left: § {}w, {}", format_count(words), chapter),
+        None => format!("Left: {}", chapter),
+    }
+}
+
+/// Roughly how long the rest of the chapter and of the book take at `wpm`, e.g.
+/// `~6 min ch, ~4h 10m book`. The book is left out until its words are counted.
+pub fn time_left_label(finish: &FinishLine, wpm: f64) -> String {
+    let time = |words: usize| match (words as f64 / wpm.max(1.0) * 60.0) as u64 {
+        secs if secs < 60 => "<1 min".to_string(),
+        secs => format!("~{}", format_minutes(secs)),
+    };
+    let chapter = format!("{} ch", time(finish.chapter_words));
+    match finish.book_words {
+        Some(words) => format!("{}, {} book", chapter, time(words)),
+        None => chapter,
+    }
+}
+
+/// One gauge of the progress line: `label`, a bar filled to `ratio`, and its percentage,
+/// together `width` columns wide.
+fn gauge_spans(label: &str, ratio: f64, width: usize, fg: Color) -> Vec<Span<'static>> {
+    let percent = format!(" {:>3.0}% ", ratio * 100.0);
+    let bar = width.saturating_sub(label.width() + percent.len() + 2);
+    let filled = ((ratio.clamp(0.0, 1.0) * bar as f64).round() as usize).min(bar);
+    vec![
+        Span::styled(format!(" {} ", label), Style::default().fg(fg)),
+        Span::styled("━".repeat(filled), Style::default().fg(fg)),
+        Span::styled(
+            "─".repeat(bar - filled),
+            Style::default().fg(fg).add_modifier(Modifier::DIM),
+        ),
+        Span::styled(percent, Style::default().fg(fg)),
+    ]
+}
+
+/// The way through the chapter and the book, side by side. PDF and DjVu chapters are
+/// single pages, so they get the book's gauge alone.
+pub fn progress_gauge_line(
+    chapter: Option<f64>,
+    book: f64,
+    width: u16,
+    fg: Color,
+) -> Line<'static> {
+    let width = width as usize;
+    match chapter {
+        Some(chapter) => {
+            let half = width / 2;
+            let mut spans = gauge_spans("Ch", chapter, half, fg);
+            spans.extend(gauge_spans("Book", book, width - half, fg));
+            Line::from(spans)
+        }
+        None => Line::from(gauge_spans("Book", book, width, fg)),
+    }
+}
+
 pub fn render(f: &mut Frame, app: &mut App) {
     // Call these before mutably borrowing book
     let selection = app.get_selection_range();
     let (_, wpm) = app.get_reading_stats();
+    let rolling_wpm = app.rolling_wpm();
     let pomodoro_label = app.pomodoro_label();
     let pomodoro_running = app.pomodoro.running;
     let focus_mode = app.focus_mode;
+    let show_gauge = app.progress_gauge.shown(focus_mode);
     let view = app.view;
     let margin = app.margin;
     let line_spacing = app.line_spacing;
+    let (paragraph_spacing, first_line_indent) = app.paragraph_layout();
+    let justify = app.justify;
+    let line_focus = if view == AppView::Reader {
+        app.line_focus
+    } else {
+        LineFocus::Off
+    };
+    let line_focus_strength = app.line_focus_strength();
+    let typewriter = app.typewriter_active();
+    let ruler = if typewriter {
+        app.focus_ruler
+    } else {
+        FocusRuler::Off
+    };
+    let pdf_reflow = app.pdf_reflow;
+    let export_picker = app.export_picker;
+    let sync_prompt = app.sync_conflict_prompt();
+    let sync_label = app.sync_state.map(|state| state.label());
+    let prompting = export_picker
+        || app.preset_picker
+        || sync_prompt.is_some()
+        || app.goto_input.is_some()
+        || app.group_input.is_some();
+    // A count or `g` waiting for the rest of its command shows, as vim's showcmd does.
+    let pending_keys = match (app.key_count, app.pending_g) {
+        (None, false) => None,
+        (count, g) => Some(format!(
+            "{}{}",
+            count.map(|c| c.to_string()).unwrap_or_default(),
+            if g { "g" } else { "" }
+        )),
+    };
+    let status_message = if export_picker {
+        Some(EXPORT_PROMPT.to_string())
+    } else if app.preset_picker {
+        Some(PRESET_PROMPT.to_string())
+    } else if sync_prompt.is_some() {
+        sync_prompt
+    } else if let Some(ref input) = app.goto_input {
+        Some(format!("Go to chapter or percent (e.g. 12 or 40%): {}_", input))
+    } else if let Some(ref token) = app.group_input {
+        Some(format!("Paste a group member's position: {}_", token))
+    } else {
+        app.status_message.clone().or(pending_keys)
+    };
+    let annotation_kinds = app.annotation_kinds.clone();
+    let (text_columns, max_text_width) = (app.text_columns, app.max_text_width);
+    let (line_numbers, gutter) = (app.line_numbers, app.line_number_width());
+    app.word_areas.clear();
 
     if let Some(ref mut book) = app.current_book {
         let (bg, fg) = match app.theme {
@@ -64,13 +384,18 @@ pub fn render(f: &mut Frame, app: &mut App) {
         };
 
         let is_search = matches!(view, crate::app::AppView::Search);
+        let case_sensitive = app.search_case_sensitive;
+        let search_matches =
+            App::search_matches(book, &app.search_query, case_sensitive).to_vec();
+        let search_position = App::search_position(book, &app.search_query, case_sensitive);
         let show_top = !focus_mode;
-        let show_status = !focus_mode || pomodoro_running;
+        let show_status = !focus_mode || pomodoro_running || prompting;
 
         let constraints = [
             Constraint::Length(if show_top { 1 } else { 0 }),
             Constraint::Min(0),
             Constraint::Length(if is_search { 3 } else { 0 }),
+            Constraint::Length(if show_gauge { 1 } else { 0 }),
             Constraint::Length(if show_status { 1 } else { 0 }),
         ];
 
@@ -84,35 +409,56 @@ pub fn render(f: &mut Frame, app: &mut App) {
 
         // 1. Render Top Bar with Buttons
         if show_top {
-            let top_bar_style = Style::default().bg(Color::Rgb(50, 50, 50)).fg(Color::White);
+            let rgb = |color: Option<[u8; 3]>, default: Color| {
+                color.map_or(default, |[r, g, b]| Color::Rgb(r, g, b))
+            };
+            let bar = &app.top_bar;
+            let top_bar_style = Style::default()
+                .bg(rgb(bar.background, Color::Rgb(50, 50, 50)))
+                .fg(rgb(bar.foreground, Color::White));
+            // The buttons are for clicking, so by default they go when the mouse does.
+            let show_buttons = bar.buttons.unwrap_or(app.mouse_capture);
             let top_chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([
                     Constraint::Min(0),
-                    Constraint::Length(15), // Buttons area
+                    Constraint::Length(if show_buttons { 15 } else { 0 }), // Buttons area
                 ])
                 .split(chunks[0]);
 
-            let title_text = format!(" Reading: {}", book.path);
+            let title_text = if app.read_only {
+                format!(" Reading (read-only): {}", book.path)
+            } else {
+                format!(" Reading: {}", book.path)
+            };
             f.render_widget(
                 Paragraph::new(title_text).style(top_bar_style),
                 top_chunks[0],
             );
 
-            // Buttons for mouse click detection
-            let buttons = Line::from(vec![
-                Span::styled(
-                    " [ - ] ",
-                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-                ),
-                Span::styled(
-                    " [ + ] ",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD),
-                ),
-            ]);
-            f.render_widget(Paragraph::new(buttons).style(top_bar_style), top_chunks[1]);
+            if show_buttons {
+                // Buttons for mouse click detection
+                let buttons = Line::from(vec![
+                    Span::styled(
+                        " [ - ] ",
+                        Style::default()
+                            .fg(rgb(bar.minus, Color::Red))
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        " [ + ] ",
+                        Style::default()
+                            .fg(rgb(bar.plus, Color::Green))
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                ]);
+                f.render_widget(Paragraph::new(buttons).style(top_bar_style), top_chunks[1]);
+                // The buttons press `-` and `+`, which only mean the margin while reading.
+                if view == crate::app::AppView::Reader {
+                    app.click_areas
+                        .extend(super::hint_areas(" [ - ]  [ + ] ", top_chunks[1]));
+                }
+            }
         }
 
         let _viewport_height = chunks[1].height as usize;
@@ -120,6 +466,28 @@ pub fn render(f: &mut Frame, app: &mut App) {
             .margin(margin)
             .constraints([Constraint::Percentage(100)])
             .split(chunks[1])[0];
+        let columns = column_areas(area, text_columns, max_text_width, gutter);
+        let number_style = Style::default().fg(fg).bg(bg).add_modifier(Modifier::DIM);
+        // The chapter line number, right-aligned in the gutter left of `area`.
+        let current_line = book.current_line;
+        let line_number = |logical_i: usize, area: Rect, y: u16| {
+            let number = line_numbers.number(logical_i, current_line)?;
+            let style = if logical_i == current_line {
+                number_style.remove_modifier(Modifier::DIM).add_modifier(Modifier::BOLD)
+            } else {
+                number_style
+            };
+            let text = format!("{:>width$} ", number, width = gutter as usize - 1);
+            Some((Paragraph::new(text).style(style), Rect::new(area.x - gutter, y, gutter, 1)))
+        };
+        let page_layout = PageLayout {
+            rows: columns.iter().map(|c| c.height as usize).sum(),
+            width: columns[0].width,
+            line_spacing,
+            paragraph_spacing,
+            first_line_indent,
+            justify,
+        };
 
         let mut rendered_protocols = HashSet::new();
 
@@ -130,15 +498,28 @@ pub fn render(f: &mut Frame, app: &mut App) {
             AppView::Reader | AppView::Search | AppView::Rsvp | AppView::Select | AppView::Visual
         );
 
-        let annotation_bg = |kind: &str| match AnnotationKind::from_str(kind) {
-            AnnotationKind::Highlight => Color::Rgb(80, 60, 40),
-            AnnotationKind::Question => Color::Rgb(40, 60, 120),
-            AnnotationKind::Summary => Color::Rgb(40, 80, 40),
+        let focus_bg = focus_color(bg, fg, line_focus_strength);
+        let middle_y = columns[0].y + columns[0].height / 2;
+        let mut focus_drawn = false;
+
+        let annotation_bg = |kind: &str| match annotation_kinds.iter().find(|k| k.name == kind) {
+            Some(custom) => Color::Rgb(custom.color[0], custom.color[1], custom.color[2]),
+            None => match AnnotationKind::from_str(kind) {
+                AnnotationKind::Highlight => Color::Rgb(80, 60, 40),
+                AnnotationKind::Question => Color::Rgb(40, 60, 120),
+                AnnotationKind::Summary => Color::Rgb(40, 80, 40),
+            },
         };
 
-        let mut y = area.y;
-        let mut logical_i = book.viewport_top;
-        while y < area.y.saturating_add(area.height) && logical_i < book.chapter_content.len() {
+        let mut column = 0;
+        let (mut logical_i, mut y) = if typewriter {
+            let (first, blank) = typewriter_start(book, &page_layout, columns[0].height);
+            (first, columns[0].y + blank)
+        } else {
+            (book.viewport_top, columns[0].y)
+        };
+        while next_row(&mut y, &mut column, &columns) && logical_i < book.chapter_content.len() {
+            let area = columns[column];
             let line_content = &book.chapter_content[logical_i];
 
             match line_content {
@@ -230,6 +611,9 @@ pub fn render(f: &mut Frame, app: &mut App) {
                             spans.push(Span::styled(format!("{} ", word), style));
                         }
 
+                        if let Some((number, number_area)) = line_number(logical_i, area, y) {
+                            f.render_widget(number, number_area);
+                        }
                         f.render_widget(
                             Paragraph::new(Line::from(spans)).wrap(Wrap { trim: false }),
                             line_area,
@@ -241,11 +625,17 @@ pub fn render(f: &mut Frame, app: &mut App) {
 
                     // Wrapped render path (Reader/Search): split into visual lines based on area.width
                     let words: Vec<&str> = text.split_whitespace().collect();
-                    let wrapped = wrap_words_to_lines(&words, area.width);
-                    for line_words in wrapped {
-                        if y >= area.y.saturating_add(area.height) {
+                    // Headings come through as `# Title` and stay flush left.
+                    let is_paragraph = !words.is_empty() && !text.trim_start().starts_with('#');
+                    let indent = if is_paragraph { first_line_indent } else { 0 };
+                    let wrapped = wrap_words_to_lines(&words, area.width, indent);
+                    // The last row of a paragraph stays ragged, as in print.
+                    let last_row = wrapped.len() - 1;
+                    for (row, line_words) in wrapped.into_iter().enumerate() {
+                        if !next_row(&mut y, &mut column, &columns) {
                             break;
                         }
+                        let area = columns[column];
                         let line_area = Rect {
                             x: area.x,
                             y,
@@ -253,9 +643,44 @@ pub fn render(f: &mut Frame, app: &mut App) {
                             height: 1,
                         };
 
+                        let focused = !focus_drawn
+                            && !line_words.is_empty()
+                            && match line_focus {
+                                LineFocus::Off => false,
+                                LineFocus::Cursor => logical_i == book.current_line && row == 0,
+                                LineFocus::Middle => y >= middle_y,
+                            };
+                        focus_drawn |= focused;
+                        let row_bg = if focused { focus_bg } else { bg };
+                        let dimmed = ruler.dims(y, middle_y);
+
+                        if row == 0 && !line_words.is_empty() {
+                            if let Some((number, number_area)) = line_number(logical_i, area, y) {
+                                f.render_widget(number, number_area);
+                            }
+                        }
                         let mut spans = Vec::new();
-                        for (wi, w) in line_words {
-                            let mut style = Style::default().fg(fg).bg(bg);
+                        if row == 0 && indent > 0 {
+                            spans.push(Span::styled(
+                                " ".repeat(indent as usize),
+                                Style::default().bg(row_bg),
+                            ));
+                        }
+                        let start = if row == 0 { indent } else { 0 };
+                        let justified = justify && is_paragraph && row < last_row;
+                        let gaps = row_gaps(&line_words, start, area.width, justified);
+                        let mut x = area.x + start;
+                        for ((wi, w), gap) in line_words.into_iter().zip(gaps) {
+                            let width = UnicodeWidthStr::width(w) as u16 + gap;
+                            app.word_areas.push(WordArea {
+                                x,
+                                y,
+                                width,
+                                line: logical_i,
+                                word: wi,
+                            });
+                            x = x.saturating_add(width);
+                            let mut style = Style::default().fg(fg).bg(row_bg);
 
                             // Persistent chapter highlights/annotations
                             for anno in &book.chapter_annotations {
@@ -280,6 +705,21 @@ pub fn render(f: &mut Frame, app: &mut App) {
                                 }
                             }
 
+                            // Matches of the in-book search, the one at the cursor brighter
+                            let search_match = search_matches.iter().find(|m| {
+                                m.line == logical_i && (m.start_word..=m.end_word).contains(&wi)
+                            });
+                            if let Some(m) = search_match {
+                                let current = m.line == book.current_line
+                                    && m.start_word == book.word_index;
+                                let bg = if current {
+                                    Color::Rgb(255, 150, 50)
+                                } else {
+                                    Color::Rgb(200, 180, 60)
+                                };
+                                style = style.bg(bg).fg(Color::Black);
+                            }
+
                             // Active selection highlight (Select/Visual)
                             let is_selected = if matches!(view, AppView::Select | AppView::Visual) {
                                 if let Some((sl, sw, el, ew)) = selection {
@@ -315,22 +755,71 @@ pub fn render(f: &mut Frame, app: &mut App) {
                                     style = style.add_modifier(Modifier::UNDERLINED);
                                 }
                             }
+                            if dimmed {
+                                style = style.add_modifier(Modifier::DIM);
+                            }
 
-                            spans.push(Span::styled(format!("{} ", w), style));
+                            spans.push(Span::styled(
+                                format!("{}{}", w, " ".repeat(gap as usize)),
+                                style,
+                            ));
                         }
 
                         f.render_widget(
-                            Paragraph::new(Line::from(spans)).wrap(Wrap { trim: false }),
+                            Paragraph::new(Line::from(spans))
+                                .style(Style::default().bg(row_bg))
+                                .wrap(Wrap { trim: false }),
                             line_area,
                         );
                         y = y.saturating_add(1 + line_spacing);
                     }
 
+                    if is_paragraph {
+                        y = y.saturating_add(paragraph_spacing);
+                    }
+
                     // We intentionally don't update selection/cursor highlighting here;
                     // Select/Visual already uses the non-wrapped path for correct indexing.
                     logical_i += 1;
                 }
-                RenderLine::Image {
+                RenderLine::Caption(caption) => {
+                    let words: Vec<&str> = caption.split_whitespace().collect();
+                    let style = Style::default()
+                        .fg(fg)
+                        .bg(bg)
+                        .add_modifier(Modifier::ITALIC | Modifier::DIM);
+                    for line_words in wrap_words_to_lines(&words, area.width, 0) {
+                        if !next_row(&mut y, &mut column, &columns) {
+                            break;
+                        }
+                        let area = columns[column];
+                        let text = line_words
+                            .iter()
+                            .map(|(_, w)| *w)
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        let style = if ruler.dims(y, middle_y) {
+                            style.add_modifier(Modifier::DIM)
+                        } else {
+                            style
+                        };
+                        f.render_widget(
+                            Paragraph::new(text)
+                                .style(style)
+                                .alignment(ratatui::layout::Alignment::Center),
+                            Rect {
+                                x: area.x,
+                                y,
+                                width: area.width,
+                                height: 1,
+                            },
+                        );
+                        y = y.saturating_add(1);
+                    }
+                    y = y.saturating_add(paragraph_spacing);
+                    logical_i += 1;
+                }
+                                RenderLine::Image {
                     protocol_idx,
                     row_idx,
                 } => {
@@ -352,11 +841,12 @@ pub fn render(f: &mut Frame, app: &mut App) {
                             .count();
 
                         let img_start_y = line_y as i32 - (*row_idx as i32);
+                        let top = img_start_y.max(area.y as i32) as u16;
                         let full_img_area = Rect {
                             x: area.x,
-                            y: img_start_y.max(area.y as i32) as u16,
+                            y: top,
                             width: area.width,
-                            height: img_height_lines as u16,
+                            height: (img_height_lines as u16).min(area.bottom() - top),
                         };
 
                         if let Some(protocol) = book.image_protocols.get_mut(*protocol_idx) {
@@ -378,34 +868,77 @@ pub fn render(f: &mut Frame, app: &mut App) {
             let search = Paragraph::new(app.search_query.as_str()).block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title(" Search (Regex supported) ")
+                    .title(format!(
+                        " Search ({}; Ctrl-t to change) - Enter to find, then n / N ",
+                        case_label(app.search_case_sensitive)
+                    ))
                     .style(Style::default().fg(fg).bg(bg)),
             );
             f.render_widget(search, chunks[2]);
         }
 
+        if show_gauge {
+            let (chapter, whole) = App::reading_progress(book);
+            let chapter = (!book.parser.is_paged()).then_some(chapter);
+            let gauge = progress_gauge_line(chapter, whole, chunks[3].width, fg);
+            let gauge = Paragraph::new(gauge).style(Style::default().bg(bg));
+            f.render_widget(gauge, chunks[3]);
+        }
+
         // Status bar
         if show_status {
             let mode_str = match view {
                 AppView::Visual => " VISUAL ",
                 AppView::Select => " SELECT ",
+                _ if pdf_reflow && book.parser.is_pdf() => " REFLOW ",
                 _ => " NORMAL ",
             };
             let pomodoro = pomodoro_label.clone().unwrap_or_default();
-            let status_text = if focus_mode {
-                if pomodoro.is_empty() {
-                    format!(
-                        " FOCUS | Ch {} | L {} ",
+            let total = book.parser.get_chapter_count();
+            // PDF and DjVu chapters are pages, so report them as such with a percentage.
+            let position = if book.parser.is_paged() && total > 0 {
+                format!(
+                    "Page {} of {} ({:.0}%)",
+                    book.current_chapter + 1,
+                    total,
+                    (book.current_chapter + 1) as f64 / total as f64 * 100.0
+                )
+            } else {
+                match book.parser.chapter_title(book.current_chapter) {
+                    Some(title) => format!(
+                        "Ch {}/{}: {}",
                         book.current_chapter + 1,
-                        book.current_line
-                    )
+                        total,
+                        ellipsize(&title, MAX_STATUS_TITLE)
+                    ),
+                    None => format!("Ch: {}/{}", book.current_chapter + 1, total),
+                }
+            };
+            let position = if book.paged {
+                let (page, pages) = App::page_position(book, page_layout);
+                format!("{} | page {} of {}", position, page, pages)
+            } else {
+                position
+            };
+            // PDF and DjVu pages are already counted down above.
+            let position = if book.parser.is_paged() {
+                position
+            } else {
+                let finish = App::finish_line(book, page_layout);
+                format!(
+                    "{} | {} | {}",
+                    position,
+                    finish_line_label(&finish),
+                    time_left_label(&finish, rolling_wpm)
+                )
+            };
+            let status_text = if let Some(ref message) = status_message {
+                format!(" {} ", message)
+            } else if focus_mode {
+                if pomodoro.is_empty() {
+                    format!(" FOCUS | {} ", position)
                 } else {
-                    format!(
-                        " FOCUS | {} | Ch {} | L {} ",
-                        pomodoro,
-                        book.current_chapter + 1,
-                        book.current_line
-                    )
+                    format!(" FOCUS | {} | {} ", pomodoro, position)
                 }
             } else {
                 let pomodoro_section = if pomodoro.is_empty() {
@@ -413,19 +946,97 @@ pub fn render(f: &mut Frame, app: &mut App) {
                 } else {
                     format!(" | {}", pomodoro)
                 };
+                let search_section = search_position
+                    .map(|position| format!(" | {}", position))
+                    .unwrap_or_default();
+                let sync_section = sync_label
+                    .map(|label| format!(" | {}", label))
+                    .unwrap_or_default();
+                // Giant chapters load in parts; say so rather than look stuck.
+                let chapter_section = if book.pending_lines.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        " | ⚠ Long chapter: {} of {} lines loaded",
+                        book.chapter_content.len(),
+                        book.chapter_content.len() + book.pending_lines.len()
+                    )
+                };
                 format!(
-                    "{}| Ch: {}/{} | L: {} | WPM: {:.0}{} | 's' select | 't' toc | 'A' notes | 'q' lib ",
+                    "{}| {} | WPM: {:.0}{}{}{}{} | 's' select | 't' toc | 'A' notes | 'q' lib ",
                     mode_str,
-                    book.current_chapter + 1,
-                    book.parser.get_chapter_count(),
-                    book.current_line,
+                    position,
                     wpm,
-                    pomodoro_section
+                    search_section,
+                    pomodoro_section,
+                    sync_section,
+                    chapter_section
                 )
             };
+            let status_text = ellipsize(&status_text, chunks[4].width as usize);
+            if export_picker || app.preset_picker {
+                app.click_areas
+                    .extend(super::hint_areas(&status_text, chunks[4]));
+            }
             let status = Paragraph::new(status_text)
                 .style(Style::default().bg(Color::Blue).fg(Color::White));
-            f.render_widget(status, chunks[3]);
+            f.render_widget(status, chunks[4]);
         }
     }
+
+    if let Some(ref summary) = app.session_summary {
+        render_session_summary(f, summary, app.theme);
+    }
+}
+
+/// Columns of the popup shown on leaving a book.
+const SESSION_SUMMARY_WIDTH: u16 = 40;
+
+/// Lines of the popup shown on leaving a book.
+fn session_summary_lines(summary: &SessionSummary) -> Vec<String> {
+    let session = &summary.session;
+    let speed = session
+        .wpm()
+        .map_or_else(|| "-".to_string(), |wpm| format!("{:.0} wpm", wpm));
+    vec![
+        format!("Time read      {}", format_minutes(session.secs)),
+        format!("Words          {}", session.words),
+        format!("Average speed  {}", speed),
+        format!("Notes made     {}", session.annotations),
+        format!("Streak         {} in a row", format_days(summary.streak)),
+    ]
+}
+
+fn render_session_summary(f: &mut Frame, summary: &SessionSummary, theme: Theme) {
+    let (bg, fg) = get_theme_colors(theme);
+    let mut text = vec![String::new()];
+    for line in session_summary_lines(summary) {
+        text.push(format!("  {}", line));
+    }
+    text.push(String::new());
+    text.push("  Press any key for the library".to_string());
+    let screen = f.area();
+    let width = SESSION_SUMMARY_WIDTH.min(screen.width);
+    let height = (text.len() as u16 + 2).min(screen.height);
+    let area = Rect::new(
+        (screen.width - width) / 2,
+        (screen.height - height) / 2,
+        width,
+        height,
+    );
+    f.render_widget(Clear, area);
+    let title = summary
+        .session
+        .title
+        .as_deref()
+        .map_or_else(|| " Session ".to_string(), |t| format!(" {} ", t));
+    let popup = Paragraph::new(text.join("\n"))
+        .block(
+            Block::default()
+                .title(ellipsize(&title, area.width.saturating_sub(2) as usize))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Cyan).bg(bg)),
+        )
+        .style(Style::default().fg(fg).bg(bg));
+    f.render_widget(popup, area);
 }
@@ -3,8 +3,25 @@ use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::Paragraph,
 };
+use unicode_width::UnicodeWidthStr;
+
+/// The character the eye should land on: a little left of the middle of the word's
+/// letters, past any opening quote or bracket.
+fn orp_index(word: &str) -> usize {
+    let lead = word.chars().take_while(|c| !c.is_alphanumeric()).count();
+    let letters = word.chars().filter(|c| c.is_alphanumeric()).count();
+    let pivot = match letters {
+        0 | 1 => 0,
+        2..=5 => 1,
+        6..=9 => 2,
+        10..=13 => 3,
+        _ => 4,
+    };
+    (lead + pivot).min(word.chars().count().saturating_sub(1))
+}
 
 pub fn render(f: &mut Frame, app: &mut App) {
     let (bg, fg) = match app.theme {
@@ -13,6 +30,7 @@ pub fn render(f: &mut Frame, app: &mut App) {
         Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
         Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
     };
+    let style = Style::default().fg(fg).bg(bg);
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -23,18 +41,45 @@ pub fn render(f: &mut Frame, app: &mut App) {
         ])
         .split(f.area());
 
+    // Every word sits with its pivot letter on the middle column, between two marks,
+    // so the eye never has to move.
+    let area = chunks[1];
+    let center = area.width as usize / 2;
+    let rule = |mark: &str| {
+        format!(
+            "{}{}{}",
+            "─".repeat(center),
+            mark,
+            "─".repeat((area.width as usize).saturating_sub(center + 1))
+        )
+    };
+    let mut lines = vec![Line::from(rule("┬"))];
     if let Some(word) = app.rsvp_words.get(app.rsvp_index) {
-        let p = Paragraph::new(word.as_str())
-            .style(Style::default().fg(fg).bg(bg).add_modifier(Modifier::BOLD))
-            .alignment(Alignment::Center);
-        f.render_widget(p, chunks[1]);
+        let pivot = orp_index(word);
+        let before: String = word.chars().take(pivot).collect();
+        let letter: String = word.chars().skip(pivot).take(1).collect();
+        let after: String = word.chars().skip(pivot + 1).collect();
+        let bold = style.add_modifier(Modifier::BOLD);
+        lines.push(Line::from(vec![
+            Span::raw(" ".repeat(center.saturating_sub(before.width()))),
+            Span::styled(before, bold),
+            Span::styled(letter, bold.fg(Color::Red)),
+            Span::styled(after, bold),
+        ]));
+    } else {
+        lines.push(Line::default());
     }
+    lines.push(Line::from(rule("┴")));
+    f.render_widget(Paragraph::new(lines).style(style), area);
 
+    let total = app.rsvp_skipped + app.rsvp_words.len();
+    let at = (app.rsvp_skipped + app.rsvp_index + 1).min(total);
+    let paused = if app.rsvp_active { "" } else { " (paused)" };
     let help = Paragraph::new(format!(
-        " WPM: {} | [Space] Pause | [+/-] Speed | [q] Back ",
-        app.rsvp_wpm
+        " Word {}/{}{} | WPM: {} | [Space] Pause | [+/-] Speed | [q] Back ",
+        at, total, paused, app.rsvp_wpm
     ))
     .alignment(Alignment::Center)
-    .style(Style::default().fg(fg).bg(bg));
+    .style(style);
     f.render_widget(help, chunks[2]);
 }
@@ -0,0 +1,573 @@
+//! Renders views into a `TestBackend` and compares the text with the files in
+//! `src/ui/snapshots/`. Run with `UPDATE_SNAPSHOTS=1` to write or accept new output.
+
+use crate::app::{
+    App, AppView, FocusRuler, LineFocus, LineNumbers, NightTint, ProgressGauge, RenderLine,
+    SessionSummary, Theme,
+};
+use crate::common::{Workspace, sample_epub};
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    buffer::Buffer,
+    style::{Color, Modifier},
+};
+use std::path::Path;
+use tbook::config::{AnnotationKindConfig, LayoutProfile};
+use tbook::db::{BookSessionRecord, NewAnnotation, SearchHit};
+use tbook::library;
+use tbook::locator::Locator;
+
+/// Small, typical, and roomy terminals.
+const SIZES: [(u16, u16); 3] = [(40, 12), (80, 24), (120, 40)];
+
+fn buffer_text(buffer: &Buffer) -> String {
+    let width = buffer.area.width as usize;
+    buffer
+        .content
+        .chunks(width)
+        .map(|row| {
+            let line: String = row.iter().map(|cell| cell.symbol()).collect();
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The fixture lives in a fresh temp dir whose path length varies and would shift the
+/// layout, so show a fixed path once the book is loaded.
+fn pin_paths(app: &mut App) {
+    for book in &mut app.books {
+        book.path = "sample.epub".to_string();
+    }
+    if let Some(ref mut book) = app.current_book {
+        book.path = "sample.epub".to_string();
+    }
+}
+
+fn render(app: &mut App, width: u16, height: u16) -> String {
+    pin_paths(app);
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|f| super::render(f, app)).unwrap();
+    buffer_text(terminal.backend().buffer())
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/ui/snapshots")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, format!("{}\n", actual)).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing snapshot {}; run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected.strip_suffix('\n') == Some(actual),
+        "snapshot {} changed (run with UPDATE_SNAPSHOTS=1 to accept)\n--- expected\n{}\n--- actual\n{}",
+        name,
+        expected,
+        actual
+    );
+}
+
+fn check_sizes(name: &str, app: &mut App) {
+    for (width, height) in SIZES {
+        let text = render(app, width, height);
+        assert_snapshot(&format!("{}_{}x{}", name, width, height), &text);
+    }
+}
+
+/// An app with the sample EPUB in its library. The workspace must outlive the app.
+fn app_with_sample_book() -> (Workspace, App) {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let mut app = App::new(&ws.path().join("tbook.db").to_string_lossy()).unwrap();
+    // Covers load on a worker in the real app; show the placeholder instead of a spinner.
+    for book in &app.books {
+        app.cover_missing.insert(book.id);
+    }
+    (ws, app)
+}
+
+fn open_sample_book(app: &mut App) {
+    let record = app.books[0].clone();
+    app.load_book(record).unwrap();
+    app.next_chapter().unwrap();
+}
+
+#[test]
+fn library_view() {
+    let (_ws, mut app) = app_with_sample_book();
+    check_sizes("library", &mut app);
+}
+
+#[test]
+fn reader_view_wraps_text() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    assert!(app.view == AppView::Reader);
+    check_sizes("reader", &mut app);
+}
+
+#[test]
+fn reader_paragraph_layout() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.paragraph_spacing = 1;
+    app.first_line_indent = 4;
+    check_sizes("reader_paragraphs", &mut app);
+}
+
+#[test]
+fn justified_rows_end_flush_right() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    let paragraph = "Whenever I find myself growing grim about the mouth; whenever it is a damp, \
+                     drizzly November in my soul, I account it high time to get to sea.";
+    app.current_book.as_mut().unwrap().chapter_content =
+        vec![RenderLine::Text(paragraph.to_string())];
+    // Where each row of the paragraph ends; the text column is 36 wide from column 2.
+    let row_ends = |app: &mut App| -> Vec<usize> {
+        render(app, 40, 12)
+            .lines()
+            .filter(|row| !row.contains("Reading:") && !row.contains("NORMAL"))
+            .map(|row| row.trim_end().len())
+            .filter(|end| *end > 0)
+            .collect()
+    };
+
+    let ragged = row_ends(&mut app);
+    assert!(
+        ragged.len() > 2 && ragged.iter().any(|end| *end < 38),
+        "{ragged:?}"
+    );
+    app.justify = true;
+    let justified = row_ends(&mut app);
+    let (last, full) = justified.split_last().unwrap();
+    assert!(full.iter().all(|end| *end == 38), "{justified:?}");
+    assert_eq!(last, ragged.last().unwrap());
+
+    // Clicks and the select cursor find the words where they are drawn.
+    app.set_reader_text_width(36);
+    let layout = app.page_layout(10);
+    let book = app.current_book.as_ref().unwrap();
+    let columns: Vec<(usize, u16)> =
+        crate::ui::reader::word_rows(&book.chapter_content[0], &layout)
+            .into_iter()
+            .flatten()
+            .collect();
+    let drawn: Vec<(usize, u16)> = app.word_areas.iter().map(|a| (a.word, a.x - 2)).collect();
+    assert_eq!(columns, drawn);
+}
+
+#[test]
+fn reader_line_numbers() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.paragraph_spacing = 1;
+    app.line_numbers = LineNumbers::Relative;
+    check_sizes("reader_line_numbers", &mut app);
+}
+
+#[test]
+fn reader_progress_gauge() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.progress_gauge = ProgressGauge::On;
+    app.current_book.as_mut().unwrap().current_line = 2;
+    check_sizes("reader_progress_gauge", &mut app);
+}
+
+#[test]
+fn reader_session_summary() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.session_summary = Some(SessionSummary {
+        session: BookSessionRecord {
+            book_id: app.books[0].id,
+            title: Some(app.books[0].title.clone()),
+            opened_at: "2025-03-02 20:00:00".to_string(),
+            closed_at: "2025-03-02 20:30:00".to_string(),
+            secs: 25 * 60,
+            words: 6000,
+            annotations: 2,
+        },
+        streak: 4,
+    });
+    check_sizes("reader_session_summary", &mut app);
+}
+
+#[test]
+fn reader_layout_profile_columns() {
+    let (_ws, mut app) = app_with_sample_book();
+    let profile = LayoutProfile {
+        name: "desktop".to_string(),
+        min_term_width: Some(100),
+        margin: Some(1),
+        max_width: Some(30),
+        columns: Some(2),
+        ..LayoutProfile::default()
+    };
+    let profiles = [profile];
+    assert_eq!(app.apply_layout_profile(&profiles, 80, 24, ""), None);
+    assert_eq!(app.text_columns, 1);
+    assert_eq!(
+        app.apply_layout_profile(&profiles, 120, 40, ""),
+        Some("desktop")
+    );
+    assert_eq!((app.margin, app.text_columns), (1, 2));
+    open_sample_book(&mut app);
+    // Short enough that the chapter flows on into the second column.
+    let text = render(&mut app, 80, 12);
+    assert_snapshot("reader_columns_80x12", &text);
+    // Too narrow for two columns, so the text keeps to one.
+    let text = render(&mut app, 40, 12);
+    assert!(text.contains(" I stuffed a shirt or two into\n"), "{text}");
+}
+
+#[test]
+fn image_rows_follow_the_column_and_font_size() {
+    let flatten = |image: &image::DynamicImage, width: u16, cap: Option<u32>, show: bool| {
+        // Halfblocks assume 10x20-pixel cells.
+        let mut picker = ratatui_image::picker::Picker::halfblocks();
+        App::flatten_content(
+            &mut picker,
+            vec![tbook::parser::PageContent::Image(std::sync::Arc::new(
+                image.clone(),
+            ))],
+            width,
+            cap,
+            show,
+            None,
+        )
+    };
+    let rows = |image, width, cap| flatten(image, width, cap, true).0.len();
+
+    // Small enough to be drawn at its own size in a wide column, shrunk in a narrow one.
+    let photo = image::DynamicImage::new_rgb8(400, 300);
+    assert_eq!(rows(&photo, 80, None), 15);
+    assert_eq!(rows(&photo, 20, None), 8);
+
+    // A tall image is cut off at the default cap, or at the pixel cap of a profile.
+    let tall = image::DynamicImage::new_rgb8(1000, 4000);
+    assert_eq!(rows(&tall, 80, None), 30);
+    assert_eq!(rows(&tall, 80, Some(800)), 40);
+    assert_eq!(rows(&tall, 80, Some(20)), 5);
+
+    // Light mode leaves a one-line marker and builds no image protocol.
+    let (lines, protocols) = flatten(&photo, 80, None, false);
+    assert!(protocols.is_empty());
+    assert!(matches!(lines.as_slice(), [RenderLine::Text(t)] if t == "[image]"));
+}
+
+#[test]
+fn light_mode_shows_no_cover_or_spinner() {
+    let (_ws, mut app) = app_with_sample_book();
+    app.cover_missing.clear();
+    app.light_mode = true;
+    assert!(app.cover_request_for_selected().is_none());
+    let text = render(&mut app, 80, 24);
+    assert!(text.contains("[ No Cover Preview ]"), "{text}");
+    assert!(!text.contains("Loading cover"), "{text}");
+}
+
+#[test]
+fn line_focus_tints_a_single_row() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.theme = Theme::Gruvbox;
+    // Twelve percent of the way from the Gruvbox background to its text color.
+    let tint = Color::Rgb(63, 61, 57);
+    let tinted_rows = |app: &mut App| {
+        // Small enough that the chapter reaches past the middle of the screen.
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|f| super::render(f, app)).unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        buffer
+            .content
+            .chunks(40)
+            .filter(|row| row.iter().any(|cell| cell.bg == tint))
+            .count()
+    };
+
+    assert_eq!(tinted_rows(&mut app), 0);
+    for focus in [LineFocus::Cursor, LineFocus::Middle] {
+        app.line_focus = focus;
+        assert_eq!(tinted_rows(&mut app), 1, "{:?}", focus);
+    }
+    app.view = AppView::Select;
+    assert_eq!(tinted_rows(&mut app), 0);
+}
+
+#[test]
+fn focus_mode_keeps_the_reading_line_in_the_middle() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.focus_mode = true;
+    app.scroll_viewport_down();
+    app.scroll_viewport_down();
+    check_sizes("reader_typewriter", &mut app);
+}
+
+#[test]
+fn focus_ruler_dims_all_but_the_middle() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.focus_mode = true;
+    // Rows with text, undimmed and dimmed.
+    let rows = |app: &mut App| {
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|f| super::render(f, app)).unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        let text_rows: Vec<bool> = buffer
+            .content
+            .chunks(40)
+            .filter(|row| row.iter().any(|cell| cell.symbol() != " "))
+            .map(|row| row.iter().any(|cell| cell.modifier.contains(Modifier::DIM)))
+            .collect();
+        let dimmed = text_rows.iter().filter(|dim| **dim).count();
+        (text_rows.len() - dimmed, dimmed)
+    };
+
+    let (shown, dimmed) = rows(&mut app);
+    assert!(shown > 1 && dimmed == 0);
+    app.focus_ruler = FocusRuler::Line;
+    assert_eq!(rows(&mut app), (1, shown - 1));
+    // Blank rows in the band don't count, so it may show fewer than three.
+    app.focus_ruler = FocusRuler::Band;
+    let (band, _) = rows(&mut app);
+    assert!((1..=3).contains(&band));
+    // Outside focus mode the ruler is put away.
+    app.focus_mode = false;
+    assert_eq!(rows(&mut app).1, 0);
+}
+
+#[test]
+fn night_tint_warms_colors_after_its_hour() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.theme = Theme::Gruvbox;
+    app.night_tint = Some(NightTint {
+        start_hour: 21,
+        end_hour: 6,
+        strength: 40,
+    });
+    let background = |app: &mut App| {
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|f| super::render(f, app)).unwrap();
+        terminal.backend().buffer()[(20, 6)].bg
+    };
+
+    app.update_night_tint(14);
+    assert_eq!(background(&mut app), Color::Rgb(40, 40, 40));
+    // The evening span runs past midnight.
+    for hour in [21, 23, 0, 5] {
+        app.update_night_tint(hour);
+        assert_eq!(background(&mut app), Color::Rgb(40, 36, 30), "{}", hour);
+    }
+    app.update_night_tint(6);
+    assert_eq!(background(&mut app), Color::Rgb(40, 40, 40));
+}
+
+#[test]
+fn reader_select_mode_highlights_word() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.view = AppView::Select;
+    if let Some(ref mut book) = app.current_book {
+        book.current_line = 2;
+        book.word_index = 3;
+    }
+    check_sizes("reader_select", &mut app);
+}
+
+#[test]
+fn rsvp_centers_each_word_on_its_pivot_letter() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    if let Some(ref mut book) = app.current_book {
+        book.current_line = 4;
+        book.word_index = 1;
+    }
+    app.start_rsvp();
+    assert_eq!(app.rsvp_words[0], "stuffed");
+    check_sizes("rsvp", &mut app);
+
+    // The pivot of "stuffed" is its "u", on the middle column in red.
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal.draw(|f| super::render(f, &mut app)).unwrap();
+    let pivot = &terminal.backend().buffer()[(40, 12)];
+    assert_eq!(pivot.symbol(), "u");
+    assert_eq!(pivot.fg, Color::Red);
+}
+
+#[test]
+fn annotation_list_view() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    let id = app.books[0].id;
+    app.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            start_line: 2,
+            end_line: 2,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            note: Some("packing light"),
+            kind: "question",
+            ..Default::default()
+        })
+        .unwrap();
+    app.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            start_line: 2,
+            end_line: 2,
+            end_word: 2,
+            content: "Call me Ishmael.",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    app.load_annotations().unwrap();
+    assert!(app.view == AppView::AnnotationList);
+    check_sizes("annotations", &mut app);
+}
+
+#[test]
+fn custom_annotation_kinds_use_their_color() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.annotation_kinds.push(AnnotationKindConfig {
+        name: "quote".to_string(),
+        color: [90, 90, 160],
+    });
+    app.view = AppView::Select;
+    if let Some(ref mut book) = app.current_book {
+        book.current_line = 2;
+    }
+    app.add_custom_highlight(0).unwrap();
+    let colored = |app: &mut App, color: Color| {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| super::render(f, app)).unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        buffer.content.iter().any(|cell| cell.bg == color || cell.fg == color)
+    };
+
+    app.view = AppView::Reader;
+    assert!(colored(&mut app, Color::Rgb(90, 90, 160)));
+    // An earlier highlight takes the list selection, which has its own color.
+    if let Some(ref mut book) = app.current_book {
+        book.current_line = 0;
+    }
+    app.add_quick_highlight("highlight").unwrap();
+    app.load_annotations().unwrap();
+    assert!(colored(&mut app, Color::Rgb(90, 90, 160)));
+}
+
+#[test]
+fn note_browser_view() {
+    let (_ws, mut app) = app_with_sample_book();
+    let id = app.books[0].id;
+    let note = app
+        .db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            start_line: 2,
+            end_line: 2,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            kind: "question",
+            ..Default::default()
+        })
+        .unwrap();
+    app.db
+        .set_annotation_tags(note, &["packing".to_string(), "sea".to_string()])
+        .unwrap();
+    app.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            start_line: 2,
+            end_line: 2,
+            end_word: 2,
+            content: "Call me Ishmael.",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    app.open_note_browser().unwrap();
+    for note in &mut app.note_browser.notes {
+        note.created_at = Some("2024-05-01 09:30:00".to_string());
+    }
+    check_sizes("note_browser", &mut app);
+}
+
+#[test]
+fn book_search_view() {
+    let (_ws, mut app) = app_with_sample_book();
+    open_sample_book(&mut app);
+    app.open_book_search();
+    app.book_search_query = "the".to_string();
+    app.run_book_search().unwrap();
+    app.selected_book_search_index = 1;
+    check_sizes("book_search", &mut app);
+}
+
+#[test]
+fn omnibox_groups_its_results() {
+    let (_ws, mut app) = app_with_sample_book();
+    let book = app.books[0].clone();
+    library::index_book(&app.db, book.id, &book.path).unwrap();
+    let note = Some("Packing light");
+    app.db
+        .add_annotation(&NewAnnotation {
+            book_id: book.id,
+            chapter: 1,
+            start_line: 4,
+            end_line: 4,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            note,
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    app.db
+        .add_to_vocabulary("shirt", "# shirt\nA garment for the upper body.", None)
+        .unwrap();
+    app.open_omnibox();
+    app.omnibox_query = "shirt".to_string();
+    app.run_omnibox_search().unwrap();
+    app.selected_omnibox_index = 1;
+    check_sizes("omnibox", &mut app);
+}
+
+#[test]
+fn global_search_previews_the_book_of_the_hit() {
+    let (_ws, mut app) = app_with_sample_book();
+    app.open_global_search();
+    app.global_search_query = "bedford".to_string();
+    app.global_search_results = vec![SearchHit {
+        book_id: app.books[0].id,
+        title: app.books[0].title.clone(),
+        locator: Locator::new(1, 60),
+        snippet: "...I duly arrived in New [Bedford].".to_string(),
+    }];
+    check_sizes("global_search", &mut app);
+}
+
+#[test]
+fn stats_view() {
+    let (_ws, mut app) = app_with_sample_book();
+    app.view = AppView::Stats;
+    check_sizes("stats", &mut app);
+}
@@ -1,11 +1,20 @@
-use crate::app::{App, Theme};
+use super::{ellipsize, format_days, format_minutes};
+use crate::app::{reading_streak, App, StatsMetric, StatsTab, Theme};
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    widgets::{BarChart, Block, Borders, Gauge, Paragraph},
+    text::Line,
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Gauge, Paragraph, Tabs},
     Frame,
 };
 
+/// Wide enough for a label like `12 Mar` and a five-digit word count.
+const BAR_WIDTH: u16 = 6;
+const BAR_GAP: u16 = 1;
+/// Columns of the panel breaking the selected bar down by tag or author, which only shows
+/// beside a chart at least as wide.
+const BREAKDOWN_WIDTH: u16 = 34;
+
 pub fn render(f: &mut Frame, app: &mut App) {
     let (bg, fg) = match app.theme {
         Theme::Default => (Color::Reset, Color::Reset),
@@ -18,6 +27,7 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([
+            Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Min(0),
@@ -28,14 +38,24 @@ pub fn render(f: &mut Frame, app: &mut App) {
     // Fill background
     f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
 
-    let title = Paragraph::new(" Reading Statistics (Last 7 Days) ")
-        .block(Block::default().borders(Borders::ALL))
-        .style(
+    let selected = StatsTab::ALL
+        .iter()
+        .position(|t| *t == app.stats_tab)
+        .unwrap_or(0);
+    let tabs = Tabs::new(StatsTab::ALL.iter().map(|t| t.label()))
+        .block(
+            Block::default()
+                .title(" Reading Statistics ")
+                .borders(Borders::ALL),
+        )
+        .select(selected)
+        .style(Style::default().fg(fg))
+        .highlight_style(
             Style::default()
                 .fg(Color::Cyan)
                 .add_modifier(Modifier::BOLD),
         );
-    f.render_widget(title, chunks[0]);
+    f.render_widget(tabs, chunks[0]);
 
     let today_words = app.db.get_today_words().unwrap_or(0);
     let goal = app.daily_goal_words.max(1);
@@ -48,30 +68,164 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .gauge_style(Style::default().fg(Color::Green))
         .label(goal_label)
         .ratio(ratio);
-    f.render_widget(goal_gauge, chunks[1]);
+    let summary = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+    f.render_widget(goal_gauge, summary[0]);
+
+    let today_time = format_minutes(app.db.get_today_reading_secs().unwrap_or(0));
+    let average = app
+        .db
+        .get_average_sitting_secs()
+        .ok()
+        .flatten()
+        .map(format_minutes)
+        .unwrap_or_else(|| "-".to_string());
+    let time_read = Paragraph::new(format!(" {} today | {} per sitting", today_time, average))
+        .block(Block::default().title(" Time Read ").borders(Borders::ALL))
+        .style(Style::default().fg(fg));
+    f.render_widget(time_read, summary[1]);
+
+    let today = chrono::Local::now().date_naive();
+    let streak = reading_streak(&app.reading_days(), today);
+    let last_session = match app.db.get_last_book_session().ok().flatten() {
+        Some(session) => format!(
+            " {}: {}, {} words, {} wpm, {} notes | Streak: {} ",
+            session.title.as_deref().unwrap_or("Removed book"),
+            format_minutes(session.secs),
+            session.words,
+            session.wpm().map_or_else(|| "-".to_string(), |wpm| format!("{:.0}", wpm)),
+            session.annotations,
+            format_days(streak)
+        ),
+        None => format!(" No sessions yet | Streak: {} ", format_days(streak)),
+    };
+    let width = chunks[2].width.saturating_sub(2) as usize;
+    let last_session = Paragraph::new(ellipsize(&last_session, width))
+        .block(Block::default().title(" Last Session ").borders(Borders::ALL))
+        .style(Style::default().fg(fg));
+    f.render_widget(last_session, chunks[2]);
+
+    let (tab, metric) = (app.stats_tab, app.stats_metric);
+    let measure = match metric {
+        StatsMetric::Words => "Words",
+        StatsMetric::Minutes => "Minutes",
+    };
+    let chart_title = format!(" {} Read per {} ", measure, tab.unit());
+    let bars = app.stats_series();
+    let (chart_area, breakdown_area) = if chunks[3].width >= BREAKDOWN_WIDTH * 2 {
+        let split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(BREAKDOWN_WIDTH)])
+            .split(chunks[3]);
+        (split[0], Some(split[1]))
+    } else {
+        (chunks[3], None)
+    };
+    if bars.is_empty() {
+        let error = Paragraph::new("No statistics available yet. Start reading!")
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(Block::default().title(chart_title).borders(Borders::ALL));
+        f.render_widget(error, chart_area);
+    } else {
+        // Keep the selected bar in view, scrolling only when it would leave the chart.
+        let inner = chart_area.width.saturating_sub(2);
+        let visible = ((inner + BAR_GAP) / (BAR_WIDTH + BAR_GAP)).max(1) as usize;
+        let cursor = app.stats_cursor.unwrap_or(bars.len() - 1).min(bars.len() - 1);
+        let mut scroll = app.stats_scroll.min(bars.len().saturating_sub(visible));
+        if cursor < scroll {
+            scroll = cursor;
+        } else if cursor >= scroll + visible {
+            scroll = cursor + 1 - visible;
+        }
+        app.stats_scroll = scroll;
+        let shown = &bars[scroll..(scroll + visible).min(bars.len())];
 
-    if let Ok(stats) = app.db.get_weekly_stats() {
-        let data: Vec<(&str, u64)> = stats.iter().map(|(d, w)| (d.as_str(), *w as u64)).collect();
+        let data: Vec<Bar> = shown
+            .iter()
+            .enumerate()
+            .map(|(i, bar)| {
+                let mut label = Line::from(bar.label(tab));
+                let color = if scroll + i == cursor {
+                    // Days with no reading have no bar, so the label marks the cursor too.
+                    label = label.style(
+                        Style::default()
+                            .fg(Color::Cyan)
+                            .add_modifier(Modifier::BOLD),
+                    );
+                    Color::Cyan
+                } else {
+                    Color::Green
+                };
+                Bar::default()
+                    .value(bar.value(metric))
+                    .label(label)
+                    .style(Style::default().fg(color))
+                    .value_style(Style::default().fg(Color::Black).bg(color))
+            })
+            .collect();
 
+        let selected = &bars[cursor];
+        let title = format!(
+            "{}| {}: {} words, {} ",
+            chart_title,
+            selected.describe(tab),
+            selected.words,
+            format_minutes(selected.secs)
+        );
+        let mut block = Block::default().title(title).borders(Borders::ALL);
+        if scroll > 0 {
+            block = block.title_bottom(Line::from(" < earlier "));
+        }
+        if scroll + visible < bars.len() {
+            block = block.title_bottom(Line::from(" later > ").right_aligned());
+        }
         let barchart = BarChart::default()
+            .block(block)
+            .data(BarGroup::new(data))
+            .bar_width(BAR_WIDTH)
+            .bar_gap(BAR_GAP);
+
+        f.render_widget(barchart, chart_area);
+    }
+
+    if let Some(area) = breakdown_area {
+        let selected = app.stats_cursor.and_then(|i| bars.get(i)).or(bars.last());
+        let total = selected.map_or(0, |bar| bar.value(metric));
+        let rows = app.stats_breakdown_rows();
+        // Name, value, and its share of the day or month.
+        let name_width = (BREAKDOWN_WIDTH as usize).saturating_sub(2 + 14);
+        let lines: Vec<String> = if rows.is_empty() {
+            vec!["No reading.".to_string()]
+        } else {
+            rows.iter()
+                .map(|(name, words, secs)| {
+                    let (value, amount) = match metric {
+                        StatsMetric::Words => (words.to_string(), *words as u64),
+                        StatsMetric::Minutes => (format_minutes(*secs), secs / 60),
+                    };
+                    let share = (amount * 100).checked_div(total).unwrap_or(0).min(100);
+                    format!(
+                        "{:<width$} {:>8} {:>3}%",
+                        ellipsize(name, name_width),
+                        value,
+                        share,
+                        width = name_width
+                    )
+                })
+                .collect()
+        };
+        let breakdown = Paragraph::new(lines.join("\n"))
             .block(
                 Block::default()
-                    .title(" Words Read per Day ")
+                    .title(format!(" By {} ", app.stats_breakdown.label()))
                     .borders(Borders::ALL),
             )
-            .data(&data)
-            .bar_width(12)
-            .bar_gap(2)
-            .bar_style(Style::default().fg(Color::Green))
-            .value_style(Style::default().fg(Color::Black).bg(Color::Green));
-
-        f.render_widget(barchart, chunks[2]);
-    } else {
-        let error = Paragraph::new("No statistics available yet. Start reading!")
-            .alignment(ratatui::layout::Alignment::Center);
-        f.render_widget(error, chunks[2]);
+            .style(Style::default().fg(fg));
+        f.render_widget(breakdown, area);
     }
 
-    let footer = Paragraph::new(" [q] Back to Library ").style(Style::default().fg(fg).bg(bg));
-    f.render_widget(footer, chunks[3]);
+    let footer = " [h/l] Select Bar | [j/k] Days / Months | [m] Words / Minutes | [b] By Tag / Author | [q] Back ";
+    super::render_footer(f, app, footer, chunks[4], Style::default().fg(fg).bg(bg));
 }
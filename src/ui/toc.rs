@@ -27,15 +27,25 @@ pub fn render(f: &mut Frame, app: &mut App) {
         .toc_items
         .iter()
         .enumerate()
-        .map(|(i, t)| {
+        .map(|(i, entry)| {
             let style = if i == app.selected_toc_index {
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD)
+            } else if entry.figure {
+                Style::default()
+                    .fg(fg)
+                    .bg(bg)
+                    .add_modifier(Modifier::ITALIC)
             } else {
                 Style::default().fg(fg).bg(bg)
             };
-            ListItem::new(format!("{:02}. {}", i + 1, t)).style(style)
+            let text = if entry.figure {
+                format!("      {}", entry.label)
+            } else {
+                format!("{:02}. {}", entry.chapter + 1, entry.label)
+            };
+            ListItem::new(text).style(style)
         })
         .collect();
 
@@ -53,4 +63,6 @@ pub fn render(f: &mut Frame, app: &mut App) {
         list_state.select(Some(app.selected_toc_index));
     }
     f.render_stateful_widget(list, chunks[0], &mut list_state);
+    let (offset, len) = (list_state.offset(), app.toc_items.len());
+    super::register_rows(app, chunks[0], offset, len);
 }
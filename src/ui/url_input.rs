@@ -0,0 +1,66 @@
+use crate::app::{App, Theme};
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = match app.theme {
+        Theme::Default => (Color::Reset, Color::Reset),
+        Theme::Gruvbox => (Color::Rgb(40, 40, 40), Color::Rgb(235, 219, 178)),
+        Theme::Nord => (Color::Rgb(46, 52, 64), Color::Rgb(216, 222, 233)),
+        Theme::Sepia => (Color::Rgb(250, 240, 230), Color::Rgb(93, 71, 139)),
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(5)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let title = Paragraph::new(" Save Web Article (Read Later) ")
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .alignment(Alignment::Center)
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(title, chunks[0]);
+
+    let input = Paragraph::new(app.url_input.as_str())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" URL ")
+                .style(Style::default().fg(fg).bg(bg)),
+        )
+        .alignment(Alignment::Left);
+    f.render_widget(input, chunks[1]);
+
+    if let Some(ref status) = app.url_fetch_status {
+        let status = Paragraph::new(format!("\n{}", status))
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::DarkGray).bg(bg));
+        f.render_widget(status, chunks[2]);
+    }
+
+    let help = Paragraph::new(" [Enter] Fetch & Import | [Esc] Cancel ")
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(fg).bg(bg));
+    f.render_widget(help, chunks[3]);
+}
@@ -0,0 +1,117 @@
+use crate::app::App;
+use crate::review::Grade;
+use crate::ui::annotation::get_theme_colors;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+const GRADES: [Grade; 4] = [Grade::Again, Grade::Hard, Grade::Good, Grade::Easy];
+
+pub fn render(f: &mut Frame, app: &mut App) {
+    let (bg, fg) = get_theme_colors(app.theme);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.area());
+
+    // Fill background
+    f.render_widget(Block::default().style(Style::default().bg(bg)), f.area());
+
+    let review = &app.vocab_review;
+    let block = Block::default()
+        .title(format!(
+            " {} ({} left, {} reviewed) ",
+            if review.on_break {
+                "Break Review"
+            } else {
+                "Vocabulary Review"
+            },
+            review.cards.len() + review.questions.len(),
+            review.reviewed
+        ))
+        .borders(Borders::ALL)
+        .style(Style::default().fg(fg).bg(bg));
+
+    // Words come first; a break review asks the book's questions after them.
+    let card = review.cards.front();
+    let question = review.questions.front().filter(|_| card.is_none());
+    let front = match (card, question) {
+        (Some(card), _) => card.word.as_str(),
+        (None, Some(question)) => question.content.as_str(),
+        (None, None) => {
+            let message = if review.on_break {
+                "Done! Enjoy the rest of your break."
+            } else if review.reviewed == 0 {
+                "No words are due for review today."
+            } else {
+                "Done! Nothing else is due today."
+            };
+            let empty = Paragraph::new(message)
+                .alignment(Alignment::Center)
+                .block(block);
+            f.render_widget(empty, chunks[0]);
+            f.render_widget(
+                Paragraph::new(" [Esc] Back ").style(Style::default().fg(fg).bg(bg)),
+                chunks[1],
+            );
+            return;
+        }
+    };
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            front,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ))
+        .alignment(Alignment::Center),
+        Line::from(""),
+    ];
+    if review.revealed {
+        let back = match (card, question) {
+            (Some(card), _) => card.definition.as_str(),
+            (None, Some(question)) => question.note.as_deref().unwrap_or("(no note)"),
+            (None, None) => "",
+        };
+        lines.extend(back.lines().map(Line::from));
+    }
+    let body = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(body, chunks[0]);
+
+    // Each grade shows when the word would come back.
+    let footer = match card {
+        None if review.revealed => " [1] Ask Again  [2-4] Got It | [Esc] Back ".to_string(),
+        None => " [Space] Show Note | [Esc] Back ".to_string(),
+        Some(card) if review.revealed => {
+            let choices: Vec<String> = GRADES
+                .iter()
+                .enumerate()
+                .map(|(i, grade)| {
+                    let interval = card.schedule.next(*grade).interval;
+                    let when = match interval {
+                        0 => "now".to_string(),
+                        1 => "1 day".to_string(),
+                        n => format!("{} days", n),
+                    };
+                    format!("[{}] {} ({})", i + 1, grade.label(), when)
+                })
+                .collect();
+            format!(" {} | [Esc] Back ", choices.join("  "))
+        }
+        Some(_) => " [Space] Show Definition | [Esc] Back ".to_string(),
+    };
+    f.render_widget(
+        Paragraph::new(footer).style(Style::default().fg(fg).bg(bg)),
+        chunks[1],
+    );
+}
@@ -2,6 +2,7 @@ use crate::app::{App, Theme};
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
@@ -55,7 +56,24 @@ pub fn render(f: &mut Frame, app: &mut App) {
 
     // Definition display
     if let Some(vocab) = app.vocabulary.get(app.selected_vocab_index) {
-        let def = Paragraph::new(vocab.definition.as_str())
+        let mut lines: Vec<Line> = vocab.definition.lines().map(Line::from).collect();
+        // The sentence the word was met in, and where.
+        if let Some(ref source) = vocab.source {
+            let place = match vocab.book_title {
+                Some(ref title) => format!("— {}, ch. {}", title, source.chapter + 1),
+                None => format!("— ch. {}", source.chapter + 1),
+            };
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                format!("“{}”", source.sentence),
+                Style::default().add_modifier(Modifier::ITALIC),
+            )));
+            lines.push(Line::from(Span::styled(
+                place,
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        let def = Paragraph::new(lines)
             .block(
                 Block::default()
                     .title(format!(" {} ", vocab.word))
@@ -0,0 +1,270 @@
+//! Fixture books built at test time, so the repository doesn't carry binary files.
+
+#![allow(dead_code)]
+
+use lopdf::content::{Content, Operation};
+use lopdf::{Document, Object, Stream, dictionary};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tbook::db::Db;
+use tempfile::TempDir;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// A scratch directory with its own library database.
+pub struct Workspace {
+    pub dir: TempDir,
+    pub db: Db,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let db = Db::new(dir.path().join("tbook.db")).expect("open database");
+        Self { dir, db }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// One spine item: a heading and its paragraphs.
+pub struct Chapter<'a> {
+    pub title: &'a str,
+    pub paragraphs: &'a [&'a str],
+}
+
+/// Writes a minimal EPUB 2 with an NCX table of contents.
+pub fn epub(dir: &Path, name: &str, title: &str, author: &str, chapters: &[Chapter]) -> PathBuf {
+    epub_with_images(dir, name, title, author, chapters, &[])
+}
+
+/// Like [`epub`], with PNG images stored next to the chapters under the given file names.
+pub fn epub_with_images(
+    dir: &Path,
+    name: &str,
+    title: &str,
+    author: &str,
+    chapters: &[Chapter],
+    images: &[(&str, &[u8])],
+) -> PathBuf {
+    let path = dir.join(name);
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).expect("create epub"));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.start_file("mimetype", stored).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+
+    zip.start_file("META-INF/container.xml", stored).unwrap();
+    zip.write_all(
+        br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#,
+    )
+    .unwrap();
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut nav_points = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let n = i + 1;
+        manifest.push_str(&format!(
+            r#"<item id="ch{n}" href="ch{n}.xhtml" media-type="application/xhtml+xml"/>"#
+        ));
+        spine.push_str(&format!(r#"<itemref idref="ch{n}"/>"#));
+        nav_points.push_str(&format!(
+            r#"<navPoint id="np{n}" playOrder="{n}"><navLabel><text>{}</text></navLabel><content src="ch{n}.xhtml"/></navPoint>"#,
+            chapter.title
+        ));
+
+        let body = chapter
+            .paragraphs
+            .iter()
+            .map(|p| format!("<p>{}</p>", p))
+            .collect::<String>();
+        zip.start_file(format!("OEBPS/ch{n}.xhtml"), stored)
+            .unwrap();
+        zip.write_all(
+            format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><title>{title}</title></head><body><h1>{title}</h1>{body}</body></html>"#,
+                title = chapter.title
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    }
+
+    for (i, (file, bytes)) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            r#"<item id="img{}" href="{}" media-type="image/png"/>"#,
+            i + 1,
+            file
+        ));
+        zip.start_file(format!("OEBPS/{}", file), stored).unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+
+    zip.start_file("OEBPS/content.opf", stored).unwrap();
+    zip.write_all(
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>{title}</dc:title>
+    <dc:creator>{author}</dc:creator>
+    <dc:language>en</dc:language>
+    <dc:identifier id="uid">tbook-fixture</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    {manifest}
+  </manifest>
+  <spine toc="ncx">{spine}</spine>
+</package>"#
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+
+    zip.start_file("OEBPS/toc.ncx", stored).unwrap();
+    zip.write_all(
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head><meta name="dtb:uid" content="tbook-fixture"/></head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>{nav_points}</navMap>
+</ncx>"#
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+
+    zip.finish().unwrap();
+    path
+}
+
+/// Writes a PDF with one line of Courier text per page.
+pub fn pdf(dir: &Path, name: &str, pages: &[&str]) -> PathBuf {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Courier",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    });
+
+    let mut kids = Vec::new();
+    for text in pages {
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 18.into()]),
+                Operation::new("Td", vec![72.into(), 720.into()]),
+                Operation::new("Tj", vec![Object::string_literal(*text)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => pages.len() as i64,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let path = dir.join(name);
+    doc.save(&path).expect("save pdf");
+    path
+}
+
+/// A three-chapter EPUB used by most tests.
+pub fn sample_epub(dir: &Path) -> PathBuf {
+    epub(
+        dir,
+        "sample.epub",
+        "Sample Book",
+        "Test Author",
+        &[
+            Chapter {
+                title: "Loomings",
+                paragraphs: &["Call me Ishmael.", "Some years ago, never mind how long."],
+            },
+            Chapter {
+                title: "The Carpet-Bag",
+                paragraphs: &[
+                    "I stuffed a shirt or two into my old carpet-bag.",
+                    "Quitting the good city of old Manhatto, I duly arrived in New Bedford.",
+                ],
+            },
+            Chapter {
+                title: "The Spouter-Inn",
+                paragraphs: &[
+                    "Entering that gable-ended Spouter-Inn, you found yourself in a wide, low entry.",
+                ],
+            },
+        ],
+    )
+}
+
+/// A two-chapter EPUB whose second chapter has a figure with a `<figcaption>`, an image
+/// with only alt text, a decorative image with neither, and a table with a `<caption>`
+/// followed by one without.
+pub fn figures_epub(dir: &Path) -> PathBuf {
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgb8(40, 20)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .expect("encode png");
+    epub_with_images(
+        dir,
+        "figures.epub",
+        "Whales Illustrated",
+        "Test Author",
+        &[
+            Chapter {
+                title: "Introduction",
+                paragraphs: &["Whales are large."],
+            },
+            Chapter {
+                title: "Anatomy",
+                paragraphs: &[
+                    "The sperm whale has a large head.",
+                    r#"<figure><img src="head.png" alt="ignored"/><figcaption>The <em>head</em> of a sperm whale</figcaption></figure>"#,
+                    "Its tail is called the flukes.",
+                    r#"<img src="tail.png" alt="Flukes raised before a dive"/>"#,
+                    r#"<img src="rule.png" alt=""/>"#,
+                    r#"<table><caption>Whale <b>sizes</b></caption><tr><td>Blue</td><td>30 m</td></tr></table>"#,
+                    "<table><tr><td>Uncaptioned</td></tr></table>",
+                    "The end.",
+                ],
+            },
+        ],
+        &[("head.png", &png), ("tail.png", &png), ("rule.png", &png)],
+    )
+}
@@ -0,0 +1,807 @@
+mod common;
+
+use common::{Chapter, Workspace, epub, sample_epub};
+use tbook::clippings;
+use tbook::config::{self, ImportRules};
+use tbook::db::{self, BookRecord, Db, NewAnnotation};
+use tbook::digest::Digest;
+use tbook::export;
+use tbook::instance::InstanceLock;
+use tbook::koreader;
+use tbook::library::{self, ReadingStatus};
+use tbook::locator::{self, Locator};
+use tbook::omnibox::{self, OmniboxHit};
+
+fn book(ws: &Workspace, path: &std::path::Path) -> BookRecord {
+    let path = path.to_string_lossy();
+    ws.db
+        .get_books()
+        .unwrap()
+        .into_iter()
+        .find(|b| b.path == path)
+        .expect("book in library")
+}
+
+#[test]
+fn import_records_metadata_once() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    let pdf = common::pdf(ws.path(), "paper.pdf", &["One", "Two", "Three", "Four"]);
+
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    library::import_book(&ws.db, pdf.to_str().unwrap()).unwrap();
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    assert_eq!(ws.db.get_books().unwrap().len(), 2);
+
+    let record = book(&ws, &epub);
+    assert_eq!(record.title, "Sample Book");
+    assert_eq!(record.author, "Test Author");
+    assert_eq!(record.total_chapters, 3);
+    assert_eq!(record.total_pages, None);
+    assert_eq!(
+        ReadingStatus::from_str(&record.status),
+        ReadingStatus::ToRead
+    );
+    assert_eq!(book(&ws, &pdf).total_pages, Some(4));
+
+    let notes = ws.path().join("notes.txt");
+    std::fs::write(&notes, "plain text").unwrap();
+    assert!(library::import_book(&ws.db, notes.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn scan_applies_skip_rules() {
+    let ws = Workspace::new();
+    let nested = ws.path().join("shelf");
+    std::fs::create_dir(&nested).unwrap();
+    sample_epub(ws.path());
+    common::pdf(&nested, "paper.pdf", &["Page"]);
+    common::pdf(&nested, "paper-sample.pdf", &["Page"]);
+    std::fs::write(ws.path().join("readme.txt"), "not a book").unwrap();
+
+    let rules = ImportRules {
+        skip_patterns: vec!["*sample.pdf".to_string()],
+        ..ImportRules::default()
+    };
+    let result = library::scan_for_books(ws.path().to_str().unwrap(), &rules);
+    assert_eq!(
+        result.paths,
+        vec![ws.path().join("sample.epub"), nested.join("paper.pdf")]
+    );
+    assert_eq!(result.skipped, 1);
+    assert!(result.sizes[&nested.join("paper.pdf")] > 0);
+
+    let everything = library::scan_for_books(ws.path().to_str().unwrap(), &ImportRules::default());
+    assert_eq!(everything.paths.len(), 3);
+}
+
+#[test]
+fn progress_and_locators_round_trip() {
+    let ws = Workspace::new();
+    let pdf = common::pdf(ws.path(), "paper.pdf", &["One", "Two", "Three", "Four"]);
+    library::import_book(&ws.db, pdf.to_str().unwrap()).unwrap();
+    assert_eq!(book(&ws, &pdf).progress(), 0.25);
+
+    let locator = Locator {
+        spine: 2,
+        path: vec![4, 2],
+        offset: 17,
+    };
+    ws.db
+        .update_progress(pdf.to_str().unwrap(), 2, 5, 40, Some(&locator.to_string()))
+        .unwrap();
+    let record = book(&ws, &pdf);
+    assert_eq!(record.current_chapter, 2);
+    assert_eq!(record.current_line, 5);
+    assert_eq!(record.progress(), 0.75);
+    assert_eq!(record.locator, Some(locator));
+}
+
+#[test]
+fn last_stops_keep_the_newest_few_per_book() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+
+    for offset in 0..db::LAST_STOPS_KEPT + 2 {
+        let snippet = format!("stop {}", offset);
+        ws.db
+            .add_last_stop(id, &Locator::new(1, offset), &snippet)
+            .unwrap();
+    }
+    // Stopping at an old place again moves it to the front.
+    ws.db
+        .add_last_stop(id, &Locator::new(1, 3), "again")
+        .unwrap();
+    let stops = ws.db.get_last_stops(id).unwrap();
+    let offsets: Vec<usize> = stops.iter().map(|s| s.locator.offset).collect();
+    assert_eq!(offsets, [3, 6, 5, 4, 2]);
+    assert_eq!(stops[0].snippet, "again");
+
+    ws.db.remove_book(id).unwrap();
+    assert!(ws.db.get_last_stops(id).unwrap().is_empty());
+}
+
+#[test]
+fn line_progress_is_capped() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+
+    let mut record = book(&ws, &epub);
+    assert_eq!(record.progress(), 0.0);
+    record.total_lines = 200;
+    record.lines_read = 50;
+    assert_eq!(record.progress(), 0.25);
+    record.lines_read = 500;
+    assert_eq!(record.progress(), 1.0);
+}
+
+#[test]
+fn annotations_round_trip_and_export() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+
+    let start = Locator::new(1, 0).to_string();
+    let end = Locator::new(1, 12).to_string();
+    ws.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            note: Some("packing light"),
+            kind: "question",
+            start_locator: Some(&start),
+            end_locator: Some(&end),
+            ..Default::default()
+        })
+        .unwrap();
+    ws.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            start_line: 2,
+            end_line: 2,
+            end_word: 2,
+            content: "Call me Ishmael.",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+
+    let annotations = ws.db.get_annotations(id).unwrap();
+    assert_eq!(annotations.len(), 2);
+    // Ordered by chapter, so the chapter-one highlight comes first.
+    assert_eq!(annotations[0].content, "Call me Ishmael.");
+    assert_eq!(annotations[0].start_locator, None);
+    assert_eq!(annotations[1].kind, "question");
+    assert_eq!(annotations[1].note.as_deref(), Some("packing light"));
+    assert_eq!(annotations[1].start_locator, Some(Locator::new(1, 0)));
+    assert_eq!(annotations[1].end_locator, Some(Locator::new(1, 12)));
+
+    let questions = ws
+        .db
+        .get_annotations_by_kinds(&["question", "summary"])
+        .unwrap();
+    assert_eq!(questions.len(), 1);
+    assert_eq!(questions[0].content, "I stuffed a shirt");
+    assert!(ws.db.get_annotations_by_kinds(&[]).unwrap().is_empty());
+
+    let markdown = export::annotations_markdown(
+        "Sample Book",
+        "Test Author",
+        "sample.epub",
+        &annotations,
+        &export::MarkdownMarkers::default(),
+    );
+    assert!(markdown.contains("# Reading Notes: Sample Book"));
+    assert!(markdown.contains("### Chapter 1\n> Call me Ishmael.\n"));
+    assert!(markdown.contains("### Chapter 2\n> I stuffed a shirt\n\n**Note:** packing light\n"));
+
+    assert!(ws.db.remove_book(id).unwrap());
+    assert!(ws.db.get_annotations(id).unwrap().is_empty());
+}
+
+#[test]
+fn tags_follow_their_notes_across_the_library() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    let note = |content: &str, kind: &str| {
+        ws.db
+            .add_annotation(&NewAnnotation {
+                book_id: id,
+                start_line: 2,
+                end_line: 2,
+                end_word: 2,
+                content,
+                kind,
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    let first = note("Call me Ishmael.", "highlight");
+    let second = note("Some years ago", "question");
+
+    let tags = library::parse_tags("#Openings, names  openings");
+    assert_eq!(tags, ["names", "openings"]);
+    ws.db.set_annotation_tags(first, &tags).unwrap();
+    ws.db.set_annotation_tags(second, &["sea".to_string()]).unwrap();
+    assert_eq!(ws.db.get_tags().unwrap(), ["names", "openings", "sea"]);
+
+    let notes = ws.db.get_all_annotations().unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].tags, ["names", "openings"]);
+    assert_eq!(notes[1].tags, ["sea"]);
+    assert!(notes[0].created_at.is_some());
+    // Links come back with their tags too.
+    ws.db.add_annotation_links(second, &[first]).unwrap();
+    let linked = ws.db.get_linked_annotations(second, true).unwrap();
+    assert_eq!(linked[0].tags, ["names", "openings"]);
+
+    ws.db.set_annotation_tags(second, &[]).unwrap();
+    assert_eq!(ws.db.get_tags().unwrap(), ["names", "openings"]);
+    assert!(ws.db.remove_book(id).unwrap());
+    assert!(ws.db.get_tags().unwrap().is_empty());
+}
+
+#[test]
+fn full_text_search_points_into_the_chapter() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    assert_eq!(ws.db.get_unindexed_books().unwrap().len(), 1);
+
+    library::index_book(&ws.db, id, epub.to_str().unwrap()).unwrap();
+    assert!(ws.db.get_unindexed_books().unwrap().is_empty());
+
+    let hits = ws.db.search_chapters("Manhatto", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].book_id, id);
+    assert_eq!(hits[0].title, "Sample Book");
+    assert_eq!(hits[0].locator.spine, 1);
+    assert!(
+        hits[0].snippet.contains("[Manhatto]"),
+        "{}",
+        hits[0].snippet
+    );
+
+    // The hit's offset lands on the matching word in the rendered chapter.
+    let mut parser = tbook::parser::BookParser::open(epub.to_str().unwrap()).unwrap();
+    let text = library::chapter_texts(&mut parser)
+        .into_iter()
+        .find(|(i, _)| *i == 1)
+        .unwrap()
+        .1;
+    let lines: Vec<&str> = text.lines().collect();
+    let (line, word) = hits[0].locator.resolve(&lines);
+    assert_eq!(lines[line].split_whitespace().nth(word), Some("Manhatto,"));
+}
+
+#[test]
+fn the_omnibox_groups_books_notes_words_and_text() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    library::index_book(&ws.db, id, epub.to_str().unwrap()).unwrap();
+    ws.db
+        .add_to_vocabulary("manhatto", "# manhatto\nOld name for Manhattan.", None)
+        .unwrap();
+
+    let hits = omnibox::search(&ws.db, "test author").unwrap();
+    assert!(matches!(hits.as_slice(), [OmniboxHit::Book(b)] if b.id == id));
+
+    let hits = omnibox::search(&ws.db, "Manhatto").unwrap();
+    let groups: Vec<_> = hits.iter().map(|h| h.group()).collect();
+    assert_eq!(groups, ["Words", "Text"]);
+
+    // A regular expression reads through the indexed text instead of the index.
+    let hits = omnibox::search(&ws.db, "carpet-bag|New Bedf.rd").unwrap();
+    let OmniboxHit::Text(ref hit) = hits[0] else {
+        panic!("expected a passage");
+    };
+    assert_eq!((hits.len(), hit.locator.spine), (1, 1));
+    assert!(omnibox::search(&ws.db, "  ").unwrap().is_empty());
+
+    // A word goes to its sentence, or to the top of its chapter when that has gone.
+    let mut source = db::VocabSource {
+        book_id: id,
+        chapter: 1,
+        sentence: "Quitting the good city of old Manhatto".to_string(),
+    };
+    let at = omnibox::word_locator(&ws.db, &source).unwrap();
+    assert_eq!(at.spine, 1);
+    assert!(at.offset > 0);
+    source.sentence = "Call me Ishmael.".to_string();
+    assert_eq!(
+        omnibox::word_locator(&ws.db, &source).unwrap(),
+        Locator::new(1, 0)
+    );
+}
+
+#[test]
+fn backup_and_restore_round_trip() {
+    let mut ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    ws.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            end_word: 1,
+            content: "Call me",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    let source = db::VocabSource {
+        book_id: id,
+        chapter: 0,
+        sentence: "Call me Ishmael.".to_string(),
+    };
+    ws.db
+        .add_to_vocabulary("ishmael", "# ishmael", Some(&source))
+        .unwrap();
+
+    let snapshot = ws.path().join("snapshot.db");
+    ws.db.backup_to(&snapshot).unwrap();
+    let saved = Db::open_read_only(&snapshot).unwrap();
+    assert!(saved.integrity_check().unwrap().is_empty());
+    assert_eq!(saved.summary().unwrap(), ws.db.summary().unwrap());
+    drop(saved);
+
+    ws.db.remove_book(id).unwrap();
+    assert_eq!(ws.db.summary().unwrap().books, 0);
+    // The word stays, but no longer points at the removed book.
+    let word = &ws.db.get_vocabulary().unwrap()[0];
+    assert_eq!((&word.source, &word.book_title), (&None, &None));
+
+    ws.db.restore_from(&snapshot).unwrap();
+    let summary = ws.db.summary().unwrap();
+    assert_eq!((summary.books, summary.annotations), (1, 1));
+    assert_eq!(ws.db.annotation_counts().unwrap().get(&id), Some(&1));
+    assert_eq!(ws.db.orphaned_annotation_count().unwrap(), 0);
+}
+
+#[test]
+fn moved_books_are_found_by_content() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    let pdf = common::pdf(ws.path(), "paper.pdf", &["One"]);
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    library::import_book(&ws.db, pdf.to_str().unwrap()).unwrap();
+    let record = book(&ws, &epub);
+    assert_eq!(record.file_size, Some(std::fs::metadata(&epub).unwrap().len()));
+    assert_eq!(record.content_hash.as_ref().map(String::len), Some(64));
+    ws.db
+        .update_progress(epub.to_str().unwrap(), 1, 3, 10, None)
+        .unwrap();
+
+    // Nothing is missing yet, so there is nothing to find.
+    assert!(library::find_moved_books(&ws.db, ws.path()).unwrap().is_empty());
+
+    let shelf = ws.path().join("shelf");
+    std::fs::create_dir(&shelf).unwrap();
+    let moved = shelf.join("renamed.epub");
+    std::fs::rename(&epub, &moved).unwrap();
+    // A different book of another size sits next to it and must not match.
+    common::pdf(&shelf, "other.pdf", &["Something else entirely"]);
+
+    let relinks = library::find_moved_books(&ws.db, ws.path()).unwrap();
+    assert_eq!(relinks.len(), 1);
+    assert_eq!(relinks[0].book_id, record.id);
+    assert_eq!(relinks[0].new_path, moved.to_string_lossy());
+
+    library::relink_book(&ws.db, record.id, &relinks[0].new_path).unwrap();
+    let relinked = book(&ws, &moved);
+    assert_eq!(relinked.id, record.id);
+    assert_eq!((relinked.current_chapter, relinked.current_line), (1, 3));
+    assert!(library::find_moved_books(&ws.db, ws.path()).unwrap().is_empty());
+
+    // Relinking onto a file that is already its own book is refused.
+    assert!(library::relink_book(&ws.db, record.id, pdf.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn reading_sessions_roll_up_by_day_and_month() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    let pdf = common::pdf(ws.path(), "paper.pdf", &["One"]);
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    library::import_book(&ws.db, pdf.to_str().unwrap()).unwrap();
+    let (a, b) = (book(&ws, &epub).id, book(&ws, &pdf).id);
+    ws.db.log_reading_session(a, 300).unwrap();
+    ws.db.log_reading_session(a, 200).unwrap();
+    ws.db.log_reading_session(b, 50).unwrap();
+
+    let month = chrono::Local::now().format("%Y-%m").to_string();
+    assert_eq!(ws.db.get_today_words().unwrap(), 550);
+    assert_eq!(ws.db.get_weekly_stats().unwrap().len(), 1);
+    assert_eq!(ws.db.get_monthly_stats().unwrap(), vec![(month, 550)]);
+}
+
+#[test]
+fn sittings_add_up_to_time_read_today() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    assert_eq!(ws.db.get_average_sitting_secs().unwrap(), None);
+
+    let now = chrono::Local::now();
+    let minutes_ago = |m| now - chrono::Duration::minutes(m);
+    ws.db.log_sitting(id, minutes_ago(20), minutes_ago(10), 900).unwrap();
+    ws.db.log_sitting(id, minutes_ago(4), now, 300).unwrap();
+
+    assert_eq!(ws.db.get_today_reading_secs().unwrap(), 14 * 60);
+    assert_eq!(ws.db.get_average_sitting_secs().unwrap(), Some(7 * 60));
+    assert_eq!(ws.db.get_average_wpm().unwrap(), Some(1200.0 / 14.0));
+}
+
+#[test]
+fn reading_is_broken_down_by_book_tag_and_author() {
+    let ws = Workspace::new();
+    let chapters = [Chapter {
+        title: "One",
+        paragraphs: &["Text."],
+    }];
+    let novel = sample_epub(ws.path());
+    let manual = epub(ws.path(), "rust.epub", "The Rust Book", "Klabnik", &chapters);
+    let diary = epub(ws.path(), "diary.epub", "Diary", "Test Author", &chapters);
+    for path in [&novel, &manual, &diary] {
+        library::import_book(&ws.db, path.to_str().unwrap()).unwrap();
+    }
+    let (novel, manual, diary) = (
+        book(&ws, &novel).id,
+        book(&ws, &manual).id,
+        book(&ws, &diary).id,
+    );
+    ws.db.set_book_tags(novel, &["fiction".to_string()]).unwrap();
+    ws.db
+        .set_book_tags(manual, &["technical".to_string(), "reference".to_string()])
+        .unwrap();
+    let tags = ws.db.get_book(manual).unwrap().unwrap().tags;
+    assert_eq!(tags, ["reference", "technical"]);
+
+    let now = chrono::Local::now();
+    // Kept within today, however soon after midnight the test runs.
+    let start = (now - chrono::Duration::minutes(20)).max(
+        now.date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_local_timezone(chrono::Local)
+            .unwrap(),
+    );
+    let secs = (now - start).num_seconds() as u64;
+    ws.db.log_reading_session(novel, 1000).unwrap();
+    ws.db.log_reading_session(manual, 500).unwrap();
+    ws.db.log_sitting(manual, start, now, 500).unwrap();
+    ws.db.log_reading_session(diary, 200).unwrap();
+
+    let today = now.date_naive();
+    let by_tag = ws.db.get_reading_by_tag(today, today).unwrap();
+    assert_eq!(
+        by_tag,
+        [
+            ("fiction".to_string(), 1000, 0),
+            ("reference".to_string(), 500, secs),
+            ("technical".to_string(), 500, secs),
+            (String::new(), 200, 0),
+        ]
+    );
+    let by_author = ws.db.get_reading_by_author(today, today).unwrap();
+    assert_eq!(
+        by_author,
+        [
+            ("Test Author".to_string(), 1200, 0),
+            ("Klabnik".to_string(), 500, secs),
+        ]
+    );
+    let tomorrow = today.succ_opt().unwrap();
+    assert!(ws.db.get_reading_by_tag(tomorrow, tomorrow).unwrap().is_empty());
+}
+
+#[test]
+fn weekly_digest_sums_up_reading_notes_and_words() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let id = book(&ws, &epub).id;
+    let now = chrono::Local::now();
+    // Kept within today, however soon after midnight the test runs.
+    let start = (now - chrono::Duration::minutes(30)).max(
+        now.date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_local_timezone(chrono::Local)
+            .unwrap(),
+    );
+    let secs = (now - start).num_seconds() as u64;
+    ws.db.log_reading_session(id, 1200).unwrap();
+    ws.db.log_sitting(id, start, now, 1200).unwrap();
+    ws.db
+        .add_annotation(&NewAnnotation {
+            book_id: id,
+            chapter: 1,
+            start_line: 2,
+            end_line: 2,
+            end_word: 3,
+            content: "I stuffed a shirt",
+            note: Some("Why only two?"),
+            kind: "question",
+            ..Default::default()
+        })
+        .unwrap();
+    ws.db
+        .add_to_vocabulary("carpet-bag", "# carpet-bag\nA travelling bag.", None)
+        .unwrap();
+
+    let today = now.date_naive();
+    let digest = Digest::gather(&ws.db, today).unwrap();
+    assert_eq!(digest.days.len(), 7);
+    assert_eq!(digest.days[6], (today, 1200, secs));
+    let markdown = digest.to_markdown();
+    let total = format!("| **Total** | **1200** | **{}** |", secs / 60);
+    assert!(markdown.contains(&total), "{markdown}");
+    assert!(markdown.contains("- **Sample Book** by Test Author: 1200 words"));
+    assert!(markdown.contains("### Sample Book\n\n- I stuffed a shirt *(question)*\n  - Why only two?"));
+    assert!(markdown.contains("- **carpet-bag**: A travelling bag."));
+
+    // A week that ended before any of it is empty.
+    let earlier = Digest::gather(&ws.db, today - chrono::Days::new(7)).unwrap();
+    assert!(earlier.books.is_empty() && earlier.notes.is_empty());
+    assert!(earlier.vocabulary.is_empty());
+    assert!(earlier.to_markdown().contains("No reading this week."));
+}
+
+#[test]
+fn indexing_counts_words_for_progress() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let record = book(&ws, &epub);
+    assert_eq!(record.total_words, None);
+    assert_eq!(ws.db.get_unindexed_books().unwrap().len(), 1);
+
+    library::index_book(&ws.db, record.id, &record.path).unwrap();
+    assert!(ws.db.get_unindexed_books().unwrap().is_empty());
+    let chapter_words = ws.db.get_chapter_words(record.id).unwrap();
+    let total: usize = chapter_words.iter().sum();
+    let record = book(&ws, &epub);
+    assert!(total > 0);
+    assert_eq!(record.total_words, Some(total));
+    assert!(record.total_lines > 0);
+
+    ws.db.set_position_words(record.id, chapter_words[0]).unwrap();
+    let progress = book(&ws, &epub).progress();
+    assert_eq!(progress, chapter_words[0] as f64 / total as f64);
+}
+
+#[test]
+fn quotes_start_at_the_locator() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    let mut parser = tbook::parser::BookParser::open(epub.to_str().unwrap()).unwrap();
+    let text = library::chapter_texts(&mut parser)[1].1.clone();
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    let start = library::quote_at(&mut parser, &Locator::new(1, 0), 3).unwrap();
+    assert_eq!(start, words[..3].join(" "));
+    // The offset of the third word, counting each earlier word and one space after it.
+    let offset = words[0].chars().count() + words[1].chars().count() + 2;
+    let later = library::quote_at(&mut parser, &Locator::new(1, offset), 2).unwrap();
+    assert_eq!(later, words[2..4].join(" "));
+    assert_eq!(library::quote_at(&mut parser, &Locator::new(1, 1_000_000), 2), None);
+}
+
+const CLIPPINGS: &str = "Sample Book: A Novel (Author, Test)
+- Your Highlight on page 3 | Location 40-42 | Added on Monday, 1 January 2024 12:00:00
+
+I duly arrived in New Bedford.
+==========
+Sample Book: A Novel (Author, Test)
+- Your Note on page 3 | Location 42 | Added on Monday, 1 January 2024 12:01:00
+
+whaling town
+==========
+Some Other Book (Nobody)
+- Your Highlight on page 1 | Location 5-6 | Added on Monday, 1 January 2024 12:02:00
+
+Not in the library.
+==========
+";
+
+#[test]
+fn kindle_clippings_land_in_their_book_or_wait_for_review() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let record = book(&ws, &epub);
+
+    let report = clippings::import_clippings(&ws.db, CLIPPINGS).unwrap();
+    assert_eq!((report.imported, report.duplicates, report.unplaced), (1, 0, 0));
+    assert_eq!(report.pending, vec![("Some Other Book".to_string(), 1)]);
+    let annotations = ws.db.get_annotations(record.id).unwrap();
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].chapter, 1);
+    assert_eq!(annotations[0].note.as_deref(), Some("whaling town"));
+    assert_eq!(annotations[0].tags, vec![clippings::KINDLE_TAG.to_string()]);
+    assert_eq!(annotations[0].start_locator.as_ref().map(|l| l.spine), Some(1));
+
+    // Importing the same file again adds nothing.
+    let again = clippings::import_clippings(&ws.db, CLIPPINGS).unwrap();
+    assert_eq!((again.imported, again.duplicates), (0, 2));
+    assert!(again.pending.is_empty());
+
+    let pending = ws.db.get_pending_clippings().unwrap();
+    assert_eq!(pending.len(), 1);
+    let assigned = clippings::assign_pending(&ws.db, &pending, &record).unwrap();
+    assert_eq!((assigned.imported, assigned.unplaced), (1, 1));
+    assert!(ws.db.get_pending_clippings().unwrap().is_empty());
+    assert_eq!(ws.db.get_annotations(record.id).unwrap().len(), 2);
+}
+
+const SIDECAR: &str = r#"-- /mnt/onboard/sample.epub
+return {
+    ["annotations"] = {
+        [1] = {
+            ["note"] = "whaling town",
+            ["pos0"] = "/body/DocFragment[2]/body/p[2]/text().26",
+            ["pos1"] = "/body/DocFragment[2]/body/p[2]/text().38",
+            ["text"] = "old Manhatto",
+        },
+    },
+    ["font_size"] = 22,
+    ["last_xpointer"] = "/body/DocFragment[3]/body/p/text().9",
+    ["percent_finished"] = 0.8,
+}
+"#;
+
+#[test]
+fn koreader_sidecars_round_trip_highlights_and_position() {
+    let ws = Workspace::new();
+    let epub = sample_epub(ws.path());
+    library::import_book(&ws.db, epub.to_str().unwrap()).unwrap();
+    let record = book(&ws, &epub);
+
+    let report = koreader::import_sidecar(&ws.db, &record, SIDECAR).unwrap();
+    assert_eq!((report.imported, report.unplaced), (1, 0));
+    assert_eq!(report.position, Some(2));
+    let annotations = ws.db.get_annotations(record.id).unwrap();
+    assert_eq!(annotations[0].chapter, 1);
+    assert_eq!(annotations[0].note.as_deref(), Some("whaling town"));
+    assert_eq!(annotations[0].tags, vec![koreader::KOREADER_TAG.to_string()]);
+    let record = book(&ws, &epub);
+    let locator = record.locator.clone().unwrap();
+    let mut parser = tbook::parser::BookParser::open(&record.path).unwrap();
+    assert_eq!(
+        library::quote_at(&mut parser, &locator, 2).as_deref(),
+        Some("that gable-ended")
+    );
+    let again = koreader::import_sidecar(&ws.db, &record, SIDECAR).unwrap();
+    assert_eq!((again.imported, again.duplicates), (0, 1));
+
+    // Exporting adds tbook's own notes and keeps KOReader's settings.
+    ws.db
+        .add_annotation(&NewAnnotation {
+            book_id: record.id,
+            chapter: 2,
+            content: "gable-ended Spouter-Inn",
+            kind: "highlight",
+            ..Default::default()
+        })
+        .unwrap();
+    let export = koreader::export_sidecar(&ws.db, &record, Some(SIDECAR)).unwrap();
+    assert_eq!((export.exported, export.duplicates), (1, 1));
+    let sidecar = &export.sidecar;
+    assert!(sidecar.contains(r#"["font_size"] = 22,"#));
+    assert!(sidecar.contains(r#"["pos0"] = "/body/DocFragment[3]/body/p/text().14","#));
+    assert!(sidecar.contains(r#"["pos1"] = "/body/DocFragment[3]/body/p/text().37","#));
+    assert!(sidecar.contains(r#"["last_xpointer"] = "/body/DocFragment[3]/body/p/text().9","#));
+    let notes = koreader::parse_sidecar(sidecar).unwrap().notes;
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].text, "old Manhatto");
+    assert_eq!(notes[1].chapter, Some(2));
+
+    // Without a sidecar the export starts a new one.
+    let fresh = koreader::export_sidecar(&ws.db, &record, None).unwrap();
+    assert_eq!(fresh.exported, 2);
+}
+
+#[test]
+fn notes_follow_their_text_when_the_book_file_changes() {
+    let ws = Workspace::new();
+    let path = sample_epub(ws.path());
+    library::import_book(&ws.db, path.to_str().unwrap()).unwrap();
+    let record = book(&ws, &path);
+    let mut parser = tbook::parser::BookParser::open(&record.path).unwrap();
+    let chapters = library::chapter_texts(&mut parser);
+    for (chapter, content) in [(1, "old Manhatto"), (0, "Call me Ishmael.")] {
+        let (start, end) = locator::find_span(&chapters[chapter].1, content).unwrap();
+        ws.db
+            .add_annotation(&NewAnnotation {
+                book_id: record.id,
+                chapter,
+                content,
+                kind: "highlight",
+                start_locator: Some(&Locator::new(chapter, start).to_string()),
+                end_locator: Some(&Locator::new(chapter, end).to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(!library::has_changed(&record));
+
+    // A new edition adds an opening paragraph and cuts the first line of Loomings.
+    epub(
+        ws.path(),
+        "sample.epub",
+        "Sample Book",
+        "Test Author",
+        &[
+            Chapter {
+                title: "Loomings",
+                paragraphs: &["Some years ago, never mind how long."],
+            },
+            Chapter {
+                title: "The Carpet-Bag",
+                paragraphs: &[
+                    "Editor's note on the new edition.",
+                    "Quitting the good city of old Manhatto, I duly arrived in New Bedford.",
+                ],
+            },
+        ],
+    );
+    assert!(library::has_changed(&record));
+    let report = library::reanchor_book(&ws.db, &record).unwrap();
+    assert_eq!((report.moved, report.kept, report.lost), (1, 0, 1));
+
+    let mut parser = tbook::parser::BookParser::open(&record.path).unwrap();
+    let annotations = ws.db.get_annotations(record.id).unwrap();
+    let moved = annotations.iter().find(|a| a.content == "old Manhatto").unwrap();
+    let start = moved.start_locator.clone().unwrap();
+    assert_eq!(
+        library::quote_at(&mut parser, &start, 2).as_deref(),
+        Some("old Manhatto,")
+    );
+    let lost = annotations.iter().find(|a| a.chapter == 0).unwrap();
+    assert_eq!(lost.tags, vec![library::UNANCHORED_TAG.to_string()]);
+
+    let record = book(&ws, &path);
+    assert!(!library::has_changed(&record));
+    assert_eq!(record.total_chapters, 2);
+}
+
+#[test]
+fn a_second_session_finds_the_lock_and_can_take_it_over() {
+    let ws = Workspace::new();
+    let db_path = ws.path().join("tbook.db");
+    let lock_path = InstanceLock::path_for(&db_path);
+    assert_eq!(lock_path, ws.path().join("tbook.db.lock"));
+
+    let first = InstanceLock::acquire(&db_path).unwrap().unwrap();
+    assert!(first.is_held());
+    let owner = InstanceLock::acquire(&db_path).unwrap().unwrap_err();
+    assert_eq!(owner.pid, std::process::id());
+    assert!(owner.is_running());
+
+    // The first session loses the lock, and leaves the new holder's file in place.
+    let second = InstanceLock::take_over(&db_path).unwrap();
+    assert!(!first.is_held() && second.is_held());
+    drop(first);
+    assert!(lock_path.exists());
+    drop(second);
+    assert!(!lock_path.exists());
+
+    // A lock left by a session that has gone is simply taken.
+    let gone = format!("{}\n{}\n", u32::MAX, config::hostname());
+    std::fs::write(&lock_path, gone).unwrap();
+    let lock = InstanceLock::acquire(&db_path).unwrap().unwrap();
+    assert!(lock.is_held());
+}
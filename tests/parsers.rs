@@ -0,0 +1,144 @@
+mod common;
+
+use common::{Workspace, figures_epub, sample_epub};
+use tbook::parser::{BookParser, Figure, FigureKind, PageContent};
+
+fn chapter_text(parser: &mut BookParser, index: usize) -> String {
+    parser
+        .get_chapter_content(index)
+        .expect("chapter content")
+        .into_iter()
+        .filter_map(|c| match c {
+            PageContent::Text(t) => Some(t),
+            PageContent::Image(_) | PageContent::Caption(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn epub_metadata_toc_and_chapters() {
+    let ws = Workspace::new();
+    let path = sample_epub(ws.path());
+    let mut parser = BookParser::open(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(
+        parser.get_metadata(),
+        ("Sample Book".to_string(), "Test Author".to_string())
+    );
+    assert_eq!(parser.get_language().as_deref(), Some("en"));
+    assert_eq!(parser.get_chapter_count(), 3);
+    assert!(!parser.is_paged());
+    assert_eq!(parser.get_page_count(), None);
+    assert_eq!(
+        parser.get_toc(),
+        vec!["Loomings", "The Carpet-Bag", "The Spouter-Inn"]
+    );
+
+    let text = chapter_text(&mut parser, 1);
+    assert!(text.contains("carpet-bag"), "{text}");
+    assert!(text.contains("New Bedford"), "{text}");
+    assert!(parser.get_chapter_content(3).is_err());
+}
+
+#[test]
+fn epub_element_path_points_at_the_paragraph() {
+    let ws = Workspace::new();
+    let path = sample_epub(ws.path());
+    let mut parser = BookParser::open(path.to_str().unwrap()).unwrap();
+
+    // <html> → <body> (2nd element, step 4) → third element after <h1> and <p> (step 6).
+    assert_eq!(
+        parser.element_path(1, "Quitting the good city"),
+        Some(vec![4, 6])
+    );
+    assert_eq!(parser.element_path(1, "not in this chapter"), None);
+}
+
+#[test]
+fn epub_figures_and_tables_are_captioned_and_numbered() {
+    let ws = Workspace::new();
+    let path = figures_epub(ws.path());
+    let mut parser = BookParser::open(path.to_str().unwrap()).unwrap();
+
+    let content = parser.get_chapter_content(1).unwrap();
+    let layout: Vec<String> = content
+        .iter()
+        .map(|c| match c {
+            PageContent::Text(_) => "text".to_string(),
+            PageContent::Image(_) => "image".to_string(),
+            PageContent::Caption(c) => c.clone(),
+        })
+        .collect();
+    // The figcaption wins over the alt text; the image without either gets no caption,
+    // and a table's caption goes above it.
+    assert_eq!(
+        layout,
+        [
+            "text",
+            "image",
+            "Fig. 2.1: The head of a sperm whale",
+            "text",
+            "image",
+            "Fig. 2.2: Flukes raised before a dive",
+            "image",
+            "Table 2.1: Whale sizes",
+            "text"
+        ]
+    );
+    // Captions are not repeated in the text; the table itself is.
+    let text = chapter_text(&mut parser, 1);
+    assert!(!text.contains("of a sperm whale"), "{text}");
+    assert!(!text.contains("sizes"), "{text}");
+    assert!(text.contains("Blue") && text.contains("Uncaptioned"), "{text}");
+
+    assert_eq!(
+        parser.figures(),
+        vec![
+            Figure {
+                chapter: 1,
+                kind: FigureKind::Image,
+                label: "Fig. 2.1: The head of a sperm whale".to_string()
+            },
+            Figure {
+                chapter: 1,
+                kind: FigureKind::Image,
+                label: "Fig. 2.2: Flukes raised before a dive".to_string()
+            },
+            Figure {
+                chapter: 1,
+                kind: FigureKind::Table,
+                label: "Table 2.1: Whale sizes".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn pdf_pages_are_chapters() {
+    let ws = Workspace::new();
+    let path = common::pdf(
+        ws.path(),
+        "paper.pdf",
+        &["First page", "Second page", "Third"],
+    );
+    let mut parser = BookParser::open(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(parser.get_metadata().0, "paper.pdf");
+    assert_eq!(parser.get_chapter_count(), 3);
+    assert!(parser.is_paged());
+    assert_eq!(parser.get_page_count(), Some(3));
+
+    // Text extraction shells out to poppler's pdftotext, which may not be installed.
+    match parser.get_chapter_content(1) {
+        Ok(_) => assert!(chapter_text(&mut parser, 1).contains("Second page")),
+        Err(e) => assert!(e.to_string().contains("pdftotext"), "{e}"),
+    }
+}
+
+#[test]
+fn unreadable_files_are_errors() {
+    let ws = Workspace::new();
+    let path = ws.path().join("broken.epub");
+    std::fs::write(&path, b"not a zip").unwrap();
+    assert!(BookParser::open(path.to_str().unwrap()).is_err());
+}